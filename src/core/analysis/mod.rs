@@ -62,6 +62,12 @@ pub struct Token {
     /// best to use the minimum number of bytes necessary. Some codec implementations
     /// may optimize payload storage when all payloads have the same length.
     pub payload: Vec<u8>,
+    /// The frequency of the term in the document, added to the frequency of the
+    /// term for each occurrence of the token, one by default.
+    ///
+    /// A custom frequency can only be indexed in a field indexing the term
+    /// frequencies but not the positions, e.g. to store a value in the postings.
+    pub term_frequency: u32,
 }
 
 impl Token {
@@ -72,6 +78,7 @@ impl Token {
             start_offset: 0,
             end_offset: 0,
             payload: Vec::with_capacity(0),
+            term_frequency: 1,
         }
     }
 
@@ -81,6 +88,7 @@ impl Token {
         self.end_offset = 0;
         self.payload.clear();
         self.term.clear();
+        self.term_frequency = 1;
     }
 
     pub fn end(&mut self) {
@@ -12,14 +12,14 @@
 // limitations under the License.
 
 use core::codec::doc_values::{
-    BinaryDocValues, NumericDocValues, SortedDocValues, SortedNumericDocValues, SortedSetDocValues,
+    BinaryDocValues, SortedDocValues, SortedNumericDocValues, SortedSetDocValues,
 };
 use core::codec::Codec;
 use core::doc::DocValuesType;
 use core::index::reader::LeafReaderContext;
 use core::search::DocIterator;
 use core::search::NO_MORE_DOCS;
-use core::util::DocId;
+use core::util::{BitsMut, DocId};
 use error::Result;
 
 pub struct DocValuesIterator {
@@ -39,8 +39,9 @@ impl DocValuesIterator {
                     }
                 }
                 DocValuesType::Numeric => {
-                    if let Ok(values) = leaf_reader.reader.get_numeric_doc_values(field) {
-                        doc_values = DocValuesEnum::Numeric(values);
+                    // numeric values of missing docs read as 0, so check the docs with field
+                    if let Ok(docs_with_field) = leaf_reader.reader.get_docs_with_field(field) {
+                        doc_values = DocValuesEnum::Numeric(docs_with_field);
                     }
                 }
                 DocValuesType::Sorted => {
@@ -82,10 +83,8 @@ impl DocValuesIterator {
                     return Ok(v.len() > 0);
                 }
             }
-            DocValuesEnum::Numeric(ref mut numeric) => {
-                if let Ok(_) = numeric.get(doc_id) {
-                    return Ok(true);
-                }
+            DocValuesEnum::Numeric(ref mut docs_with_field) => {
+                return docs_with_field.get(doc_id as usize);
             }
             DocValuesEnum::Sorted(ref mut sorted) => {
                 if let Ok(o) = sorted.get_ord(doc_id) {
@@ -113,7 +112,7 @@ impl DocValuesIterator {
 
 enum DocValuesEnum {
     Binary(Box<dyn BinaryDocValues>),
-    Numeric(Box<dyn NumericDocValues>),
+    Numeric(Box<dyn BitsMut>),
     Sorted(Box<dyn SortedDocValues>),
     SortedSet(Box<dyn SortedSetDocValues>),
     SortedNumeric(Box<dyn SortedNumericDocValues>),
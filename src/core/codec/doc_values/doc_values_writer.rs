@@ -279,6 +279,11 @@ impl NumericDocValuesWriter {
         }
     }
 
+    /// The documents that have a value for this field.
+    pub fn docs_with_field(&self) -> &FixedBitSet {
+        &self.docs_with_field
+    }
+
     pub fn add_value(&mut self, doc_id: DocId, value: i64) -> Result<()> {
         if (doc_id as i64) < self.pending.size() {
             bail!(IllegalArgument(format!(
@@ -332,7 +337,7 @@ impl DocValuesWriter for NumericDocValuesWriter {
         if let Some(sort_map) = sort_map {
             let dvs =
                 Self::sort_doc_values(max_doc, sort_map, &self.docs_with_field, values.iterator());
-            let mut iter = NumericDVIter::new(dvs);
+            let mut iter = NumericDVIter::sparse(dvs);
             consumer.add_numeric_field(&self.field_info, &mut iter)
         } else {
             let mut iter = NumericDocValuesIter::new(
@@ -362,9 +367,10 @@ impl DocValuesWriter for NumericDocValuesWriter {
             0
         };
         let mut values = vec![default_value; num_doc as usize];
+        // the pending values are only buffered for the docs with a value
         let iter = BitSetIterator::new(&self.docs_with_field);
-        for doc_id in iter {
-            values[doc_id as usize] = final_values.get(doc_id)?;
+        for (i, doc_id) in iter.enumerate() {
+            values[doc_id as usize] = final_values.get(i as i32)?;
         }
 
         // the float type is only 32 bits valid for sort
@@ -440,17 +446,27 @@ pub struct NumericDVIter {
     dv: CachedNumericDVs,
     doc: i32,
     max_doc: i32,
+    sparse: bool,
 }
 
 impl NumericDVIter {
+    /// Iterates over all values, the missing ones included.
     pub fn new(dv: CachedNumericDVs) -> Self {
         let max_doc = dv.values.len() as i32;
         Self {
             dv,
             doc: -1,
             max_doc,
+            sparse: false,
         }
     }
+
+    /// Iterates over the values, returning `Numeric::Null` for the docs without a value.
+    pub fn sparse(dv: CachedNumericDVs) -> Self {
+        let mut iter = Self::new(dv);
+        iter.sparse = true;
+        iter
+    }
 }
 
 impl Iterator for NumericDVIter {
@@ -461,7 +477,12 @@ impl Iterator for NumericDVIter {
         if self.doc >= self.max_doc {
             None
         } else {
-            let value = Numeric::Long(self.dv.values[self.doc as usize]);
+            let doc = self.doc as usize;
+            let value = if self.sparse && !self.dv.docs_with_field.get(doc).unwrap() {
+                Numeric::Null
+            } else {
+                Numeric::Long(self.dv.values[doc])
+            };
             Some(Ok(value))
         }
     }
@@ -809,6 +830,7 @@ pub struct SortedDocValuesWriter {
 
     final_ords: Option<PackedLongValues>,
     final_ord_map: Vec<i32>,
+    added_values: DocId,
 }
 
 impl SortedDocValuesWriter {
@@ -832,11 +854,12 @@ impl SortedDocValuesWriter {
             _bytes_block_pool: bytes_block_pool,
             final_ords: None,
             final_ord_map: vec![],
+            added_values: 0,
         }
     }
 
     pub fn add_value(&mut self, doc_id: DocId, value: &BytesRef) -> Result<()> {
-        if doc_id < self.pending.size() as DocId {
+        if doc_id < self.added_values {
             bail!(
                 "DocValuesField {} appears more than once in this document (only one value is \
                  allowed per field)",
@@ -858,6 +881,7 @@ impl SortedDocValuesWriter {
         }
 
         self.add_one_value(value);
+        self.added_values = doc_id + 1;
         self.docs_with_field.ensure_capacity(doc_id as usize);
         self.docs_with_field.set(doc_id as usize);
         Ok(())
@@ -904,7 +928,9 @@ impl DocValuesWriter for SortedDocValuesWriter {
         consumer: &mut W,
     ) -> Result<()> {
         let max_doc = state.segment_info.max_doc();
-        debug_assert!(self.pending.size() == max_doc as i64);
+        // only the documents with a value are pending
+        debug_assert!(self.pending.size() <= i64::from(max_doc));
+        self.docs_with_field.ensure_capacity(max_doc as usize);
 
         let value_count = self.hash.len();
 
@@ -963,7 +989,7 @@ impl DocValuesWriter for SortedDocValuesWriter {
         let doc_id_iter = BitSetIterator::new(&self.docs_with_field);
         for doc in doc_id_iter {
             let i = value_iter.next().unwrap() as usize;
-            data[doc as usize] = self.final_ord_map[i];
+            data[doc as usize] = ord_map[i];
         }
         self.final_ord_map = ord_map;
 
@@ -1047,13 +1073,13 @@ impl<'a> Iterator for SortedOrdsIterator<'a> {
         if self.doc_upto >= self.max_doc {
             None
         } else {
-            self.doc_upto += 1;
             let ord = if self.docs_with_field.get(self.doc_upto as usize).unwrap() {
                 let i = self.iter.next().unwrap();
                 self.ord_map[i as usize]
             } else {
                 -1
             };
+            self.doc_upto += 1;
             Some(Ok(Numeric::Int(ord)))
         }
     }
@@ -2338,3 +2364,95 @@ impl ReusableIterator for SortedDVIter {
         self.doc = -1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::doc::{Fieldable, NumericDocValuesField};
+    use core::index::reader::IndexReader;
+    use core::index::writer::IndexWriterConfig;
+    use core::search::query::tests::new_index_writer;
+    use core::search::sort_field::{SimpleSortField, Sort, SortField, SortFieldType};
+
+    fn sort_by(field: &str) -> Sort {
+        Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            field.into(),
+            SortFieldType::Long,
+            false,
+        ))])
+    }
+
+    /// The values of `field` of a single segment index in doc ID order, `None` for the
+    /// documents without a value.
+    fn values<R: IndexReader>(reader: &R, field: &str) -> Vec<Option<i64>> {
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = leaves[0].reader;
+        let values = leaf.get_numeric_doc_values(field).unwrap();
+        let mut docs_with_field = leaf.get_docs_with_field(field).unwrap();
+        (0..leaf.max_doc())
+            .map(|d| {
+                if docs_with_field.get(d as usize).unwrap() {
+                    Some(values.get(d).unwrap())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_sparse_numeric_doc_values() {
+        let mut config = IndexWriterConfig::default();
+        config.index_sort = Some(sort_by("id"));
+        let (_dir, writer) = new_index_writer(config);
+
+        for &(id, rank) in &[(3, Some(30)), (1, None), (2, Some(20)), (0, None)] {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("id", id))];
+            if let Some(rank) = rank {
+                doc.push(Box::new(NumericDocValuesField::new("rank", rank)));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+
+        // the documents without a value still have none once sorted
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(
+            values(&reader, "id"),
+            vec![Some(0), Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(
+            values(&reader, "rank"),
+            vec![None, None, Some(20), Some(30)]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_sparse_numeric_doc_values() {
+        let mut config = IndexWriterConfig::default();
+        config.index_sort = Some(sort_by("rank"));
+        let (_dir, writer) = new_index_writer(config);
+
+        for &(id, rank) in &[(0, Some(30)), (1, None), (2, Some(10)), (3, Some(20))] {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("id", id))];
+            if let Some(rank) = rank {
+                doc.push(Box::new(NumericDocValuesField::new("rank", rank)));
+            }
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+
+        // the document without a rank sorts as 0
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(
+            values(&reader, "id"),
+            vec![Some(1), Some(2), Some(3), Some(0)]
+        );
+        assert_eq!(
+            values(&reader, "rank"),
+            vec![None, Some(10), Some(20), Some(30)]
+        );
+    }
+}
@@ -31,7 +31,9 @@ use serde::{Serialize, Serializer};
 
 // use core::attribute::{OffsetAttribute, PayloadAttribute, PositionIncrementAttribute};
 use core::codec::points::{MAX_DIMENSIONS, MAX_NUM_BYTES};
-use core::codec::postings::{PER_FIELD_POSTING_FORMAT_KEY, PER_FIELD_POSTING_SUFFIX_KEY};
+use core::codec::postings::{
+    CUSTOM_TERM_FREQS_KEY, PER_FIELD_POSTING_FORMAT_KEY, PER_FIELD_POSTING_SUFFIX_KEY,
+};
 use core::doc::{DocValuesType, IndexOptions};
 
 /// Access to the Field Info file that describes document fields and whether or
@@ -590,7 +592,12 @@ impl<T: AsRef<FieldNumbers>> FieldInfosBuilder<T> {
             fi.doc_values_type,
             fi.point_dimension_count,
             fi.point_num_bytes,
-        )
+        )?;
+        // merged fields keep their custom term frequencies
+        if let Some(value) = fi.attribute(CUSTOM_TERM_FREQS_KEY) {
+            self.by_name[&fi.name].put_attribute(CUSTOM_TERM_FREQS_KEY.to_string(), value);
+        }
+        Ok(())
     }
 
     #[allow(clippy::too_many_arguments)]
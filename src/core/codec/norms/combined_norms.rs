@@ -0,0 +1,63 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::NumericDocValues;
+use core::search::similarity::BM25Similarity;
+use core::util::{DocId, SmallFloat};
+
+use error::Result;
+
+/// The norms of several fields combined into the norms of a single field holding
+/// all their terms, as scored by BM25F.
+///
+/// The lengths decoded from the BM25 norms of the fields are multiplied by the
+/// weights of the fields and summed, the sum being encoded back as the BM25 norm
+/// of the closest length.
+pub struct CombinedNormValues {
+    norms: Vec<(Box<dyn NumericDocValues>, f32)>,
+}
+
+impl CombinedNormValues {
+    pub fn new(norms: Vec<(Box<dyn NumericDocValues>, f32)>) -> Self {
+        CombinedNormValues { norms }
+    }
+
+    /// The combined length of the fields of `doc_id`.
+    pub fn length(&self, doc_id: DocId) -> Result<f32> {
+        let mut length = 0f32;
+        for (norms, weight) in &self.norms {
+            let norm = norms.get(doc_id)? & 0xFF;
+            // a document without the field has no norm
+            if norm != 0 {
+                length += weight * BM25Similarity::decode_norm_value(norm as usize);
+            }
+        }
+        Ok(length)
+    }
+}
+
+impl NumericDocValues for CombinedNormValues {
+    fn get(&self, doc_id: DocId) -> Result<i64> {
+        let length = self.length(doc_id)?.max(1.0);
+        let norm = SmallFloat::float_to_byte315(1.0 / length.sqrt());
+        // the encoding rounds down, the next norm may decode to a closer length
+        if norm < 255
+            && (BM25Similarity::decode_norm_value(norm as usize + 1) - length).abs()
+                < (BM25Similarity::decode_norm_value(norm as usize) - length).abs()
+        {
+            Ok(i64::from(norm) + 1)
+        } else {
+            Ok(i64::from(norm))
+        }
+    }
+}
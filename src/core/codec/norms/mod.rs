@@ -27,6 +27,10 @@ mod norm_values_writer;
 
 pub use self::norm_values_writer::*;
 
+mod combined_norms;
+
+pub use self::combined_norms::*;
+
 use core::codec::doc_values::{EmptyNumericDocValues, NumericDocValues};
 use core::codec::field_infos::FieldInfo;
 use core::codec::norms::Lucene53NormsConsumer;
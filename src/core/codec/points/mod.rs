@@ -27,6 +27,10 @@ mod point_values;
 
 pub use self::point_values::*;
 
+mod range_field;
+
+pub use self::range_field::*;
+
 use core::util::DocId;
 
 use core::codec::field_infos::{FieldInfo, FieldInfos};
@@ -119,6 +123,17 @@ impl<P: PointsReader, MP: MutablePointsReader> PointValues for PointsReaderEnum<
         }
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &impl IntersectVisitor,
+    ) -> Result<i64> {
+        match self {
+            PointsReaderEnum::Simple(s) => s.estimate_point_count(field_name, visitor),
+            PointsReaderEnum::Mutable(m) => m.estimate_point_count(field_name, visitor),
+        }
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         match self {
             PointsReaderEnum::Simple(s) => s.min_packed_value(field_name),
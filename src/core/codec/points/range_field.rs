@@ -0,0 +1,252 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use core::analysis::TokenStream;
+use core::codec::Codec;
+use core::doc::{Field, FieldType, Fieldable};
+use core::search::query::{
+    DoublePoint, IntPoint, LongPoint, PointValueType, Query, RangeFieldQuery, RangeRelation,
+};
+use core::util::{Numeric, VariantValue};
+
+use error::{ErrorKind, Result};
+
+use std::cmp::Ordering;
+
+/// The maximum number of dimensions of a range field, each dimension being indexed as
+/// two dimensions of the BKD tree.
+pub const RANGE_MAX_DIMENSIONS: usize = 4;
+
+fn range_field_type(num_dims: usize, bytes_per_dim: usize) -> FieldType {
+    FieldType {
+        tokenized: false,
+        dimension_count: 2 * num_dims as u32,
+        dimension_num_bytes: bytes_per_dim as u32,
+        ..FieldType::default()
+    }
+}
+
+fn check_range<T: PartialOrd + ToString>(min: &[T], max: &[T]) -> Result<()> {
+    if min.is_empty() || min.len() > RANGE_MAX_DIMENSIONS {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "range must have between 1 and {} dimensions, got {}",
+            RANGE_MAX_DIMENSIONS,
+            min.len()
+        )));
+    }
+    if min.len() != max.len() {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "min and max must have the same number of dimensions, got {} and {}",
+            min.len(),
+            max.len()
+        )));
+    }
+    for (dim, (min, max)) in min.iter().zip(max).enumerate() {
+        match min.partial_cmp(max) {
+            Some(Ordering::Less) | Some(Ordering::Equal) => {}
+            _ => bail!(ErrorKind::IllegalArgument(format!(
+                "min value ({}) is greater than max value ({}) in dimension {}",
+                min.to_string(),
+                max.to_string(),
+                dim
+            ))),
+        }
+    }
+    Ok(())
+}
+
+macro_rules! define_range_field {
+    (
+        $(#[$attr:meta])*
+        $name:ident, $ty:ty, $point:ident, $bytes:expr, $value_type:expr
+    ) => {
+        $(#[$attr])*
+        pub struct $name {
+            field: Field,
+        }
+
+        impl $name {
+            /// The number of bytes per dimension.
+            pub const BYTES: usize = $bytes;
+
+            /// Creates a new range field from the minimums and maximums of each dimension.
+            pub fn new(name: &str, min: &[$ty], max: &[$ty]) -> Result<$name> {
+                let packed = $name::pack(min, max)?;
+                Ok($name {
+                    field: Field::new(
+                        String::from(name),
+                        range_field_type(min.len(), Self::BYTES),
+                        Some(VariantValue::Binary(packed)),
+                        None,
+                    ),
+                })
+            }
+
+            /// Encodes the range into a packed value: the minimums of all dimensions,
+            /// followed by the maximums.
+            pub fn pack(min: &[$ty], max: &[$ty]) -> Result<Vec<u8>> {
+                check_range(min, max)?;
+                let num_dims = min.len();
+                let mut packed = vec![0u8; 2 * num_dims * Self::BYTES];
+                for dim in 0..num_dims {
+                    $point::encode_dimension(min[dim], &mut packed[dim * Self::BYTES..]);
+                    $point::encode_dimension(
+                        max[dim],
+                        &mut packed[(num_dims + dim) * Self::BYTES..],
+                    );
+                }
+                Ok(packed)
+            }
+
+            /// The number of dimensions of this range.
+            pub fn num_dims(&self) -> usize {
+                self.field.field_type().dimension_count as usize / 2
+            }
+
+            /// The minimum of the given dimension.
+            pub fn min(&self, dim: usize) -> $ty {
+                let offset = dim * Self::BYTES;
+                $point::decode_dimension(&self.field.binary_value().unwrap()[offset..])
+            }
+
+            /// The maximum of the given dimension.
+            pub fn max(&self, dim: usize) -> $ty {
+                let offset = (self.num_dims() + dim) * Self::BYTES;
+                $point::decode_dimension(&self.field.binary_value().unwrap()[offset..])
+            }
+
+            /// Create a query matching the indexed ranges that intersect the given range.
+            pub fn new_intersects_query<C: Codec>(
+                field: String,
+                min: &[$ty],
+                max: &[$ty],
+            ) -> Result<Box<dyn Query<C>>> {
+                Self::new_relation_query(field, min, max, RangeRelation::Intersects)
+            }
+
+            /// Create a query matching the indexed ranges within the given range.
+            pub fn new_within_query<C: Codec>(
+                field: String,
+                min: &[$ty],
+                max: &[$ty],
+            ) -> Result<Box<dyn Query<C>>> {
+                Self::new_relation_query(field, min, max, RangeRelation::Within)
+            }
+
+            /// Create a query matching the indexed ranges that contain the given range.
+            pub fn new_contains_query<C: Codec>(
+                field: String,
+                min: &[$ty],
+                max: &[$ty],
+            ) -> Result<Box<dyn Query<C>>> {
+                Self::new_relation_query(field, min, max, RangeRelation::Contains)
+            }
+
+            /// Create a query matching the indexed ranges that intersect the given range
+            /// without being within it.
+            pub fn new_crosses_query<C: Codec>(
+                field: String,
+                min: &[$ty],
+                max: &[$ty],
+            ) -> Result<Box<dyn Query<C>>> {
+                Self::new_relation_query(field, min, max, RangeRelation::Crosses)
+            }
+
+            fn new_relation_query<C: Codec>(
+                field: String,
+                min: &[$ty],
+                max: &[$ty],
+                relation: RangeRelation,
+            ) -> Result<Box<dyn Query<C>>> {
+                Ok(Box::new(RangeFieldQuery::new(
+                    field,
+                    Self::pack(min, max)?,
+                    min.len(),
+                    Self::BYTES,
+                    $value_type,
+                    relation,
+                )?))
+            }
+        }
+
+        impl Fieldable for $name {
+            fn name(&self) -> &str {
+                self.field.name()
+            }
+
+            fn field_type(&self) -> &FieldType {
+                self.field.field_type()
+            }
+
+            fn boost(&self) -> f32 {
+                self.field.boost()
+            }
+
+            fn field_data(&self) -> Option<&VariantValue> {
+                self.field.field_data()
+            }
+
+            fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+                self.field.token_stream()
+            }
+
+            fn binary_value(&self) -> Option<&[u8]> {
+                self.field.binary_value()
+            }
+
+            fn string_value(&self) -> Option<&str> {
+                None
+            }
+
+            fn numeric_value(&self) -> Option<Numeric> {
+                None
+            }
+        }
+    };
+}
+
+define_range_field!(
+    /// An indexed `i32` range field, of up to 4 dimensions.
+    ///
+    /// Each dimension is indexed as two dimensions of the BKD tree holding its minimum
+    /// and maximum, so that the relations of the indexed ranges with a query range can
+    /// be evaluated efficiently with the `new_*_query` factory methods. Multiple values
+    /// for the same field in one document is allowed.
+    IntRange,
+    i32,
+    IntPoint,
+    4,
+    PointValueType::Integer
+);
+
+define_range_field!(
+    /// An indexed `i64` range field, of up to 4 dimensions.
+    ///
+    /// See `IntRange` for how the ranges are indexed and queried.
+    LongRange,
+    i64,
+    LongPoint,
+    8,
+    PointValueType::Long
+);
+
+define_range_field!(
+    /// An indexed `f64` range field, of up to 4 dimensions.
+    ///
+    /// See `IntRange` for how the ranges are indexed and queried. NaN bounds are
+    /// rejected.
+    DoubleRange,
+    f64,
+    DoublePoint,
+    8,
+    PointValueType::Double
+);
@@ -15,7 +15,8 @@ use core::codec::codec_util::{write_footer, write_index_header};
 use core::codec::field_infos::{FieldInfo, FieldInfos};
 use core::codec::postings::blocktree::*;
 use core::codec::postings::{
-    FieldsConsumer, PostingsWriterBase, DEFAULT_DOC_TERM_FREQ, DEFAULT_SEGMENT_DOC_FREQ,
+    FieldsConsumer, PostingsWriterBase, CUSTOM_TERM_FREQS_KEY, DEFAULT_DOC_TERM_FREQ,
+    DEFAULT_SEGMENT_DOC_FREQ,
 };
 use core::codec::segment_infos::{segment_file_name, SegmentWriteState};
 use core::codec::Codec;
@@ -280,6 +281,11 @@ impl<T: PostingsWriterBase, O: IndexOutput> FieldsConsumer for BlockTreeTermsWri
             if let Some(terms) = fields.terms(&field)? {
                 let mut terms_iter = terms.iterator()?;
                 let field_info = self.field_infos.field_info_by_name(&field).unwrap().clone();
+                let term_freq_limit = if field_info.attribute(CUSTOM_TERM_FREQS_KEY).is_some() {
+                    i32::max_value()
+                } else {
+                    DEFAULT_DOC_TERM_FREQ
+                };
                 let mut terms_writer = TermsWriter::new(field_info, self);
 
                 while let Some(term) = terms_iter.next()? {
@@ -287,7 +293,7 @@ impl<T: PostingsWriterBase, O: IndexOutput> FieldsConsumer for BlockTreeTermsWri
                         &term,
                         &mut terms_iter,
                         DEFAULT_SEGMENT_DOC_FREQ,
-                        DEFAULT_DOC_TERM_FREQ,
+                        term_freq_limit,
                     )?;
                 }
                 terms_writer.finish()?;
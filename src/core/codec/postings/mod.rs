@@ -81,6 +81,11 @@ use std::sync::Arc;
 pub const DEFAULT_SEGMENT_DOC_FREQ: i32 = 1_000_000_000;
 pub const DEFAULT_DOC_TERM_FREQ: i32 = 10;
 
+/// Attribute of the `FieldInfo` of a field indexed with custom term frequencies,
+/// e.g. by a `FeatureField`, whose frequencies are written as is instead of
+/// being capped to `DEFAULT_DOC_TERM_FREQ`.
+pub const CUSTOM_TERM_FREQS_KEY: &str = "custom_term_freqs";
+
 /// Encodes/decodes terms, postings, and proximity data.
 /// <p>
 /// Note, when extending this class, the name ({@link #getName}) may
@@ -13,16 +13,18 @@
 
 use core::codec::doc_values::NumericDocValues;
 use core::codec::Codec;
+use core::doc::DocValuesType;
 use core::index::merge::{LiveDocsDocMap, ReaderWrapperEnum};
 use core::index::reader::{LeafReader, LeafReaderContext};
 use core::search::sort_field::Sort;
 use core::search::sort_field::{ComparatorValue, FieldComparator, FieldComparatorEnum};
 use core::search::sort_field::{SortField, SortFieldType, SortedNumericSelector};
+use core::search::NO_MORE_DOCS;
 use core::util::packed::COMPACT;
 use core::util::packed::{
     PackedLongValues, PackedLongValuesBuilder, PackedLongValuesBuilderType, DEFAULT_PAGE_SIZE,
 };
-use core::util::{BitsMut, BitsRef, DocId};
+use core::util::{BitSet, Bits, BitsMut, BitsRef, DocId, FixedBitSet, ImmutableBitSet};
 
 use error::ErrorKind::IllegalArgument;
 use error::Result;
@@ -33,20 +35,35 @@ use std::collections::BinaryHeap;
 
 /// Sorts documents of a given index by returning a permutation
 /// on the document IDs.
+///
+/// When a parent field is given, the documents are sorted by blocks: the last
+/// document of each block carries the parent field, and all the documents of
+/// a block are sorted as their parent while keeping their order.
 pub struct Sorter {
     sort: Sort,
+    parent_field: Option<String>,
 }
 
 impl Sorter {
     pub fn new(sort: Sort) -> Self {
         debug_assert!(!sort.needs_scores());
-        Sorter { sort }
+        Sorter {
+            sort,
+            parent_field: None,
+        }
+    }
+
+    pub fn with_parent_field(sort: Sort, parent_field: Option<String>) -> Self {
+        debug_assert!(!sort.needs_scores());
+        Sorter { sort, parent_field }
     }
 
     pub fn sort_field_type(sort: &SortField) -> SortFieldType {
         match sort {
             SortField::Simple(s) => s.field_type(),
             SortField::SortedNumeric(s) => s.numeric_type(),
+            SortField::Expression(_) => SortFieldType::Custom,
+            SortField::Distance(_) => SortFieldType::Custom,
         }
     }
 
@@ -142,9 +159,13 @@ impl Sorter {
     pub fn sort_by_comps(
         max_doc: i32,
         comparators: Vec<Box<dyn SorterDocComparator>>,
+        parents: Option<FixedBitSet>,
     ) -> Result<Option<PackedLongDocMap>> {
         let mut multi_cmp = MultiSorterDocComps { cmps: comparators };
-        Self::sort(max_doc, &mut multi_cmp)
+        match parents {
+            Some(parents) => Self::sort(max_doc, &mut BlockDocComparator::new(parents, multi_cmp)),
+            None => Self::sort(max_doc, &mut multi_cmp),
+        }
     }
 
     /// Returns a mapping from the old document ID to its new location in the
@@ -174,7 +195,39 @@ impl Sorter {
             comparators,
             reverses,
         };
-        Self::sort(reader.reader.max_doc(), &mut comparator)
+        let parents = match self.parent_field {
+            Some(ref parent_field) => Self::parent_bits(reader.reader, parent_field)?,
+            None => None,
+        };
+        if let Some(parents) = parents {
+            Self::sort(
+                reader.reader.max_doc(),
+                &mut BlockDocComparator::new(parents, comparator),
+            )
+        } else {
+            Self::sort(reader.reader.max_doc(), &mut comparator)
+        }
+    }
+
+    /// Returns the parent documents of the reader, that is the documents with a
+    /// value for `parent_field`, or `None` if the field has no doc values.
+    pub fn parent_bits<R: LeafReader + ?Sized>(
+        reader: &R,
+        parent_field: &str,
+    ) -> Result<Option<FixedBitSet>> {
+        match reader.field_info(parent_field) {
+            Some(field_info) if field_info.doc_values_type != DocValuesType::Null => {}
+            _ => return Ok(None),
+        }
+        let max_doc = reader.max_doc() as usize;
+        let mut docs_with_field = reader.get_docs_with_field(parent_field)?;
+        let mut parents = FixedBitSet::new(max_doc);
+        for doc in 0..max_doc {
+            if docs_with_field.get(doc)? {
+                parents.set(doc);
+            }
+        }
+        Ok(Some(parents))
     }
 
     pub fn get_or_wrap_numeric<R: LeafReader + ?Sized>(
@@ -208,6 +261,47 @@ impl SorterDocComparator for MultiSorterDocComps {
     }
 }
 
+/// Sorts each document as the parent of its block, and the documents of the
+/// same block by their doc ID, so that blocks stay contiguous.
+struct BlockDocComparator<T: SorterDocComparator> {
+    parents: FixedBitSet,
+    comparator: T,
+}
+
+impl<T: SorterDocComparator> BlockDocComparator<T> {
+    fn new(parents: FixedBitSet, comparator: T) -> Self {
+        BlockDocComparator {
+            parents,
+            comparator,
+        }
+    }
+
+    fn parent(&self, doc: DocId) -> DocId {
+        if (doc as usize) < self.parents.len() {
+            let parent = self.parents.next_set_bit(doc as usize);
+            if parent != NO_MORE_DOCS {
+                return parent;
+            }
+        }
+        // trailing documents without a parent sort on their own
+        doc
+    }
+}
+
+impl<T: SorterDocComparator> SorterDocComparator for BlockDocComparator<T> {
+    fn compare(&mut self, doc1: DocId, doc2: DocId) -> Result<Ordering> {
+        let parent1 = self.parent(doc1);
+        let parent2 = self.parent(doc2);
+        if parent1 != parent2 {
+            let res = self.comparator.compare(parent1, parent2)?;
+            if res != Ordering::Equal {
+                return Ok(res);
+            }
+        }
+        Ok(doc1.cmp(&doc2))
+    }
+}
+
 pub struct PackedLongDocMap {
     max_doc: usize,
     old_to_new: PackedLongValues,
@@ -281,9 +375,12 @@ impl MultiSorter {
     /// to map each leaf's documents into the merged segment.  The documents for
     /// each incoming leaf reader must already be sorted by the same sort!
     /// Returns null if the merge sort is not needed (segments are already in index sort order).
+    ///
+    /// With a `parent_field`, the leaves are merged block by block, ordered by their parents.
     pub fn sort<D: Directory, C: Codec>(
         sort: &Sort,
         readers: &[ReaderWrapperEnum<D, C>],
+        parent_field: Option<&str>,
     ) -> Result<Vec<LiveDocsDocMap>> {
         let fields = sort.get_sort();
 
@@ -298,10 +395,15 @@ impl MultiSorter {
         let mut builders = Vec::with_capacity(leaf_count);
 
         for (i, reader) in readers.iter().enumerate().take(leaf_count) {
+            let parents = match parent_field {
+                Some(parent_field) => Sorter::parent_bits(reader, parent_field)?,
+                None => None,
+            };
             queue.push(LeafAndDocId::new(
                 i,
                 reader.live_docs(),
                 reader.max_doc(),
+                parents,
                 &mut comparators,
             ));
             builders.push(PackedLongValuesBuilder::new(
@@ -323,12 +425,16 @@ impl MultiSorter {
                         sorted = false;
                     }
                     last_reader_index = top.reader_index;
-                    builders[last_reader_index].add(mapped_doc_id);
-                    if top.live_docs.get(top.doc_id as usize)? {
-                        mapped_doc_id += 1;
+                    // the whole block of the top document is mapped at once
+                    for doc in top.doc_id..=top.block_end {
+                        builders[last_reader_index].add(mapped_doc_id);
+                        if top.live_docs.get(doc as usize)? {
+                            mapped_doc_id += 1;
+                        }
                     }
-                    top.doc_id += 1;
+                    top.doc_id = top.block_end + 1;
                     if top.doc_id < top.max_doc {
+                        top.block_end = top.find_block_end(top.doc_id);
                         tmp = Some(top);
                     }
                 } else {
@@ -433,6 +539,9 @@ struct LeafAndDocId {
     live_docs: BitsRef,
     max_doc: i32,
     doc_id: DocId,
+    // the last document of the block starting at `doc_id`, which is compared
+    block_end: DocId,
+    parents: Option<FixedBitSet>,
     comparators: *mut [CrossReaderComparatorEnum],
 }
 
@@ -441,15 +550,32 @@ impl LeafAndDocId {
         reader_index: usize,
         live_docs: BitsRef,
         max_doc: i32,
+        parents: Option<FixedBitSet>,
         comparators: &mut [CrossReaderComparatorEnum],
     ) -> Self {
-        LeafAndDocId {
+        let mut leaf = LeafAndDocId {
             reader_index,
             live_docs,
             max_doc,
             comparators,
             doc_id: 0,
+            block_end: 0,
+            parents,
+        };
+        if max_doc > 0 {
+            leaf.block_end = leaf.find_block_end(0);
+        }
+        leaf
+    }
+
+    fn find_block_end(&self, doc: DocId) -> DocId {
+        if let Some(ref parents) = self.parents {
+            let parent = parents.next_set_bit(doc as usize);
+            if parent != NO_MORE_DOCS {
+                return parent;
+            }
         }
+        doc
     }
 
     #[allow(clippy::mut_from_ref)]
@@ -473,9 +599,9 @@ impl Ord for LeafAndDocId {
             let cmp = comparator
                 .compare(
                     other.reader_index,
-                    other.doc_id,
+                    other.block_end,
                     self.reader_index,
-                    self.doc_id,
+                    self.block_end,
                 )
                 .unwrap();
             if cmp != Ordering::Equal {
@@ -655,3 +781,99 @@ impl<T> SorterDocComparator for DVSortDocComparator<T> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{Fieldable, NumericDocValuesField};
+    use core::index::reader::IndexReader;
+    use core::index::writer::IndexWriterConfig;
+    use core::search::query::tests::new_index_writer;
+    use core::search::sort_field::SimpleSortField;
+
+    fn price_sort() -> Sort {
+        Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "price".into(),
+            SortFieldType::Long,
+            false,
+        ))])
+    }
+
+    fn price(value: i64) -> Vec<Box<dyn Fieldable>> {
+        vec![Box::new(NumericDocValuesField::new("price", value))]
+    }
+
+    /// The prices of the documents of a single segment index, in doc ID order.
+    fn prices<R: IndexReader>(reader: &R) -> Vec<i64> {
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = leaves[0].reader;
+        let values = leaf.get_numeric_doc_values("price").unwrap();
+        (0..leaf.max_doc())
+            .map(|d| values.get(d).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_blocks_without_parent_field() {
+        let mut config = IndexWriterConfig::default();
+        config.index_sort = Some(price_sort());
+        let (_dir, writer) = new_index_writer(config);
+
+        // without a parent field the documents of a block are sorted on their own
+        writer.add_documents(vec![price(3), price(1)]).unwrap();
+        writer.add_document(price(2)).unwrap();
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(prices(&reader), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_by_blocks() {
+        // blocks [0, 1] and [2, 3] with their parents last, then a doc without parent
+        let values = vec![5i64, 9, 1, 7, 3];
+        let mut parents = FixedBitSet::new(values.len());
+        parents.set(1);
+        parents.set(3);
+        let comparator: Box<dyn SorterDocComparator> =
+            Box::new(DVSortDocComparator::new(values, |a, b| a.cmp(b)));
+        let doc_map = Sorter::sort_by_comps(5, vec![comparator], Some(parents))
+            .unwrap()
+            .unwrap();
+        // the blocks are ordered by their parents and keep their inner order
+        let new_to_old: Vec<DocId> = (0..5).map(|d| doc_map.new_to_old(d)).collect();
+        assert_eq!(new_to_old, vec![4, 2, 3, 0, 1]);
+        for doc in 0..5 {
+            assert_eq!(doc_map.old_to_new(doc_map.new_to_old(doc)), doc);
+        }
+    }
+
+    #[test]
+    fn test_merge_sorted_blocks() {
+        let mut config = IndexWriterConfig::default();
+        config.index_sort = Some(price_sort());
+        config.parent_field = Some("_parent".into());
+        let (_dir, writer) = new_index_writer(config);
+
+        // the children are priced after their parent to tell them apart
+        writer
+            .add_documents(vec![price(31), price(32), price(30)])
+            .unwrap();
+        writer.add_documents(vec![price(11), price(10)]).unwrap();
+        writer.commit().unwrap();
+        {
+            let reader = writer.get_reader(true, false).unwrap();
+            assert_eq!(prices(&reader), vec![11, 10, 31, 32, 30]);
+        }
+        writer.add_documents(vec![price(21), price(20)]).unwrap();
+        writer.add_document(price(5)).unwrap();
+        writer.commit().unwrap();
+        writer.force_merge(1, true).unwrap();
+
+        // the blocks of both segments are interleaved by the price of their parents
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(prices(&reader), vec![5, 11, 10, 21, 20, 31, 32, 30]);
+    }
+}
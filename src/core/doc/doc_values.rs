@@ -16,6 +16,8 @@ use core::doc::{
     Field, FieldType, Fieldable, BINARY_DOC_VALUES_FIELD_TYPE, NUMERIC_DOC_VALUES_FIELD_TYPE,
     SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE, SORTED_SET_DOC_VALUES_FIELD_TYPE,
 };
+use core::search::sort_field::{LatLonDistanceSortField, SortField};
+use core::util::geo;
 use core::util::{BytesRef, Numeric, VariantValue};
 
 use error::Result;
@@ -342,3 +344,105 @@ impl Fieldable for SortedSetDocValuesField {
         self.field.numeric_value()
     }
 }
+
+/// A per-document location field, stored in sorted numeric doc values.
+///
+/// The latitude and longitude are quantized like for `LatLonPoint` and packed into a
+/// single long, latitude in the upper 32 bits. Several values may be added for the
+/// same field in one document.
+pub struct LatLonDocValuesField {
+    field: Field,
+}
+
+impl LatLonDocValuesField {
+    pub fn new(name: &str, latitude: f64, longitude: f64) -> Result<LatLonDocValuesField> {
+        Ok(LatLonDocValuesField {
+            field: Field::new(
+                String::from(name),
+                SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE,
+                Some(VariantValue::Long(LatLonDocValuesField::encode(
+                    latitude, longitude,
+                )?)),
+                None,
+            ),
+        })
+    }
+
+    /// Packs the quantized location into a long.
+    pub fn encode(latitude: f64, longitude: f64) -> Result<i64> {
+        let lat = geo::encode_latitude(latitude)?;
+        let lon = geo::encode_longitude(longitude)?;
+        Ok((i64::from(lat) << 32) | (i64::from(lon) & 0xFFFF_FFFF))
+    }
+
+    /// Decodes the latitude of a packed doc value.
+    pub fn decode_latitude(encoded: i64) -> f64 {
+        geo::decode_latitude((encoded >> 32) as i32)
+    }
+
+    /// Decodes the longitude of a packed doc value.
+    pub fn decode_longitude(encoded: i64) -> f64 {
+        geo::decode_longitude(encoded as i32)
+    }
+
+    pub fn numeric_value(&self) -> i64 {
+        match self.field.field_data().unwrap() {
+            VariantValue::Long(v) => *v,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The quantized latitude of this location.
+    pub fn latitude(&self) -> f64 {
+        LatLonDocValuesField::decode_latitude(self.numeric_value())
+    }
+
+    /// The quantized longitude of this location.
+    pub fn longitude(&self) -> f64 {
+        LatLonDocValuesField::decode_longitude(self.numeric_value())
+    }
+
+    /// Creates a `SortField` sorting documents by increasing distance in meters to
+    /// the given location, documents without value for `field` sorting last.
+    pub fn new_distance_sort(field: &str, latitude: f64, longitude: f64) -> Result<SortField> {
+        Ok(SortField::Distance(LatLonDistanceSortField::new(
+            field.to_string(),
+            latitude,
+            longitude,
+        )?))
+    }
+}
+
+impl Fieldable for LatLonDocValuesField {
+    fn name(&self) -> &str {
+        self.field.name()
+    }
+
+    fn field_type(&self) -> &FieldType {
+        self.field.field_type()
+    }
+
+    fn boost(&self) -> f32 {
+        self.field.boost()
+    }
+
+    fn field_data(&self) -> Option<&VariantValue> {
+        self.field.field_data()
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        self.field.token_stream()
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        None
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        self.field.numeric_value()
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::{Token, TokenStream};
+use core::doc::{DocValuesType, Field, FieldType, Fieldable, IndexOptions};
+use core::util::{Numeric, VariantValue};
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+/// Field type of the `FeatureField`: the feature term is indexed with its
+/// frequency only and norms are omitted.
+pub const FEATURE_FIELD_TYPE: FieldType = FieldType {
+    stored: false,
+    tokenized: false,
+    store_term_vectors: false,
+    store_term_vector_offsets: false,
+    store_term_vector_positions: false,
+    store_term_vector_payloads: false,
+    omit_norms: true,
+    index_options: IndexOptions::DocsAndFreqs,
+    doc_values_type: DocValuesType::Null,
+    dimension_count: 0,
+    dimension_num_bytes: 0,
+};
+
+/// The largest term frequency a feature value may be encoded to, which is
+/// the encoding of `f32::MAX`.
+pub const FEATURE_MAX_FREQ: u32 = 0x7F7F_FFFF >> 15;
+
+/// `Field` indexing a static feature of the document, such as a pagerank or a
+/// number of upvotes, as the term frequency of the term `feature_name`.
+///
+/// Values are positive normal floats, of which only the 9 most significant
+/// bits of the mantissa are kept, so the relative error of the value read back
+/// is lower than 2^-8. A given feature may be indexed at most once per
+/// document. The feature is scored by a `FeatureQuery`.
+pub struct FeatureField {
+    field: Field,
+    feature_value: f32,
+}
+
+impl FeatureField {
+    pub fn new(name: &str, feature_name: &str, feature_value: f32) -> Result<FeatureField> {
+        if !feature_value.is_finite() || feature_value < ::std::f32::MIN_POSITIVE {
+            bail!(IllegalArgument(format!(
+                "feature value must be a positive normal float, got {} for feature {}",
+                feature_value, feature_name
+            )));
+        }
+        Ok(FeatureField {
+            field: Field::new(
+                String::from(name),
+                FEATURE_FIELD_TYPE,
+                Some(VariantValue::VString(feature_name.to_string())),
+                None,
+            ),
+            feature_value,
+        })
+    }
+
+    pub fn feature_name(&self) -> &str {
+        self.field.string_value().unwrap()
+    }
+
+    pub fn feature_value(&self) -> f32 {
+        self.feature_value
+    }
+
+    pub fn set_feature_value(&mut self, feature_value: f32) -> Result<()> {
+        if !feature_value.is_finite() || feature_value < ::std::f32::MIN_POSITIVE {
+            bail!(IllegalArgument(format!(
+                "feature value must be a positive normal float, got {}",
+                feature_value
+            )));
+        }
+        self.feature_value = feature_value;
+        Ok(())
+    }
+
+    /// Encodes a positive normal float into a term frequency, keeping the
+    /// ordering of the values.
+    pub fn encode_feature_value(feature_value: f32) -> u32 {
+        debug_assert!(feature_value.is_finite() && feature_value >= ::std::f32::MIN_POSITIVE);
+        feature_value.to_bits() >> 15
+    }
+
+    /// Decodes a term frequency encoded by `encode_feature_value`.
+    pub fn decode_feature_value(freq: u32) -> f32 {
+        if freq > FEATURE_MAX_FREQ {
+            // the feature field was indexed with a custom term frequency
+            return ::std::f32::MAX;
+        }
+        f32::from_bits(freq << 15)
+    }
+}
+
+impl Fieldable for FeatureField {
+    fn name(&self) -> &str {
+        self.field.name()
+    }
+
+    fn field_type(&self) -> &FieldType {
+        self.field.field_type()
+    }
+
+    fn boost(&self) -> f32 {
+        self.field.boost()
+    }
+
+    fn field_data(&self) -> Option<&VariantValue> {
+        self.field.field_data()
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        Ok(Box::new(FeatureTokenStream::new(
+            self.feature_name().as_bytes().to_vec(),
+            FeatureField::encode_feature_value(self.feature_value),
+        )))
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        self.field.string_value()
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        Some(Numeric::Float(self.feature_value))
+    }
+}
+
+/// `TokenStream` of a `FeatureField`, emitting the feature term once with the
+/// encoded value as its term frequency.
+#[derive(Debug)]
+struct FeatureTokenStream {
+    token: Token,
+    used: bool,
+    feature_name: Vec<u8>,
+    freq: u32,
+}
+
+impl FeatureTokenStream {
+    fn new(feature_name: Vec<u8>, freq: u32) -> Self {
+        FeatureTokenStream {
+            token: Token::new(),
+            used: true,
+            feature_name,
+            freq,
+        }
+    }
+}
+
+impl TokenStream for FeatureTokenStream {
+    fn next_token(&mut self) -> Result<bool> {
+        if self.used {
+            return Ok(false);
+        }
+
+        self.clear_token();
+        self.token.term = self.feature_name.clone();
+        self.token.term_frequency = self.freq;
+        self.used = true;
+
+        Ok(true)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.end_token();
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.used = false;
+        Ok(())
+    }
+
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_feature_value() {
+        for &v in &[
+            ::std::f32::MIN_POSITIVE,
+            1e-10,
+            0.5,
+            1.0,
+            3.75,
+            1e10,
+            ::std::f32::MAX,
+        ] {
+            let freq = FeatureField::encode_feature_value(v);
+            assert!(freq >= 1 && freq <= FEATURE_MAX_FREQ);
+            let decoded = FeatureField::decode_feature_value(freq);
+            assert!(decoded <= v);
+            assert!((v - decoded) / v < 1.0 / 256.0);
+        }
+        assert!(FeatureField::encode_feature_value(1.0) < FeatureField::encode_feature_value(1.01));
+        assert!(FeatureField::new("f", "pagerank", 0.0).is_err());
+        assert!(FeatureField::new("f", "pagerank", -1.0).is_err());
+        assert!(FeatureField::new("f", "pagerank", ::std::f32::NAN).is_err());
+        assert!(FeatureField::new("f", "pagerank", ::std::f32::INFINITY).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::PerFieldDocValuesFormat;
+use core::codec::field_infos::Lucene60FieldInfosFormat;
+use core::codec::norms::Lucene53NormsFormat;
+use core::codec::points::{Lucene60PointsWriter, PointsFormat};
+use core::codec::postings::{FieldsConsumerEnum, PostingsFormat};
+use core::codec::segment_infos::{Lucene62SegmentInfoFormat, SegmentReadState, SegmentWriteState};
+use core::codec::stored_fields::Lucene50StoredFieldsFormat;
+use core::codec::term_vectors::CompressingTermVectorsFormat;
+use core::codec::{Codec, Lucene50CompoundFormat, Lucene50LiveDocsFormat, Lucene62Codec};
+use core::index::memory::MemoryFields;
+use core::store::directory::Directory;
+
+use error::ErrorKind::{IllegalArgument, UnsupportedOperation};
+use error::{Error, Result};
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// The codec of a `MemoryIndex`.
+///
+/// The postings and points of a `MemoryIndex` only live in RAM, so they can
+/// neither be written to nor read from a `Directory`. The other formats are the
+/// ones of `Lucene62Codec`, they are only needed to name the reader types.
+#[derive(Default)]
+pub struct MemoryCodec {
+    codec: Lucene62Codec,
+}
+
+impl Codec for MemoryCodec {
+    type FieldsProducer = Arc<MemoryFields>;
+    type PostingFmt = MemoryPostingsFormat;
+    type DVFmt = PerFieldDocValuesFormat;
+    type StoredFmt = Lucene50StoredFieldsFormat;
+    type TVFmt = CompressingTermVectorsFormat;
+    type FieldFmt = Lucene60FieldInfosFormat;
+    type SegmentFmt = Lucene62SegmentInfoFormat;
+    type NormFmt = Lucene53NormsFormat;
+    type LiveDocFmt = Lucene50LiveDocsFormat;
+    type CompoundFmt = Lucene50CompoundFormat;
+    type PointFmt = MemoryPointsFormat;
+
+    fn name(&self) -> &str {
+        "Memory"
+    }
+
+    fn postings_format(&self) -> Self::PostingFmt {
+        MemoryPostingsFormat
+    }
+
+    fn doc_values_format(&self) -> Self::DVFmt {
+        self.codec.doc_values_format()
+    }
+
+    fn stored_fields_format(&self) -> Self::StoredFmt {
+        self.codec.stored_fields_format()
+    }
+
+    fn term_vectors_format(&self) -> Self::TVFmt {
+        self.codec.term_vectors_format()
+    }
+
+    fn field_infos_format(&self) -> Self::FieldFmt {
+        self.codec.field_infos_format()
+    }
+
+    fn segment_info_format(&self) -> Self::SegmentFmt {
+        self.codec.segment_info_format()
+    }
+
+    fn norms_format(&self) -> Self::NormFmt {
+        self.codec.norms_format()
+    }
+
+    fn live_docs_format(&self) -> Self::LiveDocFmt {
+        self.codec.live_docs_format()
+    }
+
+    fn compound_format(&self) -> Self::CompoundFmt {
+        self.codec.compound_format()
+    }
+
+    fn points_format(&self) -> Self::PointFmt {
+        MemoryPointsFormat
+    }
+}
+
+impl TryFrom<String> for MemoryCodec {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        if value.as_str() == "Memory" {
+            Ok(Self::default())
+        } else {
+            bail!(IllegalArgument(format!(
+                "unknown codec name, expected 'Memory' got {:?}",
+                value
+            )))
+        }
+    }
+}
+
+/// `PostingsFormat` of the `MemoryCodec`, the postings are built by the `MemoryIndex`.
+#[derive(Copy, Clone, Default)]
+pub struct MemoryPostingsFormat;
+
+impl PostingsFormat for MemoryPostingsFormat {
+    type FieldsProducer = Arc<MemoryFields>;
+
+    fn fields_producer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::FieldsProducer> {
+        bail!(UnsupportedOperation(
+            "memory postings can't be read from a directory".into()
+        ))
+    }
+
+    fn fields_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<FieldsConsumerEnum<D, DW, C>> {
+        bail!(UnsupportedOperation(
+            "memory postings can't be written to a directory".into()
+        ))
+    }
+
+    fn name(&self) -> &str {
+        "Memory"
+    }
+}
+
+/// `PointsFormat` of the `MemoryCodec`, the points are built by the `MemoryIndex`.
+#[derive(Copy, Clone, Default)]
+pub struct MemoryPointsFormat;
+
+impl PointsFormat for MemoryPointsFormat {
+    type Reader = MemoryFields;
+
+    fn fields_writer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Lucene60PointsWriter<D, DW, C>> {
+        bail!(UnsupportedOperation(
+            "memory points can't be written to a directory".into()
+        ))
+    }
+
+    fn fields_reader<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::Reader> {
+        bail!(UnsupportedOperation(
+            "memory points can't be read from a directory".into()
+        ))
+    }
+}
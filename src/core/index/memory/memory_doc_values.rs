@@ -0,0 +1,187 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::lucene54::DocValuesTermIterator;
+use core::codec::doc_values::{
+    BinaryDocValues, NumericDocValues, SortedDocValues, SortedNumericDocValues, SortedSetDocValues,
+    NO_MORE_ORDS,
+};
+use core::doc::DocValuesType;
+use core::util::DocId;
+
+use error::ErrorKind::UnsupportedOperation;
+use error::Result;
+
+/// The doc values of one field of the document of a `MemoryIndex`, the multi
+/// values being kept sorted.
+#[derive(Clone, Debug)]
+pub(crate) enum DocValuesData {
+    Numeric(i64),
+    Binary(Vec<u8>),
+    Sorted(Vec<u8>),
+    SortedNumeric(Vec<i64>),
+    SortedSet(Vec<Vec<u8>>),
+}
+
+impl DocValuesData {
+    pub fn doc_values_type(&self) -> DocValuesType {
+        match self {
+            DocValuesData::Numeric(_) => DocValuesType::Numeric,
+            DocValuesData::Binary(_) => DocValuesType::Binary,
+            DocValuesData::Sorted(_) => DocValuesType::Sorted,
+            DocValuesData::SortedNumeric(_) => DocValuesType::SortedNumeric,
+            DocValuesData::SortedSet(_) => DocValuesType::SortedSet,
+        }
+    }
+}
+
+/// `NumericDocValues` of the single document of a `MemoryIndex`, also used for
+/// the norms.
+pub struct MemoryNumericDocValues {
+    value: i64,
+}
+
+impl MemoryNumericDocValues {
+    pub fn new(value: i64) -> Self {
+        MemoryNumericDocValues { value }
+    }
+}
+
+impl NumericDocValues for MemoryNumericDocValues {
+    fn get(&self, _doc_id: DocId) -> Result<i64> {
+        Ok(self.value)
+    }
+}
+
+/// `BinaryDocValues` of the single document of a `MemoryIndex`.
+pub struct MemoryBinaryDocValues {
+    value: Vec<u8>,
+}
+
+impl MemoryBinaryDocValues {
+    pub fn new(value: Vec<u8>) -> Self {
+        MemoryBinaryDocValues { value }
+    }
+}
+
+impl BinaryDocValues for MemoryBinaryDocValues {
+    fn get(&mut self, _doc_id: DocId) -> Result<Vec<u8>> {
+        Ok(self.value.clone())
+    }
+}
+
+/// `SortedDocValues` of the single document of a `MemoryIndex`, with a single ord.
+pub struct MemorySortedDocValues {
+    value: Vec<u8>,
+}
+
+impl MemorySortedDocValues {
+    pub fn new(value: Vec<u8>) -> Self {
+        MemorySortedDocValues { value }
+    }
+}
+
+impl BinaryDocValues for MemorySortedDocValues {
+    fn get(&mut self, _doc_id: DocId) -> Result<Vec<u8>> {
+        Ok(self.value.clone())
+    }
+}
+
+impl SortedDocValues for MemorySortedDocValues {
+    fn get_ord(&mut self, _doc_id: DocId) -> Result<i32> {
+        Ok(0)
+    }
+
+    fn lookup_ord(&mut self, ord: i32) -> Result<Vec<u8>> {
+        debug_assert_eq!(ord, 0);
+        Ok(self.value.clone())
+    }
+
+    fn value_count(&self) -> usize {
+        1
+    }
+
+    fn term_iterator(&self) -> Result<DocValuesTermIterator> {
+        bail!(UnsupportedOperation(
+            "MemorySortedDocValues::term_iterator unsupported".into()
+        ))
+    }
+}
+
+/// `SortedNumericDocValues` of the single document of a `MemoryIndex`.
+pub struct MemorySortedNumericDocValues {
+    values: Vec<i64>,
+}
+
+impl MemorySortedNumericDocValues {
+    pub fn new(values: Vec<i64>) -> Self {
+        MemorySortedNumericDocValues { values }
+    }
+}
+
+impl SortedNumericDocValues for MemorySortedNumericDocValues {
+    fn set_document(&mut self, _doc: DocId) -> Result<()> {
+        Ok(())
+    }
+
+    fn value_at(&mut self, index: usize) -> Result<i64> {
+        Ok(self.values[index])
+    }
+
+    fn count(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// `SortedSetDocValues` of the single document of a `MemoryIndex`, the ords
+/// being the indexes of its sorted values.
+pub struct MemorySortedSetDocValues {
+    values: Vec<Vec<u8>>,
+    upto: usize,
+}
+
+impl MemorySortedSetDocValues {
+    pub fn new(values: Vec<Vec<u8>>) -> Self {
+        MemorySortedSetDocValues { values, upto: 0 }
+    }
+}
+
+impl SortedSetDocValues for MemorySortedSetDocValues {
+    fn set_document(&mut self, _doc: DocId) -> Result<()> {
+        self.upto = 0;
+        Ok(())
+    }
+
+    fn next_ord(&mut self) -> Result<i64> {
+        if self.upto < self.values.len() {
+            self.upto += 1;
+            Ok(self.upto as i64 - 1)
+        } else {
+            Ok(NO_MORE_ORDS)
+        }
+    }
+
+    fn lookup_ord(&mut self, ord: i64) -> Result<Vec<u8>> {
+        Ok(self.values[ord as usize].clone())
+    }
+
+    fn get_value_count(&self) -> usize {
+        self.values.len()
+    }
+
+    fn term_iterator(&self) -> Result<DocValuesTermIterator> {
+        bail!(UnsupportedOperation(
+            "MemorySortedSetDocValues::term_iterator unsupported".into()
+        ))
+    }
+}
@@ -0,0 +1,702 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::TokenStream;
+use core::codec::field_infos::{FieldInfo, FieldInfos, FieldInvertState};
+use core::codec::points::{IntersectVisitor, PointValues};
+use core::codec::postings::FieldsProducer;
+use core::codec::{Fields, OrdTermState, PostingIterator, SeekStatus, TermIterator, Terms};
+use core::doc::{DocValuesType, Fieldable, IndexOptions};
+use core::index::memory::DocValuesData;
+use core::search::similarity::BM25Similarity;
+use core::search::{DocIterator, Payload, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::ErrorKind::{IllegalArgument, IllegalState};
+use error::Result;
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// The occurrences of one term in the document of a `MemoryIndex`.
+#[derive(Clone, Debug)]
+struct MemoryTerm {
+    term: Vec<u8>,
+    positions: Vec<i32>,
+    start_offsets: Vec<i32>,
+    end_offsets: Vec<i32>,
+    payloads: Vec<Payload>,
+}
+
+impl MemoryTerm {
+    fn new(term: Vec<u8>) -> MemoryTerm {
+        MemoryTerm {
+            term,
+            positions: vec![],
+            start_offsets: vec![],
+            end_offsets: vec![],
+            payloads: vec![],
+        }
+    }
+
+    fn freq(&self) -> i32 {
+        self.positions.len() as i32
+    }
+}
+
+/// Everything a `MemoryIndex` knows about one field of its document: the
+/// inverted terms, the doc values and the points.
+#[derive(Clone, Debug)]
+pub struct MemoryField {
+    name: String,
+    // sorted by term bytes, so that the term ords are the indexes
+    terms: Vec<MemoryTerm>,
+    index_options: IndexOptions,
+    omit_norms: bool,
+    has_payloads: bool,
+    sum_total_term_freq: i64,
+    // invert state, across all the values of the field
+    position: i32,
+    length: i32,
+    num_overlap: i32,
+    offset: usize,
+    boost: f32,
+    doc_values: Option<DocValuesData>,
+    points: Vec<Vec<u8>>,
+    point_dimension_count: u32,
+    point_num_bytes: u32,
+}
+
+impl MemoryField {
+    fn new(name: String) -> MemoryField {
+        MemoryField {
+            name,
+            terms: vec![],
+            index_options: IndexOptions::Null,
+            omit_norms: true,
+            has_payloads: false,
+            sum_total_term_freq: 0,
+            position: -1,
+            length: 0,
+            num_overlap: 0,
+            offset: 0,
+            boost: 1.0,
+            doc_values: None,
+            points: vec![],
+            point_dimension_count: 0,
+            point_num_bytes: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn index_options(&self) -> IndexOptions {
+        self.index_options
+    }
+
+    pub(crate) fn doc_values(&self) -> Option<&DocValuesData> {
+        self.doc_values.as_ref()
+    }
+
+    /// The encoded norm of the field, as it would have been indexed.
+    pub(crate) fn norm(&self) -> i64 {
+        if self.length == 0 {
+            return 0;
+        }
+        let state = FieldInvertState::new(
+            self.name.clone(),
+            self.position,
+            self.length,
+            self.num_overlap,
+            self.offset,
+            self.boost,
+        );
+        BM25Similarity::compute_norm(&state)
+    }
+
+    pub(crate) fn has_norms(&self) -> bool {
+        self.index_options != IndexOptions::Null && !self.omit_norms
+    }
+
+    /// Adds the tokens of one value of the field, the positions and offsets
+    /// following the ones of the previous values.
+    pub(crate) fn invert(
+        &mut self,
+        token_stream: &mut dyn TokenStream,
+        index_options: IndexOptions,
+        omit_norms: bool,
+        store_payloads: bool,
+        boost: f32,
+    ) -> Result<()> {
+        if self.index_options == IndexOptions::Null {
+            self.index_options = index_options;
+            self.omit_norms = omit_norms;
+        } else {
+            self.omit_norms |= omit_norms;
+        }
+
+        token_stream.reset()?;
+        while token_stream.next_token()? {
+            let token = token_stream.token();
+            if token.position == 0 {
+                if self.position < 0 {
+                    bail!(IllegalArgument(
+                        "first position increment must be > 0 (got 0)".into()
+                    ));
+                }
+                self.num_overlap += 1;
+            }
+            self.position += token.position as i32;
+            self.length += 1;
+
+            let position = self.position;
+            let start_offset = (self.offset + token.start_offset) as i32;
+            let end_offset = (self.offset + token.end_offset) as i32;
+            let payload = if store_payloads && !token.payload.is_empty() {
+                self.has_payloads = true;
+                token.payload.clone()
+            } else {
+                vec![]
+            };
+
+            let idx = match self
+                .terms
+                .binary_search_by(|t| t.term.as_slice().cmp(&token.term))
+            {
+                Ok(idx) => idx,
+                Err(idx) => {
+                    self.terms.insert(idx, MemoryTerm::new(token.term.clone()));
+                    idx
+                }
+            };
+            let term = &mut self.terms[idx];
+            term.positions.push(position);
+            term.start_offsets.push(start_offset);
+            term.end_offsets.push(end_offset);
+            term.payloads.push(payload);
+            self.sum_total_term_freq += 1;
+        }
+        token_stream.end()?;
+
+        self.position += token_stream.token().position as i32;
+        self.offset += token_stream.token().end_offset;
+        self.boost *= boost;
+        Ok(())
+    }
+
+    pub(crate) fn add_doc_value(
+        &mut self,
+        doc_values_type: DocValuesType,
+        field: &impl Fieldable,
+    ) -> Result<()> {
+        if let Some(ref data) = self.doc_values {
+            if data.doc_values_type() != doc_values_type {
+                bail!(IllegalArgument(format!(
+                    "cannot change DocValues type from {:?} to {:?} for field \"{}\"",
+                    data.doc_values_type(),
+                    doc_values_type,
+                    &self.name
+                )));
+            }
+        }
+
+        let numeric = || -> Result<i64> {
+            match field.numeric_value() {
+                Some(n) => Ok(n.long_value()),
+                None => bail!(IllegalArgument(format!(
+                    "field \"{}\" has no numeric value",
+                    field.name()
+                ))),
+            }
+        };
+        let binary = || -> Result<Vec<u8>> {
+            match field.binary_value() {
+                Some(b) => Ok(b.to_vec()),
+                None => bail!(IllegalArgument(format!(
+                    "field \"{}\" has no binary value",
+                    field.name()
+                ))),
+            }
+        };
+        let data = match (self.doc_values.take(), doc_values_type) {
+            (None, DocValuesType::Numeric) => DocValuesData::Numeric(numeric()?),
+            (None, DocValuesType::Binary) => DocValuesData::Binary(binary()?),
+            (None, DocValuesType::Sorted) => DocValuesData::Sorted(binary()?),
+            (None, DocValuesType::SortedNumeric) => DocValuesData::SortedNumeric(vec![numeric()?]),
+            (None, DocValuesType::SortedSet) => DocValuesData::SortedSet(vec![binary()?]),
+            (Some(DocValuesData::SortedNumeric(mut values)), _) => {
+                let value = numeric()?;
+                let idx = values.binary_search(&value).unwrap_or_else(|i| i);
+                values.insert(idx, value);
+                DocValuesData::SortedNumeric(values)
+            }
+            (Some(DocValuesData::SortedSet(mut values)), _) => {
+                let value = binary()?;
+                if let Err(idx) = values.binary_search(&value) {
+                    values.insert(idx, value);
+                }
+                DocValuesData::SortedSet(values)
+            }
+            (Some(data), _) => {
+                self.doc_values = Some(data);
+                bail!(IllegalArgument(format!(
+                    "DocValuesField \"{}\" appears more than once in this document (only one \
+                     value is allowed per field)",
+                    &self.name
+                )));
+            }
+            (None, DocValuesType::Null) => unreachable!(),
+        };
+        self.doc_values = Some(data);
+        Ok(())
+    }
+
+    pub(crate) fn add_point(
+        &mut self,
+        dimension_count: u32,
+        num_bytes: u32,
+        packed_value: &[u8],
+    ) -> Result<()> {
+        if self.point_dimension_count == 0 {
+            self.point_dimension_count = dimension_count;
+            self.point_num_bytes = num_bytes;
+        } else if self.point_dimension_count != dimension_count || self.point_num_bytes != num_bytes
+        {
+            bail!(IllegalArgument(format!(
+                "cannot change point dimension count from {} to {} or dimension num bytes from {} \
+                 to {} for field \"{}\"",
+                self.point_dimension_count,
+                dimension_count,
+                self.point_num_bytes,
+                num_bytes,
+                &self.name
+            )));
+        }
+        if packed_value.len() != (dimension_count * num_bytes) as usize {
+            bail!(IllegalArgument(format!(
+                "packed value of field \"{}\" is {} bytes, expected {}",
+                &self.name,
+                packed_value.len(),
+                dimension_count * num_bytes
+            )));
+        }
+        self.points.push(packed_value.to_vec());
+        Ok(())
+    }
+
+    fn field_info(&self, number: u32) -> Result<FieldInfo> {
+        let doc_values_type = self
+            .doc_values
+            .as_ref()
+            .map_or(DocValuesType::Null, |dv| dv.doc_values_type());
+        FieldInfo::new(
+            self.name.clone(),
+            number,
+            false,
+            self.omit_norms,
+            self.has_payloads,
+            self.index_options,
+            doc_values_type,
+            -1,
+            HashMap::new(),
+            self.point_dimension_count,
+            self.point_num_bytes,
+        )
+    }
+
+    /// The per dimension min and max of the points of the field.
+    fn point_bounds(&self) -> (Vec<u8>, Vec<u8>) {
+        let bytes = self.point_num_bytes as usize;
+        let mut min = self.points[0].clone();
+        let mut max = self.points[0].clone();
+        for point in &self.points[1..] {
+            for dim in 0..self.point_dimension_count as usize {
+                let range = dim * bytes..(dim + 1) * bytes;
+                if point[range.clone()] < min[range.clone()] {
+                    min[range.clone()].copy_from_slice(&point[range.clone()]);
+                }
+                if point[range.clone()] > max[range.clone()] {
+                    max[range.clone()].copy_from_slice(&point[range]);
+                }
+            }
+        }
+        (min, max)
+    }
+}
+
+/// The fields of the document of a `MemoryIndex`, exposed as its postings and
+/// its points.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFields {
+    fields: BTreeMap<String, Arc<MemoryField>>,
+}
+
+impl MemoryFields {
+    pub fn field(&self, name: &str) -> Option<&MemoryField> {
+        self.fields.get(name).map(|f| f.as_ref())
+    }
+
+    pub(crate) fn field_mut(&mut self, name: &str) -> &mut MemoryField {
+        let field = self
+            .fields
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(MemoryField::new(name.to_string())));
+        Arc::make_mut(field)
+    }
+
+    pub(crate) fn field_infos(&self) -> Result<FieldInfos> {
+        let mut infos = Vec::with_capacity(self.fields.len());
+        for (number, field) in self.fields.values().enumerate() {
+            infos.push(field.field_info(number as u32)?);
+        }
+        FieldInfos::new(infos)
+    }
+
+    pub(crate) fn has_points(&self) -> bool {
+        self.fields.values().any(|f| !f.points.is_empty())
+    }
+
+    fn point_field(&self, name: &str) -> Option<&MemoryField> {
+        self.field(name).filter(|f| !f.points.is_empty())
+    }
+}
+
+impl Fields for MemoryFields {
+    type Terms = MemoryTerms;
+
+    fn fields(&self) -> Vec<String> {
+        self.fields
+            .values()
+            .filter(|f| f.index_options != IndexOptions::Null)
+            .map(|f| f.name.clone())
+            .collect()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
+        Ok(self
+            .fields
+            .get(field)
+            .filter(|f| f.index_options != IndexOptions::Null)
+            .map(|f| MemoryTerms {
+                field: Arc::clone(f),
+            }))
+    }
+
+    fn size(&self) -> usize {
+        self.fields().len()
+    }
+
+    fn terms_freq(&self, field: &str) -> usize {
+        self.field(field)
+            .map_or(0, |f| f.sum_total_term_freq as usize)
+    }
+}
+
+impl FieldsProducer for MemoryFields {
+    fn check_integrity(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl PointValues for MemoryFields {
+    fn intersect(&self, field_name: &str, visitor: &mut impl IntersectVisitor) -> Result<()> {
+        if let Some(field) = self.point_field(field_name) {
+            visitor.grow(field.points.len());
+            for point in &field.points {
+                visitor.visit_by_packed_value(0, point)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
+        Ok(self
+            .point_field(field_name)
+            .map(|f| f.point_bounds().0)
+            .unwrap_or_default())
+    }
+
+    fn max_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
+        Ok(self
+            .point_field(field_name)
+            .map(|f| f.point_bounds().1)
+            .unwrap_or_default())
+    }
+
+    fn num_dimensions(&self, field_name: &str) -> Result<usize> {
+        Ok(self
+            .point_field(field_name)
+            .map_or(0, |f| f.point_dimension_count as usize))
+    }
+
+    fn bytes_per_dimension(&self, field_name: &str) -> Result<usize> {
+        Ok(self
+            .point_field(field_name)
+            .map_or(0, |f| f.point_num_bytes as usize))
+    }
+
+    fn size(&self, field_name: &str) -> Result<i64> {
+        Ok(self
+            .point_field(field_name)
+            .map_or(0, |f| f.points.len() as i64))
+    }
+
+    fn doc_count(&self, field_name: &str) -> Result<i32> {
+        Ok(self.point_field(field_name).map_or(0, |_| 1))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The terms of one field of a `MemoryIndex`.
+pub struct MemoryTerms {
+    field: Arc<MemoryField>,
+}
+
+impl Terms for MemoryTerms {
+    type Iterator = MemoryTermIterator;
+
+    fn iterator(&self) -> Result<Self::Iterator> {
+        Ok(MemoryTermIterator {
+            field: Arc::clone(&self.field),
+            ord: -1,
+        })
+    }
+
+    fn size(&self) -> Result<i64> {
+        Ok(self.field.terms.len() as i64)
+    }
+
+    fn sum_total_term_freq(&self) -> Result<i64> {
+        Ok(self.field.sum_total_term_freq)
+    }
+
+    fn sum_doc_freq(&self) -> Result<i64> {
+        Ok(self.field.terms.len() as i64)
+    }
+
+    fn doc_count(&self) -> Result<i32> {
+        Ok(if self.field.terms.is_empty() { 0 } else { 1 })
+    }
+
+    fn has_freqs(&self) -> Result<bool> {
+        Ok(self.field.index_options.has_freqs())
+    }
+
+    fn has_offsets(&self) -> Result<bool> {
+        Ok(self.field.index_options.has_offsets())
+    }
+
+    fn has_positions(&self) -> Result<bool> {
+        Ok(self.field.index_options.has_positions())
+    }
+
+    fn has_payloads(&self) -> Result<bool> {
+        Ok(self.field.has_payloads)
+    }
+}
+
+/// Iterates the terms of one field of a `MemoryIndex`, in byte order.
+pub struct MemoryTermIterator {
+    field: Arc<MemoryField>,
+    ord: i64,
+}
+
+impl MemoryTermIterator {
+    fn current(&self) -> Result<&MemoryTerm> {
+        if self.ord < 0 || self.ord >= self.field.terms.len() as i64 {
+            bail!(IllegalState("term iterator is not positioned".into()));
+        }
+        Ok(&self.field.terms[self.ord as usize])
+    }
+}
+
+impl TermIterator for MemoryTermIterator {
+    type Postings = MemoryPostingIterator;
+    type TermState = OrdTermState;
+
+    fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        let size = self.field.terms.len() as i64;
+        if self.ord < size {
+            self.ord += 1;
+        }
+        if self.ord < size {
+            Ok(Some(self.field.terms[self.ord as usize].term.clone()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn seek_ceil(&mut self, text: &[u8]) -> Result<SeekStatus> {
+        match self
+            .field
+            .terms
+            .binary_search_by(|t| t.term.as_slice().cmp(text))
+        {
+            Ok(idx) => {
+                self.ord = idx as i64;
+                Ok(SeekStatus::Found)
+            }
+            Err(idx) => {
+                self.ord = idx as i64;
+                if idx == self.field.terms.len() {
+                    Ok(SeekStatus::End)
+                } else {
+                    Ok(SeekStatus::NotFound)
+                }
+            }
+        }
+    }
+
+    fn seek_exact_ord(&mut self, ord: i64) -> Result<()> {
+        if ord < 0 || ord >= self.field.terms.len() as i64 {
+            bail!(IllegalArgument(format!("term ord {} out of bounds", ord)));
+        }
+        self.ord = ord;
+        Ok(())
+    }
+
+    fn seek_exact_state(&mut self, _text: &[u8], state: &Self::TermState) -> Result<()> {
+        self.seek_exact_ord(state.ord)
+    }
+
+    fn term(&self) -> Result<&[u8]> {
+        Ok(&self.current()?.term)
+    }
+
+    fn ord(&self) -> Result<i64> {
+        Ok(self.ord)
+    }
+
+    fn doc_freq(&mut self) -> Result<i32> {
+        self.current()?;
+        Ok(1)
+    }
+
+    fn total_term_freq(&mut self) -> Result<i64> {
+        Ok(i64::from(self.current()?.freq()))
+    }
+
+    fn postings_with_flags(&mut self, _flags: u16) -> Result<Self::Postings> {
+        self.current()?;
+        Ok(MemoryPostingIterator {
+            field: Arc::clone(&self.field),
+            ord: self.ord as usize,
+            doc: -1,
+            upto: -1,
+        })
+    }
+
+    fn term_state(&mut self) -> Result<Self::TermState> {
+        self.current()?;
+        Ok(OrdTermState { ord: self.ord })
+    }
+}
+
+/// The postings of one term of a `MemoryIndex`, always in the doc 0.
+pub struct MemoryPostingIterator {
+    field: Arc<MemoryField>,
+    ord: usize,
+    doc: DocId,
+    // index of the current position
+    upto: i32,
+}
+
+impl MemoryPostingIterator {
+    fn term(&self) -> &MemoryTerm {
+        &self.field.terms[self.ord]
+    }
+
+    fn check_position(&self) -> Result<usize> {
+        if self.doc != 0 {
+            bail!(IllegalState("posting iterator is not positioned".into()));
+        }
+        if self.upto < 0 || self.upto >= self.term().freq() {
+            bail!(IllegalState("position iterator is not positioned".into()));
+        }
+        Ok(self.upto as usize)
+    }
+}
+
+impl PostingIterator for MemoryPostingIterator {
+    fn freq(&self) -> Result<i32> {
+        if self.field.index_options.has_freqs() {
+            Ok(self.term().freq())
+        } else {
+            Ok(1)
+        }
+    }
+
+    fn next_position(&mut self) -> Result<i32> {
+        if self.doc != 0 {
+            bail!(IllegalState("posting iterator is not positioned".into()));
+        }
+        if self.upto + 1 >= self.term().freq() {
+            bail!(IllegalState("read past last position".into()));
+        }
+        self.upto += 1;
+        if self.field.index_options.has_positions() {
+            Ok(self.term().positions[self.upto as usize])
+        } else {
+            Ok(-1)
+        }
+    }
+
+    fn start_offset(&self) -> Result<i32> {
+        let upto = self.check_position()?;
+        if self.field.index_options.has_offsets() {
+            Ok(self.term().start_offsets[upto])
+        } else {
+            Ok(-1)
+        }
+    }
+
+    fn end_offset(&self) -> Result<i32> {
+        let upto = self.check_position()?;
+        if self.field.index_options.has_offsets() {
+            Ok(self.term().end_offsets[upto])
+        } else {
+            Ok(-1)
+        }
+    }
+
+    fn payload(&self) -> Result<Payload> {
+        let upto = self.check_position()?;
+        Ok(self.term().payloads[upto].clone())
+    }
+}
+
+impl DocIterator for MemoryPostingIterator {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.doc = if self.doc == -1 { 0 } else { NO_MORE_DOCS };
+        self.upto = -1;
+        Ok(self.doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.slow_advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        1
+    }
+}
@@ -0,0 +1,505 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::TokenStream;
+use core::codec::doc_values::{
+    BinaryDocValues, DocValuesProducer, NumericDocValues, SortedDocValues, SortedNumericDocValues,
+    SortedSetDocValues,
+};
+use core::codec::field_infos::{FieldInfo, FieldInfos};
+use core::codec::{
+    CodecFieldsProducer, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
+    CodecTVFields, CodecTVReader,
+};
+use core::doc::{
+    DocValuesType, Document, DocumentStoredFieldVisitor, Fieldable, IndexOptions,
+    StoredFieldVisitor,
+};
+use core::index::memory::{
+    DocValuesData, MemoryBinaryDocValues, MemoryCodec, MemoryFields, MemoryNumericDocValues,
+    MemorySortedDocValues, MemorySortedNumericDocValues, MemorySortedSetDocValues,
+};
+use core::index::reader::{IndexReader, LeafReader, LeafReaderContext};
+use core::search::collector::TopDocsCollector;
+use core::search::query::Query;
+use core::search::sort_field::Sort;
+use core::search::{DefaultIndexSearcher, IndexSearcher};
+use core::util::external::Deferred;
+use core::util::{BitsMut, BitsRef, DocId, MatchAllBits};
+
+use error::ErrorKind::{IllegalArgument, UnsupportedOperation};
+use error::Result;
+
+use std::sync::Arc;
+
+const MEMORY_INDEX_NAME: &str = "memory_index";
+
+/// A `LeafReader` over a single document analyzed into RAM.
+///
+/// The fields of the document are inverted into terms with their positions,
+/// offsets and payloads, and keep their doc values, norms and points, without
+/// ever touching a `Directory`. Any `Query<MemoryCodec>` can then be scored against
+/// the document, the `MemoryIndex` being both its only leaf and its `IndexReader`:
+///
+/// ```rust,ignore
+/// let mut index = MemoryIndex::new(true, false);
+/// index.add_token_stream("content", analyzer("the quick brown fox"))?;
+/// let score = index.search(&query)?;
+/// ```
+///
+/// A field may be added more than once, the positions and offsets of the values
+/// following each other. Stored fields are ignored and no term vectors are built.
+pub struct MemoryIndex {
+    codec: MemoryCodec,
+    fields: Arc<MemoryFields>,
+    field_infos: Arc<FieldInfos>,
+    live_docs: BitsRef,
+    store_offsets: bool,
+    store_payloads: bool,
+}
+
+impl MemoryIndex {
+    pub fn new(store_offsets: bool, store_payloads: bool) -> MemoryIndex {
+        MemoryIndex {
+            codec: MemoryCodec::default(),
+            fields: Arc::new(MemoryFields::default()),
+            field_infos: Arc::new(FieldInfos::new(vec![]).unwrap()),
+            live_docs: Arc::new(MatchAllBits::new(1)),
+            store_offsets,
+            store_payloads,
+        }
+    }
+
+    /// Adds all the fields of a document.
+    pub fn add_document(&mut self, doc: Vec<Box<dyn Fieldable>>) -> Result<()> {
+        for mut field in doc {
+            self.add_field(&mut field)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a field the way the `IndexWriter` would index it: inverted if indexed,
+    /// with its doc values and points.
+    pub fn add_field(&mut self, field: &mut impl Fieldable) -> Result<()> {
+        let field_type = field.field_type().clone();
+        if field_type.index_options != IndexOptions::Null {
+            if field_type.omit_norms && (field.boost() - 1.0).abs() > ::std::f32::EPSILON {
+                bail!(UnsupportedOperation(
+                    "You cannot set an index-time boost: norms are omitted".into()
+                ));
+            }
+            let mut token_stream = field.token_stream()?;
+            let index_options = self.index_options();
+            let store_payloads = self.store_payloads;
+            Arc::make_mut(&mut self.fields)
+                .field_mut(field.name())
+                .invert(
+                    token_stream.as_mut(),
+                    index_options,
+                    field_type.omit_norms,
+                    store_payloads,
+                    field.boost(),
+                )?;
+        }
+
+        if field_type.doc_values_type != DocValuesType::Null {
+            Arc::make_mut(&mut self.fields)
+                .field_mut(field.name())
+                .add_doc_value(field_type.doc_values_type, field)?;
+        }
+
+        if field_type.dimension_count > 0 {
+            let packed_value = match field.binary_value() {
+                Some(value) => value,
+                None => bail!(IllegalArgument(format!(
+                    "point field \"{}\" has no packed value",
+                    field.name()
+                ))),
+            };
+            Arc::make_mut(&mut self.fields)
+                .field_mut(field.name())
+                .add_point(
+                    field_type.dimension_count,
+                    field_type.dimension_num_bytes,
+                    packed_value,
+                )?;
+        }
+
+        self.field_infos = Arc::new(self.fields.field_infos()?);
+        Ok(())
+    }
+
+    /// Adds the tokens of a text analyzed by the caller to an indexed field.
+    pub fn add_token_stream(
+        &mut self,
+        field: &str,
+        mut token_stream: Box<dyn TokenStream>,
+    ) -> Result<()> {
+        let index_options = self.index_options();
+        let store_payloads = self.store_payloads;
+        Arc::make_mut(&mut self.fields).field_mut(field).invert(
+            token_stream.as_mut(),
+            index_options,
+            false,
+            store_payloads,
+            1.0,
+        )?;
+        self.field_infos = Arc::new(self.fields.field_infos()?);
+        Ok(())
+    }
+
+    /// Returns the score of the document for the query, or `None` if it doesn't match.
+    pub fn search(&self, query: &dyn Query<MemoryCodec>) -> Result<Option<f32>> {
+        let searcher = DefaultIndexSearcher::new(self, None);
+        let mut collector = TopDocsCollector::new(1);
+        searcher.search(query, &mut collector)?;
+        Ok(collector.top_docs().score_docs().first().map(|d| d.score()))
+    }
+
+    /// Removes all the fields, so that another document can be added.
+    pub fn reset(&mut self) {
+        self.fields = Arc::new(MemoryFields::default());
+        self.field_infos = Arc::new(FieldInfos::new(vec![]).unwrap());
+    }
+
+    fn index_options(&self) -> IndexOptions {
+        if self.store_offsets {
+            IndexOptions::DocsAndFreqsAndPositionsAndOffsets
+        } else {
+            IndexOptions::DocsAndFreqsAndPositions
+        }
+    }
+
+    fn doc_values(&self, field: &str) -> Option<&DocValuesData> {
+        self.fields.field(field).and_then(|f| f.doc_values())
+    }
+}
+
+impl IndexReader for MemoryIndex {
+    type Codec = MemoryCodec;
+
+    fn leaves(&self) -> Vec<LeafReaderContext<'_, MemoryCodec>> {
+        vec![LeafReaderContext::new(self, self, 0, 0)]
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<MemoryCodec>>> {
+        LeafReader::term_vector(self, doc_id)
+    }
+
+    fn document(&self, doc_id: DocId, fields: &[String]) -> Result<Document> {
+        let mut visitor = DocumentStoredFieldVisitor::new(fields);
+        LeafReader::document(self, doc_id, &mut visitor)?;
+        Ok(visitor.document())
+    }
+
+    fn max_doc(&self) -> i32 {
+        1
+    }
+
+    fn num_docs(&self) -> i32 {
+        1
+    }
+}
+
+impl LeafReader for MemoryIndex {
+    type Codec = MemoryCodec;
+    type FieldsProducer = CodecFieldsProducer<MemoryCodec>;
+    type TVFields = CodecTVFields<MemoryCodec>;
+    type TVReader = Arc<CodecTVReader<MemoryCodec>>;
+    type StoredReader = Arc<CodecStoredFieldsReader<MemoryCodec>>;
+    type NormsReader = Arc<CodecNormsProducer<MemoryCodec>>;
+    type PointsReader = Arc<CodecPointsReader<MemoryCodec>>;
+
+    fn codec(&self) -> &Self::Codec {
+        &self.codec
+    }
+
+    fn fields(&self) -> Result<Self::FieldsProducer> {
+        Ok(Arc::clone(&self.fields))
+    }
+
+    fn name(&self) -> &str {
+        MEMORY_INDEX_NAME
+    }
+
+    fn term_vector(&self, _doc_id: DocId) -> Result<Option<Self::TVFields>> {
+        Ok(None)
+    }
+
+    fn document(&self, _doc_id: DocId, _visitor: &mut dyn StoredFieldVisitor) -> Result<()> {
+        // stored fields are not kept
+        Ok(())
+    }
+
+    fn live_docs(&self) -> BitsRef {
+        Arc::clone(&self.live_docs)
+    }
+
+    fn field_info(&self, field: &str) -> Option<&FieldInfo> {
+        self.field_infos.field_info_by_name(field)
+    }
+
+    fn field_infos(&self) -> &FieldInfos {
+        &self.field_infos
+    }
+
+    fn clone_field_infos(&self) -> Arc<FieldInfos> {
+        Arc::clone(&self.field_infos)
+    }
+
+    fn max_doc(&self) -> DocId {
+        1
+    }
+
+    fn num_docs(&self) -> i32 {
+        1
+    }
+
+    fn get_numeric_doc_values(&self, field: &str) -> Result<Box<dyn NumericDocValues>> {
+        match self.doc_values(field) {
+            Some(DocValuesData::Numeric(value)) => {
+                Ok(Box::new(MemoryNumericDocValues::new(*value)))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "non-exist or non-numeric field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn get_binary_doc_values(&self, field: &str) -> Result<Box<dyn BinaryDocValues>> {
+        match self.doc_values(field) {
+            Some(DocValuesData::Binary(value)) => {
+                Ok(Box::new(MemoryBinaryDocValues::new(value.clone())))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "non-exist or non-binary field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn get_sorted_doc_values(&self, field: &str) -> Result<Box<dyn SortedDocValues>> {
+        match self.doc_values(field) {
+            Some(DocValuesData::Sorted(value)) => {
+                Ok(Box::new(MemorySortedDocValues::new(value.clone())))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "non-exist or non-sorted field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn get_sorted_numeric_doc_values(
+        &self,
+        field: &str,
+    ) -> Result<Box<dyn SortedNumericDocValues>> {
+        match self.doc_values(field) {
+            Some(DocValuesData::SortedNumeric(values)) => {
+                Ok(Box::new(MemorySortedNumericDocValues::new(values.clone())))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "non-exist or non-sorted_numeric field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn get_sorted_set_doc_values(&self, field: &str) -> Result<Box<dyn SortedSetDocValues>> {
+        match self.doc_values(field) {
+            Some(DocValuesData::SortedSet(values)) => {
+                Ok(Box::new(MemorySortedSetDocValues::new(values.clone())))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "non-exist or non-sorted_set field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn norm_values(&self, field: &str) -> Result<Option<Box<dyn NumericDocValues>>> {
+        Ok(self.fields.field(field).filter(|f| f.has_norms()).map(
+            |f| -> Box<dyn NumericDocValues> { Box::new(MemoryNumericDocValues::new(f.norm())) },
+        ))
+    }
+
+    fn get_docs_with_field(&self, field: &str) -> Result<Box<dyn BitsMut>> {
+        match self.doc_values(field) {
+            Some(_) => Ok(Box::new(MatchAllBits::new(1))),
+            None => bail!(IllegalArgument(format!(
+                "non-exist or DocValuesType::Null field: {}",
+                field
+            ))),
+        }
+    }
+
+    fn point_values(&self) -> Option<Self::PointsReader> {
+        if self.fields.has_points() {
+            Some(Arc::clone(&self.fields))
+        } else {
+            None
+        }
+    }
+
+    fn core_cache_key(&self) -> &str {
+        MEMORY_INDEX_NAME
+    }
+
+    fn index_sort(&self) -> Option<&Sort> {
+        None
+    }
+
+    fn add_core_drop_listener(&self, _listener: Deferred) {}
+
+    fn is_codec_reader(&self) -> bool {
+        false
+    }
+
+    fn store_fields_reader(&self) -> Result<Self::StoredReader> {
+        bail!(UnsupportedOperation(
+            "MemoryIndex has no stored fields reader".into()
+        ))
+    }
+
+    fn term_vectors_reader(&self) -> Result<Option<Self::TVReader>> {
+        Ok(None)
+    }
+
+    fn norms_reader(&self) -> Result<Option<Self::NormsReader>> {
+        Ok(None)
+    }
+
+    fn doc_values_reader(&self) -> Result<Option<Arc<dyn DocValuesProducer>>> {
+        Ok(None)
+    }
+
+    fn postings_reader(&self) -> Result<Self::FieldsProducer> {
+        self.fields()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::{PostingIterator, TermIterator, Terms};
+    use core::doc::{Field, FieldType, NumericDocValuesField, Term};
+    use core::search::query::tests::index_fields;
+    use core::search::query::{whitespace_analyzer, IntPoint, PhraseQuery, TermQuery};
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::util::VariantValue;
+
+    const TEXT: &str = "the quick brown fox jumps over the lazy dog";
+
+    fn document() -> Vec<Box<dyn Fieldable>> {
+        let mut text_type = FieldType::default();
+        text_type.index_options = IndexOptions::DocsAndFreqsAndPositionsAndOffsets;
+        let mut point_type = FieldType::default();
+        point_type.set_dimensions(1, 4).unwrap();
+        vec![
+            Box::new(Field::new(
+                "content".into(),
+                text_type,
+                Some(VariantValue::VString(TEXT.into())),
+                Some(whitespace_analyzer()(TEXT)),
+            )),
+            Box::new(NumericDocValuesField::new("price", 42)),
+            Box::new(Field::new(
+                "size".into(),
+                point_type,
+                Some(VariantValue::Binary(IntPoint::pack(&[7]))),
+                None,
+            )),
+        ]
+    }
+
+    fn term_query(text: &str) -> TermQuery {
+        TermQuery::new(Term::new("content".into(), text.into()), 1.0, None)
+    }
+
+    fn phrase_query(words: &[&str]) -> PhraseQuery {
+        let terms = words
+            .iter()
+            .map(|w| Term::new("content".into(), w.as_bytes().to_vec()))
+            .collect();
+        PhraseQuery::build(terms, 0, None, None).unwrap()
+    }
+
+    #[test]
+    fn test_memory_index() {
+        let mut index = MemoryIndex::new(true, false);
+        index.add_document(document()).unwrap();
+
+        // scores like the same document alone in a directory index
+        let (_dir, reader) = index_fields(vec![document()]);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let expl = searcher.explain(&term_query("fox"), 0).unwrap();
+        assert!(expl.is_match());
+        let score = index.search(&term_query("fox")).unwrap().unwrap();
+        assert!((score - expl.value()).abs() < 1e-6);
+        assert!(index.search(&term_query("the")).unwrap().is_some());
+        assert!(index.search(&term_query("cat")).unwrap().is_none());
+
+        assert!(index
+            .search(&phrase_query(&["quick", "brown"]))
+            .unwrap()
+            .is_some());
+        assert!(index
+            .search(&phrase_query(&["brown", "quick"]))
+            .unwrap()
+            .is_none());
+
+        let query = IntPoint::new_range_query("size".into(), 5, 10).unwrap();
+        assert!(index.search(query.as_ref()).unwrap().is_some());
+        let query = IntPoint::new_range_query("size".into(), 8, 10).unwrap();
+        assert!(index.search(query.as_ref()).unwrap().is_none());
+
+        let dv = index.get_numeric_doc_values("price").unwrap();
+        assert_eq!(dv.get(0).unwrap(), 42);
+        assert!(index.get_numeric_doc_values("content").is_err());
+        assert!(index.norm_values("content").unwrap().is_some());
+        assert!(index.norm_values("price").unwrap().is_none());
+
+        // terms are sorted, with their positions and offsets
+        let terms = LeafReader::terms(&index, "content").unwrap().unwrap();
+        assert_eq!(terms.size().unwrap(), 8);
+        assert_eq!(terms.sum_total_term_freq().unwrap(), 9);
+        let mut iter = terms.iterator().unwrap();
+        assert_eq!(iter.next().unwrap(), Some(b"brown".to_vec()));
+        assert!(iter.seek_exact(b"the").unwrap());
+        assert_eq!(iter.total_term_freq().unwrap(), 2);
+        let mut postings = iter.postings().unwrap();
+        assert_eq!(postings.next().unwrap(), 0);
+        assert_eq!(postings.freq().unwrap(), 2);
+        assert_eq!(postings.next_position().unwrap(), 0);
+        assert_eq!(postings.next_position().unwrap(), 6);
+        assert_eq!(postings.start_offset().unwrap(), 31);
+        assert_eq!(postings.end_offset().unwrap(), 34);
+        assert_eq!(postings.next().unwrap(), NO_MORE_DOCS);
+
+        // a field added twice continues its positions
+        index
+            .add_token_stream("content", whitespace_analyzer()("red fox"))
+            .unwrap();
+        assert!(index
+            .search(&phrase_query(&["dog", "red"]))
+            .unwrap()
+            .is_some());
+        assert!(index.search(&term_query("fox")).unwrap().unwrap() > score);
+
+        index.reset();
+        assert!(index.search(&term_query("fox")).unwrap().is_none());
+        assert!(index.point_values().is_none());
+    }
+}
@@ -19,6 +19,7 @@ use error::Result;
 
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
+use std::ops::DerefMut;
 use std::sync::Arc;
 
 /// Utility trait to help merging documents from sub-readers according to either simple
@@ -264,13 +265,14 @@ impl<T: DocIdMergerSub> DocIdMerger for SortedDocIdMerger<T> {
                 self.queue.pop();
                 break;
             }
-            let top = self.queue.peek_mut().unwrap();
+            let mut top = self.queue.peek_mut().unwrap();
             let mapped_doc_id = top.sub().base().doc_map.get(doc_id)?;
             if mapped_doc_id == -1 {
                 // doc was deleted
                 continue;
             } else {
-                top.sub().base_mut().mapped_doc_id = mapped_doc_id;
+                // the top must be borrowed mutably so that it is sifted down on drop
+                top.deref_mut().sub().base_mut().mapped_doc_id = mapped_doc_id;
                 break;
             }
         }
@@ -326,3 +328,74 @@ impl<T: DocIdMergerSub> PartialOrd for DocIdMergerSubRef<T> {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::util::packed::{
+        PackedLongValuesBuilder, PackedLongValuesBuilderType, COMPACT, DEFAULT_PAGE_SIZE,
+    };
+    use core::util::MatchAllBits;
+
+    struct TestSub {
+        doc: DocId,
+        max_doc: DocId,
+        base: DocIdMergerSubBase,
+    }
+
+    impl TestSub {
+        fn new(mapped_doc_ids: &[i64]) -> Self {
+            let mut builder = PackedLongValuesBuilder::new(
+                DEFAULT_PAGE_SIZE,
+                COMPACT,
+                PackedLongValuesBuilderType::Delta,
+            );
+            for doc_id in mapped_doc_ids {
+                builder.add(*doc_id);
+            }
+            let live_docs = Arc::new(MatchAllBits::new(mapped_doc_ids.len()));
+            let doc_map = LiveDocsDocMap::new(live_docs, builder.build(), 0);
+            TestSub {
+                doc: -1,
+                max_doc: mapped_doc_ids.len() as DocId,
+                base: DocIdMergerSubBase::new(Arc::new(doc_map)),
+            }
+        }
+    }
+
+    impl DocIdMergerSub for TestSub {
+        fn next_doc(&mut self) -> Result<DocId> {
+            self.doc += 1;
+            if self.doc >= self.max_doc {
+                self.doc = NO_MORE_DOCS;
+            }
+            Ok(self.doc)
+        }
+
+        fn base(&self) -> &DocIdMergerSubBase {
+            &self.base
+        }
+
+        fn base_mut(&mut self) -> &mut DocIdMergerSubBase {
+            &mut self.base
+        }
+
+        fn reset(&mut self) {
+            self.doc = -1;
+            self.base.reset();
+        }
+    }
+
+    #[test]
+    fn test_sorted_doc_id_merger() {
+        // the docs of both subs are interleaved in the merged segment
+        let subs = vec![TestSub::new(&[0, 2, 3, 6]), TestSub::new(&[1, 4, 5])];
+        let mut merger = doc_id_merger_of(subs, true).unwrap();
+        let mut mapped_doc_ids = vec![];
+        while let Some(sub) = merger.next().unwrap() {
+            mapped_doc_ids.push(sub.base().mapped_doc_id);
+        }
+        assert_eq!(mapped_doc_ids, vec![0, 1, 2, 3, 4, 5, 6]);
+    }
+}
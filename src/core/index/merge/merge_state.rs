@@ -88,6 +88,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     pub fn new(
         seg_readers: Vec<Arc<SegmentReader<D, C>>>,
         segment_info: &SegmentInfo<D, C>,
+        parent_field: Option<&str>,
     ) -> Result<Self> {
         let num_readers = seg_readers.len();
 
@@ -99,14 +100,19 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
         let readers: Vec<ReaderWrapperEnum<D, C>> = Self::maybe_sort_readers(
             seg_readers,
             segment_info,
+            parent_field,
             &mut leaf_doc_maps,
             &mut needs_index_sort,
         )?;
-        let doc_maps =
-            Self::build_doc_maps(&readers, segment_info.index_sort(), &mut needs_index_sort)?
-                .into_iter()
-                .map(Arc::new)
-                .collect();
+        let doc_maps = Self::build_doc_maps(
+            &readers,
+            segment_info.index_sort(),
+            parent_field,
+            &mut needs_index_sort,
+        )?
+        .into_iter()
+        .map(Arc::new)
+        .collect();
         let mut max_docs = Vec::with_capacity(num_readers);
         let mut fields_producers = Vec::with_capacity(num_readers);
         let mut norms_producers = Vec::with_capacity(num_readers);
@@ -167,6 +173,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     fn maybe_sort_readers(
         seg_readers: Vec<Arc<SegmentReader<D, C>>>,
         segment_info: &SegmentInfo<D, C>,
+        parent_field: Option<&str>,
         leaf_doc_maps: &mut Vec<MergeDocMap>,
         needs_index_sort: &mut bool,
     ) -> Result<Vec<ReaderWrapperEnum<D, C>>> {
@@ -178,7 +185,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
             return Ok(readers);
         }
         let index_sort = segment_info.index_sort.as_ref().unwrap().clone();
-        let sorter = Sorter::new(index_sort);
+        let sorter = Sorter::with_parent_field(index_sort, parent_field.map(String::from));
 
         let mut readers = Vec::with_capacity(seg_readers.len());
         for leaf in seg_readers {
@@ -224,11 +231,12 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
     fn build_doc_maps<D1: Directory>(
         readers: &[ReaderWrapperEnum<D1, C>],
         index_sort: Option<&Sort>,
+        parent_field: Option<&str>,
         needs_index_sort: &mut bool,
     ) -> Result<Vec<LiveDocsDocMap>> {
         if let Some(sort) = index_sort {
             // do a merge sort of the incoming leaves:
-            let res = MultiSorter::sort(sort, readers)?;
+            let res = MultiSorter::sort(sort, readers, parent_field)?;
             if !res.is_empty() {
                 *needs_index_sort = true;
                 return Ok(res);
@@ -54,12 +54,13 @@ where
         directory: Arc<DW>,
         field_numbers: FieldNumbersRef,
         context: IOContext,
+        parent_field: Option<&str>,
     ) -> Result<Self> {
         if !context.is_merge() {
             bail!(IllegalArgument("IOContext should be merge!".into()));
         }
         let codec = segment_info.codec().clone();
-        let merge_state = MergeState::new(readers, segment_info)?;
+        let merge_state = MergeState::new(readers, segment_info, parent_field)?;
         let field_infos_builder = FieldInfosBuilder::new(field_numbers);
         Ok(SegmentMerger {
             directory,
@@ -11,6 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod memory;
 pub mod merge;
 pub mod reader;
 pub mod writer;
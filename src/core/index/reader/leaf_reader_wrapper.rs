@@ -1231,6 +1231,14 @@ impl<P: PointValues + 'static> PointValues for SortingPointValues<P> {
         self.point_values.intersect(field_name, &mut sort_visitor)
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &impl IntersectVisitor,
+    ) -> Result<i64> {
+        self.point_values.estimate_point_count(field_name, visitor)
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         self.point_values.min_packed_value(field_name)
     }
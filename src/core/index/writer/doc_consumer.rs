@@ -22,17 +22,18 @@ use core::codec::points::PointValuesWriter;
 use core::codec::points::{PointsFormat, PointsWriter};
 use core::codec::postings::{
     FreqProxTermsWriter, FreqProxTermsWriterPerField, TermsHash, TermsHashPerField,
+    CUSTOM_TERM_FREQS_KEY,
 };
 use core::codec::segment_infos::{SegmentReadState, SegmentWriteState};
 use core::codec::stored_fields::StoredFieldsConsumer;
 use core::codec::term_vectors::TermVectorsConsumer;
 use core::codec::Codec;
-use core::doc::{DocValuesType, FieldType, Fieldable, IndexOptions};
+use core::doc::{DocValuesType, FieldType, Fieldable, IndexOptions, NumericDocValuesField};
 use core::index::merge::MergePolicy;
 use core::index::writer::{index_writer, DocState, DocumentsWriterPerThread};
 use core::store::directory::Directory;
 use core::store::IOContext;
-use core::util::{BytesRef, DocId, VariantValue};
+use core::util::{BytesRef, DocId, FixedBitSet, VariantValue};
 
 use core::search::similarity::BM25Similarity;
 
@@ -558,6 +559,26 @@ where
         None
     }
 
+    /// The documents marked with the parent field, if one is configured.
+    fn parent_docs(&mut self) -> Result<Option<FixedBitSet>> {
+        let config = Arc::clone(&self.doc_writer().index_writer_config);
+        if let Some(idx) = config
+            .parent_field()
+            .and_then(|f| self.get_per_field_index(f))
+        {
+            if let Some(DocValuesWriterEnum::Numeric(ref writer)) =
+                self.field_hash[idx].doc_values_writer
+            {
+                let docs = writer.docs_with_field();
+                return Ok(Some(FixedBitSet::copy_from(
+                    docs.bits.clone(),
+                    docs.num_bits,
+                )?));
+            }
+        }
+        Ok(None)
+    }
+
     fn maybe_sort_segment<DW: Directory>(
         &mut self,
         state: &SegmentWriteState<D, DW, C>,
@@ -588,7 +609,8 @@ where
                     }
                 }
             }
-            Sorter::sort_by_comps(state.segment_info.max_doc, comparators)
+            let parents = self.parent_docs()?;
+            Sorter::sort_by_comps(state.segment_info.max_doc, comparators, parents)
                 .map(|map_opt| map_opt.map(Arc::new))
         } else {
             Ok(None)
@@ -616,6 +638,16 @@ where
         let field_gen = self.next_field_gen;
         self.next_field_gen += 1;
 
+        let config = Arc::clone(&self.doc_writer().index_writer_config);
+        if let Some(parent_field) = config.parent_field() {
+            if doc.iter().any(|f| f.name() == parent_field) {
+                bail!(IllegalArgument(format!(
+                    "'{}' is reserved as the parent field and can't be used in documents",
+                    parent_field
+                )));
+            }
+        }
+
         // NOTE: we need two passes here, in case there are
         // multi-valued fields, because we must process all
         // instances of a given field at once, since the
@@ -628,6 +660,13 @@ where
         for field in doc {
             field_count = unsafe { self.process_field(field, doc_state, field_gen, field_count)? };
         }
+        if doc_state.is_parent {
+            if let Some(parent_field) = config.parent_field() {
+                let mut marker = NumericDocValuesField::new(parent_field, 1);
+                field_count =
+                    unsafe { self.process_field(&mut marker, doc_state, field_gen, field_count)? };
+            }
+        }
         // Finish each indexed field name seen in the document:
         for i in 0..field_count {
             let idx = self.fields[i];
@@ -940,9 +979,32 @@ impl<T: TermsHashPerField> PerField<T> {
                 self.invert_state.last_start_offset = start_offset as i32;
             }
 
-            self.invert_state.length += 1;
-            if self.invert_state.length < 0 {
-                bail!(IllegalArgument("too many tokens in field".into()));
+            let term_freq = token_stream.token().term_frequency;
+            if term_freq != 1 {
+                if term_freq == 0 || term_freq > i32::max_value() as u32 {
+                    bail!(IllegalArgument(format!(
+                        "field \"{}\": term frequency must be in [1, {}], got {}",
+                        &self.field_info().name,
+                        i32::max_value(),
+                        term_freq
+                    )));
+                }
+                if index_options != IndexOptions::DocsAndFreqs
+                    || field.field_type().store_term_vectors
+                {
+                    bail!(IllegalArgument(format!(
+                        "field \"{}\": custom term frequencies can only be indexed with \
+                         IndexOptions::DocsAndFreqs and without term vectors",
+                        &self.field_info().name
+                    )));
+                }
+                // keeps the postings writer from capping the frequencies of this field
+                consumer.field_infos.by_name[&self.field_info().name]
+                    .put_attribute(CUSTOM_TERM_FREQS_KEY.to_string(), "true".to_string());
+            }
+            match self.invert_state.length.checked_add(term_freq as i32) {
+                Some(length) => self.invert_state.length = length,
+                None => bail!(IllegalArgument("too many tokens in field".into())),
             }
 
             // If we hit an exception in here, we abort
@@ -49,6 +49,8 @@ pub struct DocState {
     // pub similarity: Option<Box<Similarity>>,
     pub doc_id: DocId,
     // pub doc: Vec<Box<dyn Fieldable>>,
+    /// Whether the document is the last one of its block, see `IndexWriterConfig#parent_field`
+    pub is_parent: bool,
 }
 
 impl DocState {
@@ -203,6 +205,7 @@ where
         self.reserve_one_doc()?;
         // self.doc_state.doc = doc;
         self.doc_state.doc_id = self.num_docs_in_ram as i32;
+        self.doc_state.is_parent = true;
         // self.doc_state.analyzer = analyzer;
 
         // Even on exception, the document is still added (but marked
@@ -259,6 +262,7 @@ where
         doc_count: &mut i32,
         all_docs_indexed: &mut bool,
     ) -> Result<u64> {
+        let num_docs = docs.len() as i32;
         for mut doc in docs {
             // Even on exception, the document is still added (but marked
             // deleted), so we don't need to un-reserve at that point.
@@ -270,6 +274,7 @@ where
             // self.doc_state.doc = doc;
             self.doc_state.doc_id = self.num_docs_in_ram as i32;
             *doc_count += 1;
+            self.doc_state.is_parent = *doc_count == num_docs;
 
             let res = unsafe {
                 self.consumer
@@ -2988,6 +2988,7 @@ where
             Arc::clone(&dir_wrapper),
             FieldNumbersRef::new(Arc::clone(&index_writer.writer.global_field_numbers)),
             context,
+            index_writer.writer.config.parent_field(),
         )?;
         merge.rate_limiter.check_abort()?;
         merge.merge_start_time.write(Some(SystemTime::now()));
@@ -65,6 +65,9 @@ pub struct IndexWriterConfig<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
     pub merge_policy: MP,
     pub merge_scheduler: MS,
     pub index_sort: Option<Sort>,
+    /// The name of the doc values field marking the last document of each block
+    /// added with `IndexWriter#add_documents`, see `#parent_field()`.
+    pub parent_field: Option<String>,
     /// True if readers should be pooled.
     pub reader_pooling: bool,
     pub open_mode: OpenMode,
@@ -91,6 +94,7 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
             merge_policy,
             merge_scheduler,
             index_sort: None,
+            parent_field: None,
             reader_pooling: true,
             open_mode: OpenMode::CreateOrAppend,
             codec,
@@ -122,6 +126,17 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         self.index_sort.as_ref()
     }
 
+    /// The parent field, if any. When set, the writer adds a numeric doc value to
+    /// this field on the last document of every block, and on every document
+    /// added on its own, so that a block of child documents followed by their
+    /// parent stays contiguous when segments are sorted with `#index_sort()`.
+    ///
+    /// Without a parent field, the documents of a block are sorted on their own like
+    /// any other document. Documents must not use the parent field themselves.
+    pub fn parent_field(&self) -> Option<&str> {
+        self.parent_field.as_deref()
+    }
+
     pub fn index_deletion_policy(&self) -> KeepOnlyLastCommitDeletionPolicy {
         KeepOnlyLastCommitDeletionPolicy::default()
     }
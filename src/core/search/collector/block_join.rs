@@ -0,0 +1,164 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::reader::{IndexReader, LeafReaderContext};
+use core::search::collector::{Collector, SearchCollector, TopDocsCollector, TopDocsLeafCollector};
+use core::search::query::{parents_bit_set, ToParentBlockJoinQuery};
+use core::search::scorer::Scorer;
+use core::search::searcher::IndexSearcher;
+use core::search::sort_field::ScoreDoc;
+use core::search::DocIterator;
+use core::util::DocId;
+
+use error::Result;
+
+use std::cmp::Ordering;
+
+/// A parent hit of a `ToParentBlockJoinQuery`, with its top matching children.
+pub struct ParentHits {
+    pub parent: ScoreDoc,
+    /// The number of matching children of the parent.
+    pub total_children: usize,
+    /// The top matching children, by descending score.
+    pub children: Vec<ScoreDoc>,
+}
+
+/// Collects the top parent hits of a `ToParentBlockJoinQuery`, and then returns
+/// the top matching children of each of them, the "inner hits".
+///
+/// The parents are collected by score like `TopDocsCollector`, and the children
+/// are found once the search is done by `#inner_hits`, which runs the child query
+/// of the join on the blocks of the top parents only.
+pub struct ToParentBlockJoinCollector {
+    collector: TopDocsCollector,
+}
+
+impl ToParentBlockJoinCollector {
+    pub fn new(num_parents: usize) -> ToParentBlockJoinCollector {
+        ToParentBlockJoinCollector {
+            collector: TopDocsCollector::new(num_parents),
+        }
+    }
+
+    /// Returns the collected parents by descending score, with at most
+    /// `max_children` of their children matching the child query of `query`,
+    /// which must be the query that was searched.
+    pub fn inner_hits<C: Codec, IS: IndexSearcher<C>>(
+        &mut self,
+        searcher: &IS,
+        query: &ToParentBlockJoinQuery<C>,
+        max_children: usize,
+    ) -> Result<Vec<ParentHits>> {
+        let mut hits: Vec<ParentHits> = self
+            .collector
+            .top_docs()
+            .score_docs()
+            .iter()
+            .map(|hit| ParentHits {
+                parent: ScoreDoc::new(hit.doc_id(), hit.score()),
+                total_children: 0,
+                children: vec![],
+            })
+            .collect();
+        if hits.is_empty() {
+            return Ok(hits);
+        }
+
+        let child_weight = searcher.create_normalized_weight(query.child_query(), true)?;
+        let parents_weight = searcher.create_weight(query.parents_filter(), false)?;
+        let mut by_doc: Vec<usize> = (0..hits.len()).collect();
+        by_doc.sort_by_key(|&i| hits[i].parent.doc);
+
+        let mut next = 0;
+        for leaf in searcher.reader().leaves() {
+            let end = leaf.doc_base + leaf.reader.max_doc();
+            let start = next;
+            while next < by_doc.len() && hits[by_doc[next]].parent.doc < end {
+                next += 1;
+            }
+            if start == next {
+                continue;
+            }
+            let parents = match parents_bit_set(parents_weight.as_ref(), &leaf)? {
+                Some(parents) => parents,
+                None => continue,
+            };
+            let mut child_scorer = match child_weight.create_scorer(&leaf)? {
+                Some(scorer) => scorer,
+                None => continue,
+            };
+            for &i in &by_doc[start..next] {
+                let parent_doc = hits[i].parent.doc - leaf.doc_base;
+                let first_child = if parent_doc == 0 {
+                    0
+                } else {
+                    parents.prev_set_bit(parent_doc as usize - 1) + 1
+                };
+                let mut doc = child_scorer.doc_id();
+                if doc < first_child {
+                    doc = child_scorer.advance(first_child)?;
+                }
+                let mut children = vec![];
+                while doc < parent_doc {
+                    children.push(ScoreDoc::new(doc + leaf.doc_base, child_scorer.score()?));
+                    doc = child_scorer.next()?;
+                }
+                children.sort_by(|a, b| {
+                    b.score
+                        .partial_cmp(&a.score)
+                        .unwrap_or(Ordering::Equal)
+                        .then(a.doc.cmp(&b.doc))
+                });
+                hits[i].total_children = children.len();
+                children.truncate(max_children);
+                hits[i].children = children;
+            }
+        }
+        Ok(hits)
+    }
+}
+
+impl SearchCollector for ToParentBlockJoinCollector {
+    type LC = TopDocsLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.collector.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        self.collector.support_parallel()
+    }
+
+    fn init_parallel(&mut self) {
+        self.collector.init_parallel()
+    }
+
+    fn leaf_collector<C: Codec>(&self, reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
+        self.collector.leaf_collector(reader)
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        self.collector.finish_parallel()
+    }
+}
+
+impl Collector for ToParentBlockJoinCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.collector.collect(doc, scorer)
+    }
+}
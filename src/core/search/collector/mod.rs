@@ -27,6 +27,10 @@ mod chain;
 
 pub use self::chain::*;
 
+mod block_join;
+
+pub use self::block_join::*;
+
 use error::Result;
 
 use core::codec::Codec;
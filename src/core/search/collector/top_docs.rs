@@ -19,11 +19,11 @@ use std::usize;
 
 use core::codec::Codec;
 use core::index::reader::LeafReaderContext;
-use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{self, Collector, ParallelLeafCollector, SearchCollector};
 use core::search::scorer::Scorer;
 use core::search::sort_field::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
 use core::util::DocId;
-use error::{ErrorKind::IllegalState, Result, ResultExt};
+use error::{ErrorKind, ErrorKind::IllegalState, Result, ResultExt};
 
 struct TopDocsBaseCollector {
     /// The priority queue which holds the top documents. Note that different
@@ -38,16 +38,21 @@ struct TopDocsBaseCollector {
     total_hits: usize,
 
     cur_doc_base: DocId,
+
+    /// Whether to stop collecting a segment once the scorer's max score can't
+    /// compete with the collected documents anymore.
+    early_termination: bool,
 }
 
 impl TopDocsBaseCollector {
-    fn new(estimated_hits: usize) -> Self {
+    fn new(estimated_hits: usize, early_termination: bool) -> Self {
         let pq = BinaryHeap::with_capacity(estimated_hits);
         Self {
             pq,
             estimated_hits,
             total_hits: 0,
             cur_doc_base: 0,
+            early_termination,
         }
     }
 
@@ -90,6 +95,18 @@ impl Collector for TopDocsBaseCollector {
         self.add_doc(id, score);
         self.total_hits += 1;
 
+        if self.early_termination && self.pq.len() >= self.estimated_hits {
+            // documents of equal score are only replaced by those of a smaller id,
+            // so nothing left in this segment can enter the queue
+            if let Some(min) = self.pq.peek() {
+                if min.score >= scorer.max_score() {
+                    bail!(ErrorKind::Collector(
+                        collector::ErrorKind::LeafCollectionTerminated
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -106,13 +123,24 @@ pub struct TopDocsCollector {
 
 impl TopDocsCollector {
     pub fn new(estimated_hits: usize) -> Self {
-        let base = TopDocsBaseCollector::new(estimated_hits);
+        let base = TopDocsBaseCollector::new(estimated_hits, false);
         Self {
             base,
             channel: None,
         }
     }
 
+    /// Skips the remaining documents of a segment once the top docs are known
+    /// to beat the `Scorer::max_score` of the query, which only helps queries
+    /// whose max score is bounded such as a `FeatureQuery`, or a `BooleanQuery`
+    /// of such queries.
+    ///
+    /// The returned `total_hits` is then a lower bound of the number of hits.
+    pub fn with_early_termination(mut self, early_termination: bool) -> Self {
+        self.base.early_termination = early_termination;
+        self
+    }
+
     /// Returns the top docs that were collected by this collector.
     pub fn top_docs(&mut self) -> TopDocs {
         self.base.top_docs()
@@ -146,7 +174,8 @@ impl SearchCollector for TopDocsCollector {
         &self,
         reader: &LeafReaderContext<'_, C>,
     ) -> Result<TopDocsLeafCollector> {
-        let mut collector = TopDocsBaseCollector::new(self.base.estimated_hits);
+        let mut collector =
+            TopDocsBaseCollector::new(self.base.estimated_hits, self.base.early_termination);
         collector.cur_doc_base = reader.doc_base;
         Ok(TopDocsLeafCollector::new(
             collector,
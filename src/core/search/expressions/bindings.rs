@@ -0,0 +1,308 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::{NumericDocValues, SortedNumericDocValues};
+use core::codec::Codec;
+use core::doc::DocValuesType;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::expressions::Expression;
+use core::search::sort_field::{SortFieldType, SortedNumericSelectorType};
+use core::util::{sortable_double_bits, sortable_float_bits, DocId};
+
+use error::{ErrorKind, Result};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The name of the variable bound to the score of the document, unless bound otherwise.
+pub const SCORE_VARIABLE: &str = "_score";
+
+/// Where the value of an expression variable comes from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Binding {
+    /// The score of the document
+    Score,
+    /// The `NumericDocValues` of a field
+    Numeric {
+        field: String,
+        field_type: SortFieldType,
+    },
+    /// The value of the `SortedNumericDocValues` of a field chosen by `selector`
+    SortedNumeric {
+        field: String,
+        field_type: SortFieldType,
+        selector: SortedNumericSelectorType,
+    },
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Score => write!(f, "score"),
+            Binding::Numeric { field, .. } => write!(f, "doc['{}'].value", field),
+            Binding::SortedNumeric {
+                field, selector, ..
+            } => write!(f, "doc['{}'].{:?}", field, selector),
+        }
+    }
+}
+
+fn check_numeric_type(field_type: SortFieldType) -> Result<()> {
+    match field_type {
+        SortFieldType::Int | SortFieldType::Long | SortFieldType::Float | SortFieldType::Double => {
+            Ok(())
+        }
+        _ => bail!(ErrorKind::IllegalArgument(format!(
+            "numeric type expected for expression variables, got {:?}",
+            field_type
+        ))),
+    }
+}
+
+/// Binds the variables of `Expression`s to the score or to doc values.
+///
+/// `_score` is bound to the score of the document unless bound explicitly.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Bindings {
+    bindings: BTreeMap<String, Binding>,
+}
+
+impl Bindings {
+    pub fn add_score(&mut self, name: &str) {
+        self.bindings.insert(name.to_string(), Binding::Score);
+    }
+
+    pub fn add_numeric(
+        &mut self,
+        name: &str,
+        field: &str,
+        field_type: SortFieldType,
+    ) -> Result<()> {
+        check_numeric_type(field_type)?;
+        self.bindings.insert(
+            name.to_string(),
+            Binding::Numeric {
+                field: field.to_string(),
+                field_type,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn add_sorted_numeric(
+        &mut self,
+        name: &str,
+        field: &str,
+        field_type: SortFieldType,
+        selector: SortedNumericSelectorType,
+    ) -> Result<()> {
+        check_numeric_type(field_type)?;
+        self.bindings.insert(
+            name.to_string(),
+            Binding::SortedNumeric {
+                field: field.to_string(),
+                field_type,
+                selector,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Binding> {
+        match self.bindings.get(name) {
+            Some(binding) => Some(binding),
+            None if name == SCORE_VARIABLE => Some(&Binding::Score),
+            None => None,
+        }
+    }
+
+    /// Resolve all the variables of `expression`, failing if one of them is not bound.
+    pub fn bind(&self, expression: Expression) -> Result<ExpressionValueSource> {
+        let mut bindings = Vec::with_capacity(expression.variables().len());
+        for variable in expression.variables() {
+            match self.get(variable) {
+                Some(binding) => bindings.push(binding.clone()),
+                None => bail!(ErrorKind::IllegalArgument(format!(
+                    "invalid expression '{}': unbound variable '{}'",
+                    expression, variable
+                ))),
+            }
+        }
+        Ok(ExpressionValueSource {
+            expression,
+            bindings,
+        })
+    }
+}
+
+/// An `Expression` with all its variables bound, which computes a value per document.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExpressionValueSource {
+    expression: Expression,
+    // the binding of each variable of the expression
+    bindings: Vec<Binding>,
+}
+
+impl ExpressionValueSource {
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn needs_scores(&self) -> bool {
+        self.bindings.iter().any(|b| *b == Binding::Score)
+    }
+
+    pub fn leaf_values<C: Codec>(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<LeafExpressionValues> {
+        let mut variables = Vec::with_capacity(self.bindings.len());
+        for binding in &self.bindings {
+            let variable = match binding {
+                Binding::Score => LeafVariable::Score,
+                Binding::Numeric { field, field_type } => match reader.reader.field_info(field) {
+                    Some(fi) if fi.doc_values_type == DocValuesType::Numeric => {
+                        LeafVariable::Numeric(
+                            reader.reader.get_numeric_doc_values(field)?,
+                            *field_type,
+                        )
+                    }
+                    _ => LeafVariable::Missing,
+                },
+                Binding::SortedNumeric {
+                    field,
+                    field_type,
+                    selector,
+                } => match reader.reader.field_info(field) {
+                    Some(fi) if fi.doc_values_type == DocValuesType::SortedNumeric => {
+                        LeafVariable::SortedNumeric(
+                            reader.reader.get_sorted_numeric_doc_values(field)?,
+                            *field_type,
+                            *selector,
+                        )
+                    }
+                    _ => LeafVariable::Missing,
+                },
+            };
+            variables.push(variable);
+        }
+        Ok(LeafExpressionValues {
+            expression: self.expression.clone(),
+            values: vec![0.0; variables.len()],
+            variables,
+        })
+    }
+}
+
+impl fmt::Display for ExpressionValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expr({})", &self.expression)
+    }
+}
+
+enum LeafVariable {
+    Score,
+    Numeric(Box<dyn NumericDocValues>, SortFieldType),
+    SortedNumeric(
+        Box<dyn SortedNumericDocValues>,
+        SortFieldType,
+        SortedNumericSelectorType,
+    ),
+    /// The field has no doc values in the segment
+    Missing,
+}
+
+impl LeafVariable {
+    /// The value of the variable for `doc`, 0 if the document has no value.
+    fn value(&mut self, doc: DocId, score: f32) -> Result<f64> {
+        let value = match self {
+            LeafVariable::Score => f64::from(score),
+            LeafVariable::Numeric(values, field_type) => {
+                let raw = values.get_mut(doc)?;
+                match field_type {
+                    SortFieldType::Int => f64::from(raw as i32),
+                    SortFieldType::Float => f64::from(f32::from_bits(raw as u32)),
+                    SortFieldType::Double => f64::from_bits(raw as u64),
+                    _ => raw as f64,
+                }
+            }
+            LeafVariable::SortedNumeric(values, field_type, selector) => {
+                values.set_document(doc)?;
+                let count = values.count();
+                if count == 0 {
+                    return Ok(0.0);
+                }
+                let raw = match selector {
+                    SortedNumericSelectorType::Min => values.value_at(0)?,
+                    SortedNumericSelectorType::Max => values.value_at(count - 1)?,
+                };
+                // sorted numeric floating point values are stored as sortable bits
+                match field_type {
+                    SortFieldType::Int => f64::from(raw as i32),
+                    SortFieldType::Float => {
+                        f64::from(f32::from_bits(sortable_float_bits(raw as i32) as u32))
+                    }
+                    SortFieldType::Double => f64::from_bits(sortable_double_bits(raw) as u64),
+                    _ => raw as f64,
+                }
+            }
+            LeafVariable::Missing => 0.0,
+        };
+        Ok(value)
+    }
+}
+
+/// The values of an `ExpressionValueSource` for the documents of a segment.
+pub struct LeafExpressionValues {
+    expression: Expression,
+    variables: Vec<LeafVariable>,
+    // reused buffer of the values of the variables
+    values: Vec<f64>,
+}
+
+impl LeafExpressionValues {
+    /// The value of the expression for `doc`, `score` being the value of the variables
+    /// bound to the score.
+    pub fn value(&mut self, doc: DocId, score: f32) -> Result<f64> {
+        for (variable, value) in self.variables.iter_mut().zip(self.values.iter_mut()) {
+            *value = variable.value(doc, score)?;
+        }
+        Ok(self.expression.evaluate(&self.values))
+    }
+
+    /// The values of the variables for `doc`, in the order of `Expression::variables`.
+    pub fn variable_values(&mut self, doc: DocId, score: f32) -> Result<&[f64]> {
+        self.value(doc, score)?;
+        Ok(&self.values)
+    }
+
+    pub fn explain(&mut self, doc: DocId, score: f32) -> Result<Explanation> {
+        let value = self.value(doc, score)?;
+        let details = self
+            .expression
+            .variables()
+            .iter()
+            .zip(self.values.iter())
+            .map(|(name, value)| {
+                Explanation::new(true, *value as f32, format!("variable {}", name), vec![])
+            })
+            .collect();
+        Ok(Explanation::new(
+            true,
+            value as f32,
+            format!("expression {}, computed from:", &self.expression),
+            details,
+        ))
+    }
+}
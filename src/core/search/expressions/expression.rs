@@ -0,0 +1,274 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::expressions::parser::{BinaryOp, Node, Parser, UnaryOp};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+use std::sync::Arc;
+
+type CompiledFn = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+type MathFn = fn(&[f64]) -> f64;
+
+fn bool_value(v: bool) -> f64 {
+    if v {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// The functions which can be called by an expression, with their arity.
+fn function(name: &str) -> Option<(usize, MathFn)> {
+    let func: (usize, MathFn) = match name {
+        "abs" => (1, |a| a[0].abs()),
+        "acos" => (1, |a| a[0].acos()),
+        "acosh" => (1, |a| a[0].acosh()),
+        "asin" => (1, |a| a[0].asin()),
+        "asinh" => (1, |a| a[0].asinh()),
+        "atan" => (1, |a| a[0].atan()),
+        "atan2" => (2, |a| a[0].atan2(a[1])),
+        "atanh" => (1, |a| a[0].atanh()),
+        "cbrt" => (1, |a| a[0].cbrt()),
+        "ceil" => (1, |a| a[0].ceil()),
+        "cos" => (1, |a| a[0].cos()),
+        "cosh" => (1, |a| a[0].cosh()),
+        "exp" => (1, |a| a[0].exp()),
+        "floor" => (1, |a| a[0].floor()),
+        "ln" | "log" => (1, |a| a[0].ln()),
+        "log10" => (1, |a| a[0].log10()),
+        "logn" => (2, |a| a[1].log(a[0])),
+        "max" => (2, |a| a[0].max(a[1])),
+        "min" => (2, |a| a[0].min(a[1])),
+        "pow" => (2, |a| a[0].powf(a[1])),
+        "sin" => (1, |a| a[0].sin()),
+        "sinh" => (1, |a| a[0].sinh()),
+        "sqrt" => (1, |a| a[0].sqrt()),
+        "tan" => (1, |a| a[0].tan()),
+        "tanh" => (1, |a| a[0].tanh()),
+        _ => {
+            return None;
+        }
+    };
+    Some(func)
+}
+
+/// A compiled ranking formula, such as `_score * log(1 + popularity) + 0.1 * recency_days`.
+///
+/// The syntax is a subset of javascript, see `Parser`. The formula is compiled to a
+/// tree of closures once, the variables being resolved to slots of the value array
+/// given to `evaluate`, in the order of `variables`. The variables are given values
+/// by binding the expression to the doc values of fields with `Bindings`.
+///
+/// The available functions are `abs`, `acos`, `acosh`, `asin`, `asinh`, `atan`,
+/// `atan2`, `atanh`, `cbrt`, `ceil`, `cos`, `cosh`, `exp`, `floor`, `ln` (or `log`),
+/// `log10`, `logn(base, x)`, `max`, `min`, `pow`, `sin`, `sinh`, `sqrt`, `tan` and `tanh`.
+#[derive(Clone)]
+pub struct Expression {
+    source: String,
+    variables: Vec<String>,
+    function: Arc<CompiledFn>,
+}
+
+impl Expression {
+    pub fn compile(source: &str) -> Result<Expression> {
+        let node = Parser::parse(source)?;
+        let mut variables = vec![];
+        let function = Self::compile_node(node, &mut variables)?;
+        Ok(Expression {
+            source: source.to_string(),
+            variables,
+            function: Arc::new(function),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The names of the variables used by the expression, in the order of
+    /// the values expected by `evaluate`.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn evaluate(&self, values: &[f64]) -> f64 {
+        debug_assert_eq!(values.len(), self.variables.len());
+        (self.function)(values)
+    }
+
+    fn compile_node(node: Node, variables: &mut Vec<String>) -> Result<CompiledFn> {
+        let compiled: CompiledFn = match node {
+            Node::Number(v) => Box::new(move |_| v),
+            Node::Variable(name) => {
+                let slot = match variables.iter().position(|v| *v == name) {
+                    Some(slot) => slot,
+                    None => {
+                        variables.push(name);
+                        variables.len() - 1
+                    }
+                };
+                Box::new(move |values| values[slot])
+            }
+            Node::Unary(op, operand) => {
+                let operand = Self::compile_node(*operand, variables)?;
+                match op {
+                    UnaryOp::Negate => Box::new(move |values| -operand(values)),
+                    UnaryOp::Not => Box::new(move |values| bool_value(operand(values) == 0.0)),
+                }
+            }
+            Node::Binary(op, left, right) => {
+                let l = Self::compile_node(*left, variables)?;
+                let r = Self::compile_node(*right, variables)?;
+                match op {
+                    BinaryOp::Add => Box::new(move |v| l(v) + r(v)),
+                    BinaryOp::Subtract => Box::new(move |v| l(v) - r(v)),
+                    BinaryOp::Multiply => Box::new(move |v| l(v) * r(v)),
+                    BinaryOp::Divide => Box::new(move |v| l(v) / r(v)),
+                    BinaryOp::Remainder => Box::new(move |v| l(v) % r(v)),
+                    BinaryOp::Less => Box::new(move |v| bool_value(l(v) < r(v))),
+                    BinaryOp::LessEqual => Box::new(move |v| bool_value(l(v) <= r(v))),
+                    BinaryOp::Greater => Box::new(move |v| bool_value(l(v) > r(v))),
+                    BinaryOp::GreaterEqual => Box::new(move |v| bool_value(l(v) >= r(v))),
+                    BinaryOp::Equal => Box::new(move |v| bool_value(l(v) == r(v))),
+                    BinaryOp::NotEqual => Box::new(move |v| bool_value(l(v) != r(v))),
+                    BinaryOp::And => Box::new(move |v| bool_value(l(v) != 0.0 && r(v) != 0.0)),
+                    BinaryOp::Or => Box::new(move |v| bool_value(l(v) != 0.0 || r(v) != 0.0)),
+                }
+            }
+            Node::Conditional(condition, then, otherwise) => {
+                let condition = Self::compile_node(*condition, variables)?;
+                let then = Self::compile_node(*then, variables)?;
+                let otherwise = Self::compile_node(*otherwise, variables)?;
+                Box::new(move |v| {
+                    if condition(v) != 0.0 {
+                        then(v)
+                    } else {
+                        otherwise(v)
+                    }
+                })
+            }
+            Node::Call(name, args, position) => {
+                let (arity, func) = match function(&name) {
+                    Some(f) => f,
+                    None => bail!(ErrorKind::IllegalArgument(format!(
+                        "invalid expression: unrecognized function '{}' at position {}",
+                        name, position
+                    ))),
+                };
+                if args.len() != arity {
+                    bail!(ErrorKind::IllegalArgument(format!(
+                        "invalid expression: function '{}' expects {} arguments, got {} at \
+                         position {}",
+                        name,
+                        arity,
+                        args.len(),
+                        position
+                    )));
+                }
+                let mut compiled_args = Vec::with_capacity(args.len());
+                for arg in args {
+                    compiled_args.push(Self::compile_node(arg, variables)?);
+                }
+                if arity == 1 {
+                    let arg = compiled_args.pop().unwrap();
+                    Box::new(move |v| func(&[arg(v)]))
+                } else {
+                    let (a, b) = (compiled_args.remove(0), compiled_args.remove(0));
+                    Box::new(move |v| func(&[a(v), b(v)]))
+                }
+            }
+        };
+        Ok(compiled)
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Expression {}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expression(source: {}, variables: {:?})",
+            &self.source, &self.variables
+        )
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, values: &[f64]) -> f64 {
+        Expression::compile(source).unwrap().evaluate(values)
+    }
+
+    #[test]
+    fn test_compile_expression() {
+        assert!((eval("1 + 2 * 3", &[]) - 7.0).abs() < 1e-12);
+        assert!((eval("(1 + 2) * 3", &[]) - 9.0).abs() < 1e-12);
+        assert!((eval("10 - 4 - 3", &[]) - 3.0).abs() < 1e-12);
+        assert!((eval("-2 * -3", &[]) - 6.0).abs() < 1e-12);
+        assert!((eval("7 % 4 + 1.5e1", &[]) - 18.0).abs() < 1e-12);
+        assert!((eval("1 < 2 && 3 >= 3", &[]) - 1.0).abs() < 1e-12);
+        assert!((eval("!(1 == 1) || 2 != 2", &[])).abs() < 1e-12);
+        assert!((eval("0 ? 1 : 2 > 1 ? 3 : 4", &[]) - 3.0).abs() < 1e-12);
+        assert!((eval("max(pow(2, 3), logn(2, 4)) + abs(-1)", &[]) - 9.0).abs() < 1e-12);
+
+        let expr =
+            Expression::compile("_score * log(1 + popularity) + 0.1 * recency_days").unwrap();
+        assert_eq!(expr.variables(), &["_score", "popularity", "recency_days"]);
+        let value = expr.evaluate(&[2.0, 9.0, 10.0]);
+        assert!((value - (2.0 * 10.0f64.ln() + 1.0)).abs() < 1e-12);
+
+        let expr = Expression::compile("a + a * doc.b").unwrap();
+        assert_eq!(expr.variables(), &["a", "doc.b"]);
+    }
+
+    #[test]
+    fn test_invalid_expression() {
+        let message = |source: &str| match Expression::compile(source) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("{} should not compile", source),
+        };
+        assert!(message("1 +").contains("unexpected end of expression at position 3"));
+        assert!(message("(1 + 2").contains("position 6"));
+        assert!(message("1 # 2").contains("unexpected character '#' at position 2"));
+        assert!(message("1 2").contains("unexpected number '2' at position 2"));
+        assert!(message("foo(1)").contains("unrecognized function 'foo' at position 0"));
+        assert!(message("1 + max(1)").contains("expects 2 arguments, got 1 at position 4"));
+        assert!(message("a ? b").contains("position 5"));
+
+        let negations = "-".repeat(100_000) + "1";
+        assert!(message(&negations).contains("expression nested too deeply at position 257"));
+        let parens = "(".repeat(100_000) + "1" + &")".repeat(100_000);
+        assert!(message(&parens).contains("expression nested too deeply"));
+        let calls = "abs(".repeat(1_000) + "1" + &")".repeat(1_000);
+        assert!(message(&calls).contains("expression nested too deeply"));
+        assert!((eval(&("-".repeat(200) + "1"), &[]) - 1.0).abs() < 1e-12);
+    }
+}
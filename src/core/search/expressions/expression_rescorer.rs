@@ -0,0 +1,370 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::reader::{IndexReader, LeafReaderContext};
+use core::search::explanation::Explanation;
+use core::search::expressions::{ExpressionValueSource, LeafExpressionValues};
+use core::search::scorer::{QueryRescorer, RescoreRequest, Rescorer};
+use core::search::searcher::IndexSearcher;
+use core::search::sort_field::{ScoreDocHit, TopDocs};
+use core::util::{DocId, VariantValue};
+
+use error::{ErrorKind, Result};
+
+use std::collections::HashMap;
+
+/// A `Rescorer` which computes the secondary score of the top hits with an expression,
+/// the variables bound to the score taking the first pass score of the hit.
+///
+/// The first pass and expression scores are combined according to the weights and the
+/// mode of the `RescoreRequest`, whose query is not used.
+pub struct ExpressionRescorer {
+    source: ExpressionValueSource,
+}
+
+impl ExpressionRescorer {
+    pub fn new(source: ExpressionValueSource) -> Self {
+        ExpressionRescorer { source }
+    }
+
+    /// Call `f` with the leaf values and the segment doc id of each hit, the hits
+    /// being sorted by doc id.
+    fn visit_hits<C, F>(
+        &self,
+        readers: &[LeafReaderContext<'_, C>],
+        hits: &mut [ScoreDocHit],
+        mut f: F,
+    ) -> Result<()>
+    where
+        C: Codec,
+        F: FnMut(&mut ScoreDocHit, &mut LeafExpressionValues, DocId) -> Result<()>,
+    {
+        let mut reader_idx = 0;
+        let mut values: Option<LeafExpressionValues> = None;
+        for hit in hits {
+            let doc_id = hit.doc_id();
+            let mut reader_changed = values.is_none();
+            while doc_id >= readers[reader_idx].doc_base() + readers[reader_idx].reader.max_doc() {
+                reader_idx += 1;
+                reader_changed = true;
+            }
+            if reader_changed {
+                values = Some(self.source.leaf_values(&readers[reader_idx])?);
+            }
+            let doc = doc_id - readers[reader_idx].doc_base();
+            f(hit, values.as_mut().unwrap(), doc)?;
+        }
+        Ok(())
+    }
+
+    fn window<C: Codec>(req: &RescoreRequest<C>, top_docs: &TopDocs) -> Vec<ScoreDocHit> {
+        let mut hits = top_docs.score_docs().to_vec();
+        if hits.len() > req.window_size {
+            hits.truncate(req.window_size);
+        }
+        hits.sort_by(ScoreDocHit::order_by_doc);
+        hits
+    }
+}
+
+impl Rescorer for ExpressionRescorer {
+    fn rescore<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        rescore_req: &RescoreRequest<C>,
+        top_docs: &mut TopDocs,
+    ) -> Result<()> {
+        if top_docs.total_hits() == 0 || top_docs.score_docs().is_empty() {
+            return Ok(());
+        }
+
+        let mut hits = Self::window(rescore_req, top_docs);
+        let score_field_index = QueryRescorer::score_field_index(top_docs);
+        let readers = searcher.reader().leaves();
+        self.visit_hits(&readers, &mut hits, |hit, values, doc| {
+            let score = hit.score();
+            let value = values.value(doc, score)? as f32;
+            hit.set_score(QueryRescorer.combine_score(rescore_req, score, true, value));
+            if score_field_index >= 0 {
+                if let ScoreDocHit::Field(ref mut f) = hit {
+                    f.fields[score_field_index as usize] = VariantValue::from(f.score);
+                }
+            }
+            Ok(())
+        })?;
+
+        hits.sort();
+        QueryRescorer.combine_docs(top_docs, hits, rescore_req);
+        Ok(())
+    }
+
+    fn rescore_features<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        rescore_req: &RescoreRequest<C>,
+        top_docs: &mut TopDocs,
+    ) -> Result<Vec<HashMap<String, VariantValue>>> {
+        if top_docs.total_hits() == 0 || top_docs.score_docs().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut hits = Self::window(rescore_req, top_docs);
+        let readers = searcher.reader().leaves();
+        let variables = self.source.expression().variables();
+        let mut result_features = Vec::with_capacity(hits.len());
+        self.visit_hits(&readers, &mut hits, |hit, values, doc| {
+            let mut feature_map = HashMap::new();
+            for (name, value) in variables
+                .iter()
+                .zip(values.variable_values(doc, hit.score())?)
+            {
+                feature_map.insert(name.clone(), VariantValue::from(*value));
+            }
+            feature_map.insert(
+                "previous_score".to_string(),
+                VariantValue::from(hit.score()),
+            );
+            result_features.push(feature_map);
+            Ok(())
+        })?;
+        *top_docs.score_docs_mut() = hits;
+        Ok(result_features)
+    }
+
+    fn explain<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        req: &RescoreRequest<C>,
+        first: Explanation,
+        doc: DocId,
+    ) -> Result<Explanation> {
+        if !first.is_match() {
+            return Ok(Explanation::new(
+                false,
+                0.0f32,
+                "First pass did not match".to_string(),
+                vec![first],
+            ));
+        }
+        for reader in searcher.reader().leaves() {
+            if doc >= reader.doc_base() && doc < reader.doc_base() + reader.reader.max_doc() {
+                let first_value = first.value();
+                let expl = self
+                    .source
+                    .leaf_values(&reader)?
+                    .explain(doc - reader.doc_base(), first_value)?;
+                return Ok(Explanation::new(
+                    true,
+                    QueryRescorer.combine_score(req, first_value, true, expl.value()),
+                    "combined first pass and expression scores of:".to_string(),
+                    vec![first, expl],
+                ));
+            }
+        }
+        bail!(ErrorKind::IllegalArgument(format!(
+            "doc {} is out of the reader bounds",
+            doc
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{
+        Field, FieldType, Fieldable, IndexOptions, NumericDocValuesField,
+        SortedNumericDocValuesField, Term,
+    };
+    use core::search::collector::TopDocsCollector;
+    use core::search::expressions::{Bindings, Expression};
+    use core::search::query::tests::index_fields;
+    use core::search::query::{
+        BoostMode, FilteredFunction, FunctionScoreMode, FunctionScoreQuery, Query, ScoreFunction,
+        TermQuery,
+    };
+    use core::search::scorer::RescoreMode;
+    use core::search::sort_field::{
+        ComparatorValue, ExpressionSortField, FieldComparator, SortField, SortFieldType,
+        SortedNumericSelectorType,
+    };
+    use core::search::DefaultIndexSearcher;
+
+    use std::cmp::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_expressions() {
+        let mut kind_type = FieldType::default();
+        kind_type.index_options = IndexOptions::Docs;
+        kind_type.tokenized = false;
+
+        // (popularity, recency_days)
+        let values: [(Option<i64>, Vec<i64>); 3] = [
+            (Some(9), vec![30, 5]),
+            (Some(99), vec![1]),
+            (None, vec![2, 70]),
+        ];
+        let mut docs = vec![];
+        for (popularity, recency_days) in &values {
+            let mut doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "kind".into(),
+                kind_type.clone(),
+                Some(VariantValue::VString("book".into())),
+                None,
+            ))];
+            if let Some(popularity) = popularity {
+                doc.push(Box::new(NumericDocValuesField::new(
+                    "popularity",
+                    *popularity,
+                )));
+            }
+            for days in recency_days {
+                doc.push(Box::new(SortedNumericDocValuesField::new(
+                    "recency_days",
+                    *days,
+                )));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let term_query = || -> Box<dyn Query<_>> {
+            Box::new(TermQuery::new(
+                Term::new("kind".into(), b"book".to_vec()),
+                1.0,
+                None,
+            ))
+        };
+
+        let mut bindings = Bindings::default();
+        bindings
+            .add_numeric("popularity", "popularity", SortFieldType::Long)
+            .unwrap();
+        bindings
+            .add_sorted_numeric(
+                "recency_days",
+                "recency_days",
+                SortFieldType::Long,
+                SortedNumericSelectorType::Min,
+            )
+            .unwrap();
+        let expression =
+            Expression::compile("_score * log(1 + popularity) + 0.1 * recency_days").unwrap();
+        let source = bindings.bind(expression).unwrap();
+        assert!(source.needs_scores());
+        let expected = |score: f32, doc: usize| -> f32 {
+            let popularity = values[doc].0.unwrap_or(0) as f64;
+            let recency_days = *values[doc].1.iter().min().unwrap() as f64;
+            (f64::from(score) * (1.0 + popularity).ln() + 0.1 * recency_days) as f32
+        };
+
+        let first_pass = || {
+            let mut collector = TopDocsCollector::new(10);
+            searcher
+                .search(term_query().as_ref(), &mut collector)
+                .unwrap();
+            collector.top_docs()
+        };
+        let query_score = first_pass().score_docs()[0].score();
+
+        // as a score function
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![FilteredFunction::new(ScoreFunction::Expression(
+                source.clone(),
+            ))],
+            FunctionScoreMode::Multiply,
+            BoostMode::Replace,
+        );
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let hits = collector.top_docs().score_docs().to_vec();
+        assert_eq!(hits.len(), 3);
+        for hit in &hits {
+            let score = expected(query_score, hit.doc_id() as usize);
+            assert!((hit.score() - score).abs() < 1e-5);
+            let expl = searcher.explain(&query, hit.doc_id()).unwrap();
+            assert!((expl.value() - hit.score()).abs() < 1e-5);
+        }
+
+        // as a rescorer
+        let rescorer = ExpressionRescorer::new(source.clone());
+        let request = RescoreRequest::new(term_query(), 1.0, 2.0, RescoreMode::Total, 2, false);
+        let mut rescored = first_pass();
+        rescorer
+            .rescore(&searcher, &request, &mut rescored)
+            .unwrap();
+        let hits = rescored.score_docs();
+        assert_eq!(hits.len(), 3);
+        assert!(hits[0].score() >= hits[1].score());
+        for hit in hits.iter().take(2) {
+            let score = query_score + 2.0 * expected(query_score, hit.doc_id() as usize);
+            assert!((hit.score() - score).abs() < 1e-5);
+            let first = searcher
+                .explain(term_query().as_ref(), hit.doc_id())
+                .unwrap();
+            let expl = rescorer
+                .explain(&searcher, &request, first, hit.doc_id())
+                .unwrap();
+            assert!((expl.value() - hit.score()).abs() < 1e-5);
+        }
+
+        let mut features_docs = first_pass();
+        let features = rescorer
+            .rescore_features(&searcher, &request, &mut features_docs)
+            .unwrap();
+        assert_eq!(features.len(), 2);
+        for (hit, feature) in features_docs.score_docs().iter().zip(&features) {
+            let doc = hit.doc_id() as usize;
+            assert_eq!(
+                feature["popularity"],
+                VariantValue::from(values[doc].0.unwrap_or(0) as f64)
+            );
+            assert_eq!(feature["previous_score"], VariantValue::from(query_score));
+        }
+
+        // as a sort field
+        let mut bindings = Bindings::default();
+        bindings
+            .add_numeric("popularity", "popularity", SortFieldType::Long)
+            .unwrap();
+        let source = bindings
+            .bind(Expression::compile("-popularity").unwrap())
+            .unwrap();
+        let sort_field = SortField::Expression(ExpressionSortField::new(source, false).unwrap());
+        assert_eq!(sort_field.field(), "-popularity");
+        assert!(!sort_field.needs_scores());
+        let mut comparator = sort_field.get_comparator(3, None);
+        assert_eq!(comparator.get_type(), SortFieldType::Double);
+        for leaf in searcher.reader().leaves() {
+            comparator.get_information_from_reader(&leaf).unwrap();
+            for doc in 0..leaf.reader.max_doc() {
+                let slot = (leaf.doc_base() + doc) as usize;
+                comparator.copy(slot, ComparatorValue::Doc(doc)).unwrap();
+            }
+        }
+        assert_eq!(comparator.value(1), VariantValue::Double(-99.0));
+        assert_eq!(comparator.compare(0, 1), Ordering::Greater);
+        assert_eq!(comparator.compare(2, 0), Ordering::Greater);
+
+        let score_source = Bindings::default()
+            .bind(Expression::compile("_score * 2").unwrap())
+            .unwrap();
+        assert!(ExpressionSortField::new(score_source, false).is_err());
+        assert!(Bindings::default()
+            .bind(Expression::compile("popularity").unwrap())
+            .is_err());
+    }
+}
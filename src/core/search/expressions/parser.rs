@@ -0,0 +1,342 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::{ErrorKind, Result};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn from_symbol(symbol: &str) -> Option<(BinaryOp, u8)> {
+        let op = match symbol {
+            "||" => (BinaryOp::Or, 1),
+            "&&" => (BinaryOp::And, 2),
+            "==" => (BinaryOp::Equal, 3),
+            "!=" => (BinaryOp::NotEqual, 3),
+            "<" => (BinaryOp::Less, 4),
+            "<=" => (BinaryOp::LessEqual, 4),
+            ">" => (BinaryOp::Greater, 4),
+            ">=" => (BinaryOp::GreaterEqual, 4),
+            "+" => (BinaryOp::Add, 5),
+            "-" => (BinaryOp::Subtract, 5),
+            "*" => (BinaryOp::Multiply, 6),
+            "/" => (BinaryOp::Divide, 6),
+            "%" => (BinaryOp::Remainder, 6),
+            _ => {
+                return None;
+            }
+        };
+        Some(op)
+    }
+}
+
+/// The syntax tree of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Number(f64),
+    Variable(String),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    /// function name, arguments and position of the call in the source
+    Call(String, Vec<Node>, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    LeftParen,
+    RightParen,
+    Comma,
+    Question,
+    Colon,
+    End,
+}
+
+const OPERATORS: [&str; 15] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", "?",
+];
+
+/// The maximum nesting of unary operators, conditionals, parentheses and function
+/// calls, which bounds the recursion of the parser.
+const MAX_DEPTH: usize = 256;
+
+fn syntax_error<T>(message: String, position: usize) -> Result<T> {
+    bail!(ErrorKind::IllegalArgument(format!(
+        "invalid expression: {} at position {}",
+        message, position
+    )))
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    'outer: while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        if c.is_ascii_digit() || (c == b'.' && i + 1 < bytes.len() && bytes[i + 1].is_ascii_digit())
+        {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                i += 1;
+                if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                    i += 1;
+                }
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            match source[start..i].parse::<f64>() {
+                Ok(v) => tokens.push((Token::Number(v), start)),
+                Err(_) => {
+                    return syntax_error(format!("invalid number '{}'", &source[start..i]), start);
+                }
+            }
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == b'_' || c == b'$' {
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'_'
+                    || bytes[i] == b'$'
+                    || bytes[i] == b'.')
+            {
+                i += 1;
+            }
+            tokens.push((Token::Ident(source[start..i].to_string()), start));
+            continue;
+        }
+        let token = match c {
+            b'(' => Some(Token::LeftParen),
+            b')' => Some(Token::RightParen),
+            b',' => Some(Token::Comma),
+            b':' => Some(Token::Colon),
+            _ => None,
+        };
+        if let Some(token) = token {
+            tokens.push((token, start));
+            i += 1;
+            continue;
+        }
+        for op in &OPERATORS {
+            if bytes[i..].starts_with(op.as_bytes()) {
+                let token = if *op == "?" {
+                    Token::Question
+                } else {
+                    Token::Op(op)
+                };
+                tokens.push((token, start));
+                i += op.len();
+                continue 'outer;
+            }
+        }
+        let ch = source[i..].chars().next().unwrap();
+        return syntax_error(format!("unexpected character '{}'", ch), start);
+    }
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+/// A recursive descent parser of the expression syntax, a subset of javascript:
+///
+/// * numbers, variables and function calls like `max(a, b)`
+/// * unary `-`, `+` and `!`
+/// * binary `*`, `/`, `%`, `+`, `-`, `<`, `<=`, `>`, `>=`, `==`, `!=`, `&&` and `||`, with the
+///   usual precedences
+/// * the conditional `cond ? a : b`
+pub(crate) struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn parse(source: &str) -> Result<Node> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let node = parser.conditional(0)?;
+        match parser.peek() {
+            Token::End => Ok(node),
+            _ => parser.unexpected(),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn unexpected<T>(&self) -> Result<T> {
+        let message = match self.peek() {
+            Token::End => "unexpected end of expression".to_string(),
+            Token::Number(v) => format!("unexpected number '{}'", v),
+            Token::Ident(name) => format!("unexpected identifier '{}'", name),
+            Token::Op(op) => format!("unexpected operator '{}'", op),
+            Token::LeftParen => "unexpected '('".to_string(),
+            Token::RightParen => "unexpected ')'".to_string(),
+            Token::Comma => "unexpected ','".to_string(),
+            Token::Question => "unexpected '?'".to_string(),
+            Token::Colon => "unexpected ':'".to_string(),
+        };
+        syntax_error(message, self.position())
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        if *self.peek() == token {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            syntax_error("expression nested too deeply".to_string(), self.position())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn conditional(&mut self, depth: usize) -> Result<Node> {
+        self.check_depth(depth)?;
+        let condition = self.binary(1, depth)?;
+        if *self.peek() == Token::Question {
+            self.pos += 1;
+            let then = self.conditional(depth + 1)?;
+            self.expect(Token::Colon)?;
+            let otherwise = self.conditional(depth + 1)?;
+            Ok(Node::Conditional(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(otherwise),
+            ))
+        } else {
+            Ok(condition)
+        }
+    }
+
+    /// Parse the binary operations whose precedence is at least `min_precedence`,
+    /// all of them being left associative.
+    fn binary(&mut self, min_precedence: u8, depth: usize) -> Result<Node> {
+        let mut left = self.unary(depth)?;
+        loop {
+            let (op, precedence) = match self.peek() {
+                Token::Op(symbol) => match BinaryOp::from_symbol(symbol) {
+                    Some((op, precedence)) if precedence >= min_precedence => (op, precedence),
+                    _ => {
+                        return Ok(left);
+                    }
+                },
+                _ => {
+                    return Ok(left);
+                }
+            };
+            self.pos += 1;
+            let right = self.binary(precedence + 1, depth)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self, depth: usize) -> Result<Node> {
+        self.check_depth(depth)?;
+        match self.peek() {
+            Token::Op("-") => {
+                self.pos += 1;
+                Ok(Node::Unary(
+                    UnaryOp::Negate,
+                    Box::new(self.unary(depth + 1)?),
+                ))
+            }
+            Token::Op("!") => {
+                self.pos += 1;
+                Ok(Node::Unary(UnaryOp::Not, Box::new(self.unary(depth + 1)?)))
+            }
+            Token::Op("+") => {
+                self.pos += 1;
+                self.unary(depth + 1)
+            }
+            _ => self.primary(depth),
+        }
+    }
+
+    fn primary(&mut self, depth: usize) -> Result<Node> {
+        let position = self.position();
+        match self.peek().clone() {
+            Token::Number(v) => {
+                self.pos += 1;
+                Ok(Node::Number(v))
+            }
+            Token::Ident(name) => {
+                self.pos += 1;
+                if *self.peek() != Token::LeftParen {
+                    return Ok(Node::Variable(name));
+                }
+                self.pos += 1;
+                let mut args = vec![];
+                if *self.peek() != Token::RightParen {
+                    loop {
+                        args.push(self.conditional(depth + 1)?);
+                        if *self.peek() == Token::Comma {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RightParen)?;
+                Ok(Node::Call(name, args, position))
+            }
+            Token::LeftParen => {
+                self.pos += 1;
+                let node = self.conditional(depth + 1)?;
+                self.expect(Token::RightParen)?;
+                Ok(node)
+            }
+            _ => self.unexpected(),
+        }
+    }
+}
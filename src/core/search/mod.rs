@@ -13,7 +13,10 @@
 
 pub mod cache;
 pub mod collector;
+pub mod expressions;
+pub mod percolator;
 pub mod query;
+pub mod query_dsl;
 pub mod scorer;
 pub mod similarity;
 pub mod sort_field;
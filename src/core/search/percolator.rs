@@ -0,0 +1,471 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds the stored queries matching a document.
+//!
+//! The queries are indexed as documents holding their id, their JSON form and the
+//! terms a document must contain to match them. A document is percolated by
+//! loading it into a `MemoryIndex`, selecting the queries sharing a term with it
+//! and running these candidates against the `MemoryIndex`.
+
+use core::codec::{Codec, Fields, TermIterator, Terms};
+use core::doc::{Field, FieldType, Fieldable, IndexOptions, Term, STORE_FIELD_TYPE};
+use core::index::memory::{MemoryCodec, MemoryIndex};
+use core::index::reader::{IndexReader, LeafReader, LeafReaderContext};
+use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::query::{
+    BooleanQuery, BoostQuery, BoostingQuery, ConstantScoreQuery, DisjunctionMaxQuery, PhraseQuery,
+    Query, SynonymQuery, TermQuery,
+};
+use core::search::query_dsl::QueryDsl;
+use core::search::scorer::Scorer;
+use core::search::IndexSearcher;
+use core::util::{DocId, VariantValue};
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+pub const DEFAULT_ID_FIELD: &str = "query_id";
+pub const DEFAULT_QUERY_FIELD: &str = "query";
+pub const DEFAULT_TERMS_FIELD: &str = "query_terms";
+
+/// The term indexed for the queries whose terms can't be extracted, they are
+/// candidates for every document. It can't collide with an encoded term as it
+/// has no field separator.
+const EXTRACTION_FAILED: &[u8] = b"extraction_failed";
+
+const FIELD_SEPARATOR: u8 = 0;
+
+/// Returns terms such that every document matching `query` contains at least one
+/// of them, or `None` if there are no such terms, e.g. for a `match_all` or a range
+/// query, or if the query type is not known.
+///
+/// Of the required clauses of a `BooleanQuery` only the one with the fewest terms
+/// is kept, the optional clauses are all needed when there is no required clause.
+pub fn extract_query_terms<C: Codec>(query: &dyn Query<C>) -> Option<Vec<Term>> {
+    let any = query.as_any();
+    if let Some(q) = any.downcast_ref::<TermQuery>() {
+        return Some(vec![q.term.clone()]);
+    }
+    if let Some(q) = any.downcast_ref::<PhraseQuery>() {
+        // every term of the phrase is required, the longest being the rarest
+        return q
+            .terms()
+            .iter()
+            .max_by_key(|t| t.bytes.len())
+            .map(|t| vec![t.clone()]);
+    }
+    if let Some(q) = any.downcast_ref::<SynonymQuery>() {
+        return Some(q.terms().to_vec());
+    }
+    if let Some(q) = any.downcast_ref::<BooleanQuery<C>>() {
+        let required = q
+            .musts()
+            .iter()
+            .chain(q.filters())
+            .filter_map(|q| extract_query_terms(q.as_ref()))
+            .min_by_key(|terms| terms.len());
+        if required.is_some() {
+            return required;
+        }
+        if !q.musts().is_empty() || !q.filters().is_empty() || q.min_should_match() == 0 {
+            return None;
+        }
+        return union_terms(q.shoulds());
+    }
+    if let Some(q) = any.downcast_ref::<DisjunctionMaxQuery<C>>() {
+        return union_terms(&q.disjuncts);
+    }
+    if let Some(q) = any.downcast_ref::<BoostQuery<C>>() {
+        return extract_query_terms(q.query());
+    }
+    if let Some(q) = any.downcast_ref::<ConstantScoreQuery<C>>() {
+        return extract_query_terms(q.get_raw_query());
+    }
+    if let Some(q) = any.downcast_ref::<BoostingQuery<C>>() {
+        return extract_query_terms(q.positive());
+    }
+    None
+}
+
+fn union_terms<C: Codec>(queries: &[Box<dyn Query<C>>]) -> Option<Vec<Term>> {
+    if queries.is_empty() {
+        return None;
+    }
+    let mut terms = vec![];
+    for query in queries {
+        terms.extend(extract_query_terms(query.as_ref())?);
+    }
+    Some(terms)
+}
+
+fn encode_term(field: &str, term: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(field.len() + 1 + term.len());
+    encoded.extend_from_slice(field.as_bytes());
+    encoded.push(FIELD_SEPARATOR);
+    encoded.extend_from_slice(term);
+    encoded
+}
+
+/// Stores queries as documents and finds the ones matching a document.
+///
+/// The queries are serialized with the `QueryDsl`, which must know the point
+/// fields and analyzers of the percolated documents to parse them back:
+///
+/// ```rust,ignore
+/// let percolator = Percolator::new(QueryDsl::new());
+/// writer.add_document(percolator.query_document("alert-1", query.as_ref())?)?;
+/// ...
+/// let ids = percolator.percolate(&searcher, document)?;
+/// ```
+pub struct Percolator {
+    dsl: QueryDsl,
+    id_field: String,
+    query_field: String,
+    terms_field: String,
+}
+
+impl Percolator {
+    pub fn new(dsl: QueryDsl) -> Self {
+        Percolator {
+            dsl,
+            id_field: DEFAULT_ID_FIELD.to_string(),
+            query_field: DEFAULT_QUERY_FIELD.to_string(),
+            terms_field: DEFAULT_TERMS_FIELD.to_string(),
+        }
+    }
+
+    /// Store the query ids in `field`, indexed so that queries can be deleted by id.
+    pub fn with_id_field(mut self, field: &str) -> Self {
+        self.id_field = field.to_string();
+        self
+    }
+
+    /// Store the JSON form of the queries in `field`.
+    pub fn with_query_field(mut self, field: &str) -> Self {
+        self.query_field = field.to_string();
+        self
+    }
+
+    /// Index the extracted terms of the queries in `field`.
+    pub fn with_terms_field(mut self, field: &str) -> Self {
+        self.terms_field = field.to_string();
+        self
+    }
+
+    pub fn id_field(&self) -> &str {
+        &self.id_field
+    }
+
+    pub fn query_field(&self) -> &str {
+        &self.query_field
+    }
+
+    pub fn terms_field(&self) -> &str {
+        &self.terms_field
+    }
+
+    /// Builds the document storing `query` under `id`, to be added to the index
+    /// of the queries.
+    pub fn query_document<C: Codec>(
+        &self,
+        id: &str,
+        query: &dyn Query<C>,
+    ) -> Result<Vec<Box<dyn Fieldable>>> {
+        let json = QueryDsl::to_json(query)?;
+        // fail now rather than when percolating
+        self.dsl.parse::<MemoryCodec>(&json)?;
+
+        let mut id_type = keyword_type();
+        id_type.stored = true;
+        let mut doc: Vec<Box<dyn Fieldable>> = vec![
+            Box::new(Field::new(
+                self.id_field.clone(),
+                id_type,
+                Some(VariantValue::VString(id.to_string())),
+                None,
+            )),
+            Box::new(Field::new(
+                self.query_field.clone(),
+                STORE_FIELD_TYPE,
+                Some(VariantValue::VString(json)),
+                None,
+            )),
+        ];
+        let terms = match extract_query_terms(query) {
+            Some(terms) => terms
+                .iter()
+                .map(|t| encode_term(t.field(), &t.bytes))
+                .collect(),
+            None => vec![EXTRACTION_FAILED.to_vec()],
+        };
+        for term in terms {
+            doc.push(Box::new(Field::new_bytes(
+                self.terms_field.clone(),
+                term,
+                keyword_type(),
+            )));
+        }
+        Ok(doc)
+    }
+
+    /// Returns the ids of the stored queries matching `doc`, in index order.
+    pub fn percolate<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        doc: Vec<Box<dyn Fieldable>>,
+    ) -> Result<Vec<String>> {
+        let mut index = MemoryIndex::new(false, false);
+        index.add_document(doc)?;
+
+        let candidates = self.candidate_query(&index)?;
+        let mut collector = CandidatesCollector::default();
+        searcher.search(candidates.as_ref(), &mut collector)?;
+
+        let fields = [self.id_field.clone(), self.query_field.clone()];
+        let mut ids = vec![];
+        for doc_id in collector.docs {
+            let stored = searcher.reader().document(doc_id, &fields)?;
+            let value = |name: &str| -> Result<String> {
+                match stored
+                    .fields
+                    .iter()
+                    .find(|f| f.field.name() == name)
+                    .and_then(|f| f.field.string_value())
+                {
+                    Some(value) => Ok(value.to_string()),
+                    None => bail!(IllegalArgument(format!(
+                        "percolator document {} has no stored field '{}'",
+                        doc_id, name
+                    ))),
+                }
+            };
+            let query = self.dsl.parse::<MemoryCodec>(&value(&self.query_field)?)?;
+            if index.search(query.as_ref())?.is_some() {
+                ids.push(value(&self.id_field)?);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// A query on the terms field matching the stored queries sharing a term with
+    /// the document, or whose terms couldn't be extracted.
+    fn candidate_query<C: Codec>(&self, index: &MemoryIndex) -> Result<Box<dyn Query<C>>> {
+        let term_query = |bytes: Vec<u8>| -> Box<dyn Query<C>> {
+            Box::new(TermQuery::new(
+                Term::new(self.terms_field.clone(), bytes),
+                1.0,
+                None,
+            ))
+        };
+        let mut shoulds = vec![term_query(EXTRACTION_FAILED.to_vec())];
+        let fields = index.fields()?;
+        for field in fields.fields() {
+            if let Some(terms) = fields.terms(&field)? {
+                let mut iter = terms.iterator()?;
+                while let Some(term) = iter.next()? {
+                    shoulds.push(term_query(encode_term(&field, &term)));
+                }
+            }
+        }
+        BooleanQuery::build(vec![], shoulds, vec![], vec![], 1)
+    }
+}
+
+fn keyword_type() -> FieldType {
+    FieldType {
+        index_options: IndexOptions::Docs,
+        tokenized: false,
+        omit_norms: true,
+        ..FieldType::default()
+    }
+}
+
+/// Collects the ids of the candidate queries documents.
+#[derive(Default)]
+struct CandidatesCollector {
+    doc_base: DocId,
+    docs: Vec<DocId>,
+}
+
+impl SearchCollector for CandidatesCollector {
+    type LC = CandidatesLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(&self, _reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
+        unreachable!()
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for CandidatesCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.docs.push(self.doc_base + doc);
+        Ok(())
+    }
+}
+
+// the candidates are always collected sequentially
+struct CandidatesLeafCollector;
+
+impl Collector for CandidatesLeafCollector {
+    fn needs_scores(&self) -> bool {
+        unreachable!()
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
+        unreachable!()
+    }
+}
+
+impl ParallelLeafCollector for CandidatesLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::search::query::tests::index_fields;
+    use core::search::query::{whitespace_analyzer, IntPoint, PointValueType};
+    use core::search::DefaultIndexSearcher;
+
+    use std::sync::Arc;
+
+    fn document(text: &str, size: i32) -> Vec<Box<dyn Fieldable>> {
+        let mut text_type = FieldType::default();
+        text_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+        let mut point_type = FieldType::default();
+        point_type.set_dimensions(1, 4).unwrap();
+        vec![
+            Box::new(Field::new(
+                "content".into(),
+                text_type,
+                Some(VariantValue::VString(text.into())),
+                Some(whitespace_analyzer()(text)),
+            )),
+            Box::new(Field::new(
+                "size".into(),
+                point_type,
+                Some(VariantValue::Binary(IntPoint::pack(&[size]))),
+                None,
+            )),
+        ]
+    }
+
+    #[test]
+    fn test_percolator() {
+        let dsl = QueryDsl::new().with_point_field("size", PointValueType::Integer);
+        let queries = [
+            ("fox", r#"{"term": {"content": "fox"}}"#),
+            (
+                "quick-dog",
+                r#"{"bool": {"must": [{"term": {"content": "quick"}},
+                                      {"term": {"content": "dog"}}]}}"#,
+            ),
+            ("lazy-dog", r#"{"match_phrase": {"content": "lazy dog"}}"#),
+            ("small", r#"{"range": {"size": {"lt": 10}}}"#),
+            (
+                "cat-or-small",
+                r#"{"bool": {"should": [{"term": {"content": "cat"}},
+                                        {"range": {"size": {"lt": 10}}}]}}"#,
+            ),
+            (
+                "fox-not-big",
+                r#"{"bool": {"must": {"term": {"content": "fox"}},
+                             "must_not": {"range": {"size": {"gte": 100}}}}}"#,
+            ),
+        ];
+        let percolator = Percolator::new(dsl);
+        let mut docs = vec![];
+        for (id, json) in &queries {
+            let query = percolator.dsl.parse::<MemoryCodec>(json).unwrap();
+            let doc = percolator.query_document(id, query.as_ref()).unwrap();
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+
+        let ids = percolator
+            .percolate(&searcher, document("the quick brown fox", 200))
+            .unwrap();
+        assert_eq!(ids, vec!["fox"]);
+
+        let ids = percolator
+            .percolate(&searcher, document("a quick fox and a lazy dog", 5))
+            .unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                "fox",
+                "quick-dog",
+                "lazy-dog",
+                "small",
+                "cat-or-small",
+                "fox-not-big"
+            ]
+        );
+
+        let ids = percolator
+            .percolate(&searcher, document("dog lazy", 50))
+            .unwrap();
+        assert!(ids.is_empty());
+    }
+
+    #[test]
+    fn test_extract_query_terms() {
+        let dsl = QueryDsl::new().with_point_field("size", PointValueType::Integer);
+        let extract = |json: &str| -> Option<Vec<String>> {
+            let query = dsl.parse::<MemoryCodec>(json).unwrap();
+            extract_query_terms(query.as_ref()).map(|terms| {
+                terms
+                    .iter()
+                    .map(|t| t.text().unwrap())
+                    .collect::<Vec<String>>()
+            })
+        };
+        assert_eq!(
+            extract(r#"{"match": {"content": "quick fox"}}"#),
+            Some(vec!["quick".to_string(), "fox".to_string()])
+        );
+        assert_eq!(
+            extract(
+                r#"{"bool": {"must": [{"match": {"content": "quick fox"}},
+                                      {"term": {"content": "dog"}}]}}"#
+            ),
+            Some(vec!["dog".to_string()])
+        );
+        assert_eq!(extract(r#"{"match_all": {}}"#), None);
+        assert_eq!(
+            extract(r#"{"bool": {"must_not": {"term": {"content": "dog"}}}}"#),
+            None
+        );
+    }
+}
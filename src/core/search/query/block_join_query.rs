@@ -0,0 +1,724 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use core::codec::Codec;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::join_query::AggregatedScore;
+use core::search::query::{JoinScoreMode, Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::{BitSet, Bits, DocId, FixedBitSet, ImmutableBitSet};
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const TO_PARENT_BLOCK_JOIN: &str = "to_parent_block_join";
+pub const TO_CHILD_BLOCK_JOIN: &str = "to_child_block_join";
+
+/// Returns the documents of the segment matching the parents filter, or `None` if
+/// there are none.
+///
+/// Deleted documents are kept, so that the blocks of the segment are not mixed up.
+pub(crate) fn parents_bit_set<C: Codec>(
+    parents_weight: &dyn Weight<C>,
+    reader: &LeafReaderContext<'_, C>,
+) -> Result<Option<FixedBitSet>> {
+    let mut scorer = match parents_weight.create_scorer(reader)? {
+        Some(scorer) => scorer,
+        None => return Ok(None),
+    };
+    let mut parents = FixedBitSet::new(reader.reader.max_doc() as usize);
+    let mut found = false;
+    loop {
+        let doc = scorer.next()?;
+        if doc == NO_MORE_DOCS {
+            break;
+        }
+        parents.set(doc as usize);
+        found = true;
+    }
+    Ok(if found { Some(parents) } else { None })
+}
+
+/// The first document of the block of `parent`.
+fn first_child(parents: &FixedBitSet, parent: DocId) -> DocId {
+    if parent == 0 {
+        0
+    } else {
+        parents.prev_set_bit(parent as usize - 1) + 1
+    }
+}
+
+/// Joins the documents matching a child query to their parent documents, for an
+/// index made of blocks of child documents followed by their parent, as added with
+/// `IndexWriter#add_documents`.
+///
+/// The parents are the documents matching the `parents_filter`, which should only
+/// match the last document of each block, for example an `ExistsQuery` over the
+/// `IndexWriterConfig#parent_field`. The child query must not match any parent.
+///
+/// Each parent with at least one matching child matches, and is scored from the
+/// scores of its matching children according to the `JoinScoreMode`.
+pub struct ToParentBlockJoinQuery<C: Codec> {
+    child_query: Box<dyn Query<C>>,
+    parents_filter: Box<dyn Query<C>>,
+    score_mode: JoinScoreMode,
+}
+
+impl<C: Codec> ToParentBlockJoinQuery<C> {
+    pub fn new(
+        child_query: Box<dyn Query<C>>,
+        parents_filter: Box<dyn Query<C>>,
+        score_mode: JoinScoreMode,
+    ) -> ToParentBlockJoinQuery<C> {
+        ToParentBlockJoinQuery {
+            child_query,
+            parents_filter,
+            score_mode,
+        }
+    }
+
+    pub fn child_query(&self) -> &dyn Query<C> {
+        self.child_query.as_ref()
+    }
+
+    pub fn parents_filter(&self) -> &dyn Query<C> {
+        self.parents_filter.as_ref()
+    }
+
+    pub fn score_mode(&self) -> JoinScoreMode {
+        self.score_mode
+    }
+}
+
+impl<C: Codec> Query<C> for ToParentBlockJoinQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let needs_scores = needs_scores && self.score_mode != JoinScoreMode::None;
+        Ok(Box::new(ToParentBlockJoinWeight {
+            child_weight: searcher.create_weight(self.child_query.as_ref(), needs_scores)?,
+            parents_weight: searcher.create_weight(self.parents_filter.as_ref(), false)?,
+            score_mode: self.score_mode,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.child_query.extract_terms()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for ToParentBlockJoinQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ToParentBlockJoinQuery(child: {}, parents: {}, score_mode: {})",
+            &self.child_query, &self.parents_filter, self.score_mode
+        )
+    }
+}
+
+struct ToParentBlockJoinWeight<C: Codec> {
+    child_weight: Box<dyn Weight<C>>,
+    parents_weight: Box<dyn Weight<C>>,
+    score_mode: JoinScoreMode,
+    needs_scores: bool,
+}
+
+impl<C: Codec> ToParentBlockJoinWeight<C> {
+    fn parent_scorer(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<ToParentBlockJoinScorer>> {
+        let child_scorer = match self.child_weight.create_scorer(reader)? {
+            Some(scorer) => scorer,
+            None => return Ok(None),
+        };
+        Ok(parents_bit_set(self.parents_weight.as_ref(), reader)?
+            .map(|parents| ToParentBlockJoinScorer::new(child_scorer, parents, self)))
+    }
+}
+
+impl<C: Codec> Weight<C> for ToParentBlockJoinWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self
+            .parent_scorer(reader)?
+            .map(|scorer| Box::new(scorer) as Box<dyn Scorer>))
+    }
+
+    fn query_type(&self) -> &'static str {
+        TO_PARENT_BLOCK_JOIN
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.child_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.child_weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(mut scorer) = self.parent_scorer(reader)? {
+            if scorer.advance(doc)? == doc {
+                return Ok(Explanation::new(
+                    true,
+                    scorer.score()?,
+                    format!(
+                        "{}, score mode {} of {} child docs in range from {} to {}",
+                        self,
+                        self.score_mode,
+                        scorer.child_count,
+                        first_child(&scorer.parents, doc),
+                        doc - 1
+                    ),
+                    vec![],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            format!("{} doesn't match id {}", self, doc),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for ToParentBlockJoinWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ToParentBlockJoinWeight(child: {}, score_mode: {})",
+            &self.child_weight, self.score_mode
+        )
+    }
+}
+
+struct ToParentBlockJoinScorer {
+    // positioned on the first matching child of the next block
+    child_scorer: Box<dyn Scorer>,
+    parents: FixedBitSet,
+    score_mode: JoinScoreMode,
+    needs_scores: bool,
+    parent_doc: DocId,
+    score: f32,
+    child_count: usize,
+}
+
+impl ToParentBlockJoinScorer {
+    fn new<C: Codec>(
+        child_scorer: Box<dyn Scorer>,
+        parents: FixedBitSet,
+        weight: &ToParentBlockJoinWeight<C>,
+    ) -> Self {
+        ToParentBlockJoinScorer {
+            child_scorer,
+            parents,
+            score_mode: weight.score_mode,
+            needs_scores: weight.needs_scores,
+            parent_doc: -1,
+            score: 0f32,
+            child_count: 0,
+        }
+    }
+
+    /// Moves to the parent of the current child, collecting the scores of the
+    /// matching children of its block.
+    fn next_block(&mut self) -> Result<DocId> {
+        let mut child_doc = self.child_scorer.doc_id();
+        if child_doc == NO_MORE_DOCS {
+            self.parent_doc = NO_MORE_DOCS;
+            return Ok(NO_MORE_DOCS);
+        }
+        let parent_doc = self.parents.next_set_bit(child_doc as usize);
+        if parent_doc == NO_MORE_DOCS {
+            // children without a parent at the end of the segment
+            self.parent_doc = NO_MORE_DOCS;
+            return Ok(NO_MORE_DOCS);
+        }
+        if parent_doc == child_doc {
+            bail!(ErrorKind::IllegalState(format!(
+                "child query must not match any docs of the parents filter, but doc {} matches \
+                 both",
+                child_doc
+            )));
+        }
+
+        let mut score = AggregatedScore::new();
+        let mut child_count = 0;
+        while child_doc < parent_doc {
+            if self.needs_scores {
+                score.add(self.score_mode, self.child_scorer.score()?);
+            }
+            child_count += 1;
+            child_doc = self.child_scorer.next()?;
+        }
+        self.parent_doc = parent_doc;
+        self.child_count = child_count;
+        self.score = if self.needs_scores {
+            score.value(self.score_mode)
+        } else {
+            1f32
+        };
+        Ok(parent_doc)
+    }
+}
+
+impl Scorer for ToParentBlockJoinScorer {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.score)
+    }
+}
+
+impl DocIterator for ToParentBlockJoinScorer {
+    fn doc_id(&self) -> DocId {
+        self.parent_doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.child_scorer.doc_id() == -1 {
+            self.child_scorer.next()?;
+        }
+        self.next_block()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        if target as usize >= self.parents.len() {
+            self.parent_doc = NO_MORE_DOCS;
+            return Ok(NO_MORE_DOCS);
+        }
+        // skip the children of the blocks before the one of `target`
+        let first_child = first_child(&self.parents, target);
+        if self.child_scorer.doc_id() < first_child {
+            self.child_scorer.advance(first_child)?;
+        }
+        self.next_block()
+    }
+
+    fn cost(&self) -> usize {
+        self.child_scorer.cost()
+    }
+}
+
+/// Joins the documents matching a parent query to their child documents, the
+/// reverse of `ToParentBlockJoinQuery`.
+///
+/// The parents are the documents matching the `parents_filter`, and the parent
+/// query must only match parents. Every child of a matching parent matches, with
+/// the score of its parent.
+pub struct ToChildBlockJoinQuery<C: Codec> {
+    parent_query: Box<dyn Query<C>>,
+    parents_filter: Box<dyn Query<C>>,
+}
+
+impl<C: Codec> ToChildBlockJoinQuery<C> {
+    pub fn new(
+        parent_query: Box<dyn Query<C>>,
+        parents_filter: Box<dyn Query<C>>,
+    ) -> ToChildBlockJoinQuery<C> {
+        ToChildBlockJoinQuery {
+            parent_query,
+            parents_filter,
+        }
+    }
+
+    pub fn parent_query(&self) -> &dyn Query<C> {
+        self.parent_query.as_ref()
+    }
+
+    pub fn parents_filter(&self) -> &dyn Query<C> {
+        self.parents_filter.as_ref()
+    }
+}
+
+impl<C: Codec> Query<C> for ToChildBlockJoinQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(ToChildBlockJoinWeight {
+            parent_weight: searcher.create_weight(self.parent_query.as_ref(), needs_scores)?,
+            parents_weight: searcher.create_weight(self.parents_filter.as_ref(), false)?,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.parent_query.extract_terms()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for ToChildBlockJoinQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ToChildBlockJoinQuery(parent: {}, parents: {})",
+            &self.parent_query, &self.parents_filter
+        )
+    }
+}
+
+struct ToChildBlockJoinWeight<C: Codec> {
+    parent_weight: Box<dyn Weight<C>>,
+    parents_weight: Box<dyn Weight<C>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> ToChildBlockJoinWeight<C> {
+    fn child_scorer(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<ToChildBlockJoinScorer>> {
+        let parent_scorer = match self.parent_weight.create_scorer(reader)? {
+            Some(scorer) => scorer,
+            None => return Ok(None),
+        };
+        Ok(
+            parents_bit_set(self.parents_weight.as_ref(), reader)?.map(|parents| {
+                ToChildBlockJoinScorer {
+                    parent_scorer,
+                    parents,
+                    needs_scores: self.needs_scores,
+                    child_doc: -1,
+                    parent_doc: -1,
+                    parent_score: 0f32,
+                }
+            }),
+        )
+    }
+}
+
+impl<C: Codec> Weight<C> for ToChildBlockJoinWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self
+            .child_scorer(reader)?
+            .map(|scorer| Box::new(scorer) as Box<dyn Scorer>))
+    }
+
+    fn query_type(&self) -> &'static str {
+        TO_CHILD_BLOCK_JOIN
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.parent_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.parent_weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(mut scorer) = self.child_scorer(reader)? {
+            if scorer.advance(doc)? == doc {
+                let parent_doc = scorer.parent_doc;
+                return Ok(Explanation::new(
+                    true,
+                    scorer.score()?,
+                    format!("{}, score from parent doc {}", self, parent_doc),
+                    vec![self.parent_weight.explain(reader, parent_doc)?],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            format!("{} doesn't match id {}", self, doc),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for ToChildBlockJoinWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ToChildBlockJoinWeight(parent: {})", &self.parent_weight)
+    }
+}
+
+struct ToChildBlockJoinScorer {
+    // positioned on the parent of the current child
+    parent_scorer: Box<dyn Scorer>,
+    parents: FixedBitSet,
+    needs_scores: bool,
+    child_doc: DocId,
+    parent_doc: DocId,
+    parent_score: f32,
+}
+
+impl Scorer for ToChildBlockJoinScorer {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.parent_score)
+    }
+}
+
+impl DocIterator for ToChildBlockJoinScorer {
+    fn doc_id(&self) -> DocId {
+        self.child_doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.child_doc == NO_MORE_DOCS {
+            return Ok(NO_MORE_DOCS);
+        }
+        let target = self.child_doc + 1;
+        self.advance(target)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        if target as usize >= self.parents.len() {
+            self.child_doc = NO_MORE_DOCS;
+            return Ok(NO_MORE_DOCS);
+        }
+        if target < self.parent_doc {
+            // still in the block of the current parent
+            self.child_doc = target;
+            return Ok(target);
+        }
+
+        // find the first matching parent of a block with children at or after target
+        let mut parent_doc = if self.parent_scorer.doc_id() == target {
+            self.parent_scorer.next()?
+        } else {
+            self.parent_scorer.advance(target)?
+        };
+        loop {
+            if parent_doc == NO_MORE_DOCS {
+                self.parent_doc = NO_MORE_DOCS;
+                self.child_doc = NO_MORE_DOCS;
+                return Ok(NO_MORE_DOCS);
+            }
+            if !self.parents.get(parent_doc as usize)? {
+                bail!(ErrorKind::IllegalState(format!(
+                    "parent query must only match docs of the parents filter, but doc {} is not a \
+                     parent",
+                    parent_doc
+                )));
+            }
+            let child_doc = first_child(&self.parents, parent_doc).max(target);
+            if child_doc < parent_doc {
+                self.parent_doc = parent_doc;
+                self.parent_score = if self.needs_scores {
+                    self.parent_scorer.score()?
+                } else {
+                    1f32
+                };
+                self.child_doc = child_doc;
+                return Ok(child_doc);
+            }
+            parent_doc = self.parent_scorer.next()?;
+        }
+    }
+
+    fn cost(&self) -> usize {
+        self.parent_scorer.cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{Field, FieldType, Fieldable, IndexOptions, NumericDocValuesField, Term};
+    use core::index::reader::IndexReader;
+    use core::index::writer::IndexWriterConfig;
+    use core::search::collector::{ToParentBlockJoinCollector, TopDocsCollector};
+    use core::search::query::tests::new_index_writer;
+    use core::search::query::{BooleanQuery, ExistsQuery};
+    use core::search::sort_field::{SimpleSortField, Sort, SortField, SortFieldType};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::VariantValue;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_block_join_queries() {
+        let mut config = IndexWriterConfig::default();
+        config.parent_field = Some("_parent".into());
+        config.index_sort = Some(Sort::new(vec![SortField::Simple(SimpleSortField::new(
+            "price".into(),
+            SortFieldType::Long,
+            false,
+        ))]));
+        let (_dir, writer) = new_index_writer(config);
+
+        let mut keyword_type = FieldType::default();
+        keyword_type.index_options = IndexOptions::Docs;
+        keyword_type.tokenized = false;
+        let keyword = |name: &str, value: &str| -> Box<dyn Fieldable> {
+            Box::new(Field::new(
+                name.into(),
+                keyword_type.clone(),
+                Some(VariantValue::VString(value.into())),
+                None,
+            ))
+        };
+        let block = |name: &str, price: i64, colors: &[&str]| {
+            let mut docs: Vec<Vec<Box<dyn Fieldable>>> =
+                colors.iter().map(|c| vec![keyword("color", c)]).collect();
+            docs.push(vec![
+                keyword("name", name),
+                Box::new(NumericDocValuesField::new("price", price)),
+            ]);
+            docs
+        };
+
+        // the blocks are sorted by the price of their parent when flushed and merged
+        writer
+            .add_documents(block("a", 30, &["red", "blue"]))
+            .unwrap();
+        writer
+            .add_documents(block("b", 10, &["red", "red", "green"]))
+            .unwrap();
+        writer.commit().unwrap();
+        writer.add_documents(block("c", 20, &["blue"])).unwrap();
+        writer.add_document(block("d", 5, &[]).remove(0)).unwrap();
+        writer.commit().unwrap();
+        writer.force_merge(1, true).unwrap();
+
+        // the parent field is reserved
+        assert!(writer
+            .add_documents(vec![vec![
+                keyword("color", "red"),
+                Box::new(NumericDocValuesField::new("_parent", 1)),
+            ]])
+            .is_err());
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 1);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let term = |field: &str, value: &str| -> Box<dyn Query<_>> {
+            Box::new(TermQuery::new(
+                Term::new(field.into(), value.as_bytes().to_vec()),
+                1.0,
+                None,
+            ))
+        };
+        let parents = || -> Box<dyn Query<_>> { Box::new(ExistsQuery::build("_parent".into())) };
+        let search = |query: &dyn Query<_>| -> Vec<(DocId, f32)> {
+            let mut collector = TopDocsCollector::new(20);
+            searcher.search(query, &mut collector).unwrap();
+            let mut hits: Vec<(DocId, f32)> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect();
+            hits.sort_by_key(|h| h.0);
+            hits
+        };
+        let docs = |query: &dyn Query<_>| -> Vec<DocId> {
+            search(query).into_iter().map(|h| h.0).collect()
+        };
+
+        // d, then the blocks of b, c and a
+        assert_eq!(docs(term("name", "d").as_ref()), vec![0]);
+        assert_eq!(docs(term("name", "b").as_ref()), vec![4]);
+        assert_eq!(docs(term("name", "c").as_ref()), vec![6]);
+        assert_eq!(docs(term("name", "a").as_ref()), vec![9]);
+        assert_eq!(docs(term("color", "red").as_ref()), vec![1, 2, 7]);
+
+        let red_score = search(term("color", "red").as_ref())[0].1;
+        for &(score_mode, factor) in &[
+            (JoinScoreMode::None, 0f32),
+            (JoinScoreMode::Avg, 1f32),
+            (JoinScoreMode::Max, 1f32),
+            (JoinScoreMode::Total, 2f32),
+        ] {
+            let query = ToParentBlockJoinQuery::new(term("color", "red"), parents(), score_mode);
+            let hits = search(&query);
+            assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), vec![4, 9]);
+            let expected = if score_mode == JoinScoreMode::None {
+                1f32
+            } else {
+                red_score * factor
+            };
+            assert!((hits[0].1 - expected).abs() < 1e-5);
+            let expl = searcher.explain(&query, 4).unwrap();
+            assert!(expl.is_match());
+            assert!((expl.value() - expected).abs() < 1e-5);
+            assert!(!searcher.explain(&query, 6).unwrap().is_match());
+        }
+
+        let query = ToChildBlockJoinQuery::new(term("name", "b"), parents());
+        assert_eq!(docs(&query), vec![1, 2, 3]);
+        let query = ToChildBlockJoinQuery::new(
+            BooleanQuery::build(
+                vec![],
+                vec![term("name", "a"), term("name", "c"), term("name", "d")],
+                vec![],
+                vec![],
+                0,
+            )
+            .unwrap(),
+            parents(),
+        );
+        assert_eq!(docs(&query), vec![5, 7, 8]);
+        assert!(searcher.explain(&query, 8).unwrap().is_match());
+        assert!(!searcher.explain(&query, 9).unwrap().is_match());
+
+        // a child query matching parents is an error
+        let query = ToParentBlockJoinQuery::new(term("name", "b"), parents(), JoinScoreMode::Max);
+        let mut collector = TopDocsCollector::new(20);
+        assert!(searcher.search(&query, &mut collector).is_err());
+
+        // the inner hits of the top parents
+        let query = ToParentBlockJoinQuery::new(
+            BooleanQuery::build(
+                vec![],
+                vec![term("color", "red"), term("color", "blue")],
+                vec![],
+                vec![],
+                0,
+            )
+            .unwrap(),
+            parents(),
+            JoinScoreMode::Total,
+        );
+        let mut collector = ToParentBlockJoinCollector::new(2);
+        searcher.search(&query, &mut collector).unwrap();
+        let hits = collector.inner_hits(&searcher, &query, 1).unwrap();
+        assert_eq!(hits.len(), 2);
+        let mut parent_docs: Vec<DocId> = hits.iter().map(|h| h.parent.doc).collect();
+        parent_docs.sort();
+        assert_eq!(parent_docs, vec![4, 9]);
+        for hit in &hits {
+            assert_eq!(hit.total_children, 2);
+            assert_eq!(hit.children.len(), 1);
+            let child = hit.children[0].doc;
+            assert!(child < hit.parent.doc && child >= hit.parent.doc - 3);
+        }
+    }
+}
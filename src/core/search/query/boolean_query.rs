@@ -86,6 +86,26 @@ impl<C: Codec> BooleanQuery<C> {
         }))
     }
 
+    pub fn musts(&self) -> &[Box<dyn Query<C>>] {
+        &self.must_queries
+    }
+
+    pub fn shoulds(&self) -> &[Box<dyn Query<C>>] {
+        &self.should_queries
+    }
+
+    pub fn filters(&self) -> &[Box<dyn Query<C>>] {
+        &self.filter_queries
+    }
+
+    pub fn must_nots(&self) -> &[Box<dyn Query<C>>] {
+        &self.must_not_queries
+    }
+
+    pub fn min_should_match(&self) -> i32 {
+        self.min_should_match
+    }
+
     fn queries_to_str(&self, queries: &[Box<dyn Query<C>>]) -> String {
         let query_strs: Vec<String> = queries.iter().map(|q| format!("{}", q)).collect();
         query_strs.join(", ")
@@ -199,13 +219,29 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
     ) -> Result<Option<Box<dyn Scorer>>> {
         let must_scorer: Option<Box<dyn Scorer>> = if !self.must_weights.is_empty() {
             let mut scorers = vec![];
-            for weight in &self.must_weights {
+            // scorers that make use of the lead cost are created once the cost of
+            // the other required scorers is known
+            for weight in self.must_weights.iter().filter(|w| !w.uses_lead_cost()) {
                 if let Some(scorer) = weight.create_scorer(leaf_reader)? {
                     scorers.push(scorer);
                 } else {
                     return Ok(None);
                 }
             }
+            let lead_cost = scorers.iter().map(|s| s.cost()).min();
+            for weight in self.must_weights.iter().filter(|w| w.uses_lead_cost()) {
+                let scorer = match lead_cost {
+                    Some(lead_cost) => {
+                        weight.create_scorer_with_lead_cost(leaf_reader, lead_cost)?
+                    }
+                    None => weight.create_scorer(leaf_reader)?,
+                };
+                if let Some(scorer) = scorer {
+                    scorers.push(scorer);
+                } else {
+                    return Ok(None);
+                }
+            }
             if scorers.len() > 1 {
                 Some(Box::new(ConjunctionScorer::new(scorers)))
             } else {
@@ -45,6 +45,14 @@ impl<C: Codec> BoostQuery<C> {
             Box::new(BoostQuery { query, boost })
         }
     }
+
+    pub fn query(&self) -> &dyn Query<C> {
+        self.query.as_ref()
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
 }
 
 impl<C: Codec> Query<C> for BoostQuery<C> {
@@ -99,6 +107,23 @@ impl<C: Codec> Weight<C> for BoostWeight<C> {
         self.weight.create_scorer(leaf_reader)
     }
 
+    fn create_scorer_with_lead_cost(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+        lead_cost: usize,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        self.weight
+            .create_scorer_with_lead_cost(leaf_reader, lead_cost)
+    }
+
+    fn uses_lead_cost(&self) -> bool {
+        self.weight.uses_lead_cost()
+    }
+
+    fn estimate_cost(&self, leaf_reader: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        self.weight.estimate_cost(leaf_reader)
+    }
+
     fn query_type(&self) -> &'static str {
         BOOST_QUERY
     }
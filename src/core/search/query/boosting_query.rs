@@ -44,6 +44,18 @@ impl<C: Codec> BoostingQuery<C> {
             negative_boost,
         })
     }
+
+    pub fn positive(&self) -> &dyn Query<C> {
+        self.positive.as_ref()
+    }
+
+    pub fn negative(&self) -> &dyn Query<C> {
+        self.negative.as_ref()
+    }
+
+    pub fn negative_boost(&self) -> f32 {
+        self.negative_boost
+    }
 }
 
 impl<C: Codec> Query<C> for BoostingQuery<C> {
@@ -0,0 +1,559 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use core::codec::doc_values::NumericDocValues;
+use core::codec::norms::CombinedNormValues;
+use core::codec::{Codec, CodecPostingIterator};
+use core::codec::{PostingIterator, PostingIteratorFlags};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::similarity::{BM25Similarity, SimScorer, SimWeight, Similarity};
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+use error::{ErrorKind, Result};
+
+pub const COMBINED_FIELDS: &str = "combined_fields";
+
+/// A query that scores terms across several text fields as if the fields were
+/// indexed as a single field, as described by BM25F.
+///
+/// The frequency of a term in the combined field is the sum of its frequencies in
+/// each field multiplied by the weight of the field, and the length of the combined
+/// field is the weighted sum of the field lengths read from the norms with
+/// `CombinedNormValues`. The collection and term statistics are combined the same
+/// way, so that a term frequent in a short field like a title isn't scored as a
+/// rare term, unlike with a `DisjunctionMaxQuery` over the fields.
+///
+/// The terms are scored as synonyms, only invoking the similarity a single time
+/// with the sum of their frequencies; the weights must be at least 1.
+#[derive(Clone, Debug)]
+pub struct CombinedFieldsQuery {
+    fields: Vec<(String, f32)>,
+    terms: Vec<Vec<u8>>,
+    boost: f32,
+}
+
+impl CombinedFieldsQuery {
+    pub fn new(fields: Vec<(String, f32)>, terms: Vec<Vec<u8>>) -> Result<CombinedFieldsQuery> {
+        if fields.is_empty() || terms.is_empty() {
+            bail!(ErrorKind::IllegalArgument(
+                "combined fields query fields and terms should not be empty!".into()
+            ));
+        }
+        for (i, (field, weight)) in fields.iter().enumerate() {
+            if *weight < 1.0 {
+                bail!(ErrorKind::IllegalArgument(format!(
+                    "weight of field '{}' must be at least 1, got {}",
+                    field, weight
+                )));
+            }
+            if fields[..i].iter().any(|(f, _)| f == field) {
+                bail!(ErrorKind::IllegalArgument(format!(
+                    "field '{}' is combined more than once",
+                    field
+                )));
+            }
+        }
+        let mut terms = terms;
+        terms.sort();
+        terms.dedup();
+        Ok(CombinedFieldsQuery {
+            fields,
+            terms,
+            boost: 1.0,
+        })
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    /// The combined fields with their weights.
+    pub fn fields(&self) -> &[(String, f32)] {
+        &self.fields
+    }
+
+    pub fn terms(&self) -> &[Vec<u8>] {
+        &self.terms
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
+
+    fn combined_field(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(field, weight)| format!("{}^{}", field, weight))
+            .collect();
+        fields.join("+")
+    }
+
+    /// The statistics of the combined field: the max doc count of the fields and the
+    /// weighted sum of their total term freqs.
+    fn collection_stats<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+    ) -> CollectionStatistics {
+        let max_doc = i64::from(searcher.max_doc());
+        let mut doc_count = 0i64;
+        let mut sum_total_term_freq = 0f64;
+        let mut sum_doc_freq = 0i64;
+        for (field, weight) in &self.fields {
+            if let Some(stats) = searcher.collections_statistics(field) {
+                doc_count = doc_count.max(if stats.doc_count == -1 {
+                    max_doc
+                } else {
+                    stats.doc_count
+                });
+                if stats.sum_total_term_freq == -1 || stats.sum_doc_freq == -1 {
+                    return CollectionStatistics::new(
+                        self.combined_field(),
+                        0,
+                        max_doc,
+                        -1,
+                        -1,
+                        -1,
+                    );
+                }
+                sum_total_term_freq += f64::from(*weight) * stats.sum_total_term_freq as f64;
+                sum_doc_freq += stats.sum_doc_freq;
+            }
+        }
+        CollectionStatistics::new(
+            self.combined_field(),
+            0,
+            max_doc,
+            doc_count,
+            sum_total_term_freq as i64,
+            sum_doc_freq,
+        )
+    }
+
+    /// The statistics of all the terms in all the fields, their total term freqs
+    /// being weighted, blended as the statistics of a single term.
+    fn term_stats<C: Codec>(&self, searcher: &dyn SearchPlanBuilder<C>) -> Result<TermStatistics> {
+        let mut stats = Vec::with_capacity(self.fields.len() * self.terms.len());
+        for term in &self.terms {
+            for (field, weight) in &self.fields {
+                let stat = searcher.term_statistics(&Term::new(field.clone(), term.clone()))?;
+                let total_term_freq = if stat.total_term_freq == -1 {
+                    -1
+                } else {
+                    (f64::from(*weight) * stat.total_term_freq as f64) as i64
+                };
+                stats.push(TermStatistics::new(
+                    term.clone(),
+                    stat.doc_freq,
+                    total_term_freq,
+                ));
+            }
+        }
+        // pseudo term made of the first term, it is only used for explain
+        Ok(BM25Similarity::blend_term_stats(
+            self.terms[0].clone(),
+            &stats,
+        ))
+    }
+}
+
+impl<C: Codec> Query<C> for CombinedFieldsQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        let (collection_stats, term_stats) = if needs_scores {
+            (self.collection_stats(searcher), self.term_stats(searcher)?)
+        } else {
+            (
+                CollectionStatistics::new(self.combined_field(), 0, max_doc, -1, -1, -1),
+                TermStatistics::new(self.terms[0].clone(), max_doc, -1),
+            )
+        };
+
+        let similarity = searcher.similarity(&self.fields[0].0, needs_scores);
+        let sim_weight =
+            similarity.compute_weight(&collection_stats, &[term_stats], None, self.boost);
+        Ok(Box::new(CombinedFieldsWeight {
+            query: self.clone(),
+            similarity,
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        let mut terms = Vec::with_capacity(self.fields.len() * self.terms.len());
+        for (field, _) in &self.fields {
+            for term in &self.terms {
+                terms.push(TermQuery::new(
+                    Term::new(field.clone(), term.clone()),
+                    self.boost,
+                    None,
+                ));
+            }
+        }
+        terms
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for CombinedFieldsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(field, weight)| format!("{}^{}", field, weight))
+            .collect();
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| String::from_utf8(t.clone()).unwrap_or_else(|_| format!("{:?}", t)))
+            .collect();
+        write!(
+            f,
+            "CombinedFieldsQuery(fields: [{}], terms: [{}], boost: {})",
+            fields.join(", "),
+            terms.join(", "),
+            self.boost
+        )
+    }
+}
+
+struct CombinedFieldsWeight<C: Codec> {
+    query: CombinedFieldsQuery,
+    similarity: Box<dyn Similarity<C>>,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> CombinedFieldsWeight<C> {
+    fn postings(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Vec<(CodecPostingIterator<C>, f32)>> {
+        let flags = if self.needs_scores {
+            PostingIteratorFlags::FREQS
+        } else {
+            PostingIteratorFlags::NONE
+        };
+        let mut postings = vec![];
+        for (field, weight) in &self.query.fields {
+            for term in &self.query.terms {
+                let term = Term::new(field.clone(), term.clone());
+                if let Some(p) = reader.reader.postings(&term, i32::from(flags))? {
+                    postings.push((p, *weight));
+                }
+            }
+        }
+        Ok(postings)
+    }
+
+    fn norms(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn NumericDocValues>>> {
+        let mut norms = vec![];
+        for (field, weight) in &self.query.fields {
+            if let Some(field_norms) = reader.reader.norm_values(field)? {
+                norms.push((field_norms, *weight));
+            }
+        }
+        if norms.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Box::new(CombinedNormValues::new(norms))))
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for CombinedFieldsWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        let postings = self.postings(reader)?;
+        if postings.is_empty() {
+            return Ok(None);
+        }
+        let norms = if self.needs_scores {
+            self.norms(reader)?
+        } else {
+            None
+        };
+        let sim_scorer = self.sim_weight.sim_scorer_with_norms(norms)?;
+        Ok(Some(Box::new(CombinedFieldsScorer::new(
+            postings, sim_scorer,
+        ))))
+    }
+
+    fn query_type(&self) -> &'static str {
+        COMBINED_FIELDS
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost * self.query.boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let postings = self.postings(reader)?;
+        if !postings.is_empty() {
+            let norms = self.norms(reader)?;
+            let mut scorer =
+                CombinedFieldsScorer::new(postings, self.sim_weight.sim_scorer_with_norms(None)?);
+            if scorer.advance(doc)? == doc {
+                let freq = scorer.freq()?;
+                let freq_expl =
+                    Explanation::new(true, freq, format!("combined termFreq={}", freq), vec![]);
+                let score_expl = self.sim_weight.explain_with_norms(doc, freq_expl, norms)?;
+
+                return Ok(Explanation::new(
+                    true,
+                    score_expl.value(),
+                    format!(
+                        "weight({} in {}) [{}], result of:",
+                        self.query, doc, self.similarity
+                    ),
+                    vec![score_expl],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            "no matching term".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for CombinedFieldsWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CombinedFieldsWeight(query: {}, similarity: {}, need_score: {})",
+            &self.query, &self.similarity, self.needs_scores
+        )
+    }
+}
+
+/// Scores the union of the postings of the terms in all the fields with the
+/// weighted sum of their freqs.
+struct CombinedFieldsScorer<T: PostingIterator> {
+    postings: Vec<(T, f32)>,
+    sim_scorer: Box<dyn SimScorer>,
+    doc: DocId,
+    cost: usize,
+}
+
+impl<T: PostingIterator> CombinedFieldsScorer<T> {
+    fn new(postings: Vec<(T, f32)>, sim_scorer: Box<dyn SimScorer>) -> Self {
+        let cost = postings.iter().map(|(p, _)| p.cost()).sum();
+        CombinedFieldsScorer {
+            postings,
+            sim_scorer,
+            doc: -1,
+            cost,
+        }
+    }
+
+    fn update_doc(&mut self) -> DocId {
+        self.doc = self
+            .postings
+            .iter()
+            .map(|(p, _)| p.doc_id())
+            .min()
+            .unwrap_or(NO_MORE_DOCS);
+        self.doc
+    }
+
+    fn freq(&self) -> Result<f32> {
+        let mut freq = 0f32;
+        for (postings, weight) in &self.postings {
+            if postings.doc_id() == self.doc {
+                freq += weight * postings.freq()? as f32;
+            }
+        }
+        Ok(freq)
+    }
+}
+
+impl<T: PostingIterator> Scorer for CombinedFieldsScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        let freq = self.freq()?;
+        self.sim_scorer.score(self.doc, freq)
+    }
+}
+
+impl<T: PostingIterator> DocIterator for CombinedFieldsScorer<T> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let current = self.doc;
+        for (postings, _) in &mut self.postings {
+            if postings.doc_id() == current {
+                postings.next()?;
+            }
+        }
+        Ok(self.update_doc())
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        for (postings, _) in &mut self.postings {
+            if postings.doc_id() < target {
+                postings.advance(target)?;
+            }
+        }
+        Ok(self.update_doc())
+    }
+
+    fn cost(&self) -> usize {
+        self.cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::analysis::WhitespaceTokenizer;
+    use core::doc::{Field, FieldType, Fieldable, IndexOptions};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::query::DisjunctionMaxQuery;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::VariantValue;
+
+    use std::io;
+    use std::sync::Arc;
+
+    fn text_field(name: &str, text: &str) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        Box::new(Field::new(
+            name.into(),
+            field_type,
+            Some(VariantValue::VString(text.into())),
+            Some(Box::new(WhitespaceTokenizer::new(Box::new(
+                io::Cursor::new(text.as_bytes().to_vec()),
+            )))),
+        ))
+    }
+
+    #[test]
+    fn test_combined_fields_query() {
+        let texts = [
+            ("fox", "a story about a dog"),
+            ("the dog", "a story about a fox and a dog"),
+            ("the cat", "fox and fox and fox"),
+            ("the bird", "a story about a bird"),
+        ];
+        let mut docs = vec![];
+        for (title, body) in &texts {
+            docs.push(vec![text_field("title", title), text_field("body", body)]);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let search = |query: &dyn Query<_>| -> Vec<(DocId, f32)> {
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(query, &mut collector).unwrap();
+            collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect()
+        };
+        let term_query = |field: &str, text: &str| -> Box<dyn Query<_>> {
+            Box::new(TermQuery::new(
+                Term::new(field.into(), text.as_bytes().to_vec()),
+                1.0,
+                None,
+            ))
+        };
+
+        // the rare title term wins over the body term frequencies
+        let dis_max = DisjunctionMaxQuery::build(
+            vec![term_query("title", "fox"), term_query("body", "fox")],
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(search(dis_max.as_ref())[0].0, 0);
+
+        // scored as a single field, the body occurrences win
+        let fields = vec![("title".to_string(), 1.0), ("body".to_string(), 1.0)];
+        let query = CombinedFieldsQuery::new(fields, vec![b"fox".to_vec()]).unwrap();
+        let hits = search(&query);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].0, 2);
+        for (doc, score) in &hits {
+            let expl = searcher.explain(&query, *doc).unwrap();
+            assert!(expl.is_match());
+            assert!((expl.value() - score).abs() < 1e-5);
+        }
+        assert!(!searcher.explain(&query, 3).unwrap().is_match());
+
+        // weighting the title twice makes the title occurrence count as two
+        let fields = vec![("title".to_string(), 2.0), ("body".to_string(), 1.0)];
+        let weighted = CombinedFieldsQuery::new(fields, vec![b"fox".to_vec()]).unwrap();
+        let weighted_hits = search(&weighted);
+        let score =
+            |hits: &[(DocId, f32)], doc: DocId| hits.iter().find(|(d, _)| *d == doc).unwrap().1;
+        assert!(
+            score(&weighted_hits, 0) / score(&weighted_hits, 2) > score(&hits, 0) / score(&hits, 2)
+        );
+
+        // a single field scores like a term query
+        let single =
+            CombinedFieldsQuery::new(vec![("body".to_string(), 1.0)], vec![b"fox".to_vec()])
+                .unwrap();
+        let single_hits = search(&single);
+        let term_hits = search(term_query("body", "fox").as_ref());
+        assert_eq!(single_hits.len(), term_hits.len());
+        for ((d1, s1), (d2, s2)) in single_hits.iter().zip(&term_hits) {
+            assert_eq!(d1, d2);
+            assert!((s1 - s2).abs() < 1e-5);
+        }
+
+        // synonyms are scored together
+        let fields = vec![("title".to_string(), 1.0), ("body".to_string(), 1.0)];
+        let synonyms =
+            CombinedFieldsQuery::new(fields, vec![b"cat".to_vec(), b"bird".to_vec()]).unwrap();
+        assert_eq!(search(&synonyms).len(), 2);
+
+        assert!(CombinedFieldsQuery::new(vec![], vec![b"fox".to_vec()]).is_err());
+        let fields = vec![("title".to_string(), 0.5)];
+        assert!(CombinedFieldsQuery::new(fields, vec![b"fox".to_vec()]).is_err());
+    }
+}
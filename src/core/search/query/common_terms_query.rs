@@ -0,0 +1,337 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::fmt;
+
+use core::codec::Codec;
+use core::doc::Term;
+use core::search::query::{BooleanQuery, Operator, Query, TermQuery, Weight};
+use core::search::searcher::SearchPlanBuilder;
+
+use error::{ErrorKind, Result};
+
+/// A query executing the terms of a query with many common words, like stopwords,
+/// efficiently.
+///
+/// The terms are split in two groups by their document frequency: the terms found in
+/// more than `max_term_frequency` documents (a fraction of the max doc if lower than 1,
+/// an absolute number of documents otherwise) are high frequency terms, and the other
+/// ones are low frequency terms. The low frequency terms are searched with
+/// `low_freq_operator`, and the high frequency terms, which are the slowest ones,
+/// only contribute to the score of the documents matching the low frequency terms.
+/// When all the terms are high frequency terms, they are searched with
+/// `high_freq_operator`, all of them being required unless a minimum number of them
+/// must match.
+///
+/// The minimum number of optional terms which must match in each group is a fraction
+/// of the terms of the group if lower than 1, and an absolute number otherwise.
+///
+/// For instance "to be or not to be" only contains high frequency terms, which are
+/// all required, and "the quick fox" only matches the documents with "quick" or "fox",
+/// "the" only adding to their score.
+#[derive(Clone, Debug)]
+pub struct CommonTermsQuery {
+    terms: Vec<Term>,
+    high_freq_operator: Operator,
+    low_freq_operator: Operator,
+    max_term_frequency: f32,
+    low_freq_min_should_match: f32,
+    high_freq_min_should_match: f32,
+    boost: f32,
+}
+
+impl CommonTermsQuery {
+    pub fn new(
+        high_freq_operator: Operator,
+        low_freq_operator: Operator,
+        max_term_frequency: f32,
+    ) -> Result<CommonTermsQuery> {
+        if max_term_frequency < 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "max_term_frequency must be positive, got {}",
+                max_term_frequency
+            )));
+        }
+        Ok(CommonTermsQuery {
+            terms: vec![],
+            high_freq_operator,
+            low_freq_operator,
+            max_term_frequency,
+            low_freq_min_should_match: 0.0,
+            high_freq_min_should_match: 0.0,
+            boost: 1.0,
+        })
+    }
+
+    pub fn add(&mut self, term: Term) {
+        self.terms.push(term);
+    }
+
+    pub fn with_terms(mut self, terms: Vec<Term>) -> Self {
+        self.terms.extend(terms);
+        self
+    }
+
+    /// The minimum number of the optional low frequency terms which must match.
+    pub fn with_low_freq_min_should_match(mut self, min_should_match: f32) -> Self {
+        self.low_freq_min_should_match = min_should_match;
+        self
+    }
+
+    /// The minimum number of the optional high frequency terms which must match.
+    pub fn with_high_freq_min_should_match(mut self, min_should_match: f32) -> Self {
+        self.high_freq_min_should_match = min_should_match;
+        self
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    fn min_should_match(min_should_match: f32, num_optional: usize) -> i32 {
+        if min_should_match >= 1.0 {
+            min_should_match as i32
+        } else {
+            (min_should_match * num_optional as f32).round() as i32
+        }
+    }
+
+    /// Build the `BooleanQuery` of the terms, split by their document frequencies.
+    fn build<C: Codec>(&self, searcher: &dyn SearchPlanBuilder<C>) -> Result<Box<dyn Query<C>>> {
+        let max_doc = searcher.max_doc();
+        let max_freq = if self.max_term_frequency >= 1.0 {
+            self.max_term_frequency as i64
+        } else {
+            (self.max_term_frequency * max_doc as f32).ceil() as i64
+        };
+
+        let mut low_freq: Vec<Box<dyn Query<C>>> = vec![];
+        let mut high_freq: Vec<Box<dyn Query<C>>> = vec![];
+        for term in &self.terms {
+            let doc_freq = searcher.term_statistics(term)?.doc_freq;
+            let query = Box::new(TermQuery::new(term.clone(), self.boost, None));
+            if doc_freq > max_freq {
+                high_freq.push(query);
+            } else {
+                low_freq.push(query);
+            }
+        }
+
+        let low_freq_query = if low_freq.is_empty() {
+            None
+        } else if self.low_freq_operator == Operator::And {
+            Some(BooleanQuery::build(low_freq, vec![], vec![], vec![], 0)?)
+        } else {
+            let min_should_match =
+                Self::min_should_match(self.low_freq_min_should_match, low_freq.len());
+            Some(BooleanQuery::build(
+                vec![],
+                low_freq,
+                vec![],
+                vec![],
+                min_should_match,
+            )?)
+        };
+        if high_freq.is_empty() {
+            return Ok(low_freq_query.unwrap());
+        }
+
+        let min_should_match =
+            Self::min_should_match(self.high_freq_min_should_match, high_freq.len());
+        match low_freq_query {
+            Some(low_freq_query) => {
+                // the high frequency terms only score the low frequency matches
+                let high_freq_query = if self.high_freq_operator == Operator::And {
+                    BooleanQuery::build(high_freq, vec![], vec![], vec![], 0)?
+                } else {
+                    BooleanQuery::build(vec![], high_freq, vec![], vec![], min_should_match)?
+                };
+                BooleanQuery::build(
+                    vec![low_freq_query],
+                    vec![high_freq_query],
+                    vec![],
+                    vec![],
+                    0,
+                )
+            }
+            None => {
+                // only high frequency terms, make them required to avoid a slow disjunction
+                if self.high_freq_operator == Operator::And || min_should_match == 0 {
+                    BooleanQuery::build(high_freq, vec![], vec![], vec![], 0)
+                } else {
+                    BooleanQuery::build(vec![], high_freq, vec![], vec![], min_should_match)
+                }
+            }
+        }
+    }
+}
+
+impl<C: Codec> Query<C> for CommonTermsQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        if self.terms.is_empty() {
+            bail!(ErrorKind::IllegalArgument(
+                "common terms query should at least contain one term!".into()
+            ));
+        }
+        self.build(searcher)?.create_weight(searcher, needs_scores)
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms
+            .iter()
+            .map(|t| TermQuery::new(t.clone(), self.boost, None))
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for CommonTermsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| {
+                format!(
+                    "{}:{}",
+                    t.field,
+                    t.text().unwrap_or_else(|_| format!("{:?}", &t.bytes))
+                )
+            })
+            .collect();
+        write!(
+            f,
+            "CommonTermsQuery(terms: [{}], high_freq_operator: {:?}, low_freq_operator: {:?}, \
+             max_term_frequency: {}, low_freq_min_should_match: {}, high_freq_min_should_match: \
+             {}, boost: {})",
+            terms.join(", "),
+            self.high_freq_operator,
+            self.low_freq_operator,
+            self.max_term_frequency,
+            self.low_freq_min_should_match,
+            self.high_freq_min_should_match,
+            self.boost
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{Field, FieldType, Fieldable, IndexOptions};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::{DocId, VariantValue};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_common_terms_query() {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        field_type.tokenized = false;
+        let texts: [&[&str]; 6] = [
+            &["the", "quick", "fox"],
+            &["the", "lazy", "dog"],
+            &["the", "quick", "dog"],
+            &["the", "fox"],
+            &["a", "fox"],
+            &["the"],
+        ];
+        let mut docs = vec![];
+        for words in &texts {
+            let doc: Vec<Box<dyn Fieldable>> = words
+                .iter()
+                .map(|w| -> Box<dyn Fieldable> {
+                    Box::new(Field::new(
+                        "text".into(),
+                        field_type.clone(),
+                        Some(VariantValue::VString((*w).into())),
+                        None,
+                    ))
+                })
+                .collect();
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let search = |query: &CommonTermsQuery| -> Vec<DocId> {
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(query, &mut collector).unwrap();
+            let mut docs: Vec<DocId> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| d.doc_id())
+                .collect();
+            docs.sort();
+            docs
+        };
+        let terms = |words: &[&str]| -> Vec<Term> {
+            words
+                .iter()
+                .map(|w| Term::new("text".into(), w.as_bytes().to_vec()))
+                .collect()
+        };
+
+        // "the" is in 5 docs of 6, "fox" in 3, "quick" and "dog" in 2
+        let query = CommonTermsQuery::new(Operator::Or, Operator::Or, 0.5)
+            .unwrap()
+            .with_terms(terms(&["the", "quick", "dog"]));
+        assert_eq!(search(&query), vec![0, 1, 2]);
+        // "the" only scores the low frequency matches
+        let low_freq = CommonTermsQuery::new(Operator::Or, Operator::Or, 0.5)
+            .unwrap()
+            .with_terms(terms(&["quick", "dog"]));
+        let expl = searcher.explain(&query, 1).unwrap();
+        assert!(expl.is_match());
+        assert!(expl.value() > searcher.explain(&low_freq, 1).unwrap().value());
+
+        let query = CommonTermsQuery::new(Operator::Or, Operator::And, 0.5)
+            .unwrap()
+            .with_terms(terms(&["the", "quick", "dog"]));
+        assert_eq!(search(&query), vec![2]);
+
+        let query = CommonTermsQuery::new(Operator::Or, Operator::Or, 0.5)
+            .unwrap()
+            .with_terms(terms(&["the", "quick", "dog", "lazy"]))
+            .with_low_freq_min_should_match(2.0);
+        assert_eq!(search(&query), vec![1, 2]);
+
+        // only high frequency terms are all required by default
+        let query = CommonTermsQuery::new(Operator::Or, Operator::Or, 2.0)
+            .unwrap()
+            .with_terms(terms(&["the", "fox"]));
+        assert_eq!(search(&query), vec![0, 3]);
+        let query = query.with_high_freq_min_should_match(0.5);
+        assert_eq!(search(&query), vec![0, 1, 2, 3, 4, 5]);
+
+        assert!(CommonTermsQuery::new(Operator::Or, Operator::Or, -1.0).is_err());
+        let query = CommonTermsQuery::new(Operator::Or, Operator::Or, 0.5).unwrap();
+        assert!(Query::<_>::create_weight(&query, &searcher, true).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::fmt;
+
+use core::codec::doc_values::NumericDocValues;
+use core::codec::Codec;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::{DisjunctionSumScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::{BitsMut, DocId};
+use error::{ErrorKind::IllegalArgument, Result};
+
+pub const COVERING: &str = "covering";
+
+/// A query that matches the documents matching at least a number of its
+/// clauses, this number being read per document from a numeric doc values
+/// field, unlike the static `min_should_match` of a `BooleanQuery`.
+///
+/// This allows e.g. to index for each document a set of tags with the number of
+/// tags it requires, and to find the documents whose tags are a subset of the
+/// tags of a query. Documents without a value for the field don't match, and
+/// documents requiring no more than one clause match any clause. The score is
+/// the sum of the scores of the matching clauses.
+pub struct CoveringQuery<C: Codec> {
+    queries: Vec<Box<dyn Query<C>>>,
+    min_match_field: String,
+}
+
+impl<C: Codec> CoveringQuery<C> {
+    pub fn new(queries: Vec<Box<dyn Query<C>>>, min_match_field: &str) -> Result<Self> {
+        if queries.is_empty() {
+            bail!(IllegalArgument(
+                "covering query should at least contain one inner query!".into()
+            ));
+        }
+        Ok(CoveringQuery {
+            queries,
+            min_match_field: min_match_field.to_string(),
+        })
+    }
+
+    pub fn queries(&self) -> &[Box<dyn Query<C>>] {
+        &self.queries
+    }
+
+    /// The numeric doc values field holding the number of clauses a document
+    /// requires.
+    pub fn min_match_field(&self) -> &str {
+        &self.min_match_field
+    }
+}
+
+impl<C: Codec> Query<C> for CoveringQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let mut weights = Vec::with_capacity(self.queries.len());
+        for q in &self.queries {
+            weights.push(searcher.create_weight(q.as_ref(), needs_scores)?);
+        }
+        Ok(Box::new(CoveringWeight {
+            weights,
+            min_match_field: self.min_match_field.clone(),
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.queries
+            .iter()
+            .flat_map(|q| q.extract_terms())
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for CoveringQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let queries: Vec<String> = self.queries.iter().map(|q| format!("{}", q)).collect();
+        write!(
+            f,
+            "CoveringQuery(queries: [{}], min_match_field: {})",
+            queries.join(", "),
+            &self.min_match_field
+        )
+    }
+}
+
+struct CoveringWeight<C: Codec> {
+    weights: Vec<Box<dyn Weight<C>>>,
+    min_match_field: String,
+    needs_scores: bool,
+}
+
+impl<C: Codec> Weight<C> for CoveringWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        let mut scorers = vec![];
+        for weight in &self.weights {
+            if let Some(scorer) = weight.create_scorer(reader)? {
+                scorers.push(scorer);
+            }
+        }
+        if scorers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(CoveringScorer {
+            disjunction: DisjunctionSumScorer::new(scorers, self.needs_scores, 1),
+            min_match_values: reader
+                .reader
+                .get_numeric_doc_values(&self.min_match_field)?,
+            docs_with_field: reader.reader.get_docs_with_field(&self.min_match_field)?,
+            score: 0.0,
+        })))
+    }
+
+    fn query_type(&self) -> &'static str {
+        COVERING
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        for weight in &mut self.weights {
+            weight.normalize(norm, boost);
+        }
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weights
+            .iter()
+            .filter(|w| w.needs_scores())
+            .map(|w| w.value_for_normalization())
+            .sum()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let mut sum = 0.0f32;
+        let mut match_count = 0;
+        let mut subs = vec![];
+        for weight in &self.weights {
+            let e = weight.explain(reader, doc)?;
+            if e.is_match() {
+                sum += e.value();
+                match_count += 1;
+                subs.push(e);
+            }
+        }
+
+        let has_min_match = reader
+            .reader
+            .get_docs_with_field(&self.min_match_field)?
+            .get(doc as usize)?;
+        if !has_min_match {
+            return Ok(Explanation::new(
+                false,
+                0.0,
+                format!("no value for the {} field", &self.min_match_field),
+                subs,
+            ));
+        }
+        let min_match = reader
+            .reader
+            .get_numeric_doc_values(&self.min_match_field)?
+            .get(doc)?;
+        if match_count == 0 {
+            Ok(Explanation::new(
+                false,
+                0.0,
+                "No matching clauses".to_string(),
+                subs,
+            ))
+        } else if (match_count as i64) < min_match {
+            Ok(Explanation::new(
+                false,
+                0.0,
+                format!(
+                    "Failure to match minimum number of clauses: {}<{}",
+                    match_count, min_match
+                ),
+                subs,
+            ))
+        } else {
+            Ok(Explanation::new(
+                true,
+                sum,
+                format!(
+                    "sum of {} matching clauses, {} required:",
+                    match_count, min_match
+                ),
+                subs,
+            ))
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for CoveringWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let weights: Vec<String> = self.weights.iter().map(|w| format!("{}", w)).collect();
+        write!(
+            f,
+            "CoveringWeight(weights: [{}], min_match_field: {}, needs_scores: {})",
+            weights.join(", "),
+            &self.min_match_field,
+            self.needs_scores
+        )
+    }
+}
+
+/// Two phase scorer of a `CoveringQuery`, approximated by the disjunction of the
+/// clauses, and only matching the docs for which enough clauses match.
+struct CoveringScorer {
+    disjunction: DisjunctionSumScorer<Box<dyn Scorer>>,
+    min_match_values: Box<dyn NumericDocValues>,
+    docs_with_field: Box<dyn BitsMut>,
+    // the sum of the scores of the clauses verified by `matches`
+    score: f32,
+}
+
+impl CoveringScorer {
+    fn next_match(&mut self, mut doc: DocId) -> Result<DocId> {
+        while doc != NO_MORE_DOCS && !self.matches()? {
+            doc = self.disjunction.approximate_next()?;
+        }
+        Ok(doc)
+    }
+}
+
+impl Scorer for CoveringScorer {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.score)
+    }
+}
+
+impl DocIterator for CoveringScorer {
+    fn doc_id(&self) -> DocId {
+        self.disjunction.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.disjunction.approximate_next()?;
+        self.next_match(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.disjunction.approximate_advance(target)?;
+        self.next_match(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.disjunction.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        let doc = self.disjunction.doc_id();
+        if !self.docs_with_field.get(doc as usize)? {
+            return Ok(false);
+        }
+        let min_match = self.min_match_values.get_mut(doc)?;
+        let (freq, score) = self.disjunction.matching_freq_and_score()?;
+        self.score = score;
+        Ok(freq > 0 && (min_match <= 1 || i64::from(freq) >= min_match))
+    }
+
+    fn match_cost(&self) -> f32 {
+        // reading the doc values and verifying the matching clauses
+        1f32
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.disjunction.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.disjunction.approximate_advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::analysis::WhitespaceTokenizer;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType, Fieldable, IndexOptions, NumericDocValuesField, Term};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::{index_fields, new_index_text_field};
+    use core::search::query::{BooleanQuery, PhraseQuery};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::VariantValue;
+
+    use std::io;
+    use std::sync::Arc;
+
+    fn tags_field(tags: &str) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        Box::new(Field::new(
+            "tags".into(),
+            field_type,
+            Some(VariantValue::VString(tags.into())),
+            Some(Box::new(WhitespaceTokenizer::new(Box::new(
+                io::Cursor::new(tags.as_bytes().to_vec()),
+            )))),
+        ))
+    }
+
+    #[test]
+    fn test_covering_query() {
+        let texts = [
+            ("a b", Some(2)),
+            ("a", Some(2)),
+            ("a b c", Some(1)),
+            ("c", Some(1)),
+            ("a b", None),
+            ("b", Some(0)),
+            ("t0 t1 t2", Some(3)),
+            ("t0 t1 t2", Some(4)),
+        ];
+        let mut docs = vec![];
+        for (tags, required) in &texts {
+            let mut doc = vec![tags_field(tags)];
+            if let Some(required) = required {
+                doc.push(Box::new(NumericDocValuesField::new(
+                    "required_tag_count",
+                    *required,
+                )));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let search = |query: &dyn Query<_>| -> Vec<DocId> {
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(query, &mut collector).unwrap();
+            let mut docs: Vec<DocId> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| d.doc_id())
+                .collect();
+            docs.sort();
+            docs
+        };
+        let term_query = |tag: &str| -> Box<dyn Query<_>> {
+            Box::new(TermQuery::new(
+                Term::new("tags".into(), tag.as_bytes().to_vec()),
+                1.0,
+                None,
+            ))
+        };
+
+        let query =
+            CoveringQuery::new(vec![term_query("a"), term_query("b")], "required_tag_count")
+                .unwrap();
+        assert_eq!(search(&query), vec![0, 2, 5]);
+        for doc in 0..texts.len() as DocId {
+            let expl = searcher.explain(&query, doc).unwrap();
+            assert_eq!(expl.is_match(), [0, 2, 5].contains(&doc));
+        }
+
+        // with enough clauses the disjunction uses a priority queue
+        let tags: Vec<String> = (0..12).map(|i| format!("t{}", i)).collect();
+        let query = CoveringQuery::new(
+            tags.iter().map(|t| term_query(t)).collect(),
+            "required_tag_count",
+        )
+        .unwrap();
+        assert_eq!(search(&query), vec![6]);
+
+        // as a required clause, the scorer is driven by its approximation
+        let covering: Box<dyn Query<_>> = Box::new(
+            CoveringQuery::new(vec![term_query("a"), term_query("b")], "required_tag_count")
+                .unwrap(),
+        );
+        let query = BooleanQuery::build(vec![covering, term_query("a")], vec![], vec![], vec![], 0)
+            .unwrap();
+        assert_eq!(search(query.as_ref()), vec![0, 2]);
+
+        assert!(CoveringQuery::new(vec![], "required_tag_count")
+            .map(|q| search(&q))
+            .is_err());
+    }
+
+    #[test]
+    fn test_covering_query_with_phrase_clause() {
+        let texts = [
+            ("a b c", 2),
+            ("c b x a", 2),
+            ("c x a", 1),
+            ("f0 f1 f2 f3 f4 f5 f6 f7 f8", 9),
+        ];
+        let mut docs = vec![];
+        for (text, required) in &texts {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(new_index_text_field("body".into(), (*text).into())),
+                Box::new(NumericDocValuesField::new("required_count", *required)),
+            ];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let term = |text: &str| Term::new("body".into(), text.as_bytes().to_vec());
+        let clauses = |fillers: usize| -> Vec<Box<dyn Query<CodecEnum>>> {
+            // the sloppy phrase is only positioned on "c b x a" and "c x a" by its
+            // approximation
+            let mut clauses: Vec<Box<dyn Query<CodecEnum>>> = vec![
+                Box::new(PhraseQuery::build(vec![term("a"), term("c")], 1, None, None).unwrap()),
+                Box::new(TermQuery::new(term("b"), 1.0, None)),
+            ];
+            for i in 0..fillers {
+                let filler = format!("f{}", i);
+                clauses.push(Box::new(TermQuery::new(term(&filler), 1.0, None)));
+            }
+            clauses
+        };
+
+        // with enough clauses the disjunction uses a priority queue
+        for (fillers, expected) in vec![(0, vec![0]), (9, vec![0, 3])] {
+            let query = CoveringQuery::new(clauses(fillers), "required_count").unwrap();
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(&query, &mut collector).unwrap();
+            let top_docs = collector.top_docs();
+            let mut hits: Vec<DocId> = vec![];
+            for score_doc in top_docs.score_docs() {
+                let expl = searcher.explain(&query, score_doc.doc_id()).unwrap();
+                assert!(expl.is_match());
+                assert!((expl.value() - score_doc.score()).abs() < 1e-5);
+                hits.push(score_doc.doc_id());
+            }
+            hits.sort_unstable();
+            assert_eq!(hits, expected);
+            for doc in 0..texts.len() as DocId {
+                let expl = searcher.explain(&query, doc).unwrap();
+                assert_eq!(expl.is_match(), expected.contains(&doc));
+            }
+        }
+    }
+}
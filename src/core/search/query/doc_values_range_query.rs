@@ -0,0 +1,560 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::{
+    NumericDocValues, SortedDocValues, SortedNumericDocValues, SortedSetDocValues, NO_MORE_ORDS,
+};
+use core::codec::Codec;
+use core::doc::DocValuesType;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{AllDocsIterator, Query, TermQuery, Weight};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::{BitsMut, DocId};
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const DOC_VALUES_RANGE: &str = "doc_values_range";
+
+/// A range query that works on top of the doc values APIs. Such queries are
+/// usually slow since they do not use an inverted index. However, in the dense
+/// case where most documents match this query, it *might* be as fast or faster
+/// than a regular `PointRangeQuery`, and when it is intersected with a selective
+/// query it only checks the documents of that query.
+///
+/// Long ranges work on `Numeric` and `SortedNumeric` doc values, byte ranges on
+/// `Sorted` and `SortedSet` doc values.
+///
+/// *NOTE*: this query matches documents by checking their doc values one by one,
+/// it is meant to be used as a filter, e.g. through `IndexOrDocValuesQuery`.
+pub struct DocValuesRangeQuery {
+    field: String,
+    range: DocValuesRange,
+    include_lower: bool,
+    include_upper: bool,
+}
+
+#[derive(Clone)]
+enum DocValuesRange {
+    Long {
+        lower: Option<i64>,
+        upper: Option<i64>,
+    },
+    Bytes {
+        lower: Option<Vec<u8>>,
+        upper: Option<Vec<u8>>,
+    },
+}
+
+impl DocValuesRangeQuery {
+    /// Create a new numeric range query on a numeric doc-values field. The field
+    /// must have been indexed with either `DocValuesType::Numeric` or
+    /// `DocValuesType::SortedNumeric` doc values. A `None` bound means the range is
+    /// open on that side.
+    pub fn new_long_range(
+        field: String,
+        lower: Option<i64>,
+        upper: Option<i64>,
+        include_lower: bool,
+        include_upper: bool,
+    ) -> DocValuesRangeQuery {
+        DocValuesRangeQuery {
+            field,
+            include_lower: include_lower && lower.is_some(),
+            include_upper: include_upper && upper.is_some(),
+            range: DocValuesRange::Long { lower, upper },
+        }
+    }
+
+    /// Create a new range query on a sorted doc-values field. The field must have
+    /// been indexed with `DocValuesType::Sorted` or `DocValuesType::SortedSet` doc
+    /// values. A `None` bound means the range is open on that side.
+    pub fn new_bytes_range(
+        field: String,
+        lower: Option<Vec<u8>>,
+        upper: Option<Vec<u8>>,
+        include_lower: bool,
+        include_upper: bool,
+    ) -> DocValuesRangeQuery {
+        DocValuesRangeQuery {
+            field,
+            include_lower: include_lower && lower.is_some(),
+            include_upper: include_upper && upper.is_some(),
+            range: DocValuesRange::Bytes { lower, upper },
+        }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn include_lower(&self) -> bool {
+        self.include_lower
+    }
+
+    pub fn include_upper(&self) -> bool {
+        self.include_upper
+    }
+}
+
+impl<C: Codec> Query<C> for DocValuesRangeQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(DocValuesRangeWeight {
+            field: self.field.clone(),
+            range: self.range.clone(),
+            include_lower: self.include_lower,
+            include_upper: self.include_upper,
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for DocValuesRangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesRangeQuery(field: {}, range: {})",
+            &self.field,
+            self.range.format(self.include_lower, self.include_upper)
+        )
+    }
+}
+
+impl DocValuesRange {
+    fn format(&self, include_lower: bool, include_upper: bool) -> String {
+        let (lower, upper) = match self {
+            DocValuesRange::Long { lower, upper } => {
+                (lower.map(|v| v.to_string()), upper.map(|v| v.to_string()))
+            }
+            DocValuesRange::Bytes { lower, upper } => (
+                lower
+                    .as_ref()
+                    .map(|v| String::from_utf8_lossy(v).into_owned()),
+                upper
+                    .as_ref()
+                    .map(|v| String::from_utf8_lossy(v).into_owned()),
+            ),
+        };
+        format!(
+            "{}{} TO {}{}",
+            if include_lower { '[' } else { '{' },
+            lower.as_ref().map_or("*", String::as_str),
+            upper.as_ref().map_or("*", String::as_str),
+            if include_upper { ']' } else { '}' }
+        )
+    }
+}
+
+struct DocValuesRangeWeight {
+    field: String,
+    range: DocValuesRange,
+    include_lower: bool,
+    include_upper: bool,
+    weight: f32,
+    norm: f32,
+}
+
+impl DocValuesRangeWeight {
+    /// The inclusive bounds of a long range, or `None` if it is empty.
+    fn long_bounds(&self, lower: Option<i64>, upper: Option<i64>) -> Option<(i64, i64)> {
+        let min = match lower {
+            None => i64::min_value(),
+            Some(v) if self.include_lower => v,
+            Some(v) => v.checked_add(1)?,
+        };
+        let max = match upper {
+            None => i64::max_value(),
+            Some(v) if self.include_upper => v,
+            Some(v) => v.checked_sub(1)?,
+        };
+        if min <= max {
+            Some((min, max))
+        } else {
+            None
+        }
+    }
+
+    /// The inclusive ordinal bounds of a bytes range, or `None` if it is empty.
+    fn ord_bounds(
+        &self,
+        lower: &Option<Vec<u8>>,
+        upper: &Option<Vec<u8>>,
+        value_count: i64,
+        lookup_term: &mut dyn FnMut(&[u8]) -> Result<i64>,
+    ) -> Result<Option<(i64, i64)>> {
+        let min_ord = match lower {
+            None => 0,
+            Some(term) => match lookup_term(term)? {
+                ord if ord >= 0 && self.include_lower => ord,
+                ord if ord >= 0 => ord + 1,
+                ord => -ord - 1,
+            },
+        };
+        let max_ord = match upper {
+            None => value_count - 1,
+            Some(term) => match lookup_term(term)? {
+                ord if ord >= 0 && self.include_upper => ord,
+                ord if ord >= 0 => ord - 1,
+                ord => -ord - 2,
+            },
+        };
+        if min_ord <= max_ord {
+            Ok(Some((min_ord, max_ord)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn matcher<C: Codec>(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn DocValuesMatcher>>> {
+        let leaf_reader = reader.reader;
+        let doc_values_type = match leaf_reader.field_info(&self.field) {
+            Some(field_info) => field_info.doc_values_type,
+            None => return Ok(None),
+        };
+        let matcher: Box<dyn DocValuesMatcher> = match (&self.range, doc_values_type) {
+            (_, DocValuesType::Null) => return Ok(None),
+            (DocValuesRange::Long { lower, upper }, DocValuesType::Numeric) => {
+                let (min, max) = match self.long_bounds(*lower, *upper) {
+                    Some(bounds) => bounds,
+                    None => return Ok(None),
+                };
+                Box::new(NumericRangeMatcher {
+                    values: leaf_reader.get_numeric_doc_values(&self.field)?,
+                    docs_with_field: leaf_reader.get_docs_with_field(&self.field)?,
+                    min,
+                    max,
+                })
+            }
+            (DocValuesRange::Long { lower, upper }, DocValuesType::SortedNumeric) => {
+                let (min, max) = match self.long_bounds(*lower, *upper) {
+                    Some(bounds) => bounds,
+                    None => return Ok(None),
+                };
+                Box::new(SortedNumericRangeMatcher {
+                    values: leaf_reader.get_sorted_numeric_doc_values(&self.field)?,
+                    min,
+                    max,
+                })
+            }
+            (DocValuesRange::Bytes { lower, upper }, DocValuesType::Sorted) => {
+                let mut values = leaf_reader.get_sorted_doc_values(&self.field)?;
+                let value_count = values.value_count() as i64;
+                let bounds = self.ord_bounds(lower, upper, value_count, &mut |term| {
+                    values.lookup_term(term).map(i64::from)
+                })?;
+                let (min, max) = match bounds {
+                    Some(bounds) => bounds,
+                    None => return Ok(None),
+                };
+                Box::new(SortedMatcher::new(values, move |ord| {
+                    ord >= min && ord <= max
+                }))
+            }
+            (DocValuesRange::Bytes { lower, upper }, DocValuesType::SortedSet) => {
+                let mut values = leaf_reader.get_sorted_set_doc_values(&self.field)?;
+                let value_count = values.get_value_count() as i64;
+                let bounds = self.ord_bounds(lower, upper, value_count, &mut |term| {
+                    values.lookup_term(term)
+                })?;
+                let (min, max) = match bounds {
+                    Some(bounds) => bounds,
+                    None => return Ok(None),
+                };
+                Box::new(SortedSetMatcher::new(values, move |ord| {
+                    ord >= min && ord <= max
+                }))
+            }
+            (DocValuesRange::Long { .. }, _) => bail!(ErrorKind::IllegalArgument(format!(
+                "field '{}' has doc values of type {:?}, but a long range needs numeric or \
+                 sorted_numeric doc values",
+                &self.field, doc_values_type
+            ))),
+            (DocValuesRange::Bytes { .. }, _) => bail!(ErrorKind::IllegalArgument(format!(
+                "field '{}' has doc values of type {:?}, but a bytes range needs sorted or \
+                 sorted_set doc values",
+                &self.field, doc_values_type
+            ))),
+        };
+        Ok(Some(matcher))
+    }
+}
+
+impl<C: Codec> Weight<C> for DocValuesRangeWeight {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self
+            .matcher(reader)?
+            .map(|matcher| doc_values_scorer(self.weight, reader.reader.max_doc(), matcher)))
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_RANGE
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.matcher(reader)? {
+            Some(mut matcher) => matcher.matches(doc)?,
+            None => false,
+        };
+        constant_score_explanation(matches, self.weight, self.norm, self, doc)
+    }
+}
+
+impl fmt::Display for DocValuesRangeWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesRangeWeight(field: {}, range: {})",
+            &self.field,
+            self.range.format(self.include_lower, self.include_upper)
+        )
+    }
+}
+
+/// Checks whether a single document matches by looking at its doc values.
+pub(crate) trait DocValuesMatcher: Send {
+    fn matches(&mut self, doc: DocId) -> Result<bool>;
+
+    /// The expected cost of a single `matches` call.
+    fn match_cost(&self) -> f32;
+}
+
+struct NumericRangeMatcher {
+    values: Box<dyn NumericDocValues>,
+    docs_with_field: Box<dyn BitsMut>,
+    min: i64,
+    max: i64,
+}
+
+impl DocValuesMatcher for NumericRangeMatcher {
+    fn matches(&mut self, doc: DocId) -> Result<bool> {
+        let value = self.values.get_mut(doc)?;
+        // documents without a value read as 0
+        Ok(value >= self.min
+            && value <= self.max
+            && (value != 0 || self.docs_with_field.get(doc as usize)?))
+    }
+
+    fn match_cost(&self) -> f32 {
+        2f32
+    }
+}
+
+struct SortedNumericRangeMatcher {
+    values: Box<dyn SortedNumericDocValues>,
+    min: i64,
+    max: i64,
+}
+
+impl DocValuesMatcher for SortedNumericRangeMatcher {
+    fn matches(&mut self, doc: DocId) -> Result<bool> {
+        self.values.set_document(doc)?;
+        for i in 0..self.values.count() {
+            let value = self.values.value_at(i)?;
+            if value >= self.min && value <= self.max {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn match_cost(&self) -> f32 {
+        // we do not know the number of values per doc, assume a few of them
+        4f32
+    }
+}
+
+/// Matches the documents of `Sorted` doc values whose ordinal is accepted.
+pub(crate) struct SortedMatcher<F> {
+    values: Box<dyn SortedDocValues>,
+    accept_ord: F,
+}
+
+impl<F: Fn(i64) -> bool + Send> SortedMatcher<F> {
+    pub(crate) fn new(values: Box<dyn SortedDocValues>, accept_ord: F) -> Self {
+        SortedMatcher { values, accept_ord }
+    }
+}
+
+impl<F: Fn(i64) -> bool + Send> DocValuesMatcher for SortedMatcher<F> {
+    fn matches(&mut self, doc: DocId) -> Result<bool> {
+        let ord = self.values.get_ord(doc)?;
+        Ok(ord >= 0 && (self.accept_ord)(i64::from(ord)))
+    }
+
+    fn match_cost(&self) -> f32 {
+        2f32
+    }
+}
+
+/// Matches the documents of `SortedSet` doc values having at least one accepted ordinal.
+pub(crate) struct SortedSetMatcher<F> {
+    values: Box<dyn SortedSetDocValues>,
+    accept_ord: F,
+}
+
+impl<F: Fn(i64) -> bool + Send> SortedSetMatcher<F> {
+    pub(crate) fn new(values: Box<dyn SortedSetDocValues>, accept_ord: F) -> Self {
+        SortedSetMatcher { values, accept_ord }
+    }
+}
+
+impl<F: Fn(i64) -> bool + Send> DocValuesMatcher for SortedSetMatcher<F> {
+    fn matches(&mut self, doc: DocId) -> Result<bool> {
+        self.values.set_document(doc)?;
+        loop {
+            let ord = self.values.next_ord()?;
+            if ord == NO_MORE_ORDS {
+                return Ok(false);
+            }
+            if (self.accept_ord)(ord) {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn match_cost(&self) -> f32 {
+        4f32
+    }
+}
+
+/// A two-phase `DocIterator` whose approximation is all documents of a segment
+/// and which verifies matches against doc values.
+pub(crate) struct DocValuesTwoPhaseIterator {
+    approximation: AllDocsIterator,
+    matcher: Box<dyn DocValuesMatcher>,
+}
+
+impl DocValuesTwoPhaseIterator {
+    fn verify(&mut self, mut doc: DocId) -> Result<DocId> {
+        while doc != NO_MORE_DOCS && !self.matcher.matches(doc)? {
+            doc = self.approximation.next()?;
+        }
+        Ok(doc)
+    }
+}
+
+impl DocIterator for DocValuesTwoPhaseIterator {
+    fn doc_id(&self) -> DocId {
+        self.approximation.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.approximation.next()?;
+        self.verify(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.approximation.advance(target)?;
+        self.verify(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.approximation.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        let doc = self.approximation.doc_id();
+        self.matcher.matches(doc)
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.matcher.match_cost()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.approximation.next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.approximation.advance(target)
+    }
+}
+
+/// Creates a constant score `Scorer` that verifies the docs of a segment with `matcher`.
+pub(crate) fn doc_values_scorer(
+    score: f32,
+    max_doc: DocId,
+    matcher: Box<dyn DocValuesMatcher>,
+) -> Box<dyn Scorer> {
+    let iterator = DocValuesTwoPhaseIterator {
+        approximation: AllDocsIterator::new(max_doc),
+        matcher,
+    };
+    let cost = iterator.cost();
+    Box::new(ConstantScoreScorer::new(score, iterator, cost))
+}
+
+pub(crate) fn constant_score_explanation(
+    matches: bool,
+    weight: f32,
+    norm: f32,
+    description: &dyn fmt::Display,
+    doc: DocId,
+) -> Result<Explanation> {
+    if matches {
+        Ok(Explanation::new(
+            true,
+            weight,
+            format!("{}, product of:", description),
+            vec![
+                Explanation::new(true, weight / norm, "boost".into(), vec![]),
+                Explanation::new(true, norm, "queryNorm".into(), vec![]),
+            ],
+        ))
+    } else {
+        Ok(Explanation::new(
+            false,
+            0f32,
+            format!("{} doesn't match id {}", description, doc),
+            vec![],
+        ))
+    }
+}
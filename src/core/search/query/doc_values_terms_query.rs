@@ -0,0 +1,216 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::doc::DocValuesType;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::doc_values_range_query::{
+    constant_score_explanation, doc_values_scorer, DocValuesMatcher, SortedMatcher,
+    SortedSetMatcher,
+};
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::util::bkd::LongBitSet;
+use core::util::DocId;
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const DOC_VALUES_TERMS: &str = "doc_values_terms";
+
+/// A `Query` that only accepts documents whose single term value in the specified
+/// field is contained in the provided set of allowed terms.
+///
+/// This is the same functionality as a `BooleanQuery` of `TermQuery` shoulds wrapped
+/// in a `ConstantScoreQuery`, except this query works on doc values instead of the
+/// inverted index: the field must have been indexed with `DocValuesType::Sorted` or
+/// `DocValuesType::SortedSet` doc values, and documents are checked one by one, which
+/// makes it a good fit for filtering the results of a selective query.
+pub struct DocValuesTermsQuery {
+    field: String,
+    terms: Vec<Vec<u8>>,
+}
+
+impl DocValuesTermsQuery {
+    pub fn new(field: String, mut terms: Vec<Vec<u8>>) -> DocValuesTermsQuery {
+        terms.sort();
+        terms.dedup();
+        DocValuesTermsQuery { field, terms }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The sorted and deduplicated terms of this query.
+    pub fn terms(&self) -> &[Vec<u8>] {
+        &self.terms
+    }
+}
+
+impl<C: Codec> Query<C> for DocValuesTermsQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(DocValuesTermsWeight {
+            field: self.field.clone(),
+            terms: self.terms.clone(),
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for DocValuesTermsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesTermsQuery(field: {}, terms: [{}])",
+            &self.field,
+            format_terms(&self.terms)
+        )
+    }
+}
+
+fn format_terms(terms: &[Vec<u8>]) -> String {
+    terms
+        .iter()
+        .map(|t| String::from_utf8_lossy(t))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct DocValuesTermsWeight {
+    field: String,
+    terms: Vec<Vec<u8>>,
+    weight: f32,
+    norm: f32,
+}
+
+impl DocValuesTermsWeight {
+    /// Looks up the ordinals of the terms, returns `None` if none of them exists.
+    fn ords(
+        &self,
+        value_count: usize,
+        lookup_term: &mut dyn FnMut(&[u8]) -> Result<i64>,
+    ) -> Result<Option<LongBitSet>> {
+        let mut ords = LongBitSet::new(value_count as i64);
+        let mut found = false;
+        for term in &self.terms {
+            let ord = lookup_term(term)?;
+            if ord >= 0 {
+                ords.set(ord);
+                found = true;
+            }
+        }
+        Ok(if found { Some(ords) } else { None })
+    }
+
+    fn matcher<C: Codec>(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn DocValuesMatcher>>> {
+        let leaf_reader = reader.reader;
+        let doc_values_type = match leaf_reader.field_info(&self.field) {
+            Some(field_info) => field_info.doc_values_type,
+            None => return Ok(None),
+        };
+        let matcher: Box<dyn DocValuesMatcher> = match doc_values_type {
+            DocValuesType::Null => return Ok(None),
+            DocValuesType::Sorted => {
+                let mut values = leaf_reader.get_sorted_doc_values(&self.field)?;
+                let value_count = values.value_count();
+                let ords = match self.ords(value_count, &mut |term| {
+                    values.lookup_term(term).map(i64::from)
+                })? {
+                    Some(ords) => ords,
+                    None => return Ok(None),
+                };
+                Box::new(SortedMatcher::new(values, move |ord| ords.get(ord)))
+            }
+            DocValuesType::SortedSet => {
+                let mut values = leaf_reader.get_sorted_set_doc_values(&self.field)?;
+                let value_count = values.get_value_count();
+                let ords = match self.ords(value_count, &mut |term| values.lookup_term(term))? {
+                    Some(ords) => ords,
+                    None => return Ok(None),
+                };
+                Box::new(SortedSetMatcher::new(values, move |ord| ords.get(ord)))
+            }
+            _ => bail!(ErrorKind::IllegalArgument(format!(
+                "field '{}' has doc values of type {:?}, but a terms query needs sorted or \
+                 sorted_set doc values",
+                &self.field, doc_values_type
+            ))),
+        };
+        Ok(Some(matcher))
+    }
+}
+
+impl<C: Codec> Weight<C> for DocValuesTermsWeight {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self
+            .matcher(reader)?
+            .map(|matcher| doc_values_scorer(self.weight, reader.reader.max_doc(), matcher)))
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_TERMS
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.matcher(reader)? {
+            Some(mut matcher) => matcher.matches(doc)?,
+            None => false,
+        };
+        constant_score_explanation(matches, self.weight, self.norm, self, doc)
+    }
+}
+
+impl fmt::Display for DocValuesTermsWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesTermsWeight(field: {}, terms: [{}])",
+            &self.field,
+            format_terms(&self.terms)
+        )
+    }
+}
@@ -35,6 +35,10 @@ impl ExistsQuery {
     pub fn build(field: String) -> ExistsQuery {
         ExistsQuery { field }
     }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
 }
 
 impl<C: Codec> Query<C> for ExistsQuery {
@@ -141,3 +145,49 @@ impl fmt::Display for ExistsWeight {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{Fieldable, NumericDocValuesField};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_exists_query_on_numeric_field() {
+        let mut docs = vec![];
+        for i in 0..10 {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("id", i))];
+            if i % 3 == 0 {
+                doc.push(Box::new(NumericDocValuesField::new("price", i * 10)));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let docs = |field: &str| -> Vec<DocId> {
+            let mut collector = TopDocsCollector::new(20);
+            searcher
+                .search(&ExistsQuery::build(field.into()), &mut collector)
+                .unwrap();
+            let mut docs: Vec<DocId> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| d.doc_id())
+                .collect();
+            docs.sort();
+            docs
+        };
+
+        // the documents without a numeric value don't match, even though it reads as 0
+        assert_eq!(docs("price"), vec![0, 3, 6, 9]);
+        assert_eq!(docs("id").len(), 10);
+        assert!(docs("missing").is_empty());
+    }
+}
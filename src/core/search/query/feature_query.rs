@@ -0,0 +1,637 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::f32;
+use std::fmt;
+
+use core::codec::Codec;
+use core::codec::{PostingIterator, PostingIteratorFlags};
+use core::doc::{FeatureField, Term};
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::DocIterator;
+use core::util::DocId;
+use error::{ErrorKind, Result};
+
+pub const FEATURE: &str = "feature";
+
+/// The function turning the value `S` of a feature into a score.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeatureFunction {
+    /// `S / (S + pivot)`, which is 0.5 for the pivot value and tends to 1 as the
+    /// value grows. A `None` pivot is computed from the index statistics as an
+    /// approximation of the geometric mean of the feature values.
+    Saturation { pivot: Option<f32> },
+    /// `ln(scaling_factor + S)`, the scaling factor being at least 1.
+    Log { scaling_factor: f32 },
+    /// `S^exp / (S^exp + pivot^exp)`, a generalization of the saturation which
+    /// also tends to 1 but gives more control over the shape of the curve.
+    Sigmoid { pivot: f32, exp: f32 },
+    /// `S`, the feature value as is. Its max score is only bounded by the max
+    /// feature value, so it hardly ever allows early termination.
+    Linear,
+}
+
+impl FeatureFunction {
+    fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            FeatureFunction::Saturation { pivot: Some(pivot) } => pivot.is_finite() && pivot > 0.0,
+            FeatureFunction::Saturation { pivot: None } => true,
+            FeatureFunction::Log { scaling_factor } => {
+                scaling_factor.is_finite() && scaling_factor >= 1.0
+            }
+            FeatureFunction::Sigmoid { pivot, exp } => {
+                pivot.is_finite() && pivot > 0.0 && exp.is_finite() && exp > 0.0
+            }
+            FeatureFunction::Linear => true,
+        };
+        if !valid {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "invalid feature function {}",
+                self
+            )));
+        }
+        Ok(())
+    }
+
+    fn value(&self, feature_value: f32) -> f32 {
+        match *self {
+            FeatureFunction::Saturation { pivot } => {
+                let pivot = pivot.unwrap_or(1.0);
+                feature_value / (feature_value + pivot)
+            }
+            FeatureFunction::Log { scaling_factor } => (scaling_factor + feature_value).ln(),
+            FeatureFunction::Sigmoid { pivot, exp } => {
+                1.0 / (1.0 + (pivot / feature_value).powf(exp))
+            }
+            FeatureFunction::Linear => feature_value,
+        }
+    }
+
+    /// Lower bound of the function, which is increasing.
+    fn min_value(&self) -> f32 {
+        self.value(0.0)
+    }
+
+    /// Upper bound of the function, which is increasing.
+    fn max_value(&self) -> f32 {
+        match *self {
+            FeatureFunction::Saturation { .. } | FeatureFunction::Sigmoid { .. } => 1.0,
+            _ => self.value(f32::MAX),
+        }
+    }
+
+    fn formula(&self) -> &'static str {
+        match *self {
+            FeatureFunction::Saturation { .. } => "w * S / (S + k)",
+            FeatureFunction::Log { .. } => "w * ln(a + S)",
+            FeatureFunction::Sigmoid { .. } => "w * S^a / (S^a + k^a)",
+            FeatureFunction::Linear => "w * S",
+        }
+    }
+
+    fn explain_params(&self) -> Vec<Explanation> {
+        match *self {
+            FeatureFunction::Saturation { pivot } => vec![Explanation::new(
+                true,
+                pivot.unwrap_or(1.0),
+                "k, pivot feature value that would give a score contribution equal to w/2".into(),
+                vec![],
+            )],
+            FeatureFunction::Log { scaling_factor } => vec![Explanation::new(
+                true,
+                scaling_factor,
+                "a, scaling factor".into(),
+                vec![],
+            )],
+            FeatureFunction::Sigmoid { pivot, exp } => vec![
+                Explanation::new(
+                    true,
+                    pivot,
+                    "k, pivot feature value that would give a score contribution equal to w/2"
+                        .into(),
+                    vec![],
+                ),
+                Explanation::new(
+                    true,
+                    exp,
+                    "a, exponent, higher values make the function grow slower before k and faster \
+                     after k"
+                        .into(),
+                    vec![],
+                ),
+            ],
+            FeatureFunction::Linear => vec![],
+        }
+    }
+}
+
+impl fmt::Display for FeatureFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FeatureFunction::Saturation { pivot: Some(pivot) } => {
+                write!(f, "Saturation(pivot: {})", pivot)
+            }
+            FeatureFunction::Saturation { pivot: None } => write!(f, "Saturation"),
+            FeatureFunction::Log { scaling_factor } => {
+                write!(f, "Log(scaling_factor: {})", scaling_factor)
+            }
+            FeatureFunction::Sigmoid { pivot, exp } => {
+                write!(f, "Sigmoid(pivot: {}, exp: {})", pivot, exp)
+            }
+            FeatureFunction::Linear => write!(f, "Linear"),
+        }
+    }
+}
+
+/// A query scoring the documents by the value of a feature indexed with a
+/// `FeatureField`, transformed by a `FeatureFunction`.
+///
+/// Unlike scoring a doc values field with a `FunctionScoreQuery`, the feature
+/// is read from the postings of its term, and the max score of the scorer is
+/// known, so that a `TopDocsCollector` with early termination may skip the
+/// documents that can't compete anymore. The query is typically added as a
+/// should clause of a `BooleanQuery` to boost the documents matching the
+/// other clauses. The max score of a `BooleanQuery` being the sum of the max
+/// scores of its clauses, it is only bounded if all of them are, which isn't
+/// the case of e.g. a `TermQuery`.
+#[derive(Clone, Debug)]
+pub struct FeatureQuery {
+    field: String,
+    feature_name: String,
+    function: FeatureFunction,
+    boost: f32,
+}
+
+impl FeatureQuery {
+    pub fn new(field: &str, feature_name: &str, function: FeatureFunction) -> Result<FeatureQuery> {
+        function.validate()?;
+        Ok(FeatureQuery {
+            field: field.to_string(),
+            feature_name: feature_name.to_string(),
+            function,
+            boost: 1.0,
+        })
+    }
+
+    /// The weight `w` of the feature, multiplying the function. It must be a
+    /// positive number.
+    pub fn with_boost(mut self, boost: f32) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn feature_name(&self) -> &str {
+        &self.feature_name
+    }
+
+    pub fn function(&self) -> FeatureFunction {
+        self.function
+    }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
+
+    fn term(&self) -> Term {
+        Term::new(self.field.clone(), self.feature_name.as_bytes().to_vec())
+    }
+
+    /// Computes the pivot of a saturation from the average encoded value, which
+    /// decodes to roughly the geometric mean of the feature values.
+    fn compute_pivot<C: Codec>(&self, searcher: &dyn SearchPlanBuilder<C>) -> Result<f32> {
+        let stats = searcher.term_statistics(&self.term())?;
+        if stats.doc_freq <= 0 || stats.total_term_freq <= 0 {
+            return Ok(1.0);
+        }
+        let avg_freq = stats.total_term_freq as f64 / stats.doc_freq as f64;
+        Ok(FeatureField::decode_feature_value(avg_freq as u32))
+    }
+}
+
+impl<C: Codec> Query<C> for FeatureQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let function = match self.function {
+            FeatureFunction::Saturation { pivot: None } if needs_scores => {
+                FeatureFunction::Saturation {
+                    pivot: Some(self.compute_pivot(searcher)?),
+                }
+            }
+            function => function,
+        };
+        if !(self.boost.is_finite() && self.boost > 0.0) {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "feature query boost must be a positive number, got {}",
+                self.boost
+            )));
+        }
+        Ok(Box::new(FeatureWeight {
+            term: self.term(),
+            function,
+            weight: self.boost,
+            boost: self.boost,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        // the feature term is not a text term worth highlighting
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for FeatureQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FeatureQuery(field: {}, feature: {}, function: {}, boost: {})",
+            &self.field, &self.feature_name, &self.function, self.boost
+        )
+    }
+}
+
+struct FeatureWeight {
+    term: Term,
+    function: FeatureFunction,
+    /// the weight `w` of the feature, including the boosts of the parent queries
+    weight: f32,
+    boost: f32,
+    needs_scores: bool,
+}
+
+impl<C: Codec> Weight<C> for FeatureWeight {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        let flags = i32::from(PostingIteratorFlags::FREQS);
+        if let Some(postings) = reader.reader.postings(&self.term, flags)? {
+            Ok(Some(Box::new(FeatureScorer {
+                postings,
+                function: self.function,
+                weight: self.weight,
+            })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        FEATURE
+    }
+
+    fn normalize(&mut self, _norm: f32, boost: f32) {
+        self.weight = self.boost * boost;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let flags = i32::from(PostingIteratorFlags::FREQS);
+        if let Some(mut postings) = reader.reader.postings(&self.term, flags)? {
+            if postings.advance(doc)? == doc {
+                let feature_value = FeatureField::decode_feature_value(postings.freq()? as u32);
+                let score = self.weight * self.function.value(feature_value);
+
+                let mut details = vec![Explanation::new(
+                    true,
+                    self.weight,
+                    "w, weight of this function".into(),
+                    vec![],
+                )];
+                details.extend(self.function.explain_params());
+                details.push(Explanation::new(
+                    true,
+                    feature_value,
+                    "S, feature value".into(),
+                    vec![],
+                ));
+                return Ok(Explanation::new(
+                    true,
+                    score,
+                    format!(
+                        "{} function on the {} field for the {} feature, computed as {} from:",
+                        self.function,
+                        &self.term.field,
+                        &self.term.text()?,
+                        self.function.formula()
+                    ),
+                    details,
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0.0,
+            format!("feature {} is not set", &self.term.text()?),
+            vec![],
+        ))
+    }
+}
+
+impl fmt::Display for FeatureWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FeatureWeight(field: {}, feature: {}, function: {}, weight: {})",
+            &self.term.field,
+            &self.term.text().unwrap(),
+            &self.function,
+            self.weight
+        )
+    }
+}
+
+struct FeatureScorer<T: PostingIterator> {
+    postings: T,
+    function: FeatureFunction,
+    weight: f32,
+}
+
+impl<T: PostingIterator> Scorer for FeatureScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        let feature_value = FeatureField::decode_feature_value(self.postings.freq()? as u32);
+        Ok(self.weight * self.function.value(feature_value))
+    }
+
+    fn max_score(&self) -> f32 {
+        // a parent query may still turn the weight negative
+        let max_score = self.weight * self.function.max_value();
+        let min_score = self.weight * self.function.min_value();
+        max_score.max(min_score)
+    }
+}
+
+impl<T: PostingIterator> DocIterator for FeatureScorer<T> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.postings.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.postings.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::CodecEnum;
+    use core::doc::Fieldable;
+    use core::index::reader::IndexReader;
+    use core::index::writer::IndexWriterConfig;
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::{index_fields, new_index_writer};
+    use core::search::query::{BooleanQuery, BoostQuery};
+    use core::search::{DefaultIndexSearcher, IndexSearcher, NO_MORE_DOCS};
+
+    use std::sync::Arc;
+
+    fn feature(name: &str, value: f32) -> Box<dyn Fieldable> {
+        Box::new(FeatureField::new("features", name, value).unwrap())
+    }
+
+    #[test]
+    fn test_feature_query() {
+        let (_dir, writer) = new_index_writer(IndexWriterConfig::default());
+
+        let pageranks = [10.0, 0.5, 3.0, 42.0];
+        for &pagerank in &pageranks {
+            writer
+                .add_document(vec![feature("pagerank", pagerank), feature("upvotes", 7.0)])
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        writer.add_document(vec![feature("upvotes", 3.0)]).unwrap();
+        for _ in 0..10 {
+            writer.add_document(vec![feature("spam", 1e30)]).unwrap();
+        }
+        writer.commit().unwrap();
+        // the merged segment keeps the exact feature values
+        writer.force_merge(1, true).unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+
+        let functions = [
+            FeatureFunction::Saturation { pivot: None },
+            FeatureFunction::Saturation { pivot: Some(5.0) },
+            FeatureFunction::Log {
+                scaling_factor: 1.0,
+            },
+            FeatureFunction::Sigmoid {
+                pivot: 5.0,
+                exp: 0.6,
+            },
+            FeatureFunction::Linear,
+        ];
+        for &function in &functions {
+            let query = FeatureQuery::new("features", "pagerank", function)
+                .unwrap()
+                .with_boost(2.0);
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(&query, &mut collector).unwrap();
+            let top_docs = collector.top_docs();
+            let hits: Vec<(DocId, f32)> = top_docs
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect();
+            // the merge may have moved the pagerank documents
+            let base = hits.iter().map(|h| h.0).min().unwrap();
+            let docs: Vec<DocId> = hits.iter().map(|h| h.0 - base).collect();
+            assert_eq!(docs, vec![3, 0, 2, 1]);
+            for (doc, score) in &hits {
+                let expl = searcher.explain(&query, *doc).unwrap();
+                assert!(expl.is_match());
+                assert!((expl.value() - score).abs() < 1e-5);
+            }
+            let missing = (0..).find(|d| hits.iter().all(|h| h.0 != *d)).unwrap();
+            assert!(!searcher.explain(&query, missing).unwrap().is_match());
+        }
+
+        // the linear function scores the decoded value
+        let query = FeatureQuery::new("features", "upvotes", FeatureFunction::Linear).unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 5);
+        assert!((top_docs.score_docs()[0].score() - 7.0).abs() < 1e-5);
+        assert!((top_docs.score_docs()[4].score() - 3.0).abs() < 1e-5);
+
+        assert!(FeatureQuery::new(
+            "features",
+            "pagerank",
+            FeatureFunction::Saturation { pivot: Some(0.0) }
+        )
+        .is_err());
+        assert!(FeatureQuery::new(
+            "features",
+            "pagerank",
+            FeatureFunction::Log {
+                scaling_factor: 0.5
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_feature_query_early_termination() {
+        // saturated values all score the max score
+        let docs = (0..10).map(|_| vec![feature("pagerank", 1e30)]).collect();
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let query = FeatureQuery::new(
+            "features",
+            "pagerank",
+            FeatureFunction::Saturation { pivot: Some(1.0) },
+        )
+        .unwrap();
+
+        let mut collector = TopDocsCollector::new(3);
+        searcher.search(&query, &mut collector).unwrap();
+        let full = collector.top_docs();
+        assert_eq!(full.total_hits(), 10);
+
+        let mut collector = TopDocsCollector::new(3).with_early_termination(true);
+        searcher.search(&query, &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 3);
+        let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        let full_docs: Vec<DocId> = full.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(docs, full_docs);
+
+        // the max score of a linear function is unbounded in practice
+        let query = FeatureQuery::new("features", "pagerank", FeatureFunction::Linear).unwrap();
+        let mut collector = TopDocsCollector::new(3).with_early_termination(true);
+        searcher.search(&query, &mut collector).unwrap();
+        assert_eq!(collector.top_docs().total_hits(), 10);
+    }
+
+    #[test]
+    fn test_feature_query_early_termination_in_boolean_query() {
+        let mut docs = vec![];
+        for i in 0..10 {
+            let mut doc = vec![feature("pagerank", 1e30), feature("upvotes", 1e30)];
+            if i % 4 == 0 {
+                doc.push(feature("spam", 1.0));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let feature_query = |name: &str| -> Box<dyn Query<CodecEnum>> {
+            let function = FeatureFunction::Saturation { pivot: Some(1.0) };
+            Box::new(FeatureQuery::new("features", name, function).unwrap())
+        };
+        let boosted = || BoostQuery::build(feature_query("upvotes"), 2.0);
+
+        // a disjunction, a conjunction, and required and optional clauses
+        let queries = vec![
+            BooleanQuery::build(
+                vec![],
+                vec![feature_query("pagerank"), boosted()],
+                vec![],
+                vec![],
+                0,
+            )
+            .unwrap(),
+            BooleanQuery::build(
+                vec![feature_query("pagerank"), boosted()],
+                vec![],
+                vec![],
+                vec![],
+                0,
+            )
+            .unwrap(),
+            BooleanQuery::build(
+                vec![feature_query("pagerank")],
+                vec![boosted()],
+                vec![],
+                vec![feature_query("spam")],
+                0,
+            )
+            .unwrap(),
+        ];
+        for query in &queries {
+            let mut collector = TopDocsCollector::new(3);
+            searcher.search(query.as_ref(), &mut collector).unwrap();
+            let full = collector.top_docs();
+            assert!(full.total_hits() > 3);
+
+            let mut collector = TopDocsCollector::new(3).with_early_termination(true);
+            searcher.search(query.as_ref(), &mut collector).unwrap();
+            let top_docs = collector.top_docs();
+            assert_eq!(top_docs.total_hits(), 3);
+            let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+            let full_docs: Vec<DocId> = full.score_docs().iter().map(|d| d.doc_id()).collect();
+            assert_eq!(docs, full_docs);
+        }
+    }
+
+    #[test]
+    fn test_feature_query_boost() {
+        let docs = (0..10)
+            .map(|i| vec![feature("pagerank", (i + 1) as f32)])
+            .collect();
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let function = FeatureFunction::Saturation { pivot: Some(5.0) };
+        for &boost in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let query = FeatureQuery::new("features", "pagerank", function)
+                .unwrap()
+                .with_boost(boost);
+            let mut collector = TopDocsCollector::new(3);
+            assert!(searcher.search(&query, &mut collector).is_err());
+        }
+
+        // a negative parent boost turns the smallest feature values into the best scores
+        let query = FeatureQuery::new("features", "pagerank", function).unwrap();
+        let mut weight = searcher.create_weight(&query, true).unwrap();
+        weight.normalize(1.0, -1.0);
+        for leaf in searcher.reader().leaves() {
+            let mut scorer = weight.create_scorer(&leaf).unwrap().unwrap();
+            let max_score = scorer.max_score();
+            assert!((max_score - 0.0).abs() < 1e-5);
+            while scorer.next().unwrap() != NO_MORE_DOCS {
+                assert!(scorer.score().unwrap() <= max_score);
+            }
+        }
+    }
+}
@@ -0,0 +1,739 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{LeafScoreFunction, Query, ScoreFunction, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::DocIterator;
+use core::util::DocId;
+use error::{ErrorKind, Result};
+
+const FUNCTION_SCORE_QUERY: &str = "function_score";
+
+/// How the scores of the functions matching a document are combined.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FunctionScoreMode {
+    Multiply,
+    Sum,
+    /// The weighted average of the function scores, 1 if all their weights
+    /// are 0
+    Avg,
+    /// The score of the first function whose filter matches
+    First,
+    Max,
+    Min,
+}
+
+impl FunctionScoreMode {
+    /// Combine the `(weighted score, weight)` of the matching functions,
+    /// 1 if no function matches.
+    fn combine(self, scores: &[(f64, f64)]) -> f64 {
+        if scores.is_empty() {
+            return 1.0;
+        }
+        match self {
+            FunctionScoreMode::Multiply => scores.iter().map(|s| s.0).product(),
+            FunctionScoreMode::Sum => scores.iter().map(|s| s.0).sum(),
+            FunctionScoreMode::Avg => {
+                let weight_sum: f64 = scores.iter().map(|s| s.1).sum();
+                if weight_sum == 0.0 {
+                    return 1.0;
+                }
+                scores.iter().map(|s| s.0).sum::<f64>() / weight_sum
+            }
+            FunctionScoreMode::First => scores[0].0,
+            FunctionScoreMode::Max => scores.iter().map(|s| s.0).fold(f64::MIN, f64::max),
+            FunctionScoreMode::Min => scores.iter().map(|s| s.0).fold(f64::MAX, f64::min),
+        }
+    }
+}
+
+impl fmt::Display for FunctionScoreMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FunctionScoreMode::Multiply => write!(f, "multiply"),
+            FunctionScoreMode::Sum => write!(f, "sum"),
+            FunctionScoreMode::Avg => write!(f, "avg"),
+            FunctionScoreMode::First => write!(f, "first"),
+            FunctionScoreMode::Max => write!(f, "max"),
+            FunctionScoreMode::Min => write!(f, "min"),
+        }
+    }
+}
+
+/// How the combined score of the functions is applied to the query score.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BoostMode {
+    Multiply,
+    /// Only the function score is used, the query score is ignored
+    Replace,
+    Sum,
+    Avg,
+    Max,
+    Min,
+}
+
+impl BoostMode {
+    pub fn combine(self, query_score: f32, function_score: f32) -> f32 {
+        match self {
+            BoostMode::Multiply => query_score * function_score,
+            BoostMode::Replace => function_score,
+            BoostMode::Sum => query_score + function_score,
+            BoostMode::Avg => (query_score + function_score) / 2.0,
+            BoostMode::Max => query_score.max(function_score),
+            BoostMode::Min => query_score.min(function_score),
+        }
+    }
+}
+
+impl fmt::Display for BoostMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BoostMode::Multiply => write!(f, "multiply"),
+            BoostMode::Replace => write!(f, "replace"),
+            BoostMode::Sum => write!(f, "sum"),
+            BoostMode::Avg => write!(f, "avg"),
+            BoostMode::Max => write!(f, "max"),
+            BoostMode::Min => write!(f, "min"),
+        }
+    }
+}
+
+/// A `ScoreFunction` only applied to the documents matching `filter`, if any,
+/// with its score multiplied by `weight`.
+pub struct FilteredFunction<C: Codec> {
+    filter: Option<Box<dyn Query<C>>>,
+    function: ScoreFunction,
+    weight: f32,
+}
+
+impl<C: Codec> FilteredFunction<C> {
+    pub fn new(function: ScoreFunction) -> Self {
+        FilteredFunction {
+            filter: None,
+            function,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: Box<dyn Query<C>>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Result<Self> {
+        if weight.is_nan() || weight < 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "function weight must not be negative, got {}",
+                weight
+            )));
+        }
+        self.weight = weight;
+        Ok(self)
+    }
+}
+
+impl<C: Codec> fmt::Display for FilteredFunction<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        if let Some(ref filter) = self.filter {
+            write!(f, "filter: {}, ", filter)?;
+        }
+        write!(f, "function: {}, weight: {}}}", &self.function, self.weight)
+    }
+}
+
+/// A query that combines the score of the wrapped query with `ScoreFunction`s
+/// computed from the doc values of the matching documents.
+///
+/// The scores of the functions whose filter matches a document are combined
+/// according to the `FunctionScoreMode`, capped by `max_boost`, and then applied
+/// to the score of the wrapped query according to the `BoostMode`.
+pub struct FunctionScoreQuery<C: Codec> {
+    query: Box<dyn Query<C>>,
+    functions: Vec<FilteredFunction<C>>,
+    score_mode: FunctionScoreMode,
+    boost_mode: BoostMode,
+    max_boost: f32,
+}
+
+impl<C: Codec> FunctionScoreQuery<C> {
+    pub fn new(
+        query: Box<dyn Query<C>>,
+        functions: Vec<FilteredFunction<C>>,
+        score_mode: FunctionScoreMode,
+        boost_mode: BoostMode,
+    ) -> Self {
+        FunctionScoreQuery {
+            query,
+            functions,
+            score_mode,
+            boost_mode,
+            max_boost: f32::MAX,
+        }
+    }
+
+    pub fn with_max_boost(mut self, max_boost: f32) -> Result<Self> {
+        if max_boost.is_nan() || max_boost <= 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "max_boost must be positive, got {}",
+                max_boost
+            )));
+        }
+        self.max_boost = max_boost;
+        Ok(self)
+    }
+}
+
+impl<C: Codec> Query<C> for FunctionScoreQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let query_weight = searcher.create_weight(self.query.as_ref(), needs_scores)?;
+        let mut functions = Vec::with_capacity(self.functions.len());
+        for func in &self.functions {
+            let filter = match func.filter {
+                Some(ref filter) => Some((
+                    filter.to_string(),
+                    searcher.create_weight(filter.as_ref(), false)?,
+                )),
+                None => None,
+            };
+            functions.push(FunctionWeight {
+                filter,
+                function: func.function.clone(),
+                weight: func.weight,
+            });
+        }
+        Ok(Box::new(FunctionScoreWeight {
+            query_weight,
+            functions,
+            score_mode: self.score_mode,
+            boost_mode: self.boost_mode,
+            max_boost: self.max_boost,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.query.extract_terms()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let functions: Vec<String> = self.functions.iter().map(|f| f.to_string()).collect();
+        write!(
+            f,
+            "FunctionScoreQuery(query: {}, functions: [{}], score_mode: {}, boost_mode: {}, \
+             max_boost: {})",
+            &self.query,
+            functions.join(", "),
+            self.score_mode,
+            self.boost_mode,
+            self.max_boost
+        )
+    }
+}
+
+struct FunctionWeight<C: Codec> {
+    // the filter query description and weight
+    filter: Option<(String, Box<dyn Weight<C>>)>,
+    function: ScoreFunction,
+    weight: f32,
+}
+
+struct FunctionScoreWeight<C: Codec> {
+    query_weight: Box<dyn Weight<C>>,
+    functions: Vec<FunctionWeight<C>>,
+    score_mode: FunctionScoreMode,
+    boost_mode: BoostMode,
+    max_boost: f32,
+}
+
+impl<C: Codec> FunctionScoreWeight<C> {
+    /// Create the functions of the segment, functions whose filter matches no
+    /// document of the segment are left out.
+    fn leaf_functions(&self, reader: &LeafReaderContext<'_, C>) -> Result<Vec<LeafFunction>> {
+        let mut functions = Vec::with_capacity(self.functions.len());
+        for (idx, func) in self.functions.iter().enumerate() {
+            let filter = match func.filter {
+                Some((_, ref weight)) => match weight.create_scorer(reader)? {
+                    Some(scorer) => Some(scorer),
+                    None => {
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            functions.push(LeafFunction {
+                idx,
+                filter,
+                function: func.function.leaf_function(reader)?,
+                weight: f64::from(func.weight),
+            });
+        }
+        Ok(functions)
+    }
+}
+
+impl<C: Codec> Weight<C> for FunctionScoreWeight<C> {
+    fn create_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(scorer) = self.query_weight.create_scorer(leaf_reader)? {
+            Ok(Some(Box::new(FunctionScoreScorer {
+                scorer,
+                functions: self.leaf_functions(leaf_reader)?,
+                score_mode: self.score_mode,
+                boost_mode: self.boost_mode,
+                max_boost: self.max_boost,
+                scores: Vec::with_capacity(self.functions.len()),
+            })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        FUNCTION_SCORE_QUERY
+    }
+
+    fn actual_query_type(&self) -> &'static str {
+        FUNCTION_SCORE_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.query_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.query_weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.query_weight.needs_scores()
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let query_expl = self.query_weight.explain(reader, doc)?;
+        if !query_expl.is_match() {
+            return Ok(query_expl);
+        }
+
+        let mut scores = Vec::with_capacity(self.functions.len());
+        let mut function_expls = Vec::with_capacity(self.functions.len());
+        for func in &mut self.leaf_functions(reader)? {
+            if !func.matches(doc)? {
+                continue;
+            }
+            let score = func.score(doc, query_expl.value())?;
+            scores.push(score);
+
+            let mut expl = func.function.explain(doc, query_expl.value())?;
+            if func.weight != 1.0 || func.filter.is_some() {
+                let mut details = vec![expl];
+                if let Some((ref filter, _)) = self.functions[func.idx].filter {
+                    details.push(Explanation::new(
+                        true,
+                        1.0,
+                        format!("match filter: {}", filter),
+                        vec![],
+                    ));
+                }
+                details.push(Explanation::new(
+                    true,
+                    func.weight as f32,
+                    "weight".to_string(),
+                    vec![],
+                ));
+                expl = Explanation::new(true, score.0 as f32, "product of:".into(), details);
+            }
+            function_expls.push(expl);
+            if self.score_mode == FunctionScoreMode::First {
+                break;
+            }
+        }
+
+        let factor = self.score_mode.combine(&scores) as f32;
+        let mut factor_expl = Explanation::new(
+            true,
+            factor,
+            format!("function score, score mode [{}]", self.score_mode),
+            function_expls,
+        );
+        if factor > self.max_boost {
+            factor_expl = Explanation::new(
+                true,
+                self.max_boost,
+                "min of:".into(),
+                vec![
+                    factor_expl,
+                    Explanation::new(true, self.max_boost, "max_boost".into(), vec![]),
+                ],
+            );
+        }
+
+        let score = self
+            .boost_mode
+            .combine(query_expl.value(), factor.min(self.max_boost));
+        Ok(Explanation::new(
+            true,
+            score,
+            format!("function score, boost mode [{}]", self.boost_mode),
+            vec![query_expl, factor_expl],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FunctionScoreWeight(query: {}, functions: {}, score_mode: {}, boost_mode: {})",
+            &self.query_weight,
+            self.functions.len(),
+            self.score_mode,
+            self.boost_mode
+        )
+    }
+}
+
+struct LeafFunction {
+    // index of the function in the weight
+    idx: usize,
+    filter: Option<Box<dyn Scorer>>,
+    function: LeafScoreFunction,
+    weight: f64,
+}
+
+impl LeafFunction {
+    fn matches(&mut self, doc: DocId) -> Result<bool> {
+        if let Some(ref mut filter) = self.filter {
+            let mut filter_doc = filter.doc_id();
+            if filter_doc < doc {
+                filter_doc = filter.advance(doc)?;
+            }
+            Ok(filter_doc == doc)
+        } else {
+            Ok(true)
+        }
+    }
+
+    /// The `(weighted score, weight)` of the function for `doc`
+    fn score(&mut self, doc: DocId, query_score: f32) -> Result<(f64, f64)> {
+        let score = self.function.score(doc, query_score)?;
+        Ok((score * self.weight, self.weight))
+    }
+}
+
+struct FunctionScoreScorer {
+    scorer: Box<dyn Scorer>,
+    functions: Vec<LeafFunction>,
+    score_mode: FunctionScoreMode,
+    boost_mode: BoostMode,
+    max_boost: f32,
+    // reused buffer of the scores of the matching functions
+    scores: Vec<(f64, f64)>,
+}
+
+impl Scorer for FunctionScoreScorer {
+    fn score(&mut self) -> Result<f32> {
+        let doc = self.scorer.doc_id();
+        let query_score = self.scorer.score()?;
+
+        self.scores.clear();
+        for func in &mut self.functions {
+            if func.matches(doc)? {
+                self.scores.push(func.score(doc, query_score)?);
+                if self.score_mode == FunctionScoreMode::First {
+                    break;
+                }
+            }
+        }
+        let factor = (self.score_mode.combine(&self.scores) as f32).min(self.max_boost);
+        Ok(self.boost_mode.combine(query_score, factor))
+    }
+}
+
+impl DocIterator for FunctionScoreScorer {
+    fn doc_id(&self) -> DocId {
+        self.scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.scorer.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorer.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        self.scorer.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.scorer.match_cost()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.scorer.support_two_phase()
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.scorer.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.approximate_advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{
+        Field, FieldType, Fieldable, IndexOptions, NumericDocValuesField, Term,
+        NUMERIC_DOC_VALUES_FIELD_TYPE,
+    };
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::query::{
+        DecayFunction, DecayType, FieldValueFactorFunction, FieldValueModifier, RandomScoreFunction,
+    };
+    use core::search::sort_field::SortFieldType;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::VariantValue;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_function_score_query() {
+        let mut kind_type = FieldType::default();
+        kind_type.index_options = IndexOptions::Docs;
+        kind_type.tokenized = false;
+
+        // (popularity, price)
+        let values = [(Some(9), 100.0), (Some(99), 130.0), (None, 101.0)];
+        let docs = values
+            .iter()
+            .map(|(popularity, price)| {
+                let mut doc: Vec<Box<dyn Fieldable>> = vec![
+                    Box::new(Field::new(
+                        "kind".into(),
+                        kind_type.clone(),
+                        Some(VariantValue::VString("book".into())),
+                        None,
+                    )),
+                    Box::new(Field::new(
+                        "price".into(),
+                        NUMERIC_DOC_VALUES_FIELD_TYPE,
+                        Some(VariantValue::Double(*price)),
+                        None,
+                    )),
+                ];
+                if let Some(popularity) = popularity {
+                    doc.push(Box::new(NumericDocValuesField::new(
+                        "popularity",
+                        *popularity,
+                    )));
+                }
+                doc
+            })
+            .collect();
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let term_query = || -> Box<dyn Query<_>> {
+            Box::new(TermQuery::new(
+                Term::new("kind".into(), b"book".to_vec()),
+                1.0,
+                None,
+            ))
+        };
+        let popularity = || {
+            ScoreFunction::FieldValueFactor(
+                FieldValueFactorFunction::new(
+                    "popularity".into(),
+                    SortFieldType::Long,
+                    1.0,
+                    FieldValueModifier::Log1p,
+                    Some(0.0),
+                )
+                .unwrap(),
+            )
+        };
+        let price = ScoreFunction::Decay(
+            DecayFunction::new(
+                "price".into(),
+                SortFieldType::Double,
+                DecayType::Gauss,
+                100.0,
+                10.0,
+                0.0,
+                0.5,
+            )
+            .unwrap(),
+        );
+
+        let search = |query: &FunctionScoreQuery<_>| -> Vec<(DocId, f32)> {
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(query, &mut collector).unwrap();
+            let mut hits: Vec<(DocId, f32)> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect();
+            hits.sort_by_key(|h| h.0);
+            for (doc, score) in &hits {
+                let expl = searcher.explain(query, *doc).unwrap();
+                assert!(expl.is_match());
+                assert!((expl.value() - *score).abs() < 1e-6);
+            }
+            hits
+        };
+
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![FilteredFunction::new(popularity())],
+            FunctionScoreMode::Multiply,
+            BoostMode::Replace,
+        );
+        let hits = search(&query);
+        assert!((hits[0].1 - 1.0).abs() < 1e-6);
+        assert!((hits[1].1 - 2.0).abs() < 1e-6);
+        assert!(hits[2].1.abs() < 1e-6);
+
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![
+                FilteredFunction::new(popularity()),
+                FilteredFunction::new(price.clone())
+                    .with_weight(2.0)
+                    .unwrap(),
+            ],
+            FunctionScoreMode::Sum,
+            BoostMode::Replace,
+        )
+        .with_max_boost(2.5)
+        .unwrap();
+        let hits = search(&query);
+        assert!((hits[0].1 - 2.5).abs() < 1e-6);
+        assert!(hits[1].1 < 2.5);
+        assert!((hits[2].1 - 2.0 * 0.5f32.powf(0.01)).abs() < 1e-5);
+
+        // the functions of no weight are averaged as if they didn't match
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![FilteredFunction::new(popularity())
+                .with_weight(0.0)
+                .unwrap()],
+            FunctionScoreMode::Avg,
+            BoostMode::Replace,
+        );
+        let hits = search(&query);
+        assert!(hits.iter().all(|h| (h.1 - 1.0).abs() < 1e-6));
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![
+                FilteredFunction::new(popularity())
+                    .with_weight(0.0)
+                    .unwrap(),
+                FilteredFunction::new(price.clone())
+                    .with_weight(2.0)
+                    .unwrap(),
+            ],
+            FunctionScoreMode::Avg,
+            BoostMode::Replace,
+        );
+        let hits = search(&query);
+        assert!((hits[2].1 - 0.5f32.powf(0.01)).abs() < 1e-5);
+
+        // the weight only applies to the documents matching its filter
+        let weight = FilteredFunction::new(ScoreFunction::Weight)
+            .with_filter(Box::new(TermQuery::new(
+                Term::new("kind".into(), b"none".to_vec()),
+                1.0,
+                None,
+            )))
+            .with_weight(3.0)
+            .unwrap();
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![weight, FilteredFunction::new(price)],
+            FunctionScoreMode::First,
+            BoostMode::Multiply,
+        );
+        let query_hits = search(&FunctionScoreQuery::new(
+            term_query(),
+            vec![],
+            FunctionScoreMode::Multiply,
+            BoostMode::Multiply,
+        ));
+        let hits = search(&query);
+        assert!((hits[0].1 - query_hits[0].1).abs() < 1e-6);
+        assert!(hits[1].1 < query_hits[1].1);
+
+        let random = || {
+            FilteredFunction::new(ScoreFunction::RandomScore(RandomScoreFunction::new(
+                7, None,
+            )))
+        };
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![random()],
+            FunctionScoreMode::Multiply,
+            BoostMode::Replace,
+        );
+        let first = search(&query);
+        assert_eq!(first, search(&query));
+        assert!(first.iter().all(|h| h.1 >= 0.0 && h.1 < 1.0));
+
+        // a missing value without default fails
+        let query = FunctionScoreQuery::new(
+            term_query(),
+            vec![FilteredFunction::new(ScoreFunction::FieldValueFactor(
+                FieldValueFactorFunction::new(
+                    "popularity".into(),
+                    SortFieldType::Long,
+                    1.0,
+                    FieldValueModifier::None,
+                    None,
+                )
+                .unwrap(),
+            ))],
+            FunctionScoreMode::Multiply,
+            BoostMode::Replace,
+        );
+        assert!(searcher.explain(&query, 2).is_err());
+        assert!(searcher.explain(&query, 1).is_ok());
+    }
+}
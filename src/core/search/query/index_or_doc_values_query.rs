@@ -0,0 +1,347 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::util::DocId;
+
+use error::Result;
+
+use std::any::Any;
+use std::fmt;
+
+pub const INDEX_OR_DOC_VALUES: &str = "index_or_doc_values";
+
+/// A query that uses either an index structure (points or terms) or doc values
+/// in order to run a query, depending which one is more efficient. This is
+/// typically useful for range queries, whose `Weight::create_scorer` is costly
+/// upfront as it needs to visit all matching docs in the index, while the doc
+/// values counterpart only checks the documents that the rest of the query
+/// leads to.
+///
+/// The choice is made per segment: the index query is used unless this query is a
+/// required clause of a conjunction whose lead iterator is much cheaper than the
+/// index query, in which case the doc values query verifies its documents instead.
+///
+/// Both queries must match the same documents and produce constant scores.
+pub struct IndexOrDocValuesQuery<C: Codec> {
+    index_query: Box<dyn Query<C>>,
+    dv_query: Box<dyn Query<C>>,
+}
+
+impl<C: Codec> IndexOrDocValuesQuery<C> {
+    /// Create an `IndexOrDocValuesQuery`. Both provided queries must match the same
+    /// documents and give the same scores.
+    pub fn new(
+        index_query: Box<dyn Query<C>>,
+        dv_query: Box<dyn Query<C>>,
+    ) -> IndexOrDocValuesQuery<C> {
+        IndexOrDocValuesQuery {
+            index_query,
+            dv_query,
+        }
+    }
+
+    /// Return the wrapped query that may be costly to initialize but has a good
+    /// iterator.
+    pub fn index_query(&self) -> &dyn Query<C> {
+        self.index_query.as_ref()
+    }
+
+    /// Return the wrapped query that may be slow at identifying all matching
+    /// documents, but which is cheap to initialize and can efficiently verify that
+    /// some documents match.
+    pub fn dv_query(&self) -> &dyn Query<C> {
+        self.dv_query.as_ref()
+    }
+}
+
+impl<C: Codec> Query<C> for IndexOrDocValuesQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(IndexOrDocValuesWeight {
+            index_weight: searcher.create_weight(self.index_query.as_ref(), needs_scores)?,
+            dv_weight: searcher.create_weight(self.dv_query.as_ref(), needs_scores)?,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.index_query.extract_terms()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for IndexOrDocValuesQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "IndexOrDocValuesQuery(index_query: {}, dv_query: {})",
+            &self.index_query, &self.dv_query
+        )
+    }
+}
+
+struct IndexOrDocValuesWeight<C: Codec> {
+    index_weight: Box<dyn Weight<C>>,
+    dv_weight: Box<dyn Weight<C>>,
+}
+
+impl<C: Codec> Weight<C> for IndexOrDocValuesWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        // without a lead iterator, this query leads the iteration
+        self.index_weight.create_scorer(reader)
+    }
+
+    fn create_scorer_with_lead_cost(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        lead_cost: usize,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        // At equal costs, doc values tend to be worse than the index since they
+        // still need to perform one comparison per document while the index can
+        // do much better than that given how values are organized. So we give an
+        // arbitrary 8x penalty to doc values.
+        // Without an estimate, the index query may match all the documents of the
+        // segment: building its scorer just to learn the cost would already pay for
+        // the upfront work this query is meant to avoid.
+        let index_cost = match self.index_weight.estimate_cost(reader)? {
+            Some(index_cost) => index_cost,
+            None => reader.reader.max_doc() as usize,
+        };
+        if index_cost >> 3 <= lead_cost {
+            self.index_weight.create_scorer(reader)
+        } else {
+            self.dv_weight.create_scorer(reader)
+        }
+    }
+
+    fn uses_lead_cost(&self) -> bool {
+        true
+    }
+
+    fn estimate_cost(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        self.index_weight.estimate_cost(reader)
+    }
+
+    fn query_type(&self) -> &'static str {
+        INDEX_OR_DOC_VALUES
+    }
+
+    fn actual_query_type(&self) -> &'static str {
+        self.index_weight.actual_query_type()
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.index_weight.normalize(norm, boost);
+        self.dv_weight.normalize(norm, boost);
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        // both weights return the same value since they are equivalent
+        self.index_weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.index_weight.needs_scores()
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        // explain is implemented by the doc values query which is cheap to verify
+        self.dv_weight.explain(reader, doc)
+    }
+}
+
+impl<C: Codec> fmt::Display for IndexOrDocValuesWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "IndexOrDocValuesWeight(index_weight: {}, dv_weight: {})",
+            &self.index_weight, &self.dv_weight
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::Term;
+    use core::doc::{
+        Fieldable, NumericDocValuesField, SortedNumericDocValuesField, SortedSetDocValuesField,
+    };
+    use core::index::reader::IndexReader;
+    use core::search::query::tests::{count_hits, index_fields, new_keyword_field, new_long_point};
+    use core::search::query::{
+        BooleanQuery, DocValuesRangeQuery, DocValuesTermsQuery, LongPoint, TermQuery,
+    };
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_doc_values_queries() {
+        let colors = ["red", "green", "blue"];
+        let mut docs = vec![];
+        for i in 0..20i64 {
+            let tag = if i % 2 == 0 { "even" } else { "odd" };
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(new_keyword_field("tag", tag)),
+                Box::new(new_keyword_field("color", colors[i as usize % 3])),
+                Box::new(new_long_point("price", i * 10)),
+                Box::new(NumericDocValuesField::new("price", i * 10)),
+                Box::new(SortedNumericDocValuesField::new("sizes", i)),
+                Box::new(SortedNumericDocValuesField::new("sizes", i + 100)),
+                Box::new(SortedSetDocValuesField::new(
+                    "color",
+                    colors[i as usize % 3].as_bytes(),
+                )),
+            ];
+            docs.push(doc);
+        }
+        // a doc without any price reads 0 from the numeric doc values
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(new_keyword_field("tag", "even"))];
+        docs.push(doc);
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+        let long_range = |field: &str, lower, upper, include_lower, include_upper| {
+            DocValuesRangeQuery::new_long_range(
+                field.into(),
+                lower,
+                upper,
+                include_lower,
+                include_upper,
+            )
+        };
+        let bytes_range = |lower: &str, upper: &str, include_lower, include_upper| {
+            DocValuesRangeQuery::new_bytes_range(
+                "color".into(),
+                Some(lower.as_bytes().to_vec()),
+                Some(upper.as_bytes().to_vec()),
+                include_lower,
+                include_upper,
+            )
+        };
+
+        assert_eq!(
+            count(&long_range("price", Some(0), Some(50), true, true)),
+            6
+        );
+        assert_eq!(
+            count(&long_range("price", Some(0), Some(50), false, false)),
+            4
+        );
+        assert_eq!(count(&long_range("price", None, Some(30), true, true)), 4);
+        assert_eq!(count(&long_range("price", Some(185), None, true, true)), 1);
+        assert_eq!(
+            count(&long_range("sizes", Some(100), Some(102), true, true)),
+            3
+        );
+        assert_eq!(
+            count(&long_range("sizes", Some(18), Some(100), true, false)),
+            2
+        );
+        assert_eq!(count(&bytes_range("blue", "green", true, true)), 13);
+        assert_eq!(count(&bytes_range("blue", "red", false, false)), 7);
+        assert_eq!(count(&bytes_range("c", "z", true, true)), 14);
+
+        let terms_query = DocValuesTermsQuery::new(
+            "color".into(),
+            vec![b"red".to_vec(), b"purple".to_vec(), b"blue".to_vec()],
+        );
+        assert_eq!(count(&terms_query), 13);
+        assert_eq!(
+            format!("{}", terms_query),
+            "DocValuesTermsQuery(field: color, terms: [blue, purple, red])"
+        );
+        assert_eq!(
+            format!("{}", long_range("price", Some(0), None, false, true)),
+            "DocValuesRangeQuery(field: price, range: {0 TO *})"
+        );
+
+        let index_or_dv = || {
+            IndexOrDocValuesQuery::new(
+                LongPoint::new_range_query("price".into(), 0, 50).unwrap(),
+                Box::new(long_range("price", Some(0), Some(50), true, true)),
+            )
+        };
+        assert_eq!(count(&index_or_dv()), 6);
+        let conjunction = BooleanQuery::build(
+            vec![
+                Box::new(TermQuery::new(
+                    Term::new("tag".into(), b"even".to_vec()),
+                    1.0,
+                    None,
+                )),
+                Box::new(index_or_dv()),
+            ],
+            vec![],
+            vec![],
+            vec![],
+            0,
+        )
+        .unwrap();
+        assert_eq!(count(conjunction.as_ref()), 3);
+
+        // a cheap lead iterator makes the doc values verify the matches
+        let weight = searcher.create_weight(&index_or_dv(), false).unwrap();
+        let leaves = searcher.reader().leaves();
+        let scorer = weight
+            .create_scorer_with_lead_cost(&leaves[0], 1)
+            .unwrap()
+            .unwrap();
+        assert!(scorer.support_two_phase());
+        let scorer = weight
+            .create_scorer_with_lead_cost(&leaves[0], 100)
+            .unwrap()
+            .unwrap();
+        assert!(!scorer.support_two_phase());
+
+        // without a cost estimate of the index query, the segment size bounds it
+        let color_query = IndexOrDocValuesQuery::new(
+            Box::new(TermQuery::new(
+                Term::new("color".into(), b"red".to_vec()),
+                1.0,
+                None,
+            )),
+            Box::new(DocValuesTermsQuery::new(
+                "color".into(),
+                vec![b"red".to_vec()],
+            )),
+        );
+        assert_eq!(count(&color_query), 7);
+        let weight = searcher.create_weight(&color_query, false).unwrap();
+        let scorer = weight
+            .create_scorer_with_lead_cost(&leaves[0], 1)
+            .unwrap()
+            .unwrap();
+        assert!(scorer.support_two_phase());
+        let scorer = weight
+            .create_scorer_with_lead_cost(&leaves[0], 2)
+            .unwrap()
+            .unwrap();
+        assert!(!scorer.support_two_phase());
+        assert!(searcher.explain(&index_or_dv(), 3).unwrap().is_match());
+        assert!(!searcher.explain(&index_or_dv(), 20).unwrap().is_match());
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::PostingIterator;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+/// The value returned by `IntervalIterator::next_interval` once the intervals
+/// of the current document are exhausted.
+pub const NO_MORE_INTERVALS: i32 = i32::max_value();
+
+/// A `DocIterator` that also iterates over the intervals of the current document.
+///
+/// `next` and `advance` only move to candidate documents, a document actually
+/// matches if `next_interval` returns at least one interval on it. Once positioned
+/// on a document, `start` and `end` return -1 until `next_interval` is called,
+/// and `NO_MORE_INTERVALS` once the intervals are exhausted.
+///
+/// The intervals are minimal: none of them contains another one, so they are
+/// returned in increasing order of both their start and end positions.
+pub trait IntervalIterator: DocIterator {
+    /// The start position of the current interval
+    fn start(&self) -> i32;
+
+    /// The end position of the current interval
+    fn end(&self) -> i32;
+
+    /// The number of positions between the sub intervals of the current
+    /// interval which are not covered by any of them.
+    fn gaps(&self) -> i32;
+
+    /// The width of the current interval
+    fn width(&self) -> i32 {
+        self.end() - self.start() + 1
+    }
+
+    /// Advance to the next interval in the current document, returning its start
+    /// position or `NO_MORE_INTERVALS`.
+    fn next_interval(&mut self) -> Result<i32>;
+}
+
+/// The intervals of a single term, one per position.
+pub struct TermIntervalIterator<P: PostingIterator> {
+    postings: P,
+    pos: i32,
+    upto: i32,
+    match_cost: f32,
+}
+
+impl<P: PostingIterator> TermIntervalIterator<P> {
+    pub fn new(postings: P, match_cost: f32) -> Self {
+        TermIntervalIterator {
+            postings,
+            pos: -1,
+            upto: 0,
+            match_cost,
+        }
+    }
+
+    fn reset(&mut self, doc: DocId) -> Result<DocId> {
+        self.pos = -1;
+        self.upto = if doc == NO_MORE_DOCS {
+            0
+        } else {
+            self.postings.freq()?
+        };
+        Ok(doc)
+    }
+}
+
+impl<P: PostingIterator> IntervalIterator for TermIntervalIterator<P> {
+    fn start(&self) -> i32 {
+        self.pos
+    }
+
+    fn end(&self) -> i32 {
+        self.pos
+    }
+
+    fn gaps(&self) -> i32 {
+        0
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        if self.upto <= 0 {
+            self.pos = NO_MORE_INTERVALS;
+        } else {
+            self.upto -= 1;
+            self.pos = self.postings.next_position()?;
+        }
+        Ok(self.pos)
+    }
+}
+
+impl<P: PostingIterator> DocIterator for TermIntervalIterator<P> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.postings.next()?;
+        self.reset(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.postings.advance(target)?;
+        self.reset(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.match_cost
+    }
+}
+
+/// The documents containing all the sub iterators, shared by the iterators
+/// combining the intervals of several sources.
+pub struct IntervalConjunction {
+    pub subs: Vec<Box<dyn IntervalIterator>>,
+    lead: usize,
+    doc: DocId,
+    match_cost: f32,
+}
+
+impl IntervalConjunction {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> Self {
+        debug_assert!(!subs.is_empty());
+        let mut lead = 0;
+        for i in 1..subs.len() {
+            if subs[i].cost() < subs[lead].cost() {
+                lead = i;
+            }
+        }
+        let match_cost = subs.iter().map(|s| s.match_cost()).sum();
+        IntervalConjunction {
+            subs,
+            lead,
+            doc: -1,
+            match_cost,
+        }
+    }
+
+    fn do_next(&mut self, mut doc: DocId) -> Result<DocId> {
+        'advance_head: while doc != NO_MORE_DOCS {
+            for i in 0..self.subs.len() {
+                if i == self.lead {
+                    continue;
+                }
+                let mut other = self.subs[i].doc_id();
+                if other < doc {
+                    other = self.subs[i].advance(doc)?;
+                }
+                if other > doc {
+                    doc = self.subs[self.lead].advance(other)?;
+                    continue 'advance_head;
+                }
+            }
+            break;
+        }
+        self.doc = doc;
+        Ok(doc)
+    }
+}
+
+impl DocIterator for IntervalConjunction {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.subs[self.lead].next()?;
+        self.do_next(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.subs[self.lead].advance(target)?;
+        self.do_next(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.subs[self.lead].cost()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.match_cost
+    }
+}
+
+/// Common trait of the iterators whose candidate documents are the ones
+/// containing all their sub iterators.
+pub trait ConjunctionIntervals: IntervalIterator {
+    fn conjunction(&self) -> &IntervalConjunction;
+
+    fn conjunction_mut(&mut self) -> &mut IntervalConjunction;
+
+    /// Called each time the conjunction moved to a new candidate document.
+    fn reset(&mut self) -> Result<()>;
+}
+
+macro_rules! conjunction_interval_doc_iter {
+    ($ty:ident) => {
+        impl DocIterator for $ty {
+            fn doc_id(&self) -> DocId {
+                self.conjunction().doc_id()
+            }
+
+            fn next(&mut self) -> Result<DocId> {
+                let doc = self.conjunction_mut().next()?;
+                if doc != NO_MORE_DOCS {
+                    self.reset()?;
+                }
+                Ok(doc)
+            }
+
+            fn advance(&mut self, target: DocId) -> Result<DocId> {
+                let doc = self.conjunction_mut().advance(target)?;
+                if doc != NO_MORE_DOCS {
+                    self.reset()?;
+                }
+                Ok(doc)
+            }
+
+            fn cost(&self) -> usize {
+                self.conjunction().cost()
+            }
+
+            fn match_cost(&self) -> f32 {
+                self.conjunction().match_cost()
+            }
+        }
+    };
+}
+
+/// Only accepts the intervals of the wrapped iterator having at most `max_gaps` gaps.
+pub struct MaxGapsIntervalIterator {
+    intervals: Box<dyn IntervalIterator>,
+    max_gaps: i32,
+}
+
+impl MaxGapsIntervalIterator {
+    pub fn new(intervals: Box<dyn IntervalIterator>, max_gaps: i32) -> Self {
+        MaxGapsIntervalIterator {
+            intervals,
+            max_gaps,
+        }
+    }
+}
+
+impl IntervalIterator for MaxGapsIntervalIterator {
+    fn start(&self) -> i32 {
+        self.intervals.start()
+    }
+
+    fn end(&self) -> i32 {
+        self.intervals.end()
+    }
+
+    fn gaps(&self) -> i32 {
+        self.intervals.gaps()
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        loop {
+            let start = self.intervals.next_interval()?;
+            if start == NO_MORE_INTERVALS || self.intervals.gaps() <= self.max_gaps {
+                return Ok(start);
+            }
+        }
+    }
+}
+
+impl DocIterator for MaxGapsIntervalIterator {
+    fn doc_id(&self) -> DocId {
+        self.intervals.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.intervals.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.intervals.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.intervals.cost()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.intervals.match_cost()
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::intervals::{IntervalIterator, IntervalsSource, NO_MORE_INTERVALS};
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::similarity::{SimScorer, SimWeight};
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+use std::fmt;
+
+pub const INTERVAL: &str = "interval";
+
+/// A query that retrieves documents containing intervals of an `IntervalsSource`.
+///
+/// The score of a document is computed by the similarity of the field, with a
+/// sloppy frequency summing the slop factor of the gaps of each interval, so
+/// that the score decays as the matched intervals get wider.
+pub struct IntervalQuery {
+    field: String,
+    source: IntervalsSource,
+}
+
+impl IntervalQuery {
+    pub fn new(field: String, source: IntervalsSource) -> Self {
+        IntervalQuery { field, source }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn source(&self) -> &IntervalsSource {
+        &self.source
+    }
+
+    fn terms(&self) -> Vec<Term> {
+        let mut terms = vec![];
+        self.source.extract_terms(&self.field, &mut terms);
+        terms
+    }
+}
+
+impl<C: Codec> Query<C> for IntervalQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        let terms = self.terms();
+        let mut term_stats: Vec<TermStatistics> = Vec::with_capacity(terms.len());
+        for term in &terms {
+            if needs_scores {
+                term_stats.push(searcher.term_statistics(term)?);
+            } else {
+                term_stats.push(TermStatistics::new(term.bytes.clone(), max_doc, -1));
+            }
+        }
+
+        let collection_stats = if needs_scores {
+            if let Some(stat) = searcher.collections_statistics(&self.field) {
+                stat.clone()
+            } else {
+                CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+            }
+        } else {
+            CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+        };
+
+        let similarity = searcher.similarity(&self.field, needs_scores);
+        let sim_weight = similarity.compute_weight(&collection_stats, &term_stats, None, 1.0f32);
+
+        Ok(Box::new(IntervalWeight {
+            field: self.field.clone(),
+            source: self.source.clone(),
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms()
+            .into_iter()
+            .map(|term| TermQuery::new(term, 1.0f32, None))
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for IntervalQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "IntervalQuery(field: {}, source: {})",
+            &self.field, &self.source
+        )
+    }
+}
+
+struct IntervalWeight<C: Codec> {
+    field: String,
+    source: IntervalsSource,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> Weight<C> for IntervalWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(intervals) = self.source.intervals(&self.field, reader)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+            Ok(Some(Box::new(IntervalScorer::new(
+                intervals,
+                sim_scorer,
+                self.needs_scores,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        INTERVAL
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(intervals) = self.source.intervals(&self.field, reader)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+            let mut scorer = IntervalScorer::new(intervals, sim_scorer, true);
+            if scorer.advance(doc)? == doc {
+                let freq = scorer.freq()?;
+                let freq_expl =
+                    Explanation::new(true, freq, format!("intervalFreq={}", freq), vec![]);
+                let score_expl = self.sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+                return Ok(Explanation::new(
+                    true,
+                    score_expl.value(),
+                    format!("weight({} in {}), result of:", self, doc),
+                    vec![score_expl],
+                ));
+            }
+        }
+
+        Ok(Explanation::new(
+            false,
+            0.0f32,
+            "no matching intervals".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for IntervalWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "IntervalWeight(field: {}, source: {}, need_score: {})",
+            &self.field, &self.source, self.needs_scores
+        )
+    }
+}
+
+/// Scores the documents having at least one interval.
+///
+/// The freq of a document sums `SimScorer::compute_slop_factor` of the gaps
+/// of all its intervals.
+pub struct IntervalScorer {
+    intervals: Box<dyn IntervalIterator>,
+    sim_scorer: Box<dyn SimScorer>,
+    needs_scores: bool,
+    freq: f32,
+    last_scored_doc: DocId,
+}
+
+impl IntervalScorer {
+    pub fn new(
+        intervals: Box<dyn IntervalIterator>,
+        sim_scorer: Box<dyn SimScorer>,
+        needs_scores: bool,
+    ) -> Self {
+        IntervalScorer {
+            intervals,
+            sim_scorer,
+            needs_scores,
+            freq: 0.0,
+            last_scored_doc: -1,
+        }
+    }
+
+    /// The sloppy frequency of the current document, only valid once the document
+    /// was checked by `matches`.
+    pub fn freq(&mut self) -> Result<f32> {
+        let doc = self.doc_id();
+        if self.last_scored_doc != doc {
+            self.last_scored_doc = doc;
+            self.freq = 0.0;
+            debug_assert_ne!(self.intervals.start(), NO_MORE_INTERVALS);
+            loop {
+                self.freq += self.sim_scorer.compute_slop_factor(self.intervals.gaps());
+                if !self.needs_scores || self.intervals.next_interval()? == NO_MORE_INTERVALS {
+                    break;
+                }
+            }
+        }
+        Ok(self.freq)
+    }
+}
+
+impl Scorer for IntervalScorer {
+    fn score(&mut self) -> Result<f32> {
+        let freq = self.freq()?;
+        let doc = self.doc_id();
+        self.sim_scorer.score(doc, freq)
+    }
+}
+
+impl DocIterator for IntervalScorer {
+    fn doc_id(&self) -> DocId {
+        self.intervals.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        loop {
+            let doc = self.intervals.next()?;
+            if doc == NO_MORE_DOCS || self.matches()? {
+                return Ok(doc);
+            }
+        }
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let mut doc = self.intervals.advance(target)?;
+        while doc != NO_MORE_DOCS && !self.matches()? {
+            doc = self.intervals.next()?;
+        }
+        Ok(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.intervals.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        Ok(self.intervals.next_interval()? != NO_MORE_INTERVALS)
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.intervals.match_cost()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.intervals.next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.intervals.advance(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::CodecEnum;
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_texts;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    const DOCS: [&str; 4] = [
+        "the quick brown fox jumps over the lazy dog",
+        "the fox is quick and brown",
+        "quick quick fox brown brown",
+        "a lazy brown dog",
+    ];
+
+    fn term(text: &str) -> IntervalsSource {
+        IntervalsSource::term(text)
+    }
+
+    /// Returns the sorted ids of the docs whose body matches `source`.
+    fn search<S: IndexSearcher<CodecEnum>>(searcher: &S, source: IntervalsSource) -> Vec<DocId> {
+        let query = IntervalQuery::new("body".into(), source);
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let mut docs: Vec<DocId> = collector
+            .top_docs()
+            .score_docs()
+            .iter()
+            .map(|d| d.doc_id())
+            .collect();
+        docs.sort_unstable();
+        docs
+    }
+
+    #[test]
+    fn test_interval_query() {
+        let (_dir, reader) = index_texts("body", &DOCS);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+
+        let quick_brown = IntervalsSource::phrase_terms(&["quick", "brown"]).unwrap();
+        assert_eq!(search(&searcher, quick_brown.clone()), vec![0]);
+
+        let ordered = IntervalsSource::ordered(vec![term("quick"), term("brown")]).unwrap();
+        assert_eq!(search(&searcher, ordered.clone()), vec![0, 1, 2]);
+        let unordered = IntervalsSource::unordered(vec![term("fox"), term("quick")]).unwrap();
+        assert_eq!(search(&searcher, unordered.clone()), vec![0, 1, 2]);
+
+        // "fox" and "quick" next to each other in any order
+        let max_gaps = IntervalsSource::max_gaps(unordered.clone(), 0).unwrap();
+        assert_eq!(search(&searcher, max_gaps), vec![2]);
+        assert!(IntervalsSource::max_gaps(ordered.clone(), -1).is_err());
+
+        let containing = IntervalsSource::containing(ordered.clone(), term("fox"));
+        assert_eq!(search(&searcher, containing), vec![2]);
+        let not_containing = IntervalsSource::not_containing(ordered.clone(), term("fox"));
+        assert_eq!(search(&searcher, not_containing), vec![0, 1]);
+        let contained_by = IntervalsSource::contained_by(term("fox"), ordered.clone());
+        assert_eq!(search(&searcher, contained_by), vec![2]);
+        let overlapping = IntervalsSource::overlapping(unordered.clone(), term("is"));
+        assert_eq!(search(&searcher, overlapping), vec![1]);
+        let non_overlapping = IntervalsSource::non_overlapping(term("brown"), unordered);
+        assert_eq!(search(&searcher, non_overlapping), vec![1, 2, 3]);
+
+        let before = IntervalsSource::before(term("brown"), term("dog"));
+        assert_eq!(search(&searcher, before), vec![0, 3]);
+        let after = IntervalsSource::after(term("brown"), term("fox"));
+        assert_eq!(search(&searcher, after), vec![1, 2]);
+        let before = IntervalsSource::before(term("dog"), term("brown"));
+        assert!(search(&searcher, before).is_empty());
+
+        // tighter intervals score higher
+        let query = IntervalQuery::new("body".into(), ordered);
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.score_docs()[0].doc_id(), 0);
+
+        let explanation = searcher.explain(&query, 1).unwrap();
+        assert!(explanation.is_match());
+        assert!(explanation.value() > 0.0);
+        let explanation = searcher.explain(&query, 3).unwrap();
+        assert!(!explanation.is_match());
+
+        assert_eq!(
+            format!("{}", query),
+            "IntervalQuery(field: body, source: ORDERED(quick,brown))"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                IntervalsSource::not_containing(quick_brown, term("x"))
+            ),
+            "NOT_CONTAINING(PHRASE(quick,brown),x)"
+        );
+    }
+
+    #[test]
+    fn test_empty_sources_and_gaps() {
+        assert!(IntervalsSource::phrase_terms(&[]).is_err());
+        assert!(IntervalsSource::ordered(vec![]).is_err());
+        assert!(IntervalsSource::unordered(vec![]).is_err());
+        // a single source is not wrapped
+        let single = IntervalsSource::ordered(vec![term("fox")]).unwrap();
+        assert_eq!(format!("{}", single), "fox");
+
+        let (_dir, reader) = index_texts("body", &DOCS);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let max_gaps = |source, gaps| IntervalsSource::max_gaps(source, gaps).unwrap();
+
+        let ordered = |first, second| IntervalsSource::ordered(vec![term(first), term(second)]);
+        assert_eq!(
+            search(&searcher, max_gaps(ordered("quick", "brown").unwrap(), 0)),
+            vec![0]
+        );
+        assert_eq!(
+            search(&searcher, max_gaps(ordered("quick", "brown").unwrap(), 1)),
+            vec![0, 1, 2]
+        );
+        // six positions between "quick" and "dog"
+        assert!(search(&searcher, max_gaps(ordered("quick", "dog").unwrap(), 5)).is_empty());
+        assert_eq!(
+            search(&searcher, max_gaps(ordered("quick", "dog").unwrap(), 6)),
+            vec![0]
+        );
+
+        let unordered = || IntervalsSource::unordered(vec![term("dog"), term("brown")]).unwrap();
+        assert_eq!(search(&searcher, max_gaps(unordered(), 0)), vec![3]);
+        assert_eq!(search(&searcher, max_gaps(unordered(), 4)), vec![3]);
+        assert_eq!(search(&searcher, max_gaps(unordered(), 5)), vec![0, 3]);
+    }
+}
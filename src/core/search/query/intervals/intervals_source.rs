@@ -0,0 +1,329 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, PostingIteratorFlags, TermIterator, Terms};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::query::intervals::{
+    BlockIntervalIterator, DifferenceIntervalIterator, IntervalIterator, IntervalRelation,
+    MaxGapsIntervalIterator, OrderedIntervalIterator, RelativeIntervalIterator,
+    TermIntervalIterator, UnorderedIntervalIterator,
+};
+use core::search::query::{TERM_OPS_PER_POS, TERM_POSNS_SEEK_OPS_PER_DOC};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+/// Describes the intervals matched by an `IntervalQuery`.
+///
+/// A source is built from term sources combined with the functions below, e.g.
+/// `ordered(vec![term("quick"), unordered(vec![term("brown"), term("red")])])`.
+/// The field is given by the `IntervalQuery` which uses the source.
+#[derive(Clone, Debug)]
+pub enum IntervalsSource {
+    Term(Vec<u8>),
+    Phrase(Vec<IntervalsSource>),
+    Ordered(Vec<IntervalsSource>),
+    Unordered(Vec<IntervalsSource>),
+    MaxGaps(Box<IntervalsSource>, i32),
+    Relative {
+        relation: IntervalRelation,
+        source: Box<IntervalsSource>,
+        reference: Box<IntervalsSource>,
+    },
+}
+
+impl IntervalsSource {
+    /// The intervals of a term, one per position
+    pub fn term(term: &str) -> Self {
+        IntervalsSource::Term(term.as_bytes().to_vec())
+    }
+
+    /// Intervals where the sub sources appear in order, directly following each other
+    pub fn phrase(subs: Vec<IntervalsSource>) -> Result<Self> {
+        Self::combine(subs, IntervalsSource::Phrase)
+    }
+
+    /// The phrase of the given terms
+    pub fn phrase_terms(terms: &[&str]) -> Result<Self> {
+        Self::phrase(terms.iter().map(|t| Self::term(t)).collect())
+    }
+
+    /// Minimal intervals where the sub sources appear in order without overlapping
+    pub fn ordered(subs: Vec<IntervalsSource>) -> Result<Self> {
+        Self::combine(subs, IntervalsSource::Ordered)
+    }
+
+    /// Minimal intervals containing all the sub sources in any order
+    pub fn unordered(subs: Vec<IntervalsSource>) -> Result<Self> {
+        Self::combine(subs, IntervalsSource::Unordered)
+    }
+
+    fn combine<F>(mut subs: Vec<IntervalsSource>, f: F) -> Result<Self>
+    where
+        F: FnOnce(Vec<IntervalsSource>) -> IntervalsSource,
+    {
+        match subs.len() {
+            0 => bail!(ErrorKind::IllegalArgument(
+                "intervals sources must not be empty".into()
+            )),
+            1 => Ok(subs.remove(0)),
+            _ => Ok(f(subs)),
+        }
+    }
+
+    /// Only keeps the intervals of `source` having at most `max_gaps` gaps
+    /// between their sub intervals.
+    pub fn max_gaps(source: IntervalsSource, max_gaps: i32) -> Result<Self> {
+        if max_gaps < 0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "max_gaps must not be negative, got {}",
+                max_gaps
+            )));
+        }
+        Ok(IntervalsSource::MaxGaps(Box::new(source), max_gaps))
+    }
+
+    /// Intervals of `big` containing an interval of `small`
+    pub fn containing(big: IntervalsSource, small: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::Containing, big, small)
+    }
+
+    /// Intervals of `small` contained by an interval of `big`
+    pub fn contained_by(small: IntervalsSource, big: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::ContainedBy, small, big)
+    }
+
+    /// Intervals of `source` overlapping an interval of `reference`
+    pub fn overlapping(source: IntervalsSource, reference: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::Overlapping, source, reference)
+    }
+
+    /// Intervals of `source` appearing before an interval of `reference`
+    pub fn before(source: IntervalsSource, reference: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::Before, source, reference)
+    }
+
+    /// Intervals of `source` appearing after an interval of `reference`
+    pub fn after(source: IntervalsSource, reference: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::After, source, reference)
+    }
+
+    /// Intervals of `minuend` not containing any interval of `subtrahend`
+    pub fn not_containing(minuend: IntervalsSource, subtrahend: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::NotContaining, minuend, subtrahend)
+    }
+
+    /// Intervals of `minuend` not contained by any interval of `subtrahend`
+    pub fn not_contained_by(minuend: IntervalsSource, subtrahend: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::NotContainedBy, minuend, subtrahend)
+    }
+
+    /// Intervals of `minuend` not overlapping any interval of `subtrahend`
+    pub fn non_overlapping(minuend: IntervalsSource, subtrahend: IntervalsSource) -> Self {
+        Self::relative(IntervalRelation::NonOverlapping, minuend, subtrahend)
+    }
+
+    fn relative(
+        relation: IntervalRelation,
+        source: IntervalsSource,
+        reference: IntervalsSource,
+    ) -> Self {
+        IntervalsSource::Relative {
+            relation,
+            source: Box::new(source),
+            reference: Box::new(reference),
+        }
+    }
+
+    /// Create the `IntervalIterator` of this source for a segment, `None` if
+    /// no document of the segment can match.
+    pub fn intervals<C: Codec>(
+        &self,
+        field: &str,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn IntervalIterator>>> {
+        let intervals: Box<dyn IntervalIterator> = match self {
+            IntervalsSource::Term(bytes) => {
+                let terms = match reader.reader.terms(field)? {
+                    Some(terms) => terms,
+                    None => {
+                        return Ok(None);
+                    }
+                };
+                if !terms.has_positions()? {
+                    bail!(ErrorKind::IllegalState(format!(
+                        "field '{}' was indexed without position data; cannot run IntervalQuery \
+                         (term={})",
+                        field,
+                        String::from_utf8_lossy(bytes)
+                    )));
+                }
+                let mut terms_iter = terms.iterator()?;
+                if !terms_iter.seek_exact(bytes)? {
+                    return Ok(None);
+                }
+                let match_cost = Self::term_positions_cost(&mut terms_iter)?;
+                let postings = terms_iter.postings_with_flags(PostingIteratorFlags::POSITIONS)?;
+                Box::new(TermIntervalIterator::new(postings, match_cost))
+            }
+            IntervalsSource::Phrase(subs) => match Self::sub_intervals(subs, field, reader)? {
+                Some(subs) => Box::new(BlockIntervalIterator::new(subs)),
+                None => {
+                    return Ok(None);
+                }
+            },
+            IntervalsSource::Ordered(subs) => match Self::sub_intervals(subs, field, reader)? {
+                Some(subs) => Box::new(OrderedIntervalIterator::new(subs)),
+                None => {
+                    return Ok(None);
+                }
+            },
+            IntervalsSource::Unordered(subs) => match Self::sub_intervals(subs, field, reader)? {
+                Some(subs) => Box::new(UnorderedIntervalIterator::new(subs)),
+                None => {
+                    return Ok(None);
+                }
+            },
+            IntervalsSource::MaxGaps(source, max_gaps) => match source.intervals(field, reader)? {
+                Some(intervals) => Box::new(MaxGapsIntervalIterator::new(intervals, *max_gaps)),
+                None => {
+                    return Ok(None);
+                }
+            },
+            IntervalsSource::Relative {
+                relation,
+                source,
+                reference,
+            } => {
+                let source = match source.intervals(field, reader)? {
+                    Some(intervals) => intervals,
+                    None => {
+                        return Ok(None);
+                    }
+                };
+                match reference.intervals(field, reader)? {
+                    Some(reference) => {
+                        if relation.is_negative() {
+                            Box::new(DifferenceIntervalIterator::new(
+                                source, reference, *relation,
+                            ))
+                        } else {
+                            Box::new(RelativeIntervalIterator::new(source, reference, *relation))
+                        }
+                    }
+                    None => {
+                        if relation.is_negative() {
+                            source
+                        } else {
+                            return Ok(None);
+                        }
+                    }
+                }
+            }
+        };
+        Ok(Some(intervals))
+    }
+
+    fn sub_intervals<C: Codec>(
+        subs: &[IntervalsSource],
+        field: &str,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Vec<Box<dyn IntervalIterator>>>> {
+        let mut intervals = Vec::with_capacity(subs.len());
+        for sub in subs {
+            match sub.intervals(field, reader)? {
+                Some(iter) => intervals.push(iter),
+                None => {
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(intervals))
+    }
+
+    fn term_positions_cost(terms_iter: &mut impl TermIterator) -> Result<f32> {
+        let doc_freq = terms_iter.doc_freq()?;
+        debug_assert!(doc_freq > 0);
+        let total_term_freq = terms_iter.total_term_freq()?; // -1 when not available
+        let exp_occurrences_in_matching_doc = if total_term_freq < i64::from(doc_freq) {
+            1.0
+        } else {
+            total_term_freq as f32 / doc_freq as f32
+        };
+        Ok(TERM_POSNS_SEEK_OPS_PER_DOC as f32
+            + exp_occurrences_in_matching_doc * TERM_OPS_PER_POS as f32)
+    }
+
+    /// Collect the terms of `field` which contribute to the matches, the
+    /// subtrahends of the negative relations are left out.
+    pub fn extract_terms(&self, field: &str, terms: &mut Vec<Term>) {
+        match self {
+            IntervalsSource::Term(bytes) => {
+                let term = Term::new(field.to_string(), bytes.clone());
+                if !terms.contains(&term) {
+                    terms.push(term);
+                }
+            }
+            IntervalsSource::Phrase(subs)
+            | IntervalsSource::Ordered(subs)
+            | IntervalsSource::Unordered(subs) => {
+                for sub in subs {
+                    sub.extract_terms(field, terms);
+                }
+            }
+            IntervalsSource::MaxGaps(source, _) => source.extract_terms(field, terms),
+            IntervalsSource::Relative {
+                relation,
+                source,
+                reference,
+            } => {
+                source.extract_terms(field, terms);
+                if !relation.is_negative() {
+                    reference.extract_terms(field, terms);
+                }
+            }
+        }
+    }
+
+    fn fmt_subs(f: &mut fmt::Formatter, name: &str, subs: &[IntervalsSource]) -> fmt::Result {
+        write!(f, "{}(", name)?;
+        for (i, sub) in subs.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", sub)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for IntervalsSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntervalsSource::Term(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            IntervalsSource::Phrase(subs) => Self::fmt_subs(f, "PHRASE", subs),
+            IntervalsSource::Ordered(subs) => Self::fmt_subs(f, "ORDERED", subs),
+            IntervalsSource::Unordered(subs) => Self::fmt_subs(f, "UNORDERED", subs),
+            IntervalsSource::MaxGaps(source, max_gaps) => {
+                write!(f, "MAXGAPS/{}({})", max_gaps, source)
+            }
+            IntervalsSource::Relative {
+                relation,
+                source,
+                reference,
+            } => write!(f, "{}({},{})", relation, source, reference),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::query::intervals::{
+    ConjunctionIntervals, IntervalConjunction, IntervalIterator, NO_MORE_INTERVALS,
+};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Intervals where the sub intervals appear in order and directly follow each
+/// other, as a phrase.
+pub struct BlockIntervalIterator {
+    conjunction: IntervalConjunction,
+    start: i32,
+    end: i32,
+}
+
+impl BlockIntervalIterator {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> Self {
+        BlockIntervalIterator {
+            conjunction: IntervalConjunction::new(subs),
+            start: -1,
+            end: -1,
+        }
+    }
+
+    fn exhausted(&mut self) -> Result<i32> {
+        self.start = NO_MORE_INTERVALS;
+        self.end = NO_MORE_INTERVALS;
+        Ok(NO_MORE_INTERVALS)
+    }
+}
+
+impl IntervalIterator for BlockIntervalIterator {
+    fn start(&self) -> i32 {
+        self.start
+    }
+
+    fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn gaps(&self) -> i32 {
+        0
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        let subs = &mut self.conjunction.subs;
+        if subs[0].next_interval()? == NO_MORE_INTERVALS {
+            return self.exhausted();
+        }
+        let mut i = 1;
+        while i < subs.len() {
+            while subs[i].start() <= subs[i - 1].end() {
+                if subs[i].next_interval()? == NO_MORE_INTERVALS {
+                    return self.exhausted();
+                }
+            }
+            if subs[i].start() == subs[i - 1].end() + 1 {
+                i += 1;
+            } else {
+                if subs[0].next_interval()? == NO_MORE_INTERVALS {
+                    return self.exhausted();
+                }
+                i = 1;
+            }
+        }
+        self.start = subs[0].start();
+        self.end = subs[subs.len() - 1].end();
+        Ok(self.start)
+    }
+}
+
+impl ConjunctionIntervals for BlockIntervalIterator {
+    fn conjunction(&self) -> &IntervalConjunction {
+        &self.conjunction
+    }
+
+    fn conjunction_mut(&mut self) -> &mut IntervalConjunction {
+        &mut self.conjunction
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.start = -1;
+        self.end = -1;
+        Ok(())
+    }
+}
+
+conjunction_interval_doc_iter!(BlockIntervalIterator);
+
+/// Minimal intervals where the sub intervals appear in order without overlapping.
+pub struct OrderedIntervalIterator {
+    conjunction: IntervalConjunction,
+    start: i32,
+    end: i32,
+    gaps: i32,
+}
+
+impl OrderedIntervalIterator {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> Self {
+        OrderedIntervalIterator {
+            conjunction: IntervalConjunction::new(subs),
+            start: -1,
+            end: -1,
+            gaps: 0,
+        }
+    }
+}
+
+impl IntervalIterator for OrderedIntervalIterator {
+    fn start(&self) -> i32 {
+        self.start
+    }
+
+    fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn gaps(&self) -> i32 {
+        self.gaps
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        self.start = NO_MORE_INTERVALS;
+        self.end = NO_MORE_INTERVALS;
+        let subs = &mut self.conjunction.subs;
+        let last = subs.len() - 1;
+        // the end of a new match must be before the start of the last sub
+        // interval of the previous match, or the match would not be minimal
+        let mut bound = i32::max_value();
+        let mut i = 1;
+        loop {
+            loop {
+                if subs[i - 1].end() >= bound {
+                    return Ok(self.start);
+                }
+                if i == subs.len() || subs[i].start() > subs[i - 1].end() {
+                    break;
+                }
+                loop {
+                    if subs[i].end() >= bound || subs[i].next_interval()? == NO_MORE_INTERVALS {
+                        return Ok(self.start);
+                    }
+                    if subs[i].start() > subs[i - 1].end() {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            self.start = subs[0].start();
+            self.end = subs[last].end();
+            self.gaps = (1..subs.len())
+                .map(|j| subs[j].start() - subs[j - 1].end() - 1)
+                .sum();
+            bound = subs[last].start();
+            i = 1;
+            if subs[0].next_interval()? == NO_MORE_INTERVALS {
+                return Ok(self.start);
+            }
+        }
+    }
+}
+
+impl ConjunctionIntervals for OrderedIntervalIterator {
+    fn conjunction(&self) -> &IntervalConjunction {
+        &self.conjunction
+    }
+
+    fn conjunction_mut(&mut self) -> &mut IntervalConjunction {
+        &mut self.conjunction
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.conjunction.subs[0].next_interval()?;
+        self.start = -1;
+        self.end = -1;
+        self.gaps = 0;
+        Ok(())
+    }
+}
+
+conjunction_interval_doc_iter!(OrderedIntervalIterator);
+
+/// Minimal intervals containing all the sub intervals in any order.
+pub struct UnorderedIntervalIterator {
+    conjunction: IntervalConjunction,
+    // the current interval of each sub iterator, as (start, Reverse(end), index),
+    // the top being the interval with the lowest start then the highest end
+    queue: BinaryHeap<Reverse<(i32, Reverse<i32>, usize)>>,
+    queue_end: i32,
+    start: i32,
+    end: i32,
+    gaps: i32,
+}
+
+impl UnorderedIntervalIterator {
+    pub fn new(subs: Vec<Box<dyn IntervalIterator>>) -> Self {
+        let queue = BinaryHeap::with_capacity(subs.len());
+        UnorderedIntervalIterator {
+            conjunction: IntervalConjunction::new(subs),
+            queue,
+            queue_end: -1,
+            start: -1,
+            end: -1,
+            gaps: 0,
+        }
+    }
+
+    fn top(&self) -> (i32, i32) {
+        let Reverse((start, Reverse(end), _)) = self.queue.peek().unwrap();
+        (*start, *end)
+    }
+
+    /// Move the sub iterator with the lowest start to its next interval.
+    fn advance_top(&mut self) -> Result<()> {
+        let Reverse((_, _, idx)) = self.queue.pop().unwrap();
+        let sub = &mut self.conjunction.subs[idx];
+        if sub.next_interval()? != NO_MORE_INTERVALS {
+            let (start, end) = (sub.start(), sub.end());
+            self.queue.push(Reverse((start, Reverse(end), idx)));
+            self.queue_end = self.queue_end.max(end);
+        }
+        Ok(())
+    }
+
+    /// The positions in `[start, end]` not covered by the sub intervals.
+    fn compute_gaps(&self) -> i32 {
+        let mut intervals: Vec<(i32, i32)> = self
+            .queue
+            .iter()
+            .map(|Reverse((start, Reverse(end), _))| (*start, *end))
+            .collect();
+        intervals.sort_unstable();
+        let mut gaps = 0;
+        let mut max_end = intervals[0].1;
+        for &(start, end) in &intervals[1..] {
+            if start > max_end + 1 {
+                gaps += start - max_end - 1;
+            }
+            max_end = max_end.max(end);
+        }
+        gaps
+    }
+}
+
+impl IntervalIterator for UnorderedIntervalIterator {
+    fn start(&self) -> i32 {
+        self.start
+    }
+
+    fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn gaps(&self) -> i32 {
+        self.gaps
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        let size = self.conjunction.subs.len();
+        // first, find a matching interval
+        while self.queue.len() == size && self.top().0 == self.start {
+            self.advance_top()?;
+        }
+        if self.queue.len() < size {
+            self.start = NO_MORE_INTERVALS;
+            self.end = NO_MORE_INTERVALS;
+            return Ok(NO_MORE_INTERVALS);
+        }
+        // then, minimize it
+        loop {
+            let (top_start, top_end) = self.top();
+            self.start = top_start;
+            self.end = self.queue_end;
+            self.gaps = self.compute_gaps();
+            if top_end == self.end {
+                return Ok(self.start);
+            }
+            self.advance_top()?;
+            if self.queue.len() < size || self.end != self.queue_end {
+                return Ok(self.start);
+            }
+        }
+    }
+}
+
+impl ConjunctionIntervals for UnorderedIntervalIterator {
+    fn conjunction(&self) -> &IntervalConjunction {
+        &self.conjunction
+    }
+
+    fn conjunction_mut(&mut self) -> &mut IntervalConjunction {
+        &mut self.conjunction
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.queue_end = -1;
+        self.start = -1;
+        self.end = -1;
+        self.gaps = 0;
+        self.queue.clear();
+        for idx in 0..self.conjunction.subs.len() {
+            let sub = &mut self.conjunction.subs[idx];
+            if sub.next_interval()? == NO_MORE_INTERVALS {
+                break;
+            }
+            let (start, end) = (sub.start(), sub.end());
+            self.queue.push(Reverse((start, Reverse(end), idx)));
+            self.queue_end = self.queue_end.max(end);
+        }
+        Ok(())
+    }
+}
+
+conjunction_interval_doc_iter!(UnorderedIntervalIterator);
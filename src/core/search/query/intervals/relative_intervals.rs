@@ -0,0 +1,298 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::query::intervals::{
+    ConjunctionIntervals, IntervalConjunction, IntervalIterator, NO_MORE_INTERVALS,
+};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+use std::fmt;
+
+/// How the intervals of a source relate to the intervals of a reference source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IntervalRelation {
+    /// source intervals containing a reference interval
+    Containing,
+    /// source intervals contained by a reference interval
+    ContainedBy,
+    /// source intervals overlapping a reference interval
+    Overlapping,
+    /// source intervals appearing before a reference interval
+    Before,
+    /// source intervals appearing after a reference interval
+    After,
+    /// source intervals not containing any reference interval
+    NotContaining,
+    /// source intervals not contained by any reference interval
+    NotContainedBy,
+    /// source intervals not overlapping any reference interval
+    NonOverlapping,
+}
+
+impl IntervalRelation {
+    /// Whether a source interval is rejected by the reference intervals
+    /// instead of being required to match one of them.
+    pub fn is_negative(self) -> bool {
+        matches!(
+            self,
+            IntervalRelation::NotContaining
+                | IntervalRelation::NotContainedBy
+                | IntervalRelation::NonOverlapping
+        )
+    }
+
+    /// Moves `reference` to the first interval which may relate to the current
+    /// interval of `source`, returning `false` if `reference` got exhausted.
+    ///
+    /// As the intervals of both iterators are minimal, the reference intervals
+    /// skipped here can not relate to the following source intervals either.
+    fn advance_reference(
+        self,
+        source: &dyn IntervalIterator,
+        reference: &mut dyn IntervalIterator,
+    ) -> Result<bool> {
+        loop {
+            let skip = match self {
+                IntervalRelation::Containing | IntervalRelation::NotContaining => {
+                    reference.start() < source.start()
+                }
+                IntervalRelation::ContainedBy | IntervalRelation::NotContainedBy => {
+                    reference.end() < source.end()
+                }
+                IntervalRelation::Overlapping | IntervalRelation::NonOverlapping => {
+                    reference.end() < source.start()
+                }
+                IntervalRelation::Before => reference.start() <= source.end(),
+                // the first reference interval has the lowest end
+                IntervalRelation::After => reference.start() == -1,
+            };
+            if !skip {
+                return Ok(true);
+            }
+            if reference.next_interval()? == NO_MORE_INTERVALS {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Whether the current intervals of `source` and `reference` satisfy the relation,
+    /// `reference` having been advanced by `advance_reference`.
+    fn relates(self, source: &dyn IntervalIterator, reference: &dyn IntervalIterator) -> bool {
+        match self {
+            IntervalRelation::Containing | IntervalRelation::NotContaining => {
+                reference.end() <= source.end()
+            }
+            IntervalRelation::ContainedBy | IntervalRelation::NotContainedBy => {
+                reference.start() <= source.start()
+            }
+            IntervalRelation::Overlapping | IntervalRelation::NonOverlapping => {
+                reference.start() <= source.end()
+            }
+            IntervalRelation::Before => true,
+            IntervalRelation::After => source.start() > reference.end(),
+        }
+    }
+}
+
+impl fmt::Display for IntervalRelation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            IntervalRelation::Containing => "CONTAINING",
+            IntervalRelation::ContainedBy => "CONTAINED_BY",
+            IntervalRelation::Overlapping => "OVERLAPPING",
+            IntervalRelation::Before => "BEFORE",
+            IntervalRelation::After => "AFTER",
+            IntervalRelation::NotContaining => "NOT_CONTAINING",
+            IntervalRelation::NotContainedBy => "NOT_CONTAINED_BY",
+            IntervalRelation::NonOverlapping => "NON_OVERLAPPING",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const SOURCE: usize = 0;
+const REFERENCE: usize = 1;
+
+/// The intervals of a source which have a positive relation with at least one
+/// interval of a reference, in documents containing both.
+pub struct RelativeIntervalIterator {
+    conjunction: IntervalConjunction,
+    relation: IntervalRelation,
+    reference_exhausted: bool,
+}
+
+impl RelativeIntervalIterator {
+    pub fn new(
+        source: Box<dyn IntervalIterator>,
+        reference: Box<dyn IntervalIterator>,
+        relation: IntervalRelation,
+    ) -> Self {
+        debug_assert!(!relation.is_negative());
+        RelativeIntervalIterator {
+            conjunction: IntervalConjunction::new(vec![source, reference]),
+            relation,
+            reference_exhausted: false,
+        }
+    }
+}
+
+impl IntervalIterator for RelativeIntervalIterator {
+    fn start(&self) -> i32 {
+        self.conjunction.subs[SOURCE].start()
+    }
+
+    fn end(&self) -> i32 {
+        self.conjunction.subs[SOURCE].end()
+    }
+
+    fn gaps(&self) -> i32 {
+        self.conjunction.subs[SOURCE].gaps()
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        if self.reference_exhausted {
+            return Ok(NO_MORE_INTERVALS);
+        }
+        let (source, reference) = self.conjunction.subs.split_at_mut(REFERENCE);
+        let (source, reference) = (source[0].as_mut(), reference[0].as_mut());
+        while source.next_interval()? != NO_MORE_INTERVALS {
+            if !self.relation.advance_reference(source, reference)? {
+                self.reference_exhausted = true;
+                return Ok(NO_MORE_INTERVALS);
+            }
+            if self.relation.relates(source, reference) {
+                return Ok(source.start());
+            }
+        }
+        Ok(NO_MORE_INTERVALS)
+    }
+}
+
+impl ConjunctionIntervals for RelativeIntervalIterator {
+    fn conjunction(&self) -> &IntervalConjunction {
+        &self.conjunction
+    }
+
+    fn conjunction_mut(&mut self) -> &mut IntervalConjunction {
+        &mut self.conjunction
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.reference_exhausted = false;
+        Ok(())
+    }
+}
+
+conjunction_interval_doc_iter!(RelativeIntervalIterator);
+
+/// The intervals of a source which have a relation with none of the intervals
+/// of a reference. The candidate documents are the ones of the source.
+pub struct DifferenceIntervalIterator {
+    source: Box<dyn IntervalIterator>,
+    reference: Box<dyn IntervalIterator>,
+    relation: IntervalRelation,
+    // whether the reference has intervals left in the current doc
+    reference_positioned: bool,
+}
+
+impl DifferenceIntervalIterator {
+    pub fn new(
+        source: Box<dyn IntervalIterator>,
+        reference: Box<dyn IntervalIterator>,
+        relation: IntervalRelation,
+    ) -> Self {
+        debug_assert!(relation.is_negative());
+        DifferenceIntervalIterator {
+            source,
+            reference,
+            relation,
+            reference_positioned: false,
+        }
+    }
+
+    fn reset(&mut self, doc: DocId) -> Result<DocId> {
+        self.reference_positioned = if doc == NO_MORE_DOCS {
+            false
+        } else {
+            let mut reference_doc = self.reference.doc_id();
+            if reference_doc < doc {
+                reference_doc = self.reference.advance(doc)?;
+            }
+            reference_doc == doc
+        };
+        Ok(doc)
+    }
+}
+
+impl IntervalIterator for DifferenceIntervalIterator {
+    fn start(&self) -> i32 {
+        self.source.start()
+    }
+
+    fn end(&self) -> i32 {
+        self.source.end()
+    }
+
+    fn gaps(&self) -> i32 {
+        self.source.gaps()
+    }
+
+    fn next_interval(&mut self) -> Result<i32> {
+        if !self.reference_positioned {
+            return self.source.next_interval();
+        }
+        while self.source.next_interval()? != NO_MORE_INTERVALS {
+            if !self
+                .relation
+                .advance_reference(self.source.as_ref(), self.reference.as_mut())?
+            {
+                self.reference_positioned = false;
+                return Ok(self.source.start());
+            }
+            if !self
+                .relation
+                .relates(self.source.as_ref(), self.reference.as_ref())
+            {
+                return Ok(self.source.start());
+            }
+        }
+        Ok(NO_MORE_INTERVALS)
+    }
+}
+
+impl DocIterator for DifferenceIntervalIterator {
+    fn doc_id(&self) -> DocId {
+        self.source.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let doc = self.source.next()?;
+        self.reset(doc)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let doc = self.source.advance(target)?;
+        self.reset(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.source.cost()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.source.match_cost() + self.reference.match_cost()
+    }
+}
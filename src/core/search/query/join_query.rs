@@ -0,0 +1,661 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use core::codec::doc_values::{SortedDocValues, SortedSetDocValues, NO_MORE_ORDS};
+use core::codec::{Codec, PostingIteratorFlags, TermIterator, Terms};
+use core::doc::DocValuesType;
+use core::index::reader::{IndexReader, LeafReaderContext};
+use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::Scorer;
+use core::search::searcher::{IndexSearcher, SearchPlanBuilder};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+
+pub const JOIN: &str = "join";
+
+/// How the scores of the matching documents are combined by a `JoinQuery`: the scores
+/// of the from-side documents sharing a join key, then the scores of the keys of a
+/// to-side document. A `ToParentBlockJoinQuery` combines the scores of the matching
+/// children of a parent the same way.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum JoinScoreMode {
+    /// Every match gets a score of 1.
+    None,
+    /// The average of the scores.
+    Avg,
+    /// The maximum of the scores.
+    Max,
+    /// The sum of the scores.
+    Total,
+}
+
+impl fmt::Display for JoinScoreMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JoinScoreMode::None => write!(f, "none"),
+            JoinScoreMode::Avg => write!(f, "avg"),
+            JoinScoreMode::Max => write!(f, "max"),
+            JoinScoreMode::Total => write!(f, "total"),
+        }
+    }
+}
+
+/// A score aggregated according to a `JoinScoreMode`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct AggregatedScore {
+    score: f32,
+    count: u32,
+}
+
+impl AggregatedScore {
+    pub(crate) fn new() -> Self {
+        AggregatedScore {
+            score: 0f32,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, score_mode: JoinScoreMode, score: f32) {
+        match score_mode {
+            JoinScoreMode::None => {}
+            JoinScoreMode::Max if self.count > 0 => self.score = self.score.max(score),
+            JoinScoreMode::Max => self.score = score,
+            JoinScoreMode::Avg | JoinScoreMode::Total => self.score += score,
+        }
+        self.count += 1;
+    }
+
+    pub(crate) fn value(&self, score_mode: JoinScoreMode) -> f32 {
+        match score_mode {
+            JoinScoreMode::None => 1f32,
+            JoinScoreMode::Avg => self.score / self.count as f32,
+            JoinScoreMode::Max | JoinScoreMode::Total => self.score,
+        }
+    }
+}
+
+/// A query-time join: matches the documents whose `to_field` contains one of the
+/// values of `from_field` on the documents matching a from-query.
+///
+/// The from-query is run when the `JoinQuery` is built, possibly on another index,
+/// and the join keys are read from the `Sorted` or `SortedSet` doc values of
+/// `from_field`. The to-side documents are then found with the indexed terms of
+/// `to_field`, and scored from the from-side scores according to the `JoinScoreMode`.
+///
+/// The collected keys are not refreshed, so a `JoinQuery` should be built again
+/// when the from-side index changes.
+pub struct JoinQuery {
+    from_field: String,
+    to_field: String,
+    score_mode: JoinScoreMode,
+    from_query: String,
+    // the sorted join keys, with the aggregated scores of their from-side documents
+    terms: Vec<Vec<u8>>,
+    scores: Vec<f32>,
+}
+
+impl JoinQuery {
+    pub fn build<C: Codec, IS: IndexSearcher<C>>(
+        from_searcher: &IS,
+        from_field: &str,
+        from_query: &dyn Query<C>,
+        to_field: &str,
+        score_mode: JoinScoreMode,
+    ) -> Result<JoinQuery> {
+        for leaf in from_searcher.reader().leaves() {
+            if let Some(field_info) = leaf.reader.field_info(from_field) {
+                match field_info.doc_values_type {
+                    DocValuesType::Sorted | DocValuesType::SortedSet => {}
+                    doc_values_type => bail!(ErrorKind::IllegalArgument(format!(
+                        "field '{}' has doc values of type {:?}, but a join needs sorted or \
+                         sorted_set doc values",
+                        from_field, doc_values_type
+                    ))),
+                }
+            }
+        }
+
+        let mut collector = JoinKeysCollector::new(from_field.to_string(), score_mode);
+        from_searcher.search(from_query, &mut collector)?;
+
+        let mut keys: Vec<(Vec<u8>, AggregatedScore)> = collector.keys.into_iter().collect();
+        keys.sort_by(|a, b| a.0.cmp(&b.0));
+        let scores = keys.iter().map(|(_, s)| s.value(score_mode)).collect();
+        let terms = keys.into_iter().map(|(term, _)| term).collect();
+        Ok(JoinQuery {
+            from_field: from_field.to_string(),
+            to_field: to_field.to_string(),
+            score_mode,
+            from_query: from_query.to_string(),
+            terms,
+            scores,
+        })
+    }
+
+    pub fn from_field(&self) -> &str {
+        &self.from_field
+    }
+
+    pub fn to_field(&self) -> &str {
+        &self.to_field
+    }
+
+    pub fn score_mode(&self) -> JoinScoreMode {
+        self.score_mode
+    }
+
+    /// The sorted join keys collected on the from-side.
+    pub fn terms(&self) -> &[Vec<u8>] {
+        &self.terms
+    }
+}
+
+impl<C: Codec> Query<C> for JoinQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(JoinWeight {
+            to_field: self.to_field.clone(),
+            score_mode: self.score_mode,
+            terms: self.terms.clone(),
+            scores: self.scores.clone(),
+            needs_scores,
+            weight: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for JoinQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "JoinQuery(from_field: {}, to_field: {}, score_mode: {}, from_query: {}, keys: {})",
+            &self.from_field,
+            &self.to_field,
+            self.score_mode,
+            &self.from_query,
+            self.terms.len()
+        )
+    }
+}
+
+enum LeafJoinValues {
+    Sorted(Box<dyn SortedDocValues>),
+    SortedSet(Box<dyn SortedSetDocValues>),
+    None,
+}
+
+/// Collects the join keys of the from-side documents, with their aggregated scores.
+struct JoinKeysCollector {
+    field: String,
+    score_mode: JoinScoreMode,
+    keys: HashMap<Vec<u8>, AggregatedScore>,
+    values: LeafJoinValues,
+}
+
+impl JoinKeysCollector {
+    fn new(field: String, score_mode: JoinScoreMode) -> Self {
+        JoinKeysCollector {
+            field,
+            score_mode,
+            keys: HashMap::new(),
+            values: LeafJoinValues::None,
+        }
+    }
+
+    fn add_key(&mut self, key: Vec<u8>, score: f32) {
+        self.keys
+            .entry(key)
+            .or_insert_with(AggregatedScore::new)
+            .add(self.score_mode, score);
+    }
+}
+
+impl SearchCollector for JoinKeysCollector {
+    type LC = JoinKeysLeafCollector;
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.values = match reader.reader.field_info(&self.field) {
+            Some(field_info) => match field_info.doc_values_type {
+                DocValuesType::Sorted => {
+                    LeafJoinValues::Sorted(reader.reader.get_sorted_doc_values(&self.field)?)
+                }
+                DocValuesType::SortedSet => {
+                    LeafJoinValues::SortedSet(reader.reader.get_sorted_set_doc_values(&self.field)?)
+                }
+                _ => LeafJoinValues::None,
+            },
+            None => LeafJoinValues::None,
+        };
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn leaf_collector<C: Codec>(&self, _reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
+        unreachable!()
+    }
+
+    fn finish_parallel(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for JoinKeysCollector {
+    fn needs_scores(&self) -> bool {
+        self.score_mode != JoinScoreMode::None
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        let score = if self.needs_scores() {
+            scorer.score()?
+        } else {
+            1f32
+        };
+        let mut keys = vec![];
+        match self.values {
+            LeafJoinValues::Sorted(ref mut values) => {
+                let ord = values.get_ord(doc)?;
+                if ord >= 0 {
+                    keys.push(values.lookup_ord(ord)?);
+                }
+            }
+            LeafJoinValues::SortedSet(ref mut values) => {
+                values.set_document(doc)?;
+                loop {
+                    let ord = values.next_ord()?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    keys.push(values.lookup_ord(ord)?);
+                }
+            }
+            LeafJoinValues::None => {}
+        }
+        for key in keys {
+            self.add_key(key, score);
+        }
+        Ok(())
+    }
+}
+
+// the from-side is always collected sequentially
+struct JoinKeysLeafCollector;
+
+impl Collector for JoinKeysLeafCollector {
+    fn needs_scores(&self) -> bool {
+        unreachable!()
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
+        unreachable!()
+    }
+}
+
+impl ParallelLeafCollector for JoinKeysLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        unreachable!()
+    }
+}
+
+struct JoinWeight {
+    to_field: String,
+    score_mode: JoinScoreMode,
+    terms: Vec<Vec<u8>>,
+    scores: Vec<f32>,
+    needs_scores: bool,
+    weight: f32,
+}
+
+impl JoinWeight {
+    /// The matching documents of the segment in order, with their scores.
+    fn matching_docs<C: Codec>(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<(Vec<DocId>, Vec<f32>)>> {
+        let terms = match reader.reader.terms(&self.to_field)? {
+            Some(terms) => terms,
+            None => return Ok(None),
+        };
+        let mut terms_iter = terms.iterator()?;
+        let mut doc_scores: Vec<Option<AggregatedScore>> =
+            vec![None; reader.reader.max_doc() as usize];
+        let mut found = false;
+        for (term, score) in self.terms.iter().zip(&self.scores) {
+            if !terms_iter.seek_exact(term)? {
+                continue;
+            }
+            found = true;
+            let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+            loop {
+                let doc = postings.next()?;
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                doc_scores[doc as usize]
+                    .get_or_insert_with(AggregatedScore::new)
+                    .add(self.score_mode, *score);
+            }
+        }
+        if !found {
+            return Ok(None);
+        }
+
+        let mut docs = vec![];
+        let mut scores = vec![];
+        for (doc, score) in doc_scores.iter().enumerate() {
+            if let Some(score) = score {
+                docs.push(doc as DocId);
+                scores.push(score.value(self.score_mode) * self.weight);
+            }
+        }
+        Ok(Some((docs, scores)))
+    }
+}
+
+impl<C: Codec> Weight<C> for JoinWeight {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self.matching_docs(reader)?.map(|(docs, scores)| {
+            Box::new(JoinScorer {
+                docs,
+                scores,
+                index: -1,
+            }) as Box<dyn Scorer>
+        }))
+    }
+
+    fn query_type(&self) -> &'static str {
+        JOIN
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        // the from-side scores are final, only the boost applies
+        self.weight = norm * boost;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        1f32
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some((docs, scores)) = self.matching_docs(reader)? {
+            if let Ok(i) = docs.binary_search(&doc) {
+                return Ok(Explanation::new(
+                    true,
+                    scores[i],
+                    format!(
+                        "{}, score mode {} of the matching from-side documents",
+                        self, self.score_mode
+                    ),
+                    vec![],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            format!("{} doesn't match id {}", self, doc),
+            vec![],
+        ))
+    }
+}
+
+impl fmt::Display for JoinWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "JoinWeight(to_field: {}, score_mode: {}, keys: {})",
+            &self.to_field,
+            self.score_mode,
+            self.terms.len()
+        )
+    }
+}
+
+struct JoinScorer {
+    docs: Vec<DocId>,
+    scores: Vec<f32>,
+    index: isize,
+}
+
+impl Scorer for JoinScorer {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.scores[self.index as usize])
+    }
+}
+
+impl DocIterator for JoinScorer {
+    fn doc_id(&self) -> DocId {
+        if self.index < 0 {
+            -1
+        } else if self.index as usize >= self.docs.len() {
+            NO_MORE_DOCS
+        } else {
+            self.docs[self.index as usize]
+        }
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.index < self.docs.len() as isize {
+            self.index += 1;
+        }
+        Ok(self.doc_id())
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let start = (self.index + 1).max(0) as usize;
+        let offset = self.docs[start.min(self.docs.len())..]
+            .binary_search(&target)
+            .unwrap_or_else(|i| i);
+        self.index = (start + offset) as isize;
+        Ok(self.doc_id())
+    }
+
+    fn cost(&self) -> usize {
+        self.docs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{
+        Field, FieldType, Fieldable, IndexOptions, SortedSetDocValuesField, Term,
+        SORTED_DOC_VALUES_FIELD_TYPE,
+    };
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::query::BooleanQuery;
+    use core::search::DefaultIndexSearcher;
+    use core::util::VariantValue;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_join_query() {
+        let mut keyword_type = FieldType::default();
+        keyword_type.index_options = IndexOptions::Docs;
+        keyword_type.tokenized = false;
+        let keyword = |name: &str, value: &str| -> Box<dyn Fieldable> {
+            Box::new(Field::new(
+                name.into(),
+                keyword_type.clone(),
+                Some(VariantValue::VString(value.into())),
+                None,
+            ))
+        };
+
+        let mut docs = vec![];
+        // the questions are docs 0 to 4
+        for i in 0..5 {
+            let doc = vec![keyword("qid", &format!("q{}", i))];
+            docs.push(doc);
+        }
+        // (question, tag, related questions)
+        let answers = [
+            ("q0", "good", vec!["q0", "q2"]),
+            ("q0", "great", vec![]),
+            ("q1", "good", vec![]),
+            ("q2", "bad", vec!["q4"]),
+            ("q3", "great", vec!["q3", "q1"]),
+            ("q9", "good", vec![]),
+        ];
+        for (question, tag, related) in &answers {
+            let mut doc = vec![
+                keyword("tag", tag),
+                Box::new(Field::new(
+                    "answer_to".into(),
+                    SORTED_DOC_VALUES_FIELD_TYPE,
+                    Some(VariantValue::Binary(question.as_bytes().to_vec())),
+                    None,
+                )) as Box<dyn Fieldable>,
+            ];
+            for q in related {
+                doc.push(Box::new(SortedSetDocValuesField::new(
+                    "related",
+                    q.as_bytes(),
+                )));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let from_query = || {
+            let tag = |t: &str, boost: f32| -> Box<dyn Query<_>> {
+                Box::new(TermQuery::new(
+                    Term::new("tag".into(), t.as_bytes().to_vec()),
+                    boost,
+                    None,
+                ))
+            };
+            BooleanQuery::build(
+                vec![],
+                vec![tag("good", 1.0), tag("great", 3.0)],
+                vec![],
+                vec![],
+                0,
+            )
+            .unwrap()
+        };
+        let search = |query: &dyn Query<_>| -> HashMap<DocId, f32> {
+            let mut collector = TopDocsCollector::new(20);
+            searcher.search(query, &mut collector).unwrap();
+            collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect()
+        };
+        let from_scores = search(from_query().as_ref());
+        assert_eq!(from_scores.len(), 5);
+
+        let expected_scores = |keys: &dyn Fn(usize) -> Vec<&'static str>,
+                               score_mode: JoinScoreMode|
+         -> HashMap<DocId, f32> {
+            let mut by_key: HashMap<&str, AggregatedScore> = HashMap::new();
+            for (&doc, &score) in &from_scores {
+                for key in keys(doc as usize - 5) {
+                    by_key
+                        .entry(key)
+                        .or_insert_with(AggregatedScore::new)
+                        .add(score_mode, score);
+                }
+            }
+            (0..5)
+                .filter_map(|i| {
+                    by_key
+                        .get(format!("q{}", i).as_str())
+                        .map(|s| (i as DocId, s.value(score_mode)))
+                })
+                .collect()
+        };
+
+        for &score_mode in &[
+            JoinScoreMode::None,
+            JoinScoreMode::Avg,
+            JoinScoreMode::Max,
+            JoinScoreMode::Total,
+        ] {
+            let query = JoinQuery::build(
+                &searcher,
+                "answer_to",
+                from_query().as_ref(),
+                "qid",
+                score_mode,
+            )
+            .unwrap();
+            // the key of the unknown question q9 is collected but matches nothing
+            assert_eq!(query.terms().len(), 4);
+            let hits = search(&query);
+            let expected = expected_scores(&|i| vec![answers[i].0], score_mode);
+            assert_eq!(hits.len(), 3);
+            for (doc, score) in &expected {
+                assert!((hits[doc] - score).abs() < 1e-5);
+                let expl = searcher.explain(&query, *doc).unwrap();
+                assert!(expl.is_match());
+                assert!((expl.value() - score).abs() < 1e-5);
+            }
+            assert!(!searcher.explain(&query, 4).unwrap().is_match());
+
+            let query = JoinQuery::build(
+                &searcher,
+                "related",
+                from_query().as_ref(),
+                "qid",
+                score_mode,
+            )
+            .unwrap();
+            let hits = search(&query);
+            let expected = expected_scores(&|i| answers[i].2.clone(), score_mode);
+            assert_eq!(hits.len(), 4);
+            for (doc, score) in &expected {
+                assert!((hits[doc] - score).abs() < 1e-5);
+            }
+        }
+
+        // the from field must have sorted or sorted_set doc values
+        assert!(JoinQuery::build(
+            &searcher,
+            "qid",
+            from_query().as_ref(),
+            "qid",
+            JoinScoreMode::None
+        )
+        .is_err());
+    }
+}
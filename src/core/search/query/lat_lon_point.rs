@@ -0,0 +1,641 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::TokenStream;
+use core::codec::points::{IntersectVisitor, PointValues, Relation};
+use core::codec::Codec;
+use core::doc::{DocValuesType, Field, FieldType, Fieldable, IndexOptions};
+use core::index::reader::{LeafReader, LeafReaderContext};
+use core::search::explanation::Explanation;
+use core::search::query::{
+    BooleanQuery, ConstantScoreQuery, LatLonPointDistanceQuery, LatLonPointInPolygonQuery,
+    PointRangeQuery, PointValueType, Query, Weight,
+};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::{DocIdSet, DocIterator};
+use core::util::geo::{
+    decode_latitude, decode_longitude, encode_latitude, encode_latitude_ceil, encode_longitude,
+    encode_longitude_ceil, Polygon, MAX_LON_INCL, MIN_LON_INCL,
+};
+use core::util::VariantValue;
+use core::util::{int2sortable_bytes, sortable_bytes2int, DocId, DocIdSetBuilder, Numeric};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+const LAT_LON_POINT_FIELD_TYPE: FieldType = FieldType {
+    stored: false,
+    tokenized: false,
+    store_term_vectors: false,
+    store_term_vector_offsets: false,
+    store_term_vector_positions: false,
+    store_term_vector_payloads: false,
+    omit_norms: false,
+    index_options: IndexOptions::Null,
+    doc_values_type: DocValuesType::Null,
+    dimension_count: 2,
+    dimension_num_bytes: LatLonPoint::BYTES as u32,
+};
+
+/// An indexed location field.
+///
+/// The latitude and longitude are quantized into two 4 byte dimensions of the BKD
+/// tree, with a precision of about 1 cm. Finding all documents within a range at search
+/// time is efficient, and multiple values for the same field in one document is allowed.
+///
+/// This field defines static factory methods for common operations:
+/// * `new_box_query` for matching points within a bounding box.
+/// * `new_distance_query` for matching points within a specified distance.
+/// * `new_polygon_query` for matching points within an arbitrary polygon.
+///
+/// To sort or score the hits by distance, also index the location with a
+/// `LatLonDocValuesField`.
+pub struct LatLonPoint {
+    field: Field,
+}
+
+impl LatLonPoint {
+    /// The number of bytes per dimension.
+    pub const BYTES: usize = 4;
+
+    /// Creates a new point with the specified latitude and longitude.
+    pub fn new(name: &str, latitude: f64, longitude: f64) -> Result<LatLonPoint> {
+        Ok(LatLonPoint {
+            field: Field::new(
+                String::from(name),
+                LAT_LON_POINT_FIELD_TYPE,
+                Some(VariantValue::Binary(LatLonPoint::pack(
+                    latitude, longitude,
+                )?)),
+                None,
+            ),
+        })
+    }
+
+    /// The quantized latitude of this point.
+    pub fn latitude(&self) -> f64 {
+        LatLonPoint::decode_latitude(self.field.binary_value().unwrap())
+    }
+
+    /// The quantized longitude of this point.
+    pub fn longitude(&self) -> f64 {
+        LatLonPoint::decode_longitude(self.field.binary_value().unwrap())
+    }
+
+    /// Encodes the location into the packed value of the two dimensions, rounding down.
+    pub fn pack(latitude: f64, longitude: f64) -> Result<Vec<u8>> {
+        Ok(Self::pack_encoded(
+            encode_latitude(latitude)?,
+            encode_longitude(longitude)?,
+        ))
+    }
+
+    // encodes the location, rounding up
+    fn pack_ceil(latitude: f64, longitude: f64) -> Result<Vec<u8>> {
+        Ok(Self::pack_encoded(
+            encode_latitude_ceil(latitude)?,
+            encode_longitude_ceil(longitude)?,
+        ))
+    }
+
+    fn pack_encoded(latitude: i32, longitude: i32) -> Vec<u8> {
+        let mut packed = vec![0u8; 2 * Self::BYTES];
+        int2sortable_bytes(latitude, &mut packed);
+        int2sortable_bytes(longitude, &mut packed[Self::BYTES..]);
+        packed
+    }
+
+    /// Decodes the latitude of a packed value.
+    pub fn decode_latitude(packed: &[u8]) -> f64 {
+        decode_latitude(sortable_bytes2int(&packed[..Self::BYTES]))
+    }
+
+    /// Decodes the longitude of a packed value.
+    pub fn decode_longitude(packed: &[u8]) -> f64 {
+        decode_longitude(sortable_bytes2int(&packed[Self::BYTES..2 * Self::BYTES]))
+    }
+
+    /// Create a query for matching a bounding box.
+    ///
+    /// The box may cross the dateline, when `min_longitude` is greater than
+    /// `max_longitude`.
+    pub fn new_box_query<C: Codec>(
+        field: String,
+        min_latitude: f64,
+        max_latitude: f64,
+        min_longitude: f64,
+        max_longitude: f64,
+    ) -> Result<Box<dyn Query<C>>> {
+        let mut min_longitude = min_longitude;
+        if (min_longitude - MAX_LON_INCL).abs() < std::f64::EPSILON && max_longitude < min_longitude
+        {
+            // 180 can not be encoded exactly, so start right after the dateline instead
+            min_longitude = MIN_LON_INCL;
+        }
+        let lower = LatLonPoint::pack_ceil(min_latitude, min_longitude)?;
+        let upper = LatLonPoint::pack(max_latitude, max_longitude)?;
+        if max_longitude < min_longitude {
+            // crosses the dateline: the union of two boxes with longitude as an open range
+            let mut left_open = lower.clone();
+            int2sortable_bytes(i32::min_value(), &mut left_open[Self::BYTES..]);
+            let mut right_open = upper.clone();
+            int2sortable_bytes(i32::max_value(), &mut right_open[Self::BYTES..]);
+            let left = Self::new_box_internal(field.clone(), left_open, upper)?;
+            let right = Self::new_box_internal(field, lower, right_open)?;
+            // a multi-valued doc could match both boxes, so don't sum the scores
+            let query = BooleanQuery::build(vec![], vec![left, right], vec![], vec![], 0)?;
+            Ok(Box::new(ConstantScoreQuery::with_boost(query, 1.0)))
+        } else {
+            Self::new_box_internal(field, lower, upper)
+        }
+    }
+
+    fn new_box_internal<C: Codec>(
+        field: String,
+        lower: Vec<u8>,
+        upper: Vec<u8>,
+    ) -> Result<Box<dyn Query<C>>> {
+        Ok(Box::new(PointRangeQuery::new(
+            field,
+            lower,
+            upper,
+            2,
+            PointValueType::Integer,
+        )?))
+    }
+
+    /// Create a query for matching points within the specified distance in meters of the
+    /// supplied location.
+    pub fn new_distance_query<C: Codec>(
+        field: String,
+        latitude: f64,
+        longitude: f64,
+        radius_meters: f64,
+    ) -> Result<Box<dyn Query<C>>> {
+        Ok(Box::new(LatLonPointDistanceQuery::new(
+            field,
+            latitude,
+            longitude,
+            radius_meters,
+        )?))
+    }
+
+    /// Create a query for matching one or more polygons. Points within the holes of the
+    /// polygons do not match.
+    pub fn new_polygon_query<C: Codec>(
+        field: String,
+        polygons: Vec<Polygon>,
+    ) -> Result<Box<dyn Query<C>>> {
+        Ok(Box::new(LatLonPointInPolygonQuery::new(field, polygons)?))
+    }
+}
+
+impl Fieldable for LatLonPoint {
+    fn name(&self) -> &str {
+        self.field.name()
+    }
+
+    fn field_type(&self) -> &FieldType {
+        self.field.field_type()
+    }
+
+    fn boost(&self) -> f32 {
+        self.field.boost()
+    }
+
+    fn field_data(&self) -> Option<&VariantValue> {
+        self.field.field_data()
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        self.field.token_stream()
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        self.field.binary_value()
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        None
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        None
+    }
+}
+
+/// The shape matched by a query on a `LatLonPoint` field, checked against the packed
+/// values of the points and against the cells of the BKD tree.
+pub(crate) trait LatLonShape: fmt::Display {
+    fn field(&self) -> &str;
+
+    /// Computes the relation of a cell of the BKD tree with this shape.
+    fn relate(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation;
+
+    /// Whether the point of the given packed value is within this shape.
+    fn contains(&self, packed_value: &[u8]) -> bool;
+}
+
+/// The weight shared by the queries on `LatLonPoint` fields, matching the docs with
+/// a single intersection of the BKD tree with the shape.
+pub(crate) struct LatLonPointWeight<S: LatLonShape> {
+    shape: S,
+    query_type: &'static str,
+    weight: f32,
+    norm: f32,
+}
+
+impl<S: LatLonShape> LatLonPointWeight<S> {
+    pub fn new(shape: S, query_type: &'static str) -> LatLonPointWeight<S> {
+        LatLonPointWeight {
+            shape,
+            query_type,
+            weight: 0f32,
+            norm: 1f32,
+        }
+    }
+
+    fn check_field<R: LeafReader + ?Sized>(&self, reader: &R) -> Result<bool> {
+        match reader.field_info(self.shape.field()) {
+            Some(field_info) => {
+                if field_info.point_dimension_count != 2
+                    || field_info.point_num_bytes != LatLonPoint::BYTES as u32
+                {
+                    bail!(ErrorKind::IllegalArgument(format!(
+                        "field '{}' was indexed with num_dims={} bytes_per_dim={} but LatLonPoint \
+                         has num_dims=2 bytes_per_dim={}",
+                        self.shape.field(),
+                        field_info.point_dimension_count,
+                        field_info.point_num_bytes,
+                        LatLonPoint::BYTES
+                    )));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<S: LatLonShape, C: Codec> Weight<C> for LatLonPointWeight<S> {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        let values = match leaf_reader.point_values() {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        if !self.check_field(leaf_reader)? {
+            return Ok(None);
+        }
+
+        let field = self.shape.field();
+        let mut result = DocIdSetBuilder::from_values(leaf_reader.max_doc(), &values, field)?;
+        {
+            let mut visitor = LatLonPointIntersectVisitor {
+                doc_id_set_builder: &mut result,
+                shape: &self.shape,
+            };
+            values.intersect(field, &mut visitor)?;
+        }
+
+        if let Some(iterator) = result.build().iterator()? {
+            let cost = iterator.cost();
+            Ok(Some(Box::new(ConstantScoreScorer::new(
+                self.weight,
+                iterator,
+                cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        self.query_type
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matches {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self.shape),
+                vec![
+                    Explanation::new(true, self.weight / self.norm, "boost".into(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".into(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("{} doesn't match id {}", self.shape, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl<S: LatLonShape> fmt::Display for LatLonPointWeight<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LatLonPointWeight(shape: {})", self.shape)
+    }
+}
+
+struct LatLonPointIntersectVisitor<'a, S: LatLonShape> {
+    doc_id_set_builder: &'a mut DocIdSetBuilder,
+    shape: &'a S,
+}
+
+impl<'a, S: LatLonShape> IntersectVisitor for LatLonPointIntersectVisitor<'a, S> {
+    fn visit(&mut self, doc_id: DocId) -> Result<()> {
+        self.doc_id_set_builder.add_doc(doc_id);
+        Ok(())
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        if self.shape.contains(packed_value) {
+            self.doc_id_set_builder.add_doc(doc_id);
+        }
+        Ok(())
+    }
+
+    fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        self.shape.relate(min_packed_value, max_packed_value)
+    }
+
+    fn grow(&mut self, count: usize) {
+        self.doc_id_set_builder.grow(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::CodecEnum;
+    use core::doc::LatLonDocValuesField;
+    use core::doc::NumericDocValuesField;
+    use core::index::reader::IndexReader;
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::{count_hits, index_fields};
+    use core::search::query::{
+        BoostMode, DecayType, FilteredFunction, FunctionScoreMode, FunctionScoreQuery,
+        GeoDecayFunction, MatchAllDocsQuery, ScoreFunction,
+    };
+    use core::search::sort_field::{ComparatorValue, FieldComparator, SortFieldType};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+    use core::util::geo::{haversin_meters, Rectangle};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lat_lon_point_queries() {
+        // a grid of points, spread over many leaf blocks
+        let mut points = vec![];
+        for lat in (-60..=60).step_by(2) {
+            for lon in (-180..180).step_by(4) {
+                points.push((f64::from(lat) + 0.5, f64::from(lon) + 0.25));
+            }
+        }
+        points.push((0.0, 179.9));
+        points.push((0.0, -179.9));
+        let mut docs = vec![];
+        for &(lat, lon) in &points {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(LatLonPoint::new("location", lat, lon).unwrap()),
+                Box::new(LatLonDocValuesField::new("location", lat, lon).unwrap()),
+            ];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let points: Vec<(f64, f64)> = points
+            .iter()
+            .map(|&(lat, lon)| {
+                let packed = LatLonPoint::pack(lat, lon).unwrap();
+                (
+                    LatLonPoint::decode_latitude(&packed),
+                    LatLonPoint::decode_longitude(&packed),
+                )
+            })
+            .collect();
+
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+        let expected = |f: &dyn Fn(f64, f64) -> bool| -> usize {
+            points.iter().filter(|&&(lat, lon)| f(lat, lon)).count()
+        };
+
+        for &(lat, lon, radius) in &[
+            (10.0, 20.0, 500_000.0),
+            (-30.0, 100.0, 1_500_000.0),
+            (0.0, 180.0, 300_000.0),
+            (59.0, -170.0, 2_000_000.0),
+            (80.0, 0.0, 3_000_000.0),
+            (0.0, 0.0, 1.0),
+        ] {
+            let query =
+                LatLonPoint::new_distance_query("location".into(), lat, lon, radius).unwrap();
+            let n = expected(&|la, lo| haversin_meters(lat, lon, la, lo) <= radius);
+            assert_eq!(count(query.as_ref()), n);
+        }
+        // the points on both sides of the dateline
+        assert_eq!(
+            count(
+                LatLonPoint::new_distance_query("location".into(), 0.0, 180.0, 300_000.0)
+                    .unwrap()
+                    .as_ref()
+            ),
+            5
+        );
+
+        for &(min_lat, max_lat, min_lon, max_lon) in &[
+            (-10.0, 10.0, -20.0, 20.0),
+            (-10.0, 10.0, 170.0, -170.0),
+            (30.2, 45.7, 179.0, -179.0),
+            (-90.0, 90.0, -180.0, 180.0),
+        ] {
+            let query =
+                LatLonPoint::new_box_query("location".into(), min_lat, max_lat, min_lon, max_lon)
+                    .unwrap();
+            let rect = Rectangle::new(min_lat, max_lat, min_lon, max_lon).unwrap();
+            assert_eq!(
+                count(query.as_ref()),
+                expected(&|la, lo| rect.contains(la, lo))
+            );
+        }
+
+        let hole = Polygon::new(
+            vec![-5.0, -5.0, 5.0, 5.0, -5.0],
+            vec![-5.0, 5.0, 5.0, -5.0, -5.0],
+            vec![],
+        )
+        .unwrap();
+        let triangle = Polygon::new(
+            vec![-30.0, 40.0, -30.0, -30.0],
+            vec![-40.0, 0.0, 40.0, -40.0],
+            vec![hole.clone()],
+        )
+        .unwrap();
+        let far = Polygon::new(
+            vec![50.0, 50.0, 58.0, 50.0],
+            vec![100.0, 120.0, 110.0, 100.0],
+            vec![],
+        )
+        .unwrap();
+        let polygons = vec![triangle.clone(), far.clone()];
+        let query = LatLonPoint::new_polygon_query("location".into(), polygons).unwrap();
+        let n = expected(&|la, lo| triangle.contains(la, lo) || far.contains(la, lo));
+        assert!(n > 100);
+        assert_eq!(count(query.as_ref()), n);
+        let query = LatLonPoint::new_polygon_query("location".into(), vec![hole]).unwrap();
+        assert_eq!(count(query.as_ref()), 15);
+
+        assert!(
+            LatLonPoint::new_distance_query::<CodecEnum>("location".into(), 91.0, 0.0, 1.0)
+                .is_err()
+        );
+        assert!(
+            LatLonPoint::new_distance_query::<CodecEnum>("location".into(), 0.0, 0.0, -1.0)
+                .is_err()
+        );
+        assert!(LatLonPoint::new_polygon_query::<CodecEnum>("location".into(), vec![]).is_err());
+    }
+
+    #[test]
+    fn test_lat_lon_distance_sort_and_decay() {
+        let locations = vec![
+            vec![(48.8566, 2.3522)],
+            vec![(51.5074, -0.1278)],
+            vec![],
+            vec![(40.7128, -74.0060), (48.86, 2.35)],
+        ];
+        let mut docs = vec![];
+        for doc_locations in &locations {
+            let mut doc: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(NumericDocValuesField::new("rank", 1))];
+            for &(lat, lon) in doc_locations {
+                doc.push(Box::new(
+                    LatLonDocValuesField::new("location", lat, lon).unwrap(),
+                ));
+            }
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let (lat, lon) = (48.8566, 2.3522);
+        let london = haversin_meters(
+            lat,
+            lon,
+            LatLonDocValuesField::decode_latitude(
+                LatLonDocValuesField::encode(51.5074, -0.1278).unwrap(),
+            ),
+            LatLonDocValuesField::decode_longitude(
+                LatLonDocValuesField::encode(51.5074, -0.1278).unwrap(),
+            ),
+        );
+
+        // sort by the closest location, documents without location last
+        let sort_field = LatLonDocValuesField::new_distance_sort("location", lat, lon).unwrap();
+        assert_eq!(sort_field.field(), "location");
+        assert!(!sort_field.needs_scores());
+        assert!(LatLonDocValuesField::new_distance_sort("location", 91.0, lon).is_err());
+        let mut comparator = sort_field.get_comparator(4, None);
+        assert_eq!(comparator.get_type(), SortFieldType::Double);
+        for leaf in searcher.reader().leaves() {
+            comparator.get_information_from_reader(&leaf).unwrap();
+            for doc in 0..leaf.reader.max_doc() {
+                let slot = (leaf.doc_base() + doc) as usize;
+                comparator.copy(slot, ComparatorValue::Doc(doc)).unwrap();
+            }
+        }
+        let mut slots = vec![0, 1, 2, 3];
+        slots.sort_by(|a, b| comparator.compare(*a, *b));
+        assert_eq!(slots, vec![0, 3, 1, 2]);
+        match comparator.value(1) {
+            VariantValue::Double(d) => assert!((d - london).abs() < 1e-6),
+            _ => unreachable!(),
+        }
+        assert_eq!(comparator.value(2), VariantValue::Double(f64::INFINITY));
+
+        // boost by the distance
+        let decay = GeoDecayFunction::new(
+            "location".into(),
+            DecayType::Gauss,
+            lat,
+            lon,
+            100_000.0,
+            1_000.0,
+            0.5,
+        )
+        .unwrap();
+        assert!(GeoDecayFunction::new(
+            "location".into(),
+            DecayType::Gauss,
+            lat,
+            lon,
+            0.0,
+            0.0,
+            0.5
+        )
+        .is_err());
+        let query = FunctionScoreQuery::new(
+            Box::new(MatchAllDocsQuery),
+            vec![FilteredFunction::new(ScoreFunction::GeoDecay(
+                decay.clone(),
+            ))],
+            FunctionScoreMode::Multiply,
+            BoostMode::Replace,
+        );
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        let mut hits: Vec<(DocId, f32)> = top_docs
+            .score_docs()
+            .iter()
+            .map(|d| (d.doc_id(), d.score()))
+            .collect();
+        hits.sort_by_key(|h| h.0);
+        assert_eq!(hits.len(), 4);
+        assert!((hits[0].1 - 1.0).abs() < 1e-6);
+        assert!((hits[1].1 - decay.evaluate(london) as f32).abs() < 1e-6);
+        assert!(hits[1].1 < 0.01);
+        assert!((hits[2].1 - 1.0).abs() < 1e-6);
+        assert!((hits[3].1 - 1.0).abs() < 1e-6);
+        for (doc, score) in &hits {
+            let expl = searcher.explain(&query, *doc).unwrap();
+            assert!((expl.value() - *score).abs() < 1e-6);
+        }
+    }
+}
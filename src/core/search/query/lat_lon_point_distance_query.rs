@@ -0,0 +1,193 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::points::Relation;
+use core::codec::Codec;
+use core::search::query::{LatLonPoint, LatLonPointWeight, LatLonShape, Query, TermQuery, Weight};
+use core::search::searcher::SearchPlanBuilder;
+use core::util::geo::{
+    check_latitude, check_longitude, decode_latitude, decode_longitude, encode_latitude,
+    encode_longitude, haversin_meters, Rectangle,
+};
+use core::util::sortable_bytes2int;
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const LAT_LON_POINT_DISTANCE: &str = "lat_lon_point_distance";
+
+/// Distance query for `LatLonPoint`, matching the points within a circle given by
+/// its center and radius in meters.
+///
+/// The bounding box of the circle is used to skip the cells of the BKD tree that can
+/// not match, the exact haversine distance is only computed for the remaining points.
+#[derive(Clone)]
+pub struct LatLonPointDistanceQuery {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+    radius_meters: f64,
+    // the encoded bounding box of the circle, whose longitude range wraps around when
+    // the circle crosses the dateline
+    min_lat: i32,
+    max_lat: i32,
+    min_lon: i32,
+    max_lon: i32,
+    crosses_dateline: bool,
+}
+
+impl LatLonPointDistanceQuery {
+    pub fn new(
+        field: String,
+        latitude: f64,
+        longitude: f64,
+        radius_meters: f64,
+    ) -> Result<LatLonPointDistanceQuery> {
+        if !radius_meters.is_finite() || radius_meters < 0.0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "radius_meters: '{}' is invalid",
+                radius_meters
+            )));
+        }
+        check_latitude(latitude)?;
+        check_longitude(longitude)?;
+        let bbox = Rectangle::from_point_distance(latitude, longitude, radius_meters)?;
+        Ok(LatLonPointDistanceQuery {
+            field,
+            latitude,
+            longitude,
+            radius_meters,
+            min_lat: encode_latitude(bbox.min_lat)?,
+            max_lat: encode_latitude(bbox.max_lat)?,
+            min_lon: encode_longitude(bbox.min_lon)?,
+            max_lon: encode_longitude(bbox.max_lon)?,
+            crosses_dateline: bbox.crosses_dateline(),
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    pub fn radius_meters(&self) -> f64 {
+        self.radius_meters
+    }
+
+    fn lat_outside(&self, min_lat: i32, max_lat: i32) -> bool {
+        max_lat < self.min_lat || min_lat > self.max_lat
+    }
+
+    fn lon_outside(&self, min_lon: i32, max_lon: i32) -> bool {
+        if self.crosses_dateline {
+            max_lon < self.min_lon && min_lon > self.max_lon
+        } else {
+            max_lon < self.min_lon || min_lon > self.max_lon
+        }
+    }
+
+    fn within_distance(&self, lat: f64, lon: f64) -> bool {
+        haversin_meters(self.latitude, self.longitude, lat, lon) <= self.radius_meters
+    }
+
+    // checking the corners of a cell is only enough when the center is less than 90
+    // degrees of longitude away from it, else the cell edges may bulge out of the circle
+    fn within_90_lon_degrees(&self, min_lon: f64, max_lon: f64) -> bool {
+        let mut lon = self.longitude;
+        if max_lon <= lon - 180.0 {
+            lon -= 360.0;
+        } else if min_lon >= lon + 180.0 {
+            lon += 360.0;
+        }
+        max_lon - lon < 90.0 && lon - min_lon < 90.0
+    }
+}
+
+impl LatLonShape for LatLonPointDistanceQuery {
+    fn field(&self) -> &str {
+        &self.field
+    }
+
+    fn relate(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        let bytes = LatLonPoint::BYTES;
+        let min_lat = sortable_bytes2int(&min_packed_value[..bytes]);
+        let max_lat = sortable_bytes2int(&max_packed_value[..bytes]);
+        let min_lon = sortable_bytes2int(&min_packed_value[bytes..2 * bytes]);
+        let max_lon = sortable_bytes2int(&max_packed_value[bytes..2 * bytes]);
+        if self.lat_outside(min_lat, max_lat) || self.lon_outside(min_lon, max_lon) {
+            return Relation::CellOutsideQuery;
+        }
+
+        let (min_lat, max_lat) = (decode_latitude(min_lat), decode_latitude(max_lat));
+        let (min_lon, max_lon) = (decode_longitude(min_lon), decode_longitude(max_lon));
+        if self.within_90_lon_degrees(min_lon, max_lon)
+            && self.within_distance(min_lat, min_lon)
+            && self.within_distance(min_lat, max_lon)
+            && self.within_distance(max_lat, min_lon)
+            && self.within_distance(max_lat, max_lon)
+        {
+            Relation::CellInsideQuery
+        } else {
+            Relation::CellCrossesQuery
+        }
+    }
+
+    fn contains(&self, packed_value: &[u8]) -> bool {
+        let bytes = LatLonPoint::BYTES;
+        let lat = sortable_bytes2int(&packed_value[..bytes]);
+        let lon = sortable_bytes2int(&packed_value[bytes..2 * bytes]);
+        !self.lat_outside(lat, lat)
+            && !self.lon_outside(lon, lon)
+            && self.within_distance(decode_latitude(lat), decode_longitude(lon))
+    }
+}
+
+impl<C: Codec> Query<C> for LatLonPointDistanceQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(LatLonPointWeight::new(
+            self.clone(),
+            LAT_LON_POINT_DISTANCE,
+        )))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for LatLonPointDistanceQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LatLonPointDistanceQuery(field: {}, latitude: {}, longitude: {}, radius: {})",
+            &self.field, self.latitude, self.longitude, self.radius_meters
+        )
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::points::Relation;
+use core::codec::Codec;
+use core::search::query::{LatLonPoint, LatLonPointWeight, LatLonShape, Query, TermQuery, Weight};
+use core::search::searcher::SearchPlanBuilder;
+use core::util::geo::{
+    decode_latitude, decode_longitude, encode_latitude, encode_longitude, Polygon,
+};
+use core::util::sortable_bytes2int;
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const LAT_LON_POINT_IN_POLYGON: &str = "lat_lon_point_in_polygon";
+
+/// Finds all previously indexed `LatLonPoint`s that fall within one of the specified
+/// polygons, excluding the points within their holes.
+///
+/// The cells of the BKD tree outside the bounding box of the polygons are skipped, and
+/// the cells fully within a polygon are matched without checking each point.
+#[derive(Clone)]
+pub struct LatLonPointInPolygonQuery {
+    field: String,
+    polygons: Vec<Polygon>,
+    // the encoded bounding box of all the polygons
+    min_lat: i32,
+    max_lat: i32,
+    min_lon: i32,
+    max_lon: i32,
+}
+
+impl LatLonPointInPolygonQuery {
+    pub fn new(field: String, polygons: Vec<Polygon>) -> Result<LatLonPointInPolygonQuery> {
+        if polygons.is_empty() {
+            bail!(ErrorKind::IllegalArgument(
+                "polygons must not be empty".into()
+            ));
+        }
+        let min_lat = polygons.iter().map(|p| p.min_lat).fold(90.0, f64::min);
+        let max_lat = polygons.iter().map(|p| p.max_lat).fold(-90.0, f64::max);
+        let min_lon = polygons.iter().map(|p| p.min_lon).fold(180.0, f64::min);
+        let max_lon = polygons.iter().map(|p| p.max_lon).fold(-180.0, f64::max);
+        Ok(LatLonPointInPolygonQuery {
+            field,
+            min_lat: encode_latitude(min_lat)?,
+            max_lat: encode_latitude(max_lat)?,
+            min_lon: encode_longitude(min_lon)?,
+            max_lon: encode_longitude(max_lon)?,
+            polygons,
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn polygons(&self) -> &[Polygon] {
+        &self.polygons
+    }
+}
+
+impl LatLonShape for LatLonPointInPolygonQuery {
+    fn field(&self) -> &str {
+        &self.field
+    }
+
+    fn relate(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        let bytes = LatLonPoint::BYTES;
+        let min_lat = sortable_bytes2int(&min_packed_value[..bytes]);
+        let max_lat = sortable_bytes2int(&max_packed_value[..bytes]);
+        let min_lon = sortable_bytes2int(&min_packed_value[bytes..2 * bytes]);
+        let max_lon = sortable_bytes2int(&max_packed_value[bytes..2 * bytes]);
+        if max_lat < self.min_lat
+            || min_lat > self.max_lat
+            || max_lon < self.min_lon
+            || min_lon > self.max_lon
+        {
+            return Relation::CellOutsideQuery;
+        }
+
+        let (min_lat, max_lat) = (decode_latitude(min_lat), decode_latitude(max_lat));
+        let (min_lon, max_lon) = (decode_longitude(min_lon), decode_longitude(max_lon));
+        let mut relation = Relation::CellOutsideQuery;
+        for polygon in &self.polygons {
+            match polygon.relate(min_lat, max_lat, min_lon, max_lon) {
+                Relation::CellInsideQuery => return Relation::CellInsideQuery,
+                Relation::CellCrossesQuery => relation = Relation::CellCrossesQuery,
+                Relation::CellOutsideQuery => {}
+            }
+        }
+        relation
+    }
+
+    fn contains(&self, packed_value: &[u8]) -> bool {
+        let lat = LatLonPoint::decode_latitude(packed_value);
+        let lon = LatLonPoint::decode_longitude(packed_value);
+        self.polygons.iter().any(|p| p.contains(lat, lon))
+    }
+}
+
+impl<C: Codec> Query<C> for LatLonPointInPolygonQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(LatLonPointWeight::new(
+            self.clone(),
+            LAT_LON_POINT_IN_POLYGON,
+        )))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for LatLonPointInPolygonQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LatLonPointInPolygonQuery(field: {}, polygons: [",
+            &self.field
+        )?;
+        for (i, polygon) in self.polygons.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", polygon)?;
+        }
+        write!(f, "])")
+    }
+}
@@ -15,7 +15,7 @@ use core::codec::Codec;
 use core::index::reader::LeafReaderContext;
 use core::search::explanation::Explanation;
 use core::search::query::{Query, TermQuery, Weight};
-use core::search::scorer::{two_phase_next, ConstantScoreScorer, Scorer};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
 use core::search::searcher::SearchPlanBuilder;
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::util::DocId;
@@ -180,6 +180,10 @@ impl<C: Codec> ConstantScoreQuery<C> {
     pub fn get_raw_query(&self) -> &dyn Query<C> {
         self.query.as_ref()
     }
+
+    pub fn boost(&self) -> f32 {
+        self.boost
+    }
 }
 
 impl<C: Codec> fmt::Display for ConstantScoreQuery<C> {
@@ -231,18 +235,42 @@ impl<C: Codec> ConstantScoreWeight<C> {
     }
 }
 
-impl<C: Codec> Weight<C> for ConstantScoreWeight<C> {
-    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
-        if let Some(inner_scorer) = self.sub_weight.create_scorer(reader)? {
+impl<C: Codec> ConstantScoreWeight<C> {
+    fn wrap_scorer(&self, inner_scorer: Option<Box<dyn Scorer>>) -> Option<Box<dyn Scorer>> {
+        inner_scorer.map(|inner_scorer| {
             let cost = inner_scorer.cost();
-            Ok(Some(Box::new(ConstantScoreScorer {
+            let scorer: Box<dyn Scorer> = Box::new(ConstantScoreScorer {
                 score: self.query_weight,
                 iterator: inner_scorer,
                 cost,
-            })))
-        } else {
-            Ok(None)
-        }
+            });
+            scorer
+        })
+    }
+}
+
+impl<C: Codec> Weight<C> for ConstantScoreWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self.wrap_scorer(self.sub_weight.create_scorer(reader)?))
+    }
+
+    fn create_scorer_with_lead_cost(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        lead_cost: usize,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        Ok(self.wrap_scorer(
+            self.sub_weight
+                .create_scorer_with_lead_cost(reader, lead_cost)?,
+        ))
+    }
+
+    fn uses_lead_cost(&self) -> bool {
+        self.sub_weight.uses_lead_cost()
+    }
+
+    fn estimate_cost(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        self.sub_weight.estimate_cost(reader)
     }
 
     fn query_type(&self) -> &'static str {
@@ -264,11 +292,7 @@ impl<C: Codec> Weight<C> for ConstantScoreWeight<C> {
 
     fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
         let exists = if let Some(mut iterator) = self.sub_weight.create_scorer(reader)? {
-            if iterator.support_two_phase() {
-                two_phase_next(iterator.as_mut())? == doc && iterator.matches()?
-            } else {
-                iterator.advance(doc)? == doc
-            }
+            iterator.advance(doc)? == doc
         } else {
             false
         };
@@ -14,10 +14,16 @@
 #[macro_use]
 pub mod spans;
 
+pub mod intervals;
+
 mod boolean_query;
 
 pub use self::boolean_query::*;
 
+mod covering_query;
+
+pub use self::covering_query::*;
+
 mod boost_query;
 
 pub use self::boost_query::*;
@@ -26,6 +32,10 @@ mod filter_query;
 
 pub use self::filter_query::*;
 
+mod function_score_query;
+
+pub use self::function_score_query::*;
+
 mod match_all_query;
 
 pub use self::match_all_query::*;
@@ -38,14 +48,50 @@ mod point_range_query;
 
 pub use self::point_range_query::*;
 
+mod point_in_set_query;
+
+pub use self::point_in_set_query::*;
+
+mod range_field_query;
+
+pub use self::range_field_query::*;
+
+mod lat_lon_point;
+
+pub use self::lat_lon_point::*;
+
+mod lat_lon_point_distance_query;
+
+pub use self::lat_lon_point_distance_query::*;
+
+mod lat_lon_point_in_polygon_query;
+
+pub use self::lat_lon_point_in_polygon_query::*;
+
+mod score_function;
+
+pub use self::score_function::*;
+
 mod query_string;
 
 pub use self::query_string::*;
 
+mod simple_query_string;
+
+pub use self::simple_query_string::*;
+
 mod term_query;
 
 pub use self::term_query::*;
 
+mod term_range_query;
+
+pub use self::term_range_query::*;
+
+mod multi_term_query;
+
+pub use self::multi_term_query::*;
+
 mod disjunction_max_query;
 
 pub use self::disjunction_max_query::*;
@@ -54,10 +100,54 @@ mod boosting_query;
 
 pub use self::boosting_query::*;
 
+mod common_terms_query;
+
+pub use self::common_terms_query::*;
+
+mod combined_fields_query;
+
+pub use self::combined_fields_query::*;
+
+mod feature_query;
+
+pub use self::feature_query::*;
+
 mod exists_query;
 
 pub use self::exists_query::*;
 
+mod doc_values_range_query;
+
+pub use self::doc_values_range_query::*;
+
+mod doc_values_terms_query;
+
+pub use self::doc_values_terms_query::*;
+
+mod index_or_doc_values_query;
+
+pub use self::index_or_doc_values_query::*;
+
+mod join_query;
+
+pub use self::join_query::*;
+
+mod block_join_query;
+
+pub use self::block_join_query::*;
+
+mod more_like_this;
+
+pub use self::more_like_this::*;
+
+mod multi_phrase_query;
+
+pub use self::multi_phrase_query::*;
+
+mod synonym_query;
+
+pub use self::synonym_query::*;
+
 use core::codec::Codec;
 use core::index::reader::LeafReaderContext;
 use core::search::explanation::Explanation;
@@ -94,12 +184,16 @@ use std::hash::{Hash, Hasher};
 /// * [`BooleanQuery`]
 /// * [`BoostQuery`]
 /// * [`PhraseQuery`]
+/// * [`MultiPhraseQuery`]
+/// * [`SynonymQuery`]
 /// * [`PointRangeQuery`](point_range/struct.PointRangeQuery.html)
 /// * [`ConstantScoreQuery`](match_all/struct.ConstantScoreQuery.html)
 /// * [`DisjunctionMaxQuery`](disjunction/struct.DisjunctionMaxQuery.html)
 /// * [`MatchAllDocsQuery`](match_all/struct.MatchAllDocsQuery.html)
+/// * [`ToParentBlockJoinQuery`] and [`ToChildBlockJoinQuery`]
 ///
 /// See also the family of [`Span Queries`](spans/index.html)
+/// and [`Interval Queries`](intervals/index.html)
 pub trait Query<C: Codec>: Display {
     /// Create new `Scorer` based on query.
     fn create_weight(
@@ -138,6 +232,29 @@ pub trait Query<C: Codec>: Display {
 pub trait Weight<C: Codec>: Display {
     fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>>;
 
+    /// Expert: create a `Scorer` that is only going to be used to verify docs coming from
+    /// iterators whose cost is at most `lead_cost`, so that a cheaper way of matching docs
+    /// may be picked, e.g. checking doc values instead of walking an index structure.
+    fn create_scorer_with_lead_cost(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        _lead_cost: usize,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        self.create_scorer(reader)
+    }
+
+    /// Whether `create_scorer_with_lead_cost` makes use of the lead cost, in which case
+    /// conjunctions create this scorer after the other required ones.
+    fn uses_lead_cost(&self) -> bool {
+        false
+    }
+
+    /// Expert: a cheap estimate of the cost of the `Scorer` for the given segment, computed
+    /// without creating it, or `None` if no such estimate is available.
+    fn estimate_cost(&self, _reader: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
     fn hash_code(&self) -> u32 {
         let key = format!("{}", self);
         let mut hasher = DefaultHasher::new();
@@ -168,3 +285,140 @@ pub trait Weight<C: Codec>: Display {
     /// An explanation of the score computation for the named document.
     fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation>;
 }
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempfile;
+
+    use core::analysis::WhitespaceTokenizer;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType, Fieldable, IndexOptions};
+    use core::index::merge::{SerialMergeScheduler, TieredMergePolicy};
+    use core::index::reader::StandardDirectoryReader;
+    use core::index::writer::{IndexWriter, IndexWriterConfig};
+    use core::search::query::{LongPoint, Query};
+    use core::search::searcher::IndexSearcher;
+    use core::store::directory::FSDirectory;
+    use core::util::VariantValue;
+
+    use std::io;
+    use std::sync::Arc;
+
+    use self::tempfile::TempDir;
+
+    pub type TestReader =
+        StandardDirectoryReader<FSDirectory, CodecEnum, SerialMergeScheduler, TieredMergePolicy>;
+
+    pub struct StringReader {
+        text: String,
+        index: usize,
+    }
+
+    impl StringReader {
+        pub fn new(text: String) -> Self {
+            StringReader { text, index: 0 }
+        }
+    }
+
+    impl io::Read for StringReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let remain = buf.len().min(self.text.len() - self.index);
+            if remain > 0 {
+                buf[..remain]
+                    .copy_from_slice(&self.text.as_bytes()[self.index..self.index + remain]);
+                self.index += remain;
+            }
+            Ok(remain)
+        }
+    }
+
+    /// A whitespace tokenized field indexed with positions.
+    pub fn new_index_text_field(field_name: String, text: String) -> Field {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+        let token_stream = WhitespaceTokenizer::new(Box::new(StringReader::new(text)));
+        Field::new(field_name, field_type, None, Some(Box::new(token_stream)))
+    }
+
+    /// A whitespace tokenized field indexed without frequencies nor positions.
+    pub fn new_keyword_field(field_name: &str, text: &str) -> Field {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::Docs;
+        let token_stream = WhitespaceTokenizer::new(Box::new(StringReader::new(text.into())));
+        Field::new(
+            field_name.into(),
+            field_type,
+            None,
+            Some(Box::new(token_stream)),
+        )
+    }
+
+    /// A one dimension long point field.
+    pub fn new_long_point(field_name: &str, value: i64) -> Field {
+        let mut field_type = FieldType::default();
+        field_type.dimension_count = 1;
+        field_type.dimension_num_bytes = 8;
+        Field::new(
+            field_name.into(),
+            field_type,
+            Some(VariantValue::Binary(LongPoint::pack(&[value]))),
+            None,
+        )
+    }
+
+    pub type TestWriter =
+        IndexWriter<FSDirectory, CodecEnum, SerialMergeScheduler, TieredMergePolicy>;
+
+    pub type TestWriterConfig =
+        IndexWriterConfig<CodecEnum, SerialMergeScheduler, TieredMergePolicy>;
+
+    /// Returns a writer over a fresh index in a temporary directory, which is
+    /// removed once the returned `TempDir` is dropped.
+    pub fn new_index_writer(config: TestWriterConfig) -> (TempDir, TestWriter) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = Arc::new(FSDirectory::with_path(temp_dir.path()).unwrap());
+        let writer = IndexWriter::new(directory, Arc::new(config)).unwrap();
+        (temp_dir, writer)
+    }
+
+    /// Indexes the given documents into a fresh temporary index and returns a
+    /// reader over it, along with the directory that must outlive it.
+    pub fn index_fields(docs: Vec<Vec<Box<dyn Fieldable>>>) -> (TempDir, TestReader) {
+        let (temp_dir, writer) = new_index_writer(IndexWriterConfig::default());
+        for doc in docs {
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+        (temp_dir, writer.get_reader(true, false).unwrap())
+    }
+
+    /// Indexes the given documents, each a list of (field, text) pairs, into a
+    /// fresh temporary index and returns a reader over it.
+    pub fn index_docs(docs: Vec<Vec<(&str, &str)>>) -> (TempDir, TestReader) {
+        index_fields(
+            docs.into_iter()
+                .map(|fields| {
+                    fields
+                        .into_iter()
+                        .map(|(field, text)| -> Box<dyn Fieldable> {
+                            Box::new(new_index_text_field(field.into(), text.into()))
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Indexes one document per text, all in the same field.
+    pub fn index_texts(field: &str, texts: &[&str]) -> (TempDir, TestReader) {
+        index_docs(texts.iter().map(|text| vec![(field, *text)]).collect())
+    }
+
+    /// Counts all the documents matching `query`, however many there are.
+    pub fn count_hits<S: IndexSearcher<CodecEnum>>(
+        searcher: &S,
+        query: &dyn Query<CodecEnum>,
+    ) -> usize {
+        searcher.count(query).unwrap() as usize
+    }
+}
@@ -0,0 +1,444 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, Fields, PostingIterator, TermIterator, Terms};
+use core::doc::{Fieldable, Term};
+use core::index::reader::IndexReader;
+use core::search::query::{whitespace_analyzer, BooleanQuery, Query, QueryAnalyzer, TermQuery};
+use core::search::searcher::IndexSearcher;
+use core::search::DocIterator;
+use core::util::DocId;
+
+use error::Result;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// An interesting term picked by `MoreLikeThis`, with its tf-idf score.
+#[derive(Debug, Clone)]
+pub struct ScoreTerm {
+    pub field: String,
+    pub word: String,
+    /// The frequency of the term in the liked document or text.
+    pub tf: i32,
+    /// The number of documents of the index containing the term.
+    pub doc_freq: i32,
+    pub idf: f32,
+    pub score: f32,
+}
+
+/// Generates "more like this" queries, searching the documents similar to a
+/// document of the index or to a free text.
+///
+/// The terms of the liked document are read from its term vectors when the field
+/// has them, and from the re-analysis of its stored values otherwise. A free text is
+/// analyzed for each field. The terms are then scored by tf-idf, and the top
+/// `max_query_terms` of them are searched in a `BooleanQuery` of should clauses,
+/// boosted by their score relatively to the best one when `boost` is enabled.
+///
+/// The terms too rare in the liked text (`min_term_freq`), too rare or too common in
+/// the index (`min_doc_freq`, `max_doc_freq`), too short or too long, or in the stop
+/// words are not picked.
+pub struct MoreLikeThis {
+    fields: Vec<String>,
+    analyzer: QueryAnalyzer,
+    min_term_freq: i32,
+    min_doc_freq: i32,
+    max_doc_freq: i32,
+    min_word_len: usize,
+    max_word_len: usize,
+    max_query_terms: usize,
+    max_num_tokens_parsed: usize,
+    stop_words: HashSet<String>,
+    boost: bool,
+    boost_factor: f32,
+}
+
+impl MoreLikeThis {
+    pub const DEFAULT_MIN_TERM_FREQ: i32 = 2;
+    pub const DEFAULT_MIN_DOC_FREQ: i32 = 5;
+    pub const DEFAULT_MAX_QUERY_TERMS: usize = 25;
+    pub const DEFAULT_MAX_NUM_TOKENS_PARSED: usize = 5000;
+
+    pub fn new(fields: Vec<String>) -> Self {
+        MoreLikeThis {
+            fields,
+            analyzer: whitespace_analyzer(),
+            min_term_freq: Self::DEFAULT_MIN_TERM_FREQ,
+            min_doc_freq: Self::DEFAULT_MIN_DOC_FREQ,
+            max_doc_freq: i32::max_value(),
+            min_word_len: 0,
+            max_word_len: 0,
+            max_query_terms: Self::DEFAULT_MAX_QUERY_TERMS,
+            max_num_tokens_parsed: Self::DEFAULT_MAX_NUM_TOKENS_PARSED,
+            stop_words: HashSet::new(),
+            boost: false,
+            boost_factor: 1.0,
+        }
+    }
+
+    /// The analyzer of the liked text and of the stored values without term vectors.
+    pub fn with_analyzer(mut self, analyzer: QueryAnalyzer) -> Self {
+        self.analyzer = analyzer;
+        self
+    }
+
+    /// Ignore the terms appearing less than `min_term_freq` times in the liked text.
+    pub fn with_min_term_freq(mut self, min_term_freq: i32) -> Self {
+        self.min_term_freq = min_term_freq;
+        self
+    }
+
+    /// Ignore the terms appearing in less than `min_doc_freq` documents.
+    pub fn with_min_doc_freq(mut self, min_doc_freq: i32) -> Self {
+        self.min_doc_freq = min_doc_freq;
+        self
+    }
+
+    /// Ignore the terms appearing in more than `max_doc_freq` documents.
+    pub fn with_max_doc_freq(mut self, max_doc_freq: i32) -> Self {
+        self.max_doc_freq = max_doc_freq;
+        self
+    }
+
+    /// Ignore the terms appearing in more than `max_percentage` percent of the documents.
+    pub fn with_max_doc_freq_pct<C: Codec, IS: IndexSearcher<C>>(
+        self,
+        searcher: &IS,
+        max_percentage: i32,
+    ) -> Self {
+        let max_doc_freq =
+            (searcher.reader().num_docs() as i64 * max_percentage as i64 / 100) as i32;
+        self.with_max_doc_freq(max_doc_freq)
+    }
+
+    /// Ignore the words shorter than `min_word_len` chars, 0 for no limit.
+    pub fn with_min_word_len(mut self, min_word_len: usize) -> Self {
+        self.min_word_len = min_word_len;
+        self
+    }
+
+    /// Ignore the words longer than `max_word_len` chars, 0 for no limit.
+    pub fn with_max_word_len(mut self, max_word_len: usize) -> Self {
+        self.max_word_len = max_word_len;
+        self
+    }
+
+    /// The maximum number of terms of the generated query.
+    pub fn with_max_query_terms(mut self, max_query_terms: usize) -> Self {
+        self.max_query_terms = max_query_terms;
+        self
+    }
+
+    /// The maximum number of tokens analyzed for each field of the liked text.
+    pub fn with_max_num_tokens_parsed(mut self, max_num_tokens_parsed: usize) -> Self {
+        self.max_num_tokens_parsed = max_num_tokens_parsed;
+        self
+    }
+
+    pub fn with_stop_words(mut self, stop_words: HashSet<String>) -> Self {
+        self.stop_words = stop_words;
+        self
+    }
+
+    /// Boost the terms by their score relatively to the best term, times `boost_factor`.
+    pub fn with_boost(mut self, boost: bool, boost_factor: f32) -> Self {
+        self.boost = boost;
+        self.boost_factor = boost_factor;
+        self
+    }
+
+    /// Build the query of the documents like `doc_id`, or `None` if no term was picked.
+    pub fn like_doc<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        doc_id: DocId,
+    ) -> Result<Option<Box<dyn Query<C>>>> {
+        let terms = self.interesting_terms_of_doc(searcher, doc_id)?;
+        self.create_query(terms)
+    }
+
+    /// Build the query of the documents like `text`, or `None` if no term was picked.
+    pub fn like_text<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        text: &str,
+    ) -> Result<Option<Box<dyn Query<C>>>> {
+        let terms = self.interesting_terms_of_text(searcher, text)?;
+        self.create_query(terms)
+    }
+
+    /// The terms picked for the documents like `doc_id`, by descending score.
+    pub fn interesting_terms_of_doc<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        doc_id: DocId,
+    ) -> Result<Vec<ScoreTerm>> {
+        let reader = searcher.reader();
+        let vectors = reader.term_vector(doc_id)?;
+        let mut field_freqs = HashMap::new();
+        for field in &self.fields {
+            let mut freqs = HashMap::new();
+            let vector = match vectors {
+                Some(ref vectors) => vectors.terms(field)?,
+                None => None,
+            };
+            if let Some(vector) = vector {
+                let mut terms = vector.iterator()?;
+                while let Some(term) = terms.next()? {
+                    if let Ok(word) = String::from_utf8(term) {
+                        let mut postings = terms.postings()?;
+                        postings.next()?;
+                        *freqs.entry(word).or_insert(0) += postings.freq()?;
+                    }
+                }
+            } else {
+                // no term vector, analyze the stored values
+                let doc = reader.document(doc_id, &[field.clone()])?;
+                for stored in &doc.fields {
+                    if let Some(text) = stored.field.string_value() {
+                        self.add_term_freqs(&mut freqs, text)?;
+                    }
+                }
+            }
+            field_freqs.insert(field.clone(), freqs);
+        }
+        self.score_terms(searcher, field_freqs)
+    }
+
+    /// The terms picked for the documents like `text`, by descending score.
+    pub fn interesting_terms_of_text<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        text: &str,
+    ) -> Result<Vec<ScoreTerm>> {
+        let mut field_freqs = HashMap::new();
+        for field in &self.fields {
+            let mut freqs = HashMap::new();
+            self.add_term_freqs(&mut freqs, text)?;
+            field_freqs.insert(field.clone(), freqs);
+        }
+        self.score_terms(searcher, field_freqs)
+    }
+
+    fn add_term_freqs(&self, freqs: &mut HashMap<String, i32>, text: &str) -> Result<()> {
+        let mut token_stream = (self.analyzer)(text);
+        token_stream.reset()?;
+        let mut num_tokens = 0;
+        while token_stream.next_token()? {
+            num_tokens += 1;
+            if num_tokens > self.max_num_tokens_parsed {
+                break;
+            }
+            if let Ok(word) = String::from_utf8(token_stream.token().term.clone()) {
+                *freqs.entry(word).or_insert(0) += 1;
+            }
+        }
+        token_stream.end()
+    }
+
+    fn is_noise_word(&self, word: &str) -> bool {
+        let len = word.chars().count();
+        (self.min_word_len > 0 && len < self.min_word_len)
+            || (self.max_word_len > 0 && len > self.max_word_len)
+            || self.stop_words.contains(word)
+    }
+
+    /// Score the terms of each field by tf-idf, keeping the `max_query_terms` best ones.
+    fn score_terms<C: Codec, IS: IndexSearcher<C>>(
+        &self,
+        searcher: &IS,
+        field_freqs: HashMap<String, HashMap<String, i32>>,
+    ) -> Result<Vec<ScoreTerm>> {
+        let reader = searcher.reader();
+        let leaves = reader.leaves();
+        let num_docs = reader.num_docs();
+        let mut score_terms = vec![];
+        for (field, freqs) in field_freqs {
+            for (word, tf) in freqs {
+                if tf < self.min_term_freq || self.is_noise_word(&word) {
+                    continue;
+                }
+                let term = Term::new(field.clone(), word.as_bytes().to_vec());
+                let mut doc_freq = 0;
+                for leaf in &leaves {
+                    doc_freq += leaf.reader.doc_freq(&term)?;
+                }
+                if doc_freq == 0 || doc_freq < self.min_doc_freq || doc_freq > self.max_doc_freq {
+                    continue;
+                }
+                let idf = 1.0 + ((num_docs as f64 + 1.0) / (doc_freq as f64 + 1.0)).ln() as f32;
+                score_terms.push(ScoreTerm {
+                    field: field.clone(),
+                    word,
+                    tf,
+                    doc_freq,
+                    idf,
+                    score: tf as f32 * idf,
+                });
+            }
+        }
+        score_terms.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.field.cmp(&b.field))
+                .then_with(|| a.word.cmp(&b.word))
+        });
+        score_terms.truncate(self.max_query_terms);
+        Ok(score_terms)
+    }
+
+    fn create_query<C: Codec>(&self, terms: Vec<ScoreTerm>) -> Result<Option<Box<dyn Query<C>>>> {
+        if terms.is_empty() {
+            return Ok(None);
+        }
+        let best_score = terms[0].score;
+        let mut shoulds: Vec<Box<dyn Query<C>>> = Vec::with_capacity(terms.len());
+        for term in terms {
+            let boost = if self.boost && best_score > 0.0 {
+                self.boost_factor * term.score / best_score
+            } else {
+                1.0
+            };
+            shoulds.push(Box::new(TermQuery::new(
+                Term::new(term.field, term.word.into_bytes()),
+                boost,
+                None,
+            )));
+        }
+        Ok(Some(BooleanQuery::build(
+            vec![],
+            shoulds,
+            vec![],
+            vec![],
+            0,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::analysis::WhitespaceTokenizer;
+    use core::doc::{Field, FieldType, IndexOptions};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_fields;
+    use core::search::DefaultIndexSearcher;
+    use core::util::VariantValue;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn text_field(name: &str, text: &str, term_vectors: bool) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        field_type.stored = true;
+        field_type.store_term_vectors = term_vectors;
+        let token_stream =
+            WhitespaceTokenizer::new(Box::new(Cursor::new(text.as_bytes().to_vec())));
+        Box::new(Field::new(
+            name.into(),
+            field_type,
+            Some(VariantValue::VString(text.into())),
+            Some(Box::new(token_stream)),
+        ))
+    }
+
+    #[test]
+    fn test_more_like_this() {
+        let texts = [
+            (
+                "rust borrow checker rust lifetimes",
+                "how to fix the borrow checker",
+            ),
+            ("rust lifetimes explained", "lifetimes of borrow"),
+            ("java garbage collector", "tuning the collector"),
+            ("rust async runtime", "async rust"),
+            ("the borrow checker and lifetimes", "rust"),
+        ];
+        let mut docs = vec![];
+        for (i, &(title, body)) in texts.iter().enumerate() {
+            // the bodies of the odd docs have no term vectors
+            docs.push(vec![
+                text_field("title", title, true),
+                text_field("body", body, i % 2 == 0),
+            ]);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+
+        let mlt = MoreLikeThis::new(vec!["title".into(), "body".into()])
+            .with_min_term_freq(1)
+            .with_min_doc_freq(2)
+            .with_max_doc_freq(2)
+            .with_min_word_len(4);
+
+        // "rust" and "lifetimes" are in too many titles, "the" is too short, and
+        // "checker" is in a single body
+        let terms = mlt.interesting_terms_of_doc(&searcher, 0).unwrap();
+        let mut words: Vec<(&str, &str)> = terms
+            .iter()
+            .map(|t| (t.field.as_str(), t.word.as_str()))
+            .collect();
+        words.sort();
+        assert_eq!(
+            words,
+            vec![
+                ("body", "borrow"),
+                ("title", "borrow"),
+                ("title", "checker"),
+            ]
+        );
+        let checker = terms.iter().find(|t| t.word == "checker").unwrap();
+        assert_eq!(checker.tf, 1);
+        assert_eq!(checker.doc_freq, 2);
+
+        // the stored body of doc 1 is re-analyzed
+        let terms = mlt.interesting_terms_of_doc(&searcher, 1).unwrap();
+        assert!(terms
+            .iter()
+            .any(|t| t.field == "body" && t.word == "borrow"));
+
+        // the top terms only, boosted relatively to the best one
+        let mlt = mlt.with_max_query_terms(2).with_boost(true, 2.0);
+        let terms = mlt.interesting_terms_of_doc(&searcher, 0).unwrap();
+        assert_eq!(terms.len(), 2);
+        assert!(terms[0].score >= terms[1].score);
+
+        let query = mlt.like_doc(&searcher, 0).unwrap().unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(query.as_ref(), &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        let hits: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(hits[0], 0);
+        assert!(!hits.contains(&2));
+
+        let query = mlt
+            .like_text(&searcher, "tuning the java collector collector")
+            .unwrap();
+        // the collector words are only in doc 2
+        assert!(query.is_none());
+        let query = mlt
+            .with_min_doc_freq(1)
+            .like_text(&searcher, "tuning the java collector collector")
+            .unwrap()
+            .unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(query.as_ref(), &mut collector).unwrap();
+        let top_docs = collector.top_docs();
+        let hits: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(hits, vec![2]);
+    }
+}
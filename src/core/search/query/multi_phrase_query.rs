@@ -0,0 +1,626 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use core::codec::{Codec, CodecPostingIterator};
+use core::codec::{PostingIterator, PostingIteratorFlags};
+use core::codec::{TermIterator, Terms};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::query::{TERM_OPS_PER_POS, TERM_POSNS_SEEK_OPS_PER_DOC};
+use core::search::scorer::{ExactPhraseScorer, PostingsAndFreq, Scorer, SloppyPhraseScorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::similarity::{SimWeight, Similarity};
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{DocIterator, Payload, NO_MORE_DOCS};
+use core::util::{DocId, KeyedContext};
+use error::{ErrorKind, Result};
+
+pub const MULTI_PHRASE: &str = "multi_phrase";
+
+/// A generalized version of `PhraseQuery`, with the possibility of
+/// adding more than one term at the same position that are treated as a disjunction (OR).
+///
+/// To use this class to search for the phrase "Microsoft app*" first create a
+/// query with all terms at position 0 being "microsoft", then find all terms that
+/// have "app" as prefix, and put them at position 1.
+///
+/// Synonyms expanded at query time are handled the same way: "new york city" and
+/// "nyc city" can both be matched by putting "new york" and "nyc" in the same slot.
+#[derive(Clone, Debug)]
+pub struct MultiPhraseQuery {
+    field: String,
+    terms: Vec<Vec<Term>>,
+    positions: Vec<i32>,
+    slop: i32,
+    ctx: Option<KeyedContext>,
+}
+
+impl MultiPhraseQuery {
+    pub fn new<S: Into<Option<KeyedContext>>>(
+        terms: Vec<Vec<Term>>,
+        positions: Vec<i32>,
+        slop: i32,
+        ctx: S,
+    ) -> Result<MultiPhraseQuery> {
+        let ctx = ctx.into();
+        if terms.len() != positions.len() {
+            bail!(ErrorKind::IllegalArgument(
+                "Must have as many term arrays as positions".into()
+            ));
+        }
+        if slop < 0 {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "Slop must be >= 0, got {}",
+                slop
+            )));
+        }
+        if terms.len() < 2 {
+            bail!(ErrorKind::IllegalArgument(
+                "multi phrase query positions should not be less than 2!".into()
+            ));
+        }
+        if terms.iter().any(Vec::is_empty) {
+            bail!(ErrorKind::IllegalArgument(
+                "every position of multi phrase query must have at least one term!".into()
+            ));
+        }
+        let field = terms[0][0].field.clone();
+        if terms.iter().flatten().any(|t| t.field != field) {
+            bail!(ErrorKind::IllegalArgument(
+                "All terms should have the same field".into()
+            ));
+        }
+        for i in 1..positions.len() {
+            if positions[i - 1] > positions[i] || positions[i - 1] < 0 {
+                bail!(ErrorKind::IllegalArgument(format!(
+                    "Positions should not go backwards, got {} before {}",
+                    positions[i - 1],
+                    positions[i]
+                )));
+            }
+        }
+        // normalize positions
+        let mut positions = positions;
+        let first = positions[0];
+        for pos in &mut positions {
+            *pos -= first;
+        }
+
+        Ok(MultiPhraseQuery {
+            field,
+            terms,
+            positions,
+            slop,
+            ctx,
+        })
+    }
+
+    pub fn build<S: Into<Option<KeyedContext>>>(
+        terms: Vec<Vec<Term>>,
+        slop: i32,
+        ctx: S,
+    ) -> Result<MultiPhraseQuery> {
+        let positions = (0..terms.len() as i32).collect();
+        Self::new(terms, positions, slop, ctx)
+    }
+}
+
+impl<C: Codec> Query<C> for MultiPhraseQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        let mut term_stats: Vec<TermStatistics> = Vec::new();
+        for term in self.terms.iter().flatten() {
+            if needs_scores {
+                term_stats.push(searcher.term_statistics(term)?);
+            } else {
+                term_stats.push(TermStatistics::new(term.bytes.clone(), max_doc, -1));
+            }
+        }
+
+        let collection_stats = if needs_scores {
+            if let Some(stat) = searcher.collections_statistics(&self.field) {
+                stat.clone()
+            } else {
+                CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+            }
+        } else {
+            CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+        };
+
+        let similarity = searcher.similarity(&self.field, needs_scores);
+        let sim_weight =
+            similarity.compute_weight(&collection_stats, &term_stats, self.ctx.as_ref(), 1.0f32);
+
+        Ok(Box::new(MultiPhraseWeight {
+            field: self.field.clone(),
+            terms: self.terms.clone(),
+            positions: self.positions.clone(),
+            slop: self.slop,
+            similarity,
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms
+            .iter()
+            .flatten()
+            .map(|t| TermQuery::new(t.clone(), 1.0f32, None))
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for MultiPhraseQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|ts| {
+                let texts: Vec<String> = ts
+                    .iter()
+                    .map(|t| t.text().unwrap_or_else(|_| format!("{:?}", &t.bytes)))
+                    .collect();
+                format!("({})", texts.join(" "))
+            })
+            .collect();
+        write!(
+            f,
+            "MultiPhraseQuery(field: {}, terms: [{}], positions: {:?}, slop: {})",
+            &self.field,
+            terms.join(", "),
+            &self.positions,
+            self.slop,
+        )
+    }
+}
+
+struct MultiPhraseWeight<C: Codec> {
+    field: String,
+    terms: Vec<Vec<Term>>,
+    positions: Vec<i32>,
+    slop: i32,
+    similarity: Box<dyn Similarity<C>>,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+}
+
+type UnionPostingsAndFreq<C> = PostingsAndFreq<UnionPostingIterator<CodecPostingIterator<C>>>;
+
+impl<C: Codec> MultiPhraseWeight<C> {
+    fn term_positions_cost(term_iter: &mut impl TermIterator) -> Result<f32> {
+        let doc_freq = term_iter.doc_freq()?;
+        debug_assert!(doc_freq > 0);
+        let total_term_freq = term_iter.total_term_freq()?; // -1 when not available
+        let exp_occurrences_in_matching_doc = if total_term_freq < i64::from(doc_freq) {
+            1.0f32
+        } else {
+            total_term_freq as f32 / doc_freq as f32
+        };
+
+        Ok(TERM_POSNS_SEEK_OPS_PER_DOC as f32
+            + exp_occurrences_in_matching_doc * TERM_OPS_PER_POS as f32)
+    }
+
+    /// collect the union postings of every position, return `None` if
+    /// any position has no matching term in this segment.
+    fn postings_freqs(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<(Vec<UnionPostingsAndFreq<C>>, f32)>> {
+        let mut term_iter = if let Some(field_terms) = reader.reader.terms(&self.field)? {
+            if !field_terms.has_positions()? {
+                bail!(ErrorKind::IllegalState(format!(
+                    "field {} was indexed without position data; cannot run MultiPhraseQuery",
+                    self.field
+                )));
+            }
+            field_terms.iterator()?
+        } else {
+            return Ok(None);
+        };
+
+        let mut total_match_cost = 0f32;
+        let mut postings_freqs = Vec::with_capacity(self.terms.len());
+        for (terms, pos) in self.terms.iter().zip(self.positions.iter()) {
+            let mut postings = Vec::with_capacity(terms.len());
+            for term in terms {
+                if term_iter.seek_exact(&term.bytes)? {
+                    total_match_cost += Self::term_positions_cost(&mut term_iter)?;
+                    postings.push(term_iter.postings_with_flags(PostingIteratorFlags::POSITIONS)?);
+                }
+            }
+            if postings.is_empty() {
+                // none of the alternatives exists, no document can match
+                return Ok(None);
+            }
+            postings_freqs.push(PostingsAndFreq::with_terms(
+                UnionPostingIterator::new(postings),
+                *pos,
+                terms,
+            ));
+        }
+        Ok(Some((postings_freqs, total_match_cost)))
+    }
+}
+
+impl<C: Codec> Weight<C> for MultiPhraseWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        let (mut postings_freqs, total_match_cost) = match self.postings_freqs(reader)? {
+            Some(p) => p,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+        let scorer: Box<dyn Scorer> = if self.slop == 0 {
+            postings_freqs.sort();
+            Box::new(ExactPhraseScorer::new(
+                postings_freqs,
+                sim_scorer,
+                self.needs_scores,
+                total_match_cost,
+            ))
+        } else {
+            Box::new(SloppyPhraseScorer::new(
+                postings_freqs,
+                self.slop,
+                sim_scorer,
+                self.needs_scores,
+                total_match_cost,
+            ))
+        };
+        Ok(Some(scorer))
+    }
+
+    fn query_type(&self) -> &'static str {
+        MULTI_PHRASE
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some((mut postings_freqs, total_match_cost)) = self.postings_freqs(reader)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+            let freq = if self.slop == 0 {
+                postings_freqs.sort();
+                let mut scorer = ExactPhraseScorer::new(
+                    postings_freqs,
+                    sim_scorer,
+                    self.needs_scores,
+                    total_match_cost,
+                );
+                if scorer.advance(doc)? == doc {
+                    Some(scorer.freq() as f32)
+                } else {
+                    None
+                }
+            } else {
+                let mut scorer = SloppyPhraseScorer::new(
+                    postings_freqs,
+                    self.slop,
+                    sim_scorer,
+                    self.needs_scores,
+                    total_match_cost,
+                );
+                if scorer.advance(doc)? == doc {
+                    Some(scorer.sloppy_freq())
+                } else {
+                    None
+                }
+            };
+
+            if let Some(freq) = freq {
+                let freq_expl =
+                    Explanation::new(true, freq, format!("phraseFreq={}", freq), vec![]);
+                let score_expl = self.sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+                return Ok(Explanation::new(
+                    true,
+                    score_expl.value(),
+                    format!("weight({} in {}), result of:", self, doc),
+                    vec![score_expl],
+                ));
+            }
+        }
+
+        Ok(Explanation::new(
+            false,
+            0.0f32,
+            "no matching term".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for MultiPhraseWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MultiPhraseWeight(field: {}, terms: {:?}, positions: {:?}, similarity: {}, \
+             need_score: {})",
+            &self.field, &self.terms, &self.positions, &self.similarity, self.needs_scores
+        )
+    }
+}
+
+/// Takes the logical union of multiple `PostingIterator`s.
+///
+/// The freq of a doc is the sum of the freqs of all the sub iterators positioned on
+/// it, and positions are returned in increasing order across all of them.
+/// Offsets and payloads are not supported.
+pub struct UnionPostingIterator<T: PostingIterator> {
+    subs: Vec<T>,
+    doc: DocId,
+    positions: Vec<i32>,
+    pos_upto: usize,
+    positions_loaded: bool,
+    cost: usize,
+}
+
+impl<T: PostingIterator> UnionPostingIterator<T> {
+    pub fn new(subs: Vec<T>) -> Self {
+        let cost = subs.iter().map(DocIterator::cost).sum();
+        UnionPostingIterator {
+            subs,
+            doc: -1,
+            positions: Vec::new(),
+            pos_upto: 0,
+            positions_loaded: false,
+            cost,
+        }
+    }
+
+    fn update_doc(&mut self) -> DocId {
+        self.doc = self
+            .subs
+            .iter()
+            .map(DocIterator::doc_id)
+            .min()
+            .unwrap_or(NO_MORE_DOCS);
+        self.positions.clear();
+        self.pos_upto = 0;
+        self.positions_loaded = false;
+        self.doc
+    }
+
+    fn load_positions(&mut self) -> Result<()> {
+        for sub in &mut self.subs {
+            if sub.doc_id() == self.doc {
+                for _ in 0..sub.freq()? {
+                    self.positions.push(sub.next_position()?);
+                }
+            }
+        }
+        self.positions.sort_unstable();
+        self.positions_loaded = true;
+        Ok(())
+    }
+}
+
+impl<T: PostingIterator> DocIterator for UnionPostingIterator<T> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        let current = self.doc;
+        for sub in &mut self.subs {
+            if sub.doc_id() == current {
+                sub.next()?;
+            }
+        }
+        Ok(self.update_doc())
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        for sub in &mut self.subs {
+            if sub.doc_id() < target {
+                sub.advance(target)?;
+            }
+        }
+        Ok(self.update_doc())
+    }
+
+    fn cost(&self) -> usize {
+        self.cost
+    }
+}
+
+impl<T: PostingIterator> PostingIterator for UnionPostingIterator<T> {
+    fn freq(&self) -> Result<i32> {
+        let mut freq = 0;
+        for sub in &self.subs {
+            if sub.doc_id() == self.doc {
+                freq += sub.freq()?;
+            }
+        }
+        Ok(freq)
+    }
+
+    fn next_position(&mut self) -> Result<i32> {
+        if !self.positions_loaded {
+            self.load_positions()?;
+        }
+        debug_assert!(self.pos_upto < self.positions.len());
+        let pos = self.positions[self.pos_upto];
+        self.pos_upto += 1;
+        Ok(pos)
+    }
+
+    fn start_offset(&self) -> Result<i32> {
+        Ok(-1)
+    }
+
+    fn end_offset(&self) -> Result<i32> {
+        Ok(-1)
+    }
+
+    fn payload(&self) -> Result<Payload> {
+        Ok(Payload::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::Term;
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::index_texts;
+    use core::search::query::SynonymQuery;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    fn term(text: &str) -> Term {
+        Term::new("title".into(), text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn multi_phrase_and_synonym_query() {
+        let (_dir, reader) =
+            index_texts("title", &["new york city", "nyc city hall", "city of york"]);
+        let index_searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+
+        {
+            // exact
+            let query = MultiPhraseQuery::build(
+                vec![vec![term("york"), term("nyc")], vec![term("city")]],
+                0,
+                None,
+            )
+            .unwrap();
+            let mut collector = TopDocsCollector::new(10);
+            index_searcher.search(&query, &mut collector).unwrap();
+            assert_eq!(collector.top_docs().total_hits(), 2);
+
+            let explanation = index_searcher.explain(&query, 1).unwrap();
+            assert!(explanation.is_match());
+            let explanation = index_searcher.explain(&query, 2).unwrap();
+            assert!(!explanation.is_match());
+        }
+
+        {
+            // sloppy, "city of york" needs slop 3 for the reversed order
+            let query = MultiPhraseQuery::build(
+                vec![vec![term("york"), term("nyc")], vec![term("city")]],
+                3,
+                None,
+            )
+            .unwrap();
+            let mut collector = TopDocsCollector::new(10);
+            index_searcher.search(&query, &mut collector).unwrap();
+            assert_eq!(collector.top_docs().total_hits(), 3);
+        }
+
+        {
+            // missing alternatives are ignored
+            let query = MultiPhraseQuery::build(
+                vec![vec![term("nyc"), term("missing")], vec![term("city")]],
+                0,
+                None,
+            )
+            .unwrap();
+            let mut collector = TopDocsCollector::new(10);
+            index_searcher.search(&query, &mut collector).unwrap();
+            assert_eq!(collector.top_docs().total_hits(), 1);
+        }
+
+        {
+            let query = SynonymQuery::new(vec![term("york"), term("nyc")], 1.0, None).unwrap();
+            let mut collector = TopDocsCollector::new(10);
+            index_searcher.search(&query, &mut collector).unwrap();
+            assert_eq!(collector.top_docs().total_hits(), 3);
+
+            let explanation = index_searcher.explain(&query, 0).unwrap();
+            assert!(explanation.is_match());
+        }
+    }
+
+    #[test]
+    fn test_empty_term_lists() {
+        assert!(MultiPhraseQuery::build(vec![], 0, None).is_err());
+        assert!(MultiPhraseQuery::build(vec![vec![term("york")]], 0, None).is_err());
+        assert!(MultiPhraseQuery::build(vec![vec![], vec![term("city")]], 0, None).is_err());
+        assert!(
+            MultiPhraseQuery::new(vec![vec![term("york")], vec![]], vec![0, 1], 0, None).is_err()
+        );
+        assert!(SynonymQuery::new(vec![], 1.0, None).is_err());
+    }
+
+    #[test]
+    fn test_slop_and_position_gaps() {
+        let (_dir, reader) =
+            index_texts("title", &["new york city", "nyc city hall", "city of york"]);
+        let index_searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let search = |query: &MultiPhraseQuery| -> Vec<DocId> {
+            let mut collector = TopDocsCollector::new(10);
+            index_searcher.search(query, &mut collector).unwrap();
+            let mut docs: Vec<DocId> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| d.doc_id())
+                .collect();
+            docs.sort();
+            docs
+        };
+
+        // "city of york" is 3 moves away from "york city"
+        let york_city = |slop| {
+            MultiPhraseQuery::build(vec![vec![term("york")], vec![term("city")]], slop, None)
+                .unwrap()
+        };
+        assert_eq!(search(&york_city(2)), vec![0]);
+        assert_eq!(search(&york_city(3)), vec![0, 2]);
+
+        // explicit positions leave a gap for any term in between
+        let nyc_hall = |positions, slop| {
+            MultiPhraseQuery::new(
+                vec![vec![term("nyc"), term("new")], vec![term("hall")]],
+                positions,
+                slop,
+                None,
+            )
+            .unwrap()
+        };
+        assert_eq!(search(&nyc_hall(vec![0, 2], 0)), vec![1]);
+        assert!(search(&nyc_hall(vec![0, 1], 0)).is_empty());
+        assert_eq!(search(&nyc_hall(vec![0, 1], 1)), vec![1]);
+        assert!(search(&nyc_hall(vec![0, 3], 0)).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, PostingIteratorFlags, SeekStatus, TermIterator, Terms};
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::spans::MultiTermMatcher;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIdSet, DocIterator};
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::Result;
+
+use std::any::Any;
+use std::fmt;
+
+pub const MULTI_TERM: &str = "multi_term";
+
+/// A `Query` that matches the documents containing any of the terms of a field accepted
+/// by a `MultiTermMatcher`: a prefix, wildcard, regexp or fuzzy query.
+///
+/// All the accepted terms of each segment are expanded, however many there are, into a
+/// filter so the matching documents all get a constant score. Unlike
+/// `SpanMultiTermQueryWrapper`, the field does not need to be indexed with positions.
+pub struct MultiTermQuery {
+    field: String,
+    matcher: MultiTermMatcher,
+}
+
+impl MultiTermQuery {
+    pub fn new(field: String, matcher: MultiTermMatcher) -> MultiTermQuery {
+        MultiTermQuery { field, matcher }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn matcher(&self) -> &MultiTermMatcher {
+        &self.matcher
+    }
+}
+
+impl<C: Codec> Query<C> for MultiTermQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(MultiTermWeight {
+            field: self.field.clone(),
+            matcher: self.matcher.clone(),
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for MultiTermQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MultiTermQuery(field: {}, query: {})",
+            &self.field, &self.matcher
+        )
+    }
+}
+
+struct MultiTermWeight {
+    field: String,
+    matcher: MultiTermMatcher,
+    weight: f32,
+    norm: f32,
+}
+
+impl<C: Codec> Weight<C> for MultiTermWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        let terms = match leaf_reader.terms(&self.field)? {
+            Some(terms) => terms,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let mut terms_iter = terms.iterator()?;
+        let prefix = self.matcher.common_prefix();
+        let mut term = if prefix.is_empty() {
+            terms_iter.next()?
+        } else if terms_iter.seek_ceil(&prefix)? == SeekStatus::End {
+            None
+        } else {
+            Some(terms_iter.term()?.to_vec())
+        };
+        let mut result = DocIdSetBuilder::from_terms(leaf_reader.max_doc(), &terms)?;
+        let mut matched = false;
+        while let Some(bytes) = term {
+            if !bytes.starts_with(&prefix) {
+                break;
+            }
+            if self.matcher.accept(&bytes).is_some() {
+                let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+                result.add(&mut postings)?;
+                matched = true;
+            }
+            term = terms_iter.next()?;
+        }
+        if !matched {
+            return Ok(None);
+        }
+
+        if let Some(iterator) = result.build().iterator()? {
+            let cost = iterator.cost();
+            Ok(Some(Box::new(ConstantScoreScorer::new(
+                self.weight,
+                iterator,
+                cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        MULTI_TERM
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matches {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.weight / self.norm, "boost".into(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".into(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("{} doesn't match id {}", self, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for MultiTermWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MultiTermWeight(field: {}, query: {})",
+            &self.field, &self.matcher
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::Fieldable;
+    use core::search::query::tests::{count_hits, index_fields, new_keyword_field};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_multi_term_query() {
+        // more distinct terms than `DEFAULT_MAX_EXPANSIONS`, in a field without positions
+        let mut docs = vec![];
+        for i in 0..1500 {
+            let text = format!("t{:04} {}", i, if i % 2 == 0 { "even" } else { "odd" });
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(new_keyword_field("code", &text))];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |matcher: MultiTermMatcher| -> usize {
+            let query = MultiTermQuery::new("code".into(), matcher);
+            count_hits(&searcher, &query)
+        };
+
+        assert_eq!(count(MultiTermMatcher::prefix("t")), 1500);
+        assert_eq!(count(MultiTermMatcher::prefix("t01")), 100);
+        assert_eq!(count(MultiTermMatcher::prefix("x")), 0);
+        assert_eq!(count(MultiTermMatcher::wildcard("t1?00").unwrap()), 5);
+        assert_eq!(count(MultiTermMatcher::wildcard("*ve*").unwrap()), 750);
+        assert_eq!(count(MultiTermMatcher::regexp("t0[0-4]00").unwrap()), 5);
+        assert_eq!(count(MultiTermMatcher::fuzzy("ood", 1, 0).unwrap()), 750);
+        assert_eq!(count(MultiTermMatcher::fuzzy("t0010", 1, 4).unwrap()), 10);
+
+        let query = MultiTermQuery::new("code".into(), MultiTermMatcher::prefix("t000"));
+        assert_eq!(
+            format!("{}", &query),
+            "MultiTermQuery(field: code, query: t000*)"
+        );
+        assert!(searcher.explain(&query, 9).unwrap().is_match());
+        assert!(!searcher.explain(&query, 10).unwrap().is_match());
+    }
+}
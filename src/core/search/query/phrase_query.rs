@@ -130,6 +130,22 @@ impl PhraseQuery {
     fn increment_positions(length: usize) -> Vec<i32> {
         (0..length as i32).collect()
     }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    pub fn positions(&self) -> &[i32] {
+        &self.positions
+    }
+
+    pub fn slop(&self) -> i32 {
+        self.slop
+    }
 }
 
 impl<C: Codec> Query<C> for PhraseQuery {
@@ -0,0 +1,443 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::points::{IntersectVisitor, PointValues, Relation};
+use core::codec::Codec;
+use core::index::reader::{LeafReader, LeafReaderContext};
+use core::search::explanation::Explanation;
+use core::search::query::{PointValueType, Query, TermQuery, Weight};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIdSet, DocIterator};
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const POINT_IN_SET: &str = "point_in_set";
+
+/// Abstract query class to find all documents whose single or multi-dimensional
+/// point values, previously indexed with e.g. `IntPoint`, is contained in the
+/// specified set.
+///
+/// The points are sorted upfront so that a single walk of the BKD tree finds the
+/// matching documents of all of them, which is much cheaper than a `BooleanQuery`
+/// of exact `PointRangeQuery`s. Use the `new_set_query` factories of the point types
+/// to create one.
+pub struct PointInSetQuery {
+    field: String,
+    num_dims: usize,
+    bytes_per_dim: usize,
+    sorted_packed_points: Vec<Vec<u8>>,
+    value_type: PointValueType,
+}
+
+impl PointInSetQuery {
+    /// Creates a query matching the documents having any of the given packed points,
+    /// each of them `num_dims * bytes_per_dim` bytes long. The points do not have to
+    /// be sorted or unique.
+    pub fn new(
+        field: String,
+        num_dims: usize,
+        bytes_per_dim: usize,
+        mut packed_points: Vec<Vec<u8>>,
+        value_type: PointValueType,
+    ) -> Result<PointInSetQuery> {
+        assert!(!field.is_empty());
+        assert!(num_dims > 0 && bytes_per_dim > 0);
+
+        let packed_bytes_length = num_dims * bytes_per_dim;
+        if let Some(point) = packed_points
+            .iter()
+            .find(|p| p.len() != packed_bytes_length)
+        {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "packed point length should be {} but got {}; field=\"{}\" num_dims={} \
+                 bytes_per_dim={}",
+                packed_bytes_length,
+                point.len(),
+                &field,
+                num_dims,
+                bytes_per_dim
+            )));
+        }
+        packed_points.sort();
+        packed_points.dedup();
+        Ok(PointInSetQuery {
+            field,
+            num_dims,
+            bytes_per_dim,
+            sorted_packed_points: packed_points,
+            value_type,
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn num_dims(&self) -> usize {
+        self.num_dims
+    }
+
+    pub fn bytes_per_dim(&self) -> usize {
+        self.bytes_per_dim
+    }
+
+    /// The packed points of this query, sorted and deduplicated.
+    pub fn packed_points(&self) -> &[Vec<u8>] {
+        &self.sorted_packed_points
+    }
+
+    pub fn value_type(&self) -> PointValueType {
+        self.value_type
+    }
+}
+
+impl<C: Codec> Query<C> for PointInSetQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(PointInSetWeight {
+            field: self.field.clone(),
+            num_dims: self.num_dims,
+            bytes_per_dim: self.bytes_per_dim,
+            sorted_packed_points: self.sorted_packed_points.clone(),
+            value_type: self.value_type,
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for PointInSetQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PointInSetQuery(field: {}, type:{}, num_dims: {}, bytes_per_dim: {}, points: [{}])",
+            &self.field,
+            &self.value_type,
+            self.num_dims,
+            self.bytes_per_dim,
+            format_points(
+                &self.sorted_packed_points,
+                self.num_dims,
+                self.bytes_per_dim,
+                self.value_type
+            )
+        )
+    }
+}
+
+fn format_points(
+    points: &[Vec<u8>],
+    num_dims: usize,
+    bytes_per_dim: usize,
+    value_type: PointValueType,
+) -> String {
+    points
+        .iter()
+        .map(|point| {
+            if num_dims == 1 {
+                value_type.format_single_value(point)
+            } else {
+                value_type.format_bytes(point, bytes_per_dim)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct PointInSetWeight {
+    field: String,
+    num_dims: usize,
+    bytes_per_dim: usize,
+    sorted_packed_points: Vec<Vec<u8>>,
+    value_type: PointValueType,
+    weight: f32,
+    norm: f32,
+}
+
+impl PointInSetWeight {
+    /// Whether the points of this segment can be searched by this weight.
+    fn check_field<R: LeafReader + ?Sized>(&self, reader: &R) -> Result<bool> {
+        if self.sorted_packed_points.is_empty() {
+            return Ok(false);
+        }
+        match reader.field_info(&self.field) {
+            Some(field_info) => {
+                if field_info.point_dimension_count != self.num_dims as u32 {
+                    bail!(ErrorKind::IllegalArgument(format!(
+                        "field '{}' was indexed with num_dims={} but this query has num_dims={}",
+                        &self.field, field_info.point_dimension_count, self.num_dims
+                    )));
+                }
+                if field_info.point_num_bytes != self.bytes_per_dim as u32 {
+                    bail!(ErrorKind::IllegalArgument(format!(
+                        "field '{}' was indexed with bytes_per_dim={} but this query has \
+                         bytes_per_dim={}",
+                        &self.field, field_info.point_num_bytes, self.bytes_per_dim
+                    )));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for PointInSetWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        let values = match leaf_reader.point_values() {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        if !self.check_field(leaf_reader)? {
+            return Ok(None);
+        }
+
+        let mut result = DocIdSetBuilder::from_values(leaf_reader.max_doc(), &values, &self.field)?;
+        {
+            let mut visitor = PointInSetIntersectVisitor {
+                doc_id_set_builder: &mut result,
+                weight: self,
+            };
+            values.intersect(&self.field, &mut visitor)?;
+        }
+
+        if let Some(iterator) = result.build().iterator()? {
+            let cost = iterator.cost();
+            Ok(Some(Box::new(ConstantScoreScorer::new(
+                self.weight,
+                iterator,
+                cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn estimate_cost(&self, leaf_reader_ctx: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        if let Some(values) = leaf_reader.point_values() {
+            if self.check_field(leaf_reader)? {
+                let mut result =
+                    DocIdSetBuilder::from_values(leaf_reader.max_doc(), &values, &self.field)?;
+                let visitor = PointInSetIntersectVisitor {
+                    doc_id_set_builder: &mut result,
+                    weight: self,
+                };
+                let point_count = values.estimate_point_count(&self.field, &visitor)?;
+                return Ok(Some(point_count as usize));
+            }
+        }
+        Ok(Some(0))
+    }
+
+    fn query_type(&self) -> &'static str {
+        POINT_IN_SET
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matches {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.weight / self.norm, "boost".into(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".into(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("{} doesn't match id {}", self, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for PointInSetWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PointInSetWeight(field: {}, type:{}, num_dims: {}, bytes_per_dim: {}, points: [{}])",
+            &self.field,
+            &self.value_type,
+            self.num_dims,
+            self.bytes_per_dim,
+            format_points(
+                &self.sorted_packed_points,
+                self.num_dims,
+                self.bytes_per_dim,
+                self.value_type
+            )
+        )
+    }
+}
+
+/// Walks the BKD tree once for all the points of the set: since the points are
+/// sorted, the ones that may fall into a cell are found with a binary search on
+/// the first dimension.
+struct PointInSetIntersectVisitor<'a> {
+    doc_id_set_builder: &'a mut DocIdSetBuilder,
+    weight: &'a PointInSetWeight,
+}
+
+impl<'a> IntersectVisitor for PointInSetIntersectVisitor<'a> {
+    fn visit(&mut self, doc_id: DocId) -> Result<()> {
+        self.doc_id_set_builder.add_doc(doc_id);
+        Ok(())
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        let points = &self.weight.sorted_packed_points;
+        if points
+            .binary_search_by(|point| point.as_slice().cmp(packed_value))
+            .is_ok()
+        {
+            self.doc_id_set_builder.add_doc(doc_id);
+        }
+        Ok(())
+    }
+
+    fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        let bytes = self.weight.bytes_per_dim;
+        let points = &self.weight.sorted_packed_points;
+        let start = points.partition_point(|point| point[..bytes] < min_packed_value[..bytes]);
+        for point in &points[start..] {
+            if point[..bytes] > max_packed_value[..bytes] {
+                break;
+            }
+            let inside = (1..self.weight.num_dims).all(|dim| {
+                let offset = dim * bytes;
+                let end = offset + bytes;
+                point[offset..end] >= min_packed_value[offset..end]
+                    && point[offset..end] <= max_packed_value[offset..end]
+            });
+            if inside {
+                // the cell may hold other points as well
+                return Relation::CellCrossesQuery;
+            }
+        }
+        Relation::CellOutsideQuery
+    }
+
+    fn grow(&mut self, count: usize) {
+        self.doc_id_set_builder.grow(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::{Fieldable, NumericDocValuesField};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::{count_hits, index_fields, new_long_point};
+    use core::search::query::{IntPoint, LongPoint};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_point_in_set_query() {
+        let mut docs = vec![];
+        // enough docs to have the points spread over several leaf blocks
+        for i in 0..5000i64 {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(new_long_point("value", i % 2500)),
+                Box::new(NumericDocValuesField::new("value", i % 2500)),
+            ];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        let query = LongPoint::new_set_query("value".into(), &[2499, 7, 0, 1300, 7, 9999]).unwrap();
+        assert_eq!(count(query.as_ref()), 8);
+        let query = LongPoint::new_set_query("value".into(), &[]).unwrap();
+        assert_eq!(count(query.as_ref()), 0);
+        let query = LongPoint::new_set_query("missing".into(), &[1]).unwrap();
+        assert_eq!(count(query.as_ref()), 0);
+
+        let query = PointInSetQuery::new(
+            "xy".into(),
+            2,
+            4,
+            vec![
+                IntPoint::pack(&[3, 49]),
+                IntPoint::pack(&[3, 50]),
+                IntPoint::pack(&[99, 0]),
+                IntPoint::pack(&[0, 99]),
+            ],
+            PointValueType::Integer,
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{}", query),
+            "PointInSetQuery(field: xy, type:int, num_dims: 2, bytes_per_dim: 4, points: [[0, \
+             99], [3, 49], [3, 50], [99, 0]])"
+        );
+
+        // the dimensions of the query must agree with the indexed ones
+        let query = IntPoint::new_set_query("value".into(), &[3]).unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        assert!(searcher.search(query.as_ref(), &mut collector).is_err());
+        assert!(PointInSetQuery::new(
+            "value".into(),
+            1,
+            8,
+            vec![IntPoint::pack(&[1])],
+            PointValueType::Long
+        )
+        .is_err());
+    }
+}
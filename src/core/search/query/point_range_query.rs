@@ -13,12 +13,13 @@
 
 use error::{ErrorKind, Result};
 use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use core::codec::points::{IntersectVisitor, PointValues, Relation};
 use core::codec::Codec;
 use core::index::reader::{LeafReader, LeafReaderContext};
 use core::search::explanation::Explanation;
-use core::search::query::{AllDocsIterator, Query, TermQuery, Weight};
+use core::search::query::{AllDocsIterator, PointInSetQuery, Query, TermQuery, Weight};
 use core::search::scorer::{ConstantScoreScorer, Scorer};
 use core::search::searcher::SearchPlanBuilder;
 use core::search::{DocIdSet, DocIterator, EmptyDocIterator};
@@ -111,6 +112,18 @@ impl FloatPoint {
         )?))
     }
 
+    /// Create a query matching any of the specified 1D float values.
+    pub fn new_set_query<C: Codec>(field: String, values: &[f32]) -> Result<Box<dyn Query<C>>> {
+        let points = values.iter().map(|v| FloatPoint::pack(&[*v])).collect();
+        Ok(Box::new(PointInSetQuery::new(
+            field,
+            1,
+            4,
+            points,
+            PointValueType::Float,
+        )?))
+    }
+
     pub fn encode_dimension(value: f32, dest: &mut [u8]) {
         int2sortable_bytes(float2sortable_int(value), dest)
     }
@@ -218,6 +231,18 @@ impl DoublePoint {
             PointValueType::Double,
         )?))
     }
+
+    /// Create a query matching any of the specified 1D double values.
+    pub fn new_set_query<C: Codec>(field: String, values: &[f64]) -> Result<Box<dyn Query<C>>> {
+        let points = values.iter().map(|v| DoublePoint::pack(&[*v])).collect();
+        Ok(Box::new(PointInSetQuery::new(
+            field,
+            1,
+            8,
+            points,
+            PointValueType::Double,
+        )?))
+    }
 }
 
 pub struct IntPoint;
@@ -265,6 +290,18 @@ impl IntPoint {
             PointValueType::Integer,
         )?))
     }
+
+    /// Create a query matching any of the specified 1D int values.
+    pub fn new_set_query<C: Codec>(field: String, values: &[i32]) -> Result<Box<dyn Query<C>>> {
+        let points = values.iter().map(|v| IntPoint::pack(&[*v])).collect();
+        Ok(Box::new(PointInSetQuery::new(
+            field,
+            1,
+            4,
+            points,
+            PointValueType::Integer,
+        )?))
+    }
 }
 
 pub struct LongPoint;
@@ -312,6 +349,156 @@ impl LongPoint {
             PointValueType::Long,
         )?))
     }
+
+    /// Create a query matching any of the specified 1D long values.
+    pub fn new_set_query<C: Codec>(field: String, values: &[i64]) -> Result<Box<dyn Query<C>>> {
+        let points = values.iter().map(|v| LongPoint::pack(&[*v])).collect();
+        Ok(Box::new(PointInSetQuery::new(
+            field,
+            1,
+            8,
+            points,
+            PointValueType::Long,
+        )?))
+    }
+}
+
+/// An indexed 128-bit `IpAddr` field.
+///
+/// IPv4 addresses are indexed as IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`), so both
+/// address families can share one field and are decoded back to the family they came from.
+///
+/// This field defines static factory methods for creating common queries: exact, range,
+/// set and CIDR prefix queries.
+pub struct InetAddressPoint;
+
+impl InetAddressPoint {
+    /// The number of bytes per dimension.
+    pub const BYTES: usize = 16;
+
+    /// The minimum value, the IPv6 address `::`.
+    pub const MIN_VALUE: IpAddr = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0));
+
+    /// The maximum value, the IPv6 address `ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff`.
+    pub const MAX_VALUE: IpAddr = IpAddr::V6(Ipv6Addr::new(
+        0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff, 0xffff,
+    ));
+
+    /// The address immediately after `value`, or `None` if it is `MAX_VALUE`.
+    pub fn next_up(value: IpAddr) -> Option<IpAddr> {
+        Self::to_u128(value)
+            .checked_add(1)
+            .map(|v| Self::decode_dimension(&v.to_be_bytes()))
+    }
+
+    /// The address immediately before `value`, or `None` if it is `MIN_VALUE`.
+    pub fn next_down(value: IpAddr) -> Option<IpAddr> {
+        Self::to_u128(value)
+            .checked_sub(1)
+            .map(|v| Self::decode_dimension(&v.to_be_bytes()))
+    }
+
+    pub fn pack(point: &[IpAddr]) -> Vec<u8> {
+        assert!(!point.is_empty());
+        let mut packed = vec![0u8; point.len() * Self::BYTES];
+        for dim in 0..point.len() {
+            InetAddressPoint::encode_dimension(point[dim], &mut packed[dim * Self::BYTES..]);
+        }
+        packed
+    }
+
+    pub fn encode_dimension(value: IpAddr, dest: &mut [u8]) {
+        let octets = match value {
+            IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+            IpAddr::V6(v6) => v6.octets(),
+        };
+        dest[..Self::BYTES].copy_from_slice(&octets);
+    }
+
+    pub fn decode_dimension(value: &[u8]) -> IpAddr {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&value[..Self::BYTES]);
+        // only the mapped addresses are IPv4, `Ipv6Addr::to_ipv4` also accepts `::a.b.c.d`
+        if octets[..10].iter().all(|b| *b == 0) && octets[10] == 0xff && octets[11] == 0xff {
+            IpAddr::V4(Ipv4Addr::new(
+                octets[12], octets[13], octets[14], octets[15],
+            ))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+    }
+
+    pub fn new_exact_query<C: Codec>(field: String, value: IpAddr) -> Result<Box<dyn Query<C>>> {
+        InetAddressPoint::new_range_query(field, value, value)
+    }
+
+    /// Create a range query for addresses between `lower` and `upper`, both inclusive.
+    ///
+    /// Use `next_up` and `next_down` for exclusive bounds.
+    pub fn new_range_query<C: Codec>(
+        field: String,
+        lower: IpAddr,
+        upper: IpAddr,
+    ) -> Result<Box<dyn Query<C>>> {
+        Ok(Box::new(PointRangeQuery::new(
+            field,
+            InetAddressPoint::pack(&[lower]),
+            InetAddressPoint::pack(&[upper]),
+            1,
+            PointValueType::InetAddress,
+        )?))
+    }
+
+    /// Create a query for the CIDR block of `value` with the `prefix_length` leading
+    /// bits, e.g. `192.168.0.0/16`.
+    pub fn new_prefix_query<C: Codec>(
+        field: String,
+        value: IpAddr,
+        prefix_length: u32,
+    ) -> Result<Box<dyn Query<C>>> {
+        // IPv4 addresses are preceded by the 96 bits of the mapped prefix
+        let (max_length, offset) = match value {
+            IpAddr::V4(_) => (32, 96),
+            IpAddr::V6(_) => (128, 0),
+        };
+        if prefix_length > max_length {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "illegal prefix_length '{}' for {}, must be 0-{}",
+                prefix_length, value, max_length
+            )));
+        }
+        let bits = prefix_length + offset;
+        let mask = if bits == 0 { 0 } else { !0u128 << (128 - bits) };
+        let value = Self::to_u128(value);
+        Ok(Box::new(PointRangeQuery::new(
+            field,
+            (value & mask).to_be_bytes().to_vec(),
+            (value | !mask).to_be_bytes().to_vec(),
+            1,
+            PointValueType::InetAddress,
+        )?))
+    }
+
+    /// Create a query matching any of the specified addresses.
+    pub fn new_set_query<C: Codec>(field: String, values: &[IpAddr]) -> Result<Box<dyn Query<C>>> {
+        let points = values
+            .iter()
+            .map(|v| InetAddressPoint::pack(&[*v]))
+            .collect();
+        Ok(Box::new(PointInSetQuery::new(
+            field,
+            1,
+            Self::BYTES,
+            points,
+            PointValueType::InetAddress,
+        )?))
+    }
+
+    fn to_u128(value: IpAddr) -> u128 {
+        let mut octets = [0u8; 16];
+        InetAddressPoint::encode_dimension(value, &mut octets);
+        u128::from_be_bytes(octets)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -320,6 +507,7 @@ pub enum PointValueType {
     Float,
     Double,
     Long,
+    InetAddress,
     /* Byte,
      * SmallFloat,
      * Short */
@@ -332,6 +520,7 @@ impl PointValueType {
             PointValueType::Double => DoublePoint::decode_dimension(bytes).to_string(),
             PointValueType::Integer => IntPoint::decode_dimension(bytes).to_string(),
             PointValueType::Long => LongPoint::decode_dimension(bytes).to_string(),
+            PointValueType::InetAddress => InetAddressPoint::decode_dimension(bytes).to_string(),
         }
     }
 
@@ -354,6 +543,7 @@ impl fmt::Display for PointValueType {
             PointValueType::Float => "float",
             PointValueType::Double => "double",
             PointValueType::Long => "long",
+            PointValueType::InetAddress => "inet",
         };
         write!(f, "{}", value)
     }
@@ -405,6 +595,26 @@ impl PointRangeQuery {
             value_type,
         })
     }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn num_dims(&self) -> usize {
+        self.num_dims
+    }
+
+    pub fn lower_point(&self) -> &[u8] {
+        &self.lower_point
+    }
+
+    pub fn upper_point(&self) -> &[u8] {
+        &self.upper_point
+    }
+
+    pub fn value_type(&self) -> PointValueType {
+        self.value_type
+    }
 }
 
 pub const POINT_RANGE: &str = "point_range";
@@ -560,6 +770,28 @@ impl<C: Codec> Weight<C> for PointRangeWeight {
         Ok(None)
     }
 
+    fn estimate_cost(&self, leaf_reader_ctx: &LeafReaderContext<'_, C>) -> Result<Option<usize>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        if let Some(ref values) = leaf_reader.point_values() {
+            if leaf_reader.field_info(&self.field).is_some() {
+                let mut result =
+                    DocIdSetBuilder::from_values(leaf_reader.max_doc(), values, &self.field)?;
+                let visitor = PointRangeIntersectVisitor::new(&mut result, self);
+                let point_count = values.estimate_point_count(&self.field, &visitor)?;
+                // turn the number of points into a number of docs for multi-valued fields
+                let size = values.size(&self.field)?;
+                let doc_count = i64::from(values.doc_count(&self.field)?);
+                let cost = if size > doc_count && size > 0 {
+                    (point_count * doc_count + size - 1) / size
+                } else {
+                    point_count
+                };
+                return Ok(Some(cost as usize));
+            }
+        }
+        Ok(Some(0))
+    }
+
     fn query_type(&self) -> &'static str {
         POINT_RANGE
     }
@@ -747,3 +979,157 @@ impl DocIterator for PointDocIterEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::tests::TestCodec;
+    use core::doc::{Field, FieldType, Fieldable, NumericDocValuesField};
+    use core::search::query::tests::{count_hits, index_fields};
+    use core::search::DefaultIndexSearcher;
+    use core::util::VariantValue;
+
+    use std::sync::Arc;
+
+    fn new_inet_address_point(field_name: &str, value: IpAddr) -> Field {
+        let mut field_type = FieldType::default();
+        field_type.dimension_count = 1;
+        field_type.dimension_num_bytes = InetAddressPoint::BYTES as u32;
+        Field::new(
+            field_name.into(),
+            field_type,
+            Some(VariantValue::Binary(InetAddressPoint::pack(&[value]))),
+            None,
+        )
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_inet_address_encoding() {
+        for s in &[
+            "0.0.0.0",
+            "192.168.1.7",
+            "::",
+            "::1",
+            "2001:db8::ff00:42:8329",
+        ] {
+            let packed = InetAddressPoint::pack(&[addr(s)]);
+            assert_eq!(packed.len(), 16);
+            assert_eq!(InetAddressPoint::decode_dimension(&packed), addr(s));
+        }
+        // IPv4 addresses sort as their mapped IPv6 addresses
+        assert!(
+            InetAddressPoint::pack(&[addr("::1")]) < InetAddressPoint::pack(&[addr("0.0.0.1")])
+        );
+        assert!(
+            InetAddressPoint::pack(&[addr("255.255.255.255")])
+                < InetAddressPoint::pack(&[addr("2001:db8::")])
+        );
+
+        assert_eq!(
+            InetAddressPoint::next_up(addr("10.0.0.255")),
+            Some(addr("10.0.1.0"))
+        );
+        assert_eq!(
+            InetAddressPoint::next_down(addr("::ffff:0:0")),
+            Some(addr("::fffe:ffff:ffff"))
+        );
+        assert_eq!(InetAddressPoint::next_up(InetAddressPoint::MAX_VALUE), None);
+        assert_eq!(
+            InetAddressPoint::next_down(InetAddressPoint::MIN_VALUE),
+            None
+        );
+    }
+
+    #[test]
+    fn test_inet_address_queries() {
+        let addresses = [
+            "10.0.0.1",
+            "10.0.0.2",
+            "10.0.1.1",
+            "10.1.0.1",
+            "192.168.1.1",
+            "2001:db8::1",
+            "2001:db8::2",
+            "2001:db8:1::1",
+            "fe80::1",
+        ];
+        let mut docs = vec![];
+        for (i, s) in addresses.iter().enumerate() {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(new_inet_address_point("ip", addr(s))),
+                Box::new(NumericDocValuesField::new("id", i as i64)),
+            ];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        let field = || "ip".to_string();
+        let query = InetAddressPoint::new_exact_query(field(), addr("10.0.0.2")).unwrap();
+        assert_eq!(count(query.as_ref()), 1);
+        let query = InetAddressPoint::new_exact_query(field(), addr("::ffff:10.0.0.2")).unwrap();
+        assert_eq!(count(query.as_ref()), 1);
+        let query = InetAddressPoint::new_exact_query(field(), addr("10.0.0.3")).unwrap();
+        assert_eq!(count(query.as_ref()), 0);
+
+        let query =
+            InetAddressPoint::new_range_query(field(), addr("10.0.0.2"), addr("192.168.1.1"))
+                .unwrap();
+        assert_eq!(count(query.as_ref()), 4);
+        let query = InetAddressPoint::new_range_query(
+            field(),
+            InetAddressPoint::MIN_VALUE,
+            InetAddressPoint::MAX_VALUE,
+        )
+        .unwrap();
+        assert_eq!(count(query.as_ref()), addresses.len());
+
+        let query = InetAddressPoint::new_set_query(
+            field(),
+            &[addr("10.0.1.1"), addr("fe80::1"), addr("127.0.0.1")],
+        )
+        .unwrap();
+        assert_eq!(count(query.as_ref()), 2);
+
+        let prefix_count = |value: &str, prefix_length: u32| -> usize {
+            let query =
+                InetAddressPoint::new_prefix_query(field(), addr(value), prefix_length).unwrap();
+            count(query.as_ref())
+        };
+        assert_eq!(prefix_count("10.0.0.0", 24), 2);
+        assert_eq!(prefix_count("10.0.0.0", 16), 3);
+        assert_eq!(prefix_count("10.0.0.0", 8), 4);
+        // the host bits of the address are ignored
+        assert_eq!(prefix_count("10.0.255.255", 16), 3);
+        assert_eq!(prefix_count("10.0.0.1", 32), 1);
+        // a zero length IPv4 prefix only matches IPv4 addresses
+        assert_eq!(prefix_count("0.0.0.0", 0), 5);
+        assert_eq!(prefix_count("2001:db8::", 32), 3);
+        assert_eq!(prefix_count("2001:db8::", 48), 2);
+        assert_eq!(prefix_count("::", 0), addresses.len());
+        assert!(
+            InetAddressPoint::new_prefix_query::<TestCodec>(field(), addr("10.0.0.0"), 33).is_err()
+        );
+        assert!(InetAddressPoint::new_prefix_query::<TestCodec>(field(), addr("::"), 129).is_err());
+
+        let query = PointRangeQuery::new(
+            field(),
+            InetAddressPoint::pack(&[addr("10.0.0.0")]),
+            InetAddressPoint::pack(&[addr("2001:db8::ffff")]),
+            1,
+            PointValueType::InetAddress,
+        )
+        .unwrap();
+        assert_eq!(
+            query.to_string(),
+            "PointRangeQuery(field: ip, type:inet, num_dims: 1, bytes_per_dim: 16, lower: \
+             [10.0.0.0], upper: [2001:db8::ffff])"
+        );
+    }
+}
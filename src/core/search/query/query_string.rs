@@ -11,27 +11,61 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use error::{ErrorKind::IllegalArgument, Result};
-use std::option::Option::{None, Some};
-use std::result::Result::Ok;
-use std::str::Chars;
-use std::vec::Vec;
+use error::{Error, ErrorKind, ErrorKind::IllegalArgument, Result};
 
 use core::codec::Codec;
 use core::doc::Term;
-use core::search::query::{BooleanQuery, BoostQuery, PhraseQuery, Query, TermQuery};
+use core::search::query::spans::MultiTermMatcher;
+use core::search::query::{
+    BooleanQuery, BoostQuery, DoublePoint, ExistsQuery, FloatPoint, InetAddressPoint, IntPoint,
+    LongPoint, MatchAllDocsQuery, MultiTermQuery, PhraseQuery, PointValueType, Query, TermQuery,
+    TermRangeQuery,
+};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The maximum nesting depth of the groups of a query string.
+pub const MAX_GROUP_DEPTH: usize = 64;
+
+/// The operator between the clauses of a query string which are not joined by
+/// an explicit `AND` or `OR`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operator {
+    Or,
+    And,
+}
 
 /// A query that parses a query string and runs it.
 ///
+/// The syntax is the one of the Lucene classic query parser:
+/// * `term` searched in the default fields, `field:term` and `field:(a b)` to search a group of
+///   clauses in a field
+/// * `"a phrase"`, with an optional slop like `"a phrase"~2`
+/// * `+term` and `-term` (or `!term`, `NOT term`) for required and prohibited clauses, the `AND`
+///   (`&&`) and `OR` (`||`, `|`) operators and the default `Operator` between the other clauses
+/// * `(...)` groups and boosts like `term^2` or `(a b)^0.5`
+/// * `te?m*` wildcards, `/reg(ular)?exp/` and fuzzy terms like `term~1`
+/// * `[a TO b]`, `{a TO b}` or mixed ranges with `*` for an open bound, searched with a
+///   `PointRangeQuery` on the point fields registered with `with_point_field` and with a
+///   `TermRangeQuery` on the other fields
+/// * `field:*` for the documents with a value in the field, `*:*` for all the documents
+/// * `\` escaping the next character
+///
+/// Syntax errors are reported with their position in the query string, as are the groups
+/// nested deeper than `MAX_GROUP_DEPTH`.
+///
 /// See: [`Query String Syntax`](https://www.elastic.co/guide/en/elasticsearch/reference/6.3/query-dsl-query-string-query.html#query-string-syntax) for more detail.
-/// NOTE: currently this builder only support some of the syntax than elastic search do.
 pub struct QueryStringQueryBuilder {
     query_string: String,
     fields: Vec<(String, f32)>,
-    #[allow(dead_code)]
     min_should_match: i32,
     #[allow(dead_code)]
     boost: f32,
+    default_operator: Operator,
+    point_fields: HashMap<String, PointValueType>,
+    phrase_slop: i32,
+    fuzzy_prefix_length: usize,
 }
 
 impl QueryStringQueryBuilder {
@@ -46,205 +80,905 @@ impl QueryStringQueryBuilder {
             fields,
             min_should_match,
             boost,
+            default_operator: Operator::Or,
+            point_fields: HashMap::new(),
+            phrase_slop: 0,
+            fuzzy_prefix_length: 0,
         }
     }
 
+    pub fn with_default_operator(mut self, operator: Operator) -> Self {
+        self.default_operator = operator;
+        self
+    }
+
+    /// Search `field` as a point field, with `PointRangeQuery`s for its terms and ranges.
+    pub fn with_point_field(mut self, field: &str, value_type: PointValueType) -> Self {
+        self.point_fields.insert(field.to_string(), value_type);
+        self
+    }
+
+    /// The slop of the phrases without explicit slop.
+    pub fn with_phrase_slop(mut self, slop: i32) -> Self {
+        self.phrase_slop = slop;
+        self
+    }
+
+    /// The number of leading characters of fuzzy terms which must match exactly.
+    pub fn with_fuzzy_prefix_length(mut self, prefix_length: usize) -> Self {
+        self.fuzzy_prefix_length = prefix_length;
+        self
+    }
+
     pub fn build<C: Codec>(&self) -> Result<Box<dyn Query<C>>> {
-        match self.parse_query(&mut self.query_string.chars(), None) {
-            Ok(Some(q)) => Ok(q),
-            Ok(None) => bail!(IllegalArgument("empty query string!".into())),
-            Err(e) => Err(e),
+        if self.query_string.trim().is_empty() {
+            bail!(IllegalArgument("empty query string!".into()));
         }
+        Parser {
+            builder: self,
+            chars: self.query_string.chars().collect(),
+            pos: 0,
+        }
+        .parse_query(None, 0)
+    }
+}
+
+/// The default maximum edit distance of fuzzy terms without explicit distance.
+const DEFAULT_FUZZY_EDITS: u32 = 2;
+
+/// The characters which can't appear unescaped in a term.
+const SPECIAL_CHARS: &str = "()[]{}:^~\"/|";
+
+/// The position of a `~` suffix and its optional number.
+type Tilde = (usize, Option<f32>);
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Conjunction {
+    None,
+    And,
+    Or,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Modifier {
+    None,
+    Required,
+    Prohibited,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Occur {
+    Must,
+    Should,
+    MustNot,
+}
+
+/// A term of the query string, `raw` keeping the escapes for wildcard patterns.
+struct TermToken {
+    text: String,
+    raw: String,
+    wildcard: bool,
+    position: usize,
+}
+
+/// A recursive descent parser of the query string, the positions being char offsets.
+struct Parser<'a> {
+    builder: &'a QueryStringQueryBuilder,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: String, position: usize) -> Result<T> {
+        bail!(IllegalArgument(format!(
+            "failed to parse query string '{}': {} at position {}",
+            &self.builder.query_string, message, position
+        )))
+    }
+
+    /// Report the errors of the query constructors at `position`.
+    fn at<T>(&self, result: Result<T>, position: usize) -> Result<T> {
+        result.or_else(|e: Error| {
+            let message = match e.kind() {
+                ErrorKind::IllegalArgument(desc) => desc.clone(),
+                _ => e.to_string(),
+            };
+            self.error(message, position)
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
     }
 
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    /// Whether the keyword `word` is at the current position, as a separate token.
+    fn at_keyword(&self, word: &str) -> bool {
+        self.starts_with(word)
+            && self
+                .peek_at(word.len())
+                .map_or(true, |c| c.is_whitespace() || c == '(' || c == '"')
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn unexpected<T>(&self) -> Result<T> {
+        match self.peek() {
+            Some(c) => self.error(format!("unexpected '{}'", c), self.pos),
+            None => self.error("unexpected end of query string".into(), self.pos),
+        }
+    }
+
+    /// Query ::= ( [Conjunction] [Modifier] Clause )*
     fn parse_query<C: Codec>(
+        &mut self,
+        field: Option<&str>,
+        depth: usize,
+    ) -> Result<Box<dyn Query<C>>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query<C>>)> = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(')') if depth > 0 => break,
+                _ => {}
+            }
+
+            let position = self.pos;
+            let conjunction = self.parse_conjunction();
+            if conjunction != Conjunction::None {
+                if clauses.is_empty() {
+                    return self.error("a query can't start with an operator".into(), position);
+                }
+                self.skip_whitespace();
+            }
+            let modifier = self.parse_modifier();
+            self.skip_whitespace();
+            let query = self.parse_clause(field, depth)?;
+            self.add_clause(&mut clauses, conjunction, modifier, query);
+        }
+
+        if clauses.is_empty() {
+            return self.unexpected();
+        }
+        if clauses.len() == 1 && clauses[0].0 != Occur::MustNot {
+            return Ok(clauses.remove(0).1);
+        }
+        let mut musts = vec![];
+        let mut shoulds = vec![];
+        let mut must_nots = vec![];
+        for (occur, query) in clauses {
+            match occur {
+                Occur::Must => musts.push(query),
+                Occur::Should => shoulds.push(query),
+                Occur::MustNot => must_nots.push(query),
+            }
+        }
+        BooleanQuery::build(
+            musts,
+            shoulds,
+            vec![],
+            must_nots,
+            self.builder.min_should_match,
+        )
+    }
+
+    fn parse_conjunction(&mut self) -> Conjunction {
+        if self.at_keyword("AND") {
+            self.pos += 3;
+            Conjunction::And
+        } else if self.starts_with("&&") || self.starts_with("||") {
+            let conjunction = if self.starts_with("&&") {
+                Conjunction::And
+            } else {
+                Conjunction::Or
+            };
+            self.pos += 2;
+            conjunction
+        } else if self.at_keyword("OR") {
+            self.pos += 2;
+            Conjunction::Or
+        } else if self.peek() == Some('|') {
+            self.pos += 1;
+            Conjunction::Or
+        } else {
+            Conjunction::None
+        }
+    }
+
+    fn parse_modifier(&mut self) -> Modifier {
+        match self.peek() {
+            Some('+') => {
+                self.pos += 1;
+                Modifier::Required
+            }
+            Some('-') | Some('!') => {
+                self.pos += 1;
+                Modifier::Prohibited
+            }
+            _ if self.at_keyword("NOT") => {
+                self.pos += 3;
+                Modifier::Prohibited
+            }
+            _ => Modifier::None,
+        }
+    }
+
+    /// Add a clause with the same semantics as the Lucene classic query parser: an
+    /// explicit `AND` also makes the previous clause required, and an explicit `OR`
+    /// makes it optional if the default operator is `AND`.
+    fn add_clause<C: Codec>(
         &self,
-        chars: &mut Chars,
-        end_char: Option<char>,
-    ) -> Result<Option<Box<dyn Query<C>>>> {
-        let mut musts = Vec::new();
-        let mut shoulds = Vec::new();
-        let mut is_option = true;
-        while let Some(ch) = chars.next() {
-            match ch {
-                '+' => is_option = false,
-                '|' => is_option = true,
-                '(' => {
-                    if let Ok(Some(query)) = self.parse_query(chars, Some(')')) {
-                        if is_option {
-                            shoulds.push(query);
-                        } else {
-                            musts.push(query);
-                        }
-                    }
+        clauses: &mut Vec<(Occur, Box<dyn Query<C>>)>,
+        conjunction: Conjunction,
+        modifier: Modifier,
+        query: Box<dyn Query<C>>,
+    ) {
+        let default_operator = self.builder.default_operator;
+        if let Some(last) = clauses.last_mut() {
+            if last.0 != Occur::MustNot {
+                if conjunction == Conjunction::And {
+                    last.0 = Occur::Must;
+                } else if conjunction == Conjunction::Or && default_operator == Operator::And {
+                    last.0 = Occur::Should;
                 }
-                '"' => {
-                    let mut term_chars = Vec::new();
-                    while let Some(ch) = chars.next() {
-                        if ch == '"' {
-                            break;
-                        }
-                        term_chars.push(ch);
-                    }
+            }
+        }
 
-                    if let Some(ch) = chars.next() {
-                        if ch == '^' || ch == '~' {
-                            term_chars.push(ch);
-                            while let Some(ch) = chars.next() {
-                                if ch == ' ' {
-                                    break;
-                                }
-                                term_chars.push(ch);
-                            }
-                        }
-                    }
+        let occur = match modifier {
+            Modifier::Prohibited => Occur::MustNot,
+            Modifier::Required => Occur::Must,
+            Modifier::None => {
+                if conjunction == Conjunction::And
+                    || (conjunction == Conjunction::None && default_operator == Operator::And)
+                {
+                    Occur::Must
+                } else {
+                    Occur::Should
+                }
+            }
+        };
+        clauses.push((occur, query));
+    }
 
-                    if !term_chars.is_empty() {
-                        let term: String = term_chars.iter().cloned().collect();
-                        let query = self.build_field_query(term);
-                        match query {
-                            Ok(q) => {
-                                if is_option {
-                                    shoulds.push(q);
-                                } else {
-                                    musts.push(q);
-                                }
-                            }
-                            Err(e) => {
-                                return Err(e);
-                            }
-                        }
-                    }
-                    is_option = true;
+    /// Clause ::= [ Field ':' ] ( Term | Phrase | Range | Regexp | '(' Query ')' ) [ '^' Boost ]
+    fn parse_clause<C: Codec>(
+        &mut self,
+        field: Option<&str>,
+        depth: usize,
+    ) -> Result<Box<dyn Query<C>>> {
+        let field_prefix = self.parse_field_prefix()?;
+        let has_prefix = field_prefix.is_some();
+        let field = match field_prefix {
+            Some(ref name) => Some(name.as_str()),
+            None => field,
+        };
+
+        match self.peek() {
+            Some('(') => {
+                let open = self.pos;
+                if depth == MAX_GROUP_DEPTH {
+                    return self.error("groups nested too deeply".into(), open);
                 }
-                ' ' => is_option = true,
-                ')' => {
-                    if end_char.is_none() || end_char.unwrap() != ')' {
-                        bail!(IllegalArgument("parenthesis not match!".into()));
-                    }
-                    break;
+                self.pos += 1;
+                let query = self.parse_query(field, depth + 1)?;
+                if self.peek() != Some(')') {
+                    return self.error(
+                        format!("missing ')' to close the '(' at position {}", open),
+                        self.pos,
+                    );
                 }
-                _ => {
-                    let mut term_chars = Vec::new();
-                    term_chars.push(ch);
-                    let mut should_return = false;
-                    while let Some(c) = chars.next() {
-                        if c == ' ' {
-                            break;
-                        }
-                        if c == ')' {
-                            if end_char.is_none() || end_char.unwrap() != ')' {
-                                bail!(IllegalArgument("parenthesis not match!".into()));
-                            }
-                            should_return = true;
-                            break;
-                        }
-                        term_chars.push(c);
-                    }
-                    if !term_chars.is_empty() {
-                        let term: String = term_chars.iter().cloned().collect();
-                        let query_res = self.build_field_query(term);
-                        match query_res {
-                            Ok(q) => {
-                                if is_option {
-                                    shoulds.push(q);
-                                } else {
-                                    musts.push(q);
-                                }
-                            }
-                            Err(e) => {
-                                return Err(e);
-                            }
-                        }
+                self.pos += 1;
+                let boost = self.parse_boost()?;
+                Ok(BoostQuery::build(query, boost))
+            }
+            Some('"') => self.parse_phrase(field),
+            Some('[') | Some('{') => self.parse_range(field),
+            Some('/') => self.parse_regexp(field),
+            _ => self.parse_term(field, has_prefix),
+        }
+    }
+
+    fn parse_field_prefix(&mut self) -> Result<Option<String>> {
+        let start = self.pos;
+        if let Ok(token) = self.read_term(false) {
+            if self.peek() == Some(':') && (token.raw == "*" || !token.text.is_empty()) {
+                if token.wildcard && token.raw != "*" {
+                    return self.error(format!("invalid field name '{}'", token.raw), start);
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+                return Ok(Some(token.text));
+            }
+        }
+        self.pos = start;
+        Ok(None)
+    }
+
+    /// Read a term up to the next whitespace or special char, the first char of the term
+    /// being allowed to be a sign only if `allow_sign`, for the negative numbers.
+    fn read_term(&mut self, allow_sign: bool) -> Result<TermToken> {
+        let mut token = TermToken {
+            text: String::new(),
+            raw: String::new(),
+            wildcard: false,
+            position: self.pos,
+        };
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => match self.peek_at(1) {
+                    Some(escaped) => {
+                        token.text.push(escaped);
+                        token.raw.push('\\');
+                        token.raw.push(escaped);
+                        self.pos += 2;
+                        continue;
                     }
-                    is_option = true;
-                    if should_return {
-                        break;
+                    None => {
+                        return self.error("trailing escape character".into(), self.pos);
                     }
+                },
+                '*' | '?' => token.wildcard = true,
+                '+' | '-' if token.raw.is_empty() && !allow_sign => break,
+                '!' if token.raw.is_empty() => break,
+                c if c.is_whitespace() || SPECIAL_CHARS.contains(c) => break,
+                _ => {}
+            }
+            token.text.push(c);
+            token.raw.push(c);
+            self.pos += 1;
+        }
+        Ok(token)
+    }
+
+    /// Parse the optional number following a `~` or `^`.
+    fn parse_number(&mut self) -> Result<Option<f32>> {
+        let start = self.pos;
+        while self
+            .peek()
+            .map_or(false, |c| c.is_ascii_digit() || c == '.')
+        {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        let number: String = self.chars[start..self.pos].iter().collect();
+        match number.parse::<f32>() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => self.error(format!("invalid number '{}'", number), start),
+        }
+    }
+
+    fn parse_boost(&mut self) -> Result<f32> {
+        if self.peek() != Some('^') {
+            return Ok(1.0);
+        }
+        self.pos += 1;
+        match self.parse_number()? {
+            Some(boost) => Ok(boost),
+            None => self.error("expected a boost after '^'".into(), self.pos),
+        }
+    }
+
+    /// Parse the `~` and `^` suffixes of a term or phrase, in any order, returning
+    /// the position and optional number of the `~` suffix and the boost.
+    fn parse_suffixes(&mut self) -> Result<(Option<Tilde>, f32)> {
+        let mut tilde = None;
+        let mut boost = None;
+        loop {
+            match self.peek() {
+                Some('~') if tilde.is_none() => {
+                    let position = self.pos;
+                    self.pos += 1;
+                    tilde = Some((position, self.parse_number()?));
+                }
+                Some('^') if boost.is_none() => {
+                    boost = Some(self.parse_boost()?);
+                }
+                _ => {
+                    return Ok((tilde, boost.unwrap_or(1.0)));
                 }
             }
         }
-        let query: Box<dyn Query<C>> = if musts.len() + shoulds.len() == 1 {
-            if !musts.is_empty() {
-                musts.remove(0)
-            } else {
-                shoulds.remove(0)
+    }
+
+    fn parse_term<C: Codec>(
+        &mut self,
+        field: Option<&str>,
+        has_prefix: bool,
+    ) -> Result<Box<dyn Query<C>>> {
+        let token = self.read_term(has_prefix)?;
+        if token.raw.is_empty() {
+            return self.unexpected();
+        }
+        let (tilde, boost) = self.parse_suffixes()?;
+        let fuzzy = match tilde {
+            Some((position, _)) if token.wildcard => {
+                return self.error("a wildcard term can't be fuzzy".into(), position);
             }
-        } else {
-            BooleanQuery::build(musts, shoulds, vec![], vec![], self.min_should_match)?
+            Some((position, similarity)) => {
+                Some(self.fuzzy_edits(similarity, token.text.chars().count(), position)?)
+            }
+            None => None,
         };
-        Ok(Some(query))
+
+        if field == Some("*") {
+            if token.raw != "*" {
+                return self.error(
+                    "only '*' can be searched in all the fields".into(),
+                    token.position,
+                );
+            }
+            return Ok(BoostQuery::build(Box::new(MatchAllDocsQuery), boost));
+        }
+        self.field_queries(field, boost, token.position, |field, boost| {
+            self.term_query(field, &token, fuzzy, boost)
+        })
     }
 
-    fn term_query<C: Codec>(&self, term: String, field: String, boost: f32) -> Box<dyn Query<C>> {
-        Box::new(TermQuery::new(Term::new(field, term.into()), boost, None))
+    /// The edit distance of a fuzzy term, `similarity` being either the distance or
+    /// the legacy minimum similarity between 0 and 1.
+    fn fuzzy_edits(
+        &self,
+        similarity: Option<f32>,
+        term_length: usize,
+        position: usize,
+    ) -> Result<u32> {
+        match similarity {
+            None => Ok(DEFAULT_FUZZY_EDITS),
+            Some(s) if s >= 1.0 => {
+                if s.fract() != 0.0 || s > DEFAULT_FUZZY_EDITS as f32 {
+                    self.error(
+                        format!(
+                            "fuzzy edit distance must be between 0 and {}",
+                            DEFAULT_FUZZY_EDITS
+                        ),
+                        position,
+                    )
+                } else {
+                    Ok(s as u32)
+                }
+            }
+            Some(s) => Ok((((1.0 - s) * term_length as f32) as u32).min(DEFAULT_FUZZY_EDITS)),
+        }
     }
 
-    fn build_field_query<C: Codec>(&self, term_boost: String) -> Result<Box<dyn Query<C>>> {
-        let mut queries = if term_boost.find('~').is_some() {
-            self.field_phrase_query(&term_boost)?
+    fn term_query<C: Codec>(
+        &self,
+        field: &str,
+        token: &TermToken,
+        fuzzy: Option<u32>,
+        boost: f32,
+    ) -> Result<Box<dyn Query<C>>> {
+        if token.raw == "*" {
+            return Ok(BoostQuery::build(
+                Box::new(ExistsQuery::build(field.to_string())),
+                boost,
+            ));
+        }
+        if let Some(value_type) = self.builder.point_fields.get(field) {
+            if token.wildcard || fuzzy.is_some() {
+                return self.error(
+                    format!(
+                        "wildcard and fuzzy terms are not supported on point field '{}'",
+                        field
+                    ),
+                    token.position,
+                );
+            }
+            let query = self.point_exact_query(field, *value_type, &token.text, token.position)?;
+            return Ok(BoostQuery::build(query, boost));
+        }
+
+        let matcher = if token.wildcard {
+            match token.raw.strip_suffix('*') {
+                Some(prefix) if !prefix.contains(|c| c == '*' || c == '?' || c == '\\') => {
+                    MultiTermMatcher::prefix(prefix)
+                }
+                _ => self.at(MultiTermMatcher::wildcard(&token.raw), token.position)?,
+            }
+        } else if let Some(max_edits) = fuzzy {
+            self.at(
+                MultiTermMatcher::fuzzy(&token.text, max_edits, self.builder.fuzzy_prefix_length),
+                token.position,
+            )?
         } else {
-            self.field_term_query(term_boost)?
+            return Ok(Box::new(TermQuery::new(
+                Term::new(field.to_string(), token.text.as_bytes().to_vec()),
+                boost,
+                None,
+            )));
         };
+        Ok(BoostQuery::build(
+            Box::new(MultiTermQuery::new(field.to_string(), matcher)),
+            boost,
+        ))
+    }
 
-        let res = if queries.len() == 1 {
-            queries.remove(0)
-        } else {
-            BooleanQuery::build(Vec::new(), queries, vec![], vec![], self.min_should_match)?
+    fn parse_phrase<C: Codec>(&mut self, field: Option<&str>) -> Result<Box<dyn Query<C>>> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return self.error(
+                        format!("missing '\"' to close the phrase at position {}", open),
+                        self.pos,
+                    );
+                }
+                Some('\\') if self.peek_at(1).is_some() => {
+                    text.push(self.peek_at(1).unwrap());
+                    self.pos += 2;
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        let (tilde, boost) = self.parse_suffixes()?;
+        let slop = match tilde {
+            Some((_, None)) | None => self.builder.phrase_slop,
+            Some((position, Some(slop))) => {
+                if slop.fract() != 0.0 {
+                    return self.error(format!("invalid phrase slop '{}'", slop), position);
+                }
+                slop as i32
+            }
         };
-        Ok(res)
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            return self.error("empty phrase".into(), open);
+        }
+        self.field_queries(field, boost, open, |field, boost| {
+            if let Some(value_type) = self.builder.point_fields.get(field) {
+                if words.len() > 1 {
+                    return self.error(
+                        format!("phrases are not supported on point field '{}'", field),
+                        open,
+                    );
+                }
+                let query = self.point_exact_query(field, *value_type, words[0], open)?;
+                return Ok(BoostQuery::build(query, boost));
+            }
+            if words.len() == 1 {
+                return Ok(Box::new(TermQuery::new(
+                    Term::new(field.to_string(), words[0].as_bytes().to_vec()),
+                    boost,
+                    None,
+                )));
+            }
+            let terms = words
+                .iter()
+                .map(|word| Term::new(field.to_string(), word.as_bytes().to_vec()))
+                .collect();
+            let query = self.at(PhraseQuery::build(terms, slop, None, None), open)?;
+            Ok(BoostQuery::build(Box::new(query), boost))
+        })
     }
 
-    fn field_term_query<C: Codec>(&self, query: String) -> Result<Vec<Box<dyn Query<C>>>> {
-        let (term, boost) = if let Some(i) = query.find('^') {
-            let (t, b) = query.split_at(i as usize);
-            let boost_str: String = b.chars().skip(1).collect();
-            let boost = boost_str.parse::<f32>()?;
-            (t.to_string(), boost)
-        } else {
-            (query, 1f32)
+    /// Range ::= ( '[' | '{' ) Bound 'TO' Bound ( ']' | '}' ) [ '^' Boost ]
+    fn parse_range<C: Codec>(&mut self, field: Option<&str>) -> Result<Box<dyn Query<C>>> {
+        let open = self.pos;
+        let include_lower = self.peek() == Some('[');
+        self.pos += 1;
+        self.skip_whitespace();
+        let lower = self.parse_range_bound()?;
+        self.skip_whitespace();
+        if !self.at_keyword("TO") {
+            return self.error("expected 'TO' in range".into(), self.pos);
+        }
+        self.pos += 2;
+        self.skip_whitespace();
+        let upper = self.parse_range_bound()?;
+        self.skip_whitespace();
+        let include_upper = match self.peek() {
+            Some(']') => true,
+            Some('}') => false,
+            _ => {
+                return self.error(
+                    format!(
+                        "missing ']' or '}}' to close the range at position {}",
+                        open
+                    ),
+                    self.pos,
+                );
+            }
         };
-        let term = if term.starts_with('"') {
-            term.chars().skip(1).take(term.len() - 2).collect()
+        self.pos += 1;
+        let boost = self.parse_boost()?;
+
+        self.field_queries(field, boost, open, |field, boost| {
+            match self.builder.point_fields.get(field) {
+                Some(value_type) => {
+                    let query = self.point_range_query(
+                        field,
+                        *value_type,
+                        (&lower, include_lower),
+                        (&upper, include_upper),
+                    )?;
+                    Ok(BoostQuery::build(query, boost))
+                }
+                None => {
+                    let query = TermRangeQuery::new_string_range(
+                        field.to_string(),
+                        lower.as_ref().map(|(bound, _)| bound.as_str()),
+                        upper.as_ref().map(|(bound, _)| bound.as_str()),
+                        include_lower,
+                        include_upper,
+                    );
+                    Ok(BoostQuery::build(Box::new(query), boost))
+                }
+            }
+        })
+    }
+
+    /// Parse a quoted or plain range bound, `None` being the `*` open bound.
+    fn parse_range_bound(&mut self) -> Result<Option<(String, usize)>> {
+        let position = self.pos;
+        let quoted = self.peek() == Some('"');
+        if quoted {
+            self.pos += 1;
+        }
+        let mut bound = String::new();
+        loop {
+            match self.peek() {
+                Some('\\') if self.peek_at(1).is_some() => {
+                    bound.push(self.peek_at(1).unwrap());
+                    self.pos += 2;
+                    continue;
+                }
+                Some('"') if quoted => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(c) if quoted || !(c.is_whitespace() || c == ']' || c == '}') => {
+                    bound.push(c);
+                }
+                None if quoted => {
+                    return self.error(
+                        format!("missing '\"' to close the bound at position {}", position),
+                        self.pos,
+                    );
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if !quoted && bound.is_empty() {
+            return self.unexpected();
+        }
+        if !quoted && bound == "*" {
+            Ok(None)
         } else {
-            term
-        };
-        let mut queries = Vec::new();
-        for fb in &self.fields {
-            queries.push(self.term_query(term.clone(), fb.0.clone(), fb.1 * boost));
-        }
-        Ok(queries)
-    }
-
-    fn field_phrase_query<C: Codec>(&self, query: &str) -> Result<Vec<Box<dyn Query<C>>>> {
-        if let Some(idx) = query.find('~') {
-            let (t, s) = query.split_at(idx);
-            let slop_str: String = s.chars().skip(1).collect();
-            let slop = slop_str.parse::<i32>()?;
-            let term_strs: Vec<&str> = t.split_whitespace().collect();
-            if term_strs.len() < 2 {
-                bail!(IllegalArgument(
-                    "phrase query terms size must not small than 2".into()
-                ));
-            }
-            let mut queries = Vec::with_capacity(self.fields.len());
-            for fb in &self.fields {
-                let terms: Vec<Term> = term_strs
-                    .iter()
-                    .map(|term| Term::new(fb.0.clone(), term.as_bytes().to_vec()))
-                    .collect();
-                queries.push(BoostQuery::build(
-                    Box::new(PhraseQuery::build(terms, slop, None, None)?),
-                    fb.1,
-                ))
-            }
-
-            Ok(queries)
+            Ok(Some((bound, position)))
+        }
+    }
+
+    fn parse_regexp<C: Codec>(&mut self, field: Option<&str>) -> Result<Box<dyn Query<C>>> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut pattern = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return self.error(
+                        format!(
+                            "missing '/' to close the regular expression at position {}",
+                            open
+                        ),
+                        self.pos,
+                    );
+                }
+                Some('\\') if self.peek_at(1) == Some('/') => {
+                    pattern.push('/');
+                    self.pos += 2;
+                    continue;
+                }
+                Some('\\') if self.peek_at(1).is_some() => {
+                    pattern.push('\\');
+                    pattern.push(self.peek_at(1).unwrap());
+                    self.pos += 2;
+                    continue;
+                }
+                Some('/') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(c) => pattern.push(c),
+            }
+            self.pos += 1;
+        }
+        let boost = self.parse_boost()?;
+
+        self.field_queries(field, boost, open, |field, boost| {
+            if self.builder.point_fields.contains_key(field) {
+                return self.error(
+                    format!(
+                        "regular expressions are not supported on point field '{}'",
+                        field
+                    ),
+                    open,
+                );
+            }
+            let matcher = self.at(MultiTermMatcher::regexp(&pattern), open)?;
+            Ok(BoostQuery::build(
+                Box::new(MultiTermQuery::new(field.to_string(), matcher)),
+                boost,
+            ))
+        })
+    }
+
+    /// Build the query of a clause with `build`, for `field` or for each of the default
+    /// fields with their boosts.
+    fn field_queries<C: Codec, F>(
+        &self,
+        field: Option<&str>,
+        boost: f32,
+        position: usize,
+        mut build: F,
+    ) -> Result<Box<dyn Query<C>>>
+    where
+        F: FnMut(&str, f32) -> Result<Box<dyn Query<C>>>,
+    {
+        if let Some(field) = field {
+            return build(field, boost);
+        }
+        if self.builder.fields.is_empty() {
+            return self.error("no field to search".into(), position);
+        }
+        let mut queries = Vec::with_capacity(self.builder.fields.len());
+        for (field, field_boost) in &self.builder.fields {
+            queries.push(build(field, field_boost * boost)?);
+        }
+        if queries.len() == 1 {
+            Ok(queries.remove(0))
         } else {
-            bail!(IllegalArgument(format!(
-                "invalid query string '{}' for phrase query",
-                &query
-            )));
+            BooleanQuery::build(
+                vec![],
+                queries,
+                vec![],
+                vec![],
+                self.builder.min_should_match,
+            )
+        }
+    }
+
+    fn parse_point<T: FromStr>(
+        &self,
+        value: &str,
+        value_type: PointValueType,
+        position: usize,
+    ) -> Result<T> {
+        match value.trim().parse::<T>() {
+            Ok(v) => Ok(v),
+            Err(_) => self.error(
+                format!("invalid {} value '{}'", value_type, value),
+                position,
+            ),
+        }
+    }
+
+    fn point_exact_query<C: Codec>(
+        &self,
+        field: &str,
+        value_type: PointValueType,
+        value: &str,
+        position: usize,
+    ) -> Result<Box<dyn Query<C>>> {
+        let field = field.to_string();
+        match value_type {
+            PointValueType::Integer => {
+                IntPoint::new_exact_query(field, self.parse_point(value, value_type, position)?)
+            }
+            PointValueType::Long => {
+                LongPoint::new_exact_query(field, self.parse_point(value, value_type, position)?)
+            }
+            PointValueType::Float => {
+                FloatPoint::new_exact_query(field, self.parse_point(value, value_type, position)?)
+            }
+            PointValueType::Double => {
+                DoublePoint::new_exact_query(field, self.parse_point(value, value_type, position)?)
+            }
+            PointValueType::InetAddress => InetAddressPoint::new_exact_query(
+                field,
+                self.parse_point(value, value_type, position)?,
+            ),
+        }
+    }
+
+    /// A point range query from the bounds and whether they are inclusive, the exclusive
+    /// bounds being converted to the next inclusive value.
+    fn point_range_query<C: Codec>(
+        &self,
+        field: &str,
+        value_type: PointValueType,
+        lower: (&Option<(String, usize)>, bool),
+        upper: (&Option<(String, usize)>, bool),
+    ) -> Result<Box<dyn Query<C>>> {
+        let field = field.to_string();
+        macro_rules! bound {
+            ($bound:expr, $open:expr, $exclusive:expr) => {
+                match $bound {
+                    (Some((value, position)), inclusive) => {
+                        let v = self.parse_point(value, value_type, *position)?;
+                        if inclusive {
+                            Some(v)
+                        } else {
+                            $exclusive(v)
+                        }
+                    }
+                    (None, _) => Some($open),
+                }
+            };
+        }
+        match value_type {
+            PointValueType::Integer => {
+                let l = bound!(lower, i32::min_value(), |v: i32| v.checked_add(1));
+                let u = bound!(upper, i32::max_value(), |v: i32| v.checked_sub(1));
+                match (l, u) {
+                    (Some(l), Some(u)) => IntPoint::new_range_query(field, l, u),
+                    _ => IntPoint::new_range_query(field, i32::max_value(), i32::min_value()),
+                }
+            }
+            PointValueType::Long => {
+                let l = bound!(lower, i64::min_value(), |v: i64| v.checked_add(1));
+                let u = bound!(upper, i64::max_value(), |v: i64| v.checked_sub(1));
+                match (l, u) {
+                    (Some(l), Some(u)) => LongPoint::new_range_query(field, l, u),
+                    _ => LongPoint::new_range_query(field, i64::max_value(), i64::min_value()),
+                }
+            }
+            PointValueType::Float => {
+                let l = bound!(lower, f32::NEG_INFINITY, |v| Some(FloatPoint::next_up(v)));
+                let u = bound!(upper, f32::INFINITY, |v| Some(FloatPoint::next_down(v)));
+                FloatPoint::new_range_query(field, l.unwrap(), u.unwrap())
+            }
+            PointValueType::Double => {
+                let l = bound!(lower, f64::NEG_INFINITY, |v| Some(DoublePoint::next_up(v)));
+                let u = bound!(upper, f64::INFINITY, |v| Some(DoublePoint::next_down(v)));
+                DoublePoint::new_range_query(field, l.unwrap(), u.unwrap())
+            }
+            PointValueType::InetAddress => {
+                let l = bound!(
+                    lower,
+                    InetAddressPoint::MIN_VALUE,
+                    InetAddressPoint::next_up
+                );
+                let u = bound!(
+                    upper,
+                    InetAddressPoint::MAX_VALUE,
+                    InetAddressPoint::next_down
+                );
+                match (l, u) {
+                    (Some(l), Some(u)) => InetAddressPoint::new_range_query(field, l, u),
+                    _ => InetAddressPoint::new_range_query(
+                        field,
+                        InetAddressPoint::MAX_VALUE,
+                        InetAddressPoint::MIN_VALUE,
+                    ),
+                }
+            }
         }
     }
 }
@@ -252,7 +986,13 @@ impl QueryStringQueryBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+
     use core::codec::tests::TestCodec;
+    use core::doc::Fieldable;
+    use core::search::query::tests::{count_hits, index_fields, new_keyword_field};
+    use core::search::DefaultIndexSearcher;
+
+    use std::sync::Arc;
 
     #[test]
     fn test_query_string_query() {
@@ -410,4 +1150,191 @@ mod tests {
             )
         );
     }
+
+    fn parse(query_string: &str, operator: Operator) -> Result<Box<dyn Query<TestCodec>>> {
+        QueryStringQueryBuilder::new(
+            query_string.to_string(),
+            vec![("text".to_string(), 1.0)],
+            0,
+            1.0,
+        )
+        .with_default_operator(operator)
+        .with_point_field("price", PointValueType::Integer)
+        .with_point_field("weight", PointValueType::Double)
+        .build()
+    }
+
+    fn to_string(query_string: &str) -> String {
+        parse(query_string, Operator::Or).unwrap().to_string()
+    }
+
+    fn error(query_string: &str) -> String {
+        match parse(query_string, Operator::Or) {
+            Ok(q) => panic!("'{}' should not parse, got {}", query_string, q),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_classic_query_syntax() {
+        assert_eq!(
+            to_string("title:quick AND (body:fox OR body:dog) -title:slow"),
+            "BooleanQuery(must: [TermQuery(field: title, term: quick, boost: 1), \
+             BooleanQuery(must: [], should: [TermQuery(field: body, term: fox, boost: 1), \
+             TermQuery(field: body, term: dog, boost: 1)], filters: [], must_not: [], match: 1)], \
+             should: [], filters: [], must_not: [TermQuery(field: title, term: slow, boost: 1)], \
+             match: 0)"
+        );
+        assert_eq!(
+            parse("a b OR NOT c", Operator::And).unwrap().to_string(),
+            "BooleanQuery(must: [TermQuery(field: text, term: a, boost: 1)], should: \
+             [TermQuery(field: text, term: b, boost: 1)], filters: [], must_not: \
+             [TermQuery(field: text, term: c, boost: 1)], match: 0)"
+        );
+        assert_eq!(
+            to_string("title:(quick fox)^2"),
+            "BoostQuery(query: BooleanQuery(must: [], should: [TermQuery(field: title, term: \
+             quick, boost: 1), TermQuery(field: title, term: fox, boost: 1)], filters: [], \
+             must_not: [], match: 1), boost: 2)"
+        );
+        assert_eq!(
+            to_string(r"title:foo\:bar\ baz^0.5"),
+            "TermQuery(field: title, term: foo:bar baz, boost: 0.5)"
+        );
+
+        let phrase = to_string("\"quick brown\"~2");
+        assert!(phrase.starts_with("PhraseQuery(field: text"));
+        assert!(phrase.ends_with("slop: 2)"));
+        assert_eq!(
+            to_string("\"quick\"^2"),
+            "TermQuery(field: text, term: quick, boost: 2)"
+        );
+
+        assert_eq!(
+            to_string("qui*"),
+            "MultiTermQuery(field: text, query: qui*)"
+        );
+        assert_eq!(
+            to_string("q?i*k"),
+            "MultiTermQuery(field: text, query: q?i*k)"
+        );
+        assert_eq!(
+            to_string("quikc~1"),
+            "MultiTermQuery(field: text, query: quikc~1)"
+        );
+        assert_eq!(
+            to_string("quikc~"),
+            "MultiTermQuery(field: text, query: quikc~2)"
+        );
+        assert_eq!(
+            to_string(r"/qu[a-z]+\/x/"),
+            "MultiTermQuery(field: text, query: /qu[a-z]+/x/)"
+        );
+        // the wildcard terms may end with a multi-byte character
+        assert_eq!(
+            to_string("fo*ö"),
+            "MultiTermQuery(field: text, query: fo*ö)"
+        );
+        assert_eq!(
+            to_string("te?é"),
+            "MultiTermQuery(field: text, query: te?é)"
+        );
+        assert_eq!(to_string("fö*"), "MultiTermQuery(field: text, query: fö*)");
+        assert_eq!(to_string("*:*"), "MatchAllDocsQuery()");
+        assert_eq!(to_string("title:*"), "ExistsQuery(field=title)");
+
+        assert_eq!(
+            to_string("price:[10 TO 20}"),
+            "PointRangeQuery(field: price, type:int, num_dims: 1, bytes_per_dim: 4, lower: [10], \
+             upper: [19])"
+        );
+        assert_eq!(
+            to_string("price:{* TO 5]"),
+            "PointRangeQuery(field: price, type:int, num_dims: 1, bytes_per_dim: 4, lower: \
+             [-2147483648], upper: [5])"
+        );
+        assert_eq!(
+            to_string("price:-3"),
+            "PointRangeQuery(field: price, type:int, num_dims: 1, bytes_per_dim: 4, lower: [-3], \
+             upper: [-3])"
+        );
+        assert_eq!(
+            to_string("weight:[\"1.5\" TO *]"),
+            "PointRangeQuery(field: weight, type:double, num_dims: 1, bytes_per_dim: 8, lower: \
+             [1.5], upper: [inf])"
+        );
+        assert_eq!(
+            to_string("title:[a TO \"m n\"}"),
+            "TermRangeQuery(field: title, range: [a TO m n})"
+        );
+        assert_eq!(
+            to_string("title:{* TO b]"),
+            "TermRangeQuery(field: title, range: {* TO b])"
+        );
+    }
+
+    #[test]
+    fn test_query_string_errors() {
+        assert!(error("(a b").contains("missing ')' to close the '(' at position 0 at position 4"));
+        assert!(error("a b)").contains("unexpected ')' at position 3"));
+        assert!(error("AND a").contains("a query can't start with an operator at position 0"));
+        assert!(error("a +").contains("unexpected end of query string at position 3"));
+        assert!(error("a ()").contains("unexpected ')' at position 3"));
+        assert!(error("a^").contains("expected a boost after '^' at position 2"));
+        assert!(error("a~3").contains("fuzzy edit distance must be between 0 and 2 at position 1"));
+        assert!(error(r"a\").contains("trailing escape character at position 1"));
+        assert!(error("\"a b").contains("missing '\"' to close the phrase at position 0"));
+        assert!(error("price:[1 TO x]").contains("invalid int value 'x' at position 12"));
+        assert!(error("price:[1 x]").contains("expected 'TO' in range at position 9"));
+        assert!(error("price:ab*").contains("not supported on point field 'price'"));
+        assert!(error("/a(/").contains("at position 0"));
+
+        // deeply nested groups are rejected rather than overflowing the stack
+        let nested = format!("{}a{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(error(&nested).contains("groups nested too deeply at position 64"));
+        let nested = format!("{}a", "+(-(NOT (".repeat(50_000));
+        assert!(error(&nested).contains("groups nested too deeply"));
+        let nested = format!(
+            "{}a{}",
+            "(".repeat(MAX_GROUP_DEPTH),
+            ")".repeat(MAX_GROUP_DEPTH)
+        );
+        assert_eq!(
+            to_string(&nested),
+            "TermQuery(field: text, term: a, boost: 1)"
+        );
+        // and the modifiers are read one per clause, without nesting
+        assert!(parse(&("NOT ".repeat(100_000) + "a"), Operator::Or).is_ok());
+    }
+
+    #[test]
+    fn test_multi_term_query_without_positions() {
+        // a keyword field indexed without positions, with more terms than the span
+        // queries expand to
+        let mut docs = vec![];
+        for i in 0..1200 {
+            let text = format!("foo{:04}", i);
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(new_keyword_field("tag", &text))];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query_string: &str| -> usize {
+            let query: Box<dyn Query<_>> = QueryStringQueryBuilder::new(
+                query_string.to_string(),
+                vec![("tag".to_string(), 1.0)],
+                0,
+                1.0,
+            )
+            .build()
+            .unwrap();
+            count_hits(&searcher, query.as_ref())
+        };
+
+        assert_eq!(count("foo*"), 1200);
+        assert_eq!(count("foo1*"), 200);
+        assert_eq!(count("foo?00?"), 20);
+        assert_eq!(count("foo0000~1"), 29);
+        assert_eq!(count("/foo00[0-4]0/"), 5);
+    }
 }
@@ -0,0 +1,582 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use core::codec::points::{IntersectVisitor, PointValues, Relation, RANGE_MAX_DIMENSIONS};
+use core::codec::Codec;
+use core::index::reader::{LeafReader, LeafReaderContext};
+use core::search::explanation::Explanation;
+use core::search::query::{PointValueType, Query, TermQuery, Weight};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIdSet, DocIterator};
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::{ErrorKind, Result};
+
+use std::any::Any;
+use std::fmt;
+
+pub const RANGE_FIELD: &str = "range_field";
+
+/// The relation between the indexed ranges and the range of a `RangeFieldQuery`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RangeRelation {
+    /// Matches the ranges that intersect the query range.
+    Intersects,
+    /// Matches the ranges within the query range.
+    Within,
+    /// Matches the ranges that contain the query range.
+    Contains,
+    /// Matches the ranges that intersect the query range but are not within it.
+    Crosses,
+}
+
+impl RangeRelation {
+    // the relation of a BKD cell for one dimension, `ranges` being the packed query
+    // ranges and `min_packed_value` / `max_packed_value` the bounds of the cell
+    fn compare_dim(
+        self,
+        ranges: &[u8],
+        min_packed_value: &[u8],
+        max_packed_value: &[u8],
+        num_dims: usize,
+        bytes_per_dim: usize,
+        dim: usize,
+    ) -> Relation {
+        let min_offset = dim * bytes_per_dim;
+        let max_offset = min_offset + num_dims * bytes_per_dim;
+        let query_min = &ranges[min_offset..min_offset + bytes_per_dim];
+        let query_max = &ranges[max_offset..max_offset + bytes_per_dim];
+        // the bounds of the minimums and of the maximums of the ranges in the cell
+        let min_of_mins = &min_packed_value[min_offset..min_offset + bytes_per_dim];
+        let max_of_mins = &max_packed_value[min_offset..min_offset + bytes_per_dim];
+        let min_of_maxes = &min_packed_value[max_offset..max_offset + bytes_per_dim];
+        let max_of_maxes = &max_packed_value[max_offset..max_offset + bytes_per_dim];
+        match self {
+            RangeRelation::Intersects => {
+                if query_max < min_of_mins || query_min > max_of_maxes {
+                    Relation::CellOutsideQuery
+                } else if query_max >= max_of_mins && query_min <= min_of_maxes {
+                    Relation::CellInsideQuery
+                } else {
+                    Relation::CellCrossesQuery
+                }
+            }
+            RangeRelation::Within => {
+                if query_max < min_of_mins || query_min > max_of_maxes {
+                    Relation::CellOutsideQuery
+                } else if query_max >= max_of_maxes && query_min <= min_of_mins {
+                    Relation::CellInsideQuery
+                } else {
+                    Relation::CellCrossesQuery
+                }
+            }
+            RangeRelation::Contains => {
+                if query_max > max_of_maxes || query_min < min_of_mins {
+                    Relation::CellOutsideQuery
+                } else if query_max <= min_of_maxes && query_min >= max_of_mins {
+                    Relation::CellInsideQuery
+                } else {
+                    Relation::CellCrossesQuery
+                }
+            }
+            RangeRelation::Crosses => unreachable!(),
+        }
+    }
+
+    /// Computes the relation of a cell of the BKD tree with the query ranges.
+    fn compare(
+        self,
+        ranges: &[u8],
+        min_packed_value: &[u8],
+        max_packed_value: &[u8],
+        num_dims: usize,
+        bytes_per_dim: usize,
+    ) -> Relation {
+        if self == RangeRelation::Crosses {
+            let intersects = RangeRelation::Intersects.compare(
+                ranges,
+                min_packed_value,
+                max_packed_value,
+                num_dims,
+                bytes_per_dim,
+            );
+            if intersects == Relation::CellOutsideQuery {
+                return Relation::CellOutsideQuery;
+            }
+            let within = RangeRelation::Within.compare(
+                ranges,
+                min_packed_value,
+                max_packed_value,
+                num_dims,
+                bytes_per_dim,
+            );
+            return if within == Relation::CellInsideQuery {
+                Relation::CellOutsideQuery
+            } else if intersects == Relation::CellInsideQuery
+                && within == Relation::CellOutsideQuery
+            {
+                Relation::CellInsideQuery
+            } else {
+                Relation::CellCrossesQuery
+            };
+        }
+
+        let mut inside = true;
+        for dim in 0..num_dims {
+            match self.compare_dim(
+                ranges,
+                min_packed_value,
+                max_packed_value,
+                num_dims,
+                bytes_per_dim,
+                dim,
+            ) {
+                Relation::CellOutsideQuery => return Relation::CellOutsideQuery,
+                Relation::CellInsideQuery => {}
+                Relation::CellCrossesQuery => inside = false,
+            }
+        }
+        if inside {
+            Relation::CellInsideQuery
+        } else {
+            Relation::CellCrossesQuery
+        }
+    }
+
+    /// Whether the packed indexed range matches the query ranges.
+    fn matches(
+        self,
+        ranges: &[u8],
+        packed_value: &[u8],
+        num_dims: usize,
+        bytes_per_dim: usize,
+    ) -> bool {
+        if self == RangeRelation::Crosses {
+            return RangeRelation::Intersects.matches(
+                ranges,
+                packed_value,
+                num_dims,
+                bytes_per_dim,
+            ) && !RangeRelation::Within.matches(
+                ranges,
+                packed_value,
+                num_dims,
+                bytes_per_dim,
+            );
+        }
+        (0..num_dims).all(|dim| {
+            let min_offset = dim * bytes_per_dim;
+            let max_offset = min_offset + num_dims * bytes_per_dim;
+            let query_min = &ranges[min_offset..min_offset + bytes_per_dim];
+            let query_max = &ranges[max_offset..max_offset + bytes_per_dim];
+            let min = &packed_value[min_offset..min_offset + bytes_per_dim];
+            let max = &packed_value[max_offset..max_offset + bytes_per_dim];
+            match self {
+                RangeRelation::Intersects => query_min <= max && query_max >= min,
+                RangeRelation::Within => query_min <= min && query_max >= max,
+                RangeRelation::Contains => min <= query_min && max >= query_max,
+                RangeRelation::Crosses => unreachable!(),
+            }
+        })
+    }
+}
+
+impl fmt::Display for RangeRelation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeRelation::Intersects => write!(f, "INTERSECTS"),
+            RangeRelation::Within => write!(f, "WITHIN"),
+            RangeRelation::Contains => write!(f, "CONTAINS"),
+            RangeRelation::Crosses => write!(f, "CROSSES"),
+        }
+    }
+}
+
+/// Query on the ranges indexed by range fields such as `IntRange`, matching the
+/// documents with a range in the given `RangeRelation` with the query range.
+///
+/// The ranges are packed like the range fields: the minimums of all dimensions,
+/// followed by the maximums. A document matches if any of its ranges does.
+#[derive(Clone)]
+pub struct RangeFieldQuery {
+    field: String,
+    ranges: Vec<u8>,
+    num_dims: usize,
+    bytes_per_dim: usize,
+    value_type: PointValueType,
+    relation: RangeRelation,
+}
+
+impl RangeFieldQuery {
+    pub fn new(
+        field: String,
+        ranges: Vec<u8>,
+        num_dims: usize,
+        bytes_per_dim: usize,
+        value_type: PointValueType,
+        relation: RangeRelation,
+    ) -> Result<RangeFieldQuery> {
+        if num_dims == 0 || num_dims > RANGE_MAX_DIMENSIONS {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "num_dims must be in 1..={}, got {}",
+                RANGE_MAX_DIMENSIONS, num_dims
+            )));
+        }
+        if bytes_per_dim == 0 || ranges.len() != 2 * num_dims * bytes_per_dim {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "ranges of {} bytes do not match num_dims={} bytes_per_dim={}",
+                ranges.len(),
+                num_dims,
+                bytes_per_dim
+            )));
+        }
+        for dim in 0..num_dims {
+            let min_offset = dim * bytes_per_dim;
+            let max_offset = min_offset + num_dims * bytes_per_dim;
+            if ranges[min_offset..min_offset + bytes_per_dim]
+                > ranges[max_offset..max_offset + bytes_per_dim]
+            {
+                bail!(ErrorKind::IllegalArgument(format!(
+                    "min value is greater than max value in dimension {}",
+                    dim
+                )));
+            }
+        }
+        Ok(RangeFieldQuery {
+            field,
+            ranges,
+            num_dims,
+            bytes_per_dim,
+            value_type,
+            relation,
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn ranges(&self) -> &[u8] {
+        &self.ranges
+    }
+
+    pub fn num_dims(&self) -> usize {
+        self.num_dims
+    }
+
+    pub fn bytes_per_dim(&self) -> usize {
+        self.bytes_per_dim
+    }
+
+    pub fn value_type(&self) -> PointValueType {
+        self.value_type
+    }
+
+    pub fn relation(&self) -> RangeRelation {
+        self.relation
+    }
+
+    fn format_ranges(&self) -> String {
+        let split = self.num_dims * self.bytes_per_dim;
+        format!(
+            "min: {}, max: {}",
+            self.value_type
+                .format_bytes(&self.ranges[..split], self.bytes_per_dim),
+            self.value_type
+                .format_bytes(&self.ranges[split..], self.bytes_per_dim)
+        )
+    }
+}
+
+impl<C: Codec> Query<C> for RangeFieldQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(RangeFieldWeight {
+            query: self.clone(),
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for RangeFieldQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RangeFieldQuery(field: {}, type:{}, relation: {}, num_dims: {}, {})",
+            &self.field,
+            &self.value_type,
+            self.relation,
+            self.num_dims,
+            self.format_ranges()
+        )
+    }
+}
+
+struct RangeFieldWeight {
+    query: RangeFieldQuery,
+    weight: f32,
+    norm: f32,
+}
+
+impl RangeFieldWeight {
+    fn check_field<R: LeafReader + ?Sized>(&self, reader: &R) -> Result<bool> {
+        match reader.field_info(&self.query.field) {
+            Some(field_info) => {
+                if field_info.point_dimension_count != 2 * self.query.num_dims as u32
+                    || field_info.point_num_bytes != self.query.bytes_per_dim as u32
+                {
+                    bail!(ErrorKind::IllegalArgument(format!(
+                        "field '{}' was indexed with num_dims={} bytes_per_dim={} but this query \
+                         has num_dims={} bytes_per_dim={}",
+                        &self.query.field,
+                        field_info.point_dimension_count,
+                        field_info.point_num_bytes,
+                        2 * self.query.num_dims,
+                        self.query.bytes_per_dim
+                    )));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for RangeFieldWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        let values = match leaf_reader.point_values() {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+        if !self.check_field(leaf_reader)? {
+            return Ok(None);
+        }
+
+        let field = &self.query.field;
+        let mut result = DocIdSetBuilder::from_values(leaf_reader.max_doc(), &values, field)?;
+        {
+            let mut visitor = RangeFieldIntersectVisitor {
+                doc_id_set_builder: &mut result,
+                query: &self.query,
+            };
+            values.intersect(field, &mut visitor)?;
+        }
+
+        if let Some(iterator) = result.build().iterator()? {
+            let cost = iterator.cost();
+            Ok(Some(Box::new(ConstantScoreScorer::new(
+                self.weight,
+                iterator,
+                cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        RANGE_FIELD
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matches {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self.query),
+                vec![
+                    Explanation::new(true, self.weight / self.norm, "boost".into(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".into(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("{} doesn't match id {}", self.query, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for RangeFieldWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RangeFieldWeight(query: {})", self.query)
+    }
+}
+
+struct RangeFieldIntersectVisitor<'a> {
+    doc_id_set_builder: &'a mut DocIdSetBuilder,
+    query: &'a RangeFieldQuery,
+}
+
+impl<'a> IntersectVisitor for RangeFieldIntersectVisitor<'a> {
+    fn visit(&mut self, doc_id: DocId) -> Result<()> {
+        self.doc_id_set_builder.add_doc(doc_id);
+        Ok(())
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        if self.query.relation.matches(
+            &self.query.ranges,
+            packed_value,
+            self.query.num_dims,
+            self.query.bytes_per_dim,
+        ) {
+            self.doc_id_set_builder.add_doc(doc_id);
+        }
+        Ok(())
+    }
+
+    fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        self.query.relation.compare(
+            &self.query.ranges,
+            min_packed_value,
+            max_packed_value,
+            self.query.num_dims,
+            self.query.bytes_per_dim,
+        )
+    }
+
+    fn grow(&mut self, count: usize) {
+        self.doc_id_set_builder.grow(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::points::{DoubleRange, IntRange};
+    use core::codec::CodecEnum;
+    use core::doc::{Fieldable, NumericDocValuesField};
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::tests::{count_hits, index_fields};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_range_field_queries() {
+        // 2d int boxes and 1d double ranges, spread over many leaf blocks
+        let mut boxes = vec![];
+        for i in 0..3000i32 {
+            let x = (i * 37) % 1000 - 500;
+            let y = (i * 91) % 800 - 400;
+            let (w, h) = ((i * 13) % 200, (i * 7) % 150);
+            boxes.push(([x, y], [x + w, y + h]));
+        }
+        let mut docs = vec![];
+        for (i, (min, max)) in boxes.iter().enumerate() {
+            let price = f64::from(min[0]) / 10.0;
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                Box::new(IntRange::new("box", min, max).unwrap()),
+                Box::new(DoubleRange::new("price", &[price], &[price + 2.5]).unwrap()),
+                Box::new(NumericDocValuesField::new("id", i as i64)),
+            ];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        let intersects = |a: &([i32; 2], [i32; 2]), b: &([i32; 2], [i32; 2])| {
+            (0..2).all(|d| a.0[d] <= b.1[d] && a.1[d] >= b.0[d])
+        };
+        let within = |a: &([i32; 2], [i32; 2]), b: &([i32; 2], [i32; 2])| {
+            (0..2).all(|d| a.0[d] >= b.0[d] && a.1[d] <= b.1[d])
+        };
+        for query_box in &[
+            ([-100, -100], [100, 100]),
+            ([-500, -400], [700, 600]),
+            ([0, 0], [0, 0]),
+            ([200, -300], [450, -50]),
+            ([-600, 500], [-550, 600]),
+        ] {
+            let (min, max) = query_box;
+            let expected = |f: &dyn Fn(&([i32; 2], [i32; 2])) -> bool| -> usize {
+                boxes.iter().filter(|b| f(b)).count()
+            };
+            let n = expected(&|b| intersects(b, query_box));
+            let q = IntRange::new_intersects_query("box".into(), min, max).unwrap();
+            assert_eq!(count(q.as_ref()), n);
+            let n = expected(&|b| within(b, query_box));
+            let q = IntRange::new_within_query("box".into(), min, max).unwrap();
+            assert_eq!(count(q.as_ref()), n);
+            let n = expected(&|b| within(query_box, b));
+            let q = IntRange::new_contains_query("box".into(), min, max).unwrap();
+            assert_eq!(count(q.as_ref()), n);
+            let n = expected(&|b| intersects(b, query_box) && !within(b, query_box));
+            let q = IntRange::new_crosses_query("box".into(), min, max).unwrap();
+            assert_eq!(count(q.as_ref()), n);
+        }
+
+        let q = DoubleRange::new_within_query("price".into(), &[-10.0], &[10.0]).unwrap();
+        let n = boxes
+            .iter()
+            .filter(|b| {
+                let price = f64::from(b.0[0]) / 10.0;
+                price >= -10.0 && price + 2.5 <= 10.0
+            })
+            .count();
+        assert!(n > 0);
+        assert_eq!(count(q.as_ref()), n);
+
+        // the number of dimensions must match the indexed field
+        let q = IntRange::new_intersects_query("box".into(), &[0], &[1]).unwrap();
+        let mut collector = TopDocsCollector::new(10);
+        assert!(searcher.search(q.as_ref(), &mut collector).is_err());
+
+        let field = IntRange::new("box", &[1, -2], &[3, 4]).unwrap();
+        assert_eq!(field.num_dims(), 2);
+        assert_eq!((field.min(1), field.max(1)), (-2, 4));
+        assert!(IntRange::new("box", &[3], &[1]).is_err());
+        assert!(IntRange::new("box", &[1, 2, 3, 4, 5], &[6, 7, 8, 9, 10]).is_err());
+        assert!(DoubleRange::new("price", &[std::f64::NAN], &[1.0]).is_err());
+        assert!(IntRange::new_within_query::<CodecEnum>("box".into(), &[1], &[0, 1]).is_err());
+    }
+}
@@ -0,0 +1,626 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::doc_values::NumericDocValues;
+use core::codec::Codec;
+use core::doc::DocValuesType;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::expressions::{ExpressionValueSource, LeafExpressionValues};
+use core::search::sort_field::{LeafDistanceValues, SortFieldType};
+use core::util::geo::{check_latitude, check_longitude};
+use core::util::{BitsMut, DocId};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+/// The numeric doc values of a field for one segment, decoded according to
+/// the type the values were indexed with.
+///
+/// Date fields are indexed as `Long` millisecond values.
+struct LeafNumericValues {
+    field_type: SortFieldType,
+    values: Option<(Box<dyn NumericDocValues>, Box<dyn BitsMut>)>,
+}
+
+impl LeafNumericValues {
+    fn new<C: Codec>(
+        reader: &LeafReaderContext<'_, C>,
+        field: &str,
+        field_type: SortFieldType,
+    ) -> Result<Self> {
+        let values = match reader.reader.field_info(field) {
+            Some(fi) if fi.doc_values_type == DocValuesType::Numeric => Some((
+                reader.reader.get_numeric_doc_values(field)?,
+                reader.reader.get_docs_with_field(field)?,
+            )),
+            _ => None,
+        };
+        Ok(LeafNumericValues { field_type, values })
+    }
+
+    /// The value of `doc`, `None` if the document has no value for the field
+    fn get(&mut self, doc: DocId) -> Result<Option<f64>> {
+        if let Some((ref mut values, ref mut docs_with_field)) = self.values {
+            if docs_with_field.get(doc as usize)? {
+                let raw = values.get_mut(doc)?;
+                let value = match self.field_type {
+                    SortFieldType::Int => f64::from(raw as i32),
+                    SortFieldType::Long => raw as f64,
+                    SortFieldType::Float => f64::from(f32::from_bits(raw as u32)),
+                    SortFieldType::Double => f64::from_bits(raw as u64),
+                    _ => unreachable!(),
+                };
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn check_numeric_type(field: &str, field_type: SortFieldType) -> Result<()> {
+    match field_type {
+        SortFieldType::Int | SortFieldType::Long | SortFieldType::Float | SortFieldType::Double => {
+            Ok(())
+        }
+        _ => bail!(ErrorKind::IllegalArgument(format!(
+            "field '{}' must be of a numeric type, got {:?}",
+            field, field_type
+        ))),
+    }
+}
+
+/// The modifier applied to the field value by `FieldValueFactorFunction`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FieldValueModifier {
+    None,
+    Log,
+    Log1p,
+    Log2p,
+    Ln,
+    Ln1p,
+    Ln2p,
+    Square,
+    Sqrt,
+    Reciprocal,
+}
+
+impl FieldValueModifier {
+    pub fn apply(self, v: f64) -> f64 {
+        match self {
+            FieldValueModifier::None => v,
+            FieldValueModifier::Log => v.log10(),
+            FieldValueModifier::Log1p => (v + 1.0).log10(),
+            FieldValueModifier::Log2p => (v + 2.0).log10(),
+            FieldValueModifier::Ln => v.ln(),
+            FieldValueModifier::Ln1p => v.ln_1p(),
+            FieldValueModifier::Ln2p => (v + 2.0).ln(),
+            FieldValueModifier::Square => v * v,
+            FieldValueModifier::Sqrt => v.sqrt(),
+            FieldValueModifier::Reciprocal => 1.0 / v,
+        }
+    }
+}
+
+impl fmt::Display for FieldValueModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FieldValueModifier::None => "none",
+            FieldValueModifier::Log => "log",
+            FieldValueModifier::Log1p => "log1p",
+            FieldValueModifier::Log2p => "log2p",
+            FieldValueModifier::Ln => "ln",
+            FieldValueModifier::Ln1p => "ln1p",
+            FieldValueModifier::Ln2p => "ln2p",
+            FieldValueModifier::Square => "square",
+            FieldValueModifier::Sqrt => "sqrt",
+            FieldValueModifier::Reciprocal => "reciprocal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Computes `modifier(factor * value)` from the numeric doc value of a field.
+#[derive(Debug, Clone)]
+pub struct FieldValueFactorFunction {
+    field: String,
+    field_type: SortFieldType,
+    factor: f64,
+    modifier: FieldValueModifier,
+    missing: Option<f64>,
+}
+
+impl FieldValueFactorFunction {
+    /// `missing` is used for the documents without value, the score of such a
+    /// document fails if no `missing` is given.
+    pub fn new(
+        field: String,
+        field_type: SortFieldType,
+        factor: f64,
+        modifier: FieldValueModifier,
+        missing: Option<f64>,
+    ) -> Result<Self> {
+        check_numeric_type(&field, field_type)?;
+        Ok(FieldValueFactorFunction {
+            field,
+            field_type,
+            factor,
+            modifier,
+            missing,
+        })
+    }
+
+    fn compute(&self, values: &mut LeafNumericValues, doc: DocId) -> Result<f64> {
+        let value = match values.get(doc)? {
+            Some(v) => v,
+            None => match self.missing {
+                Some(v) => v,
+                None => bail!(ErrorKind::IllegalState(format!(
+                    "missing value for field [{}]",
+                    &self.field
+                ))),
+            },
+        };
+        let score = self.modifier.apply(value * self.factor);
+        if score.is_nan() || score.is_infinite() {
+            bail!(ErrorKind::IllegalState(format!(
+                "result of field modification [{}({} * factor={})] must be a number",
+                self.modifier, value, self.factor
+            )));
+        }
+        Ok(score)
+    }
+}
+
+impl fmt::Display for FieldValueFactorFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(doc['{}'].value", self.modifier, &self.field)?;
+        if let Some(missing) = self.missing {
+            write!(f, "?:{}", missing)?;
+        }
+        write!(f, " * factor={})", self.factor)
+    }
+}
+
+/// The shape of the curve of a `DecayFunction`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DecayType {
+    Gauss,
+    Linear,
+    Exp,
+}
+
+impl DecayType {
+    /// The value of the curve at `distance`, which is `decay` at `scale`.
+    fn evaluate(self, distance: f64, scale: f64, decay: f64) -> f64 {
+        match self {
+            DecayType::Gauss => (decay.ln() * distance * distance / (scale * scale)).exp(),
+            DecayType::Exp => (decay.ln() * distance / scale).exp(),
+            DecayType::Linear => {
+                let s = scale / (1.0 - decay);
+                ((s - distance) / s).max(0.0)
+            }
+        }
+    }
+}
+
+fn check_decay(scale: f64, offset: f64, decay: f64) -> Result<()> {
+    if scale.is_nan() || scale <= 0.0 {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "decay scale must be positive, got {}",
+            scale
+        )));
+    }
+    if offset.is_nan() || offset < 0.0 {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "decay offset must not be negative, got {}",
+            offset
+        )));
+    }
+    if !(decay > 0.0 && decay < 1.0) {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "decay must be in the range (0, 1), got {}",
+            decay
+        )));
+    }
+    Ok(())
+}
+
+impl fmt::Display for DecayType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecayType::Gauss => write!(f, "gauss"),
+            DecayType::Linear => write!(f, "linear"),
+            DecayType::Exp => write!(f, "exp"),
+        }
+    }
+}
+
+/// Scores a document according to the distance of a numeric field value to an origin.
+///
+/// Documents within `offset` of `origin` get a score of 1, the score then decays
+/// with the distance to reach `decay` at `offset + scale`. Date fields are handled
+/// as `Long` fields, with `origin`, `scale` and `offset` given in milliseconds.
+/// Documents without value for the field get a score of 1.
+#[derive(Debug, Clone)]
+pub struct DecayFunction {
+    field: String,
+    field_type: SortFieldType,
+    decay_type: DecayType,
+    origin: f64,
+    scale: f64,
+    offset: f64,
+    decay: f64,
+}
+
+impl DecayFunction {
+    pub fn new(
+        field: String,
+        field_type: SortFieldType,
+        decay_type: DecayType,
+        origin: f64,
+        scale: f64,
+        offset: f64,
+        decay: f64,
+    ) -> Result<Self> {
+        check_numeric_type(&field, field_type)?;
+        check_decay(scale, offset, decay)?;
+        Ok(DecayFunction {
+            field,
+            field_type,
+            decay_type,
+            origin,
+            scale,
+            offset,
+            decay,
+        })
+    }
+
+    fn distance(&self, value: f64) -> f64 {
+        ((value - self.origin).abs() - self.offset).max(0.0)
+    }
+
+    pub fn evaluate(&self, value: f64) -> f64 {
+        self.decay_type
+            .evaluate(self.distance(value), self.scale, self.decay)
+    }
+}
+
+impl fmt::Display for DecayFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}(doc['{}'].value, origin={}, scale={}, offset={}, decay={})",
+            self.decay_type, &self.field, self.origin, self.scale, self.offset, self.decay
+        )
+    }
+}
+
+/// Scores a document according to the haversine distance from the values of a
+/// `LatLonDocValuesField` to an origin location.
+///
+/// Works like `DecayFunction`, with `scale` and `offset` given in meters. The closest
+/// location is used for documents with several ones, and documents without location
+/// get a score of 1.
+#[derive(Debug, Clone)]
+pub struct GeoDecayFunction {
+    field: String,
+    decay_type: DecayType,
+    latitude: f64,
+    longitude: f64,
+    scale: f64,
+    offset: f64,
+    decay: f64,
+}
+
+impl GeoDecayFunction {
+    pub fn new(
+        field: String,
+        decay_type: DecayType,
+        latitude: f64,
+        longitude: f64,
+        scale: f64,
+        offset: f64,
+        decay: f64,
+    ) -> Result<Self> {
+        check_latitude(latitude)?;
+        check_longitude(longitude)?;
+        check_decay(scale, offset, decay)?;
+        Ok(GeoDecayFunction {
+            field,
+            decay_type,
+            latitude,
+            longitude,
+            scale,
+            offset,
+            decay,
+        })
+    }
+
+    fn distance(&self, meters: f64) -> f64 {
+        (meters - self.offset).max(0.0)
+    }
+
+    /// The score of a document at `meters` from the origin.
+    pub fn evaluate(&self, meters: f64) -> f64 {
+        self.decay_type
+            .evaluate(self.distance(meters), self.scale, self.decay)
+    }
+}
+
+impl fmt::Display for GeoDecayFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}(doc['{}'].distance, origin=[{}, {}], scale={}m, offset={}m, decay={})",
+            self.decay_type,
+            &self.field,
+            self.latitude,
+            self.longitude,
+            self.scale,
+            self.offset,
+            self.decay
+        )
+    }
+}
+
+/// A pseudo random score in `[0, 1)`, reproducible for a given `seed`.
+///
+/// The score is computed from the doc id, which changes when segments are merged,
+/// or from the numeric doc values of `field` when given.
+#[derive(Debug, Clone)]
+pub struct RandomScoreFunction {
+    seed: i64,
+    field: Option<String>,
+}
+
+impl RandomScoreFunction {
+    pub fn new(seed: i64, field: Option<String>) -> Self {
+        RandomScoreFunction { seed, field }
+    }
+
+    fn mix(mut v: u64) -> u64 {
+        v = (v ^ (v >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        v = (v ^ (v >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        v ^ (v >> 33)
+    }
+
+    fn compute(&self, key: i64) -> f64 {
+        let hash = Self::mix(self.seed as u64 ^ Self::mix(key as u64));
+        // the 24 highest bits, as the score is a f32 eventually
+        (hash >> 40) as f64 / (1u64 << 24) as f64
+    }
+}
+
+impl fmt::Display for RandomScoreFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field {
+            Some(ref field) => write!(f, "random(seed={}, field={})", self.seed, field),
+            None => write!(f, "random(seed={})", self.seed),
+        }
+    }
+}
+
+/// The functions available to a `FunctionScoreQuery`.
+#[derive(Debug, Clone)]
+pub enum ScoreFunction {
+    FieldValueFactor(FieldValueFactorFunction),
+    Decay(DecayFunction),
+    GeoDecay(GeoDecayFunction),
+    RandomScore(RandomScoreFunction),
+    /// The value of an expression, whose score variables take the query score
+    Expression(ExpressionValueSource),
+    /// Always 1, so that the function only contributes its weight
+    Weight,
+}
+
+impl ScoreFunction {
+    pub(crate) fn leaf_function<C: Codec>(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<LeafScoreFunction> {
+        let values = match self {
+            ScoreFunction::FieldValueFactor(f) => {
+                Some(LeafNumericValues::new(reader, &f.field, f.field_type)?)
+            }
+            ScoreFunction::Decay(f) => {
+                Some(LeafNumericValues::new(reader, &f.field, f.field_type)?)
+            }
+            ScoreFunction::RandomScore(RandomScoreFunction {
+                field: Some(ref field),
+                ..
+            }) => Some(LeafNumericValues::new(reader, field, SortFieldType::Long)?),
+            _ => None,
+        };
+        let expression = match self {
+            ScoreFunction::Expression(source) => Some(source.leaf_values(reader)?),
+            _ => None,
+        };
+        let distances = match self {
+            ScoreFunction::GeoDecay(f) => Some(LeafDistanceValues::new(
+                reader,
+                &f.field,
+                f.latitude,
+                f.longitude,
+            )?),
+            _ => None,
+        };
+        Ok(LeafScoreFunction {
+            function: self.clone(),
+            values,
+            expression,
+            distances,
+            doc_base: reader.doc_base,
+        })
+    }
+}
+
+impl fmt::Display for ScoreFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScoreFunction::FieldValueFactor(func) => write!(f, "{}", func),
+            ScoreFunction::Decay(func) => write!(f, "{}", func),
+            ScoreFunction::GeoDecay(func) => write!(f, "{}", func),
+            ScoreFunction::RandomScore(func) => write!(f, "{}", func),
+            ScoreFunction::Expression(source) => write!(f, "{}", source),
+            ScoreFunction::Weight => write!(f, "weight"),
+        }
+    }
+}
+
+/// A `ScoreFunction` bound to a segment.
+pub(crate) struct LeafScoreFunction {
+    function: ScoreFunction,
+    values: Option<LeafNumericValues>,
+    expression: Option<LeafExpressionValues>,
+    distances: Option<LeafDistanceValues>,
+    doc_base: DocId,
+}
+
+impl LeafScoreFunction {
+    /// The score of the function for `doc`, `query_score` being the score of the query
+    /// for the expressions bound to the score.
+    pub fn score(&mut self, doc: DocId, query_score: f32) -> Result<f64> {
+        match self.function {
+            ScoreFunction::FieldValueFactor(ref f) => f.compute(self.values.as_mut().unwrap(), doc),
+            ScoreFunction::Decay(ref f) => match self.values.as_mut().unwrap().get(doc)? {
+                Some(value) => Ok(f.evaluate(value)),
+                None => Ok(1.0),
+            },
+            ScoreFunction::GeoDecay(ref f) => {
+                match self.distances.as_mut().unwrap().distance(doc)? {
+                    Some(meters) => Ok(f.evaluate(meters)),
+                    None => Ok(1.0),
+                }
+            }
+            ScoreFunction::RandomScore(ref f) => {
+                let key = match self.values {
+                    Some(ref mut values) => values.get(doc)?.map_or(0, |v| v as i64),
+                    None => i64::from(self.doc_base + doc),
+                };
+                Ok(f.compute(key))
+            }
+            ScoreFunction::Expression(_) => {
+                self.expression.as_mut().unwrap().value(doc, query_score)
+            }
+            ScoreFunction::Weight => Ok(1.0),
+        }
+    }
+
+    pub fn explain(&mut self, doc: DocId, query_score: f32) -> Result<Explanation> {
+        if let ScoreFunction::Expression(_) = self.function {
+            return self.expression.as_mut().unwrap().explain(doc, query_score);
+        }
+        let score = self.score(doc, query_score)?;
+        let description = match self.function {
+            ScoreFunction::FieldValueFactor(ref f) => format!("field value function: {}", f),
+            ScoreFunction::Decay(ref f) => {
+                let value = self.values.as_mut().unwrap().get(doc)?;
+                match value {
+                    Some(v) => format!(
+                        "decay function: {}, value={}, distance={}",
+                        f,
+                        v,
+                        f.distance(v)
+                    ),
+                    None => format!("decay function: {}, missing value", f),
+                }
+            }
+            ScoreFunction::GeoDecay(ref f) => {
+                match self.distances.as_mut().unwrap().distance(doc)? {
+                    Some(meters) => format!(
+                        "geo decay function: {}, distance={}m, decayed distance={}m",
+                        f,
+                        meters,
+                        f.distance(meters)
+                    ),
+                    None => format!("geo decay function: {}, missing location", f),
+                }
+            }
+            ScoreFunction::RandomScore(ref f) => format!("random score function: {}", f),
+            ScoreFunction::Expression(_) => unreachable!(),
+            ScoreFunction::Weight => "constant score 1.0".to_string(),
+        };
+        Ok(Explanation::new(true, score as f32, description, vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decay(decay_type: DecayType) -> DecayFunction {
+        DecayFunction::new(
+            "price".into(),
+            SortFieldType::Double,
+            decay_type,
+            100.0,
+            10.0,
+            5.0,
+            0.5,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decay_functions() {
+        for decay_type in &[DecayType::Gauss, DecayType::Linear, DecayType::Exp] {
+            let func = decay(*decay_type);
+            assert!((func.evaluate(100.0) - 1.0).abs() < 1e-9);
+            assert!((func.evaluate(95.0) - 1.0).abs() < 1e-9);
+            // the score reaches `decay` at `offset + scale` from the origin
+            assert!((func.evaluate(115.0) - 0.5).abs() < 1e-9);
+            assert!((func.evaluate(85.0) - 0.5).abs() < 1e-9);
+            assert!(func.evaluate(120.0) < 0.5);
+        }
+        assert!(decay(DecayType::Linear).evaluate(200.0).abs() < 1e-9);
+
+        assert!(DecayFunction::new(
+            "price".into(),
+            SortFieldType::Double,
+            DecayType::Gauss,
+            0.0,
+            0.0,
+            0.0,
+            0.5
+        )
+        .is_err());
+        assert!(DecayFunction::new(
+            "price".into(),
+            SortFieldType::String,
+            DecayType::Gauss,
+            0.0,
+            1.0,
+            0.0,
+            0.5
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert!((FieldValueModifier::Log1p.apply(9.0) - 1.0).abs() < 1e-9);
+        assert!((FieldValueModifier::Ln1p.apply(0.0)).abs() < 1e-9);
+        assert!((FieldValueModifier::Sqrt.apply(16.0) - 4.0).abs() < 1e-9);
+        assert!((FieldValueModifier::Reciprocal.apply(4.0) - 0.25).abs() < 1e-9);
+
+        let random = RandomScoreFunction::new(42, None);
+        for key in 0..100 {
+            let score = random.compute(key);
+            assert!(score >= 0.0 && score < 1.0);
+            assert!((score - random.compute(key)).abs() < 1e-12);
+        }
+        assert!((random.compute(1) - RandomScoreFunction::new(43, None).compute(1)).abs() > 0.0);
+    }
+}
@@ -0,0 +1,596 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::{TokenStream, WhitespaceTokenizer};
+use core::codec::Codec;
+use core::doc::Term;
+use core::search::query::spans::{MultiTermMatcher, MAX_FUZZY_EDITS};
+use core::search::query::{
+    BooleanQuery, BoostQuery, MatchAllDocsQuery, MultiTermQuery, Operator, PhraseQuery, Query,
+    TermQuery,
+};
+
+use error::Result;
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// The maximum nesting depth of the groups of a simple query string.
+pub const MAX_GROUP_DEPTH: usize = 64;
+
+/// Creates the `TokenStream` analyzing the text of a query for a field.
+pub type QueryAnalyzer = Arc<dyn Fn(&str) -> Box<dyn TokenStream> + Send + Sync>;
+
+/// The `QueryAnalyzer` used by default, splitting the text on whitespaces.
+pub fn whitespace_analyzer() -> QueryAnalyzer {
+    Arc::new(|text: &str| -> Box<dyn TokenStream> {
+        Box::new(WhitespaceTokenizer::new(Box::new(Cursor::new(
+            text.as_bytes().to_vec(),
+        ))))
+    })
+}
+
+/// A forgiving query parser for search boxes, like the elasticsearch `simple_query_string`.
+///
+/// The supported operators are:
+/// * `+` for AND and `|` for OR, the default `Operator` being used between the clauses without
+///   explicit operator
+/// * `-` to negate a clause
+/// * `"a phrase"`, with an optional slop like `"a phrase"~2`
+/// * `prefix*` and fuzzy terms like `term~1`
+/// * `(` and `)` for precedence
+/// * `\` to escape the next character
+///
+/// The operators are applied from left to right, a change of operator wrapping the
+/// clauses on its left, so that `a b +c` is `(a b) +c`. The terms and phrases are
+/// analyzed with the `QueryAnalyzer` of each field and searched in all the fields
+/// with their boost.
+///
+/// The parser never fails on malformed input: unmatched parentheses are ignored, an
+/// unterminated phrase is parsed as plain terms and dangling operators are dropped.
+/// The groups nested deeper than `MAX_GROUP_DEPTH` are flattened into their parent.
+/// `build` returns `None` when there is nothing to search.
+pub struct SimpleQueryStringBuilder {
+    query_string: String,
+    fields: Vec<(String, f32)>,
+    default_operator: Operator,
+    analyzers: HashMap<String, QueryAnalyzer>,
+    default_analyzer: QueryAnalyzer,
+    fuzzy_prefix_length: usize,
+}
+
+impl SimpleQueryStringBuilder {
+    pub fn new(query_string: String, fields: Vec<(String, f32)>) -> Self {
+        SimpleQueryStringBuilder {
+            query_string,
+            fields,
+            default_operator: Operator::Or,
+            analyzers: HashMap::new(),
+            default_analyzer: whitespace_analyzer(),
+            fuzzy_prefix_length: 0,
+        }
+    }
+
+    pub fn with_default_operator(mut self, operator: Operator) -> Self {
+        self.default_operator = operator;
+        self
+    }
+
+    /// Analyze the text searched in `field` with `analyzer`.
+    pub fn with_analyzer(mut self, field: &str, analyzer: QueryAnalyzer) -> Self {
+        self.analyzers.insert(field.to_string(), analyzer);
+        self
+    }
+
+    /// Analyze the text searched in the fields without analyzer with `analyzer`.
+    pub fn with_default_analyzer(mut self, analyzer: QueryAnalyzer) -> Self {
+        self.default_analyzer = analyzer;
+        self
+    }
+
+    /// The number of leading characters of fuzzy terms which must match exactly.
+    pub fn with_fuzzy_prefix_length(mut self, prefix_length: usize) -> Self {
+        self.fuzzy_prefix_length = prefix_length;
+        self
+    }
+
+    /// Build the query, only failing if an analyzer fails.
+    pub fn build<C: Codec>(&self) -> Result<Option<Box<dyn Query<C>>>> {
+        let chars: Vec<char> = self.query_string.chars().collect();
+        self.parse(&chars, &mut 0, 0)
+    }
+
+    /// Parse the clauses up to the `)` closing the current group, if any.
+    fn parse<C: Codec>(
+        &self,
+        chars: &[char],
+        pos: &mut usize,
+        depth: usize,
+    ) -> Result<Option<Box<dyn Query<C>>>> {
+        let mut state = State::new();
+        // the groups opened past the max depth, whose parentheses are ignored
+        let mut flattened = 0;
+        while *pos < chars.len() {
+            let branch = match chars[*pos] {
+                '(' => {
+                    *pos += 1;
+                    if depth == MAX_GROUP_DEPTH {
+                        flattened += 1;
+                        continue;
+                    }
+                    self.parse(chars, pos, depth + 1)?
+                }
+                ')' => {
+                    *pos += 1;
+                    if flattened > 0 {
+                        flattened -= 1;
+                        continue;
+                    }
+                    if depth > 0 {
+                        break;
+                    }
+                    continue;
+                }
+                '"' => self.consume_phrase(chars, pos)?,
+                '+' | '|' => {
+                    if state.current.is_none() {
+                        state.current = Some(if chars[*pos] == '+' {
+                            Operator::And
+                        } else {
+                            Operator::Or
+                        });
+                    }
+                    *pos += 1;
+                    continue;
+                }
+                '-' => {
+                    state.negations += 1;
+                    *pos += 1;
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    *pos += 1;
+                    continue;
+                }
+                _ => self.consume_token(chars, pos)?,
+            };
+            self.add_branch(&mut state, branch)?;
+        }
+        state.finish(self)
+    }
+
+    fn add_branch<C: Codec>(
+        &self,
+        state: &mut State<C>,
+        branch: Option<Box<dyn Query<C>>>,
+    ) -> Result<()> {
+        let negated = state.negations % 2 == 1;
+        state.negations = 0;
+        let mut branch = match branch {
+            Some(branch) => branch,
+            None => {
+                return Ok(());
+            }
+        };
+        if negated {
+            branch = BooleanQuery::build(vec![], vec![], vec![], vec![branch], 0)?;
+        }
+        if state.clauses.is_empty() {
+            state.clauses.push(branch);
+        } else {
+            let operator = state.current.unwrap_or(self.default_operator);
+            // a change of operator wraps the previous clauses
+            if state.previous != Some(operator) {
+                let clauses = state.clauses.split_off(0);
+                let previous = state.previous.unwrap_or(operator);
+                state.clauses.push(self.combine(clauses, previous)?);
+            }
+            state.clauses.push(branch);
+            state.previous = Some(operator);
+        }
+        state.current = None;
+        Ok(())
+    }
+
+    fn combine<C: Codec>(
+        &self,
+        mut clauses: Vec<Box<dyn Query<C>>>,
+        operator: Operator,
+    ) -> Result<Box<dyn Query<C>>> {
+        if clauses.len() == 1 {
+            return Ok(clauses.remove(0));
+        }
+        match operator {
+            Operator::And => BooleanQuery::build(clauses, vec![], vec![], vec![], 0),
+            Operator::Or => BooleanQuery::build(vec![], clauses, vec![], vec![], 0),
+        }
+    }
+
+    /// Parse a phrase and its optional slop, or the text after the quote as plain
+    /// terms if the phrase is not terminated.
+    fn consume_phrase<C: Codec>(
+        &self,
+        chars: &[char],
+        pos: &mut usize,
+    ) -> Result<Option<Box<dyn Query<C>>>> {
+        let start = *pos + 1;
+        let mut text = String::new();
+        let mut end = start;
+        while end < chars.len() && chars[end] != '"' {
+            if chars[end] == '\\' && end + 1 < chars.len() {
+                end += 1;
+            }
+            text.push(chars[end]);
+            end += 1;
+        }
+        if end == chars.len() {
+            *pos = start;
+            return Ok(None);
+        }
+        *pos = end + 1;
+        let slop = Self::consume_number(chars, pos).unwrap_or(0);
+
+        self.field_queries(|field| {
+            let terms = self.analyze(field, &text)?;
+            match terms.len() {
+                0 => Ok(None),
+                1 => Ok(Some(Self::term_query(field, terms[0].clone()))),
+                _ => {
+                    let terms = terms
+                        .into_iter()
+                        .map(|term| Term::new(field.to_string(), term))
+                        .collect();
+                    let query = PhraseQuery::build(terms, slop as i32, None, None)?;
+                    Ok(Some(Box::new(query)))
+                }
+            }
+        })
+    }
+
+    fn consume_token<C: Codec>(
+        &self,
+        chars: &[char],
+        pos: &mut usize,
+    ) -> Result<Option<Box<dyn Query<C>>>> {
+        let mut text = String::new();
+        // a trailing `*` makes a prefix query, unless escaped
+        let mut prefix = false;
+        while *pos < chars.len() {
+            let c = chars[*pos];
+            if c.is_whitespace() || "\"|+()~".contains(c) {
+                break;
+            }
+            if c == '\\' {
+                if *pos + 1 < chars.len() {
+                    text.push(chars[*pos + 1]);
+                }
+                prefix = false;
+                *pos += 2;
+                continue;
+            }
+            text.push(c);
+            prefix = c == '*';
+            *pos += 1;
+        }
+        if prefix {
+            text.pop();
+        }
+        let fuzziness = Self::consume_number(chars, pos);
+        if text.is_empty() {
+            if prefix {
+                return Ok(Some(Box::new(MatchAllDocsQuery)));
+            }
+            // a dangling `~` or escape
+            return Ok(None);
+        }
+
+        let matcher = if prefix {
+            MultiTermMatcher::prefix(&text)
+        } else {
+            match fuzziness {
+                Some(edits) if edits > 0 => MultiTermMatcher::fuzzy(
+                    &text,
+                    edits.min(MAX_FUZZY_EDITS),
+                    self.fuzzy_prefix_length,
+                )?,
+                _ => {
+                    return self.field_queries(|field| {
+                        let mut terms = self.analyze(field, &text)?;
+                        let mut queries: Vec<Box<dyn Query<C>>> = terms
+                            .drain(..)
+                            .map(|term| Self::term_query(field, term))
+                            .collect();
+                        match queries.len() {
+                            0 => Ok(None),
+                            1 => Ok(queries.pop()),
+                            _ => Ok(Some(self.combine(queries, self.default_operator)?)),
+                        }
+                    });
+                }
+            }
+        };
+        self.field_queries(|field| {
+            Ok(Some(Box::new(MultiTermQuery::new(
+                field.to_string(),
+                matcher.clone(),
+            ))))
+        })
+    }
+
+    /// Parse the number of a `~N` suffix, a `~` without number being the max edits.
+    fn consume_number(chars: &[char], pos: &mut usize) -> Option<u32> {
+        if *pos >= chars.len() || chars[*pos] != '~' {
+            return None;
+        }
+        *pos += 1;
+        let start = *pos;
+        while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+        let number: String = chars[start..*pos].iter().collect();
+        Some(number.parse().unwrap_or(MAX_FUZZY_EDITS))
+    }
+
+    fn term_query<C: Codec>(field: &str, term: Vec<u8>) -> Box<dyn Query<C>> {
+        Box::new(TermQuery::new(
+            Term::new(field.to_string(), term),
+            1.0,
+            None,
+        ))
+    }
+
+    fn analyze(&self, field: &str, text: &str) -> Result<Vec<Vec<u8>>> {
+        let analyzer = self.analyzers.get(field).unwrap_or(&self.default_analyzer);
+        let mut token_stream = analyzer(text);
+        token_stream.reset()?;
+        let mut terms = vec![];
+        while token_stream.next_token()? {
+            terms.push(token_stream.token().term.clone());
+        }
+        token_stream.end()?;
+        Ok(terms)
+    }
+
+    /// Build the query of a clause for each field with `build`, boosted by the
+    /// field boost.
+    fn field_queries<C: Codec, F>(&self, mut build: F) -> Result<Option<Box<dyn Query<C>>>>
+    where
+        F: FnMut(&str) -> Result<Option<Box<dyn Query<C>>>>,
+    {
+        let mut queries = Vec::with_capacity(self.fields.len());
+        for (field, boost) in &self.fields {
+            if let Some(query) = build(field)? {
+                queries.push(BoostQuery::build(query, *boost));
+            }
+        }
+        match queries.len() {
+            0 => Ok(None),
+            1 => Ok(queries.pop()),
+            _ => Ok(Some(BooleanQuery::build(
+                vec![],
+                queries,
+                vec![],
+                vec![],
+                0,
+            )?)),
+        }
+    }
+}
+
+/// The state of the parsing of a group of clauses.
+struct State<C: Codec> {
+    clauses: Vec<Box<dyn Query<C>>>,
+    // the operator between the clauses
+    previous: Option<Operator>,
+    // the explicit operator before the next clause
+    current: Option<Operator>,
+    negations: usize,
+}
+
+impl<C: Codec> State<C> {
+    fn new() -> Self {
+        State {
+            clauses: vec![],
+            previous: None,
+            current: None,
+            negations: 0,
+        }
+    }
+
+    fn finish(self, builder: &SimpleQueryStringBuilder) -> Result<Option<Box<dyn Query<C>>>> {
+        if self.clauses.is_empty() {
+            return Ok(None);
+        }
+        let operator = self.previous.unwrap_or(builder.default_operator);
+        Ok(Some(builder.combine(self.clauses, operator)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::codec::tests::TestCodec;
+    use core::doc::Fieldable;
+    use core::search::query::tests::{count_hits, index_fields, new_keyword_field};
+    use core::search::DefaultIndexSearcher;
+
+    fn parse(query_string: &str) -> Option<String> {
+        SimpleQueryStringBuilder::new(query_string.to_string(), vec![("title".into(), 1.0)])
+            .build::<TestCodec>()
+            .unwrap()
+            .map(|q| q.to_string())
+    }
+
+    fn term(text: &str) -> String {
+        format!("TermQuery(field: title, term: {}, boost: 1)", text)
+    }
+
+    #[test]
+    fn test_simple_query_string() {
+        assert_eq!(parse("quick"), Some(term("quick")));
+        assert_eq!(
+            parse("quick brown | fox"),
+            Some(format!(
+                "BooleanQuery(must: [], should: [{}, {}, {}], filters: [], must_not: [], match: 1)",
+                term("quick"),
+                term("brown"),
+                term("fox")
+            ))
+        );
+        // a change of operator wraps the previous clauses
+        assert_eq!(
+            parse("quick brown +fox"),
+            Some(format!(
+                "BooleanQuery(must: [BooleanQuery(must: [], should: [{}, {}], filters: [], \
+                 must_not: [], match: 1), {}], should: [], filters: [], must_not: [], match: 0)",
+                term("quick"),
+                term("brown"),
+                term("fox")
+            ))
+        );
+        assert_eq!(
+            parse("quick +(brown | fox)"),
+            Some(format!(
+                "BooleanQuery(must: [{}, BooleanQuery(must: [], should: [{}, {}], filters: [], \
+                 must_not: [], match: 1)], should: [], filters: [], must_not: [], match: 0)",
+                term("quick"),
+                term("brown"),
+                term("fox")
+            ))
+        );
+        assert_eq!(
+            parse("-slow"),
+            Some(format!(
+                "BooleanQuery(must: [MatchAllDocsQuery()], should: [], filters: [], must_not: \
+                 [{}], match: 1)",
+                term("slow")
+            ))
+        );
+        assert_eq!(parse("--slow"), Some(term("slow")));
+        assert_eq!(parse("e-mail"), Some(term("e-mail")));
+
+        let phrase = parse("\"quick brown\"~2").unwrap();
+        assert!(phrase.starts_with("PhraseQuery(field: title"));
+        assert!(phrase.ends_with("slop: 2)"));
+        assert_eq!(parse("\"quick\""), Some(term("quick")));
+        assert_eq!(
+            parse("qui*"),
+            Some("MultiTermQuery(field: title, query: qui*)".into())
+        );
+        assert_eq!(parse(r"qui\*"), Some(term("qui*")));
+        assert_eq!(
+            parse("quikc~1"),
+            Some("MultiTermQuery(field: title, query: quikc~1)".into())
+        );
+        assert_eq!(parse("quick~0"), Some(term("quick")));
+        assert_eq!(parse("*"), Some("MatchAllDocsQuery()".into()));
+    }
+
+    #[test]
+    fn test_malformed_simple_query_string() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("  + | - ~ ( ) \\"), None);
+        assert_eq!(parse("((quick"), Some(term("quick")));
+        assert_eq!(parse("quick))"), Some(term("quick")));
+        assert_eq!(parse("quick +"), Some(term("quick")));
+        assert_eq!(parse("~2 quick"), Some(term("quick")));
+        // deeply nested groups don't overflow the stack
+        let nested = format!("{}quick{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(parse(&nested), Some(term("quick")));
+        assert_eq!(parse(&"(".repeat(100_000)), None);
+        let nested = format!("{}quick) fox", "(".repeat(MAX_GROUP_DEPTH + 2));
+        assert_eq!(
+            parse(&nested),
+            Some(format!(
+                "BooleanQuery(must: [], should: [{}, {}], filters: [], must_not: [], match: 1)",
+                term("quick"),
+                term("fox")
+            ))
+        );
+        // an unterminated phrase is parsed as terms
+        assert_eq!(
+            parse("\"quick fox"),
+            Some(format!(
+                "BooleanQuery(must: [], should: [{}, {}], filters: [], must_not: [], match: 1)",
+                term("quick"),
+                term("fox")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_simple_query_string_fields() {
+        let lowercase: QueryAnalyzer = Arc::new(|text: &str| -> Box<dyn TokenStream> {
+            Box::new(WhitespaceTokenizer::new(Box::new(Cursor::new(
+                text.to_lowercase().into_bytes(),
+            ))))
+        });
+        let query: Box<dyn Query<TestCodec>> = SimpleQueryStringBuilder::new(
+            "Quick".into(),
+            vec![("title".into(), 2.0), ("body".into(), 1.0)],
+        )
+        .with_analyzer("title", lowercase)
+        .build()
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            query.to_string(),
+            "BooleanQuery(must: [], should: [BoostQuery(query: TermQuery(field: title, term: \
+             quick, boost: 1), boost: 2), TermQuery(field: body, term: Quick, boost: 1)], \
+             filters: [], must_not: [], match: 1)"
+        );
+
+        let query: Box<dyn Query<TestCodec>> =
+            SimpleQueryStringBuilder::new("quick brown".into(), vec![("title".into(), 1.0)])
+                .with_default_operator(Operator::And)
+                .build()
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            query.to_string(),
+            format!(
+                "BooleanQuery(must: [{}, {}], should: [], filters: [], must_not: [], match: 0)",
+                term("quick"),
+                term("brown")
+            )
+        );
+    }
+
+    #[test]
+    fn test_multi_term_query_without_positions() {
+        // a keyword field indexed without positions, with more terms than the span
+        // queries expand to
+        let mut docs = vec![];
+        for i in 0..1200 {
+            let text = format!("foo{:04}", i);
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(new_keyword_field("tag", &text))];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query_string: &str| -> usize {
+            let query: Box<dyn Query<_>> =
+                SimpleQueryStringBuilder::new(query_string.into(), vec![("tag".into(), 1.0)])
+                    .build()
+                    .unwrap()
+                    .unwrap();
+            count_hits(&searcher, query.as_ref())
+        };
+
+        assert_eq!(count("foo*"), 1200);
+        assert_eq!(count("foo1*"), 200);
+        assert_eq!(count("foo0000~1"), 29);
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::search::query::spans::{SpanQuery, SpanQueryEnum, SpanWeightEnum};
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::searcher::SearchPlanBuilder;
+use core::util::KeyedContext;
+
+use error::Result;
+
+use std::fmt;
+
+/// Wrapper to allow `SpanQuery` objects participate in composite
+/// single-field SpanQueries by 'lying' about their search field.
+/// That is, the masked SpanQuery will function as normal,
+/// but `SpanQuery::field()` simply hands back the value supplied
+/// in this class's constructor.
+///
+/// This can be used to support Queries like `SpanNearQuery` or
+/// `SpanOrQuery` across different fields, which is not ordinarily
+/// permitted.
+///
+/// This can be useful for denormalized relational data: for example, when
+/// indexing a document with conceptually many 'children':
+///
+/// ```text
+///  teacherid: 1
+///  studentfirstname: james
+///  studentsurname: jones
+///
+///  teacherid: 2
+///  studenfirstname: james
+///  studentsurname: smith
+///  studentfirstname: sally
+///  studentsurname: jones
+/// ```
+///
+/// a SpanNearQuery with a slop of 0 can be applied across two
+/// `SpanTermQuery` objects as follows:
+///
+/// ```rust,ignore
+/// let q1 = SpanTermQuery::new(Term::new("studentfirstname".into(), b"james".to_vec()), None);
+/// let q2 = SpanTermQuery::new(Term::new("studentsurname".into(), b"jones".to_vec()), None);
+/// let q2m = FieldMaskingSpanQuery::new(SpanQueryEnum::Term(q2), "studentfirstname".into());
+/// let q = SpanNearQuery::new(
+///     vec![SpanQueryEnum::Term(q1), SpanQueryEnum::FieldMasking(q2m)], -1, false)?;
+/// ```
+///
+/// to search for 'studentfirstname:james studentsurname:jones' and find
+/// teacherid 1 without matching teacherid 2 (which has a 'james' in position 0
+/// and 'jones' in position 1).
+///
+/// Note: as `field()` returns the masked field, scoring will be
+/// done using the Similarity and collection statistics of the field name supplied,
+/// but with the term statistics of the real field. This may lead to exceptions,
+/// poor performance, and unexpected scoring behaviour.
+pub struct FieldMaskingSpanQuery {
+    masked_query: Box<SpanQueryEnum>,
+    field: String,
+}
+
+impl FieldMaskingSpanQuery {
+    pub fn new(masked_query: SpanQueryEnum, masked_field: String) -> Self {
+        FieldMaskingSpanQuery {
+            masked_query: Box::new(masked_query),
+            field: masked_field,
+        }
+    }
+
+    pub fn masked_query(&self) -> &SpanQueryEnum {
+        &self.masked_query
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for FieldMaskingSpanQuery {
+    type Weight = SpanWeightEnum<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.masked_query.span_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        &self.field
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.masked_query.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for FieldMaskingSpanQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        self.masked_query.create_weight(searcher, needs_scores)
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.masked_query.as_ref())
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for FieldMaskingSpanQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FieldMaskingSpanQuery(query: {}, field: {})",
+            &self.masked_query, &self.field
+        )
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::PostingIterator;
+use core::search::query::spans::{SpanCollector, Spans, SpansEnum, NO_MORE_POSITIONS};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::util::DocId;
+
+use error::Result;
+
+/// Status returned from `SpanFilter::accept` that indicates whether a candidate
+/// match should be accepted, rejected, or rejected and move on to the next document.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AcceptStatus {
+    /// Indicates the match should be accepted
+    Yes,
+    /// Indicates the match should be rejected
+    No,
+    /// Indicates the match should be rejected, and the enumeration may continue
+    /// with the next document.
+    NoMoreInCurrentDoc,
+}
+
+/// Decides whether the current position of a `Spans` is a match for a `FilterSpans`.
+pub trait SpanFilter<P: PostingIterator>: Send {
+    /// Returns YES if the candidate should be an accepted match,
+    /// NO if it should not, and NO_MORE_IN_CURRENT_DOC if iteration
+    /// should move on to the next document.
+    fn accept(&mut self, candidate: &SpansEnum<P>) -> Result<AcceptStatus>;
+}
+
+/// A `Spans` implementation wrapping another spans instance,
+/// allowing to filter spans matches easily by implementing `SpanFilter`
+pub struct FilterSpans<P: PostingIterator, F: SpanFilter<P>> {
+    in_spans: SpansEnum<P>,
+    filter: F,
+    at_first_in_current_doc: bool,
+    start_pos: i32,
+}
+
+impl<P: PostingIterator, F: SpanFilter<P>> FilterSpans<P, F> {
+    pub fn new(in_spans: SpansEnum<P>, filter: F) -> Self {
+        FilterSpans {
+            in_spans,
+            filter,
+            at_first_in_current_doc: false,
+            start_pos: -1,
+        }
+    }
+
+    /// Returns true if the current document matches.
+    ///
+    /// This is called during two-phase processing.
+    fn two_phase_current_doc_matches(&mut self) -> Result<bool> {
+        self.at_first_in_current_doc = false;
+        self.start_pos = self.in_spans.next_start_position()?;
+        debug_assert_ne!(self.start_pos, NO_MORE_POSITIONS);
+        loop {
+            match self.filter.accept(&self.in_spans)? {
+                AcceptStatus::Yes => {
+                    self.at_first_in_current_doc = true;
+                    return Ok(true);
+                }
+                AcceptStatus::No => {
+                    self.start_pos = self.in_spans.next_start_position()?;
+                    if self.start_pos == NO_MORE_POSITIONS {
+                        break;
+                    }
+                }
+                AcceptStatus::NoMoreInCurrentDoc => {
+                    break;
+                }
+            }
+        }
+        self.start_pos = -1;
+        Ok(false)
+    }
+}
+
+impl<P: PostingIterator, F: SpanFilter<P>> Spans for FilterSpans<P, F> {
+    fn next_start_position(&mut self) -> Result<i32> {
+        if self.at_first_in_current_doc {
+            self.at_first_in_current_doc = false;
+            return Ok(self.start_pos);
+        }
+
+        loop {
+            self.start_pos = self.in_spans.next_start_position()?;
+            if self.start_pos == NO_MORE_POSITIONS {
+                return Ok(NO_MORE_POSITIONS);
+            }
+            match self.filter.accept(&self.in_spans)? {
+                AcceptStatus::Yes => {
+                    return Ok(self.start_pos);
+                }
+                AcceptStatus::No => {}
+                AcceptStatus::NoMoreInCurrentDoc => {
+                    self.start_pos = NO_MORE_POSITIONS;
+                    return Ok(NO_MORE_POSITIONS);
+                }
+            }
+        }
+    }
+
+    fn start_position(&self) -> i32 {
+        if self.at_first_in_current_doc {
+            -1
+        } else {
+            self.start_pos
+        }
+    }
+
+    fn end_position(&self) -> i32 {
+        if self.at_first_in_current_doc {
+            -1
+        } else if self.start_pos != NO_MORE_POSITIONS {
+            self.in_spans.end_position()
+        } else {
+            NO_MORE_POSITIONS
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.in_spans.width()
+    }
+
+    fn collect(&mut self, collector: &mut impl SpanCollector) -> Result<()> {
+        self.in_spans.collect(collector)
+    }
+
+    fn positions_cost(&self) -> f32 {
+        self.in_spans.positions_cost()
+    }
+}
+
+impl<P: PostingIterator, F: SpanFilter<P>> DocIterator for FilterSpans<P, F> {
+    fn doc_id(&self) -> DocId {
+        self.in_spans.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        loop {
+            let doc = self.in_spans.next()?;
+            if doc == NO_MORE_DOCS {
+                return Ok(NO_MORE_DOCS);
+            }
+            if self.two_phase_current_doc_matches()? {
+                return Ok(doc);
+            }
+        }
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        let mut doc = self.in_spans.advance(target)?;
+        while doc != NO_MORE_DOCS {
+            if self.two_phase_current_doc_matches()? {
+                break;
+            }
+            doc = self.in_spans.next()?;
+        }
+        Ok(doc)
+    }
+
+    fn cost(&self) -> usize {
+        self.in_spans.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        if self.in_spans.support_two_phase() && !self.in_spans.matches()? {
+            return Ok(false);
+        }
+        self.two_phase_current_doc_matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        if self.in_spans.support_two_phase() {
+            self.in_spans.match_cost()
+        } else {
+            // overestimate
+            self.in_spans.positions_cost()
+        }
+    }
+
+    fn support_two_phase(&self) -> bool {
+        true
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.in_spans.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.in_spans.approximate_advance(target)
+    }
+}
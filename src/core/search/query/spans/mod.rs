@@ -16,6 +16,14 @@ mod span;
 
 pub use self::span::*;
 
+mod filter_spans;
+
+pub use self::filter_spans::*;
+
+mod field_masking_span;
+
+pub use self::field_masking_span::*;
+
 mod span_boost;
 
 pub use self::span_boost::*;
@@ -24,10 +32,26 @@ mod span_near;
 
 pub use self::span_near::*;
 
+mod span_contain;
+
+pub use self::span_contain::*;
+
+mod span_multi;
+
+pub use self::span_multi::*;
+
+mod span_not;
+
+pub use self::span_not::*;
+
 mod span_or;
 
 pub use self::span_or::*;
 
+mod span_position_range;
+
+pub use self::span_position_range::*;
+
 mod span_term;
 
 pub use self::span_term::*;
@@ -16,12 +16,19 @@ use core::codec::{PostingIterator, PostingIteratorFlags};
 use core::doc::Term;
 use core::index::reader::{LeafReaderContext, SearchLeafReader};
 use core::search::explanation::Explanation;
+use core::search::query::spans::{ContainSpans, SpanContainWeight, SpanContainingQuery};
+use core::search::query::spans::{FieldMaskingSpanQuery, SpanWithinQuery};
 use core::search::query::spans::{
     GapSpans, NearSpansOrdered, NearSpansUnordered, SpanGapQuery, SpanGapWeight, SpanNearQuery,
     SpanNearWeight,
 };
 use core::search::query::spans::{SpanBoostQuery, SpanBoostWeight, SpanBoostWeightEnum};
+use core::search::query::spans::{SpanMultiTermQueryWrapper, SpanMultiTermWeight};
+use core::search::query::spans::{SpanNotQuery, SpanNotSpans, SpanNotWeight};
 use core::search::query::spans::{SpanOrQuery, SpanOrSpans, SpanOrWeight};
+use core::search::query::spans::{
+    SpanPositionRangeQuery, SpanPositionRangeSpans, SpanPositionRangeWeight,
+};
 use core::search::query::spans::{SpanTermQuery, SpanTermWeight, TermSpans};
 use core::search::query::{Query, TermQuery, Weight};
 use core::search::scorer::{ConjunctionScorer, Scorer};
@@ -67,6 +74,12 @@ pub enum SpanQueryEnum {
     Or(SpanOrQuery),
     Near(SpanNearQuery),
     Boost(SpanBoostQuery),
+    Not(SpanNotQuery),
+    PositionRange(SpanPositionRangeQuery),
+    MultiTerm(SpanMultiTermQueryWrapper),
+    Containing(SpanContainingQuery),
+    Within(SpanWithinQuery),
+    FieldMasking(FieldMaskingSpanQuery),
 }
 
 impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
@@ -82,6 +95,20 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanWeightEnum::Gap(q.span_weight(searcher, needs_scores)?),
             SpanQueryEnum::Or(q) => SpanWeightEnum::Or(q.span_weight(searcher, needs_scores)?),
             SpanQueryEnum::Near(q) => SpanWeightEnum::Near(q.span_weight(searcher, needs_scores)?),
+            SpanQueryEnum::Not(q) => SpanWeightEnum::Not(q.span_weight(searcher, needs_scores)?),
+            SpanQueryEnum::PositionRange(q) => {
+                SpanWeightEnum::PositionRange(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanQueryEnum::MultiTerm(q) => {
+                SpanWeightEnum::MultiTerm(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanQueryEnum::Containing(q) => {
+                SpanWeightEnum::Contain(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanQueryEnum::Within(q) => {
+                SpanWeightEnum::Contain(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanQueryEnum::FieldMasking(q) => q.span_weight(searcher, needs_scores)?,
             SpanQueryEnum::Boost(q) => q.span_weight(searcher, needs_scores)?,
         };
         Ok(weight)
@@ -93,6 +120,12 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Or(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Near(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::Not(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::PositionRange(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::MultiTerm(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::Containing(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::Within(q) => SpanQuery::<C>::field(q),
+            SpanQueryEnum::FieldMasking(q) => SpanQuery::<C>::field(q),
             SpanQueryEnum::Boost(q) => SpanQuery::<C>::field(q),
         }
     }
@@ -103,6 +136,12 @@ impl<C: Codec> SpanQuery<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Or(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Near(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::Not(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::PositionRange(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::MultiTerm(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::Containing(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::Within(q) => SpanQuery::<C>::ctx(q),
+            SpanQueryEnum::FieldMasking(q) => SpanQuery::<C>::ctx(q),
             SpanQueryEnum::Boost(q) => SpanQuery::<C>::ctx(q),
         }
     }
@@ -119,6 +158,12 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Or(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Near(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::Not(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::PositionRange(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::MultiTerm(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::Containing(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::Within(q) => q.create_weight(searcher, needs_scores),
+            SpanQueryEnum::FieldMasking(q) => q.create_weight(searcher, needs_scores),
             SpanQueryEnum::Boost(q) => q.create_weight(searcher, needs_scores),
         }
     }
@@ -129,6 +174,12 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Or(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Near(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::Not(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::PositionRange(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::MultiTerm(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::Containing(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::Within(q) => Query::<C>::extract_terms(q),
+            SpanQueryEnum::FieldMasking(q) => Query::<C>::extract_terms(q),
             SpanQueryEnum::Boost(q) => Query::<C>::extract_terms(q),
         }
     }
@@ -139,6 +190,12 @@ impl<C: Codec> Query<C> for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Or(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Near(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::Not(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::PositionRange(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::MultiTerm(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::Containing(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::Within(q) => Query::<C>::as_any(q),
+            SpanQueryEnum::FieldMasking(q) => Query::<C>::as_any(q),
             SpanQueryEnum::Boost(q) => Query::<C>::as_any(q),
         }
     }
@@ -151,6 +208,12 @@ impl fmt::Display for SpanQueryEnum {
             SpanQueryEnum::Gap(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Or(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Near(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::Not(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::PositionRange(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::MultiTerm(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::Containing(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::Within(q) => write!(f, "SpanQueryEnum({})", q),
+            SpanQueryEnum::FieldMasking(q) => write!(f, "SpanQueryEnum({})", q),
             SpanQueryEnum::Boost(q) => write!(f, "SpanQueryEnum({})", q),
         }
     }
@@ -225,6 +288,9 @@ pub enum SpansEnum<P: PostingIterator> {
     NearUnordered(Box<NearSpansUnordered<P>>),
     Or(SpanOrSpans<P>),
     Term(TermSpans<P>),
+    Not(Box<SpanNotSpans<P>>),
+    PositionRange(Box<SpanPositionRangeSpans<P>>),
+    Contain(Box<ContainSpans<P>>),
 }
 
 impl<P: PostingIterator> Spans for SpansEnum<P> {
@@ -234,6 +300,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.next_start_position(),
             SpansEnum::NearUnordered(s) => s.next_start_position(),
             SpansEnum::Or(s) => s.next_start_position(),
+            SpansEnum::Not(s) => s.next_start_position(),
+            SpansEnum::PositionRange(s) => s.next_start_position(),
+            SpansEnum::Contain(s) => s.next_start_position(),
             SpansEnum::Term(s) => s.next_start_position(),
         }
     }
@@ -244,6 +313,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.start_position(),
             SpansEnum::NearUnordered(s) => s.start_position(),
             SpansEnum::Or(s) => s.start_position(),
+            SpansEnum::Not(s) => s.start_position(),
+            SpansEnum::PositionRange(s) => s.start_position(),
+            SpansEnum::Contain(s) => s.start_position(),
             SpansEnum::Term(s) => s.start_position(),
         }
     }
@@ -254,6 +326,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.end_position(),
             SpansEnum::NearUnordered(s) => s.end_position(),
             SpansEnum::Or(s) => s.end_position(),
+            SpansEnum::Not(s) => s.end_position(),
+            SpansEnum::PositionRange(s) => s.end_position(),
+            SpansEnum::Contain(s) => s.end_position(),
             SpansEnum::Term(s) => s.end_position(),
         }
     }
@@ -264,6 +339,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.width(),
             SpansEnum::NearUnordered(s) => s.width(),
             SpansEnum::Or(s) => s.width(),
+            SpansEnum::Not(s) => s.width(),
+            SpansEnum::PositionRange(s) => s.width(),
+            SpansEnum::Contain(s) => s.width(),
             SpansEnum::Term(s) => s.width(),
         }
     }
@@ -274,6 +352,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.collect(collector),
             SpansEnum::NearUnordered(s) => s.collect(collector),
             SpansEnum::Or(s) => s.collect(collector),
+            SpansEnum::Not(s) => s.collect(collector),
+            SpansEnum::PositionRange(s) => s.collect(collector),
+            SpansEnum::Contain(s) => s.collect(collector),
             SpansEnum::Term(s) => s.collect(collector),
         }
     }
@@ -284,6 +365,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.positions_cost(),
             SpansEnum::NearUnordered(s) => s.positions_cost(),
             SpansEnum::Or(s) => s.positions_cost(),
+            SpansEnum::Not(s) => s.positions_cost(),
+            SpansEnum::PositionRange(s) => s.positions_cost(),
+            SpansEnum::Contain(s) => s.positions_cost(),
             SpansEnum::Term(s) => s.positions_cost(),
         }
     }
@@ -294,6 +378,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.do_start_current_doc(),
             SpansEnum::NearUnordered(s) => s.do_start_current_doc(),
             SpansEnum::Or(s) => s.do_start_current_doc(),
+            SpansEnum::Not(s) => s.do_start_current_doc(),
+            SpansEnum::PositionRange(s) => s.do_start_current_doc(),
+            SpansEnum::Contain(s) => s.do_start_current_doc(),
             SpansEnum::Term(s) => s.do_start_current_doc(),
         }
     }
@@ -304,6 +391,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.do_current_spans(),
             SpansEnum::NearUnordered(s) => s.do_current_spans(),
             SpansEnum::Or(s) => s.do_current_spans(),
+            SpansEnum::Not(s) => s.do_current_spans(),
+            SpansEnum::PositionRange(s) => s.do_current_spans(),
+            SpansEnum::Contain(s) => s.do_current_spans(),
             SpansEnum::Term(s) => s.do_current_spans(),
         }
     }
@@ -314,6 +404,9 @@ impl<P: PostingIterator> Spans for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.advance_position(position),
             SpansEnum::NearUnordered(s) => s.advance_position(position),
             SpansEnum::Or(s) => s.advance_position(position),
+            SpansEnum::Not(s) => s.advance_position(position),
+            SpansEnum::PositionRange(s) => s.advance_position(position),
+            SpansEnum::Contain(s) => s.advance_position(position),
             SpansEnum::Term(s) => s.advance_position(position),
         }
     }
@@ -326,6 +419,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.doc_id(),
             SpansEnum::NearUnordered(s) => s.doc_id(),
             SpansEnum::Or(s) => s.doc_id(),
+            SpansEnum::Not(s) => s.doc_id(),
+            SpansEnum::PositionRange(s) => s.doc_id(),
+            SpansEnum::Contain(s) => s.doc_id(),
             SpansEnum::Term(s) => s.doc_id(),
         }
     }
@@ -336,6 +432,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.next(),
             SpansEnum::NearUnordered(s) => s.next(),
             SpansEnum::Or(s) => s.next(),
+            SpansEnum::Not(s) => s.next(),
+            SpansEnum::PositionRange(s) => s.next(),
+            SpansEnum::Contain(s) => s.next(),
             SpansEnum::Term(s) => s.next(),
         }
     }
@@ -346,6 +445,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.advance(target),
             SpansEnum::NearUnordered(s) => s.advance(target),
             SpansEnum::Or(s) => s.advance(target),
+            SpansEnum::Not(s) => s.advance(target),
+            SpansEnum::PositionRange(s) => s.advance(target),
+            SpansEnum::Contain(s) => s.advance(target),
             SpansEnum::Term(s) => s.advance(target),
         }
     }
@@ -356,6 +458,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.slow_advance(target),
             SpansEnum::NearUnordered(s) => s.slow_advance(target),
             SpansEnum::Or(s) => s.slow_advance(target),
+            SpansEnum::Not(s) => s.slow_advance(target),
+            SpansEnum::PositionRange(s) => s.slow_advance(target),
+            SpansEnum::Contain(s) => s.slow_advance(target),
             SpansEnum::Term(s) => s.slow_advance(target),
         }
     }
@@ -366,6 +471,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.cost(),
             SpansEnum::NearUnordered(s) => s.cost(),
             SpansEnum::Or(s) => s.cost(),
+            SpansEnum::Not(s) => s.cost(),
+            SpansEnum::PositionRange(s) => s.cost(),
+            SpansEnum::Contain(s) => s.cost(),
             SpansEnum::Term(s) => s.cost(),
         }
     }
@@ -376,6 +484,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.matches(),
             SpansEnum::NearUnordered(s) => s.matches(),
             SpansEnum::Or(s) => s.matches(),
+            SpansEnum::Not(s) => s.matches(),
+            SpansEnum::PositionRange(s) => s.matches(),
+            SpansEnum::Contain(s) => s.matches(),
             SpansEnum::Term(s) => s.matches(),
         }
     }
@@ -386,6 +497,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.match_cost(),
             SpansEnum::NearUnordered(s) => s.match_cost(),
             SpansEnum::Or(s) => s.match_cost(),
+            SpansEnum::Not(s) => s.match_cost(),
+            SpansEnum::PositionRange(s) => s.match_cost(),
+            SpansEnum::Contain(s) => s.match_cost(),
             SpansEnum::Term(s) => s.match_cost(),
         }
     }
@@ -396,6 +510,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.support_two_phase(),
             SpansEnum::NearUnordered(s) => s.support_two_phase(),
             SpansEnum::Or(s) => s.support_two_phase(),
+            SpansEnum::Not(s) => s.support_two_phase(),
+            SpansEnum::PositionRange(s) => s.support_two_phase(),
+            SpansEnum::Contain(s) => s.support_two_phase(),
             SpansEnum::Term(s) => s.support_two_phase(),
         }
     }
@@ -406,6 +523,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.approximate_next(),
             SpansEnum::NearUnordered(s) => s.approximate_next(),
             SpansEnum::Or(s) => s.approximate_next(),
+            SpansEnum::Not(s) => s.approximate_next(),
+            SpansEnum::PositionRange(s) => s.approximate_next(),
+            SpansEnum::Contain(s) => s.approximate_next(),
             SpansEnum::Term(s) => s.approximate_next(),
         }
     }
@@ -416,6 +536,9 @@ impl<P: PostingIterator> DocIterator for SpansEnum<P> {
             SpansEnum::NearOrdered(s) => s.approximate_advance(target),
             SpansEnum::NearUnordered(s) => s.approximate_advance(target),
             SpansEnum::Or(s) => s.approximate_advance(target),
+            SpansEnum::Not(s) => s.approximate_advance(target),
+            SpansEnum::PositionRange(s) => s.approximate_advance(target),
+            SpansEnum::Contain(s) => s.approximate_advance(target),
             SpansEnum::Term(s) => s.approximate_advance(target),
         }
     }
@@ -518,11 +641,15 @@ impl<S: Spans> SpanScorer<S> {
         Ok(())
     }
 
+    /// The spans without any term statistics, e.g. only made of multi term
+    /// clauses, have a constant score of 1.
     fn score_current_doc(&mut self) -> Result<f32> {
-        debug_assert!(self.doc_scorer.is_some());
         let doc = self.doc_id();
         let freq = self.freq;
-        self.doc_scorer.as_mut().unwrap().score(doc, freq)
+        match self.doc_scorer {
+            Some(ref mut doc_scorer) => doc_scorer.score(doc, freq),
+            None => Ok(1.0),
+        }
     }
 }
 
@@ -668,7 +795,17 @@ pub trait SpanWeight<C: Codec>: Weight<C> {
                         format!("weight({} in {}), result of:", self, doc),
                         vec![score_expl],
                     ));
-                };
+                } else {
+                    return Ok(Explanation::new(
+                        true,
+                        1.0f32,
+                        format!(
+                            "weight({} in {}), constant score without term statistics",
+                            self, doc
+                        ),
+                        vec![],
+                    ));
+                }
             }
         }
 
@@ -687,6 +824,10 @@ pub enum SpanWeightEnum<C: Codec> {
     Boost(SpanBoostWeight<C>),
     Near(SpanNearWeight<C>),
     Or(SpanOrWeight<C>),
+    Not(SpanNotWeight<C>),
+    PositionRange(SpanPositionRangeWeight<C>),
+    MultiTerm(SpanMultiTermWeight<C>),
+    Contain(SpanContainWeight<C>),
 }
 
 impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
@@ -696,6 +837,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_weight(),
             SpanWeightEnum::Or(w) => w.sim_weight(),
             SpanWeightEnum::Near(w) => w.sim_weight(),
+            SpanWeightEnum::Not(w) => w.sim_weight(),
+            SpanWeightEnum::PositionRange(w) => w.sim_weight(),
+            SpanWeightEnum::MultiTerm(w) => w.sim_weight(),
+            SpanWeightEnum::Contain(w) => w.sim_weight(),
             SpanWeightEnum::Boost(w) => w.sim_weight(),
         }
     }
@@ -706,6 +851,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_weight_mut(),
             SpanWeightEnum::Or(w) => w.sim_weight_mut(),
             SpanWeightEnum::Near(w) => w.sim_weight_mut(),
+            SpanWeightEnum::Not(w) => w.sim_weight_mut(),
+            SpanWeightEnum::PositionRange(w) => w.sim_weight_mut(),
+            SpanWeightEnum::MultiTerm(w) => w.sim_weight_mut(),
+            SpanWeightEnum::Contain(w) => w.sim_weight_mut(),
             SpanWeightEnum::Boost(w) => w.sim_weight_mut(),
         }
     }
@@ -720,6 +869,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Or(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Near(w) => w.get_spans(reader, required_postings),
+            SpanWeightEnum::Not(w) => w.get_spans(reader, required_postings),
+            SpanWeightEnum::PositionRange(w) => w.get_spans(reader, required_postings),
+            SpanWeightEnum::MultiTerm(w) => w.get_spans(reader, required_postings),
+            SpanWeightEnum::Contain(w) => w.get_spans(reader, required_postings),
             SpanWeightEnum::Boost(w) => w.get_spans(reader, required_postings),
         }
     }
@@ -730,6 +883,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.extract_term_keys(terms),
             SpanWeightEnum::Or(w) => w.extract_term_keys(terms),
             SpanWeightEnum::Near(w) => w.extract_term_keys(terms),
+            SpanWeightEnum::Not(w) => w.extract_term_keys(terms),
+            SpanWeightEnum::PositionRange(w) => w.extract_term_keys(terms),
+            SpanWeightEnum::MultiTerm(w) => w.extract_term_keys(terms),
+            SpanWeightEnum::Contain(w) => w.extract_term_keys(terms),
             SpanWeightEnum::Boost(w) => w.extract_term_keys(terms),
         }
     }
@@ -740,6 +897,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Or(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Near(w) => w.do_create_scorer(ctx),
+            SpanWeightEnum::Not(w) => w.do_create_scorer(ctx),
+            SpanWeightEnum::PositionRange(w) => w.do_create_scorer(ctx),
+            SpanWeightEnum::MultiTerm(w) => w.do_create_scorer(ctx),
+            SpanWeightEnum::Contain(w) => w.do_create_scorer(ctx),
             SpanWeightEnum::Boost(w) => w.do_create_scorer(ctx),
         }
     }
@@ -750,6 +911,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Or(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Near(w) => w.do_value_for_normalization(),
+            SpanWeightEnum::Not(w) => w.do_value_for_normalization(),
+            SpanWeightEnum::PositionRange(w) => w.do_value_for_normalization(),
+            SpanWeightEnum::MultiTerm(w) => w.do_value_for_normalization(),
+            SpanWeightEnum::Contain(w) => w.do_value_for_normalization(),
             SpanWeightEnum::Boost(w) => w.do_value_for_normalization(),
         }
     }
@@ -760,6 +925,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Or(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Near(w) => w.do_normalize(query_norm, boost),
+            SpanWeightEnum::Not(w) => w.do_normalize(query_norm, boost),
+            SpanWeightEnum::PositionRange(w) => w.do_normalize(query_norm, boost),
+            SpanWeightEnum::MultiTerm(w) => w.do_normalize(query_norm, boost),
+            SpanWeightEnum::Contain(w) => w.do_normalize(query_norm, boost),
             SpanWeightEnum::Boost(w) => w.do_normalize(query_norm, boost),
         }
     }
@@ -770,6 +939,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.sim_scorer(reader),
             SpanWeightEnum::Or(w) => w.sim_scorer(reader),
             SpanWeightEnum::Near(w) => w.sim_scorer(reader),
+            SpanWeightEnum::Not(w) => w.sim_scorer(reader),
+            SpanWeightEnum::PositionRange(w) => w.sim_scorer(reader),
+            SpanWeightEnum::MultiTerm(w) => w.sim_scorer(reader),
+            SpanWeightEnum::Contain(w) => w.sim_scorer(reader),
             SpanWeightEnum::Boost(w) => w.sim_scorer(reader),
         }
     }
@@ -780,6 +953,10 @@ impl<C: Codec> SpanWeight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Or(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Near(w) => w.explain_span(reader, doc),
+            SpanWeightEnum::Not(w) => w.explain_span(reader, doc),
+            SpanWeightEnum::PositionRange(w) => w.explain_span(reader, doc),
+            SpanWeightEnum::MultiTerm(w) => w.explain_span(reader, doc),
+            SpanWeightEnum::Contain(w) => w.explain_span(reader, doc),
             SpanWeightEnum::Boost(w) => w.explain_span(reader, doc),
         }
     }
@@ -795,6 +972,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Or(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Near(w) => w.create_scorer(leaf_reader),
+            SpanWeightEnum::Not(w) => w.create_scorer(leaf_reader),
+            SpanWeightEnum::PositionRange(w) => w.create_scorer(leaf_reader),
+            SpanWeightEnum::MultiTerm(w) => w.create_scorer(leaf_reader),
+            SpanWeightEnum::Contain(w) => w.create_scorer(leaf_reader),
             SpanWeightEnum::Boost(w) => w.create_scorer(leaf_reader),
         }
     }
@@ -805,6 +986,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.hash_code(),
             SpanWeightEnum::Or(w) => w.hash_code(),
             SpanWeightEnum::Near(w) => w.hash_code(),
+            SpanWeightEnum::Not(w) => w.hash_code(),
+            SpanWeightEnum::PositionRange(w) => w.hash_code(),
+            SpanWeightEnum::MultiTerm(w) => w.hash_code(),
+            SpanWeightEnum::Contain(w) => w.hash_code(),
             SpanWeightEnum::Boost(w) => w.hash_code(),
         }
     }
@@ -819,6 +1004,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.actual_query_type(),
             SpanWeightEnum::Or(w) => w.actual_query_type(),
             SpanWeightEnum::Near(w) => w.actual_query_type(),
+            SpanWeightEnum::Not(w) => w.actual_query_type(),
+            SpanWeightEnum::PositionRange(w) => w.actual_query_type(),
+            SpanWeightEnum::MultiTerm(w) => w.actual_query_type(),
+            SpanWeightEnum::Contain(w) => w.actual_query_type(),
             SpanWeightEnum::Boost(w) => w.actual_query_type(),
         }
     }
@@ -829,6 +1018,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.normalize(norm, boost),
             SpanWeightEnum::Or(w) => w.normalize(norm, boost),
             SpanWeightEnum::Near(w) => w.normalize(norm, boost),
+            SpanWeightEnum::Not(w) => w.normalize(norm, boost),
+            SpanWeightEnum::PositionRange(w) => w.normalize(norm, boost),
+            SpanWeightEnum::MultiTerm(w) => w.normalize(norm, boost),
+            SpanWeightEnum::Contain(w) => w.normalize(norm, boost),
             SpanWeightEnum::Boost(w) => w.normalize(norm, boost),
         }
     }
@@ -839,6 +1032,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.value_for_normalization(),
             SpanWeightEnum::Or(w) => w.value_for_normalization(),
             SpanWeightEnum::Near(w) => w.value_for_normalization(),
+            SpanWeightEnum::Not(w) => w.value_for_normalization(),
+            SpanWeightEnum::PositionRange(w) => w.value_for_normalization(),
+            SpanWeightEnum::MultiTerm(w) => w.value_for_normalization(),
+            SpanWeightEnum::Contain(w) => w.value_for_normalization(),
             SpanWeightEnum::Boost(w) => w.value_for_normalization(),
         }
     }
@@ -849,6 +1046,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.needs_scores(),
             SpanWeightEnum::Or(w) => w.needs_scores(),
             SpanWeightEnum::Near(w) => w.needs_scores(),
+            SpanWeightEnum::Not(w) => w.needs_scores(),
+            SpanWeightEnum::PositionRange(w) => w.needs_scores(),
+            SpanWeightEnum::MultiTerm(w) => w.needs_scores(),
+            SpanWeightEnum::Contain(w) => w.needs_scores(),
             SpanWeightEnum::Boost(w) => w.needs_scores(),
         }
     }
@@ -859,6 +1060,10 @@ impl<C: Codec> Weight<C> for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => w.explain(reader, doc),
             SpanWeightEnum::Or(w) => w.explain(reader, doc),
             SpanWeightEnum::Near(w) => w.explain(reader, doc),
+            SpanWeightEnum::Not(w) => w.explain(reader, doc),
+            SpanWeightEnum::PositionRange(w) => w.explain(reader, doc),
+            SpanWeightEnum::MultiTerm(w) => w.explain(reader, doc),
+            SpanWeightEnum::Contain(w) => w.explain(reader, doc),
             SpanWeightEnum::Boost(w) => w.explain(reader, doc),
         }
     }
@@ -871,6 +1076,10 @@ impl<C: Codec> fmt::Display for SpanWeightEnum<C> {
             SpanWeightEnum::Gap(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Or(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Near(w) => write!(f, "SpanWeightEnum({})", w),
+            SpanWeightEnum::Not(w) => write!(f, "SpanWeightEnum({})", w),
+            SpanWeightEnum::PositionRange(w) => write!(f, "SpanWeightEnum({})", w),
+            SpanWeightEnum::MultiTerm(w) => write!(f, "SpanWeightEnum({})", w),
+            SpanWeightEnum::Contain(w) => write!(f, "SpanWeightEnum({})", w),
             SpanWeightEnum::Boost(w) => write!(f, "SpanWeightEnum({})", w),
         }
     }
@@ -883,6 +1092,10 @@ impl<C: Codec> From<SpanBoostWeightEnum<C>> for SpanWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => SpanWeightEnum::Gap(w),
             SpanBoostWeightEnum::Or(w) => SpanWeightEnum::Or(w),
             SpanBoostWeightEnum::Near(w) => SpanWeightEnum::Near(w),
+            SpanBoostWeightEnum::Not(w) => SpanWeightEnum::Not(w),
+            SpanBoostWeightEnum::PositionRange(w) => SpanWeightEnum::PositionRange(w),
+            SpanBoostWeightEnum::MultiTerm(w) => SpanWeightEnum::MultiTerm(w),
+            SpanBoostWeightEnum::Contain(w) => SpanWeightEnum::Contain(w),
         }
     }
 }
@@ -1019,7 +1232,12 @@ pub trait ConjunctionSpans<P: PostingIterator>: Spans {
             if self.two_phase_current_doc_matches()? {
                 return Ok(self.doc_id());
             }
-            if self.conjunction_span_base_mut().conjunction.next()? == NO_MORE_DOCS {
+            if self
+                .conjunction_span_base_mut()
+                .conjunction
+                .approximate_next()?
+                == NO_MORE_DOCS
+            {
                 return Ok(NO_MORE_DOCS);
             }
         }
@@ -1038,7 +1256,7 @@ macro_rules! conjunction_span_doc_iter {
             }
 
             fn next(&mut self) -> Result<i32> {
-                let next = self.conjunction_span_base_mut().conjunction.next()?;
+                let next = self.conjunction_span_base_mut().conjunction.approximate_next()?;
                 if next == NO_MORE_DOCS {
                     Ok(NO_MORE_DOCS)
                 } else {
@@ -1050,7 +1268,7 @@ macro_rules! conjunction_span_doc_iter {
                 let next = self
                     .conjunction_span_base_mut()
                     .conjunction
-                    .advance(target)?;
+                    .approximate_advance(target)?;
                 if next == NO_MORE_DOCS {
                     Ok(NO_MORE_DOCS)
                 } else {
@@ -1081,11 +1299,11 @@ macro_rules! conjunction_span_doc_iter {
             }
 
             fn approximate_next(&mut self) -> Result<i32> {
-                self.conjunction_span_base_mut().conjunction.next()
+                self.conjunction_span_base_mut().conjunction.approximate_next()
             }
 
             fn approximate_advance(&mut self, target: i32) -> Result<i32> {
-                self.conjunction_span_base_mut().conjunction.advance(target)
+                self.conjunction_span_base_mut().conjunction.approximate_advance(target)
             }
         }
     };
@@ -20,6 +20,10 @@ use core::search::query::spans::{
     SpanOrQuery, SpanOrWeight, SpanQuery, SpanQueryEnum, SpanTermQuery, SpanTermWeight, SpanWeight,
     SpanWeightEnum, SpansEnum,
 };
+use core::search::query::spans::{SpanContainWeight, SpanContainingQuery, SpanWithinQuery};
+use core::search::query::spans::{SpanMultiTermQueryWrapper, SpanMultiTermWeight};
+use core::search::query::spans::{SpanNotQuery, SpanNotWeight};
+use core::search::query::spans::{SpanPositionRangeQuery, SpanPositionRangeWeight};
 use core::search::searcher::SearchPlanBuilder;
 use core::search::{
     query::Query, query::TermQuery, query::Weight, scorer::Scorer, similarity::SimScorer,
@@ -123,6 +127,11 @@ pub enum SpanBoostQueryEnum {
     Gap(SpanGapQuery),
     Or(SpanOrQuery),
     Near(SpanNearQuery),
+    Not(SpanNotQuery),
+    PositionRange(SpanPositionRangeQuery),
+    MultiTerm(SpanMultiTermQueryWrapper),
+    Containing(SpanContainingQuery),
+    Within(SpanWithinQuery),
 }
 
 impl SpanBoostQueryEnum {
@@ -132,6 +141,11 @@ impl SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => SpanQueryEnum::Gap(q),
             SpanBoostQueryEnum::Or(q) => SpanQueryEnum::Or(q),
             SpanBoostQueryEnum::Near(q) => SpanQueryEnum::Near(q),
+            SpanBoostQueryEnum::Not(q) => SpanQueryEnum::Not(q),
+            SpanBoostQueryEnum::PositionRange(q) => SpanQueryEnum::PositionRange(q),
+            SpanBoostQueryEnum::MultiTerm(q) => SpanQueryEnum::MultiTerm(q),
+            SpanBoostQueryEnum::Containing(q) => SpanQueryEnum::Containing(q),
+            SpanBoostQueryEnum::Within(q) => SpanQueryEnum::Within(q),
         }
     }
 }
@@ -157,6 +171,21 @@ impl<C: Codec> SpanQuery<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Near(q) => {
                 SpanBoostWeightEnum::Near(q.span_weight(searcher, needs_scores)?)
             }
+            SpanBoostQueryEnum::Not(q) => {
+                SpanBoostWeightEnum::Not(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanBoostQueryEnum::PositionRange(q) => {
+                SpanBoostWeightEnum::PositionRange(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanBoostQueryEnum::MultiTerm(q) => {
+                SpanBoostWeightEnum::MultiTerm(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanBoostQueryEnum::Containing(q) => {
+                SpanBoostWeightEnum::Contain(q.span_weight(searcher, needs_scores)?)
+            }
+            SpanBoostQueryEnum::Within(q) => {
+                SpanBoostWeightEnum::Contain(q.span_weight(searcher, needs_scores)?)
+            }
         };
         Ok(weight)
     }
@@ -167,6 +196,11 @@ impl<C: Codec> SpanQuery<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => SpanQuery::<C>::field(q),
             SpanBoostQueryEnum::Or(q) => SpanQuery::<C>::field(q),
             SpanBoostQueryEnum::Near(q) => SpanQuery::<C>::field(q),
+            SpanBoostQueryEnum::Not(q) => SpanQuery::<C>::field(q),
+            SpanBoostQueryEnum::PositionRange(q) => SpanQuery::<C>::field(q),
+            SpanBoostQueryEnum::MultiTerm(q) => SpanQuery::<C>::field(q),
+            SpanBoostQueryEnum::Containing(q) => SpanQuery::<C>::field(q),
+            SpanBoostQueryEnum::Within(q) => SpanQuery::<C>::field(q),
         }
     }
 
@@ -176,6 +210,11 @@ impl<C: Codec> SpanQuery<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => SpanQuery::<C>::ctx(q),
             SpanBoostQueryEnum::Or(q) => SpanQuery::<C>::ctx(q),
             SpanBoostQueryEnum::Near(q) => SpanQuery::<C>::ctx(q),
+            SpanBoostQueryEnum::Not(q) => SpanQuery::<C>::ctx(q),
+            SpanBoostQueryEnum::PositionRange(q) => SpanQuery::<C>::ctx(q),
+            SpanBoostQueryEnum::MultiTerm(q) => SpanQuery::<C>::ctx(q),
+            SpanBoostQueryEnum::Containing(q) => SpanQuery::<C>::ctx(q),
+            SpanBoostQueryEnum::Within(q) => SpanQuery::<C>::ctx(q),
         }
     }
 }
@@ -191,6 +230,11 @@ impl<C: Codec> Query<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => q.create_weight(searcher, needs_scores),
             SpanBoostQueryEnum::Or(q) => q.create_weight(searcher, needs_scores),
             SpanBoostQueryEnum::Near(q) => q.create_weight(searcher, needs_scores),
+            SpanBoostQueryEnum::Not(q) => q.create_weight(searcher, needs_scores),
+            SpanBoostQueryEnum::PositionRange(q) => q.create_weight(searcher, needs_scores),
+            SpanBoostQueryEnum::MultiTerm(q) => q.create_weight(searcher, needs_scores),
+            SpanBoostQueryEnum::Containing(q) => q.create_weight(searcher, needs_scores),
+            SpanBoostQueryEnum::Within(q) => q.create_weight(searcher, needs_scores),
         }
     }
 
@@ -200,6 +244,11 @@ impl<C: Codec> Query<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => Query::<C>::extract_terms(q),
             SpanBoostQueryEnum::Or(q) => Query::<C>::extract_terms(q),
             SpanBoostQueryEnum::Near(q) => Query::<C>::extract_terms(q),
+            SpanBoostQueryEnum::Not(q) => Query::<C>::extract_terms(q),
+            SpanBoostQueryEnum::PositionRange(q) => Query::<C>::extract_terms(q),
+            SpanBoostQueryEnum::MultiTerm(q) => Query::<C>::extract_terms(q),
+            SpanBoostQueryEnum::Containing(q) => Query::<C>::extract_terms(q),
+            SpanBoostQueryEnum::Within(q) => Query::<C>::extract_terms(q),
         }
     }
 
@@ -209,6 +258,11 @@ impl<C: Codec> Query<C> for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => Query::<C>::as_any(q),
             SpanBoostQueryEnum::Or(q) => Query::<C>::as_any(q),
             SpanBoostQueryEnum::Near(q) => Query::<C>::as_any(q),
+            SpanBoostQueryEnum::Not(q) => Query::<C>::as_any(q),
+            SpanBoostQueryEnum::PositionRange(q) => Query::<C>::as_any(q),
+            SpanBoostQueryEnum::MultiTerm(q) => Query::<C>::as_any(q),
+            SpanBoostQueryEnum::Containing(q) => Query::<C>::as_any(q),
+            SpanBoostQueryEnum::Within(q) => Query::<C>::as_any(q),
         }
     }
 }
@@ -220,6 +274,11 @@ impl fmt::Display for SpanBoostQueryEnum {
             SpanBoostQueryEnum::Gap(q) => write!(f, "SpanBoostQueryEnum({})", q),
             SpanBoostQueryEnum::Or(q) => write!(f, "SpanBoostQueryEnum({})", q),
             SpanBoostQueryEnum::Near(q) => write!(f, "SpanBoostQueryEnum({})", q),
+            SpanBoostQueryEnum::Not(q) => write!(f, "SpanBoostQueryEnum({})", q),
+            SpanBoostQueryEnum::PositionRange(q) => write!(f, "SpanBoostQueryEnum({})", q),
+            SpanBoostQueryEnum::MultiTerm(q) => write!(f, "SpanBoostQueryEnum({})", q),
+            SpanBoostQueryEnum::Containing(q) => write!(f, "SpanBoostQueryEnum({})", q),
+            SpanBoostQueryEnum::Within(q) => write!(f, "SpanBoostQueryEnum({})", q),
         }
     }
 }
@@ -320,6 +379,10 @@ pub enum SpanBoostWeightEnum<C: Codec> {
     Gap(SpanGapWeight<C>),
     Or(SpanOrWeight<C>),
     Near(SpanNearWeight<C>),
+    Not(SpanNotWeight<C>),
+    PositionRange(SpanPositionRangeWeight<C>),
+    MultiTerm(SpanMultiTermWeight<C>),
+    Contain(SpanContainWeight<C>),
 }
 
 impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
@@ -329,6 +392,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.sim_weight(),
             SpanBoostWeightEnum::Or(w) => w.sim_weight(),
             SpanBoostWeightEnum::Near(w) => w.sim_weight(),
+            SpanBoostWeightEnum::Not(w) => w.sim_weight(),
+            SpanBoostWeightEnum::PositionRange(w) => w.sim_weight(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.sim_weight(),
+            SpanBoostWeightEnum::Contain(w) => w.sim_weight(),
         }
     }
 
@@ -338,6 +405,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.sim_weight_mut(),
             SpanBoostWeightEnum::Or(w) => w.sim_weight_mut(),
             SpanBoostWeightEnum::Near(w) => w.sim_weight_mut(),
+            SpanBoostWeightEnum::Not(w) => w.sim_weight_mut(),
+            SpanBoostWeightEnum::PositionRange(w) => w.sim_weight_mut(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.sim_weight_mut(),
+            SpanBoostWeightEnum::Contain(w) => w.sim_weight_mut(),
         }
     }
 
@@ -351,6 +422,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.get_spans(reader, required_postings),
             SpanBoostWeightEnum::Or(w) => w.get_spans(reader, required_postings),
             SpanBoostWeightEnum::Near(w) => w.get_spans(reader, required_postings),
+            SpanBoostWeightEnum::Not(w) => w.get_spans(reader, required_postings),
+            SpanBoostWeightEnum::PositionRange(w) => w.get_spans(reader, required_postings),
+            SpanBoostWeightEnum::MultiTerm(w) => w.get_spans(reader, required_postings),
+            SpanBoostWeightEnum::Contain(w) => w.get_spans(reader, required_postings),
         }
     }
 
@@ -360,6 +435,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.extract_term_keys(terms),
             SpanBoostWeightEnum::Or(w) => w.extract_term_keys(terms),
             SpanBoostWeightEnum::Near(w) => w.extract_term_keys(terms),
+            SpanBoostWeightEnum::Not(w) => w.extract_term_keys(terms),
+            SpanBoostWeightEnum::PositionRange(w) => w.extract_term_keys(terms),
+            SpanBoostWeightEnum::MultiTerm(w) => w.extract_term_keys(terms),
+            SpanBoostWeightEnum::Contain(w) => w.extract_term_keys(terms),
         }
     }
 
@@ -369,6 +448,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.do_create_scorer(ctx),
             SpanBoostWeightEnum::Or(w) => w.do_create_scorer(ctx),
             SpanBoostWeightEnum::Near(w) => w.do_create_scorer(ctx),
+            SpanBoostWeightEnum::Not(w) => w.do_create_scorer(ctx),
+            SpanBoostWeightEnum::PositionRange(w) => w.do_create_scorer(ctx),
+            SpanBoostWeightEnum::MultiTerm(w) => w.do_create_scorer(ctx),
+            SpanBoostWeightEnum::Contain(w) => w.do_create_scorer(ctx),
         }
     }
 
@@ -378,6 +461,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.do_value_for_normalization(),
             SpanBoostWeightEnum::Or(w) => w.do_value_for_normalization(),
             SpanBoostWeightEnum::Near(w) => w.do_value_for_normalization(),
+            SpanBoostWeightEnum::Not(w) => w.do_value_for_normalization(),
+            SpanBoostWeightEnum::PositionRange(w) => w.do_value_for_normalization(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.do_value_for_normalization(),
+            SpanBoostWeightEnum::Contain(w) => w.do_value_for_normalization(),
         }
     }
 
@@ -387,6 +474,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.do_normalize(query_norm, boost),
             SpanBoostWeightEnum::Or(w) => w.do_normalize(query_norm, boost),
             SpanBoostWeightEnum::Near(w) => w.do_normalize(query_norm, boost),
+            SpanBoostWeightEnum::Not(w) => w.do_normalize(query_norm, boost),
+            SpanBoostWeightEnum::PositionRange(w) => w.do_normalize(query_norm, boost),
+            SpanBoostWeightEnum::MultiTerm(w) => w.do_normalize(query_norm, boost),
+            SpanBoostWeightEnum::Contain(w) => w.do_normalize(query_norm, boost),
         }
     }
 
@@ -396,6 +487,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.sim_scorer(reader),
             SpanBoostWeightEnum::Or(w) => w.sim_scorer(reader),
             SpanBoostWeightEnum::Near(w) => w.sim_scorer(reader),
+            SpanBoostWeightEnum::Not(w) => w.sim_scorer(reader),
+            SpanBoostWeightEnum::PositionRange(w) => w.sim_scorer(reader),
+            SpanBoostWeightEnum::MultiTerm(w) => w.sim_scorer(reader),
+            SpanBoostWeightEnum::Contain(w) => w.sim_scorer(reader),
         }
     }
 
@@ -405,6 +500,10 @@ impl<C: Codec> SpanWeight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.explain_span(reader, doc),
             SpanBoostWeightEnum::Or(w) => w.explain_span(reader, doc),
             SpanBoostWeightEnum::Near(w) => w.explain_span(reader, doc),
+            SpanBoostWeightEnum::Not(w) => w.explain_span(reader, doc),
+            SpanBoostWeightEnum::PositionRange(w) => w.explain_span(reader, doc),
+            SpanBoostWeightEnum::MultiTerm(w) => w.explain_span(reader, doc),
+            SpanBoostWeightEnum::Contain(w) => w.explain_span(reader, doc),
         }
     }
 }
@@ -419,6 +518,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.create_scorer(leaf_reader),
             SpanBoostWeightEnum::Or(w) => w.create_scorer(leaf_reader),
             SpanBoostWeightEnum::Near(w) => w.create_scorer(leaf_reader),
+            SpanBoostWeightEnum::Not(w) => w.create_scorer(leaf_reader),
+            SpanBoostWeightEnum::PositionRange(w) => w.create_scorer(leaf_reader),
+            SpanBoostWeightEnum::MultiTerm(w) => w.create_scorer(leaf_reader),
+            SpanBoostWeightEnum::Contain(w) => w.create_scorer(leaf_reader),
         }
     }
 
@@ -428,6 +531,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.hash_code(),
             SpanBoostWeightEnum::Or(w) => w.hash_code(),
             SpanBoostWeightEnum::Near(w) => w.hash_code(),
+            SpanBoostWeightEnum::Not(w) => w.hash_code(),
+            SpanBoostWeightEnum::PositionRange(w) => w.hash_code(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.hash_code(),
+            SpanBoostWeightEnum::Contain(w) => w.hash_code(),
         }
     }
 
@@ -437,6 +544,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.query_type(),
             SpanBoostWeightEnum::Or(w) => w.query_type(),
             SpanBoostWeightEnum::Near(w) => w.query_type(),
+            SpanBoostWeightEnum::Not(w) => w.query_type(),
+            SpanBoostWeightEnum::PositionRange(w) => w.query_type(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.query_type(),
+            SpanBoostWeightEnum::Contain(w) => w.query_type(),
         }
     }
 
@@ -446,6 +557,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.actual_query_type(),
             SpanBoostWeightEnum::Or(w) => w.actual_query_type(),
             SpanBoostWeightEnum::Near(w) => w.actual_query_type(),
+            SpanBoostWeightEnum::Not(w) => w.actual_query_type(),
+            SpanBoostWeightEnum::PositionRange(w) => w.actual_query_type(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.actual_query_type(),
+            SpanBoostWeightEnum::Contain(w) => w.actual_query_type(),
         }
     }
 
@@ -455,6 +570,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.normalize(norm, boost),
             SpanBoostWeightEnum::Or(w) => w.normalize(norm, boost),
             SpanBoostWeightEnum::Near(w) => w.normalize(norm, boost),
+            SpanBoostWeightEnum::Not(w) => w.normalize(norm, boost),
+            SpanBoostWeightEnum::PositionRange(w) => w.normalize(norm, boost),
+            SpanBoostWeightEnum::MultiTerm(w) => w.normalize(norm, boost),
+            SpanBoostWeightEnum::Contain(w) => w.normalize(norm, boost),
         }
     }
 
@@ -464,6 +583,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.value_for_normalization(),
             SpanBoostWeightEnum::Or(w) => w.value_for_normalization(),
             SpanBoostWeightEnum::Near(w) => w.value_for_normalization(),
+            SpanBoostWeightEnum::Not(w) => w.value_for_normalization(),
+            SpanBoostWeightEnum::PositionRange(w) => w.value_for_normalization(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.value_for_normalization(),
+            SpanBoostWeightEnum::Contain(w) => w.value_for_normalization(),
         }
     }
 
@@ -473,6 +596,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.needs_scores(),
             SpanBoostWeightEnum::Or(w) => w.needs_scores(),
             SpanBoostWeightEnum::Near(w) => w.needs_scores(),
+            SpanBoostWeightEnum::Not(w) => w.needs_scores(),
+            SpanBoostWeightEnum::PositionRange(w) => w.needs_scores(),
+            SpanBoostWeightEnum::MultiTerm(w) => w.needs_scores(),
+            SpanBoostWeightEnum::Contain(w) => w.needs_scores(),
         }
     }
 
@@ -482,6 +609,10 @@ impl<C: Codec> Weight<C> for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => w.explain(reader, doc),
             SpanBoostWeightEnum::Or(w) => w.explain(reader, doc),
             SpanBoostWeightEnum::Near(w) => w.explain(reader, doc),
+            SpanBoostWeightEnum::Not(w) => w.explain(reader, doc),
+            SpanBoostWeightEnum::PositionRange(w) => w.explain(reader, doc),
+            SpanBoostWeightEnum::MultiTerm(w) => w.explain(reader, doc),
+            SpanBoostWeightEnum::Contain(w) => w.explain(reader, doc),
         }
     }
 }
@@ -493,6 +624,10 @@ impl<C: Codec> fmt::Display for SpanBoostWeightEnum<C> {
             SpanBoostWeightEnum::Gap(w) => write!(f, "SpanBoostWeightEnum({})", w),
             SpanBoostWeightEnum::Or(w) => write!(f, "SpanBoostWeightEnum({})", w),
             SpanBoostWeightEnum::Near(w) => write!(f, "SpanBoostWeightEnum({})", w),
+            SpanBoostWeightEnum::Not(w) => write!(f, "SpanBoostWeightEnum({})", w),
+            SpanBoostWeightEnum::PositionRange(w) => write!(f, "SpanBoostWeightEnum({})", w),
+            SpanBoostWeightEnum::MultiTerm(w) => write!(f, "SpanBoostWeightEnum({})", w),
+            SpanBoostWeightEnum::Contain(w) => write!(f, "SpanBoostWeightEnum({})", w),
         }
     }
 }
@@ -0,0 +1,501 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecEnum, CodecPostingIterator, PostingIterator};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::spans::{
+    build_sim_weight, ConjunctionSpanBase, ConjunctionSpans, PostingsFlag, SpanCollector,
+    SpanQueryEnum, SpanWeightEnum, Spans, SpansEnum, NO_MORE_POSITIONS,
+};
+use core::search::query::spans::{SpanQuery, SpanWeight};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{
+    query::Query, query::TermQuery, query::Weight, scorer::Scorer, similarity::SimWeight,
+    DocIterator, NO_MORE_DOCS,
+};
+use core::util::{DocId, KeyedContext};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+const SPAN_CONTAINING_QUERY: &str = "span_containing";
+const SPAN_WITHIN_QUERY: &str = "span_within";
+
+fn check_same_field(big: &SpanQueryEnum, little: &SpanQueryEnum) -> Result<()> {
+    if SpanQuery::<CodecEnum>::field(big) != SpanQuery::<CodecEnum>::field(little) {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "big and little not same field: big is '{}', little is '{}'",
+            SpanQuery::<CodecEnum>::field(big),
+            SpanQuery::<CodecEnum>::field(little)
+        )));
+    }
+    Ok(())
+}
+
+/// Keep matches that contain another SpanScorer.
+pub struct SpanContainingQuery {
+    big: Box<SpanQueryEnum>,
+    little: Box<SpanQueryEnum>,
+}
+
+impl SpanContainingQuery {
+    /// Construct a SpanContainingQuery matching spans from `big`
+    /// that contain at least one spans from `little`.
+    /// This query has the boost of `big`.
+    /// `big` and `little` must be in the same field.
+    pub fn new(big: SpanQueryEnum, little: SpanQueryEnum) -> Result<Self> {
+        check_same_field(&big, &little)?;
+        Ok(SpanContainingQuery {
+            big: Box::new(big),
+            little: Box::new(little),
+        })
+    }
+
+    pub fn big(&self) -> &SpanQueryEnum {
+        &self.big
+    }
+
+    pub fn little(&self) -> &SpanQueryEnum {
+        &self.little
+    }
+
+    fn span_contain_weight<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<SpanContainWeight<C>> {
+        let big_weight = self.big.span_weight(searcher, false)?;
+        let little_weight = self.little.span_weight(searcher, false)?;
+        SpanContainWeight::new(
+            SpanQuery::<C>::field(self),
+            SpanQuery::<C>::ctx(self),
+            big_weight,
+            little_weight,
+            false,
+            searcher,
+            needs_scores,
+        )
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanContainingQuery {
+    type Weight = SpanContainWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.span_contain_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        SpanQuery::<C>::field(self.big.as_ref())
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.big.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for SpanContainingQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(self.span_contain_weight(searcher, needs_scores)?))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.big.as_ref())
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SpanContainingQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanContainingQuery(big: {}, little: {})",
+            &self.big, &self.little
+        )
+    }
+}
+
+/// Keep matches that are contained within another Spans.
+pub struct SpanWithinQuery {
+    big: Box<SpanQueryEnum>,
+    little: Box<SpanQueryEnum>,
+}
+
+impl SpanWithinQuery {
+    /// Construct a SpanWithinQuery matching spans from `little`
+    /// that are inside of `big`.
+    /// This query has the boost of `little`.
+    /// `big` and `little` must be in the same field.
+    pub fn new(big: SpanQueryEnum, little: SpanQueryEnum) -> Result<Self> {
+        check_same_field(&big, &little)?;
+        Ok(SpanWithinQuery {
+            big: Box::new(big),
+            little: Box::new(little),
+        })
+    }
+
+    pub fn big(&self) -> &SpanQueryEnum {
+        &self.big
+    }
+
+    pub fn little(&self) -> &SpanQueryEnum {
+        &self.little
+    }
+
+    fn span_contain_weight<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<SpanContainWeight<C>> {
+        let big_weight = self.big.span_weight(searcher, false)?;
+        let little_weight = self.little.span_weight(searcher, false)?;
+        SpanContainWeight::new(
+            SpanQuery::<C>::field(self),
+            SpanQuery::<C>::ctx(self),
+            big_weight,
+            little_weight,
+            true,
+            searcher,
+            needs_scores,
+        )
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanWithinQuery {
+    type Weight = SpanContainWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.span_contain_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        SpanQuery::<C>::field(self.little.as_ref())
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.little.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for SpanWithinQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(self.span_contain_weight(searcher, needs_scores)?))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.little.as_ref())
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SpanWithinQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanWithinQuery(big: {}, little: {})",
+            &self.big, &self.little
+        )
+    }
+}
+
+/// The weight shared by `SpanContainingQuery` and `SpanWithinQuery`.
+pub struct SpanContainWeight<C: Codec> {
+    sim_weight: Option<Box<dyn SimWeight<C>>>,
+    big_weight: Box<SpanWeightEnum<C>>,
+    little_weight: Box<SpanWeightEnum<C>>,
+    // `true` for `SpanWithinQuery`, the little spans are returned
+    within: bool,
+}
+
+impl<C: Codec> SpanContainWeight<C> {
+    pub fn new<IS: SearchPlanBuilder<C> + ?Sized>(
+        field: &str,
+        ctx: Option<KeyedContext>,
+        big_weight: SpanWeightEnum<C>,
+        little_weight: SpanWeightEnum<C>,
+        within: bool,
+        searcher: &IS,
+        needs_scores: bool,
+    ) -> Result<Self> {
+        let mut terms = Vec::new();
+        if needs_scores {
+            big_weight.extract_term_keys(&mut terms);
+            little_weight.extract_term_keys(&mut terms);
+        }
+        let sim_weight = build_sim_weight(field, searcher, terms, ctx)?;
+        Ok(SpanContainWeight {
+            sim_weight,
+            big_weight: Box::new(big_weight),
+            little_weight: Box::new(little_weight),
+            within,
+        })
+    }
+}
+
+impl<C: Codec> SpanWeight<C> for SpanContainWeight<C> {
+    fn sim_weight(&self) -> Option<&dyn SimWeight<C>> {
+        self.sim_weight.as_ref().map(|x| &**x)
+    }
+
+    fn sim_weight_mut(&mut self) -> Option<&mut dyn SimWeight<C>> {
+        if let Some(ref mut sim_weight) = self.sim_weight {
+            Some(sim_weight.as_mut())
+        } else {
+            None
+        }
+    }
+
+    fn get_spans(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Option<SpansEnum<CodecPostingIterator<C>>>> {
+        let big_spans = match self.big_weight.get_spans(reader, required_postings)? {
+            Some(spans) => spans,
+            None => {
+                return Ok(None);
+            }
+        };
+        let little_spans = match self.little_weight.get_spans(reader, required_postings)? {
+            Some(spans) => spans,
+            None => {
+                return Ok(None);
+            }
+        };
+        Ok(Some(SpansEnum::Contain(Box::new(ContainSpans::new(
+            big_spans,
+            little_spans,
+            self.within,
+        )?))))
+    }
+
+    fn extract_term_keys(&self, terms: &mut Vec<Term>) {
+        if self.within {
+            self.little_weight.extract_term_keys(terms)
+        } else {
+            self.big_weight.extract_term_keys(terms)
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for SpanContainWeight<C> {
+    fn create_scorer(&self, ctx: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        self.do_create_scorer(ctx)
+    }
+
+    fn query_type(&self) -> &'static str {
+        if self.within {
+            SPAN_WITHIN_QUERY
+        } else {
+            SPAN_CONTAINING_QUERY
+        }
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.do_normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.do_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        self.explain_span(reader, doc)
+    }
+}
+
+impl<C: Codec> fmt::Display for SpanContainWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanContainWeight(big: {}, little: {}, within: {})",
+            &self.big_weight, &self.little_weight, self.within
+        )
+    }
+}
+
+const BIG: usize = 0;
+const LITTLE: usize = 1;
+
+/// Spans for `SpanContainingQuery` and `SpanWithinQuery`: a conjunction of
+/// the big and the little spans, returning the big spans which contain a little spans
+/// (containing) or the little spans which are contained by a big spans (within).
+pub struct ContainSpans<P: PostingIterator> {
+    conjunction_span: ConjunctionSpanBase<P>,
+    // [big, little], the vec must not be resized because the
+    // conjunction holds pointers to its elements
+    sub_spans: Vec<SpansEnum<P>>,
+    within: bool,
+}
+
+impl<P: PostingIterator> ContainSpans<P> {
+    pub fn new(big: SpansEnum<P>, little: SpansEnum<P>, within: bool) -> Result<Self> {
+        let mut sub_spans = vec![big, little];
+        let conjunction_span = ConjunctionSpanBase::new(&mut sub_spans)?;
+        Ok(ContainSpans {
+            conjunction_span,
+            sub_spans,
+            within,
+        })
+    }
+
+    /// the spans whose positions are returned
+    fn source(&self) -> &SpansEnum<P> {
+        if self.within {
+            &self.sub_spans[LITTLE]
+        } else {
+            &self.sub_spans[BIG]
+        }
+    }
+
+    /// move to the next matching position in current doc, return `false` if one
+    /// of the sub spans was exhausted.
+    fn next_contain_position(&mut self) -> Result<bool> {
+        if self.within {
+            while self.sub_spans[LITTLE].next_start_position()? != NO_MORE_POSITIONS {
+                while self.sub_spans[BIG].end_position() < self.sub_spans[LITTLE].end_position() {
+                    if self.sub_spans[BIG].next_start_position()? == NO_MORE_POSITIONS {
+                        self.conjunction_span.one_exhausted_in_current_doc = true;
+                        return Ok(false);
+                    }
+                }
+                if self.sub_spans[BIG].start_position() <= self.sub_spans[LITTLE].start_position() {
+                    return Ok(true);
+                }
+            }
+        } else {
+            while self.sub_spans[BIG].next_start_position()? != NO_MORE_POSITIONS {
+                while self.sub_spans[LITTLE].start_position() < self.sub_spans[BIG].start_position()
+                {
+                    if self.sub_spans[LITTLE].next_start_position()? == NO_MORE_POSITIONS {
+                        self.conjunction_span.one_exhausted_in_current_doc = true;
+                        return Ok(false);
+                    }
+                }
+                if self.sub_spans[BIG].end_position() >= self.sub_spans[LITTLE].end_position() {
+                    return Ok(true);
+                }
+            }
+        }
+        self.conjunction_span.one_exhausted_in_current_doc = true;
+        Ok(false)
+    }
+}
+
+impl<P: PostingIterator> ConjunctionSpans<P> for ContainSpans<P> {
+    fn conjunction_span_base(&self) -> &ConjunctionSpanBase<P> {
+        &self.conjunction_span
+    }
+
+    fn conjunction_span_base_mut(&mut self) -> &mut ConjunctionSpanBase<P> {
+        &mut self.conjunction_span
+    }
+
+    fn two_phase_current_doc_matches(&mut self) -> Result<bool> {
+        self.conjunction_span.one_exhausted_in_current_doc = false;
+        // the conjunction only agrees on the approximations, the two phase
+        // sub spans must be positioned on their first match before going on
+        for spans in &mut self.sub_spans {
+            if spans.support_two_phase() && !spans.matches()? {
+                return Ok(false);
+            }
+        }
+        debug_assert_eq!(self.sub_spans[LITTLE].start_position(), -1);
+        if self.next_contain_position()? {
+            self.conjunction_span.first_in_current_doc = true;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl<P: PostingIterator> Spans for ContainSpans<P> {
+    fn next_start_position(&mut self) -> Result<i32> {
+        if self.conjunction_span.first_in_current_doc {
+            self.conjunction_span.first_in_current_doc = false;
+            return Ok(self.source().start_position());
+        }
+        if self.next_contain_position()? {
+            Ok(self.source().start_position())
+        } else {
+            Ok(NO_MORE_POSITIONS)
+        }
+    }
+
+    fn start_position(&self) -> i32 {
+        if self.conjunction_span.first_in_current_doc {
+            -1
+        } else if self.conjunction_span.one_exhausted_in_current_doc {
+            NO_MORE_POSITIONS
+        } else {
+            self.source().start_position()
+        }
+    }
+
+    fn end_position(&self) -> i32 {
+        if self.conjunction_span.first_in_current_doc {
+            -1
+        } else if self.conjunction_span.one_exhausted_in_current_doc {
+            NO_MORE_POSITIONS
+        } else {
+            self.source().end_position()
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.source().width()
+    }
+
+    fn collect(&mut self, collector: &mut impl SpanCollector) -> Result<()> {
+        for spans in &mut self.sub_spans {
+            spans.collect(collector)?;
+        }
+        Ok(())
+    }
+
+    fn positions_cost(&self) -> f32 {
+        unreachable!()
+    }
+}
+
+conjunction_span_doc_iter!(ContainSpans<P: PostingIterator>);
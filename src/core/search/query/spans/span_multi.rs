@@ -0,0 +1,846 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecPostingIterator, SeekStatus, TermIterator, Terms};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::spans::span_term::PHRASE_TO_SPAN_TERM_POSITIONS_COST;
+use core::search::query::spans::{
+    PostingsFlag, SpanOrSpans, SpanQuery, SpanScorer, SpanTermWeight, SpanWeight, SpansEnum,
+    TermSpans,
+};
+use core::search::scorer::DisjunctionSumScorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::similarity::{BM25Similarity, Similarity};
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{
+    query::Query, query::TermQuery, query::Weight, scorer::Scorer, similarity::SimWeight,
+    DocIterator,
+};
+use core::util::{DocId, KeyedContext};
+
+use error::{ErrorKind, Result};
+
+use regex::bytes::Regex;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+
+const SPAN_MULTI_TERM_QUERY: &str = "span_multi_term";
+
+/// Default number of terms a `SpanMultiTermQueryWrapper` expands to in each segment.
+pub const DEFAULT_MAX_EXPANSIONS: usize = 1024;
+
+/// The largest edit distance supported by `MultiTermMatcher::fuzzy`.
+pub const MAX_FUZZY_EDITS: u32 = 2;
+
+/// Decides which terms of a field a multi term query expands to.
+#[derive(Clone, Debug)]
+pub enum MultiTermMatcher {
+    /// Terms starting with the given bytes.
+    Prefix(Vec<u8>),
+    /// Terms matching a wildcard pattern, where `*` matches any character
+    /// sequence, `?` matches any single character and `\` escapes the next one.
+    Wildcard { pattern: String, regex: Regex },
+    /// Terms entirely matching a regular expression.
+    Regexp { pattern: String, regex: Regex },
+    /// Terms within `max_edits` Damerau-Levenshtein edits of `text`, the first
+    /// `prefix_length` characters having to match exactly.
+    Fuzzy {
+        text: Vec<char>,
+        max_edits: u32,
+        prefix_length: usize,
+    },
+}
+
+impl MultiTermMatcher {
+    pub fn prefix(prefix: &str) -> Self {
+        MultiTermMatcher::Prefix(prefix.as_bytes().to_vec())
+    }
+
+    pub fn wildcard(pattern: &str) -> Result<Self> {
+        let mut expr = String::with_capacity(pattern.len() + 8);
+        expr.push_str("^(?s:");
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => expr.push_str(".*"),
+                '?' => expr.push('.'),
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        expr.push_str(&::regex::escape(&escaped.to_string()));
+                    } else {
+                        expr.push_str(&::regex::escape("\\"));
+                    }
+                }
+                c => expr.push_str(&::regex::escape(&c.to_string())),
+            }
+        }
+        expr.push_str(")$");
+        Ok(MultiTermMatcher::Wildcard {
+            pattern: pattern.to_string(),
+            regex: Self::compile(&expr)?,
+        })
+    }
+
+    pub fn regexp(pattern: &str) -> Result<Self> {
+        Ok(MultiTermMatcher::Regexp {
+            pattern: pattern.to_string(),
+            regex: Self::compile(&format!("^(?:{})$", pattern))?,
+        })
+    }
+
+    pub fn fuzzy(text: &str, max_edits: u32, prefix_length: usize) -> Result<Self> {
+        if max_edits > MAX_FUZZY_EDITS {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "max_edits must be between 0 and {}",
+                MAX_FUZZY_EDITS
+            )));
+        }
+        Ok(MultiTermMatcher::Fuzzy {
+            text: text.chars().collect(),
+            max_edits,
+            prefix_length,
+        })
+    }
+
+    fn compile(expr: &str) -> Result<Regex> {
+        match Regex::new(expr) {
+            Ok(regex) => Ok(regex),
+            Err(e) => bail!(ErrorKind::IllegalArgument(format!(
+                "invalid pattern '{}': {}",
+                expr, e
+            ))),
+        }
+    }
+
+    /// The bytes all the accepted terms start with, used to seek the terms.
+    pub fn common_prefix(&self) -> Vec<u8> {
+        match self {
+            MultiTermMatcher::Prefix(prefix) => prefix.clone(),
+            MultiTermMatcher::Wildcard { pattern, .. } => {
+                let mut prefix = String::new();
+                let mut chars = pattern.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '*' | '?' => break,
+                        '\\' => match chars.next() {
+                            Some(escaped) => prefix.push(escaped),
+                            None => break,
+                        },
+                        c => prefix.push(c),
+                    }
+                }
+                prefix.into_bytes()
+            }
+            MultiTermMatcher::Regexp { .. } => Vec::new(),
+            MultiTermMatcher::Fuzzy {
+                text,
+                prefix_length,
+                ..
+            } => text
+                .iter()
+                .take(*prefix_length)
+                .collect::<String>()
+                .into_bytes(),
+        }
+    }
+
+    /// Returns the boost of `term` if it is accepted, `None` otherwise.
+    ///
+    /// Fuzzy matches are boosted by their similarity to the query text, all
+    /// the other matches get a boost of 1.
+    pub fn accept(&self, term: &[u8]) -> Option<f32> {
+        match self {
+            MultiTermMatcher::Prefix(prefix) => {
+                if term.starts_with(prefix) {
+                    Some(1.0)
+                } else {
+                    None
+                }
+            }
+            MultiTermMatcher::Wildcard { regex, .. } | MultiTermMatcher::Regexp { regex, .. } => {
+                if regex.is_match(term) {
+                    Some(1.0)
+                } else {
+                    None
+                }
+            }
+            MultiTermMatcher::Fuzzy {
+                text,
+                max_edits,
+                prefix_length,
+            } => {
+                let term: Vec<char> = ::std::str::from_utf8(term).ok()?.chars().collect();
+                let prefix_length = (*prefix_length).min(text.len());
+                if term.len() < prefix_length || term[..prefix_length] != text[..prefix_length] {
+                    return None;
+                }
+                let edits = edit_distance(
+                    &text[prefix_length..],
+                    &term[prefix_length..],
+                    *max_edits as usize,
+                )?;
+                let min_len = term.len().min(text.len());
+                if min_len == 0 {
+                    Some(1.0)
+                } else {
+                    Some((1.0 - edits as f32 / min_len as f32).max(0.0))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for MultiTermMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultiTermMatcher::Prefix(prefix) => {
+                write!(f, "{}*", String::from_utf8_lossy(prefix))
+            }
+            MultiTermMatcher::Wildcard { pattern, .. } => write!(f, "{}", pattern),
+            MultiTermMatcher::Regexp { pattern, .. } => write!(f, "/{}/", pattern),
+            MultiTermMatcher::Fuzzy {
+                text, max_edits, ..
+            } => write!(f, "{}~{}", text.iter().collect::<String>(), max_edits),
+        }
+    }
+}
+
+/// Optimal string alignment distance between `a` and `b`, counting adjacent
+/// transpositions as a single edit. Returns `None` once it exceeds `max_edits`.
+fn edit_distance(a: &[char], b: &[char], max_edits: usize) -> Option<usize> {
+    let (n, m) = (a.len(), b.len());
+    if (n as isize - m as isize).abs() as usize > max_edits {
+        return None;
+    }
+    // rows i - 2, i - 1 and i of the distance matrix
+    let mut prev2 = vec![0usize; m + 1];
+    let mut prev: Vec<usize> = (0..=m).collect();
+    let mut cur = vec![0usize; m + 1];
+    for i in 1..=n {
+        cur[0] = i;
+        let mut row_min = cur[0];
+        for j in 1..=m {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut d = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(prev2[j - 2] + 1);
+            }
+            cur[j] = d;
+            row_min = row_min.min(d);
+        }
+        if row_min > max_edits {
+            return None;
+        }
+        ::std::mem::swap(&mut prev2, &mut prev);
+        ::std::mem::swap(&mut prev, &mut cur);
+    }
+    if prev[m] <= max_edits {
+        Some(prev[m])
+    } else {
+        None
+    }
+}
+
+/// Wraps a multi term query (prefix, wildcard, regexp or fuzzy) as a span query,
+/// so that it can be used as a clause of the other span queries, e.g. to search
+/// for `micro* NEAR/3 soft*`.
+///
+/// The terms are expanded for each segment: the at most `max_expansions`
+/// accepted terms with the highest boost, then the highest doc freq, are
+/// combined in a span or. On its own, the query scores each expanded term
+/// with the blended statistics of all of them, as `SynonymQuery` does, times
+/// the boost of the term. As the expanded terms are only known once a segment
+/// is reached, they add no statistics to the enclosing span queries.
+pub struct SpanMultiTermQueryWrapper {
+    field: String,
+    matcher: MultiTermMatcher,
+    max_expansions: usize,
+}
+
+impl SpanMultiTermQueryWrapper {
+    pub fn new(field: String, matcher: MultiTermMatcher) -> Self {
+        SpanMultiTermQueryWrapper {
+            field,
+            matcher,
+            max_expansions: DEFAULT_MAX_EXPANSIONS,
+        }
+    }
+
+    pub fn with_max_expansions(
+        field: String,
+        matcher: MultiTermMatcher,
+        max_expansions: usize,
+    ) -> Result<Self> {
+        if max_expansions == 0 {
+            bail!(ErrorKind::IllegalArgument(
+                "max_expansions must be greater than 0".into()
+            ));
+        }
+        Ok(SpanMultiTermQueryWrapper {
+            field,
+            matcher,
+            max_expansions,
+        })
+    }
+
+    pub fn matcher(&self) -> &MultiTermMatcher {
+        &self.matcher
+    }
+
+    pub fn max_expansions(&self) -> usize {
+        self.max_expansions
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanMultiTermQueryWrapper {
+    type Weight = SpanMultiTermWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        SpanMultiTermWeight::new(self, searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        &self.field
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        None
+    }
+}
+
+impl<C: Codec> Query<C> for SpanMultiTermQueryWrapper {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(SpanMultiTermWeight::new(
+            self,
+            searcher,
+            needs_scores,
+        )?))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SpanMultiTermQueryWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanMultiTermQueryWrapper(field: {}, query: {}, max_expansions: {})",
+            &self.field, &self.matcher, self.max_expansions
+        )
+    }
+}
+
+type ExpandedTerm<C> = (
+    ScoreTerm,
+    TermStatistics,
+    TermSpans<CodecPostingIterator<C>>,
+);
+
+type ExpandedTermScorer<C> = SpanScorer<TermSpans<CodecPostingIterator<C>>>;
+
+pub struct SpanMultiTermWeight<C: Codec> {
+    field: String,
+    matcher: MultiTermMatcher,
+    max_expansions: usize,
+    similarity: Box<dyn Similarity<C>>,
+    collection_stats: CollectionStatistics,
+    needs_scores: bool,
+    boost: f32,
+}
+
+impl<C: Codec> SpanMultiTermWeight<C> {
+    pub fn new<IS: SearchPlanBuilder<C> + ?Sized>(
+        query: &SpanMultiTermQueryWrapper,
+        searcher: &IS,
+        needs_scores: bool,
+    ) -> Result<Self> {
+        let max_doc = i64::from(searcher.max_doc());
+        let collection_stats = match searcher.collections_statistics(&query.field) {
+            Some(stat) if needs_scores => stat.clone(),
+            _ => CollectionStatistics::new(query.field.clone(), 0, max_doc, -1, -1, -1),
+        };
+        Ok(SpanMultiTermWeight {
+            field: query.field.clone(),
+            matcher: query.matcher.clone(),
+            max_expansions: query.max_expansions,
+            similarity: searcher.similarity(&query.field, needs_scores),
+            collection_stats,
+            needs_scores,
+            boost: 1.0,
+        })
+    }
+
+    /// Collects the best `max_expansions` terms accepted by the matcher.
+    fn top_terms(&self, terms_iter: &mut impl TermIterator) -> Result<Vec<ScoreTerm>> {
+        let prefix = self.matcher.common_prefix();
+        let mut term = if prefix.is_empty() {
+            terms_iter.next()?
+        } else if terms_iter.seek_ceil(&prefix)? == SeekStatus::End {
+            None
+        } else {
+            Some(terms_iter.term()?.to_vec())
+        };
+
+        let mut queue = BinaryHeap::with_capacity(self.max_expansions.min(1024) + 1);
+        while let Some(bytes) = term {
+            if !bytes.starts_with(&prefix) {
+                break;
+            }
+            if let Some(boost) = self.matcher.accept(&bytes) {
+                let doc_freq = terms_iter.doc_freq()?;
+                queue.push(Reverse(ScoreTerm {
+                    boost,
+                    doc_freq,
+                    bytes,
+                }));
+                if queue.len() > self.max_expansions {
+                    queue.pop();
+                }
+            }
+            term = terms_iter.next()?;
+        }
+
+        let mut top_terms: Vec<ScoreTerm> = queue.into_iter().map(|t| t.0).collect();
+        top_terms.sort_by(|a, b| a.bytes.cmp(&b.bytes));
+        Ok(top_terms)
+    }
+
+    /// Returns the expanded terms of the segment, with their statistics in the
+    /// segment and their spans.
+    fn expand(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Vec<ExpandedTerm<C>>> {
+        let terms = match reader.reader.terms(&self.field)? {
+            Some(terms) => terms,
+            None => {
+                return Ok(vec![]);
+            }
+        };
+        if !terms.has_positions()? {
+            bail!(ErrorKind::IllegalState(format!(
+                "field '{}' was indexed without position data; cannot run \
+                 SpanMultiTermQueryWrapper (query={})",
+                &self.field, &self.matcher
+            )));
+        }
+
+        let mut terms_iter = terms.iterator()?;
+        let top_terms = self.top_terms(&mut terms_iter)?;
+        let mut expanded = Vec::with_capacity(top_terms.len());
+        for term in top_terms {
+            if !terms_iter.seek_exact(&term.bytes)? {
+                continue;
+            }
+            let stats = TermStatistics::new(
+                term.bytes.clone(),
+                i64::from(terms_iter.doc_freq()?),
+                terms_iter.total_term_freq()?,
+            );
+            let postings = terms_iter.postings_with_flags(required_postings.required_postings())?;
+            let positions_cost = SpanTermWeight::<C>::term_positions_cost(&mut terms_iter)?
+                + PHRASE_TO_SPAN_TERM_POSITIONS_COST;
+            let spans = TermSpans::new(
+                postings,
+                Term::new(self.field.clone(), term.bytes.clone()),
+                positions_cost,
+            );
+            expanded.push((term, stats, spans));
+        }
+        Ok(expanded)
+    }
+
+    /// Returns a scorer for each expanded term of the segment, scored with the
+    /// blended statistics of all the expanded terms and boosted by its own boost.
+    fn term_scorers(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Vec<(ScoreTerm, ExpandedTermScorer<C>)>> {
+        let expanded = self.expand(reader, &PostingsFlag::Positions)?;
+        if expanded.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut term_stats = Vec::with_capacity(expanded.len());
+        let mut term_spans = Vec::with_capacity(expanded.len());
+        for (term, stats, spans) in expanded {
+            term_stats.push(stats);
+            term_spans.push((term, spans));
+        }
+        let term_stats = [BM25Similarity::blend_term_stats(
+            self.matcher.to_string().into_bytes(),
+            &term_stats,
+        )];
+
+        // the terms of a same boost share their weight, only the fuzzy terms having
+        // a boost other than 1
+        let mut sim_weights: Vec<(f32, Box<dyn SimWeight<C>>)> = vec![];
+        let mut scorers = Vec::with_capacity(term_spans.len());
+        for (term, spans) in term_spans {
+            let idx = match sim_weights.iter().position(|w| w.0 == term.boost) {
+                Some(idx) => idx,
+                None => {
+                    let sim_weight = self.similarity.compute_weight(
+                        &self.collection_stats,
+                        &term_stats,
+                        None,
+                        self.boost * term.boost,
+                    );
+                    sim_weights.push((term.boost, sim_weight));
+                    sim_weights.len() - 1
+                }
+            };
+            let sim_scorer = sim_weights[idx].1.sim_scorer(reader.reader)?;
+            scorers.push((term, SpanScorer::new(spans, Some(sim_scorer))));
+        }
+        Ok(scorers)
+    }
+}
+
+impl<C: Codec> SpanWeight<C> for SpanMultiTermWeight<C> {
+    fn sim_weight(&self) -> Option<&dyn SimWeight<C>> {
+        None
+    }
+
+    fn sim_weight_mut(&mut self) -> Option<&mut dyn SimWeight<C>> {
+        None
+    }
+
+    fn get_spans(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Option<SpansEnum<CodecPostingIterator<C>>>> {
+        let mut sub_spans: Vec<_> = self
+            .expand(reader, required_postings)?
+            .into_iter()
+            .map(|(_, _, spans)| SpansEnum::Term(spans))
+            .collect();
+        if sub_spans.len() <= 1 {
+            Ok(sub_spans.pop())
+        } else {
+            Ok(Some(SpansEnum::Or(SpanOrSpans::new(sub_spans))))
+        }
+    }
+
+    fn extract_term_keys(&self, _terms: &mut Vec<Term>) {}
+}
+
+impl<C: Codec> Weight<C> for SpanMultiTermWeight<C> {
+    fn create_scorer(&self, ctx: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        let mut scorers: Vec<_> = self
+            .term_scorers(ctx)?
+            .into_iter()
+            .map(|(_, scorer)| scorer)
+            .collect();
+        match scorers.len() {
+            0 => Ok(None),
+            1 => Ok(Some(Box::new(scorers.remove(0)))),
+            _ => Ok(Some(Box::new(DisjunctionSumScorer::new(
+                scorers,
+                self.needs_scores,
+                1,
+            )))),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        SPAN_MULTI_TERM_QUERY
+    }
+
+    fn normalize(&mut self, _norm: f32, boost: f32) {
+        self.boost = boost;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.boost * self.boost
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let mut details = vec![];
+        for (term, mut scorer) in self.term_scorers(reader)? {
+            if scorer.advance(doc)? == doc {
+                details.push(Explanation::new(
+                    true,
+                    scorer.score()?,
+                    format!(
+                        "weight({}:{}^{} in {})",
+                        &self.field,
+                        String::from_utf8_lossy(&term.bytes),
+                        term.boost,
+                        doc
+                    ),
+                    vec![],
+                ));
+            }
+        }
+        if details.is_empty() {
+            Ok(Explanation::new(
+                false,
+                0.0f32,
+                "no matching term".to_string(),
+                vec![],
+            ))
+        } else {
+            Ok(Explanation::new(
+                true,
+                details.iter().map(|e| e.value()).sum(),
+                format!("weight({} in {}), sum of:", self, doc),
+                details,
+            ))
+        }
+    }
+}
+
+impl<C: Codec> fmt::Display for SpanMultiTermWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanMultiTermWeight(field: {}, query: {}, max_expansions: {})",
+            &self.field, &self.matcher, self.max_expansions
+        )
+    }
+}
+
+/// An expanded term, a greater `ScoreTerm` is a better candidate.
+struct ScoreTerm {
+    boost: f32,
+    doc_freq: i32,
+    bytes: Vec<u8>,
+}
+
+impl Ord for ScoreTerm {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.boost
+            .partial_cmp(&other.boost)
+            .unwrap_or(Ordering::Equal)
+            .then(self.doc_freq.cmp(&other.doc_freq))
+            .then_with(|| other.bytes.cmp(&self.bytes))
+    }
+}
+
+impl PartialOrd for ScoreTerm {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScoreTerm {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScoreTerm {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::search::collector::TopDocsCollector;
+    use core::search::query::spans::{SpanNearQuery, SpanQueryEnum};
+    use core::search::query::tests::{count_hits, index_texts};
+    use core::search::query::SynonymQuery;
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    fn multi(matcher: MultiTermMatcher) -> SpanQueryEnum {
+        SpanQueryEnum::MultiTerm(SpanMultiTermQueryWrapper::new("body".into(), matcher))
+    }
+
+    #[test]
+    fn test_matchers() {
+        let fuzzy = MultiTermMatcher::fuzzy("software", 2, 2).unwrap();
+        assert_eq!(fuzzy.common_prefix(), b"so".to_vec());
+        assert_eq!(fuzzy.accept(b"software"), Some(1.0));
+        assert!(fuzzy.accept(b"sofwtare").is_some());
+        assert!(fuzzy.accept(b"hardware").is_none());
+        assert!(fuzzy.accept(b"osftware").is_none());
+        assert!(MultiTermMatcher::fuzzy("software", 3, 0).is_err());
+
+        let wildcard = MultiTermMatcher::wildcard("mi\\*cro?s*").unwrap();
+        assert_eq!(wildcard.common_prefix(), b"mi*cro".to_vec());
+        assert!(wildcard.accept(b"mi*cross").is_some());
+        assert!(wildcard.accept(b"micros").is_none());
+
+        let regexp = MultiTermMatcher::regexp("soft(ly|ware)").unwrap();
+        assert!(regexp.accept(b"softly").is_some());
+        assert!(regexp.accept(b"softer").is_none());
+        assert!(MultiTermMatcher::regexp("soft(").is_err());
+    }
+
+    #[test]
+    fn test_span_multi_term_query() {
+        let docs = [
+            "microsoft software engineer",
+            "micro soft drinks",
+            "microscope was softly placed",
+            "soft tissue under the microscope",
+        ];
+        let (_dir, reader) = index_texts("body", &docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        // micro* NEAR/3 soft*
+        let near = |in_order| {
+            SpanNearQuery::new(
+                vec![
+                    multi(MultiTermMatcher::prefix("micro")),
+                    multi(MultiTermMatcher::prefix("soft")),
+                ],
+                3,
+                in_order,
+            )
+            .unwrap()
+        };
+        assert_eq!(count(&near(true)), 3);
+        assert_eq!(count(&near(false)), 4);
+        let explanation = searcher.explain(&near(true), 2).unwrap();
+        assert!(explanation.is_match());
+
+        let query = multi(MultiTermMatcher::fuzzy("sofware", 1, 0).unwrap());
+        assert_eq!(count(&query), 1);
+        let explanation = searcher.explain(&query, 0).unwrap();
+        assert!(explanation.is_match());
+        assert!(explanation.value() > 0.0);
+
+        let query = multi(MultiTermMatcher::regexp("soft(ly|ware)").unwrap());
+        assert_eq!(count(&query), 2);
+
+        let query = multi(MultiTermMatcher::wildcard("s?ft*").unwrap());
+        assert_eq!(count(&query), 4);
+
+        // only the most frequent expansion "soft" is kept
+        let query = SpanMultiTermQueryWrapper::with_max_expansions(
+            "body".into(),
+            MultiTermMatcher::prefix("soft"),
+            1,
+        )
+        .unwrap();
+        assert_eq!(count(&query), 2);
+        assert!(SpanMultiTermQueryWrapper::with_max_expansions(
+            "body".into(),
+            MultiTermMatcher::prefix("soft"),
+            0
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_span_multi_term_scores() {
+        let docs = [
+            "microsoft windows",
+            "microscope lens",
+            "microsoft office",
+            "microsoft azure",
+            "soft drinks",
+            "sofa bed",
+        ];
+        let (_dir, reader) = index_texts("body", &docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let scores = |query: &dyn Query<_>| -> Vec<(DocId, f32)> {
+            let mut collector = TopDocsCollector::new(10);
+            searcher.search(query, &mut collector).unwrap();
+            let mut scores: Vec<(DocId, f32)> = collector
+                .top_docs()
+                .score_docs()
+                .iter()
+                .map(|d| (d.doc_id(), d.score()))
+                .collect();
+            scores.sort_by_key(|d| d.0);
+            scores
+        };
+        let term = |text: &str| Term::new("body".into(), text.as_bytes().to_vec());
+
+        // the expanded terms share their blended statistics, so the rare "microscope"
+        // doesn't score higher than the frequent "microsoft"
+        let prefix = scores(&multi(MultiTermMatcher::prefix("micro")));
+        assert_eq!(prefix.len(), 4);
+        for &(_, score) in &prefix {
+            assert!((score - prefix[0].1).abs() < 1e-6);
+        }
+        let synonym =
+            SynonymQuery::new(vec![term("microsoft"), term("microscope")], 1.0, None).unwrap();
+        assert!((scores(&synonym)[1].1 - prefix[1].1).abs() < 1e-6);
+        let rare = TermQuery::new(term("microscope"), 1.0, None);
+        assert!(scores(&rare)[0].1 > prefix[1].1);
+
+        // "soft" is one edit away from "sofa", out of 4 characters
+        let fuzzy = multi(MultiTermMatcher::fuzzy("sofa", 1, 0).unwrap());
+        let fuzzy_scores = scores(&fuzzy);
+        assert_eq!(
+            fuzzy_scores.iter().map(|d| d.0).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert!((fuzzy_scores[0].1 - 0.75 * fuzzy_scores[1].1).abs() < 1e-6);
+        let explanation = searcher.explain(&fuzzy, 4).unwrap();
+        assert!((explanation.value() - fuzzy_scores[0].1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fuzzy_max_edits_boundary() {
+        let fuzzy = |text, max_edits| MultiTermMatcher::fuzzy(text, max_edits, 0).unwrap();
+        assert!(MultiTermMatcher::fuzzy("software", MAX_FUZZY_EDITS + 1, 0).is_err());
+
+        let at_max = fuzzy("software", MAX_FUZZY_EDITS);
+        assert_eq!(at_max.accept(b"software"), Some(1.0));
+        assert_eq!(at_max.accept(b"softwr"), Some(1.0 - 2.0 / 6.0));
+        assert!(at_max.accept(b"sftwr").is_none());
+        // adjacent transpositions are single edits
+        assert!(at_max.accept(b"osftawre").is_some());
+        assert!(at_max.accept(b"osftawer").is_none());
+        assert!(at_max.accept(b"softwarexy").is_some());
+        assert!(at_max.accept(b"softwarexyz").is_none());
+
+        let exact = fuzzy("software", 0);
+        assert_eq!(exact.accept(b"software"), Some(1.0));
+        assert!(exact.accept(b"softwarr").is_none());
+
+        // a prefix longer than the text only requires the whole text as a prefix
+        let prefixed = MultiTermMatcher::fuzzy("soft", MAX_FUZZY_EDITS, 10).unwrap();
+        assert_eq!(prefixed.common_prefix(), b"soft".to_vec());
+        assert!(prefixed.accept(b"softly").is_some());
+        assert!(prefixed.accept(b"sofa").is_none());
+
+        let docs = ["microsoft software engineer", "micro soft drinks"];
+        let (_dir, reader) = index_texts("body", &docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+        // "sotfwre" is a transposition and an insertion away from "software"
+        assert_eq!(count(&multi(fuzzy("sotfwre", MAX_FUZZY_EDITS))), 1);
+        assert_eq!(count(&multi(fuzzy("sotfwre", MAX_FUZZY_EDITS - 1))), 0);
+    }
+}
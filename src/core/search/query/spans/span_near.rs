@@ -123,6 +123,18 @@ impl SpanNearQuery {
         })
     }
 
+    pub fn clauses(&self) -> &[SpanQueryEnum] {
+        &self.clauses
+    }
+
+    pub fn slop(&self) -> i32 {
+        self.slop
+    }
+
+    pub fn is_in_order(&self) -> bool {
+        self.in_order
+    }
+
     fn merge_idf_ctx(
         ctx1: Option<KeyedContext>,
         ctx2: Option<KeyedContext>,
@@ -864,6 +876,10 @@ impl SpanGapQuery {
     pub fn new(field: String, width: i32) -> Self {
         SpanGapQuery { field, width }
     }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
 }
 
 impl<C: Codec> Query<C> for SpanGapQuery {
@@ -0,0 +1,489 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecEnum, CodecPostingIterator, PostingIterator};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::spans::{
+    build_sim_weight, AcceptStatus, FilterSpans, PostingsFlag, SpanFilter, SpanQueryEnum,
+    SpanWeightEnum, Spans, SpansEnum, NO_MORE_POSITIONS,
+};
+use core::search::query::spans::{SpanQuery, SpanWeight};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{
+    query::Query, query::TermQuery, query::Weight, scorer::Scorer, similarity::SimWeight,
+    DocIterator,
+};
+use core::util::{DocId, KeyedContext};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+const SPAN_NOT_QUERY: &str = "span_not";
+
+/// Removes matches which overlap with another SpanQuery or which are
+/// within x tokens before or y tokens after another SpanQuery.
+pub struct SpanNotQuery {
+    include: Box<SpanQueryEnum>,
+    exclude: Box<SpanQueryEnum>,
+    pre: i32,
+    post: i32,
+}
+
+impl SpanNotQuery {
+    /// Construct a SpanNotQuery matching spans from `include` which
+    /// have no overlap with spans from `exclude`.
+    pub fn new(include: SpanQueryEnum, exclude: SpanQueryEnum) -> Result<Self> {
+        Self::with_distance(include, exclude, 0, 0)
+    }
+
+    /// Construct a SpanNotQuery matching spans from `include` which
+    /// have no overlap with spans from `exclude` within
+    /// `pre` tokens before or `post` tokens of `include`. Inversely,
+    /// negative values for `pre` and/or `post` allow a certain amount of
+    /// overlap to occur.
+    pub fn with_distance(
+        include: SpanQueryEnum,
+        exclude: SpanQueryEnum,
+        pre: i32,
+        post: i32,
+    ) -> Result<Self> {
+        if SpanQuery::<CodecEnum>::field(&include) != SpanQuery::<CodecEnum>::field(&exclude) {
+            bail!(ErrorKind::IllegalArgument(
+                "Clauses must have same field.".into()
+            ));
+        }
+        Ok(SpanNotQuery {
+            include: Box::new(include),
+            exclude: Box::new(exclude),
+            pre,
+            post,
+        })
+    }
+
+    /// Return the SpanQuery whose matches are filtered.
+    pub fn include(&self) -> &SpanQueryEnum {
+        &self.include
+    }
+
+    /// Return the SpanQuery whose matches must not overlap those returned.
+    pub fn exclude(&self) -> &SpanQueryEnum {
+        &self.exclude
+    }
+
+    pub fn pre(&self) -> i32 {
+        self.pre
+    }
+
+    pub fn post(&self) -> i32 {
+        self.post
+    }
+
+    fn span_not_weight<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<SpanNotWeight<C>> {
+        let include_weight = self.include.span_weight(searcher, needs_scores)?;
+        let exclude_weight = self.exclude.span_weight(searcher, false)?;
+        SpanNotWeight::new(self, include_weight, exclude_weight, searcher, needs_scores)
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanNotQuery {
+    type Weight = SpanNotWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.span_not_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        SpanQuery::<C>::field(self.include.as_ref())
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.include.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for SpanNotQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(self.span_not_weight(searcher, needs_scores)?))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.include.as_ref())
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SpanNotQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanNotQuery(include: {}, exclude: {}, pre: {}, post: {})",
+            &self.include, &self.exclude, self.pre, self.post
+        )
+    }
+}
+
+pub struct SpanNotWeight<C: Codec> {
+    sim_weight: Option<Box<dyn SimWeight<C>>>,
+    include_weight: Box<SpanWeightEnum<C>>,
+    exclude_weight: Box<SpanWeightEnum<C>>,
+    pre: i32,
+    post: i32,
+}
+
+impl<C: Codec> SpanNotWeight<C> {
+    pub fn new<IS: SearchPlanBuilder<C> + ?Sized>(
+        query: &SpanNotQuery,
+        include_weight: SpanWeightEnum<C>,
+        exclude_weight: SpanWeightEnum<C>,
+        searcher: &IS,
+        needs_scores: bool,
+    ) -> Result<Self> {
+        let mut terms = Vec::new();
+        if needs_scores {
+            include_weight.extract_term_keys(&mut terms);
+        }
+        let sim_weight = build_sim_weight(
+            SpanQuery::<C>::field(query),
+            searcher,
+            terms,
+            SpanQuery::<C>::ctx(query),
+        )?;
+        Ok(SpanNotWeight {
+            sim_weight,
+            include_weight: Box::new(include_weight),
+            exclude_weight: Box::new(exclude_weight),
+            pre: query.pre,
+            post: query.post,
+        })
+    }
+}
+
+impl<C: Codec> SpanWeight<C> for SpanNotWeight<C> {
+    fn sim_weight(&self) -> Option<&dyn SimWeight<C>> {
+        self.sim_weight.as_ref().map(|x| &**x)
+    }
+
+    fn sim_weight_mut(&mut self) -> Option<&mut dyn SimWeight<C>> {
+        if let Some(ref mut sim_weight) = self.sim_weight {
+            Some(sim_weight.as_mut())
+        } else {
+            None
+        }
+    }
+
+    fn get_spans(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Option<SpansEnum<CodecPostingIterator<C>>>> {
+        let include_spans = match self.include_weight.get_spans(reader, required_postings)? {
+            Some(spans) => spans,
+            None => {
+                return Ok(None);
+            }
+        };
+        if let Some(exclude_spans) = self.exclude_weight.get_spans(reader, required_postings)? {
+            let filter = SpanNotFilter::new(exclude_spans, self.pre, self.post);
+            Ok(Some(SpansEnum::Not(Box::new(FilterSpans::new(
+                include_spans,
+                filter,
+            )))))
+        } else {
+            Ok(Some(include_spans))
+        }
+    }
+
+    fn extract_term_keys(&self, terms: &mut Vec<Term>) {
+        self.include_weight.extract_term_keys(terms)
+    }
+}
+
+impl<C: Codec> Weight<C> for SpanNotWeight<C> {
+    fn create_scorer(&self, ctx: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        self.do_create_scorer(ctx)
+    }
+
+    fn query_type(&self) -> &'static str {
+        SPAN_NOT_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.do_normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.do_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        self.explain_span(reader, doc)
+    }
+}
+
+impl<C: Codec> fmt::Display for SpanNotWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanNotWeight(include: {}, exclude: {}, pre: {}, post: {})",
+            &self.include_weight, &self.exclude_weight, self.pre, self.post
+        )
+    }
+}
+
+pub type SpanNotSpans<P> = FilterSpans<P, SpanNotFilter<P>>;
+
+/// Rejects the include spans which are within `pre` tokens before or `post`
+/// tokens after a span of the exclude spans.
+pub struct SpanNotFilter<P: PostingIterator> {
+    exclude_spans: SpansEnum<P>,
+    pre: i32,
+    post: i32,
+    last_approx_doc: DocId,
+    last_approx_result: bool,
+}
+
+impl<P: PostingIterator> SpanNotFilter<P> {
+    pub fn new(exclude_spans: SpansEnum<P>, pre: i32, post: i32) -> Self {
+        SpanNotFilter {
+            exclude_spans,
+            pre,
+            post,
+            last_approx_doc: -1,
+            last_approx_result: false,
+        }
+    }
+}
+
+impl<P: PostingIterator> SpanFilter<P> for SpanNotFilter<P> {
+    fn accept(&mut self, candidate: &SpansEnum<P>) -> Result<AcceptStatus> {
+        let doc = candidate.doc_id();
+        let two_phase = self.exclude_spans.support_two_phase();
+        if doc > self.exclude_spans.doc_id() {
+            // catch up 'exclude' to the current doc
+            if two_phase {
+                if self.exclude_spans.approximate_advance(doc)? == doc {
+                    self.last_approx_doc = doc;
+                    self.last_approx_result = self.exclude_spans.matches()?;
+                }
+            } else {
+                self.exclude_spans.advance(doc)?;
+            }
+        } else if two_phase && doc == self.exclude_spans.doc_id() && doc != self.last_approx_doc {
+            // exclude_spans already sitting on our candidate doc, but matches not called yet.
+            self.last_approx_doc = doc;
+            self.last_approx_result = self.exclude_spans.matches()?;
+        }
+
+        if doc != self.exclude_spans.doc_id()
+            || (two_phase && doc == self.last_approx_doc && !self.last_approx_result)
+        {
+            return Ok(AcceptStatus::Yes);
+        }
+
+        // init exclude start position if needed
+        if self.exclude_spans.start_position() == -1 {
+            self.exclude_spans.next_start_position()?;
+        }
+
+        while self.exclude_spans.end_position() <= candidate.start_position() - self.pre {
+            // exclude end position is before a possible exclusion
+            if self.exclude_spans.next_start_position()? == NO_MORE_POSITIONS {
+                // no more exclude at current doc.
+                return Ok(AcceptStatus::Yes);
+            }
+        }
+
+        // exclude end position far enough in current doc, check start position:
+        if self.exclude_spans.start_position() - self.post >= candidate.end_position() {
+            Ok(AcceptStatus::Yes)
+        } else {
+            Ok(AcceptStatus::No)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::search::query::spans::{
+        FieldMaskingSpanQuery, SpanContainingQuery, SpanNearQuery, SpanPositionRangeQuery,
+        SpanTermQuery, SpanWithinQuery,
+    };
+    use core::search::query::tests::{count_hits, index_docs, index_texts};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    fn span_term(field: &str, text: &str) -> SpanQueryEnum {
+        SpanQueryEnum::Term(SpanTermQuery::new(
+            Term::new(field.into(), text.as_bytes().to_vec()),
+            None,
+        ))
+    }
+
+    fn span_near(clauses: Vec<SpanQueryEnum>, slop: i32, in_order: bool) -> SpanQueryEnum {
+        SpanQueryEnum::Near(SpanNearQuery::new(clauses, slop, in_order).unwrap())
+    }
+
+    #[test]
+    fn test_span_position_filters() {
+        let docs = [
+            ("the quick brown fox jumps", "james jones"),
+            ("the quick red fox jumps", "james smith sally jones"),
+            ("a fox is quick", "sally smith"),
+        ];
+        let docs = docs
+            .iter()
+            .map(|&(title, name)| vec![("title", title), ("name", name)])
+            .collect();
+        let (_dir, reader) = index_docs(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        // "quick" not followed by "brown" within one position
+        let query = SpanNotQuery::with_distance(
+            span_term("title", "quick"),
+            span_term("title", "brown"),
+            0,
+            1,
+        )
+        .unwrap();
+        assert_eq!(count(&query), 2);
+        let explanation = searcher.explain(&query, 1).unwrap();
+        assert!(explanation.is_match());
+        let explanation = searcher.explain(&query, 0).unwrap();
+        assert!(!explanation.is_match());
+
+        // "fox" within the first three positions
+        let query = SpanPositionRangeQuery::first(span_term("title", "fox"), 3).unwrap();
+        assert_eq!(count(&query), 1);
+        let query = SpanPositionRangeQuery::new(span_term("title", "fox"), 3, 4).unwrap();
+        assert_eq!(count(&query), 2);
+
+        // "quick ... fox" spans containing "red"
+        let big = span_near(
+            vec![span_term("title", "quick"), span_term("title", "fox")],
+            1,
+            true,
+        );
+        let query = SpanContainingQuery::new(big, span_term("title", "red")).unwrap();
+        assert_eq!(count(&query), 1);
+
+        // "brown" or "red" within "quick ... fox"
+        let big = span_near(
+            vec![span_term("title", "quick"), span_term("title", "fox")],
+            1,
+            true,
+        );
+        let query = SpanWithinQuery::new(big, span_term("title", "brown")).unwrap();
+        assert_eq!(count(&query), 1);
+        let explanation = searcher.explain(&query, 0).unwrap();
+        assert!(explanation.is_match());
+
+        // title terms immediately followed by name "jones", across the two fields
+        let masked = FieldMaskingSpanQuery::new(span_term("name", "jones"), "title".into());
+        let query = SpanNearQuery::new(
+            vec![
+                span_term("title", "quick"),
+                SpanQueryEnum::FieldMasking(masked),
+            ],
+            0,
+            true,
+        )
+        .unwrap();
+        assert_eq!(count(&query), 0);
+        let masked = FieldMaskingSpanQuery::new(span_term("name", "jones"), "title".into());
+        let query = SpanNearQuery::new(
+            vec![
+                span_term("title", "the"),
+                SpanQueryEnum::FieldMasking(masked),
+            ],
+            0,
+            true,
+        )
+        .unwrap();
+        assert_eq!(count(&query), 1);
+    }
+
+    #[test]
+    fn test_slop_and_position_gaps() {
+        let titles = [
+            "the quick brown fox jumps",
+            "the quick red fox jumps",
+            "a fox is quick",
+        ];
+        let (_dir, reader) = index_texts("title", &titles);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |query: &dyn Query<_>| count_hits(&searcher, query);
+
+        // "fox" is one position after "quick" in the first two docs, and two
+        // positions before it in the last one
+        let not_fox = |pre, post| {
+            SpanNotQuery::with_distance(
+                span_term("title", "quick"),
+                span_term("title", "fox"),
+                pre,
+                post,
+            )
+            .unwrap()
+        };
+        assert_eq!(count(&not_fox(0, 0)), 3);
+        assert_eq!(count(&not_fox(0, 1)), 3);
+        assert_eq!(count(&not_fox(0, 2)), 1);
+        assert_eq!(count(&not_fox(1, 2)), 1);
+        assert_eq!(count(&not_fox(2, 2)), 0);
+
+        // both ends of the range are inclusive, on the start and end of the spans
+        let fox_in =
+            |start, end| SpanPositionRangeQuery::new(span_term("title", "fox"), start, end);
+        assert_eq!(count(&fox_in(0, 3).unwrap()), 1);
+        assert_eq!(count(&fox_in(2, 4).unwrap()), 2);
+        assert_eq!(count(&fox_in(3, 3).unwrap()), 0);
+        assert!(fox_in(4, 3).is_err());
+        assert!(fox_in(-1, 3).is_err());
+
+        // the "quick ... fox" spans need a slop of one to skip the color
+        for &(slop, hits) in &[(0, 0), (1, 1), (2, 1)] {
+            let big = span_near(
+                vec![span_term("title", "quick"), span_term("title", "fox")],
+                slop,
+                true,
+            );
+            let query = SpanContainingQuery::new(big, span_term("title", "red")).unwrap();
+            assert_eq!(count(&query), hits);
+        }
+    }
+}
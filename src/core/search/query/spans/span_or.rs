@@ -62,6 +62,10 @@ impl SpanOrQuery {
         Ok(SpanOrQuery { field, clauses })
     }
 
+    pub fn clauses(&self) -> &[SpanQueryEnum] {
+        &self.clauses
+    }
+
     fn span_or_weight<C: Codec>(
         &self,
         searcher: &dyn SearchPlanBuilder<C>,
@@ -180,13 +184,7 @@ impl<C: Codec> SpanWeight<C> for SpanOrWeight<C> {
             return Ok(sub_spans.pop());
         }
 
-        let capasity = sub_spans.len();
-        let by_doc_queue = DisiPriorityQueue::new(sub_spans);
-
-        Ok(Some(SpansEnum::Or(SpanOrSpans::new(
-            by_doc_queue,
-            BinaryHeap::with_capacity(capasity),
-        ))))
+        Ok(Some(SpansEnum::Or(SpanOrSpans::new(sub_spans))))
     }
 
     fn extract_term_keys(&self, terms: &mut Vec<Term>) {
@@ -247,10 +245,9 @@ pub struct SpanOrSpans<P: PostingIterator> {
 unsafe impl<P: PostingIterator> Send for SpanOrSpans<P> {}
 
 impl<P: PostingIterator> SpanOrSpans<P> {
-    fn new(
-        by_doc_queue: DisiPriorityQueue<SpansEnum<P>>,
-        by_position_queue: BinaryHeap<SpansElement<P>>,
-    ) -> Self {
+    pub fn new(sub_spans: Vec<SpansEnum<P>>) -> Self {
+        let by_position_queue = BinaryHeap::with_capacity(sub_spans.len());
+        let by_doc_queue = DisiPriorityQueue::new(sub_spans);
         let (positions_cost, match_cost, cost) = {
             let mut sum_position_cost = 0.0f32;
             let mut sum_approx_cost = 0.0f32;
@@ -0,0 +1,277 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecPostingIterator, PostingIterator};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::spans::{
+    build_sim_weight, AcceptStatus, FilterSpans, PostingsFlag, SpanFilter, SpanQueryEnum,
+    SpanWeightEnum, Spans, SpansEnum,
+};
+use core::search::query::spans::{SpanQuery, SpanWeight};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{
+    query::Query, query::TermQuery, query::Weight, scorer::Scorer, similarity::SimWeight,
+};
+use core::util::{DocId, KeyedContext};
+
+use error::{ErrorKind, Result};
+
+use std::fmt;
+
+const SPAN_POSITION_RANGE_QUERY: &str = "span_position_range";
+
+/// Checks to see if the match of the wrapped `SpanQuery` lies between a start
+/// and end position.
+///
+/// The span first query of Lucene, which matches spans near the beginning of a field,
+/// is a special case where the start is 0, see `SpanPositionRangeQuery::first`.
+pub struct SpanPositionRangeQuery {
+    match_query: Box<SpanQueryEnum>,
+    start: i32,
+    end: i32,
+}
+
+impl SpanPositionRangeQuery {
+    /// Matches spans of `match_query` which start at or after `start`
+    /// and end at or before `end`.
+    pub fn new(match_query: SpanQueryEnum, start: i32, end: i32) -> Result<Self> {
+        if start < 0 || end < start {
+            bail!(ErrorKind::IllegalArgument(format!(
+                "invalid position range [{}, {}]",
+                start, end
+            )));
+        }
+        Ok(SpanPositionRangeQuery {
+            match_query: Box::new(match_query),
+            start,
+            end,
+        })
+    }
+
+    /// Construct a span first query matching spans in `match_query` whose end
+    /// position is less than or equal to `end`.
+    pub fn first(match_query: SpanQueryEnum, end: i32) -> Result<Self> {
+        Self::new(match_query, 0, end)
+    }
+
+    /// The wrapped `SpanQuery` whose matches are filtered.
+    pub fn match_query(&self) -> &SpanQueryEnum {
+        &self.match_query
+    }
+
+    /// The minimum position permitted in a match
+    pub fn start(&self) -> i32 {
+        self.start
+    }
+
+    /// The maximum end position permitted in a match.
+    pub fn end(&self) -> i32 {
+        self.end
+    }
+
+    fn span_position_range_weight<C: Codec>(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<SpanPositionRangeWeight<C>> {
+        let match_weight = self.match_query.span_weight(searcher, needs_scores)?;
+        SpanPositionRangeWeight::new(self, match_weight, searcher, needs_scores)
+    }
+}
+
+impl<C: Codec> SpanQuery<C> for SpanPositionRangeQuery {
+    type Weight = SpanPositionRangeWeight<C>;
+
+    fn span_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Self::Weight> {
+        self.span_position_range_weight(searcher, needs_scores)
+    }
+
+    fn field(&self) -> &str {
+        SpanQuery::<C>::field(self.match_query.as_ref())
+    }
+
+    fn ctx(&self) -> Option<KeyedContext> {
+        SpanQuery::<C>::ctx(self.match_query.as_ref())
+    }
+}
+
+impl<C: Codec> Query<C> for SpanPositionRangeQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(
+            self.span_position_range_weight(searcher, needs_scores)?,
+        ))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        Query::<C>::extract_terms(self.match_query.as_ref())
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SpanPositionRangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start == 0 {
+            write!(
+                f,
+                "SpanFirstQuery(match: {}, end: {})",
+                &self.match_query, self.end
+            )
+        } else {
+            write!(
+                f,
+                "SpanPositionRangeQuery(match: {}, start: {}, end: {})",
+                &self.match_query, self.start, self.end
+            )
+        }
+    }
+}
+
+pub struct SpanPositionRangeWeight<C: Codec> {
+    sim_weight: Option<Box<dyn SimWeight<C>>>,
+    match_weight: Box<SpanWeightEnum<C>>,
+    start: i32,
+    end: i32,
+}
+
+impl<C: Codec> SpanPositionRangeWeight<C> {
+    pub fn new<IS: SearchPlanBuilder<C> + ?Sized>(
+        query: &SpanPositionRangeQuery,
+        match_weight: SpanWeightEnum<C>,
+        searcher: &IS,
+        needs_scores: bool,
+    ) -> Result<Self> {
+        let mut terms = Vec::new();
+        if needs_scores {
+            match_weight.extract_term_keys(&mut terms);
+        }
+        let sim_weight = build_sim_weight(
+            SpanQuery::<C>::field(query),
+            searcher,
+            terms,
+            SpanQuery::<C>::ctx(query),
+        )?;
+        Ok(SpanPositionRangeWeight {
+            sim_weight,
+            match_weight: Box::new(match_weight),
+            start: query.start,
+            end: query.end,
+        })
+    }
+}
+
+impl<C: Codec> SpanWeight<C> for SpanPositionRangeWeight<C> {
+    fn sim_weight(&self) -> Option<&dyn SimWeight<C>> {
+        self.sim_weight.as_ref().map(|x| &**x)
+    }
+
+    fn sim_weight_mut(&mut self) -> Option<&mut dyn SimWeight<C>> {
+        if let Some(ref mut sim_weight) = self.sim_weight {
+            Some(sim_weight.as_mut())
+        } else {
+            None
+        }
+    }
+
+    fn get_spans(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        required_postings: &PostingsFlag,
+    ) -> Result<Option<SpansEnum<CodecPostingIterator<C>>>> {
+        if let Some(spans) = self.match_weight.get_spans(reader, required_postings)? {
+            let filter = SpanPositionRangeFilter {
+                start: self.start,
+                end: self.end,
+            };
+            Ok(Some(SpansEnum::PositionRange(Box::new(FilterSpans::new(
+                spans, filter,
+            )))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn extract_term_keys(&self, terms: &mut Vec<Term>) {
+        self.match_weight.extract_term_keys(terms)
+    }
+}
+
+impl<C: Codec> Weight<C> for SpanPositionRangeWeight<C> {
+    fn create_scorer(&self, ctx: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        self.do_create_scorer(ctx)
+    }
+
+    fn query_type(&self) -> &'static str {
+        SPAN_POSITION_RANGE_QUERY
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.do_normalize(norm, boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.do_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        self.explain_span(reader, doc)
+    }
+}
+
+impl<C: Codec> fmt::Display for SpanPositionRangeWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SpanPositionRangeWeight(match: {}, start: {}, end: {})",
+            &self.match_weight, self.start, self.end
+        )
+    }
+}
+
+pub type SpanPositionRangeSpans<P> = FilterSpans<P, SpanPositionRangeFilter>;
+
+/// Accepts the spans which lie in `[start, end]`.
+pub struct SpanPositionRangeFilter {
+    start: i32,
+    end: i32,
+}
+
+impl<P: PostingIterator> SpanFilter<P> for SpanPositionRangeFilter {
+    fn accept(&mut self, candidate: &SpansEnum<P>) -> Result<AcceptStatus> {
+        debug_assert_ne!(candidate.start_position(), candidate.end_position());
+        let res = if candidate.start_position() >= self.end {
+            AcceptStatus::NoMoreInCurrentDoc
+        } else if candidate.start_position() >= self.start && candidate.end_position() <= self.end {
+            AcceptStatus::Yes
+        } else {
+            AcceptStatus::No
+        };
+        Ok(res)
+    }
+}
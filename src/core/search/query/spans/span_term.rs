@@ -210,7 +210,7 @@ impl<T: PostingIterator> Spans for TermSpans<T> {
 
 /// A guess of the relative cost of dealing with the term positions
 /// when using a SpanNearQuery instead of a PhraseQuery.
-const PHRASE_TO_SPAN_TERM_POSITIONS_COST: f32 = 4.0;
+pub(crate) const PHRASE_TO_SPAN_TERM_POSITIONS_COST: f32 = 4.0;
 const TERM_POSNS_SEEK_OPS_PER_DOC: i32 = 128;
 const TERM_OPS_PER_POS: i32 = 7;
 
@@ -234,7 +234,7 @@ impl<C: Codec> SpanTermWeight<C> {
         })
     }
 
-    fn term_positions_cost(terms_iter: &mut impl TermIterator) -> Result<f32> {
+    pub(crate) fn term_positions_cost(terms_iter: &mut impl TermIterator) -> Result<f32> {
         let doc_freq = terms_iter.doc_freq()?;
         assert!(doc_freq > 0);
         let total_term_freq = terms_iter.total_term_freq()?; // -1 when not available
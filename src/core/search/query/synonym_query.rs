@@ -0,0 +1,258 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use core::codec::{Codec, CodecPostingIterator};
+use core::codec::{PostingIterator, PostingIteratorFlags};
+use core::doc::Term;
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, UnionPostingIterator, Weight};
+use core::search::scorer::{Scorer, TermScorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::similarity::{BM25Similarity, SimWeight, Similarity};
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::DocIterator;
+use core::util::{DocId, KeyedContext};
+use error::{ErrorKind, Result};
+
+pub const SYNONYM: &str = "synonym";
+
+/// A query that treats multiple terms as synonyms.
+///
+/// For scoring purposes, this query tries to score the terms as if you
+/// had indexed them as one term: it will match any of the terms but
+/// only invoke the similarity a single time, scoring the sum of all
+/// term frequencies for the document, with the doc freq and total term freq
+/// blended by `BM25Similarity::blend_term_stats`.
+#[derive(Clone, Debug)]
+pub struct SynonymQuery {
+    field: String,
+    terms: Vec<Term>,
+    boost: f32,
+    ctx: Option<KeyedContext>,
+}
+
+impl SynonymQuery {
+    pub fn new<S: Into<Option<KeyedContext>>>(
+        terms: Vec<Term>,
+        boost: f32,
+        ctx: S,
+    ) -> Result<SynonymQuery> {
+        if terms.is_empty() {
+            bail!(ErrorKind::IllegalArgument(
+                "synonym query terms should not be empty!".into()
+            ));
+        }
+        let field = terms[0].field.clone();
+        if terms.iter().any(|t| t.field != field) {
+            bail!(ErrorKind::IllegalArgument(
+                "Synonyms must be across the same field".into()
+            ));
+        }
+        let mut terms = terms;
+        terms.sort_by(|t1, t2| t1.bytes.cmp(&t2.bytes));
+        terms.dedup();
+        Ok(SynonymQuery {
+            field,
+            terms,
+            boost,
+            ctx: ctx.into(),
+        })
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+}
+
+impl<C: Codec> Query<C> for SynonymQuery {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let max_doc = i64::from(searcher.max_doc());
+        // pseudo term made of the first synonym, it is only used for explain
+        let pseudo_term = self.terms[0].bytes.clone();
+        let term_stats = if needs_scores {
+            let mut stats = Vec::with_capacity(self.terms.len());
+            for term in &self.terms {
+                stats.push(searcher.term_statistics(term)?);
+            }
+            BM25Similarity::blend_term_stats(pseudo_term, &stats)
+        } else {
+            TermStatistics::new(pseudo_term, max_doc, -1)
+        };
+
+        let collection_stats = if needs_scores {
+            if let Some(stat) = searcher.collections_statistics(&self.field) {
+                stat.clone()
+            } else {
+                CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+            }
+        } else {
+            CollectionStatistics::new(self.field.clone(), 0, max_doc, -1, -1, -1)
+        };
+
+        let similarity = searcher.similarity(&self.field, needs_scores);
+        let sim_weight = similarity.compute_weight(
+            &collection_stats,
+            &[term_stats],
+            self.ctx.as_ref(),
+            self.boost,
+        );
+        Ok(Box::new(SynonymWeight {
+            field: self.field.clone(),
+            terms: self.terms.clone(),
+            boost: self.boost,
+            similarity,
+            sim_weight,
+            needs_scores,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.terms
+            .iter()
+            .map(|t| TermQuery::new(t.clone(), self.boost, self.ctx.clone()))
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn (::std::any::Any) {
+        self
+    }
+}
+
+impl fmt::Display for SynonymQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| t.text().unwrap_or_else(|_| format!("{:?}", &t.bytes)))
+            .collect();
+        write!(
+            f,
+            "SynonymQuery(field: {}, terms: [{}], boost: {})",
+            &self.field,
+            terms.join(", "),
+            self.boost
+        )
+    }
+}
+
+struct SynonymWeight<C: Codec> {
+    field: String,
+    terms: Vec<Term>,
+    boost: f32,
+    similarity: Box<dyn Similarity<C>>,
+    sim_weight: Box<dyn SimWeight<C>>,
+    needs_scores: bool,
+}
+
+impl<C: Codec> SynonymWeight<C> {
+    fn union_postings(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<UnionPostingIterator<CodecPostingIterator<C>>>> {
+        let flags = if self.needs_scores {
+            PostingIteratorFlags::FREQS
+        } else {
+            PostingIteratorFlags::NONE
+        };
+        let mut subs = Vec::with_capacity(self.terms.len());
+        for term in &self.terms {
+            if let Some(postings) = reader.reader.postings(term, i32::from(flags))? {
+                subs.push(postings);
+            }
+        }
+        if subs.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(UnionPostingIterator::new(subs)))
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for SynonymWeight<C> {
+    fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(postings) = self.union_postings(reader)? {
+            let sim_scorer = self.sim_weight.sim_scorer(reader.reader)?;
+            Ok(Some(Box::new(TermScorer::new(sim_scorer, postings))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        SYNONYM
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.sim_weight.normalize(norm, boost * self.boost)
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        if let Some(mut postings) = self.union_postings(reader)? {
+            if postings.advance(doc)? == doc {
+                let freq = postings.freq()? as f32;
+                let freq_expl = Explanation::new(true, freq, format!("termFreq={}", freq), vec![]);
+                let score_expl = self.sim_weight.explain(reader.reader, doc, freq_expl)?;
+
+                return Ok(Explanation::new(
+                    true,
+                    score_expl.value(),
+                    format!(
+                        "weight({} in {}) [{}], result of:",
+                        self, doc, self.similarity
+                    ),
+                    vec![score_expl],
+                ));
+            }
+        }
+        Ok(Explanation::new(
+            false,
+            0f32,
+            "no matching term".to_string(),
+            vec![],
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for SynonymWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| t.text().unwrap_or_else(|_| format!("{:?}", &t.bytes)))
+            .collect();
+        write!(
+            f,
+            "SynonymWeight(field: {}, terms: [{}], boost: {}, similarity: {}, need_score: {})",
+            &self.field,
+            terms.join(", "),
+            self.boost,
+            &self.similarity,
+            self.needs_scores
+        )
+    }
+}
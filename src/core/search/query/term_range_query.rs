@@ -0,0 +1,357 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, PostingIteratorFlags, SeekStatus, TermIterator, Terms};
+use core::index::reader::LeafReaderContext;
+use core::search::explanation::Explanation;
+use core::search::query::{Query, TermQuery, Weight};
+use core::search::scorer::{ConstantScoreScorer, Scorer};
+use core::search::searcher::SearchPlanBuilder;
+use core::search::{DocIdSet, DocIterator};
+use core::util::{DocId, DocIdSetBuilder};
+
+use error::Result;
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt;
+
+pub const TERM_RANGE: &str = "term_range";
+
+/// A `Query` that matches documents within a range of terms.
+///
+/// This query matches the documents looking for terms that fall into the supplied
+/// range according to the byte order of the terms, which is the lexicographic order
+/// of the strings for UTF-8 terms. It is not intended for numerical ranges; use
+/// `PointRangeQuery` instead.
+///
+/// The matching documents all get a constant score.
+pub struct TermRangeQuery {
+    field: String,
+    lower_term: Option<Vec<u8>>,
+    upper_term: Option<Vec<u8>>,
+    include_lower: bool,
+    include_upper: bool,
+}
+
+impl TermRangeQuery {
+    /// Constructs a query selecting all terms greater/equal than `lower_term`
+    /// but less/equal than `upper_term`.
+    ///
+    /// If a bound is `None`, the range is open on that side and the matching
+    /// `include_*` flag is ignored.
+    pub fn new(
+        field: String,
+        lower_term: Option<Vec<u8>>,
+        upper_term: Option<Vec<u8>>,
+        include_lower: bool,
+        include_upper: bool,
+    ) -> TermRangeQuery {
+        TermRangeQuery {
+            field,
+            include_lower: include_lower && lower_term.is_some(),
+            include_upper: include_upper && upper_term.is_some(),
+            lower_term,
+            upper_term,
+        }
+    }
+
+    /// Factory that creates a new `TermRangeQuery` using strings for term text.
+    pub fn new_string_range(
+        field: String,
+        lower_term: Option<&str>,
+        upper_term: Option<&str>,
+        include_lower: bool,
+        include_upper: bool,
+    ) -> TermRangeQuery {
+        Self::new(
+            field,
+            lower_term.map(|t| t.as_bytes().to_vec()),
+            upper_term.map(|t| t.as_bytes().to_vec()),
+            include_lower,
+            include_upper,
+        )
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn lower_term(&self) -> Option<&[u8]> {
+        self.lower_term.as_deref()
+    }
+
+    pub fn upper_term(&self) -> Option<&[u8]> {
+        self.upper_term.as_deref()
+    }
+
+    pub fn include_lower(&self) -> bool {
+        self.include_lower
+    }
+
+    pub fn include_upper(&self) -> bool {
+        self.include_upper
+    }
+}
+
+impl<C: Codec> Query<C> for TermRangeQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(TermRangeWeight {
+            field: self.field.clone(),
+            lower_term: self.lower_term.clone(),
+            upper_term: self.upper_term.clone(),
+            include_lower: self.include_lower,
+            include_upper: self.include_upper,
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for TermRangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TermRangeQuery(field: {}, range: {})",
+            &self.field,
+            format_range(
+                &self.lower_term,
+                &self.upper_term,
+                self.include_lower,
+                self.include_upper
+            )
+        )
+    }
+}
+
+fn format_range(
+    lower_term: &Option<Vec<u8>>,
+    upper_term: &Option<Vec<u8>>,
+    include_lower: bool,
+    include_upper: bool,
+) -> String {
+    let bound = |term: &Option<Vec<u8>>| match term {
+        Some(term) => String::from_utf8_lossy(term).into_owned(),
+        None => "*".to_string(),
+    };
+    format!(
+        "{}{} TO {}{}",
+        if include_lower { '[' } else { '{' },
+        bound(lower_term),
+        bound(upper_term),
+        if include_upper { ']' } else { '}' }
+    )
+}
+
+struct TermRangeWeight {
+    field: String,
+    lower_term: Option<Vec<u8>>,
+    upper_term: Option<Vec<u8>>,
+    include_lower: bool,
+    include_upper: bool,
+    weight: f32,
+    norm: f32,
+}
+
+impl TermRangeWeight {
+    /// Whether `term` is beyond the upper bound of the range.
+    fn above_upper(&self, term: &[u8]) -> bool {
+        match self.upper_term {
+            Some(ref upper) => match term.cmp(upper) {
+                Ordering::Greater => true,
+                Ordering::Equal => !self.include_upper,
+                Ordering::Less => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Seeks `terms_iter` to the first term of the range.
+    fn first_term(&self, terms_iter: &mut impl TermIterator) -> Result<Option<Vec<u8>>> {
+        match self.lower_term {
+            Some(ref lower) => match terms_iter.seek_ceil(lower)? {
+                SeekStatus::End => Ok(None),
+                SeekStatus::Found if !self.include_lower => terms_iter.next(),
+                _ => Ok(Some(terms_iter.term()?.to_vec())),
+            },
+            None => terms_iter.next(),
+        }
+    }
+}
+
+impl<C: Codec> Weight<C> for TermRangeWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        let leaf_reader = leaf_reader_ctx.reader;
+        let terms = match leaf_reader.terms(&self.field)? {
+            Some(terms) => terms,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let mut terms_iter = terms.iterator()?;
+        let mut term = self.first_term(&mut terms_iter)?;
+        let mut result = DocIdSetBuilder::from_terms(leaf_reader.max_doc(), &terms)?;
+        let mut matched = false;
+        while let Some(bytes) = term {
+            if self.above_upper(&bytes) {
+                break;
+            }
+            let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+            result.add(&mut postings)?;
+            matched = true;
+            term = terms_iter.next()?;
+        }
+        if !matched {
+            return Ok(None);
+        }
+
+        if let Some(iterator) = result.build().iterator()? {
+            let cost = iterator.cost();
+            Ok(Some(Box::new(ConstantScoreScorer::new(
+                self.weight,
+                iterator,
+                cost,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        TERM_RANGE
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matches = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matches {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.weight / self.norm, "boost".into(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".into(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("{} doesn't match id {}", self, doc),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for TermRangeWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TermRangeWeight(field: {}, range: {})",
+            &self.field,
+            format_range(
+                &self.lower_term,
+                &self.upper_term,
+                self.include_lower,
+                self.include_upper
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::doc::Fieldable;
+    use core::search::query::tests::{count_hits, index_fields, new_keyword_field};
+    use core::search::{DefaultIndexSearcher, IndexSearcher};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_term_range_query() {
+        let mut docs = vec![];
+        for name in &["alice", "bob", "carol", "dave", "mallory", "trent"] {
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(new_keyword_field("name", name))];
+            docs.push(doc);
+        }
+        let (_dir, reader) = index_fields(docs);
+        let searcher = DefaultIndexSearcher::new(Arc::new(reader), None);
+        let count = |lower, upper, include_lower, include_upper| -> usize {
+            let query = TermRangeQuery::new_string_range(
+                "name".into(),
+                lower,
+                upper,
+                include_lower,
+                include_upper,
+            );
+            count_hits(&searcher, &query)
+        };
+
+        assert_eq!(count(Some("bob"), Some("dave"), true, true), 3);
+        assert_eq!(count(Some("bob"), Some("dave"), false, true), 2);
+        assert_eq!(count(Some("bob"), Some("dave"), true, false), 2);
+        assert_eq!(count(Some("bob"), Some("dave"), false, false), 1);
+        // the bounds do not have to be indexed terms
+        assert_eq!(count(Some("a"), Some("m"), true, false), 4);
+        assert_eq!(count(None, Some("carol"), true, true), 3);
+        assert_eq!(count(Some("m"), None, true, true), 2);
+        assert_eq!(count(None, None, true, true), 6);
+        assert_eq!(count(Some("x"), None, true, true), 0);
+        assert_eq!(count(Some("dave"), Some("bob"), true, true), 0);
+        assert_eq!(count(Some("bob"), Some("bob"), true, false), 0);
+
+        let query =
+            TermRangeQuery::new_string_range("name".into(), Some("a"), Some("m"), true, false);
+        assert_eq!(
+            format!("{}", &query),
+            "TermRangeQuery(field: name, range: [a TO m})"
+        );
+        assert!(searcher.explain(&query, 1).unwrap().is_match());
+        assert!(!searcher.explain(&query, 5).unwrap().is_match());
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A JSON query DSL, modeled after the elasticsearch one.
+//!
+//! The supported queries are `match_all`, `term`, `terms`, `match`, `match_phrase`,
+//! `bool`, `dis_max`, `range`, `exists`, `boosting`, `constant_score` and the span
+//! queries `span_term`, `span_near`, `span_or`, `span_not`, `span_first`,
+//! `span_containing`, `span_within`, `span_multi`, `span_gap` and
+//! `field_masking_span`, e.g.
+//!
+//! ```json
+//! {"bool": {
+//!     "must": {"match": {"title": {"query": "quick fox", "operator": "and"}}},
+//!     "filter": [{"range": {"price": {"gte": 10, "lt": 20}}}],
+//!     "must_not": {"term": {"status": "deleted"}}
+//! }}
+//! ```
+//!
+//! Invalid documents are reported with the path of the offending value, like
+//! `[query.bool.filter[0].range.price.gte] expected an integer, got "ten"`.
+
+mod parser;
+
+mod writer;
+
+use core::codec::Codec;
+use core::search::query::{whitespace_analyzer, PointValueType, Query, QueryAnalyzer};
+
+use error::{ErrorKind, Result};
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+use serde_json::{self, Value};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::result;
+
+/// The path of the root of a query document in the error messages.
+const ROOT: &str = "query";
+
+/// Converts JSON documents to `Query` trees and `Query` trees back to JSON.
+///
+/// As the JSON does not tell how the fields are indexed, the point fields must be
+/// declared with `with_point_field` for `term`, `terms`, `match` and `range` queries
+/// to search them with point queries, the `range` queries on the other fields being
+/// `TermRangeQuery`s. The text of `match` and `match_phrase` queries
+/// is analyzed with the `QueryAnalyzer` of the field.
+pub struct QueryDsl {
+    point_fields: HashMap<String, PointValueType>,
+    analyzers: HashMap<String, QueryAnalyzer>,
+    default_analyzer: QueryAnalyzer,
+}
+
+impl Default for QueryDsl {
+    fn default() -> Self {
+        QueryDsl {
+            point_fields: HashMap::new(),
+            analyzers: HashMap::new(),
+            default_analyzer: whitespace_analyzer(),
+        }
+    }
+}
+
+impl QueryDsl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Search `field` as a point field holding values of type `value_type`.
+    pub fn with_point_field(mut self, field: &str, value_type: PointValueType) -> Self {
+        self.point_fields.insert(field.to_string(), value_type);
+        self
+    }
+
+    /// Analyze the text searched in `field` with `analyzer`.
+    pub fn with_analyzer(mut self, field: &str, analyzer: QueryAnalyzer) -> Self {
+        self.analyzers.insert(field.to_string(), analyzer);
+        self
+    }
+
+    /// Analyze the text searched in the fields without analyzer with `analyzer`.
+    pub fn with_default_analyzer(mut self, analyzer: QueryAnalyzer) -> Self {
+        self.default_analyzer = analyzer;
+        self
+    }
+
+    /// Parse a JSON query document.
+    pub fn parse<C: Codec>(&self, json: &str) -> Result<Box<dyn Query<C>>> {
+        let value: Value = serde_json::from_str(json)?;
+        self.parse_value(&value)
+    }
+
+    /// Convert an already parsed JSON query document.
+    pub fn parse_value<C: Codec>(&self, value: &Value) -> Result<Box<dyn Query<C>>> {
+        self.parse_query(value, ROOT)
+    }
+
+    /// A `DeserializeSeed` reading a query with this configuration, to deserialize
+    /// the queries embedded in larger documents.
+    pub fn seed<C: Codec>(&self) -> QuerySeed<'_, C> {
+        QuerySeed {
+            dsl: self,
+            _codec: PhantomData,
+        }
+    }
+
+    /// Convert `query` to its JSON representation.
+    ///
+    /// The boosts of the `BoostQuery`s are folded in the queries they wrap, and a
+    /// `PhraseQuery` is written as a `match_phrase` of its terms separated by spaces,
+    /// which only gives the same query back with the whitespace analyzer.
+    pub fn to_value<C: Codec>(query: &dyn Query<C>) -> Result<Value> {
+        writer::query_to_value(query, ROOT)
+    }
+
+    /// Convert `query` to a JSON string.
+    pub fn to_json<C: Codec>(query: &dyn Query<C>) -> Result<String> {
+        Ok(serde_json::to_string(&Self::to_value(query)?)?)
+    }
+}
+
+/// Deserializes a query with the configuration of a `QueryDsl`.
+pub struct QuerySeed<'a, C: Codec> {
+    dsl: &'a QueryDsl,
+    _codec: PhantomData<C>,
+}
+
+impl<'de, 'a, C: Codec> DeserializeSeed<'de> for QuerySeed<'a, C> {
+    type Value = Box<dyn Query<C>>;
+
+    fn deserialize<D>(self, deserializer: D) -> result::Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        self.dsl
+            .parse_value(&value)
+            .map_err(|e| de::Error::custom(message(&e)))
+    }
+}
+
+/// Serializes a query to its JSON representation.
+pub struct QueryJson<'a, C: Codec>(pub &'a dyn Query<C>);
+
+impl<'a, C: Codec> Serialize for QueryJson<'a, C> {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        QueryDsl::to_value(self.0)
+            .map_err(|e| ser::Error::custom(message(&e)))?
+            .serialize(serializer)
+    }
+}
+
+impl<'a, C: Codec> fmt::Display for QueryJson<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match QueryDsl::to_json(self.0) {
+            Ok(json) => write!(f, "{}", json),
+            Err(_) => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// The error for the invalid value at `path`.
+fn invalid<T: fmt::Display>(path: &str, msg: T) -> ::error::Error {
+    ErrorKind::IllegalArgument(format!("[{}] {}", path, msg)).into()
+}
+
+/// The message of `e`, without the kind of error for the argument errors.
+fn message(e: &::error::Error) -> String {
+    match e.kind() {
+        ErrorKind::IllegalArgument(msg) => msg.clone(),
+        _ => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+
+    fn dsl() -> QueryDsl {
+        QueryDsl::new()
+            .with_point_field("price", PointValueType::Long)
+            .with_point_field("rating", PointValueType::Float)
+            .with_point_field("ip", PointValueType::InetAddress)
+    }
+
+    fn parse(json: &str) -> String {
+        dsl().parse::<TestCodec>(json).unwrap().to_string()
+    }
+
+    fn error(json: &str) -> String {
+        match dsl().parse::<TestCodec>(json) {
+            Ok(q) => panic!("expected an error, got {}", q),
+            Err(e) => message(&e),
+        }
+    }
+
+    fn term(field: &str, text: &str) -> String {
+        format!("TermQuery(field: {}, term: {}, boost: 1)", field, text)
+    }
+
+    /// Parse `json`, write the query back and check that it gives `expected`.
+    fn round_trip(json: &str, expected: &str) {
+        let query = dsl().parse::<TestCodec>(json).unwrap();
+        let written = QueryDsl::to_json(query.as_ref()).unwrap();
+        assert_eq!(written, expected);
+        let reparsed = dsl().parse::<TestCodec>(&written).unwrap();
+        assert_eq!(reparsed.to_string(), query.to_string());
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        assert_eq!(parse(r#"{"term": {"title": "fox"}}"#), term("title", "fox"));
+        assert_eq!(
+            parse(r#"{"term": {"title": {"value": "fox", "boost": 2}}}"#),
+            format!("BoostQuery(query: {}, boost: 2)", term("title", "fox"))
+        );
+        assert_eq!(
+            parse(r#"{"match": {"title": "quick fox"}}"#),
+            format!(
+                "BooleanQuery(must: [], should: [{}, {}], filters: [], must_not: [], match: 1)",
+                term("title", "quick"),
+                term("title", "fox")
+            )
+        );
+        assert_eq!(
+            parse(r#"{"match": {"title": {"query": "quick fox", "operator": "and"}}}"#),
+            format!(
+                "BooleanQuery(must: [{}, {}], should: [], filters: [], must_not: [], match: 0)",
+                term("title", "quick"),
+                term("title", "fox")
+            )
+        );
+        assert!(
+            parse(r#"{"match_phrase": {"title": {"query": "quick fox", "slop": 1}}}"#)
+                .starts_with("PhraseQuery(field: title")
+        );
+        assert_eq!(
+            parse(
+                r#"{"bool": {"must": {"term": {"a": "x"}}, "must_not": [{"term": {"b": "y"}}]}}"#
+            ),
+            format!(
+                "BooleanQuery(must: [{}], should: [], filters: [], must_not: [{}], match: 0)",
+                term("a", "x"),
+                term("b", "y")
+            )
+        );
+        assert_eq!(parse(r#"{"bool": {}}"#), "MatchAllDocsQuery()");
+        assert_eq!(
+            parse(r#"{"range": {"price": {"gt": 10, "lte": 20}}}"#),
+            "PointRangeQuery(field: price, type:long, num_dims: 1, bytes_per_dim: 8, lower: [11], \
+             upper: [20])"
+        );
+        assert_eq!(
+            parse(r#"{"term": {"price": 10}}"#),
+            "PointRangeQuery(field: price, type:long, num_dims: 1, bytes_per_dim: 8, lower: [10], \
+             upper: [10])"
+        );
+        assert_eq!(
+            parse(r#"{"term": {"ip": "10.1.0.0/16"}}"#),
+            "PointRangeQuery(field: ip, type:inet, num_dims: 1, bytes_per_dim: 16, lower: \
+             [10.1.0.0], upper: [10.1.255.255])"
+        );
+        assert_eq!(
+            parse(r#"{"constant_score": {"filter": {"exists": {"field": "title"}}}}"#),
+            "ConstantScoreQuery(sub_query: ExistsQuery(field=title), weight: 1)"
+        );
+        assert_eq!(
+            parse(
+                r#"{"span_near": {"clauses": [{"span_term": {"title": "quick"}},
+                    {"span_multi": {"match": {"prefix": {"title": "fo"}}}}], "slop": 2}}"#
+            ),
+            "SpanQueryEnum(SpanNearQuery(clauses: [SpanQueryEnum(SpanTermQuery(field: title, \
+             term: quick)), SpanQueryEnum(SpanMultiTermQueryWrapper(field: title, query: fo*, \
+             max_expansions: 1024))], slop: 2, in_order: true))"
+        );
+    }
+
+    #[test]
+    fn test_query_dsl_errors() {
+        assert_eq!(
+            error(r#"{"bool": {"must": [{"term": {"a": "x"}}, {"trem": {"a": "x"}}]}}"#),
+            "[query.bool.must[1]] unknown query 'trem'"
+        );
+        assert_eq!(
+            error(r#"{"range": {"price": {"gte": "ten"}}}"#),
+            "[query.range.price.gte] expected an integer, got \"ten\""
+        );
+        assert_eq!(
+            error(r#"{"range": {"title": {"gte": "a", "gt": "b"}}}"#),
+            "[query.range.title] gt and gte can not be both given"
+        );
+        assert_eq!(
+            error(r#"{"range": {"rating": {"gt": 1e300}}}"#),
+            "[query.range.rating.gt] 1e300 is out of the float range"
+        );
+        assert_eq!(
+            error(r#"{"term": {"rating": "-1e39"}}"#),
+            "[query.term.rating] \"-1e39\" is out of the float range"
+        );
+        assert_eq!(
+            error(r#"{"term": {"ip": "10.0.0.0/33"}}"#),
+            "[query.term.ip] illegal prefix_length '33' for 10.0.0.0, must be 0-32"
+        );
+        assert_eq!(
+            error(r#"{"dis_max": {"queries": [], "tie_breaker": 0.5}}"#),
+            "[query.dis_max.queries] expected at least one query"
+        );
+        assert_eq!(
+            error(r#"{"term": {"a": "x", "b": "y"}}"#),
+            "[query.term] expected a single field, got 'a' and 'b'"
+        );
+        assert_eq!(
+            error(r#"{"bool": {"must": {"term": {"a": "x"}}, "mus_not": []}}"#),
+            "[query.bool] unknown field 'mus_not', expected one of: must, should, filter, \
+             must_not, minimum_should_match, boost"
+        );
+        assert_eq!(
+            error(r#"{"span_near": {"clauses": [{"term": {"a": "x"}}]}}"#),
+            "[query.span_near.clauses[0]] expected a span query, got 'term'"
+        );
+        assert_eq!(
+            error(
+                r#"{"span_or": {"clauses": [{"span_term": {"a": "x"}}, {"span_term": {"b": "y"}}]}}"#
+            ),
+            "[query.span_or] Clauses must have same field."
+        );
+        assert!(dsl().parse::<TestCodec>("{\"term\": ").is_err());
+    }
+
+    #[test]
+    fn test_query_dsl_round_trip() {
+        round_trip(
+            r#"{"term":{"title":{"value":"fox","boost":2.5}}}"#,
+            r#"{"term":{"title":{"boost":2.5,"value":"fox"}}}"#,
+        );
+        round_trip(
+            r#"{"bool":{"must":[{"term":{"a":"x"}},{"term":{"b":"y"}}],"must_not":{"term":{"c":"z"}},"minimum_should_match":0}}"#,
+            r#"{"bool":{"must":[{"term":{"a":"x"}},{"term":{"b":"y"}}],"must_not":[{"term":{"c":"z"}}]}}"#,
+        );
+        round_trip(
+            r#"{"dis_max":{"queries":[{"term":{"a":"x"}},{"match_phrase":{"b":"y z"}}],"tie_breaker":0.3}}"#,
+            r#"{"dis_max":{"queries":[{"term":{"a":"x"}},{"match_phrase":{"b":{"query":"y z"}}}],"tie_breaker":0.3}}"#,
+        );
+        round_trip(
+            r#"{"boosting":{"positive":{"term":{"a":"x"}},"negative":{"term":{"a":"y"}},"negative_boost":0.2}}"#,
+            r#"{"boosting":{"negative":{"term":{"a":"y"}},"negative_boost":0.2,"positive":{"term":{"a":"x"}}}}"#,
+        );
+        round_trip(
+            r#"{"range":{"rating":{"gte":1.5}}}"#,
+            r#"{"range":{"rating":{"gte":1.5}}}"#,
+        );
+        // the infinite bounds are kept, unless the range is open on their side
+        round_trip(
+            r#"{"range":{"rating":{"gt":3.4028235e38}}}"#,
+            r#"{"term":{"rating":"inf"}}"#,
+        );
+        round_trip(
+            r#"{"range":{"rating":{"gte":"-inf","lt":"inf"}}}"#,
+            r#"{"range":{"rating":{"lte":3.4028235e38}}}"#,
+        );
+        round_trip(
+            r#"{"range":{"rating":{"gte":"-Infinity","lte":"-inf"}}}"#,
+            r#"{"term":{"rating":"-inf"}}"#,
+        );
+        round_trip(
+            r#"{"range":{"title":{"gt":"a","lte":"m","boost":2}}}"#,
+            r#"{"range":{"title":{"boost":2.0,"gt":"a","lte":"m"}}}"#,
+        );
+        round_trip(
+            r#"{"range":{"price":{"gte":1,"lt":10,"boost":3}}}"#,
+            r#"{"range":{"price":{"boost":3.0,"gte":1,"lte":9}}}"#,
+        );
+        round_trip(
+            r#"{"range":{"ip":{"gt":"192.168.0.255","lt":"2001:db8::"}}}"#,
+            r#"{"range":{"ip":{"gte":"192.168.1.0","lte":"2001:db7:ffff:ffff:ffff:ffff:ffff:ffff"}}}"#,
+        );
+        round_trip(
+            r#"{"span_not":{"include":{"span_term":{"t":"a"}},"exclude":{"span_or":{"clauses":[{"span_term":{"t":"b"}},{"span_gap":{"t":2}}]}},"dist":1}}"#,
+            r#"{"span_not":{"exclude":{"span_or":{"clauses":[{"span_term":{"t":"b"}},{"span_gap":{"t":2}}]}},"include":{"span_term":{"t":"a"}},"post":1,"pre":1}}"#,
+        );
+        round_trip(
+            r#"{"span_first":{"match":{"span_multi":{"match":{"fuzzy":{"t":{"value":"fox","fuzziness":1}}}}},"end":3,"boost":2}}"#,
+            r#"{"span_first":{"boost":2.0,"end":3,"match":{"span_multi":{"match":{"fuzzy":{"t":{"fuzziness":1,"prefix_length":0,"value":"fox"}}}}}}}"#,
+        );
+
+        let query = dsl()
+            .parse::<TestCodec>(r#"{"match_all": {"boost": 2}}"#)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&QueryJson(query.as_ref())).unwrap(),
+            r#"{"match_all":{"boost":2.0}}"#
+        );
+        let query: Box<dyn Query<TestCodec>> = dsl()
+            .seed()
+            .deserialize(&mut serde_json::Deserializer::from_str(
+                r#"{"exists": {"field": "title"}}"#,
+            ))
+            .unwrap();
+        assert_eq!(query.to_string(), "ExistsQuery(field=title)");
+    }
+}
@@ -0,0 +1,1062 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::doc::Term;
+use core::search::query::spans::{
+    FieldMaskingSpanQuery, MultiTermMatcher, SpanBoostQuery, SpanBoostQueryEnum,
+    SpanContainingQuery, SpanGapQuery, SpanMultiTermQueryWrapper, SpanNearQuery, SpanNotQuery,
+    SpanOrQuery, SpanPositionRangeQuery, SpanQueryEnum, SpanTermQuery, SpanWithinQuery,
+    DEFAULT_MAX_EXPANSIONS, MAX_FUZZY_EDITS,
+};
+use core::search::query::{
+    BooleanQuery, BoostQuery, BoostingQuery, ConstantScoreQuery, DisjunctionMaxQuery, DoublePoint,
+    ExistsQuery, FloatPoint, InetAddressPoint, IntPoint, LongPoint, MatchAllDocsQuery, Operator,
+    PhraseQuery, PointValueType, Query, TermQuery, TermRangeQuery,
+};
+use core::search::query_dsl::{invalid, message, QueryDsl};
+
+use error::Result;
+
+use serde_json::{Map, Value};
+
+use std::net::IpAddr;
+use std::{f32, f64, i32, i64};
+
+const BOOL_FIELDS: &[&str] = &[
+    "must",
+    "should",
+    "filter",
+    "must_not",
+    "minimum_should_match",
+    "boost",
+];
+
+const SPAN_QUERIES: &[&str] = &[
+    "span_term",
+    "span_gap",
+    "span_near",
+    "span_or",
+    "span_not",
+    "span_first",
+    "span_containing",
+    "span_within",
+    "span_multi",
+    "field_masking_span",
+];
+
+impl QueryDsl {
+    pub(super) fn parse_query<C: Codec>(
+        &self,
+        value: &Value,
+        path: &str,
+    ) -> Result<Box<dyn Query<C>>> {
+        let (kind, body) = single_entry(value, path, "query")?;
+        if is_span(kind) {
+            return Ok(Box::new(self.parse_span(value, path)?));
+        }
+        let query_path = path;
+        let path = &format!("{}.{}", path, kind);
+        match kind {
+            "match_all" => {
+                let body = object(body, path)?;
+                check_fields(body, &["boost"], path)?;
+                Ok(boosted(
+                    Box::new(MatchAllDocsQuery),
+                    f32_field(body, "boost", path)?,
+                ))
+            }
+            "term" => self.parse_term(body, path),
+            "terms" => self.parse_terms(body, path),
+            "match" => self.parse_match(body, path),
+            "match_phrase" => self.parse_match_phrase(body, path),
+            "bool" => self.parse_bool(body, path),
+            "dis_max" => {
+                let body = object(body, path)?;
+                check_fields(body, &["queries", "tie_breaker", "boost"], path)?;
+                let queries_path = format!("{}.queries", path);
+                let queries =
+                    self.parse_queries(required(body, "queries", path)?, &queries_path)?;
+                if queries.is_empty() {
+                    return Err(invalid(&queries_path, "expected at least one query"));
+                }
+                let tie_breaker = f32_field(body, "tie_breaker", path)?.unwrap_or(0.0);
+                let query = checked(DisjunctionMaxQuery::build(queries, tie_breaker), path)?;
+                Ok(boosted(query, f32_field(body, "boost", path)?))
+            }
+            "range" => self.parse_range(body, path),
+            "exists" => {
+                let body = object(body, path)?;
+                check_fields(body, &["field", "boost"], path)?;
+                let field = string(required(body, "field", path)?, &format!("{}.field", path))?;
+                Ok(boosted(
+                    Box::new(ExistsQuery::build(field.to_string())),
+                    f32_field(body, "boost", path)?,
+                ))
+            }
+            "boosting" => {
+                let body = object(body, path)?;
+                check_fields(
+                    body,
+                    &["positive", "negative", "negative_boost", "boost"],
+                    path,
+                )?;
+                let positive = self.parse_query(
+                    required(body, "positive", path)?,
+                    &format!("{}.positive", path),
+                )?;
+                let negative = self.parse_query(
+                    required(body, "negative", path)?,
+                    &format!("{}.negative", path),
+                )?;
+                let negative_boost = match f32_field(body, "negative_boost", path)? {
+                    Some(boost) => boost,
+                    None => return Err(missing(path, "negative_boost")),
+                };
+                Ok(boosted(
+                    BoostingQuery::build(positive, negative, negative_boost),
+                    f32_field(body, "boost", path)?,
+                ))
+            }
+            "constant_score" => {
+                let body = object(body, path)?;
+                check_fields(body, &["filter", "boost"], path)?;
+                let filter =
+                    self.parse_query(required(body, "filter", path)?, &format!("{}.filter", path))?;
+                let boost = f32_field(body, "boost", path)?.unwrap_or(1.0);
+                Ok(Box::new(ConstantScoreQuery::with_boost(filter, boost)))
+            }
+            kind => Err(invalid(query_path, format!("unknown query '{}'", kind))),
+        }
+    }
+
+    /// Parse a query or an array of queries.
+    fn parse_queries<C: Codec>(&self, value: &Value, path: &str) -> Result<Vec<Box<dyn Query<C>>>> {
+        match value {
+            Value::Array(values) => values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| self.parse_query(v, &format!("{}[{}]", path, i)))
+                .collect(),
+            value => Ok(vec![self.parse_query(value, path)?]),
+        }
+    }
+
+    fn parse_term<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let (field, value, path, boost) = field_entry(body, path)?;
+        let (value, value_path, inner_boost) = match value {
+            Value::Object(map) => {
+                check_fields(map, &["value", "boost"], &path)?;
+                (
+                    required(map, "value", &path)?,
+                    format!("{}.value", path),
+                    f32_field(map, "boost", &path)?,
+                )
+            }
+            value => (value, path.clone(), None),
+        };
+        let query = self.term_query(field, value, &value_path)?;
+        Ok(boosted(boosted(query, boost), inner_boost))
+    }
+
+    fn parse_terms<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let (field, values, path, boost) = field_entry(body, path)?;
+        let values = match values {
+            Value::Array(values) if !values.is_empty() => values,
+            value => return Err(invalid(&path, expected("a non empty array", value))),
+        };
+        let mut queries = Vec::with_capacity(values.len());
+        for (i, value) in values.iter().enumerate() {
+            queries.push(self.term_query(field, value, &format!("{}[{}]", path, i))?);
+        }
+        let query = if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            checked(
+                BooleanQuery::build(vec![], queries, vec![], vec![], 0),
+                &path,
+            )?
+        };
+        Ok(Box::new(ConstantScoreQuery::with_boost(
+            query,
+            boost.unwrap_or(1.0),
+        )))
+    }
+
+    fn parse_match<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let (field, value, path, boost) = field_entry(body, path)?;
+        let mut operator = Operator::Or;
+        let mut min_should_match = 0;
+        let mut inner_boost = None;
+        let (text, text_path) = match value {
+            Value::Object(map) => {
+                check_fields(
+                    map,
+                    &["query", "operator", "minimum_should_match", "boost"],
+                    &path,
+                )?;
+                if let Some(value) = map.get("operator") {
+                    let operator_path = format!("{}.operator", path);
+                    operator = match string(value, &operator_path)?.to_lowercase().as_str() {
+                        "or" => Operator::Or,
+                        "and" => Operator::And,
+                        _ => return Err(invalid(&operator_path, expected("'and' or 'or'", value))),
+                    };
+                }
+                if let Some(value) = map.get("minimum_should_match") {
+                    min_should_match =
+                        min_should_match_value(value, &format!("{}.minimum_should_match", path))?;
+                }
+                inner_boost = f32_field(map, "boost", &path)?;
+                (required(map, "query", &path)?, format!("{}.query", path))
+            }
+            value => (value, path.clone()),
+        };
+        let query = self.match_query(field, text, &text_path, operator, min_should_match)?;
+        Ok(boosted(boosted(query, boost), inner_boost))
+    }
+
+    fn match_query<C: Codec>(
+        &self,
+        field: &str,
+        value: &Value,
+        path: &str,
+        operator: Operator,
+        min_should_match: i32,
+    ) -> Result<Box<dyn Query<C>>> {
+        if self.point_fields.contains_key(field) {
+            return self.term_query(field, value, path);
+        }
+        let text = scalar_text(value, path)?;
+        let mut queries: Vec<Box<dyn Query<C>>> = self
+            .analyze(field, &text)?
+            .into_iter()
+            .map(|(term, _)| term_query(field, term))
+            .collect();
+        match queries.len() {
+            0 => Err(invalid(
+                path,
+                format!("'{}' has no terms once analyzed", text),
+            )),
+            1 => Ok(queries.remove(0)),
+            _ => {
+                let query = match operator {
+                    Operator::Or => {
+                        BooleanQuery::build(vec![], queries, vec![], vec![], min_should_match)
+                    }
+                    Operator::And => BooleanQuery::build(queries, vec![], vec![], vec![], 0),
+                };
+                checked(query, path)
+            }
+        }
+    }
+
+    fn parse_match_phrase<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let (field, value, path, boost) = field_entry(body, path)?;
+        let (text, text_path, slop, inner_boost) = match value {
+            Value::Object(map) => {
+                check_fields(map, &["query", "slop", "boost"], &path)?;
+                (
+                    required(map, "query", &path)?,
+                    format!("{}.query", path),
+                    i32_field(map, "slop", &path)?.unwrap_or(0),
+                    f32_field(map, "boost", &path)?,
+                )
+            }
+            value => (value, path.clone(), 0, None),
+        };
+        if slop < 0 {
+            return Err(invalid(&format!("{}.slop", path), "slop must be >= 0"));
+        }
+        let text = scalar_text(text, &text_path)?;
+        let tokens = self.analyze(field, &text)?;
+        let query: Box<dyn Query<C>> = match tokens.len() {
+            0 => {
+                return Err(invalid(
+                    &text_path,
+                    format!("'{}' has no terms once analyzed", text),
+                ))
+            }
+            1 => term_query(field, tokens.into_iter().next().unwrap().0),
+            _ => {
+                let (terms, positions) = tokens
+                    .into_iter()
+                    .map(|(term, position)| (Term::new(field.to_string(), term), position))
+                    .unzip();
+                Box::new(checked(
+                    PhraseQuery::new(terms, positions, slop, None, None),
+                    &text_path,
+                )?)
+            }
+        };
+        Ok(boosted(boosted(query, boost), inner_boost))
+    }
+
+    fn parse_bool<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let body = object(body, path)?;
+        check_fields(body, BOOL_FIELDS, path)?;
+        let mut clauses = Vec::with_capacity(4);
+        for occur in &BOOL_FIELDS[..4] {
+            clauses.push(match body.get(*occur) {
+                Some(value) => self.parse_queries(value, &format!("{}.{}", path, occur))?,
+                None => vec![],
+            });
+        }
+        let min_should_match = match body.get("minimum_should_match") {
+            Some(value) => {
+                min_should_match_value(value, &format!("{}.minimum_should_match", path))?
+            }
+            None => 0,
+        };
+        let boost = f32_field(body, "boost", path)?;
+        if clauses.iter().all(Vec::is_empty) {
+            return Ok(boosted(Box::new(MatchAllDocsQuery), boost));
+        }
+        let must_nots = clauses.pop().unwrap();
+        let filters = clauses.pop().unwrap();
+        let shoulds = clauses.pop().unwrap();
+        let musts = clauses.pop().unwrap();
+        let query = checked(
+            BooleanQuery::build(musts, shoulds, filters, must_nots, min_should_match),
+            path,
+        )?;
+        Ok(boosted(query, boost))
+    }
+
+    fn parse_range<C: Codec>(&self, body: &Value, path: &str) -> Result<Box<dyn Query<C>>> {
+        let (field, value, path, boost) = field_entry(body, path)?;
+        let bounds = object(value, &path)?;
+        check_fields(bounds, &["gt", "gte", "lt", "lte", "boost"], &path)?;
+        let lower = bound(bounds, "gt", "gte", &path)?;
+        let upper = bound(bounds, "lt", "lte", &path)?;
+        let inner_boost = f32_field(bounds, "boost", &path)?;
+        let value_type = match self.point_fields.get(field) {
+            Some(value_type) => *value_type,
+            None => {
+                let mut texts = Vec::with_capacity(2);
+                for bound in &[&lower, &upper] {
+                    texts.push(match bound {
+                        Some((value, path, _)) => Some(scalar_text(value, path)?.into_bytes()),
+                        None => None,
+                    });
+                }
+                let upper_text = texts.pop().unwrap();
+                let lower_text = texts.pop().unwrap();
+                let query = TermRangeQuery::new(
+                    field.to_string(),
+                    lower_text,
+                    upper_text,
+                    lower.map_or(false, |(_, _, inclusive)| inclusive),
+                    upper.map_or(false, |(_, _, inclusive)| inclusive),
+                );
+                return Ok(boosted(boosted(Box::new(query), boost), inner_boost));
+            }
+        };
+        let field = field.to_string();
+        let query = match value_type {
+            PointValueType::Integer => {
+                let (lower, upper) =
+                    integer_range(lower, upper, i64::from(i32::MIN), i64::from(i32::MAX))?;
+                IntPoint::new_range_query(field, lower as i32, upper as i32)
+            }
+            PointValueType::Long => {
+                let (lower, upper) = integer_range(lower, upper, i64::MIN, i64::MAX)?;
+                LongPoint::new_range_query(field, lower, upper)
+            }
+            PointValueType::Float => {
+                let lower = match lower {
+                    Some((value, path, true)) => float_point(value, &path)?,
+                    Some((value, path, false)) => FloatPoint::next_up(float_point(value, &path)?),
+                    None => f32::NEG_INFINITY,
+                };
+                let upper = match upper {
+                    Some((value, path, true)) => float_point(value, &path)?,
+                    Some((value, path, false)) => FloatPoint::next_down(float_point(value, &path)?),
+                    None => f32::INFINITY,
+                };
+                FloatPoint::new_range_query(field, lower, upper)
+            }
+            PointValueType::Double => {
+                let lower = match lower {
+                    Some((value, path, true)) => double_point(value, &path)?,
+                    Some((value, path, false)) => DoublePoint::next_up(double_point(value, &path)?),
+                    None => f64::NEG_INFINITY,
+                };
+                let upper = match upper {
+                    Some((value, path, true)) => double_point(value, &path)?,
+                    Some((value, path, false)) => {
+                        DoublePoint::next_down(double_point(value, &path)?)
+                    }
+                    None => f64::INFINITY,
+                };
+                DoublePoint::new_range_query(field, lower, upper)
+            }
+            PointValueType::InetAddress => {
+                let lower = match lower {
+                    Some((value, path, true)) => Some(inet_address(value, &path)?),
+                    Some((value, path, false)) => {
+                        InetAddressPoint::next_up(inet_address(value, &path)?)
+                    }
+                    None => Some(InetAddressPoint::MIN_VALUE),
+                };
+                let upper = match upper {
+                    Some((value, path, true)) => Some(inet_address(value, &path)?),
+                    Some((value, path, false)) => {
+                        InetAddressPoint::next_down(inet_address(value, &path)?)
+                    }
+                    None => Some(InetAddressPoint::MAX_VALUE),
+                };
+                match (lower, upper) {
+                    (Some(lower), Some(upper)) => {
+                        InetAddressPoint::new_range_query(field, lower, upper)
+                    }
+                    // an exclusive bound past the last address, nothing matches
+                    _ => InetAddressPoint::new_range_query(
+                        field,
+                        InetAddressPoint::MAX_VALUE,
+                        InetAddressPoint::MIN_VALUE,
+                    ),
+                }
+            }
+        };
+        let query = checked(query, &path)?;
+        Ok(boosted(boosted(query, boost), inner_boost))
+    }
+
+    /// The query matching `value` in `field`, a point query for the point fields.
+    fn term_query<C: Codec>(
+        &self,
+        field: &str,
+        value: &Value,
+        path: &str,
+    ) -> Result<Box<dyn Query<C>>> {
+        let value_type = match self.point_fields.get(field) {
+            Some(value_type) => *value_type,
+            None => {
+                return Ok(term_query(field, scalar_text(value, path)?.into_bytes()));
+            }
+        };
+        let field = field.to_string();
+        let query = match value_type {
+            PointValueType::Integer => IntPoint::new_exact_query(
+                field,
+                integer(value, path, i64::from(i32::MIN), i64::from(i32::MAX))? as i32,
+            ),
+            PointValueType::Long => {
+                LongPoint::new_exact_query(field, integer(value, path, i64::MIN, i64::MAX)?)
+            }
+            PointValueType::Float => FloatPoint::new_exact_query(field, float_point(value, path)?),
+            PointValueType::Double => {
+                DoublePoint::new_exact_query(field, double_point(value, path)?)
+            }
+            PointValueType::InetAddress => {
+                // a CIDR block such as `10.0.0.0/8` matches the addresses with its prefix
+                let text = string(value, path)?.trim();
+                match text.find('/') {
+                    Some(i) => {
+                        let prefix_length = match text[i + 1..].parse() {
+                            Ok(n) => n,
+                            Err(_) => {
+                                return Err(invalid(path, expected("a CIDR block", value)));
+                            }
+                        };
+                        InetAddressPoint::new_prefix_query(
+                            field,
+                            inet_address(&Value::from(&text[..i]), path)?,
+                            prefix_length,
+                        )
+                    }
+                    None => InetAddressPoint::new_exact_query(field, inet_address(value, path)?),
+                }
+            }
+        };
+        checked(query, path)
+    }
+
+    /// The terms of `text` analyzed for `field` with their positions.
+    fn analyze(&self, field: &str, text: &str) -> Result<Vec<(Vec<u8>, i32)>> {
+        let analyzer = self.analyzers.get(field).unwrap_or(&self.default_analyzer);
+        let mut token_stream = analyzer(text);
+        token_stream.reset()?;
+        let mut tokens = vec![];
+        let mut position = -1;
+        while token_stream.next_token()? {
+            let token = token_stream.token();
+            position += token.position as i32;
+            tokens.push((token.term.clone(), position.max(0)));
+        }
+        token_stream.end()?;
+        Ok(tokens)
+    }
+
+    fn parse_span(&self, value: &Value, path: &str) -> Result<SpanQueryEnum> {
+        let (kind, body) = single_entry(value, path, "span query")?;
+        if !is_span(kind) {
+            return Err(invalid(
+                path,
+                format!("expected a span query, got '{}'", kind),
+            ));
+        }
+        let path = format!("{}.{}", path, kind);
+        let path = path.as_str();
+        let (query, boost) = match kind {
+            "span_term" => {
+                let (field, value, path, boost) = field_entry(body, path)?;
+                let (value, value_path, inner_boost) = match value {
+                    Value::Object(map) => {
+                        check_fields(map, &["value", "boost"], &path)?;
+                        (
+                            required(map, "value", &path)?,
+                            format!("{}.value", path),
+                            f32_field(map, "boost", &path)?,
+                        )
+                    }
+                    value => (value, path.clone(), None),
+                };
+                let term = Term::new(
+                    field.to_string(),
+                    scalar_text(value, &value_path)?.into_bytes(),
+                );
+                let query = SpanQueryEnum::Term(SpanTermQuery::new(term, None));
+                (span_boosted(query, boost, &path)?, inner_boost)
+            }
+            "span_gap" => {
+                let (field, value, path, boost) = field_entry(body, path)?;
+                let width = integer(value, &path, 0, i64::from(i32::MAX))? as i32;
+                let query = SpanQueryEnum::Gap(SpanGapQuery::new(field.to_string(), width));
+                (query, boost)
+            }
+            "span_near" => {
+                let body = object(body, path)?;
+                check_fields(body, &["clauses", "slop", "in_order", "boost"], path)?;
+                let clauses = self.parse_span_clauses(body, path)?;
+                let slop = i32_field(body, "slop", path)?.unwrap_or(0);
+                let in_order = bool_field(body, "in_order", path)?.unwrap_or(true);
+                let query = checked(SpanNearQuery::new(clauses, slop, in_order), path)?;
+                (SpanQueryEnum::Near(query), f32_field(body, "boost", path)?)
+            }
+            "span_or" => {
+                let body = object(body, path)?;
+                check_fields(body, &["clauses", "boost"], path)?;
+                let clauses = self.parse_span_clauses(body, path)?;
+                let query = checked(SpanOrQuery::new(clauses), path)?;
+                (SpanQueryEnum::Or(query), f32_field(body, "boost", path)?)
+            }
+            "span_not" => {
+                let body = object(body, path)?;
+                check_fields(
+                    body,
+                    &["include", "exclude", "pre", "post", "dist", "boost"],
+                    path,
+                )?;
+                let include = self.parse_span(
+                    required(body, "include", path)?,
+                    &format!("{}.include", path),
+                )?;
+                let exclude = self.parse_span(
+                    required(body, "exclude", path)?,
+                    &format!("{}.exclude", path),
+                )?;
+                let (pre, post) = match i32_field(body, "dist", path)? {
+                    Some(dist) => {
+                        if body.contains_key("pre") || body.contains_key("post") {
+                            return Err(invalid(path, "dist can not be combined with pre or post"));
+                        }
+                        (dist, dist)
+                    }
+                    None => (
+                        i32_field(body, "pre", path)?.unwrap_or(0),
+                        i32_field(body, "post", path)?.unwrap_or(0),
+                    ),
+                };
+                let query = checked(
+                    SpanNotQuery::with_distance(include, exclude, pre, post),
+                    path,
+                )?;
+                (SpanQueryEnum::Not(query), f32_field(body, "boost", path)?)
+            }
+            "span_first" => {
+                let body = object(body, path)?;
+                check_fields(body, &["match", "end", "boost"], path)?;
+                let match_query =
+                    self.parse_span(required(body, "match", path)?, &format!("{}.match", path))?;
+                let end = match i32_field(body, "end", path)? {
+                    Some(end) => end,
+                    None => return Err(missing(path, "end")),
+                };
+                let query = checked(SpanPositionRangeQuery::first(match_query, end), path)?;
+                (
+                    SpanQueryEnum::PositionRange(query),
+                    f32_field(body, "boost", path)?,
+                )
+            }
+            "span_containing" | "span_within" => {
+                let body = object(body, path)?;
+                check_fields(body, &["big", "little", "boost"], path)?;
+                let big =
+                    self.parse_span(required(body, "big", path)?, &format!("{}.big", path))?;
+                let little =
+                    self.parse_span(required(body, "little", path)?, &format!("{}.little", path))?;
+                let query = if kind == "span_containing" {
+                    SpanQueryEnum::Containing(checked(SpanContainingQuery::new(big, little), path)?)
+                } else {
+                    SpanQueryEnum::Within(checked(SpanWithinQuery::new(big, little), path)?)
+                };
+                (query, f32_field(body, "boost", path)?)
+            }
+            "span_multi" => {
+                let body = object(body, path)?;
+                check_fields(body, &["match", "max_expansions", "boost"], path)?;
+                let (field, matcher, inner_boost) =
+                    parse_multi_term(required(body, "match", path)?, &format!("{}.match", path))?;
+                let max_expansions = match body.get("max_expansions") {
+                    Some(value) => integer(
+                        value,
+                        &format!("{}.max_expansions", path),
+                        1,
+                        i64::from(i32::MAX),
+                    )? as usize,
+                    None => DEFAULT_MAX_EXPANSIONS,
+                };
+                let query = checked(
+                    SpanMultiTermQueryWrapper::with_max_expansions(
+                        field.to_string(),
+                        matcher,
+                        max_expansions,
+                    ),
+                    path,
+                )?;
+                let query = span_boosted(SpanQueryEnum::MultiTerm(query), inner_boost, path)?;
+                (query, f32_field(body, "boost", path)?)
+            }
+            "field_masking_span" => {
+                let body = object(body, path)?;
+                check_fields(body, &["query", "field", "boost"], path)?;
+                let query =
+                    self.parse_span(required(body, "query", path)?, &format!("{}.query", path))?;
+                let field = string(required(body, "field", path)?, &format!("{}.field", path))?;
+                let query = SpanQueryEnum::FieldMasking(FieldMaskingSpanQuery::new(
+                    query,
+                    field.to_string(),
+                ));
+                (query, f32_field(body, "boost", path)?)
+            }
+            _ => unreachable!(),
+        };
+        span_boosted(query, boost, path)
+    }
+
+    fn parse_span_clauses(
+        &self,
+        body: &Map<String, Value>,
+        path: &str,
+    ) -> Result<Vec<SpanQueryEnum>> {
+        let clauses_path = format!("{}.clauses", path);
+        match required(body, "clauses", path)? {
+            Value::Array(values) => values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| self.parse_span(v, &format!("{}[{}]", clauses_path, i)))
+                .collect(),
+            value => Err(invalid(&clauses_path, expected("an array", value))),
+        }
+    }
+}
+
+fn is_span(kind: &str) -> bool {
+    SPAN_QUERIES.contains(&kind)
+}
+
+/// Parse the `prefix`, `wildcard`, `regexp` or `fuzzy` query of a `span_multi`.
+fn parse_multi_term<'a>(
+    value: &'a Value,
+    path: &str,
+) -> Result<(&'a str, MultiTermMatcher, Option<f32>)> {
+    let (kind, body) = single_entry(value, path, "multi term query")?;
+    match kind {
+        "prefix" | "wildcard" | "regexp" | "fuzzy" => {}
+        kind => {
+            return Err(invalid(
+                path,
+                format!(
+                    "expected a prefix, wildcard, regexp or fuzzy query, got '{}'",
+                    kind
+                ),
+            ));
+        }
+    }
+    let path = format!("{}.{}", path, kind);
+    let (field, value, path, boost) = field_entry(body, &path)?;
+    let mut fuzziness = MAX_FUZZY_EDITS;
+    let mut prefix_length = 0;
+    let (text, text_path, inner_boost) = match value {
+        Value::Object(map) => {
+            let fields: &[&str] = if kind == "fuzzy" {
+                &["value", "fuzziness", "prefix_length", "boost"]
+            } else {
+                &["value", "boost"]
+            };
+            check_fields(map, fields, &path)?;
+            let text_path = format!("{}.value", path);
+            let text = string(required(map, "value", &path)?, &text_path)?;
+            if let Some(value) = map.get("fuzziness") {
+                let fuzziness_path = format!("{}.fuzziness", path);
+                fuzziness = match value {
+                    Value::String(s) if s.eq_ignore_ascii_case("auto") => {
+                        match text.chars().count() {
+                            0..=2 => 0,
+                            3..=5 => 1,
+                            _ => 2,
+                        }
+                    }
+                    value => integer(value, &fuzziness_path, 0, i64::from(MAX_FUZZY_EDITS))? as u32,
+                };
+            }
+            if let Some(value) = map.get("prefix_length") {
+                prefix_length = integer(
+                    value,
+                    &format!("{}.prefix_length", path),
+                    0,
+                    i64::from(i32::MAX),
+                )? as usize;
+            }
+            (text, text_path, f32_field(map, "boost", &path)?)
+        }
+        value => (string(value, &path)?, path.clone(), None),
+    };
+    let matcher = match kind {
+        "prefix" => MultiTermMatcher::prefix(text),
+        "wildcard" => checked(MultiTermMatcher::wildcard(text), &text_path)?,
+        "regexp" => checked(MultiTermMatcher::regexp(text), &text_path)?,
+        _ => checked(
+            MultiTermMatcher::fuzzy(text, fuzziness, prefix_length),
+            &text_path,
+        )?,
+    };
+    let boost = match (boost, inner_boost) {
+        (Some(a), Some(b)) => Some(a * b),
+        (a, b) => a.or(b),
+    };
+    Ok((field, matcher, boost))
+}
+
+fn term_query<C: Codec>(field: &str, term: Vec<u8>) -> Box<dyn Query<C>> {
+    Box::new(TermQuery::new(
+        Term::new(field.to_string(), term),
+        1.0,
+        None,
+    ))
+}
+
+fn boosted<C: Codec>(query: Box<dyn Query<C>>, boost: Option<f32>) -> Box<dyn Query<C>> {
+    match boost {
+        Some(boost) => BoostQuery::build(query, boost),
+        None => query,
+    }
+}
+
+fn span_boosted(query: SpanQueryEnum, boost: Option<f32>, path: &str) -> Result<SpanQueryEnum> {
+    let boost = match boost {
+        Some(boost) if (boost - 1.0f32).abs() > f32::EPSILON => boost,
+        _ => return Ok(query),
+    };
+    let query = match query {
+        SpanQueryEnum::Term(q) => SpanBoostQueryEnum::Term(q),
+        SpanQueryEnum::Gap(q) => SpanBoostQueryEnum::Gap(q),
+        SpanQueryEnum::Or(q) => SpanBoostQueryEnum::Or(q),
+        SpanQueryEnum::Near(q) => SpanBoostQueryEnum::Near(q),
+        SpanQueryEnum::Not(q) => SpanBoostQueryEnum::Not(q),
+        SpanQueryEnum::PositionRange(q) => SpanBoostQueryEnum::PositionRange(q),
+        SpanQueryEnum::MultiTerm(q) => SpanBoostQueryEnum::MultiTerm(q),
+        SpanQueryEnum::Containing(q) => SpanBoostQueryEnum::Containing(q),
+        SpanQueryEnum::Within(q) => SpanBoostQueryEnum::Within(q),
+        SpanQueryEnum::Boost(q) => {
+            let boost = q.boost() * boost;
+            return span_boosted(q.query.into_span_query(), Some(boost), path);
+        }
+        SpanQueryEnum::FieldMasking(_) => {
+            return Err(invalid(path, "field_masking_span can not be boosted"));
+        }
+    };
+    Ok(SpanQueryEnum::Boost(SpanBoostQuery::new(query, boost)))
+}
+
+/// Attach the path to the errors of the query constructors.
+fn checked<T>(result: Result<T>, path: &str) -> Result<T> {
+    result.map_err(|e| invalid(path, message(&e)))
+}
+
+/// The only entry of `value`, the key being the type of a query.
+fn single_entry<'a>(value: &'a Value, path: &str, what: &str) -> Result<(&'a str, &'a Value)> {
+    let map = object(value, path)?;
+    let mut entries = map.iter();
+    match (entries.next(), entries.next()) {
+        (Some((key, value)), None) => Ok((key, value)),
+        (None, _) => Err(invalid(
+            path,
+            format!("expected a {}, got an empty object", what),
+        )),
+        (Some((first, _)), Some((second, _))) => Err(invalid(
+            path,
+            format!(
+                "expected a single {}, got '{}' and '{}'",
+                what, first, second
+            ),
+        )),
+    }
+}
+
+/// The field of a query keyed by field name, with its value, its path and the
+/// boost given next to it.
+fn field_entry<'a>(
+    value: &'a Value,
+    path: &str,
+) -> Result<(&'a str, &'a Value, String, Option<f32>)> {
+    let map = object(value, path)?;
+    let mut fields = map.iter().filter(|(key, _)| key.as_str() != "boost");
+    match (fields.next(), fields.next()) {
+        (Some((field, value)), None) => Ok((
+            field,
+            value,
+            format!("{}.{}", path, field),
+            f32_field(map, "boost", path)?,
+        )),
+        (None, _) => Err(invalid(path, "expected a field")),
+        (Some((first, _)), Some((second, _))) => Err(invalid(
+            path,
+            format!("expected a single field, got '{}' and '{}'", first, second),
+        )),
+    }
+}
+
+fn object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>> {
+    match value {
+        Value::Object(map) => Ok(map),
+        value => Err(invalid(path, expected("an object", value))),
+    }
+}
+
+fn check_fields(map: &Map<String, Value>, fields: &[&str], path: &str) -> Result<()> {
+    for key in map.keys() {
+        if !fields.contains(&key.as_str()) {
+            return Err(invalid(
+                path,
+                format!(
+                    "unknown field '{}', expected one of: {}",
+                    key,
+                    fields.join(", ")
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn missing(path: &str, field: &str) -> ::error::Error {
+    invalid(path, format!("missing field '{}'", field))
+}
+
+fn required<'a>(map: &'a Map<String, Value>, field: &str, path: &str) -> Result<&'a Value> {
+    map.get(field).ok_or_else(|| missing(path, field))
+}
+
+fn expected(what: &str, value: &Value) -> String {
+    format!("expected {}, got {}", what, value)
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => Err(invalid(path, expected("a string", value))),
+    }
+}
+
+/// The text of a string, number or boolean.
+fn scalar_text(value: &Value, path: &str) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        value => Err(invalid(
+            path,
+            expected("a string, number or boolean", value),
+        )),
+    }
+}
+
+/// An integer between `min` and `max`, given as a number or a string.
+fn integer(value: &Value, path: &str, min: i64, max: i64) -> Result<i64> {
+    let n = match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    match n {
+        Some(n) if n >= min && n <= max => Ok(n),
+        Some(n) => Err(invalid(
+            path,
+            format!("{} is not between {} and {}", n, min, max),
+        )),
+        None => Err(invalid(path, expected("an integer", value))),
+    }
+}
+
+/// A number, given as a number or a string.
+fn number(value: &Value, path: &str) -> Result<f64> {
+    let n = match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    match n {
+        Some(n) if !n.is_nan() => Ok(n),
+        _ => Err(invalid(path, expected("a number", value))),
+    }
+}
+
+/// A value of a float point field. The numbers overflowing `f32` are rejected instead of
+/// being rounded to an infinity, which has to be given explicitly as `"inf"` or `"-inf"`.
+fn float_point(value: &Value, path: &str) -> Result<f32> {
+    let n = number(value, path)? as f32;
+    if n.is_infinite() && !is_infinity(value) {
+        return Err(invalid(
+            path,
+            format!("{} is out of the float range", value),
+        ));
+    }
+    Ok(n)
+}
+
+/// A value of a double point field, see `float_point`.
+fn double_point(value: &Value, path: &str) -> Result<f64> {
+    let n = number(value, path)?;
+    if n.is_infinite() && !is_infinity(value) {
+        return Err(invalid(
+            path,
+            format!("{} is out of the double range", value),
+        ));
+    }
+    Ok(n)
+}
+
+/// Whether `value` is a string spelling an infinity, like `"inf"` or `"-Infinity"`.
+fn is_infinity(value: &Value) -> bool {
+    match value {
+        Value::String(s) => {
+            let s = s.trim().trim_start_matches(|c| c == '+' || c == '-');
+            s.eq_ignore_ascii_case("inf") || s.eq_ignore_ascii_case("infinity")
+        }
+        _ => false,
+    }
+}
+
+/// An IPv4 or IPv6 address, given as a string.
+fn inet_address(value: &Value, path: &str) -> Result<IpAddr> {
+    match value {
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| invalid(path, expected("an IP address", value))),
+        value => Err(invalid(path, expected("an IP address", value))),
+    }
+}
+
+fn f32_field(map: &Map<String, Value>, field: &str, path: &str) -> Result<Option<f32>> {
+    match map.get(field) {
+        Some(value) => Ok(Some(number(value, &format!("{}.{}", path, field))? as f32)),
+        None => Ok(None),
+    }
+}
+
+fn i32_field(map: &Map<String, Value>, field: &str, path: &str) -> Result<Option<i32>> {
+    match map.get(field) {
+        Some(value) => Ok(Some(integer(
+            value,
+            &format!("{}.{}", path, field),
+            i64::from(i32::MIN),
+            i64::from(i32::MAX),
+        )? as i32)),
+        None => Ok(None),
+    }
+}
+
+fn bool_field(map: &Map<String, Value>, field: &str, path: &str) -> Result<Option<bool>> {
+    match map.get(field) {
+        Some(Value::Bool(b)) => Ok(Some(*b)),
+        Some(value) => Err(invalid(
+            &format!("{}.{}", path, field),
+            expected("a boolean", value),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn min_should_match_value(value: &Value, path: &str) -> Result<i32> {
+    integer(value, path, 0, i64::from(i32::MAX)).map(|n| n as i32)
+}
+
+/// The bound of a range given by the `exclusive` or the `inclusive` field, with its
+/// path and whether it is inclusive.
+fn bound<'a>(
+    map: &'a Map<String, Value>,
+    exclusive: &str,
+    inclusive: &str,
+    path: &str,
+) -> Result<Option<(&'a Value, String, bool)>> {
+    match (map.get(exclusive), map.get(inclusive)) {
+        (Some(_), Some(_)) => Err(invalid(
+            path,
+            format!("{} and {} can not be both given", exclusive, inclusive),
+        )),
+        (Some(value), None) => Ok(Some((value, format!("{}.{}", path, exclusive), false))),
+        (None, Some(value)) => Ok(Some((value, format!("{}.{}", path, inclusive), true))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// The inclusive bounds of an integer range, an empty range being returned when
+/// an exclusive bound is already the extreme value of the type.
+fn integer_range(
+    lower: Option<(&Value, String, bool)>,
+    upper: Option<(&Value, String, bool)>,
+    min: i64,
+    max: i64,
+) -> Result<(i64, i64)> {
+    let lower = match lower {
+        Some((value, path, inclusive)) => {
+            let n = integer(value, &path, min, max)?;
+            if inclusive {
+                n
+            } else if n == max {
+                return Ok((max, min));
+            } else {
+                n + 1
+            }
+        }
+        None => min,
+    };
+    let upper = match upper {
+        Some((value, path, inclusive)) => {
+            let n = integer(value, &path, min, max)?;
+            if inclusive {
+                n
+            } else if n == min {
+                return Ok((max, min));
+            } else {
+                n - 1
+            }
+        }
+        None => max,
+    };
+    Ok((lower, upper))
+}
@@ -0,0 +1,443 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::doc::Term;
+use core::search::query::spans::{
+    FieldMaskingSpanQuery, MultiTermMatcher, SpanBoostQuery, SpanContainingQuery, SpanGapQuery,
+    SpanMultiTermQueryWrapper, SpanNearQuery, SpanNotQuery, SpanOrQuery, SpanPositionRangeQuery,
+    SpanQuery, SpanQueryEnum, SpanTermQuery, SpanWithinQuery, DEFAULT_MAX_EXPANSIONS,
+};
+use core::search::query::{
+    BooleanQuery, BoostQuery, BoostingQuery, ConstantScoreQuery, DisjunctionMaxQuery, DoublePoint,
+    ExistsQuery, FloatPoint, InetAddressPoint, IntPoint, LongPoint, MatchAllDocsQuery, PhraseQuery,
+    PointRangeQuery, PointValueType, Query, TermQuery, TermRangeQuery,
+};
+use core::search::query_dsl::invalid;
+
+use error::Result;
+
+use serde_json::{Map, Number, Value};
+
+use std::{f32, f64, i32, i64};
+
+pub(super) fn query_to_value<C: Codec>(query: &dyn Query<C>, path: &str) -> Result<Value> {
+    let any = query.as_any();
+    if let Some(q) = any.downcast_ref::<TermQuery>() {
+        let value = field_query("term", q.term.field(), Value::from(text(&q.term, path)?));
+        return Ok(with_boost(value, q.boost));
+    }
+    if let Some(q) = any.downcast_ref::<BooleanQuery<C>>() {
+        let path = format!("{}.bool", path);
+        let mut body = Map::new();
+        let clauses = [
+            ("must", q.musts()),
+            ("should", q.shoulds()),
+            ("filter", q.filters()),
+            ("must_not", q.must_nots()),
+        ];
+        for (occur, queries) in &clauses {
+            if !queries.is_empty() {
+                let path = format!("{}.{}", path, occur);
+                body.insert(occur.to_string(), queries_to_value(queries, &path)?);
+            }
+        }
+        let default_min_should_match = if q.musts().is_empty() { 1 } else { 0 };
+        if q.min_should_match() != default_min_should_match {
+            body.insert(
+                "minimum_should_match".into(),
+                Value::from(q.min_should_match()),
+            );
+        }
+        return Ok(entry("bool", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<BoostQuery<C>>() {
+        return Ok(with_boost(query_to_value(q.query(), path)?, q.boost()));
+    }
+    if let Some(q) = any.downcast_ref::<BoostingQuery<C>>() {
+        let path = format!("{}.boosting", path);
+        let mut body = Map::new();
+        body.insert(
+            "positive".into(),
+            query_to_value(q.positive(), &format!("{}.positive", path))?,
+        );
+        body.insert(
+            "negative".into(),
+            query_to_value(q.negative(), &format!("{}.negative", path))?,
+        );
+        body.insert("negative_boost".into(), float(q.negative_boost()));
+        return Ok(entry("boosting", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<DisjunctionMaxQuery<C>>() {
+        let path = format!("{}.dis_max.queries", path);
+        let mut body = Map::new();
+        body.insert("queries".into(), queries_to_value(&q.disjuncts, &path)?);
+        if q.tie_breaker_multiplier != 0.0 {
+            body.insert("tie_breaker".into(), float(q.tie_breaker_multiplier));
+        }
+        return Ok(entry("dis_max", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<ConstantScoreQuery<C>>() {
+        let path = format!("{}.constant_score.filter", path);
+        let mut body = Map::new();
+        body.insert("filter".into(), query_to_value(q.get_raw_query(), &path)?);
+        let value = entry("constant_score", Value::Object(body));
+        return Ok(with_boost(value, q.boost()));
+    }
+    if any.downcast_ref::<MatchAllDocsQuery>().is_some() {
+        return Ok(entry("match_all", Value::Object(Map::new())));
+    }
+    if let Some(q) = any.downcast_ref::<ExistsQuery>() {
+        return Ok(entry("exists", entry("field", Value::from(q.field()))));
+    }
+    if let Some(q) = any.downcast_ref::<PhraseQuery>() {
+        let mut texts = Vec::with_capacity(q.terms().len());
+        for term in q.terms() {
+            texts.push(text(term, path)?);
+        }
+        let mut body = Map::new();
+        body.insert("query".into(), Value::from(texts.join(" ")));
+        if q.slop() != 0 {
+            body.insert("slop".into(), Value::from(q.slop()));
+        }
+        return Ok(field_query("match_phrase", q.field(), Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<PointRangeQuery>() {
+        return point_range_to_value(q, path);
+    }
+    if let Some(q) = any.downcast_ref::<TermRangeQuery>() {
+        let mut bounds = Map::new();
+        if let Some(lower) = q.lower_term() {
+            let key = if q.include_lower() { "gte" } else { "gt" };
+            bounds.insert(key.into(), Value::from(utf8(lower, path)?));
+        }
+        if let Some(upper) = q.upper_term() {
+            let key = if q.include_upper() { "lte" } else { "lt" };
+            bounds.insert(key.into(), Value::from(utf8(upper, path)?));
+        }
+        return Ok(field_query("range", q.field(), Value::Object(bounds)));
+    }
+    span_to_value(query, path)
+}
+
+fn queries_to_value<C: Codec>(queries: &[Box<dyn Query<C>>], path: &str) -> Result<Value> {
+    let mut values = Vec::with_capacity(queries.len());
+    for (i, query) in queries.iter().enumerate() {
+        values.push(query_to_value(query.as_ref(), &format!("{}[{}]", path, i))?);
+    }
+    Ok(Value::Array(values))
+}
+
+fn point_range_to_value(q: &PointRangeQuery, path: &str) -> Result<Value> {
+    if q.num_dims() != 1 {
+        return Err(invalid(
+            path,
+            format!("{} has more than one dimension", q.value_type()),
+        ));
+    }
+    let (lower, upper, min, max) = match q.value_type() {
+        PointValueType::Integer => (
+            Value::from(IntPoint::decode_dimension(q.lower_point())),
+            Value::from(IntPoint::decode_dimension(q.upper_point())),
+            Value::from(i32::MIN),
+            Value::from(i32::MAX),
+        ),
+        PointValueType::Long => (
+            Value::from(LongPoint::decode_dimension(q.lower_point())),
+            Value::from(LongPoint::decode_dimension(q.upper_point())),
+            Value::from(i64::MIN),
+            Value::from(i64::MAX),
+        ),
+        // an infinite bound is the open bound on its own side
+        PointValueType::Float => (
+            float_point(FloatPoint::decode_dimension(q.lower_point()), path)?,
+            float_point(FloatPoint::decode_dimension(q.upper_point()), path)?,
+            float_point(f32::NEG_INFINITY, path)?,
+            float_point(f32::INFINITY, path)?,
+        ),
+        PointValueType::Double => (
+            double_point(DoublePoint::decode_dimension(q.lower_point()), path)?,
+            double_point(DoublePoint::decode_dimension(q.upper_point()), path)?,
+            double_point(f64::NEG_INFINITY, path)?,
+            double_point(f64::INFINITY, path)?,
+        ),
+        PointValueType::InetAddress => (
+            Value::from(InetAddressPoint::decode_dimension(q.lower_point()).to_string()),
+            Value::from(InetAddressPoint::decode_dimension(q.upper_point()).to_string()),
+            Value::from(InetAddressPoint::MIN_VALUE.to_string()),
+            Value::from(InetAddressPoint::MAX_VALUE.to_string()),
+        ),
+    };
+    if lower == upper {
+        return Ok(field_query("term", q.field(), lower));
+    }
+    let mut bounds = Map::new();
+    if lower != min {
+        bounds.insert("gte".into(), lower);
+    }
+    if upper != max {
+        bounds.insert("lte".into(), upper);
+    }
+    Ok(field_query("range", q.field(), Value::Object(bounds)))
+}
+
+fn span_to_value<C: Codec>(query: &dyn Query<C>, path: &str) -> Result<Value> {
+    let any = query.as_any();
+    if let Some(q) = any.downcast_ref::<SpanTermQuery>() {
+        return Ok(field_query(
+            "span_term",
+            q.term.field(),
+            Value::from(text(&q.term, path)?),
+        ));
+    }
+    if let Some(q) = any.downcast_ref::<SpanGapQuery>() {
+        let field = SpanQuery::<C>::field(q);
+        return Ok(field_query("span_gap", field, Value::from(q.width())));
+    }
+    if let Some(q) = any.downcast_ref::<SpanNearQuery>() {
+        let path = format!("{}.span_near.clauses", path);
+        let mut body = Map::new();
+        body.insert("clauses".into(), spans_to_value::<C>(q.clauses(), &path)?);
+        if q.slop() != 0 {
+            body.insert("slop".into(), Value::from(q.slop()));
+        }
+        if !q.is_in_order() {
+            body.insert("in_order".into(), Value::from(false));
+        }
+        return Ok(entry("span_near", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<SpanOrQuery>() {
+        let path = format!("{}.span_or.clauses", path);
+        let clauses = spans_to_value::<C>(q.clauses(), &path)?;
+        return Ok(entry("span_or", entry("clauses", clauses)));
+    }
+    if let Some(q) = any.downcast_ref::<SpanNotQuery>() {
+        let path = format!("{}.span_not", path);
+        let mut body = Map::new();
+        body.insert(
+            "include".into(),
+            query_to_value::<C>(q.include(), &format!("{}.include", path))?,
+        );
+        body.insert(
+            "exclude".into(),
+            query_to_value::<C>(q.exclude(), &format!("{}.exclude", path))?,
+        );
+        if q.pre() != 0 {
+            body.insert("pre".into(), Value::from(q.pre()));
+        }
+        if q.post() != 0 {
+            body.insert("post".into(), Value::from(q.post()));
+        }
+        return Ok(entry("span_not", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<SpanPositionRangeQuery>() {
+        if q.start() != 0 {
+            return Err(invalid(
+                path,
+                format!("{} does not start at the first position", q),
+            ));
+        }
+        let mut body = Map::new();
+        body.insert(
+            "match".into(),
+            query_to_value::<C>(q.match_query(), &format!("{}.span_first.match", path))?,
+        );
+        body.insert("end".into(), Value::from(q.end()));
+        return Ok(entry("span_first", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<SpanContainingQuery>() {
+        return contain_to_value::<C>("span_containing", q.big(), q.little(), path);
+    }
+    if let Some(q) = any.downcast_ref::<SpanWithinQuery>() {
+        return contain_to_value::<C>("span_within", q.big(), q.little(), path);
+    }
+    if let Some(q) = any.downcast_ref::<SpanMultiTermQueryWrapper>() {
+        let field = SpanQuery::<C>::field(q);
+        let mut matcher = Map::new();
+        let kind = match q.matcher() {
+            MultiTermMatcher::Prefix(prefix) => {
+                matcher.insert("value".into(), Value::from(utf8(prefix, path)?));
+                "prefix"
+            }
+            MultiTermMatcher::Wildcard { pattern, .. } => {
+                matcher.insert("value".into(), Value::from(pattern.as_str()));
+                "wildcard"
+            }
+            MultiTermMatcher::Regexp { pattern, .. } => {
+                matcher.insert("value".into(), Value::from(pattern.as_str()));
+                "regexp"
+            }
+            MultiTermMatcher::Fuzzy {
+                text,
+                max_edits,
+                prefix_length,
+            } => {
+                let text: String = text.iter().collect();
+                matcher.insert("value".into(), Value::from(text));
+                matcher.insert("fuzziness".into(), Value::from(*max_edits));
+                matcher.insert("prefix_length".into(), Value::from(*prefix_length));
+                "fuzzy"
+            }
+        };
+        let mut body = Map::new();
+        body.insert(
+            "match".into(),
+            field_query(kind, field, Value::Object(matcher)),
+        );
+        if q.max_expansions() != DEFAULT_MAX_EXPANSIONS {
+            body.insert("max_expansions".into(), Value::from(q.max_expansions()));
+        }
+        return Ok(entry("span_multi", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<FieldMaskingSpanQuery>() {
+        let mut body = Map::new();
+        body.insert(
+            "query".into(),
+            query_to_value::<C>(
+                q.masked_query(),
+                &format!("{}.field_masking_span.query", path),
+            )?,
+        );
+        body.insert("field".into(), Value::from(SpanQuery::<C>::field(q)));
+        return Ok(entry("field_masking_span", Value::Object(body)));
+    }
+    if let Some(q) = any.downcast_ref::<SpanBoostQuery>() {
+        return Ok(with_boost(query_to_value::<C>(&q.query, path)?, q.boost()));
+    }
+    Err(invalid(
+        path,
+        format!("{} can not be written with the query DSL", query),
+    ))
+}
+
+fn spans_to_value<C: Codec>(spans: &[SpanQueryEnum], path: &str) -> Result<Value> {
+    let mut values = Vec::with_capacity(spans.len());
+    for (i, span) in spans.iter().enumerate() {
+        values.push(query_to_value::<C>(span, &format!("{}[{}]", path, i))?);
+    }
+    Ok(Value::Array(values))
+}
+
+fn contain_to_value<C: Codec>(
+    kind: &str,
+    big: &SpanQueryEnum,
+    little: &SpanQueryEnum,
+    path: &str,
+) -> Result<Value> {
+    let path = format!("{}.{}", path, kind);
+    let mut body = Map::new();
+    body.insert(
+        "big".into(),
+        query_to_value::<C>(big, &format!("{}.big", path))?,
+    );
+    body.insert(
+        "little".into(),
+        query_to_value::<C>(little, &format!("{}.little", path))?,
+    );
+    Ok(entry(kind, Value::Object(body)))
+}
+
+/// Add `boost` to a written query, next to the value of the queries keyed by field.
+fn with_boost(mut query: Value, boost: f32) -> Value {
+    if (boost - 1.0f32).abs() <= f32::EPSILON {
+        return query;
+    }
+    if let Value::Object(map) = &mut query {
+        if let Some((kind, body)) = map.iter_mut().next() {
+            let value_key = match kind.as_str() {
+                "term" | "span_term" => Some("value"),
+                "match" | "match_phrase" | "range" => Some("query"),
+                _ => None,
+            };
+            let target = match value_key {
+                Some(value_key) => {
+                    let value = body
+                        .as_object_mut()
+                        .and_then(|fields| fields.values_mut().next())
+                        .expect("a query keyed by field");
+                    if !value.is_object() {
+                        *value = entry(value_key, value.take());
+                    }
+                    value
+                }
+                None => body,
+            };
+            if let Value::Object(target) = target {
+                let boost = match target.get("boost").and_then(Value::as_f64) {
+                    Some(b) => b as f32 * boost,
+                    None => boost,
+                };
+                target.insert("boost".into(), float(boost));
+            }
+        }
+    }
+    query
+}
+
+fn entry(key: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert(key.to_string(), value);
+    Value::Object(map)
+}
+
+/// A query keyed by field, like `{"term": {"title": "fox"}}`.
+fn field_query(kind: &str, field: &str, value: Value) -> Value {
+    entry(kind, entry(field, value))
+}
+
+fn text(term: &Term, path: &str) -> Result<String> {
+    utf8(&term.bytes, path)
+}
+
+fn utf8(bytes: &[u8], path: &str) -> Result<String> {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(s) => Ok(s),
+        Err(_) => Err(invalid(path, format!("{:?} is not valid UTF-8", bytes))),
+    }
+}
+
+/// A float written with its shortest representation, `null` if it is not finite.
+fn float(value: f32) -> Value {
+    double(
+        value
+            .to_string()
+            .parse()
+            .unwrap_or_else(|_| f64::from(value)),
+    )
+}
+
+fn double(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+/// A value of a float point field, see `double_point`.
+fn float_point(value: f32, path: &str) -> Result<Value> {
+    if value.is_finite() {
+        Ok(float(value))
+    } else {
+        double_point(f64::from(value), path)
+    }
+}
+
+/// A value of a double point field, the infinities which JSON numbers can't hold being
+/// written as the `"inf"` and `"-inf"` strings.
+fn double_point(value: f64, path: &str) -> Result<Value> {
+    if value.is_infinite() {
+        let text = if value > 0.0 { "inf" } else { "-inf" };
+        return Ok(Value::from(text));
+    }
+    match Number::from_f64(value) {
+        Some(n) => Ok(Value::Number(n)),
+        None => Err(invalid(path, format!("can't write the bound {}", value))),
+    }
+}
@@ -11,11 +11,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::search::scorer::Scorer;
+use core::search::scorer::{two_phase_next, Scorer};
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::util::DocId;
 use error::Result;
 
+use std::iter;
+
 /// Scorer for conjunctions, sets of queries, all of which are required.
 pub struct ConjunctionScorer<T: Scorer> {
     lead1: T,
@@ -41,6 +43,12 @@ impl<T: Scorer> ConjunctionScorer<T> {
         }
     }
 
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        iter::once(&self.lead1)
+            .chain(iter::once(&self.lead2))
+            .chain(self.others.iter())
+    }
+
     fn skip_to_approx(&mut self, target: DocId) -> Result<DocId> {
         let mut doc = target;
 
@@ -93,6 +101,10 @@ impl<T: Scorer> Scorer for ConjunctionScorer<T> {
 
         Ok(score)
     }
+
+    fn max_score(&self) -> f32 {
+        self.iter().map(|s| s.max_score()).sum()
+    }
 }
 
 impl<T: Scorer> DocIterator for ConjunctionScorer<T> {
@@ -101,11 +113,13 @@ impl<T: Scorer> DocIterator for ConjunctionScorer<T> {
     }
 
     fn next(&mut self) -> Result<DocId> {
-        self.approximate_next()
+        self.approximate_next()?;
+        two_phase_next(self)
     }
 
     fn advance(&mut self, target: DocId) -> Result<DocId> {
-        self.approximate_advance(target)
+        self.approximate_advance(target)?;
+        two_phase_next(self)
     }
 
     fn cost(&self) -> usize {
@@ -113,9 +127,31 @@ impl<T: Scorer> DocIterator for ConjunctionScorer<T> {
     }
 
     fn matches(&mut self) -> Result<bool> {
+        if self.lead1.support_two_phase() && !self.lead1.matches()? {
+            return Ok(false);
+        }
+        if self.lead2.support_two_phase() && !self.lead2.matches()? {
+            return Ok(false);
+        }
+        for other in &mut self.others {
+            if other.support_two_phase() && !other.matches()? {
+                return Ok(false);
+            }
+        }
         Ok(true)
     }
 
+    fn match_cost(&self) -> f32 {
+        self.iter()
+            .filter(|s| s.support_two_phase())
+            .map(|s| s.match_cost())
+            .sum()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.iter().any(|s| s.support_two_phase())
+    }
+
     fn approximate_next(&mut self) -> Result<DocId> {
         let doc = self.lead1.approximate_next()?;
         self.skip_to_approx(doc)
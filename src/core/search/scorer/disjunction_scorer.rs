@@ -26,6 +26,7 @@ pub struct DisjunctionSumScorer<T: Scorer> {
     needs_scores: bool,
     cost: usize,
     min_should_match: i32,
+    max_score: f32,
 }
 
 impl<T: Scorer> DisjunctionSumScorer<T> {
@@ -37,6 +38,11 @@ impl<T: Scorer> DisjunctionSumScorer<T> {
         debug_assert!(children.len() > 0);
 
         let cost = children.iter().map(|w| w.cost()).sum();
+        let max_score = if needs_scores {
+            children.iter().map(|s| s.max_score()).sum()
+        } else {
+            0.0f32
+        };
 
         let sub_scorers = if children.len() < 10 || min_should_match > DEFAULT_MIN_SHOULD_MATCH {
             SubScorers::SQ(SimpleQueue::new(children))
@@ -49,8 +55,16 @@ impl<T: Scorer> DisjunctionSumScorer<T> {
             needs_scores,
             cost,
             min_should_match,
+            max_score,
         }
     }
+
+    /// Returns the number of sub scorers matching the current doc and the sum of
+    /// their scores. The two phase sub scorers positioned on the doc by their
+    /// approximation are only counted if they match it.
+    pub fn matching_freq_and_score(&mut self) -> Result<(i32, f32)> {
+        self.sub_scorers.matching_freq_and_score(self.needs_scores)
+    }
 }
 
 impl<T: Scorer> Scorer for DisjunctionSumScorer<T> {
@@ -61,6 +75,10 @@ impl<T: Scorer> Scorer for DisjunctionSumScorer<T> {
 
         self.sub_scorers.score_sum()
     }
+
+    fn max_score(&self) -> f32 {
+        self.max_score
+    }
 }
 
 impl<T: Scorer> DocIterator for DisjunctionSumScorer<T> {
@@ -285,6 +303,45 @@ impl<T: Scorer> SubScorers<T> {
         }
     }
 
+    fn matching_freq_and_score(&mut self, needs_scores: bool) -> Result<(i32, f32)> {
+        let mut freq = 0;
+        let mut score = 0.0f32;
+        match self {
+            SubScorers::SQ(sq) => {
+                let doc_id = sq.curr_doc;
+                for s in sq.scorers.iter_mut() {
+                    if s.doc_id() == doc_id && (!s.support_two_phase() || s.matches()?) {
+                        freq += 1;
+                        if needs_scores {
+                            score += s.score()?;
+                        }
+                    }
+                }
+            }
+            SubScorers::DPQ(dpq) => {
+                let mut disi = dpq.top_list();
+
+                loop {
+                    let s = disi.inner_mut();
+                    if !s.support_two_phase() || s.matches()? {
+                        freq += 1;
+                        if needs_scores {
+                            score += s.score()?;
+                        }
+                    }
+
+                    if disi.next.is_null() {
+                        break;
+                    } else {
+                        unsafe { disi = &mut *disi.next };
+                    }
+                }
+            }
+        }
+
+        Ok((freq, score))
+    }
+
     fn doc_id(&self) -> DocId {
         match self {
             SubScorers::SQ(sq) => sq.curr_doc,
@@ -52,6 +52,7 @@ mod phrase_scorer;
 pub use self::phrase_scorer::*;
 
 use std::collections::HashMap;
+use std::f32;
 use std::i32;
 
 use core::util::{DocId, IndexedContext, VariantValue};
@@ -96,6 +97,15 @@ pub trait Scorer: DocIterator {
     fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
         Ok(vec![])
     }
+
+    /// Returns an upper bound of the scores of all the documents this scorer
+    /// may match, `f32::INFINITY` if unknown.
+    ///
+    /// Collectors may use this bound to skip the remaining documents of the
+    /// segment once no document could compete anymore.
+    fn max_score(&self) -> f32 {
+        f32::INFINITY
+    }
 }
 
 impl Scorer for Box<dyn Scorer> {
@@ -110,6 +120,10 @@ impl Scorer for Box<dyn Scorer> {
     fn score_feature(&mut self) -> Result<Vec<FeatureResult>> {
         (**self).score_feature()
     }
+
+    fn max_score(&self) -> f32 {
+        (**self).max_score()
+    }
 }
 
 impl DocIterator for Box<dyn Scorer> {
@@ -295,6 +309,18 @@ impl<T: DocIterator> DocIterator for ConstantScoreScorer<T> {
         self.cost
     }
 
+    fn matches(&mut self) -> Result<bool> {
+        self.iterator.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.iterator.match_cost()
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.iterator.support_two_phase()
+    }
+
     fn approximate_next(&mut self) -> Result<DocId> {
         self.iterator.approximate_next()
     }
@@ -73,6 +73,19 @@ impl<T: PostingIterator> PostingsAndFreq<T> {
             nterms: 1,
         }
     }
+
+    /// used when several terms share the same position, e.g. `MultiPhraseQuery`
+    pub fn with_terms(postings: T, pos: i32, terms: &[Term]) -> Self {
+        let mut terms = terms.to_vec();
+        terms.sort_by(|t1, t2| t1.bytes.cmp(&t2.bytes));
+        let nterms = terms.len() as i32;
+        PostingsAndFreq {
+            postings,
+            pos,
+            terms,
+            nterms,
+        }
+    }
 }
 
 impl<T: PostingIterator> Ord for PostingsAndFreq<T> {
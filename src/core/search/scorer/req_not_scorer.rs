@@ -36,6 +36,10 @@ impl Scorer for ReqNotScorer {
     fn score(&mut self) -> Result<f32> {
         self.req_scorer.score()
     }
+
+    fn max_score(&self) -> f32 {
+        self.req_scorer.max_score()
+    }
 }
 
 impl DocIterator for ReqNotScorer {
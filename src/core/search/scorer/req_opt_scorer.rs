@@ -63,6 +63,10 @@ impl Scorer for ReqOptScorer {
 
         Ok(score)
     }
+
+    fn max_score(&self) -> f32 {
+        self.req_scorer.max_score() + self.opt_scorer.max_score()
+    }
 }
 
 impl DocIterator for ReqOptScorer {
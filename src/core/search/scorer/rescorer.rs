@@ -310,26 +310,7 @@ impl QueryRescorer {
         hits.sort_by(ScoreDocHit::order_by_doc);
 
         let readers = searcher.reader().leaves();
-        let mut score_field_index = -1;
-        match *top_docs {
-            TopDocs::Field(ref f) => {
-                for (index, field) in f.fields.iter().enumerate() {
-                    if field.field_type() == SortFieldType::Score {
-                        score_field_index = index as i32;
-                        break;
-                    }
-                }
-            }
-            TopDocs::Collapse(ref c) => {
-                for (index, field) in c.fields.iter().enumerate() {
-                    if field.field_type() == SortFieldType::Score {
-                        score_field_index = index as i32;
-                        break;
-                    }
-                }
-            }
-            _ => {}
-        }
+        let score_field_index = Self::score_field_index(top_docs);
 
         let weight = req.query.create_weight(searcher, true)?;
 
@@ -353,7 +334,23 @@ impl QueryRescorer {
         Ok(hits)
     }
 
-    fn combine_score<C: Codec>(
+    /// The index of the score in the sort values of the hits, -1 if the hits
+    /// are not sorted by score.
+    pub(crate) fn score_field_index(top_docs: &TopDocs) -> i32 {
+        let fields = match *top_docs {
+            TopDocs::Field(ref f) => &f.fields,
+            TopDocs::Collapse(ref c) => &c.fields,
+            _ => {
+                return -1;
+            }
+        };
+        fields
+            .iter()
+            .position(|field| field.field_type() == SortFieldType::Score)
+            .map_or(-1, |index| index as i32)
+    }
+
+    pub(crate) fn combine_score<C: Codec>(
         &self,
         ctx: &RescoreRequest<C>,
         last_score: f32,
@@ -372,7 +369,7 @@ impl QueryRescorer {
         }
     }
 
-    fn combine_docs<C: Codec>(
+    pub(crate) fn combine_docs<C: Codec>(
         &self,
         docs: &mut TopDocs,
         resorted: Vec<ScoreDocHit>,
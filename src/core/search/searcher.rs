@@ -18,6 +18,7 @@ use std::sync::Arc;
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
 
+use core::codec::doc_values::NumericDocValues;
 use core::codec::postings::FieldsProducer;
 use core::codec::{Codec, Terms};
 use core::codec::{TermIterator, TermState};
@@ -187,6 +188,13 @@ impl<C: Codec> SimWeight<C> for NonScoringSimWeight {
     fn sim_scorer(&self, _reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
         Ok(Box::new(NonScoringSimScorer {}))
     }
+
+    fn sim_scorer_with_norms(
+        &self,
+        _norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Box<dyn SimScorer>> {
+        Ok(Box::new(NonScoringSimScorer {}))
+    }
 }
 
 struct NonScoringSimScorer;
@@ -82,6 +82,28 @@ impl BM25Similarity {
         }
     }
 
+    /// Blends the statistics of several terms which should be scored as if they
+    /// were the same term, e.g. synonyms.
+    ///
+    /// The doc freq is the max doc freq of all terms, because the blended term can't
+    /// appear in fewer documents than any of them, and the total term freq is the
+    /// sum of all total term freqs, or -1 if any of them is not available.
+    pub fn blend_term_stats(term: Vec<u8>, term_stats: &[TermStatistics]) -> TermStatistics {
+        let mut doc_freq = 0i64;
+        let mut total_term_freq = 0i64;
+        for stat in term_stats {
+            doc_freq = doc_freq.max(stat.doc_freq);
+            if total_term_freq != -1 {
+                total_term_freq = if stat.total_term_freq == -1 {
+                    -1
+                } else {
+                    total_term_freq + stat.total_term_freq
+                };
+            }
+        }
+        TermStatistics::new(term, doc_freq, total_term_freq)
+    }
+
     pub fn compute_norm(state: &FieldInvertState) -> i64 {
         let num_terms = state.length - state.num_overlap;
         BM25Similarity::encode_norm_value(state.boost, num_terms) as i64
@@ -91,8 +113,9 @@ impl BM25Similarity {
         SmallFloat::float_to_byte315(boost / (field_length as f32).sqrt())
     }
 
+    /// Decodes a norm into the field length divided by the square of the index-time boost.
     #[inline]
-    fn decode_norm_value(b: usize) -> f32 {
+    pub fn decode_norm_value(b: usize) -> f32 {
         NORM_TABLE[b]
     }
 
@@ -389,6 +412,22 @@ impl<C: Codec> SimWeight<C> for BM25SimWeight {
         let norms = reader.norm_values(&self.field)?;
         self.explain_score(doc, freq, norms)
     }
+
+    fn sim_scorer_with_norms(
+        &self,
+        norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Box<dyn SimScorer>> {
+        Ok(Box::new(BM25SimScorer::new(self, norms)))
+    }
+
+    fn explain_with_norms(
+        &self,
+        doc: DocId,
+        freq: Explanation,
+        norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Explanation> {
+        self.explain_score(doc, freq, norms)
+    }
 }
 
 #[cfg(test)]
@@ -17,10 +17,12 @@ pub use self::bm25_similarity::*;
 
 use core::util::{DocId, KeyedContext};
 
+use core::codec::doc_values::NumericDocValues;
 use core::codec::Codec;
 use core::index::reader::SearchLeafReader;
 use core::search::explanation::Explanation;
 use core::search::statistics::{CollectionStatistics, TermStatistics};
+use error::ErrorKind::UnsupportedOperation;
 use error::Result;
 use std::fmt::Display;
 
@@ -171,4 +173,30 @@ pub trait SimWeight<C: Codec> {
             vec![freq],
         ))
     }
+
+    /// Creates a `SimScorer` reading the norms of the documents from `norms` rather than
+    /// from the field of the weight, e.g. the norms of several fields combined.
+    fn sim_scorer_with_norms(
+        &self,
+        _norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Box<dyn SimScorer>> {
+        bail!(UnsupportedOperation(
+            "this similarity can't score with external norms".into()
+        ))
+    }
+
+    /// Explain the score for a single document scored with `norms`.
+    fn explain_with_norms(
+        &self,
+        doc: DocId,
+        freq: Explanation,
+        norms: Option<Box<dyn NumericDocValues>>,
+    ) -> Result<Explanation> {
+        Ok(Explanation::new(
+            true,
+            self.sim_scorer_with_norms(norms)?.score(doc, freq.value())?,
+            format!("score(doc={},freq={}), with freq of:", doc, freq.value()),
+            vec![freq],
+        ))
+    }
 }
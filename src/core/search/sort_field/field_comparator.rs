@@ -11,9 +11,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::doc_values::NumericDocValues;
+use core::codec::doc_values::{NumericDocValues, SortedNumericDocValues};
+use core::doc::{DocValuesType, LatLonDocValuesField};
 use core::index::reader::{LeafReaderContext, SearchLeafReader};
+use core::search::expressions::{ExpressionValueSource, LeafExpressionValues};
 use core::search::sort_field::{SortFieldType, SortedWrapperDocValuesSource};
+use core::util::geo::haversin_meters;
 use core::util::{BitsMut, DocId, VariantValue};
 use error::Result;
 
@@ -145,6 +148,8 @@ pub enum FieldComparatorEnum {
     Doc(DocComparator),
     NumericDV(NumericDocValuesComparator<DefaultDocValuesSource>),
     SortedNumericDV(NumericDocValuesComparator<SortedWrapperDocValuesSource>),
+    Expression(ExpressionComparator),
+    Distance(LatLonDistanceComparator),
 }
 
 impl FieldComparator for FieldComparatorEnum {
@@ -154,6 +159,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::NumericDV(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare(slot1, slot2),
+            FieldComparatorEnum::Expression(c) => c.compare(slot1, slot2),
+            FieldComparatorEnum::Distance(c) => c.compare(slot1, slot2),
         }
     }
 
@@ -163,6 +170,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.value(slot),
             FieldComparatorEnum::NumericDV(c) => c.value(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.value(slot),
+            FieldComparatorEnum::Expression(c) => c.value(slot),
+            FieldComparatorEnum::Distance(c) => c.value(slot),
         }
     }
 
@@ -172,6 +181,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.set_bottom(slot),
             FieldComparatorEnum::NumericDV(c) => c.set_bottom(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.set_bottom(slot),
+            FieldComparatorEnum::Expression(c) => c.set_bottom(slot),
+            FieldComparatorEnum::Distance(c) => c.set_bottom(slot),
         }
     }
 
@@ -181,6 +192,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare_bottom(value),
             FieldComparatorEnum::NumericDV(c) => c.compare_bottom(value),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare_bottom(value),
+            FieldComparatorEnum::Expression(c) => c.compare_bottom(value),
+            FieldComparatorEnum::Distance(c) => c.compare_bottom(value),
         }
     }
 
@@ -190,6 +203,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.copy(slot, value),
             FieldComparatorEnum::NumericDV(c) => c.copy(slot, value),
             FieldComparatorEnum::SortedNumericDV(c) => c.copy(slot, value),
+            FieldComparatorEnum::Expression(c) => c.copy(slot, value),
+            FieldComparatorEnum::Distance(c) => c.copy(slot, value),
         }
     }
 
@@ -202,6 +217,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::NumericDV(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_information_from_reader(reader),
+            FieldComparatorEnum::Expression(c) => c.get_information_from_reader(reader),
+            FieldComparatorEnum::Distance(c) => c.get_information_from_reader(reader),
         }
     }
 
@@ -211,6 +228,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_type(),
             FieldComparatorEnum::NumericDV(c) => c.get_type(),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_type(),
+            FieldComparatorEnum::Expression(c) => c.get_type(),
+            FieldComparatorEnum::Distance(c) => c.get_type(),
         }
     }
 }
@@ -222,6 +241,8 @@ impl fmt::Display for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::NumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::SortedNumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
+            FieldComparatorEnum::Expression(c) => write!(f, "FieldComparatorEnum({})", c),
+            FieldComparatorEnum::Distance(c) => write!(f, "FieldComparatorEnum({})", c),
         }
     }
 }
@@ -363,6 +384,219 @@ impl fmt::Display for DocComparator {
     }
 }
 
+/// compare doc hit by the value of an expression over its doc values
+pub struct ExpressionComparator {
+    source: ExpressionValueSource,
+    current_values: Option<LeafExpressionValues>,
+    values: Vec<f64>,
+    bottom: f64,
+}
+
+impl ExpressionComparator {
+    pub fn new(num_hits: usize, source: ExpressionValueSource) -> Self {
+        ExpressionComparator {
+            source,
+            current_values: None,
+            values: vec![0.0; num_hits],
+            bottom: 0.0,
+        }
+    }
+
+    fn get_value(&mut self, value: ComparatorValue) -> Result<f64> {
+        debug_assert!(value.is_doc());
+        self.current_values
+            .as_mut()
+            .unwrap()
+            .value(value.doc(), 0.0)
+    }
+}
+
+impl FieldComparator for ExpressionComparator {
+    fn compare(&self, slot1: usize, slot2: usize) -> Ordering {
+        self.values[slot1]
+            .partial_cmp(&self.values[slot2])
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn value(&self, slot: usize) -> VariantValue {
+        VariantValue::Double(self.values[slot])
+    }
+
+    fn set_bottom(&mut self, slot: usize) {
+        self.bottom = self.values[slot];
+    }
+
+    fn compare_bottom(&mut self, value: ComparatorValue) -> Result<Ordering> {
+        let value = self.get_value(value)?;
+        Ok(self.bottom.partial_cmp(&value).unwrap_or(Ordering::Equal))
+    }
+
+    fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
+        self.values[slot] = self.get_value(value)?;
+        Ok(())
+    }
+
+    fn get_information_from_reader<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<()> {
+        self.current_values = Some(self.source.leaf_values(reader)?);
+        Ok(())
+    }
+
+    fn get_type(&self) -> SortFieldType {
+        SortFieldType::Double
+    }
+}
+
+impl fmt::Display for ExpressionComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "source: {}\tbottom: {:?}\tvalues: {:?}",
+            &self.source, self.bottom, self.values
+        )
+    }
+}
+
+/// The distances in meters from a location to the closest `LatLonDocValuesField` value
+/// of the documents of a segment.
+pub(crate) struct LeafDistanceValues {
+    values: Option<Box<dyn SortedNumericDocValues>>,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl LeafDistanceValues {
+    pub fn new<C: Codec>(
+        reader: &LeafReaderContext<'_, C>,
+        field: &str,
+        latitude: f64,
+        longitude: f64,
+    ) -> Result<Self> {
+        let values = match reader.reader.field_info(field) {
+            Some(fi) if fi.doc_values_type == DocValuesType::SortedNumeric => {
+                Some(reader.reader.get_sorted_numeric_doc_values(field)?)
+            }
+            _ => None,
+        };
+        Ok(LeafDistanceValues {
+            values,
+            latitude,
+            longitude,
+        })
+    }
+
+    /// The distance of `doc`, `None` if the document has no location.
+    pub fn distance(&mut self, doc: DocId) -> Result<Option<f64>> {
+        let values = match self.values {
+            Some(ref mut values) => values,
+            None => return Ok(None),
+        };
+        values.set_document(doc)?;
+        let mut min_distance = None;
+        for i in 0..values.count() {
+            let encoded = values.value_at(i)?;
+            let distance = haversin_meters(
+                self.latitude,
+                self.longitude,
+                LatLonDocValuesField::decode_latitude(encoded),
+                LatLonDocValuesField::decode_longitude(encoded),
+            );
+            if min_distance.map_or(true, |d| distance < d) {
+                min_distance = Some(distance);
+            }
+        }
+        Ok(min_distance)
+    }
+}
+
+/// compare doc hit by the distance of its closest location to a point
+pub struct LatLonDistanceComparator {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+    current_values: Option<LeafDistanceValues>,
+    values: Vec<f64>,
+    bottom: f64,
+}
+
+impl LatLonDistanceComparator {
+    pub fn new(num_hits: usize, field: String, latitude: f64, longitude: f64) -> Self {
+        LatLonDistanceComparator {
+            field,
+            latitude,
+            longitude,
+            current_values: None,
+            values: vec![0.0; num_hits],
+            bottom: 0.0,
+        }
+    }
+
+    fn get_value(&mut self, value: ComparatorValue) -> Result<f64> {
+        debug_assert!(value.is_doc());
+        let distance = self
+            .current_values
+            .as_mut()
+            .unwrap()
+            .distance(value.doc())?;
+        Ok(distance.unwrap_or(f64::INFINITY))
+    }
+}
+
+impl FieldComparator for LatLonDistanceComparator {
+    fn compare(&self, slot1: usize, slot2: usize) -> Ordering {
+        self.values[slot1]
+            .partial_cmp(&self.values[slot2])
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn value(&self, slot: usize) -> VariantValue {
+        VariantValue::Double(self.values[slot])
+    }
+
+    fn set_bottom(&mut self, slot: usize) {
+        self.bottom = self.values[slot];
+    }
+
+    fn compare_bottom(&mut self, value: ComparatorValue) -> Result<Ordering> {
+        let value = self.get_value(value)?;
+        Ok(self.bottom.partial_cmp(&value).unwrap_or(Ordering::Equal))
+    }
+
+    fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
+        self.values[slot] = self.get_value(value)?;
+        Ok(())
+    }
+
+    fn get_information_from_reader<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<()> {
+        self.current_values = Some(LeafDistanceValues::new(
+            reader,
+            &self.field,
+            self.latitude,
+            self.longitude,
+        )?);
+        Ok(())
+    }
+
+    fn get_type(&self) -> SortFieldType {
+        SortFieldType::Double
+    }
+}
+
+impl fmt::Display for LatLonDistanceComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LatLonDistanceComparator(field: {}, latitude: {}, longitude: {}, bottom: {})",
+            &self.field, self.latitude, self.longitude, self.bottom
+        )
+    }
+}
+
 /// compare doc hit by numeric doc values field
 pub struct NumericDocValuesComparator<T: DocValuesSource> {
     missing_value: Option<VariantValue>,
@@ -14,10 +14,12 @@
 use core::codec::doc_values::{NumericDocValues, SortedNumericDocValues};
 use core::codec::Codec;
 use core::index::reader::SearchLeafReader;
+use core::search::expressions::ExpressionValueSource;
 use core::search::sort_field::{
-    DefaultDocValuesSource, DocComparator, DocValuesSource, FieldComparatorEnum,
-    NumericDocValuesComparator, RelevanceComparator,
+    DefaultDocValuesSource, DocComparator, DocValuesSource, ExpressionComparator,
+    FieldComparatorEnum, LatLonDistanceComparator, NumericDocValuesComparator, RelevanceComparator,
 };
+use core::util::geo::{check_latitude, check_longitude};
 use core::util::{sortable_double_bits, sortable_float_bits};
 use core::util::{BitsMut, DocId, VariantValue};
 
@@ -62,6 +64,8 @@ pub enum SortFieldMissingValue {
 pub enum SortField {
     Simple(SimpleSortField),
     SortedNumeric(SortedNumericSortField),
+    Expression(ExpressionSortField),
+    Distance(LatLonDistanceSortField),
     // SortedSet(SortedSetSortField),
 }
 
@@ -74,6 +78,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => &s.field,
             SortField::SortedNumeric(s) => &s.raw_field.field,
+            SortField::Expression(s) => s.source.expression().source(),
+            SortField::Distance(s) => &s.field,
         }
     }
 
@@ -81,6 +87,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.field_type,
             SortField::SortedNumeric(s) => s.raw_field.field_type,
+            SortField::Expression(_) => SortFieldType::Custom,
+            SortField::Distance(_) => SortFieldType::Custom,
         }
     }
 
@@ -88,6 +96,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.is_reverse,
             SortField::SortedNumeric(s) => s.raw_field.is_reverse,
+            SortField::Expression(s) => s.is_reverse,
+            SortField::Distance(_) => false,
         }
     }
 
@@ -95,6 +105,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.missing_value.as_ref(),
             SortField::SortedNumeric(s) => s.raw_field.missing_value.as_ref(),
+            SortField::Expression(_) => None,
+            SortField::Distance(_) => None,
         }
     }
 
@@ -102,6 +114,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.needs_scores(),
             SortField::SortedNumeric(s) => s.raw_field.needs_scores(),
+            SortField::Expression(_) => false,
+            SortField::Distance(_) => false,
         }
     }
 
@@ -113,6 +127,10 @@ impl SortField {
             SortField::SortedNumeric(s) => {
                 s.raw_field.missing_value = value;
             }
+            // documents without values are given 0 by the expression variables
+            SortField::Expression(_) => {}
+            // documents without location always sort last
+            SortField::Distance(_) => {}
         }
     }
 
@@ -124,6 +142,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.get_comparator(num_hits, missing_value),
             SortField::SortedNumeric(s) => s.get_comparator(num_hits, missing_value),
+            SortField::Expression(s) => s.get_comparator(num_hits),
+            SortField::Distance(s) => s.get_comparator(num_hits),
         }
     }
 }
@@ -270,6 +290,78 @@ impl SortedNumericSortField {
     }
 }
 
+/// SortField for the values computed by an `Expression` over the doc values of a document.
+///
+/// The expression can not use the score, the comparators only being given doc ids.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpressionSortField {
+    source: ExpressionValueSource,
+    is_reverse: bool,
+}
+
+impl ExpressionSortField {
+    pub fn new(source: ExpressionValueSource, is_reverse: bool) -> Result<Self> {
+        if source.needs_scores() {
+            bail!(IllegalArgument(format!(
+                "can't sort by expression '{}' which uses the score",
+                source.expression()
+            )));
+        }
+        Ok(ExpressionSortField { source, is_reverse })
+    }
+
+    pub fn source(&self) -> &ExpressionValueSource {
+        &self.source
+    }
+
+    pub fn get_comparator(&self, num_hits: usize) -> FieldComparatorEnum {
+        FieldComparatorEnum::Expression(ExpressionComparator::new(num_hits, self.source.clone()))
+    }
+}
+
+/// SortField for the haversine distance in meters from a location to the values of a
+/// `LatLonDocValuesField`.
+///
+/// The closest value is used for the documents with several locations, the documents
+/// without location sort last.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatLonDistanceSortField {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Eq for LatLonDistanceSortField {}
+
+impl LatLonDistanceSortField {
+    pub fn new(field: String, latitude: f64, longitude: f64) -> Result<Self> {
+        check_latitude(latitude)?;
+        check_longitude(longitude)?;
+        Ok(LatLonDistanceSortField {
+            field,
+            latitude,
+            longitude,
+        })
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    pub fn get_comparator(&self, num_hits: usize) -> FieldComparatorEnum {
+        FieldComparatorEnum::Distance(LatLonDistanceComparator::new(
+            num_hits,
+            self.field.clone(),
+            self.latitude,
+            self.longitude,
+        ))
+    }
+}
+
 pub struct SortedWrapperDocValuesSource {
     selector: SortedNumericSelectorType,
     field_type: SortFieldType,
@@ -318,6 +318,28 @@ impl FixedBitSet {
         self.bits[end_word] ^= end_mask;
     }
 
+    /// Returns the index of the last set bit before or on the index specified.
+    /// -1 is returned if there are no more set bits.
+    pub fn prev_set_bit(&self, index: usize) -> i32 {
+        debug_assert!(index < self.num_bits);
+        let mut i = index >> 6;
+        let sub_index = index & 0x3f; // index within the word
+        let word = (self.bits[i] << (63 - sub_index)) as u64; // skip all the bits to the left of index
+
+        if word != 0 {
+            return ((i << 6) + sub_index) as i32 - word.leading_zeros() as i32;
+        }
+
+        while i > 0 {
+            i -= 1;
+            let word = self.bits[i] as u64;
+            if word != 0 {
+                return ((i << 6) + 63) as i32 - word.leading_zeros() as i32;
+            }
+        }
+        -1
+    }
+
     /// returns true if the sets have any elements in common
     pub fn intersects(&self, other: &FixedBitSet) -> bool {
         // Depends on the ghost bits being clear!
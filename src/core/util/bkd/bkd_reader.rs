@@ -344,6 +344,17 @@ impl BKDReader {
         &self,
         visitor: &'a mut IV,
     ) -> Result<IntersectState<'a, IV>> {
+        Ok(IntersectState::new(
+            self.input.as_ref().clone()?,
+            self.num_dims,
+            self.packed_bytes_length,
+            self.max_points_in_leaf_node,
+            visitor,
+            self.create_index_tree()?,
+        ))
+    }
+
+    fn create_index_tree(&self) -> Result<Box<dyn IndexTree>> {
         let index_tree: Box<dyn IndexTree> = if !self.packed_index.is_empty() {
             Box::new(PackedIndexTree::new(
                 self.bytes_per_dim,
@@ -365,15 +376,108 @@ impl BKDReader {
                 self.leaf_node_offset,
             ))
         };
+        Ok(index_tree)
+    }
 
-        Ok(IntersectState::new(
-            self.input.as_ref().clone()?,
-            self.num_dims,
-            self.packed_bytes_length,
-            self.max_points_in_leaf_node,
+    /// Returns an estimate of the number of points that would be visited by `intersect`
+    /// with the given visitor. This only walks the index, it never reads leaf blocks.
+    pub fn estimate_point_count(&self, visitor: &impl IntersectVisitor) -> Result<i64> {
+        let mut index_tree = self.create_index_tree()?;
+        self.estimate_point_count_in_tree(
             visitor,
-            index_tree,
-        ))
+            index_tree.as_mut(),
+            &self.min_packed_value,
+            &self.max_packed_value,
+        )
+    }
+
+    fn estimate_point_count_in_tree(
+        &self,
+        visitor: &impl IntersectVisitor,
+        index_tree: &mut dyn IndexTree,
+        cell_min_packed: &[u8],
+        cell_max_packed: &[u8],
+    ) -> Result<i64> {
+        match visitor.compare(cell_min_packed, cell_max_packed) {
+            Relation::CellOutsideQuery => Ok(0),
+            Relation::CellInsideQuery => {
+                Ok(self.max_points_in_leaf_node as i64 * self.count_leaves(index_tree)?)
+            }
+            Relation::CellCrossesQuery if index_tree.is_leaf_node() => {
+                if index_tree.node_exists() {
+                    // assume half the points of a crossing leaf match
+                    Ok((self.max_points_in_leaf_node as i64 + 1) / 2)
+                } else {
+                    Ok(0)
+                }
+            }
+            Relation::CellCrossesQuery => {
+                let split_dim = index_tree.split_dim() as usize;
+                let split_packed_value_idx = index_tree.split_packed_value_index();
+                let mut split_dim_value = index_tree.split_dim_value();
+                let dim_range =
+                    split_dim * self.bytes_per_dim..(split_dim + 1) * self.bytes_per_dim;
+
+                index_tree.set_split_packed_value(
+                    split_packed_value_idx,
+                    0,
+                    &cell_max_packed[0..self.packed_bytes_length],
+                );
+                index_tree.set_split_packed_value(
+                    split_packed_value_idx,
+                    split_dim * self.bytes_per_dim,
+                    &split_dim_value,
+                );
+                let mut split_packed_value = index_tree.split_packed_value();
+
+                index_tree.push_left()?;
+                let left = self.estimate_point_count_in_tree(
+                    visitor,
+                    index_tree,
+                    cell_min_packed,
+                    &split_packed_value,
+                )?;
+                index_tree.pop();
+
+                // Restore the split dim value since it may have been overwritten while recursing:
+                split_dim_value[0..self.bytes_per_dim]
+                    .copy_from_slice(&split_packed_value[dim_range.clone()]);
+
+                split_packed_value[0..self.packed_bytes_length]
+                    .copy_from_slice(&cell_min_packed[0..self.packed_bytes_length]);
+                split_packed_value[dim_range]
+                    .copy_from_slice(&split_dim_value[0..self.bytes_per_dim]);
+                index_tree.set_split_packed_value(split_packed_value_idx, 0, &split_packed_value);
+                index_tree.set_split_dim_value(&split_dim_value);
+
+                index_tree.push_right()?;
+                let right = self.estimate_point_count_in_tree(
+                    visitor,
+                    index_tree,
+                    &split_packed_value,
+                    cell_max_packed,
+                )?;
+                index_tree.pop();
+
+                Ok(left + right)
+            }
+        }
+    }
+
+    fn count_leaves(&self, index_tree: &mut dyn IndexTree) -> Result<i64> {
+        if index_tree.is_leaf_node() {
+            Ok(if index_tree.node_exists() { 1 } else { 0 })
+        } else {
+            index_tree.push_left()?;
+            let left = self.count_leaves(index_tree)?;
+            index_tree.pop();
+
+            index_tree.push_right()?;
+            let right = self.count_leaves(index_tree)?;
+            index_tree.pop();
+
+            Ok(left + right)
+        }
     }
 
     /// Fast path: this is called when the query box fully encompasses all cells under this
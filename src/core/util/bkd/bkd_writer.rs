@@ -80,7 +80,9 @@ impl<'a, D: Directory, O: IndexOutput> OneDimIntersectVisitor<'a, D, O> {
 
 impl<'a, D: Directory, O: IndexOutput> IntersectVisitor for OneDimIntersectVisitor<'a, D, O> {
     fn visit(&mut self, _doc_id: DocId) -> Result<()> {
-        bail!(IllegalState("".into()))
+        bail!(IllegalState(
+            "the points of a mutable reader must be visited with their packed values".into()
+        ))
     }
 
     fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
@@ -92,6 +94,26 @@ impl<'a, D: Directory, O: IndexOutput> IntersectVisitor for OneDimIntersectVisit
     }
 }
 
+struct NDimIntersectVisitor<'a, D: Directory> {
+    writer: &'a mut BKDWriter<D>,
+}
+
+impl<'a, D: Directory> IntersectVisitor for NDimIntersectVisitor<'a, D> {
+    fn visit(&mut self, _doc_id: DocId) -> Result<()> {
+        bail!(IllegalState(
+            "the points of a mutable reader must be visited with their packed values".into()
+        ))
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        self.writer.add(packed_value, doc_id)
+    }
+
+    fn compare(&self, _min_packed_value: &[u8], _max_packed_value: &[u8]) -> Relation {
+        Relation::CellCrossesQuery
+    }
+}
+
 pub struct OneDimensionBKDWriter<'a, D: Directory, O: IndexOutput> {
     out: &'a mut O,
     leaf_block_fps: Vec<i64>,
@@ -514,7 +536,7 @@ impl<D: Directory> BKDWriter<D> {
         if self.num_dims == 1 {
             self.write_field_1_dim(out, field_name, reader)
         } else {
-            unimplemented!()
+            self.write_field_n_dims(out, field_name, reader)
         }
     }
 
@@ -779,6 +801,24 @@ impl<D: Directory> BKDWriter<D> {
         one_dim_writer.finish()
     }
 
+    // In the N-dim case, we add all the points and build the tree by recursively partitioning
+    // them on each dimension, like for points that are not coming from a mutable reader.
+    fn write_field_n_dims<O: IndexOutput>(
+        &mut self,
+        out: &mut O,
+        field_name: &str,
+        reader: impl MutablePointsReader,
+    ) -> Result<i64> {
+        {
+            let mut visitor = NDimIntersectVisitor { writer: self };
+            reader.intersect(field_name, &mut visitor)?;
+        }
+        if self.point_count == 0 {
+            return Ok(-1);
+        }
+        self.finish(out)
+    }
+
     fn rotate_to_tree(
         &self,
         node_id: i32,
@@ -1413,9 +1453,9 @@ impl<D: Directory> BKDWriter<D> {
                     let mut cardinality = 1;
                     let mut previous = self.scratch1[offset + prefix];
 
-                    for _i in 1..source.count {
+                    for i in 1..source.count {
                         heap_source
-                            .read_packed_value(source.start as usize + 1, &mut self.scratch2);
+                            .read_packed_value((source.start + i) as usize, &mut self.scratch2);
                         let b = self.scratch2[offset + prefix];
                         debug_assert!(previous <= b);
                         if b != previous {
@@ -1491,13 +1531,7 @@ impl<D: Directory> BKDWriter<D> {
         } else {
             // Inner node: partition/recurse
             let split_dim = if self.num_dims > 1 {
-                let min_packed_value = (&self.min_packed_value) as *const Vec<u8>;
-                let max_packed_value = (&self.max_packed_value) as *const Vec<u8>;
-                self.split(
-                    unsafe { &(*min_packed_value) },
-                    unsafe { &(*max_packed_value) },
-                    parent_splits,
-                )
+                self.split(min_packed_value, max_packed_value, parent_splits)
             } else {
                 0
             };
@@ -1514,6 +1548,8 @@ impl<D: Directory> BKDWriter<D> {
                 self.mark_right_tree(right_count, split_dim as i32, source, ord_bitset)?;
             let address = node_id as usize * (1 + self.bytes_per_dim);
             split_packed_values[address] = split_dim as u8;
+            split_packed_values[address + 1..address + 1 + self.bytes_per_dim]
+                .copy_from_slice(&split_value[0..self.bytes_per_dim]);
 
             // Partition all PathSlice that are not the split dim into sorted left and right sets,
             // so we can recurse:
@@ -1983,3 +2019,123 @@ impl<D: Directory> Sorter for BKDWriterMSBIntroSorter<D> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+
+    use core::store::directory::FSDirectory;
+    use core::store::IOContext;
+    use core::util::bkd::BKDReader;
+
+    fn pack(x: u32, y: u32) -> Vec<u8> {
+        let mut packed = x.to_be_bytes().to_vec();
+        packed.extend_from_slice(&y.to_be_bytes());
+        packed
+    }
+
+    struct BoxVisitor {
+        min: Vec<u8>,
+        max: Vec<u8>,
+        docs: Vec<DocId>,
+    }
+
+    impl BoxVisitor {
+        fn dim<'a>(packed: &'a [u8], dim: usize) -> &'a [u8] {
+            &packed[dim * 4..(dim + 1) * 4]
+        }
+    }
+
+    impl IntersectVisitor for BoxVisitor {
+        fn visit(&mut self, doc_id: DocId) -> Result<()> {
+            self.docs.push(doc_id);
+            Ok(())
+        }
+
+        fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+            if self.compare(packed_value, packed_value) == Relation::CellInsideQuery {
+                self.docs.push(doc_id);
+            }
+            Ok(())
+        }
+
+        fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+            let mut inside = true;
+            for dim in 0..2 {
+                let (cell_min, cell_max) = (
+                    Self::dim(min_packed_value, dim),
+                    Self::dim(max_packed_value, dim),
+                );
+                let (min, max) = (Self::dim(&self.min, dim), Self::dim(&self.max, dim));
+                if cell_max < min || cell_min > max {
+                    return Relation::CellOutsideQuery;
+                }
+                inside &= cell_min >= min && cell_max <= max;
+            }
+            if inside {
+                Relation::CellInsideQuery
+            } else {
+                Relation::CellCrossesQuery
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_dims_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = Arc::new(FSDirectory::with_path(temp_dir.path()).unwrap());
+
+        // a grid with many duplicate values on each dimension, split into small leaves
+        let points: Vec<(u32, u32)> = (0..200u32).map(|i| (i % 20, (i / 20) * 300)).collect();
+        let mut writer = BKDWriter::new(
+            points.len() as i32,
+            Arc::clone(&directory),
+            "bkd",
+            2,
+            4,
+            4,
+            16.0,
+            points.len() as i64,
+            true,
+        )
+        .unwrap();
+        for (doc, &(x, y)) in points.iter().enumerate() {
+            writer.add(&pack(x, y), doc as DocId).unwrap();
+        }
+        let index_fp = {
+            let mut out = directory.create_output("bkd", &IOContext::Default).unwrap();
+            writer.finish(&mut out).unwrap()
+        };
+
+        let mut input = directory.open_input("bkd", &IOContext::Default).unwrap();
+        input.seek(index_fp).unwrap();
+        let reader = BKDReader::new(Arc::from(input)).unwrap();
+        assert_eq!(reader.min_packed_value, pack(0, 0));
+        assert_eq!(reader.max_packed_value, pack(19, 2700));
+
+        let boxes = [
+            (0, 0, 19, 2700),
+            (2, 300, 5, 2100),
+            (7, 1000, 7, 2000),
+            (20, 0, 30, 9),
+        ];
+        for &(min_x, min_y, max_x, max_y) in &boxes {
+            let mut visitor = BoxVisitor {
+                min: pack(min_x, min_y),
+                max: pack(max_x, max_y),
+                docs: vec![],
+            };
+            reader.intersect(&mut visitor).unwrap();
+            visitor.docs.sort();
+            let expected: Vec<DocId> = points
+                .iter()
+                .enumerate()
+                .filter(|(_, &(x, y))| x >= min_x && x <= max_x && y >= min_y && y <= max_y)
+                .map(|(doc, _)| doc as DocId)
+                .collect();
+            assert_eq!(visitor.docs, expected);
+        }
+    }
+}
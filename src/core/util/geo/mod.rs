@@ -0,0 +1,177 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Basic reusable geo-spatial utility methods: validation of coordinates, encoding of
+//! latitude/longitude into 32 bit integers for points and doc values, and distances.
+
+mod rectangle;
+
+pub use self::rectangle::*;
+
+mod polygon;
+
+pub use self::polygon::*;
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+/// Minimum latitude value.
+pub const MIN_LAT_INCL: f64 = -90.0;
+/// Maximum latitude value.
+pub const MAX_LAT_INCL: f64 = 90.0;
+/// Minimum longitude value.
+pub const MIN_LON_INCL: f64 = -180.0;
+/// Maximum longitude value.
+pub const MAX_LON_INCL: f64 = 180.0;
+
+pub const MIN_LAT_RADIANS: f64 = -std::f64::consts::FRAC_PI_2;
+pub const MAX_LAT_RADIANS: f64 = std::f64::consts::FRAC_PI_2;
+pub const MIN_LON_RADIANS: f64 = -std::f64::consts::PI;
+pub const MAX_LON_RADIANS: f64 = std::f64::consts::PI;
+
+/// Mean earth radius in meters, as defined by the WGS84 ellipsoid.
+pub const EARTH_MEAN_RADIUS_METERS: f64 = 6_371_008.771_4;
+
+const LAT_SCALE: f64 = (1u64 << 32) as f64 / 180.0;
+const LAT_DECODE: f64 = 1.0 / LAT_SCALE;
+const LON_SCALE: f64 = (1u64 << 32) as f64 / 360.0;
+const LON_DECODE: f64 = 1.0 / LON_SCALE;
+
+/// Validates that latitude is within the standard +/-90 coordinate bounds.
+pub fn check_latitude(latitude: f64) -> Result<()> {
+    if latitude.is_nan() || latitude < MIN_LAT_INCL || latitude > MAX_LAT_INCL {
+        bail!(IllegalArgument(format!(
+            "invalid latitude {}; must be between {} and {}",
+            latitude, MIN_LAT_INCL, MAX_LAT_INCL
+        )));
+    }
+    Ok(())
+}
+
+/// Validates that longitude is within the standard +/-180 coordinate bounds.
+pub fn check_longitude(longitude: f64) -> Result<()> {
+    if longitude.is_nan() || longitude < MIN_LON_INCL || longitude > MAX_LON_INCL {
+        bail!(IllegalArgument(format!(
+            "invalid longitude {}; must be between {} and {}",
+            longitude, MIN_LON_INCL, MAX_LON_INCL
+        )));
+    }
+    Ok(())
+}
+
+// the largest double value smaller than the given positive one
+fn next_down(value: f64) -> f64 {
+    debug_assert!(value > 0.0);
+    f64::from_bits(value.to_bits() - 1)
+}
+
+/// Quantizes a latitude value to a 32 bit integer, rounding down.
+///
+/// The maximum latitude can not be represented exactly and is stored as the largest
+/// encoded value instead.
+pub fn encode_latitude(latitude: f64) -> Result<i32> {
+    check_latitude(latitude)?;
+    let latitude = if (latitude - MAX_LAT_INCL).abs() < std::f64::EPSILON {
+        next_down(latitude)
+    } else {
+        latitude
+    };
+    Ok((latitude / LAT_DECODE).floor() as i32)
+}
+
+/// Quantizes a latitude value to a 32 bit integer, rounding up.
+pub fn encode_latitude_ceil(latitude: f64) -> Result<i32> {
+    check_latitude(latitude)?;
+    // the cast saturates to i32::MAX for the maximum latitude
+    Ok((latitude / LAT_DECODE).ceil() as i32)
+}
+
+/// Turns a quantized latitude value back into a double.
+pub fn decode_latitude(encoded: i32) -> f64 {
+    encoded as f64 * LAT_DECODE
+}
+
+/// Quantizes a longitude value to a 32 bit integer, rounding down.
+///
+/// The maximum longitude can not be represented exactly and is stored as the largest
+/// encoded value instead.
+pub fn encode_longitude(longitude: f64) -> Result<i32> {
+    check_longitude(longitude)?;
+    let longitude = if (longitude - MAX_LON_INCL).abs() < std::f64::EPSILON {
+        next_down(longitude)
+    } else {
+        longitude
+    };
+    Ok((longitude / LON_DECODE).floor() as i32)
+}
+
+/// Quantizes a longitude value to a 32 bit integer, rounding up.
+pub fn encode_longitude_ceil(longitude: f64) -> Result<i32> {
+    check_longitude(longitude)?;
+    // the cast saturates to i32::MAX for the maximum longitude
+    Ok((longitude / LON_DECODE).ceil() as i32)
+}
+
+/// Turns a quantized longitude value back into a double.
+pub fn decode_longitude(encoded: i32) -> f64 {
+    encoded as f64 * LON_DECODE
+}
+
+/// Returns the great circle distance in meters between two points, using the
+/// haversine formula on a sphere of radius `EARTH_MEAN_RADIUS_METERS`.
+pub fn haversin_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let h1 = (d_lat / 2.0).sin();
+    let h2 = (d_lon / 2.0).sin();
+    let h = h1 * h1 + lat1.to_radians().cos() * lat2.to_radians().cos() * h2 * h2;
+    2.0 * EARTH_MEAN_RADIUS_METERS * h.sqrt().min(1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding() {
+        for &lat in &[-90.0, -45.5, 0.0, 12.345_678, 89.999_999, 90.0] {
+            let encoded = encode_latitude(lat).unwrap();
+            assert!(decode_latitude(encoded) <= lat);
+            assert!(lat - decode_latitude(encoded) < 1e-7);
+            let ceil = encode_latitude_ceil(lat).unwrap();
+            assert!(ceil == encoded || ceil == encoded + 1);
+        }
+        for &lon in &[-180.0, -0.5, 0.0, 120.123_456, 180.0] {
+            let encoded = encode_longitude(lon).unwrap();
+            assert!(decode_longitude(encoded) <= lon);
+            assert!(lon - decode_longitude(encoded) < 1e-7);
+        }
+        assert_eq!(encode_latitude(90.0).unwrap(), i32::max_value());
+        assert_eq!(encode_longitude(-180.0).unwrap(), i32::min_value());
+        assert!(encode_latitude(90.1).is_err());
+        assert!(encode_longitude(std::f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_haversin() {
+        assert!(haversin_meters(40.0, 30.0, 40.0, 30.0).abs() < 1e-9);
+        // one degree of latitude is about 111.2 km
+        let d = haversin_meters(0.0, 0.0, 1.0, 0.0);
+        assert!((d - 111_195.08).abs() < 1.0);
+        // half the circumference between antipodes
+        let d = haversin_meters(0.0, -90.0, 0.0, 90.0);
+        assert!((d - std::f64::consts::PI * EARTH_MEAN_RADIUS_METERS).abs() < 1e-3);
+        // across the dateline
+        let d = haversin_meters(0.0, 179.5, 0.0, -179.5);
+        assert!((d - 111_195.08).abs() < 1.0);
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::points::Relation;
+use core::util::geo::{check_latitude, check_longitude};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::fmt;
+
+/// Represents a closed polygon on the earth's surface, with optional holes.
+///
+/// The first and last vertices must be the same, so at least 4 vertices are needed to
+/// describe a triangle. Polygons must not cross the dateline, and holes may not have
+/// holes of their own.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    poly_lats: Vec<f64>,
+    poly_lons: Vec<f64>,
+    holes: Vec<Polygon>,
+
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+impl Polygon {
+    pub fn new(poly_lats: Vec<f64>, poly_lons: Vec<f64>, holes: Vec<Polygon>) -> Result<Polygon> {
+        if poly_lats.len() != poly_lons.len() {
+            bail!(IllegalArgument(
+                "poly_lats and poly_lons must be equal length".into()
+            ));
+        }
+        if poly_lats.len() < 4 {
+            bail!(IllegalArgument("at least 4 polygon points required".into()));
+        }
+        let last = poly_lats.len() - 1;
+        if poly_lats[0] != poly_lats[last] || poly_lons[0] != poly_lons[last] {
+            bail!(IllegalArgument(format!(
+                "first and last points of the polygon must be the same (it must close itself): \
+                 ({}, {}) != ({}, {})",
+                poly_lats[0], poly_lons[0], poly_lats[last], poly_lons[last]
+            )));
+        }
+        for (lat, lon) in poly_lats.iter().zip(poly_lons.iter()) {
+            check_latitude(*lat)?;
+            check_longitude(*lon)?;
+        }
+        if holes.iter().any(|hole| !hole.holes.is_empty()) {
+            bail!(IllegalArgument(
+                "holes may not have holes of their own".into()
+            ));
+        }
+
+        let min = |values: &[f64]| values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = |values: &[f64]| values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        Ok(Polygon {
+            min_lat: min(&poly_lats),
+            max_lat: max(&poly_lats),
+            min_lon: min(&poly_lons),
+            max_lon: max(&poly_lons),
+            poly_lats,
+            poly_lons,
+            holes,
+        })
+    }
+
+    pub fn poly_lats(&self) -> &[f64] {
+        &self.poly_lats
+    }
+
+    pub fn poly_lons(&self) -> &[f64] {
+        &self.poly_lons
+    }
+
+    pub fn holes(&self) -> &[Polygon] {
+        &self.holes
+    }
+
+    /// Returns true if the point is inside the polygon and not inside any of its holes.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        self.contains_ring(lat, lon) && !self.holes.iter().any(|h| h.contains_ring(lat, lon))
+    }
+
+    /// Computes the relation of the given lat/lon cell with the polygon, from the point of
+    /// view of the cell: it is inside if all its points are in the polygon.
+    pub fn relate(&self, min_lat: f64, max_lat: f64, min_lon: f64, max_lon: f64) -> Relation {
+        if max_lat < self.min_lat
+            || min_lat > self.max_lat
+            || max_lon < self.min_lon
+            || min_lon > self.max_lon
+        {
+            return Relation::CellOutsideQuery;
+        }
+        if min_lat <= self.min_lat
+            && max_lat >= self.max_lat
+            && min_lon <= self.min_lon
+            && max_lon >= self.max_lon
+        {
+            // the polygon is within the cell
+            return Relation::CellCrossesQuery;
+        }
+        for hole in &self.holes {
+            match hole.relate(min_lat, max_lat, min_lon, max_lon) {
+                Relation::CellCrossesQuery => return Relation::CellCrossesQuery,
+                Relation::CellInsideQuery => return Relation::CellOutsideQuery,
+                Relation::CellOutsideQuery => {}
+            }
+        }
+
+        let corners = [
+            (min_lat, min_lon),
+            (min_lat, max_lon),
+            (max_lat, min_lon),
+            (max_lat, max_lon),
+        ];
+        let num_corners = corners
+            .iter()
+            .filter(|(lat, lon)| self.contains_ring(*lat, *lon))
+            .count();
+        let crosses = self.crosses(min_lat, max_lat, min_lon, max_lon);
+        if num_corners == 4 && !crosses {
+            Relation::CellInsideQuery
+        } else if num_corners > 0 || crosses {
+            Relation::CellCrossesQuery
+        } else {
+            Relation::CellOutsideQuery
+        }
+    }
+
+    // point in polygon test by ray casting, ignoring the holes
+    fn contains_ring(&self, lat: f64, lon: f64) -> bool {
+        if lat < self.min_lat || lat > self.max_lat || lon < self.min_lon || lon > self.max_lon {
+            return false;
+        }
+        let mut inside = false;
+        for i in 1..self.poly_lats.len() {
+            let (lat1, lon1) = (self.poly_lats[i - 1], self.poly_lons[i - 1]);
+            let (lat2, lon2) = (self.poly_lats[i], self.poly_lons[i]);
+            if lat == lat1 && lon == lon1 {
+                // a vertex of the polygon
+                return true;
+            }
+            if (lat1 > lat) != (lat2 > lat)
+                && lon < (lon2 - lon1) * (lat - lat1) / (lat2 - lat1) + lon1
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    // whether any edge of the polygon intersects the border of the cell
+    fn crosses(&self, min_lat: f64, max_lat: f64, min_lon: f64, max_lon: f64) -> bool {
+        let sides = [
+            ((min_lat, min_lon), (min_lat, max_lon)),
+            ((min_lat, max_lon), (max_lat, max_lon)),
+            ((max_lat, max_lon), (max_lat, min_lon)),
+            ((max_lat, min_lon), (min_lat, min_lon)),
+        ];
+        (1..self.poly_lats.len()).any(|i| {
+            let a = (self.poly_lats[i - 1], self.poly_lons[i - 1]);
+            let b = (self.poly_lats[i], self.poly_lons[i]);
+            // skip the edges whose bounding box does not touch the cell
+            if a.0.max(b.0) < min_lat
+                || a.0.min(b.0) > max_lat
+                || a.1.max(b.1) < min_lon
+                || a.1.min(b.1) > max_lon
+            {
+                return false;
+            }
+            sides.iter().any(|&(c, d)| segments_intersect(a, b, c, d))
+        })
+    }
+}
+
+// the sign of the cross product (b - a) x (c - a)
+fn orient(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> i32 {
+    let v = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    if v > 0.0 {
+        1
+    } else if v < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+fn on_segment(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+// whether the segments [a, b] and [c, d] have at least one point in common
+fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let o1 = orient(a, b, c);
+    let o2 = orient(a, b, d);
+    let o3 = orient(c, d, a);
+    let o4 = orient(c, d, b);
+    if o1 != o2 && o3 != o4 {
+        return true;
+    }
+    (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b))
+}
+
+impl fmt::Display for Polygon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for i in 0..self.poly_lats.len() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "[{}, {}]", self.poly_lats[i], self.poly_lons[i])?;
+        }
+        write!(f, "]")?;
+        if !self.holes.is_empty() {
+            write!(f, ", holes=[")?;
+            for (i, hole) in self.holes.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", hole)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(min: f64, max: f64) -> Polygon {
+        Polygon::new(
+            vec![min, min, max, max, min],
+            vec![min, max, max, min, min],
+            vec![],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_contains_and_relate() {
+        let polygon = Polygon::new(
+            vec![-10.0, -10.0, 10.0, 10.0, -10.0],
+            vec![-10.0, 10.0, 10.0, -10.0, -10.0],
+            vec![square(-2.0, 2.0)],
+        )
+        .unwrap();
+        assert!(polygon.contains(5.0, 5.0));
+        assert!(!polygon.contains(0.0, 0.0));
+        assert!(!polygon.contains(11.0, 0.0));
+
+        assert!(polygon.relate(3.0, 4.0, 3.0, 4.0) == Relation::CellInsideQuery);
+        assert!(polygon.relate(-1.0, 1.0, -1.0, 1.0) == Relation::CellOutsideQuery);
+        assert!(polygon.relate(20.0, 30.0, 20.0, 30.0) == Relation::CellOutsideQuery);
+        assert!(polygon.relate(1.0, 3.0, 1.0, 3.0) == Relation::CellCrossesQuery);
+        assert!(polygon.relate(5.0, 15.0, 5.0, 6.0) == Relation::CellCrossesQuery);
+        assert!(polygon.relate(-20.0, 20.0, -20.0, 20.0) == Relation::CellCrossesQuery);
+
+        assert!(Polygon::new(vec![0.0, 1.0, 0.0], vec![0.0, 1.0, 0.0], vec![]).is_err());
+        assert!(Polygon::new(vec![0.0, 1.0, 1.0, 0.5], vec![0.0, 1.0, 0.0, 0.0], vec![]).is_err());
+        assert!(Polygon::new(
+            vec![-10.0, -10.0, 10.0, 10.0, -10.0],
+            vec![-10.0, 10.0, 10.0, -10.0, -10.0],
+            vec![polygon.clone()]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::util::geo::{
+    check_latitude, check_longitude, EARTH_MEAN_RADIUS_METERS, MAX_LAT_RADIANS, MAX_LON_RADIANS,
+    MIN_LAT_RADIANS, MIN_LON_RADIANS,
+};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Represents a lat/lon rectangle.
+///
+/// A rectangle whose `max_lon` is less than its `min_lon` crosses the dateline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rectangle {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+impl Rectangle {
+    pub fn new(min_lat: f64, max_lat: f64, min_lon: f64, max_lon: f64) -> Result<Rectangle> {
+        check_latitude(min_lat)?;
+        check_latitude(max_lat)?;
+        check_longitude(min_lon)?;
+        check_longitude(max_lon)?;
+        if min_lat > max_lat {
+            bail!(IllegalArgument(format!(
+                "max_lat {} must not be less than min_lat {}",
+                max_lat, min_lat
+            )));
+        }
+        Ok(Rectangle {
+            min_lat,
+            max_lat,
+            min_lon,
+            max_lon,
+        })
+    }
+
+    /// Computes the bounding box of all the points within `radius_meters` of the center.
+    ///
+    /// The box spans the whole longitude range when a pole is within the distance, and
+    /// crosses the dateline when the circle does.
+    pub fn from_point_distance(
+        center_lat: f64,
+        center_lon: f64,
+        radius_meters: f64,
+    ) -> Result<Rectangle> {
+        check_latitude(center_lat)?;
+        check_longitude(center_lon)?;
+        let rad_lat = center_lat.to_radians();
+        let rad_lon = center_lon.to_radians();
+        // a small extra distance makes up for the rounding errors of the distance computation
+        let rad_distance = (radius_meters + 7e-2) / EARTH_MEAN_RADIUS_METERS;
+        let mut min_lat = rad_lat - rad_distance;
+        let mut max_lat = rad_lat + rad_distance;
+        let mut min_lon;
+        let mut max_lon;
+
+        if min_lat > MIN_LAT_RADIANS && max_lat < MAX_LAT_RADIANS {
+            let delta_lon = (rad_distance.sin() / rad_lat.cos()).asin();
+            min_lon = rad_lon - delta_lon;
+            if min_lon < MIN_LON_RADIANS {
+                min_lon += 2.0 * PI;
+            }
+            max_lon = rad_lon + delta_lon;
+            if max_lon > MAX_LON_RADIANS {
+                max_lon -= 2.0 * PI;
+            }
+        } else {
+            // a pole is within the distance
+            min_lat = min_lat.max(MIN_LAT_RADIANS);
+            max_lat = max_lat.min(MAX_LAT_RADIANS);
+            min_lon = MIN_LON_RADIANS;
+            max_lon = MAX_LON_RADIANS;
+        }
+
+        Ok(Rectangle {
+            min_lat: min_lat.to_degrees(),
+            max_lat: max_lat.to_degrees(),
+            min_lon: min_lon.to_degrees(),
+            max_lon: max_lon.to_degrees(),
+        })
+    }
+
+    /// Returns true if this rectangle crosses the dateline.
+    pub fn crosses_dateline(&self) -> bool {
+        self.max_lon < self.min_lon
+    }
+
+    /// Returns true if the given point is within this rectangle, edges included.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        if lat < self.min_lat || lat > self.max_lat {
+            return false;
+        }
+        if self.crosses_dateline() {
+            lon >= self.min_lon || lon <= self.max_lon
+        } else {
+            lon >= self.min_lon && lon <= self.max_lon
+        }
+    }
+}
+
+impl fmt::Display for Rectangle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Rectangle(lat={} TO {} lon={} TO {}{})",
+            self.min_lat,
+            self.max_lat,
+            self.min_lon,
+            self.max_lon,
+            if self.crosses_dateline() {
+                " [crosses dateline!]"
+            } else {
+                ""
+            }
+        )
+    }
+}
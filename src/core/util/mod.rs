@@ -16,6 +16,7 @@ pub type DocId = i32;
 pub mod bkd;
 pub mod external;
 pub mod fst;
+pub mod geo;
 pub mod packed;
 
 mod numeric;
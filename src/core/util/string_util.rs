@@ -32,8 +32,7 @@ pub fn bytes_subtract(bytes_per_dim: usize, dim: usize, a: &[u8], b: &[u8], resu
     let start = dim * bytes_per_dim;
     let end = start + bytes_per_dim;
     let mut borrow = 0;
-    let mut i = end - 1;
-    while i >= start {
+    for i in (start..end).rev() {
         let mut diff: i32 = (a[i] as u32 as i32) - (b[i] as u32 as i32) - borrow;
         if diff < 0 {
             diff += 256;
@@ -43,7 +42,6 @@ pub fn bytes_subtract(bytes_per_dim: usize, dim: usize, a: &[u8], b: &[u8], resu
         }
 
         result[i - start] = diff as u8;
-        i -= 1;
     }
 
     if borrow != 0 {
@@ -91,4 +89,23 @@ mod tests {
         let strv = id2str(&v[..]);
         assert_eq!("4161047F", strv);
     }
+
+    #[test]
+    fn test_bytes_subtract() {
+        let a = [9u8, 1, 0, 3, 0, 0];
+        let b = [2u8, 0, 255, 1, 0, 1];
+        let mut result = [0u8; 3];
+        // the borrow ripples across the low bytes of the second dimension
+        bytes_subtract(3, 1, &a, &b, &mut result);
+        assert_eq!(result, [1, 255, 255]);
+        bytes_subtract(3, 0, &a, &b, &mut result);
+        assert_eq!(result, [7, 0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a<b")]
+    fn test_bytes_subtract_underflow() {
+        let mut result = [0u8; 2];
+        bytes_subtract(2, 0, &[1, 0], &[1, 1], &mut result);
+    }
 }